@@ -7,8 +7,8 @@
 
 use async_trait::async_trait;
 use happy_core::{
-    Adapter, BuildResult, Feature, HappyError, InstallTarget, Platform, ProjectConfig, Result,
-    SkillDefinition, ValidationResult, WorkflowDefinition,
+    Adapter, BuildContext, BuildResult, Feature, HappyError, InstallTarget, Platform,
+    ProjectConfig, Result, SkillDefinition, ValidationResult, WorkflowDefinition,
 };
 use std::path::{Path, PathBuf};
 
@@ -158,7 +158,8 @@ impl Adapter for CodexAdapter {
         ]
     }
 
-    async fn build(&self, config: &ProjectConfig, output_dir: &Path) -> Result<BuildResult> {
+    async fn build(&self, ctx: &BuildContext, config: &ProjectConfig) -> Result<BuildResult> {
+        let output_dir = &ctx.output_dir(config);
         let mut files = Vec::new();
 
         // Ensure output directory exists
@@ -3,12 +3,18 @@
 mod antigravity;
 mod claude;
 mod codex;
+mod ollama;
+mod openai;
 
 pub use antigravity::AntigravityAdapter;
 pub use claude::ClaudeAdapter;
 pub use codex::CodexAdapter;
+pub use ollama::{
+    ChatMessage, OllamaAdapter, OllamaError, Role, DEFAULT_BASE_URL as OLLAMA_DEFAULT_URL,
+};
+pub use openai::{GenericOpenAIAdapter, OpenAIError};
 
-use happy_core::{Adapter, AdapterFactory, Platform};
+use happy_core::{AIProfile, AIProvider, Adapter, AdapterFactory, Platform};
 
 /// Create an adapter factory with all platform adapters registered
 pub fn create_adapter_factory() -> AdapterFactory {
@@ -16,14 +22,28 @@ pub fn create_adapter_factory() -> AdapterFactory {
     factory.register(Box::new(ClaudeAdapter::new()));
     factory.register(Box::new(CodexAdapter::new()));
     factory.register(Box::new(AntigravityAdapter::new()));
+    factory.register(Box::new(OllamaAdapter::new()));
+    factory.register(Box::new(GenericOpenAIAdapter::new()));
     factory
 }
 
+/// Get an adapter that talks to a profile's own server: Ollama, or any
+/// OpenAI-compatible server for `Custom` providers
+pub fn get_profile_adapter(profile: &AIProfile) -> Option<Box<dyn Adapter>> {
+    match profile.provider {
+        AIProvider::Ollama => Some(Box::new(OllamaAdapter::from_profile(profile))),
+        AIProvider::Custom(_) => Some(Box::new(GenericOpenAIAdapter::from_profile(profile))),
+        _ => None,
+    }
+}
+
 /// Get an adapter for a specific platform
 pub fn get_adapter(platform: Platform) -> Box<dyn Adapter> {
     match platform {
         Platform::Claude => Box::new(ClaudeAdapter::new()),
         Platform::Codex => Box::new(CodexAdapter::new()),
         Platform::Antigravity => Box::new(AntigravityAdapter::new()),
+        Platform::Ollama => Box::new(OllamaAdapter::new()),
+        Platform::OpenAI => Box::new(GenericOpenAIAdapter::new()),
     }
 }
@@ -0,0 +1,454 @@
+//! Ollama adapter
+//!
+//! Generates configuration for local models served by Ollama:
+//! - `.ollama/Modelfile` - The active profile's model with the project's skills
+//!   as its system prompt, for `ollama create`
+//!
+//! Also talks to the Ollama server at the profile's `base_url`: listing models
+//! (`/api/tags`) and streaming chats (`/api/chat`, NDJSON).
+
+use async_trait::async_trait;
+use happy_core::{
+    AIProfile, AIProvider, Adapter, BuildContext, BuildResult, Feature, HappyError,
+    InstallTarget, Platform, ProjectConfig, Result, ValidationResult,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Where Ollama listens unless the profile sets `base_url`
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+
+/// Model for the Modelfile when the active profile doesn't name one
+const DEFAULT_MODEL: &str = "llama3.2";
+
+/// Errors talking to the Ollama server
+#[derive(Debug, thiserror::Error)]
+pub enum OllamaError {
+    #[error("Ollama is not running at {0}. Start it with `ollama serve`")]
+    Offline(String),
+
+    #[error("Ollama returned {status}: {message}")]
+    Status { status: u16, message: String },
+
+    #[error("Ollama request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Unexpected response from Ollama: {0}")]
+    InvalidResponse(String),
+}
+
+impl From<OllamaError> for HappyError {
+    fn from(e: OllamaError) -> Self {
+        HappyError::Other(e.to_string())
+    }
+}
+
+/// Who a chat message is from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+/// One message of an `/api/chat` conversation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    stream: bool,
+}
+
+/// One line of a streamed `/api/chat` response
+#[derive(Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    message: Option<ChatMessage>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Ollama platform adapter
+pub struct OllamaAdapter {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl OllamaAdapter {
+    pub fn new() -> Self {
+        Self::with_base_url(DEFAULT_BASE_URL)
+    }
+
+    /// Adapter for the Ollama server at `base_url`
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Adapter for the server a profile points at, the local default if unset
+    pub fn from_profile(profile: &AIProfile) -> Self {
+        Self::with_base_url(profile.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL))
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Models pulled into the server, e.g. `llama3.2:latest`
+    pub async fn list_models(&self) -> std::result::Result<Vec<String>, OllamaError> {
+        let response = self
+            .client
+            .get(format!("{}/api/tags", self.base_url))
+            .send()
+            .await
+            .map_err(|e| self.send_error(e))?;
+        let body: serde_json::Value = check_status(response).await?.json().await?;
+        Ok(model_names(&body))
+    }
+
+    /// Send a conversation to `model`, calling `on_chunk` with each piece of the
+    /// reply as it streams in, and return the whole reply
+    pub async fn chat(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        mut on_chunk: impl FnMut(&str),
+    ) -> std::result::Result<ChatMessage, OllamaError> {
+        let request = ChatRequest {
+            model,
+            messages,
+            stream: true,
+        };
+        let mut response = self
+            .client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| self.send_error(e))?;
+        response = check_status(response).await?;
+
+        let mut reply = ChatMessage::new(Role::Assistant, "");
+        let mut pending = Vec::new();
+        let mut done = false;
+        while !done {
+            let Some(bytes) = response.chunk().await? else {
+                break;
+            };
+            pending.extend_from_slice(&bytes);
+            // Lines can be split across chunks; keep the unfinished tail
+            while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                done |= apply_chunk(&line, &mut reply, &mut on_chunk)?;
+            }
+        }
+        if !done && !pending.is_empty() {
+            done = apply_chunk(&pending, &mut reply, &mut on_chunk)?;
+        }
+        if !done {
+            return Err(OllamaError::InvalidResponse(
+                "stream ended before the reply was done".to_string(),
+            ));
+        }
+        Ok(reply)
+    }
+
+    fn send_error(&self, e: reqwest::Error) -> OllamaError {
+        if e.is_connect() {
+            OllamaError::Offline(self.base_url.clone())
+        } else {
+            OllamaError::Request(e)
+        }
+    }
+
+    /// Model the Modelfile builds on: the active Ollama profile's, if any
+    fn model_for(ctx: &BuildContext) -> String {
+        ctx.active_profile()
+            .filter(|p| matches!(p.provider, AIProvider::Ollama))
+            .and_then(|p| p.model.clone())
+            .unwrap_or_else(|| DEFAULT_MODEL.to_string())
+    }
+
+    /// Generate the Modelfile, with the skills as the system prompt
+    fn generate_modelfile(&self, model: &str, config: &ProjectConfig) -> String {
+        let mut system = format!("# {}\n", config.name);
+        if let Some(ref desc) = config.description {
+            system.push_str(&format!("\n{}\n", desc));
+        }
+        if !config.skills.is_empty() {
+            system.push_str("\n## Skills\n");
+            for skill in &config.skills {
+                system.push_str(&format!("\n### {}\n\n{}\n", skill.name, skill.description));
+                if let Some(ref prompt) = skill.prompt {
+                    system.push_str(&format!("\n{}\n", prompt.trim_end()));
+                }
+            }
+        }
+
+        // A triple quote would end the SYSTEM block early
+        let system = system.replace("\"\"\"", "\"\" \"");
+        format!("FROM {}\n\nSYSTEM \"\"\"\n{}\"\"\"\n", model, system)
+    }
+}
+
+impl Default for OllamaAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Adapter for OllamaAdapter {
+    fn platform(&self) -> Platform {
+        Platform::Ollama
+    }
+
+    fn supported_features(&self) -> &[Feature] {
+        &[Feature::Skill]
+    }
+
+    fn limitations(&self) -> &[&str] {
+        &[
+            "Skills are folded into a single system prompt",
+            "Workflows, commands and MCP servers are not supported",
+        ]
+    }
+
+    async fn build(&self, ctx: &BuildContext, config: &ProjectConfig) -> Result<BuildResult> {
+        let output_dir = &ctx.output_dir(config);
+        tokio::fs::create_dir_all(output_dir).await?;
+
+        let modelfile = self.generate_modelfile(&Self::model_for(ctx), config);
+        tokio::fs::write(output_dir.join("Modelfile"), modelfile).await?;
+
+        Ok(BuildResult::success(
+            Platform::Ollama,
+            output_dir.display().to_string(),
+            vec!["Modelfile".to_string()],
+        ))
+    }
+
+    async fn install(&self, source: &Path, target: &InstallTarget) -> Result<()> {
+        if target.global {
+            return Err(HappyError::Other(
+                "Ollama has no global config; run `ollama create <name> -f .ollama/Modelfile`"
+                    .to_string(),
+            ));
+        }
+        let dest = target
+            .project_path
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(".ollama"));
+
+        let source_modelfile = source.join("Modelfile");
+        if source_modelfile.exists() {
+            tokio::fs::create_dir_all(&dest).await?;
+            tokio::fs::copy(&source_modelfile, dest.join("Modelfile")).await?;
+        }
+        Ok(())
+    }
+
+    fn validate(&self, config: &ProjectConfig) -> ValidationResult {
+        let mut result = ValidationResult::ok();
+
+        if !config.workflows.is_empty() || !config.commands.is_empty() {
+            result = result.with_warning(happy_core::ValidationWarning {
+                field: "workflows".to_string(),
+                message: "Ollama doesn't support workflows or commands".to_string(),
+                suggestion: Some("They will be ignored for the Ollama platform".to_string()),
+            });
+        }
+
+        result
+    }
+
+    async fn detect(&self) -> bool {
+        // Check if the ollama CLI is available
+        tokio::process::Command::new("ollama")
+            .arg("--version")
+            .output()
+            .await
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    fn global_install_path(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Fail with the server's own message for a non-2xx response
+async fn check_status(
+    response: reqwest::Response,
+) -> std::result::Result<reqwest::Response, OllamaError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    Err(OllamaError::Status {
+        status: status.as_u16(),
+        message: body["error"].as_str().unwrap_or("no details").to_string(),
+    })
+}
+
+/// Fold one NDJSON line into `reply`, returning whether it was the last one
+fn apply_chunk(
+    line: &[u8],
+    reply: &mut ChatMessage,
+    on_chunk: &mut impl FnMut(&str),
+) -> std::result::Result<bool, OllamaError> {
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return Ok(false);
+    }
+    let chunk: ChatChunk = serde_json::from_slice(line)
+        .map_err(|e| OllamaError::InvalidResponse(e.to_string()))?;
+    if let Some(error) = chunk.error {
+        return Err(OllamaError::InvalidResponse(error));
+    }
+    if let Some(message) = chunk.message {
+        on_chunk(&message.content);
+        reply.content.push_str(&message.content);
+    }
+    Ok(chunk.done)
+}
+
+/// Names from an `/api/tags` response
+fn model_names(body: &serde_json::Value) -> Vec<String> {
+    body["models"]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| m["name"].as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_list_models() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/tags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "models": [
+                    { "name": "llama3.2:latest", "size": 2019393189 },
+                    { "name": "qwen2.5-coder:7b", "details": { "family": "qwen2" } },
+                    { "size": 1 },
+                ]
+            })))
+            .mount(&server)
+            .await;
+
+        let adapter = OllamaAdapter::with_base_url(&format!("{}/", server.uri()));
+        let models = adapter.list_models().await.unwrap();
+        assert_eq!(models, vec!["llama3.2:latest", "qwen2.5-coder:7b"]);
+    }
+
+    #[tokio::test]
+    async fn test_offline() {
+        // Nothing listens on a port that was just released
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let adapter = OllamaAdapter::with_base_url(&format!("http://127.0.0.1:{}", port));
+        let err = adapter.list_models().await.unwrap_err();
+        assert!(matches!(err, OllamaError::Offline(_)), "{:?}", err);
+        assert!(HappyError::from(err).to_string().contains("ollama serve"));
+    }
+
+    #[tokio::test]
+    async fn test_chat_streams_ndjson() {
+        let server = MockServer::start().await;
+        let stream = [
+            r#"{"model":"llama3.2","message":{"role":"assistant","content":"Hel"},"done":false}"#,
+            r#"{"model":"llama3.2","message":{"role":"assistant","content":"lo"},"done":false}"#,
+            r#"{"model":"llama3.2","message":{"role":"assistant","content":""},"done":true}"#,
+        ]
+        .join("\n");
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(serde_json::json!({
+                "model": "llama3.2",
+                "stream": true,
+                "messages": [{ "role": "user", "content": "Hi" }],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(stream))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(serde_json::json!({ "model": "missing" })))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .set_body_json(serde_json::json!({ "error": "model 'missing' not found" })),
+            )
+            .mount(&server)
+            .await;
+
+        let adapter = OllamaAdapter::with_base_url(&server.uri());
+        let messages = [ChatMessage::new(Role::User, "Hi")];
+        let mut chunks = Vec::new();
+        let reply = adapter
+            .chat("llama3.2", &messages, |c| chunks.push(c.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(reply, ChatMessage::new(Role::Assistant, "Hello"));
+        assert_eq!(chunks, ["Hel", "lo", ""]);
+
+        let err = adapter.chat("missing", &messages, |_| {}).await.unwrap_err();
+        match err {
+            OllamaError::Status { status, message } => {
+                assert_eq!(status, 404);
+                assert!(message.contains("not found"), "{}", message);
+            }
+            err => panic!("expected a status error, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let message = ChatMessage::new(Role::System, "Be brief");
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json, serde_json::json!({ "role": "system", "content": "Be brief" }));
+        assert_eq!(serde_json::from_value::<ChatMessage>(json).unwrap(), message);
+
+        // Extra fields Ollama sends, like `images`, are ignored
+        let message: ChatMessage = serde_json::from_str(
+            r#"{"role":"assistant","content":"Hi","images":null}"#,
+        )
+        .unwrap();
+        assert_eq!(message.role, Role::Assistant);
+    }
+}
@@ -0,0 +1,471 @@
+//! Generic OpenAI-compatible adapter
+//!
+//! For self-hosted servers that speak the OpenAI API (vLLM, LocalAI,
+//! text-generation-webui), used by profiles with a `Custom` provider.
+//! Generates configuration for them:
+//! - `.openai/config.json` - The active profile's server and model
+//! - `.openai/system_prompt.md` - The project's skills as one system prompt
+//!
+//! Also talks to the server at the profile's `base_url`: listing models
+//! (`/models`) and streaming chat completions (`/chat/completions`, SSE).
+
+use crate::ollama::{ChatMessage, Role};
+use async_trait::async_trait;
+use happy_core::{
+    AIProfile, AIProvider, Adapter, BuildContext, BuildResult, Feature, HappyError,
+    InstallTarget, Platform, ProjectConfig, Result, ValidationResult,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The OpenAI API, for when a profile doesn't set `base_url`
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Errors talking to an OpenAI-compatible server
+#[derive(Debug, thiserror::Error)]
+pub enum OpenAIError {
+    #[error("No OpenAI-compatible server is listening at {0}")]
+    Offline(String),
+
+    #[error("Server returned {status}: {message}")]
+    Status { status: u16, message: String },
+
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Unexpected response from server: {0}")]
+    InvalidResponse(String),
+}
+
+impl From<OpenAIError> for HappyError {
+    fn from(e: OpenAIError) -> Self {
+        HappyError::Other(e.to_string())
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    stream: bool,
+}
+
+/// One `data:` event of a streamed chat completion
+#[derive(Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: Delta,
+}
+
+#[derive(Default, Deserialize)]
+struct Delta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// What `.openai/config.json` holds; the API key stays in the profile
+#[derive(Serialize)]
+struct ServerConfig<'a> {
+    base_url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+}
+
+/// Adapter for any server that speaks the OpenAI API
+pub struct GenericOpenAIAdapter {
+    base_url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl GenericOpenAIAdapter {
+    pub fn new() -> Self {
+        Self::with_base_url(DEFAULT_BASE_URL)
+    }
+
+    /// Adapter for the server at `base_url`, e.g. `http://localhost:8000/v1`
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Send `api_key` as a bearer token; self-hosted servers often need none
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Adapter for the server and key a profile points at
+    pub fn from_profile(profile: &AIProfile) -> Self {
+        let adapter =
+            Self::with_base_url(profile.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL));
+        match &profile.api_key {
+            Some(key) => adapter.with_api_key(key.clone()),
+            None => adapter,
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Models the server offers
+    pub async fn list_models(&self) -> std::result::Result<Vec<String>, OpenAIError> {
+        let response = self
+            .request(self.client.get(format!("{}/models", self.base_url)))
+            .send()
+            .await
+            .map_err(|e| self.send_error(e))?;
+        let body: serde_json::Value = check_status(response).await?.json().await?;
+        Ok(model_ids(&body))
+    }
+
+    /// Send a conversation to `model`, calling `on_chunk` with each piece of the
+    /// reply as it streams in, and return the whole reply
+    pub async fn chat(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        mut on_chunk: impl FnMut(&str),
+    ) -> std::result::Result<ChatMessage, OpenAIError> {
+        let request = ChatRequest {
+            model,
+            messages,
+            stream: true,
+        };
+        let mut response = self
+            .request(self.client.post(format!("{}/chat/completions", self.base_url)))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| self.send_error(e))?;
+        response = check_status(response).await?;
+
+        let mut reply = ChatMessage::new(Role::Assistant, "");
+        let mut pending = Vec::new();
+        let mut done = false;
+        while !done {
+            let Some(bytes) = response.chunk().await? else {
+                break;
+            };
+            pending.extend_from_slice(&bytes);
+            // Events can be split across chunks; keep the unfinished tail
+            while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                done |= apply_event(&line, &mut reply, &mut on_chunk)?;
+            }
+        }
+        if !done && !pending.is_empty() {
+            done = apply_event(&pending, &mut reply, &mut on_chunk)?;
+        }
+        if !done {
+            return Err(OpenAIError::InvalidResponse(
+                "stream ended before [DONE]".to_string(),
+            ));
+        }
+        Ok(reply)
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+
+    fn send_error(&self, e: reqwest::Error) -> OpenAIError {
+        if e.is_connect() {
+            OpenAIError::Offline(self.base_url.clone())
+        } else {
+            OpenAIError::Request(e)
+        }
+    }
+
+    /// The active profile, if it is for a self-hosted server
+    fn custom_profile(ctx: &BuildContext) -> Option<&AIProfile> {
+        ctx.active_profile()
+            .filter(|p| matches!(p.provider, AIProvider::Custom(_)))
+    }
+
+    /// The skills folded into a single system prompt
+    fn generate_system_prompt(&self, config: &ProjectConfig) -> String {
+        let mut prompt = format!("# {}\n", config.name);
+        if let Some(ref desc) = config.description {
+            prompt.push_str(&format!("\n{}\n", desc));
+        }
+        if !config.skills.is_empty() {
+            prompt.push_str("\n## Skills\n");
+            for skill in &config.skills {
+                prompt.push_str(&format!("\n### {}\n\n{}\n", skill.name, skill.description));
+                if let Some(ref skill_prompt) = skill.prompt {
+                    prompt.push_str(&format!("\n{}\n", skill_prompt.trim_end()));
+                }
+            }
+        }
+        prompt
+    }
+}
+
+impl Default for GenericOpenAIAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Adapter for GenericOpenAIAdapter {
+    fn platform(&self) -> Platform {
+        Platform::OpenAI
+    }
+
+    fn supported_features(&self) -> &[Feature] {
+        &[Feature::Skill]
+    }
+
+    fn limitations(&self) -> &[&str] {
+        &[
+            "Skills are folded into a single system prompt",
+            "Workflows, commands and MCP servers are not supported",
+        ]
+    }
+
+    async fn build(&self, ctx: &BuildContext, config: &ProjectConfig) -> Result<BuildResult> {
+        let output_dir = &ctx.output_dir(config);
+        tokio::fs::create_dir_all(output_dir).await?;
+
+        let profile = Self::custom_profile(ctx);
+        let server = ServerConfig {
+            base_url: profile
+                .and_then(|p| p.base_url.as_deref())
+                .unwrap_or(&self.base_url),
+            model: profile.and_then(|p| p.model.as_deref()),
+        };
+        let server_json = serde_json::to_string_pretty(&server)
+            .map_err(|e| HappyError::Other(e.to_string()))?;
+        tokio::fs::write(output_dir.join("config.json"), server_json).await?;
+        tokio::fs::write(
+            output_dir.join("system_prompt.md"),
+            self.generate_system_prompt(config),
+        )
+        .await?;
+
+        Ok(BuildResult::success(
+            Platform::OpenAI,
+            output_dir.display().to_string(),
+            vec!["config.json".to_string(), "system_prompt.md".to_string()],
+        ))
+    }
+
+    async fn install(&self, source: &Path, target: &InstallTarget) -> Result<()> {
+        if target.global {
+            return Err(HappyError::Other(
+                "OpenAI-compatible servers have no global config to install into".to_string(),
+            ));
+        }
+        let dest = target
+            .project_path
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(".openai"));
+
+        tokio::fs::create_dir_all(&dest).await?;
+        for file in ["config.json", "system_prompt.md"] {
+            let source_file = source.join(file);
+            if source_file.exists() {
+                tokio::fs::copy(&source_file, dest.join(file)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self, config: &ProjectConfig) -> ValidationResult {
+        let mut result = ValidationResult::ok();
+
+        if !config.workflows.is_empty() || !config.commands.is_empty() {
+            result = result.with_warning(happy_core::ValidationWarning {
+                field: "workflows".to_string(),
+                message: "OpenAI-compatible servers don't support workflows or commands"
+                    .to_string(),
+                suggestion: Some("They will be ignored for the OpenAI platform".to_string()),
+            });
+        }
+
+        result
+    }
+
+    async fn detect(&self) -> bool {
+        // Nothing is installed locally; servers are set up through Custom profiles
+        false
+    }
+
+    fn global_install_path(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Fail with the server's own message for a non-2xx response
+async fn check_status(
+    response: reqwest::Response,
+) -> std::result::Result<reqwest::Response, OpenAIError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    // OpenAI nests the message; some servers put a plain string there
+    let message = body["error"]["message"]
+        .as_str()
+        .or_else(|| body["error"].as_str())
+        .unwrap_or("no details");
+    Err(OpenAIError::Status {
+        status: status.as_u16(),
+        message: message.to_string(),
+    })
+}
+
+/// Fold one SSE line into `reply`, returning whether the stream is done
+fn apply_event(
+    line: &[u8],
+    reply: &mut ChatMessage,
+    on_chunk: &mut impl FnMut(&str),
+) -> std::result::Result<bool, OpenAIError> {
+    let line = String::from_utf8_lossy(line);
+    // Blank lines separate events; comments and other fields carry nothing
+    let Some(data) = line.trim().strip_prefix("data:") else {
+        return Ok(false);
+    };
+    let data = data.trim();
+    if data == "[DONE]" {
+        return Ok(true);
+    }
+    let chunk: ChatChunk =
+        serde_json::from_str(data).map_err(|e| OpenAIError::InvalidResponse(e.to_string()))?;
+    for choice in chunk.choices {
+        if let Some(content) = choice.delta.content {
+            on_chunk(&content);
+            reply.content.push_str(&content);
+        }
+    }
+    Ok(false)
+}
+
+/// IDs from a `/models` response
+fn model_ids(body: &serde_json::Value) -> Vec<String> {
+    body["data"]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| m["id"].as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_list_models() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .and(header("authorization", "Bearer secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "object": "list",
+                "data": [
+                    { "id": "Qwen/Qwen2.5-Coder-7B", "object": "model" },
+                    { "id": "llama-3.1-8b", "owned_by": "vllm" },
+                    { "object": "model" },
+                ]
+            })))
+            .mount(&server)
+            .await;
+
+        let mut profile = AIProfile::new("vllm", AIProvider::Custom("vllm".to_string()));
+        profile.base_url = Some(format!("{}/v1/", server.uri()));
+        profile.api_key = Some("secret".to_string());
+        let adapter = GenericOpenAIAdapter::from_profile(&profile);
+        let models = adapter.list_models().await.unwrap();
+        assert_eq!(models, vec!["Qwen/Qwen2.5-Coder-7B", "llama-3.1-8b"]);
+    }
+
+    #[tokio::test]
+    async fn test_chat_streams_sse() {
+        let server = MockServer::start().await;
+        let stream = [
+            r#"data: {"choices":[{"index":0,"delta":{"role":"assistant"}}]}"#,
+            "",
+            r#"data: {"choices":[{"index":0,"delta":{"content":"Hel"}}]}"#,
+            "",
+            ": keep-alive",
+            r#"data: {"choices":[{"index":0,"delta":{"content":"lo"}}]}"#,
+            "",
+            "data: [DONE]",
+            "",
+        ]
+        .join("\n");
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "model": "llama-3.1-8b",
+                "stream": true,
+                "messages": [{ "role": "user", "content": "Hi" }],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(stream))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({ "model": "missing" })))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "error": { "message": "The model `missing` does not exist." }
+            })))
+            .mount(&server)
+            .await;
+
+        let adapter = GenericOpenAIAdapter::with_base_url(&server.uri());
+        let messages = [ChatMessage::new(Role::User, "Hi")];
+        let mut chunks = Vec::new();
+        let reply = adapter
+            .chat("llama-3.1-8b", &messages, |c| chunks.push(c.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(reply, ChatMessage::new(Role::Assistant, "Hello"));
+        assert_eq!(chunks, ["Hel", "lo"]);
+
+        let err = adapter.chat("missing", &messages, |_| {}).await.unwrap_err();
+        match err {
+            OpenAIError::Status { status, message } => {
+                assert_eq!(status, 404);
+                assert!(message.contains("does not exist"), "{}", message);
+            }
+            err => panic!("expected a status error, got {:?}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_offline() {
+        // Nothing listens on a port that was just released
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let adapter = GenericOpenAIAdapter::with_base_url(&format!("http://127.0.0.1:{}", port));
+        let err = adapter.list_models().await.unwrap_err();
+        assert!(matches!(err, OpenAIError::Offline(_)), "{:?}", err);
+    }
+}
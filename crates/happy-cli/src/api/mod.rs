@@ -1,13 +1,45 @@
 //! API client for Happy Remote server
 
+use crate::commands::notify::NotificationQueue;
+use crate::config::SettingsManager;
 use anyhow::{Context, Result};
-use happy_core::{AuthTokens, User};
-use reqwest::Client as ReqwestClient;
+use happy_core::{AuthTokens, Settings, User};
+use futures::{Stream, StreamExt};
+use happy_types::{MachineInfo, Session, SessionEvent};
+use rand::Rng;
+use reqwest::{header, Client as ReqwestClient, Method, RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+/// Attempts per request for calls that should survive transient failures
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+
+/// First back-off interval; it doubles on every further attempt
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 500;
+
+/// Longest wait between attempts, whatever `Retry-After` asks for
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Warn once fewer than this many requests are left in the server's rate limit window
+const RATE_LIMIT_WARN_REMAINING: u64 = 10;
+
+/// Refresh the access token once it has less than this left
+const TOKEN_REFRESH_MARGIN_SECS: i64 = 60;
+
+/// What a password login returned
+#[derive(Debug)]
+pub enum LoginOutcome {
+    Tokens(AuthTokens),
+    /// Two-factor login is enabled; finish with [`Client::totp_challenge`]
+    TotpRequired { partial_token: String },
+}
 
 #[allow(dead_code)]
 pub struct Client {
     http: ReqwestClient,
     base_url: String,
+    /// Total attempts per request, including the first
+    max_attempts: u32,
+    base_delay_ms: u64,
 }
 
 impl Client {
@@ -21,6 +53,8 @@ impl Client {
         Self {
             http: ReqwestClient::new(),
             base_url,
+            max_attempts: 1,
+            base_delay_ms: DEFAULT_RETRY_BASE_DELAY_MS,
         }
     }
 
@@ -29,54 +63,128 @@ impl Client {
         self
     }
 
+    /// Retry network errors, 429 and 502/503/504 with jittered exponential back-off
+    ///
+    /// Requests that aren't idempotent, like POST, are only retried on 429: the
+    /// server turned them away unhandled, whereas after a dropped connection or
+    /// a gateway error they may already have taken effect.
+    pub fn with_retry(mut self, max_attempts: u32, base_delay_ms: u64) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.base_delay_ms = base_delay_ms;
+        self
+    }
+
     /// Create client from settings explicitly
     pub fn from_settings() -> Self {
         Self::new()
     }
 
+    /// Page that starts a SAML login, sending the browser to `redirect` with
+    /// the tokens once the IdP has vouched for the user
+    pub fn sso_login_url(&self, redirect: &str) -> Result<String> {
+        let mut url = reqwest::Url::parse(&format!("{}/auth/sso/saml/login", self.base_url))
+            .context("Invalid server URL")?;
+        url.query_pairs_mut().append_pair("redirect", redirect);
+        Ok(url.to_string())
+    }
+
+    /// Log in with a password, failing if the account also needs a TOTP code
     pub async fn login(&self, email: &str, password: &str) -> Result<AuthTokens> {
+        match self.start_login(email, password).await? {
+            LoginOutcome::Tokens(tokens) => Ok(tokens),
+            LoginOutcome::TotpRequired { .. } => anyhow::bail!(
+                "Login failed: two-factor authentication is enabled. \
+                 Run `happy auth login` to sign in with a code"
+            ),
+        }
+    }
+
+    /// Log in with a password; accounts with two-factor login need a second step
+    pub async fn start_login(&self, email: &str, password: &str) -> Result<LoginOutcome> {
         // server_url already includes /api/v1, so just append the endpoint
         let url = format!("{}/auth/login", self.base_url);
 
         let response = self
-            .http
-            .post(&url)
-            .json(&serde_json::json!({
+            .send(self.http.post(&url).json(&serde_json::json!({
                 "email": email,
                 "password": password,
-            }))
-            .send()
+            })))
             .await
             .context("Failed to send login request")?;
 
         let status = response.status();
         let body = response.text().await?;
 
+        if status == StatusCode::LOCKED {
+            let error: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+            anyhow::bail!(
+                "Login failed: too many failed attempts, try again after {}",
+                error["unlock_at"].as_str().unwrap_or("15 minutes")
+            );
+        }
+
         if !status.is_success() {
             let error: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+            if error["error"].as_str() == Some("sso_required") {
+                anyhow::bail!(
+                    "Login failed: this account signs in through SSO. \
+                     Run `happy auth login --sso`"
+                );
+            }
             anyhow::bail!(
                 "Login failed: {}",
                 error["message"].as_str().unwrap_or("Invalid credentials")
             );
         }
 
-        // Server returns LoginResponse { access_token, refresh_token, expires_in, user }
-        let login_response: serde_json::Value =
+        let response: serde_json::Value =
             serde_json::from_str(&body).context("Failed to parse login response")?;
-
-        let tokens = AuthTokens {
-            access_token: login_response["access_token"]
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Missing access_token in response"))?
-                .to_string(),
-            refresh_token: login_response["refresh_token"]
+        if response["require_totp"].as_bool() == Some(true) {
+            let partial_token = response["partial_token"]
                 .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Missing refresh_token in response"))?
-                .to_string(),
-            expires_in: login_response["expires_in"].as_i64().unwrap_or(0),
-        };
+                .context("Missing partial_token in login response")?;
+            return Ok(LoginOutcome::TotpRequired {
+                partial_token: partial_token.to_string(),
+            });
+        }
 
-        Ok(tokens)
+        parse_tokens(&body)
+            .context("Failed to parse login response")
+            .map(LoginOutcome::Tokens)
+    }
+
+    /// Finish a two-factor login with the code from the user's authenticator app
+    pub async fn totp_challenge(&self, partial_token: &str, code: &str) -> Result<AuthTokens> {
+        let response = self
+            .send(
+                self.http
+                    .post(format!("{}/auth/totp/challenge", self.base_url))
+                    .json(&serde_json::json!({
+                        "partial_token": partial_token,
+                        "code": code,
+                    })),
+            )
+            .await
+            .context("Failed to send authentication code")?;
+
+        let status = response.status();
+        let body = response.text().await?;
+
+        if status == StatusCode::LOCKED {
+            let error: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+            anyhow::bail!(
+                "Login failed: too many failed attempts, try again after {}",
+                error["unlock_at"].as_str().unwrap_or("15 minutes")
+            );
+        }
+        if status == StatusCode::UNAUTHORIZED {
+            anyhow::bail!("Login failed: invalid or expired authentication code");
+        }
+        if !status.is_success() {
+            anyhow::bail!("Login failed: {}", status);
+        }
+
+        parse_tokens(&body).context("Failed to parse login response")
     }
 
     pub async fn register(
@@ -86,14 +194,15 @@ impl Client {
         name: Option<&str>,
     ) -> Result<AuthTokens> {
         let response = self
-            .http
-            .post(format!("{}/auth/register", self.base_url))
-            .json(&serde_json::json!({
-                "email": email,
-                "password": password,
-                "name": name,
-            }))
-            .send()
+            .send(
+                self.http
+                    .post(format!("{}/auth/register", self.base_url))
+                    .json(&serde_json::json!({
+                        "email": email,
+                        "password": password,
+                        "name": name,
+                    })),
+            )
             .await
             .context("Failed to send register request")?;
 
@@ -108,41 +217,102 @@ impl Client {
             );
         }
 
-        // Server returns LoginResponse { access_token, refresh_token, expires_in, user }
-        let login_response: serde_json::Value =
-            serde_json::from_str(&body).context("Failed to parse register response")?;
+        parse_tokens(&body).context("Failed to parse register response")
+    }
+
+    /// Exchange a refresh token for new tokens; `None` when the server rejects it
+    pub async fn refresh(&self, refresh_token: &str) -> Result<Option<AuthTokens>> {
+        let response = self
+            .send(
+                self.http
+                    .post(format!("{}/auth/refresh", self.base_url))
+                    .json(&serde_json::json!({ "refresh_token": refresh_token })),
+            )
+            .await
+            .context("Failed to send refresh request")?;
+
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            anyhow::bail!("Token refresh failed: {}", status);
+        }
+
+        let body = response.text().await?;
+        parse_tokens(&body)
+            .context("Failed to parse refresh response")
+            .map(Some)
+    }
 
-        let tokens = AuthTokens {
-            access_token: login_response["access_token"]
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Missing access_token in response"))?
-                .to_string(),
-            refresh_token: login_response["refresh_token"]
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Missing refresh_token in response"))?
-                .to_string(),
-            expires_in: login_response["expires_in"].as_i64().unwrap_or(0),
-        };
+    /// Refresh the saved tokens and return the new access token.
+    ///
+    /// Once the refresh token has expired too, logs in again with the saved
+    /// credentials if there are any.
+    pub async fn refresh_token(&self) -> Result<String> {
+        let mut settings = SettingsManager::load()?;
+        self.renew_tokens(&mut settings).await?;
+        SettingsManager::save(&settings)?;
+        Ok(settings.access_token.unwrap_or_default())
+    }
+
+    /// The saved access token, refreshed first if it is about to expire
+    pub async fn access_token(&self) -> Result<String> {
+        let mut settings = SettingsManager::load()?;
+        if self.ensure_fresh_token(&mut settings).await? {
+            SettingsManager::save(&settings)?;
+        }
+        Ok(settings.access_token.unwrap_or_default())
+    }
 
-        Ok(tokens)
+    /// Renew `settings`' tokens if they are about to expire, returning whether they changed
+    async fn ensure_fresh_token(&self, settings: &mut Settings) -> Result<bool> {
+        if settings.access_token.is_none() {
+            anyhow::bail!("Not logged in. Run `happy auth login` first");
+        }
+        if !settings.token_expires_within(chrono::Duration::seconds(TOKEN_REFRESH_MARGIN_SECS)) {
+            return Ok(false);
+        }
+        self.renew_tokens(settings).await?;
+        Ok(true)
+    }
+
+    async fn renew_tokens(&self, settings: &mut Settings) -> Result<()> {
+        if let Some(refresh_token) = settings.refresh_token.clone() {
+            if let Some(tokens) = self.refresh(&refresh_token).await? {
+                settings.store_tokens(tokens);
+                return Ok(());
+            }
+            tracing::info!("Refresh token expired, logging in again");
+        }
+
+        let (Some(email), Some(password)) = (settings.email.clone(), settings.password.clone())
+        else {
+            anyhow::bail!("Session expired. Run `happy auth login` to sign in again");
+        };
+        let tokens = self.login(&email, &password).await?;
+        settings.store_tokens(tokens);
+        Ok(())
     }
 
     pub async fn logout(&self, token: &str) -> Result<()> {
         let _ = self
-            .http
-            .post(format!("{}/auth/logout", self.base_url))
-            .bearer_auth(token)
-            .send()
+            .send(
+                self.http
+                    .post(format!("{}/auth/logout", self.base_url))
+                    .bearer_auth(token),
+            )
             .await;
         Ok(())
     }
 
     pub async fn get_user_info(&self, token: &str) -> Result<User> {
         let response = self
-            .http
-            .get(format!("{}/users/me", self.base_url))
-            .bearer_auth(token)
-            .send()
+            .send(
+                self.http
+                    .get(format!("{}/users/me", self.base_url))
+                    .bearer_auth(token),
+            )
             .await
             .context("Failed to get user info")?;
 
@@ -156,10 +326,11 @@ impl Client {
 
     pub async fn list_access_keys(&self, token: &str) -> Result<Vec<AccessKeyInfo>> {
         let response = self
-            .http
-            .get(format!("{}/access-keys", self.base_url))
-            .bearer_auth(token)
-            .send()
+            .send(
+                self.http
+                    .get(format!("{}/access-keys", self.base_url))
+                    .bearer_auth(token),
+            )
             .await
             .context("Failed to list access keys")?;
 
@@ -172,16 +343,76 @@ impl Client {
     }
 
     pub async fn send_notification(&self, token: &str, message: &str) -> Result<()> {
-        let _ = self
-            .http
+        let response = self
+            .send(self.notification_request(token, message))
+            .await
+            .context("Failed to reach server")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to send notification: {}", response.status());
+        }
+
+        Ok(())
+    }
+
+    fn notification_request(&self, token: &str, message: &str) -> RequestBuilder {
+        self.http
             .post(format!("{}/push/send", self.base_url))
             .bearer_auth(token)
             .json(&serde_json::json!({
                 "message": message,
             }))
-            .send()
-            .await;
-        Ok(())
+    }
+
+    /// Deliver notifications queued by `happy notify` while the server was unreachable
+    async fn flush_notification_queue(&self, token: &str) {
+        let Ok(queue) = NotificationQueue::open() else {
+            return;
+        };
+        let pending = match queue.drain() {
+            Ok(pending) if !pending.is_empty() => pending,
+            Ok(_) => return,
+            Err(e) => {
+                tracing::warn!("Failed to read pending notifications: {}", e);
+                return;
+            }
+        };
+
+        let mut pending = pending.into_iter();
+        let mut undelivered = Vec::new();
+        for request in pending.by_ref() {
+            // Sent directly rather than via `send`, which would flush again
+            match self
+                .notification_request(token, &request.message)
+                .send()
+                .await
+            {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response)
+                    if response.status().is_client_error()
+                        && !matches!(
+                            response.status(),
+                            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+                        ) =>
+                {
+                    tracing::warn!(
+                        "Dropping queued notification rejected by the server: {}",
+                        response.status()
+                    );
+                }
+                _ => {
+                    undelivered.push(request);
+                    break;
+                }
+            }
+        }
+
+        undelivered.extend(pending);
+        if !undelivered.is_empty() {
+            if let Err(e) = queue.enqueue_all(undelivered) {
+                tracing::warn!("Failed to re-queue notifications: {}", e);
+            }
+        }
     }
 
     /// Create a new session on the server
@@ -193,19 +424,20 @@ impl Client {
         machine_id: &str,
         machine_name: &str,
         cwd: &str,
-    ) -> Result<SessionInfo> {
+    ) -> Result<Session> {
         let response = self
-            .http
-            .post(format!("{}/sessions", self.base_url))
-            .bearer_auth(token)
-            .header("X-Machine-ID", machine_id)
-            .header("X-Machine-Name", machine_name)
-            .json(&serde_json::json!({
-                "tag": tag,
-                "profile": profile,
-                "cwd": cwd,
-            }))
-            .send()
+            .send(
+                self.http
+                    .post(format!("{}/sessions", self.base_url))
+                    .bearer_auth(token)
+                    .header("X-Machine-ID", machine_id)
+                    .header("X-Machine-Name", machine_name)
+                    .json(&serde_json::json!({
+                        "tag": tag,
+                        "profile": profile,
+                        "cwd": cwd,
+                    })),
+            )
             .await
             .context("Failed to create session")?;
 
@@ -218,19 +450,17 @@ impl Client {
         }
 
         let result: SessionResponse = response.json().await?;
-        Ok(SessionInfo {
-            id: result.session.id,
-            tag: result.session.tag,
-        })
+        Ok(result.session)
     }
 
     /// List all sessions for the user
-    pub async fn list_sessions(&self, token: &str) -> Result<Vec<SessionInfo>> {
+    pub async fn list_sessions(&self, token: &str) -> Result<Vec<Session>> {
         let response = self
-            .http
-            .get(format!("{}/sessions", self.base_url))
-            .bearer_auth(token)
-            .send()
+            .send(
+                self.http
+                    .get(format!("{}/sessions", self.base_url))
+                    .bearer_auth(token),
+            )
             .await
             .context("Failed to list sessions")?;
 
@@ -239,23 +469,55 @@ impl Client {
         }
 
         let result: SessionsListResponse = response.json().await?;
-        Ok(result
-            .sessions
-            .into_iter()
-            .map(|s| SessionInfo {
-                id: s.id,
-                tag: s.tag,
+        Ok(result.sessions)
+    }
+
+    /// Follow session creations, updates and deletions until the server closes the stream
+    pub async fn stream_session_events(
+        &self,
+        token: &str,
+    ) -> Result<impl Stream<Item = Result<SessionEvent>>> {
+        let response = self
+            .send(
+                self.http
+                    .get(format!("{}/sessions/events", self.base_url))
+                    .bearer_auth(token)
+                    .header(header::ACCEPT, "text/event-stream"),
+            )
+            .await
+            .context("Failed to open session event stream")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to open session event stream: {}", response.status());
+        }
+
+        let events = response
+            .bytes_stream()
+            .scan(SseParser::default(), |parser, chunk| {
+                let events: Vec<Result<SessionEvent>> = match chunk {
+                    Ok(bytes) => parser
+                        .push(&bytes)
+                        .into_iter()
+                        .map(|data| {
+                            serde_json::from_str(&data).context("Invalid session event")
+                        })
+                        .collect(),
+                    Err(e) => vec![Err(anyhow::Error::new(e).context("Session event stream failed"))],
+                };
+                futures::future::ready(Some(futures::stream::iter(events)))
             })
-            .collect())
+            .flatten();
+        Ok(events)
     }
 
     /// Delete a session
     pub async fn delete_session(&self, token: &str, session_id: &str) -> Result<()> {
         let response = self
-            .http
-            .delete(format!("{}/sessions/{}", self.base_url, session_id))
-            .bearer_auth(token)
-            .send()
+            .send(
+                self.http
+                    .delete(format!("{}/sessions/{}", self.base_url, session_id))
+                    .bearer_auth(token),
+            )
             .await
             .context("Failed to delete session")?;
 
@@ -265,28 +527,295 @@ impl Client {
 
         Ok(())
     }
+
+    /// Change a session's tag
+    pub async fn rename_session(&self, token: &str, session_id: &str, tag: &str) -> Result<()> {
+        let response = self
+            .send(
+                self.http
+                    .patch(format!("{}/sessions/{}", self.base_url, session_id))
+                    .bearer_auth(token)
+                    .json(&serde_json::json!({ "tag": tag })),
+            )
+            .await
+            .context("Failed to rename session")?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::BAD_REQUEST => anyhow::bail!(
+                "Invalid tag '{}': use 1-64 letters, digits, '_' or '-'",
+                tag
+            ),
+            StatusCode::CONFLICT => {
+                anyhow::bail!("Another active session is already tagged '{}'", tag)
+            }
+            status => anyhow::bail!("Failed to rename session: {}", status),
+        }
+    }
+
+    /// List the user's registered machines, optionally narrowed by a search query
+    pub async fn list_machines(
+        &self,
+        token: &str,
+        search: Option<&str>,
+    ) -> Result<Vec<MachineInfo>> {
+        let mut request = self
+            .http
+            .get(format!("{}/machines", self.base_url))
+            .bearer_auth(token);
+        if let Some(query) = search {
+            request = request.query(&[("q", query)]);
+        }
+        let response = self
+            .send(request)
+            .await
+            .context("Failed to list machines")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to list machines: {}", response.status());
+        }
+
+        let result: MachinesListResponse = response.json().await?;
+        Ok(result.machines)
+    }
+
+    /// A page of the server's audit log, newest first; admins only
+    pub async fn list_audit_entries(
+        &self,
+        token: &str,
+        before: Option<i64>,
+        limit: u32,
+    ) -> Result<AuditPage> {
+        let mut request = self
+            .http
+            .get(format!("{}/audit", self.base_url))
+            .bearer_auth(token)
+            .query(&[("limit", limit)]);
+        if let Some(before) = before {
+            request = request.query(&[("before", before)]);
+        }
+        let response = self
+            .send(request)
+            .await
+            .context("Failed to fetch audit log")?;
+
+        match response.status() {
+            status if status.is_success() => Ok(response.json().await?),
+            StatusCode::FORBIDDEN => anyhow::bail!("Only administrators can read the audit log"),
+            status => anyhow::bail!("Failed to fetch audit log: {}", status),
+        }
+    }
+
+    /// Rename a machine
+    pub async fn rename_machine(&self, token: &str, machine_id: &str, name: &str) -> Result<()> {
+        let response = self
+            .send(
+                self.http
+                    .patch(format!("{}/machines/{}", self.base_url, machine_id))
+                    .bearer_auth(token)
+                    .json(&serde_json::json!({ "name": name })),
+            )
+            .await
+            .context("Failed to rename machine")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to rename machine: {}", response.status());
+        }
+
+        Ok(())
+    }
+
+    /// Remove a machine
+    pub async fn delete_machine(&self, token: &str, machine_id: &str) -> Result<()> {
+        let response = self
+            .send(
+                self.http
+                    .delete(format!("{}/machines/{}", self.base_url, machine_id))
+                    .bearer_auth(token),
+            )
+            .await
+            .context("Failed to remove machine")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to remove machine: {}", response.status());
+        }
+
+        Ok(())
+    }
+
+    /// Migrations the server's database has run, oldest first
+    pub async fn admin_db_status(&self, admin_token: &str) -> Result<Vec<MigrationInfo>> {
+        let response = self
+            .send(
+                self.http
+                    .get(format!("{}/admin/db-status", self.base_url))
+                    .bearer_auth(admin_token),
+            )
+            .await
+            .context("Failed to fetch database status")?;
+
+        match response.status() {
+            status if status.is_success() => {
+                let result: DbStatusResponse = response.json().await?;
+                Ok(result.migrations)
+            }
+            StatusCode::NOT_FOUND => {
+                anyhow::bail!("Admin endpoints are disabled; set ADMIN_TOKEN on the server")
+            }
+            StatusCode::UNAUTHORIZED => anyhow::bail!("Admin token was rejected"),
+            status => anyhow::bail!("Failed to fetch database status: {}", status),
+        }
+    }
+
+    /// Move an orphaned machine's sessions onto `new_id` and delete it; returns sessions moved
+    pub async fn admin_merge_machines(
+        &self,
+        admin_token: &str,
+        old_id: &str,
+        new_id: &str,
+    ) -> Result<u64> {
+        let response = self
+            .send(
+                self.http
+                    .post(format!("{}/admin/machines/merge", self.base_url))
+                    .bearer_auth(admin_token)
+                    .json(&serde_json::json!({ "old_id": old_id, "new_id": new_id })),
+            )
+            .await
+            .context("Failed to merge machines")?;
+
+        match response.status() {
+            status if status.is_success() => {
+                let result: MergeMachinesResponse = response.json().await?;
+                Ok(result.sessions_moved)
+            }
+            StatusCode::NOT_FOUND => {
+                anyhow::bail!("Machine not found, or admin endpoints are disabled on the server")
+            }
+            StatusCode::UNAUTHORIZED => anyhow::bail!("Admin token was rejected"),
+            StatusCode::CONFLICT => anyhow::bail!("The machines belong to different users"),
+            status => anyhow::bail!("Failed to merge machines: {}", status),
+        }
+    }
+
+    /// Send a request, retrying transient failures according to the retry policy.
+    ///
+    /// The last response is returned as-is, so callers still see the final status.
+    /// Once an authenticated request succeeds, queued notifications are delivered too.
+    async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let token = bearer_token(&request);
+        let response = self.send_with_retry(request).await?;
+        if let Some(remaining) = rate_limit_remaining(&response) {
+            if remaining < RATE_LIMIT_WARN_REMAINING {
+                tracing::warn!(
+                    "Approaching the server's rate limit: {} requests left until {}",
+                    remaining,
+                    rate_limit_reset(&response).unwrap_or_else(|| "the window resets".to_string())
+                );
+            }
+        }
+        if response.status().is_success() {
+            if let Some(token) = token {
+                self.flush_notification_queue(&token).await;
+            }
+        }
+        Ok(response)
+    }
+
+    async fn send_with_retry(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let idempotent = request
+            .try_clone()
+            .and_then(|r| r.build().ok())
+            .is_some_and(|r| is_idempotent(r.method()));
+
+        let mut attempt = 1;
+        loop {
+            // Requests with streaming bodies can't be cloned and are sent only once
+            let Some(this_try) = request.try_clone().filter(|_| attempt < self.max_attempts) else {
+                return request.send().await;
+            };
+
+            let delay = match this_try.send().await {
+                Ok(response) => match response.status() {
+                    StatusCode::TOO_MANY_REQUESTS => retry_after(&response)
+                        .unwrap_or_else(|| backoff_delay(self.base_delay_ms, attempt)),
+                    StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+                        if idempotent =>
+                    {
+                        backoff_delay(self.base_delay_ms, attempt)
+                    }
+                    _ => return Ok(response),
+                },
+                Err(_) if idempotent => backoff_delay(self.base_delay_ms, attempt),
+                Err(e) => return Err(e),
+            }
+            .min(MAX_RETRY_DELAY);
+
+            tracing::debug!(
+                "Request attempt {}/{} failed, retrying in {:?}",
+                attempt,
+                self.max_attempts,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MigrationInfo {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
-pub struct SessionInfo {
-    pub id: String,
-    pub tag: String,
+struct DbStatusResponse {
+    pub migrations: Vec<MigrationInfo>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct MergeMachinesResponse {
+    pub sessions_moved: u64,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
 struct SessionResponse {
-    pub session: SessionDetails,
+    pub session: Session,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
 struct SessionsListResponse {
-    pub sessions: Vec<SessionDetails>,
+    pub sessions: Vec<Session>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
-struct SessionDetails {
-    pub id: String,
-    pub tag: String,
+struct MachinesListResponse {
+    pub machines: Vec<MachineInfo>,
+}
+
+/// One page of `GET /audit`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    /// `before` for the next page, if there is one
+    pub next_before: Option<i64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub user_id: Option<String>,
+    pub event_type: String,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub ip_address: Option<String>,
+    /// JSON object with event-specific details
+    pub metadata: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -298,8 +827,310 @@ pub struct AccessKeyInfo {
     pub is_revoked: bool,
 }
 
+/// Incremental `text/event-stream` parser yielding the `data` of each complete event
+#[derive(Default)]
+struct SseParser {
+    /// Bytes after the last newline seen
+    buffer: Vec<u8>,
+    /// `data:` lines of the event being read
+    data: Vec<String>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(self.data.join("\n"));
+                    self.data.clear();
+                }
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            }
+            // Comments (keep-alives) and other fields are ignored
+        }
+        events
+    }
+}
+
+/// Whether sending the request twice has the same effect as sending it once
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+    )
+}
+
+/// Back-off after failed attempt `attempt` (1-based): doubling from `base_delay_ms`, plus jitter
+fn backoff_delay(base_delay_ms: u64, attempt: u32) -> Duration {
+    let delay_ms = base_delay_ms.saturating_mul(1 << (attempt - 1).min(16));
+    let jitter_ms = if delay_ms > 0 {
+        rand::thread_rng().gen_range(0..delay_ms)
+    } else {
+        0
+    };
+    Duration::from_millis(delay_ms + jitter_ms)
+}
+
+/// The delay a 429 response asks for, when given in seconds
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// `X-RateLimit-Remaining`, when the server sent it
+fn rate_limit_remaining(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get("x-ratelimit-remaining")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// `X-RateLimit-Reset` as a local time
+fn rate_limit_reset(response: &Response) -> Option<String> {
+    let epoch: i64 = response
+        .headers()
+        .get("x-ratelimit-reset")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    let reset = chrono::DateTime::from_timestamp(epoch, 0)?;
+    Some(reset.with_timezone(&chrono::Local).format("%H:%M:%S").to_string())
+}
+
+/// The bearer token a request will be sent with
+fn bearer_token(request: &RequestBuilder) -> Option<String> {
+    let request = request.try_clone()?.build().ok()?;
+    request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::to_string)
+}
+
+/// Tokens from a `LoginResponse { access_token, refresh_token, expires_in, user }`
+fn parse_tokens(body: &str) -> Result<AuthTokens> {
+    let response: serde_json::Value = serde_json::from_str(body)?;
+    Ok(AuthTokens {
+        access_token: response["access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing access_token in response"))?
+            .to_string(),
+        refresh_token: response["refresh_token"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing refresh_token in response"))?
+            .to_string(),
+        expires_in: response["expires_in"].as_i64().unwrap_or(0),
+    })
+}
+
+/// Whether a request failed because the server couldn't be reached at all
+pub fn is_offline_error(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|e| e.is_connect() || e.is_timeout())
+}
+
 impl Default for Client {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn tokens_response(access_token: &str, refresh_token: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "access_token": access_token,
+            "refresh_token": refresh_token,
+            "expires_in": 3600,
+            "user": { "id": "u1", "email": "dev@example.com", "name": null },
+        }))
+    }
+
+    fn logged_in(expires_in: i64) -> Settings {
+        let mut settings = Settings::default();
+        settings.store_tokens(AuthTokens {
+            access_token: "access-1".to_string(),
+            refresh_token: "refresh-1".to_string(),
+            expires_in,
+        });
+        settings
+    }
+
+    #[tokio::test]
+    async fn test_refreshes_token_about_to_expire() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/auth/refresh"))
+            .and(body_json(serde_json::json!({ "refresh_token": "refresh-1" })))
+            .respond_with(tokens_response("access-2", "refresh-2"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = Client::new().with_base_url(server.uri());
+
+        // An hour left: used as is
+        let mut settings = logged_in(3600);
+        assert!(!client.ensure_fresh_token(&mut settings).await.unwrap());
+        assert_eq!(settings.access_token.as_deref(), Some("access-1"));
+
+        let mut settings = logged_in(30);
+        assert!(client.ensure_fresh_token(&mut settings).await.unwrap());
+        assert_eq!(settings.access_token.as_deref(), Some("access-2"));
+        assert_eq!(settings.refresh_token.as_deref(), Some("refresh-2"));
+        assert!(!settings.token_expires_within(chrono::Duration::minutes(59)));
+    }
+
+    #[tokio::test]
+    async fn test_expired_refresh_token_logs_in_again() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/auth/refresh"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/auth/login"))
+            .and(body_json(serde_json::json!({
+                "email": "dev@example.com",
+                "password": "hunter22",
+            })))
+            .respond_with(tokens_response("access-3", "refresh-3"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = Client::new().with_base_url(server.uri());
+
+        // Without saved credentials the user has to log in themselves
+        let mut settings = logged_in(3600);
+        settings.token_expires_at = Some(chrono::Utc::now() - chrono::Duration::minutes(5));
+        let err = client.ensure_fresh_token(&mut settings).await.unwrap_err();
+        assert!(err.to_string().contains("happy auth login"), "{}", err);
+
+        settings.email = Some("dev@example.com".to_string());
+        settings.password = Some("hunter22".to_string());
+        assert!(client.ensure_fresh_token(&mut settings).await.unwrap());
+        assert_eq!(settings.access_token.as_deref(), Some("access-3"));
+        assert_eq!(settings.refresh_token.as_deref(), Some("refresh-3"));
+    }
+
+    #[tokio::test]
+    async fn test_totp_login() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/auth/login"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "require_totp": true,
+                "partial_token": "partial-1",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/auth/totp/challenge"))
+            .and(body_json(serde_json::json!({
+                "partial_token": "partial-1",
+                "code": "123456",
+            })))
+            .respond_with(tokens_response("access-4", "refresh-4"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = Client::new().with_base_url(server.uri());
+
+        let LoginOutcome::TotpRequired { partial_token } =
+            client.start_login("dev@example.com", "hunter22").await.unwrap()
+        else {
+            panic!("expected a TOTP challenge");
+        };
+        assert_eq!(partial_token, "partial-1");
+        let tokens = client.totp_challenge(&partial_token, "123456").await.unwrap();
+        assert_eq!(tokens.access_token, "access-4");
+
+        // Saved credentials alone can't get past the second factor
+        let err = client.login("dev@example.com", "hunter22").await.unwrap_err();
+        assert!(err.to_string().contains("happy auth login"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_retries_only_idempotent_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/sessions"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/sessions"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/notifications"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let client = Client::new().with_base_url(server.uri()).with_retry(3, 0);
+
+        let url = |p: &str| format!("{}{}", server.uri(), p);
+        let get = client.send(client.http.get(url("/sessions"))).await.unwrap();
+        assert_eq!(get.status(), StatusCode::SERVICE_UNAVAILABLE);
+        // The POST may have been handled before the gateway gave up
+        let post = client.send(client.http.post(url("/sessions"))).await.unwrap();
+        assert_eq!(post.status(), StatusCode::SERVICE_UNAVAILABLE);
+        // but a rate-limited one wasn't
+        let limited = client.send(client.http.post(url("/notifications"))).await.unwrap();
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn test_backoff_delay_doubles_with_jitter() {
+        for attempt in 1..=3 {
+            let base = Duration::from_millis(500 * (1 << (attempt - 1)));
+            let delay = backoff_delay(500, attempt);
+            assert!(delay >= base && delay < base * 2, "{:?}", delay);
+        }
+        assert_eq!(backoff_delay(0, 1), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_sse_parser_handles_split_chunks_and_keep_alives() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b":\n\ndata: {\"type\":").is_empty());
+        assert_eq!(
+            parser.push(b"\"session.deleted\",\"session_id\":\"s1\"}\r\n\r\ndata: a\ndata: b\n\n"),
+            vec![
+                r#"{"type":"session.deleted","session_id":"s1"}"#.to_string(),
+                "a\nb".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! Admin commands - Server operator tools (need the server's ADMIN_TOKEN)
+
+use crate::api::Client;
+use crate::OutputFormat;
+use anyhow::Result;
+use chrono::Local;
+use colored::Colorize;
+
+pub async fn db_status(admin_token: &str, output: OutputFormat) -> Result<()> {
+    let migrations = Client::new().admin_db_status(admin_token).await?;
+
+    if output == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&migrations)?);
+        return Ok(());
+    }
+
+    println!("{}", "🗄  Database migrations".blue().bold());
+    println!();
+
+    if migrations.is_empty() {
+        println!("   (No migrations have run)");
+        return Ok(());
+    }
+
+    println!(
+        "   {}  {}  {}",
+        format!("{:<7}", "Version").bold(),
+        format!("{:<16}", "Installed").bold(),
+        "Description".bold(),
+    );
+    for migration in &migrations {
+        println!(
+            "   {}  {}  {}",
+            format!("{:<7}", format!("{:04}", migration.version)).cyan(),
+            migration
+                .installed_on
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            migration.description,
+        );
+    }
+
+    Ok(())
+}
@@ -1,9 +1,22 @@
 //! Authentication commands
 
-use crate::api::Client;
-use crate::config::SettingsManager;
+use crate::api::{AuditEntry, Client, LoginOutcome};
+use crate::config::{keychain, SettingsManager};
+use crate::OutputFormat;
 use anyhow::{Context, Result};
+use base64::Engine;
+use chrono::{DateTime, Local, Utc};
 use colored::Colorize;
+use happy_core::AuthTokens;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Local port the browser is sent back to after an SSO login
+const SSO_CALLBACK_PORT: u16 = 16793;
+
+/// How long to wait for the user to finish signing in at their IdP
+const SSO_TIMEOUT: Duration = Duration::from_secs(300);
 
 pub async fn login_interactive() -> Result<()> {
     println!("{}", "🔹 Login to Happy Remote".blue().bold());
@@ -22,7 +35,7 @@ pub async fn login_interactive() -> Result<()> {
     println!();
     println!("{}", "🔐 Authenticating...".dimmed());
 
-    do_login(&email, &password).await
+    do_login(&email, &password, true).await
 }
 
 pub async fn login_non_interactive(email: &str, password: &str) -> Result<()> {
@@ -33,16 +46,37 @@ pub async fn login_non_interactive(email: &str, password: &str) -> Result<()> {
     println!();
     println!("{}", "🔐 Authenticating...".dimmed());
 
-    do_login(email, password).await
+    do_login(email, password, false).await
 }
 
-async fn do_login(email: &str, password: &str) -> Result<()> {
+/// `interactive` logins prompt for a TOTP code when the account needs one
+async fn do_login(email: &str, password: &str, interactive: bool) -> Result<()> {
 
     // Call API
     let client = Client::new();
-    let result = client
-        .login(&email, &password)
-        .await;
+    let result = match client.start_login(email, password).await {
+        Ok(LoginOutcome::Tokens(tokens)) => Ok(tokens),
+        Ok(LoginOutcome::TotpRequired { partial_token }) => {
+            if !interactive {
+                anyhow::bail!(
+                    "Two-factor authentication is enabled. \
+                     Run `happy auth login` without credentials to enter a code"
+                );
+            }
+            let code: String = dialoguer::Input::new()
+                .with_prompt("Authentication code")
+                .validate_with(|code: &String| {
+                    if code.len() == 6 && code.bytes().all(|b| b.is_ascii_digit()) {
+                        Ok(())
+                    } else {
+                        Err("Enter the 6-digit code from your authenticator app")
+                    }
+                })
+                .interact_text()?;
+            client.totp_challenge(&partial_token, code.trim()).await
+        }
+        Err(e) => Err(e),
+    };
 
     let tokens = match result {
         Ok(tokens) => tokens,
@@ -57,15 +91,15 @@ async fn do_login(email: &str, password: &str) -> Result<()> {
     };
 
     // Save tokens and credentials for auto-login
+    let access_token = tokens.access_token.clone();
     let mut settings = SettingsManager::load()?;
-    settings.access_token = Some(tokens.access_token.clone());
-    settings.refresh_token = Some(tokens.refresh_token);
+    settings.store_tokens(tokens);
     settings.email = Some(email.to_string());
     settings.password = Some(password.to_string());
     SettingsManager::save(&settings)?;
 
     // Get user info
-    match client.get_user_info(&tokens.access_token).await {
+    match client.get_user_info(&access_token).await {
         Ok(user) => {
             settings.user_id = Some(user.id.clone());
             SettingsManager::save(&settings)?;
@@ -84,6 +118,102 @@ async fn do_login(email: &str, password: &str) -> Result<()> {
     Ok(())
 }
 
+/// Sign in through the server's SAML IdP: open the login page in the
+/// browser and wait for the server to redirect back with tokens
+pub async fn login_sso() -> Result<()> {
+    println!("{}", "🔹 Login to Happy Remote with SSO".blue().bold());
+    println!();
+
+    let listener = TcpListener::bind(("127.0.0.1", SSO_CALLBACK_PORT))
+        .await
+        .with_context(|| {
+            format!("Port {} is in use; is another login running?", SSO_CALLBACK_PORT)
+        })?;
+    let client = Client::new();
+    let url = client.sso_login_url(&format!("http://localhost:{}/callback", SSO_CALLBACK_PORT))?;
+
+    println!("   Opening your browser to sign in. If it doesn't open, visit:");
+    println!("   {}", url.cyan());
+    if let Err(e) = webbrowser::open(&url) {
+        tracing::debug!("Failed to open browser: {}", e);
+    }
+    println!();
+    println!("{}", "⏳ Waiting for sign-in to finish in the browser...".dimmed());
+
+    let params = tokio::time::timeout(SSO_TIMEOUT, wait_for_callback(&listener))
+        .await
+        .context("Timed out waiting for the SSO login")??;
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let (Some(access_token), Some(refresh_token)) = (param("access_token"), param("refresh_token"))
+    else {
+        anyhow::bail!("The server did not return tokens");
+    };
+
+    let mut settings = SettingsManager::load()?;
+    settings.store_tokens(AuthTokens {
+        access_token,
+        refresh_token,
+        expires_in: param("expires_in").and_then(|v| v.parse().ok()).unwrap_or(0),
+    });
+    settings.user_id = param("user_id");
+    settings.email = param("email");
+    // SSO accounts have no password to fall back on
+    settings.password = None;
+    SettingsManager::save(&settings)?;
+
+    println!();
+    println!("{}", "✅ Login successful!".green().bold());
+    if let Some(email) = &settings.email {
+        println!();
+        println!("   Welcome, {}!", email.cyan());
+    }
+    Ok(())
+}
+
+/// Serve the loopback callback until the browser arrives with the query string
+async fn wait_for_callback(listener: &TcpListener) -> Result<Vec<(String, String)>> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut request = vec![0u8; 16 * 1024];
+        let len = stream.read(&mut request).await?;
+        let request = String::from_utf8_lossy(&request[..len]);
+
+        let Some(params) = callback_params(&request) else {
+            // Favicons and the like
+            let _ = stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                .await;
+            continue;
+        };
+
+        let body = "<html><body><h3>Signed in to Happy Remote.</h3>\
+                    <p>You can close this tab and return to the terminal.</p></body></html>";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        return Ok(params);
+    }
+}
+
+/// Query parameters of a `GET /callback?...` request
+fn callback_params(request: &str) -> Option<Vec<(String, String)>> {
+    let target = request.lines().next()?.strip_prefix("GET ")?.split(' ').next()?;
+    let url = reqwest::Url::parse(&format!("http://localhost{}", target)).ok()?;
+    if url.path() != "/callback" {
+        return None;
+    }
+    Some(url.query_pairs().into_owned().collect())
+}
+
 pub async fn logout() -> Result<()> {
     let mut settings = SettingsManager::load()?;
 
@@ -101,6 +231,7 @@ pub async fn logout() -> Result<()> {
     // Clear local tokens and credentials
     settings.access_token = None;
     settings.refresh_token = None;
+    settings.token_expires_at = None;
     settings.user_id = None;
     settings.email = None;
     settings.password = None;
@@ -110,7 +241,38 @@ pub async fn logout() -> Result<()> {
     Ok(())
 }
 
-pub async fn whoami() -> Result<()> {
+/// Turn OS keychain storage for tokens and profile API keys on or off
+pub async fn keyring(enable: bool) -> Result<()> {
+    let mut settings = SettingsManager::load()?;
+
+    if enable {
+        if let Err(e) = keychain::probe() {
+            anyhow::bail!("The OS keychain is unavailable: {}", e);
+        }
+        settings.use_keyring = true;
+        SettingsManager::save(&settings)?;
+        println!(
+            "{}",
+            "✅ Tokens and API keys are now kept in the OS keychain".green()
+        );
+    } else {
+        if !settings.use_keyring {
+            println!("{}", "⚠️  Keychain storage is not enabled".yellow());
+            return Ok(());
+        }
+        settings.use_keyring = false;
+        SettingsManager::save(&settings)?;
+        keychain::forget(&settings);
+        println!(
+            "{} {}",
+            "✅ Tokens and API keys moved back to".green(),
+            SettingsManager::settings_path()?.display()
+        );
+    }
+    Ok(())
+}
+
+pub async fn whoami(verbose: bool) -> Result<()> {
     let settings = SettingsManager::load()?;
 
     if settings.access_token.is_none() {
@@ -119,7 +281,7 @@ pub async fn whoami() -> Result<()> {
     }
 
     let client = Client::new();
-    let token = settings.access_token.unwrap();
+    let token = client.access_token().await?;
 
     match client.get_user_info(&token).await {
         Ok(user) => {
@@ -136,9 +298,84 @@ pub async fn whoami() -> Result<()> {
         }
     }
 
+    if !verbose {
+        return Ok(());
+    }
+
+    println!();
+    println!("{}", "🔐 Session".blue().bold());
+    println!();
+    println!("   Server:        {}", settings.server_url.dimmed());
+    let expiry = match token_expiry(&token) {
+        Some(expires_at) => format!(
+            "{} ({})",
+            expires_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            describe_expiry(expires_at - Utc::now())
+        ),
+        None => "unknown".to_string(),
+    };
+    println!("   Access token:  {}", expiry);
+    println!(
+        "   Refresh token: {}",
+        if settings.refresh_token.is_some() { "yes" } else { "no" }
+    );
+
+    println!();
+    println!("{}", "🔑 Access Keys".blue().bold());
+    println!();
+    match client.list_access_keys(&token).await {
+        Ok(keys) if keys.is_empty() => println!("   (No access keys)"),
+        Ok(keys) => {
+            for key in keys {
+                println!(
+                    "   {} {} - {} ({})",
+                    if key.is_revoked { "❌" } else { "✓" },
+                    key.name.cyan(),
+                    key.key_prefix,
+                    key.created_at.format("%Y-%m-%d")
+                );
+            }
+        }
+        Err(e) => {
+            println!("{}", format!("⚠️  Failed to list keys: {}", e).yellow());
+        }
+    }
+
     Ok(())
 }
 
+/// Read the `exp` claim of a JWT. The signature is not checked; this is for display only.
+fn token_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
+    DateTime::from_timestamp(claims.get("exp")?.as_i64()?, 0)
+}
+
+/// "expires in 23m 41s" or "expired 5m 0s ago"
+fn describe_expiry(remaining: chrono::Duration) -> String {
+    let secs = remaining.num_seconds();
+    let span = chrono::Duration::seconds(secs.abs());
+    let (hours, minutes, seconds) = (
+        span.num_hours(),
+        span.num_minutes() % 60,
+        span.num_seconds() % 60,
+    );
+    let text = if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else {
+        format!("{}m {}s", minutes, seconds)
+    };
+
+    if secs >= 0 {
+        format!("expires in {}", text)
+    } else {
+        format!("expired {} ago", text)
+    }
+}
+
 pub async fn keys() -> Result<()> {
     let settings = SettingsManager::load()?;
 
@@ -148,7 +385,7 @@ pub async fn keys() -> Result<()> {
     }
 
     let client = Client::new();
-    let token = settings.access_token.unwrap();
+    let token = client.access_token().await?;
 
     match client.list_access_keys(&token).await {
         Ok(keys) => {
@@ -176,3 +413,149 @@ pub async fn keys() -> Result<()> {
 
     Ok(())
 }
+
+pub async fn audit(limit: u32, before: Option<i64>, output: OutputFormat) -> Result<()> {
+    let settings = SettingsManager::load()?;
+    if settings.access_token.is_none() {
+        println!("{}", "⚠️  Not logged in".yellow());
+        return Ok(());
+    }
+
+    let client = Client::new();
+    let token = client.access_token().await?;
+    let page = client.list_audit_entries(&token, before, limit).await?;
+
+    if output == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&page)?);
+        return Ok(());
+    }
+
+    println!("{}", "📜 Audit Log".blue().bold());
+    println!();
+    if page.entries.is_empty() {
+        println!("   (No entries)");
+        return Ok(());
+    }
+
+    let event_width = page
+        .entries
+        .iter()
+        .map(|e| e.event_type.len())
+        .max()
+        .unwrap_or(0)
+        .max("Event".len());
+
+    // Pad before colouring so escape codes don't skew the columns
+    println!(
+        "   {}  {}  {}  {}  {}  {}",
+        format!("{:>6}", "ID").bold(),
+        format!("{:<19}", "Time").bold(),
+        format!("{:<event_width$}", "Event").bold(),
+        format!("{:<8}", "User").bold(),
+        format!("{:<17}", "Resource").bold(),
+        "IP".bold(),
+    );
+    for entry in &page.entries {
+        let event = format!("{:<event_width$}", entry.event_type);
+        let event = if is_failure(entry) { event.red() } else { event.normal() };
+        println!(
+            "   {}  {}  {}  {}  {:<17}  {}",
+            format!("{:>6}", entry.id).dimmed(),
+            entry.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+            event,
+            format!("{:<8}", short(entry.user_id.as_deref())).cyan(),
+            resource_label(entry),
+            entry.ip_address.as_deref().unwrap_or("-"),
+        );
+    }
+
+    if let Some(next) = page.next_before {
+        println!();
+        println!(
+            "   Older entries: {}",
+            format!("happy auth audit --before {}", next).dimmed()
+        );
+    }
+    Ok(())
+}
+
+fn is_failure(entry: &AuditEntry) -> bool {
+    entry.event_type.ends_with("_failure")
+}
+
+/// First 8 characters of an ID, or `-`
+fn short(id: Option<&str>) -> &str {
+    id.map_or("-", |id| id.get(..8).unwrap_or(id))
+}
+
+/// `session:1a2b3c4d`; sign-in events are about the acting user, so they show `-`
+fn resource_label(entry: &AuditEntry) -> String {
+    match (entry.resource_type.as_deref(), entry.resource_id.as_deref()) {
+        (Some("session"), Some(id)) => format!("session:{}", short(Some(id))),
+        _ => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_label() {
+        let entry = AuditEntry {
+            id: 1,
+            timestamp: Utc::now(),
+            user_id: Some("0f1e2d3c-4b5a".into()),
+            event_type: "session_delete".into(),
+            resource_type: Some("session".into()),
+            resource_id: Some("1a2b3c4d-5e6f".into()),
+            ip_address: None,
+            metadata: None,
+        };
+        assert_eq!(resource_label(&entry), "session:1a2b3c4d");
+        assert_eq!(short(entry.user_id.as_deref()), "0f1e2d3c");
+        assert_eq!(short(Some("u1")), "u1");
+        assert!(!is_failure(&entry));
+
+        let login = AuditEntry {
+            event_type: "login_failure".into(),
+            resource_type: Some("user".into()),
+            ..entry
+        };
+        assert_eq!(resource_label(&login), "-");
+        assert!(is_failure(&login));
+    }
+
+    #[test]
+    fn test_token_expiry() {
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(r#"{"sub":"u1","exp":1700000000}"#);
+        let token = format!("header.{}.signature", payload);
+        assert_eq!(token_expiry(&token).unwrap().timestamp(), 1700000000);
+        assert!(token_expiry("not-a-jwt").is_none());
+
+        assert_eq!(
+            describe_expiry(chrono::Duration::seconds(23 * 60 + 41)),
+            "expires in 23m 41s"
+        );
+        assert_eq!(
+            describe_expiry(chrono::Duration::seconds(-3_661)),
+            "expired 1h 1m 1s ago"
+        );
+    }
+
+    #[test]
+    fn test_callback_params() {
+        let request = "GET /callback?access_token=a%2Bb&email=a%40corp.example.com HTTP/1.1\r\n\
+                       Host: localhost:16793\r\n\r\n";
+        assert_eq!(
+            callback_params(request).unwrap(),
+            vec![
+                ("access_token".to_string(), "a+b".to_string()),
+                ("email".to_string(), "a@corp.example.com".to_string()),
+            ]
+        );
+        assert!(callback_params("GET /favicon.ico HTTP/1.1\r\n\r\n").is_none());
+        assert!(callback_params("POST /callback HTTP/1.1\r\n\r\n").is_none());
+    }
+}
@@ -1,22 +1,42 @@
 //! Build command - Build for all configured platforms
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use anyhow::Result;
 use colored::Colorize;
-use happy_core::{Builder, BuildOptions, ConfigManager, Platform};
+use happy_core::{
+    watcher::{is_config_file, WatchEvent, Watcher},
+    BuildContext, BuildEvent, BuildOptions, BuildSummary, Builder, ConfigManager, Platform,
+    ProjectConfig,
+};
 use happy_adapters::create_adapter_factory;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 
-pub async fn run(target: Option<String>, watch: bool, clean: bool) -> Result<()> {
-    println!("{}", "🔨 Building Happy Coding project...".cyan().bold());
+use crate::config::SettingsManager;
+use crate::OutputFormat;
+
+pub async fn run(
+    target: Option<String>,
+    watch: bool,
+    clean: bool,
+    release: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let human = output != OutputFormat::Json;
+    if human {
+        println!("{}", "🔨 Building Happy Coding project...".cyan().bold());
+    }
 
     let project_dir = std::env::current_dir()?;
-    
+
     // Load configuration
     let mut config_manager = ConfigManager::new();
     let (config, config_path) = config_manager.load_from_directory(&project_dir)
         .map_err(|e| anyhow::anyhow!("Failed to load configuration: {}", e))?;
 
-    println!("  📁 Using config: {}", config_path.display().to_string().dimmed());
+    if human {
+        println!("  📁 Using config: {}", config_path.display().to_string().dimmed());
+    }
 
     // Parse target platform
     let target_platform = if let Some(ref t) = target {
@@ -24,6 +44,8 @@ pub async fn run(target: Option<String>, watch: bool, clean: bool) -> Result<()>
             "claude" => Platform::Claude,
             "codex" => Platform::Codex,
             "antigravity" => Platform::Antigravity,
+            "ollama" => Platform::Ollama,
+            "openai" => Platform::OpenAI,
             _ => return Err(anyhow::anyhow!("Unknown platform: {}", t)),
         })
     } else {
@@ -39,31 +61,181 @@ pub async fn run(target: Option<String>, watch: bool, clean: bool) -> Result<()>
         target: target_platform,
         watch,
         clean,
+        release,
+    };
+
+    // Progress is only drawn for humans; JSON consumers get the summary alone
+    let progress = if human {
+        ProgressBar::new_spinner()
+    } else {
+        ProgressBar::hidden()
     };
 
     // Run build
-    let summary = builder.build(&config, &project_dir, &options).await
-        .map_err(|e| anyhow::anyhow!("Build failed: {}", e))?;
+    let ctx = build_context(&project_dir, target_platform)?;
+    let summary = build_once(&builder, &ctx, &config, &options, &progress).await?;
+    report(&builder, &summary, output)?;
+
+    if !summary.success {
+        return Err(anyhow::anyhow!("Build failed"));
+    }
+
+    // Watch mode
+    if watch {
+        if human {
+            println!();
+            println!("{}", "👀 Watching for changes... (Ctrl+C to stop)".yellow());
+        }
+
+        let mut watcher = Watcher::new();
+        watcher.watch(&project_dir)
+            .map_err(|e| anyhow::anyhow!("Failed to start watcher: {}", e))?;
+
+        // Rebuilds write their own outputs, so only the initial build cleans
+        let options = BuildOptions { clean: false, ..options };
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => break,
+                batch = watcher.next_batch() => {
+                    let changed: Vec<PathBuf> = batch
+                        .into_iter()
+                        .filter_map(|event| match event {
+                            WatchEvent::Changed(path) | WatchEvent::Created(path) => Some(path),
+                            _ => None,
+                        })
+                        .filter(|path| !is_build_output(path))
+                        .collect();
+                    if changed.is_empty() {
+                        continue;
+                    }
+                    for path in &changed {
+                        if human {
+                            println!();
+                            println!("{} {}", "📝 Changed:".yellow(), path.display());
+                        }
+                        if is_config_file(path) {
+                            config_manager.clear_cache();
+                        }
+                    }
+
+                    let config = match config_manager.load_from_directory(&project_dir) {
+                        Ok((config, _)) => config,
+                        Err(e) => {
+                            eprintln!("{} {}", "❌ Config error:".red(), e);
+                            continue;
+                        }
+                    };
+
+                    // Settings or `.happy/project.toml` may have changed too
+                    let ctx = match build_context(&project_dir, target_platform) {
+                        Ok(ctx) => ctx,
+                        Err(e) => {
+                            eprintln!("{} {}", "❌ Config error:".red(), e);
+                            continue;
+                        }
+                    };
+
+                    progress.reset();
+                    match build_once(&builder, &ctx, &config, &options, &progress).await {
+                        Ok(summary) => report(&builder, &summary, output)?,
+                        Err(e) => eprintln!("{} {}", "❌ Rebuild failed:".red(), e),
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Run one build, spinning until the first step arrives and counting steps after that
+async fn build_once(
+    builder: &Builder,
+    ctx: &BuildContext,
+    config: &ProjectConfig,
+    options: &BuildOptions,
+    progress: &ProgressBar,
+) -> Result<BuildSummary> {
+    progress.set_style(
+        ProgressStyle::with_template("{spinner:.cyan} {msg} [{elapsed}]")
+            .expect("valid progress template"),
+    );
+    progress.unset_length();
+    progress.set_message("Building...");
+    progress.enable_steady_tick(Duration::from_millis(100));
+
+    let result = builder
+        .build_with_progress(ctx, config, options, |event| match event {
+            BuildEvent::Step { name, total, current } => {
+                if progress.length() != Some(total as u64) {
+                    progress.set_style(
+                        ProgressStyle::with_template(
+                            "{spinner:.cyan} [{pos}/{len}] {wide_bar:.cyan/blue} {msg} [{elapsed}]",
+                        )
+                        .expect("valid progress template")
+                        .progress_chars("=> "),
+                    );
+                    progress.set_length(total as u64);
+                }
+                // `current` is the step that just started, so the bar shows completed steps
+                progress.set_position(current.saturating_sub(1) as u64);
+                progress.set_message(format!("Building {}", name));
+            }
+        })
+        .await;
+
+    progress.finish_and_clear();
+    result.map_err(|e| anyhow::anyhow!("Build failed: {}", e))
+}
+
+/// Build context for `project_dir` with the user's settings and resolved environment.
+///
+/// The builder retargets it per platform, so building everything starts from Claude.
+pub(crate) fn build_context(project_dir: &Path, target: Option<Platform>) -> Result<BuildContext> {
+    let settings = SettingsManager::load()?;
+    BuildContext::new(
+        settings,
+        project_dir.to_path_buf(),
+        target.unwrap_or(Platform::Claude),
+    )
+    .map_err(|e| anyhow::anyhow!("Failed to resolve build environment: {}", e))
+}
+
+fn report(builder: &Builder, summary: &BuildSummary, output: OutputFormat) -> Result<()> {
+    if output == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(summary)?);
+        return Ok(());
+    }
 
     // Print summary
-    println!("{}", builder.format_summary(&summary));
+    println!("{}", builder.format_summary(summary));
 
     if summary.success {
         println!("{}", "✅ Build completed successfully!".green().bold());
-        
+
         for result in &summary.results {
-            println!("  {} {} → {}", 
+            println!("  {} {} → {} ({})",
                 "📦".green(),
                 result.platform.as_str().cyan(),
-                result.output_path.dimmed()
+                result.output_path.dimmed(),
+                HumanBytes(dir_size(Path::new(&result.output_path)))
             );
+            if result.saved_bytes > 0 {
+                println!("      {}", format!(
+                    "minified {} → {} (saved {})",
+                    HumanBytes(result.size_bytes + result.saved_bytes),
+                    HumanBytes(result.size_bytes),
+                    HumanBytes(result.saved_bytes)
+                ).dimmed());
+            }
             for file in &result.files {
                 println!("      {}", file.dimmed());
             }
         }
     } else {
         println!("{}", "❌ Build failed!".red().bold());
-        
+
         for result in &summary.results {
             if !result.success {
                 println!("  {} {}:", "❌".red(), result.platform.as_str().red());
@@ -72,18 +244,35 @@ pub async fn run(target: Option<String>, watch: bool, clean: bool) -> Result<()>
                 }
             }
         }
-
-        return Err(anyhow::anyhow!("Build failed"));
-    }
-
-    // Watch mode
-    if watch {
-        println!();
-        println!("{}", "👀 Watching for changes... (Ctrl+C to stop)".yellow());
-        
-        // TODO: Implement watch mode using watcher
-        tokio::signal::ctrl_c().await?;
     }
 
     Ok(())
 }
+
+/// Generated platform directories; changes there come from the build itself
+fn is_build_output(path: &Path) -> bool {
+    let path_str = path.display().to_string();
+    path_str.contains(".claude/")
+        || path_str.contains(".codex/")
+        || path_str.contains(".agent/")
+        || path_str.contains(".idx/")
+}
+
+/// Total size of all files under `path`
+fn dir_size(path: &Path) -> u64 {
+    let mut total = 0;
+    let mut pending: Vec<PathBuf> = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            match entry.metadata() {
+                Ok(meta) if meta.is_dir() => pending.push(entry.path()),
+                Ok(meta) => total += meta.len(),
+                Err(_) => {}
+            }
+        }
+    }
+    total
+}
@@ -0,0 +1,34 @@
+//! Shell completion scripts
+
+use anyhow::Result;
+use clap::builder::PossibleValuesParser;
+use clap::Command;
+use clap_complete::Shell;
+
+/// Write the completion script for `shell` to stdout, ready to `eval` or source
+pub fn execute(shell: Shell, cmd: Command) -> Result<()> {
+    let mut cmd = with_agent_hint(cmd);
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+    Ok(())
+}
+
+/// Offer the registered adapters for `happy run <agent>`.
+///
+/// Only the command used to generate the script gets the hint; parsing still
+/// accepts any agent name.
+fn with_agent_hint(cmd: Command) -> Command {
+    let agents: Vec<&'static str> = happy_adapters::create_adapter_factory()
+        .registered_platforms()
+        .iter()
+        .map(|platform| platform.as_str())
+        .collect();
+
+    // `mut_arg` would move `agent` behind the trailing `args` positional
+    cmd.mut_subcommand("run", |run| {
+        run.mut_args(|arg| match arg.get_id().as_str() {
+            "agent" => arg.value_parser(PossibleValuesParser::new(&agents)),
+            _ => arg,
+        })
+    })
+}
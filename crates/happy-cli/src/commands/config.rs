@@ -3,9 +3,26 @@
 use crate::config::SettingsManager;
 use anyhow::{Context, Result};
 use colored::Colorize;
+use happy_core::{AIProfile, Settings};
+use serde_json::Value;
+use std::path::Path;
+
+/// Written in place of secrets by `config export`
+const REDACTED: &str = "<redacted>";
 
 /// Set the remote server URL
 pub async fn set_server(url: &str) -> Result<()> {
+    let settings = save_server_url(url)?;
+
+    println!("{} Server URL set to: {}", "✓".green(), settings.webapp_url.cyan());
+    println!("  API endpoint: {}", settings.server_url.dimmed());
+    println!("  Web app: {}", settings.webapp_url.dimmed());
+
+    Ok(())
+}
+
+/// Validate and persist a new server URL, shared with the daemon's remote config updates
+pub fn save_server_url(url: &str) -> Result<Settings> {
     let mut settings = SettingsManager::load().context("Failed to load settings")?;
 
     // Validate URL format
@@ -22,11 +39,7 @@ pub async fn set_server(url: &str) -> Result<()> {
 
     SettingsManager::save(&settings).context("Failed to save settings")?;
 
-    println!("{} Server URL set to: {}", "✓".green(), url.cyan());
-    println!("  API endpoint: {}", settings.server_url.dimmed());
-    println!("  Web app: {}", settings.webapp_url.dimmed());
-
-    Ok(())
+    Ok(settings)
 }
 
 /// Set the daemon WebSocket port
@@ -110,7 +123,7 @@ pub async fn show() -> Result<()> {
             println!(
                 "  {} ({}){}",
                 profile.name.cyan(),
-                format!("{:?}", profile.provider).to_lowercase(),
+                profile.provider,
                 active
             );
         }
@@ -177,6 +190,178 @@ pub async fn reset() -> Result<()> {
     Ok(())
 }
 
+/// Write settings to `output` as TOML, safe to commit as a team template
+pub async fn export(output: &Path) -> Result<()> {
+    let settings = SettingsManager::load().context("Failed to load settings")?;
+    let content =
+        toml::to_string_pretty(&redacted(settings)).context("Failed to serialize settings")?;
+    tokio::fs::write(output, content)
+        .await
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    println!(
+        "{} Settings exported to: {}",
+        "✓".green(),
+        output.display().to_string().cyan()
+    );
+    println!("{}", "  API keys and login tokens were redacted.".dimmed());
+
+    Ok(())
+}
+
+/// Load settings from a TOML file, replacing or merging into the current ones
+pub async fn import(input: &Path, merge: bool) -> Result<()> {
+    let content = tokio::fs::read_to_string(input)
+        .await
+        .with_context(|| format!("Failed to read {}", input.display()))?;
+    let imported: Settings = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse settings from {}", input.display()))?;
+    let local = SettingsManager::load().context("Failed to load settings")?;
+
+    let settings = if merge {
+        merge_settings(local, imported)?
+    } else {
+        replace_settings(local, imported)
+    };
+    SettingsManager::save(&settings).context("Failed to save settings")?;
+
+    println!(
+        "{} Settings {} from: {}",
+        "✓".green(),
+        if merge { "merged" } else { "imported" },
+        input.display().to_string().cyan()
+    );
+    let keyless: Vec<&str> = settings
+        .profiles
+        .iter()
+        .filter(|p| p.api_key.is_none())
+        .map(|p| p.name.as_str())
+        .collect();
+    if !keyless.is_empty() {
+        println!(
+            "  {} {}",
+            "Profiles without an API key:".yellow(),
+            keyless.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// `settings` without login state, machines or secrets
+fn redacted(mut settings: Settings) -> Settings {
+    settings.user_id = None;
+    settings.email = None;
+    settings.password = None;
+    settings.access_token = None;
+    settings.refresh_token = None;
+    settings.token_expires_at = None;
+    settings.machines.clear();
+    for profile in &mut settings.profiles {
+        if profile.api_key.is_some() {
+            profile.api_key = Some(REDACTED.to_string());
+        }
+        for (name, value) in profile.env_vars.iter_mut() {
+            if is_secret_var(name) {
+                *value = REDACTED.to_string();
+            }
+        }
+    }
+    settings
+}
+
+fn is_secret_var(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    ["KEY", "TOKEN", "SECRET", "PASSWORD"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+/// Whether an imported value stands in for a secret rather than being one
+fn is_unset(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty() || s == REDACTED,
+        _ => false,
+    }
+}
+
+/// Drop redacted and empty secrets so they are never saved as real values
+fn without_placeholders(mut profile: AIProfile) -> AIProfile {
+    if profile
+        .api_key
+        .as_deref()
+        .is_some_and(|key| key.is_empty() || key == REDACTED)
+    {
+        profile.api_key = None;
+    }
+    profile.env_vars.retain(|_, value| value != REDACTED);
+    profile
+}
+
+/// `imported` in place of `local`, keeping this machine's login, machines and keyring choice
+fn replace_settings(local: Settings, mut imported: Settings) -> Settings {
+    imported.profiles = imported
+        .profiles
+        .into_iter()
+        .map(without_placeholders)
+        .collect();
+    Settings {
+        user_id: local.user_id,
+        email: local.email,
+        password: local.password,
+        access_token: local.access_token,
+        refresh_token: local.refresh_token,
+        token_expires_at: local.token_expires_at,
+        machines: local.machines,
+        use_keyring: local.use_keyring,
+        machine_id: local.machine_id,
+        ..imported
+    }
+}
+
+/// `imported` layered over `local`; profiles match by name and keep local secrets
+/// wherever the import has none
+fn merge_settings(mut local: Settings, imported: Settings) -> Result<Settings> {
+    local.server_url = imported.server_url;
+    local.webapp_url = imported.webapp_url;
+    if imported.active_profile.is_some() {
+        local.active_profile = imported.active_profile;
+    }
+
+    for profile in imported.profiles {
+        match local.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => {
+                let mut merged = serde_json::to_value(&*existing)?;
+                deep_merge(&mut merged, serde_json::to_value(profile)?);
+                *existing = without_placeholders(serde_json::from_value(merged)?);
+            }
+            None => local.profiles.push(without_placeholders(profile)),
+        }
+    }
+
+    Ok(local)
+}
+
+/// Overlay `overlay` onto `base`, recursing into objects; unset values leave `base` alone
+fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(slot) => deep_merge(slot, value),
+                    None if !is_unset(&value) => {
+                        base.insert(key, value);
+                    }
+                    None => {}
+                }
+            }
+        }
+        (base, overlay) if !is_unset(&overlay) => *base = overlay,
+        _ => {}
+    }
+}
+
 /// Get the configured daemon port (default: 16790)
 pub async fn get_daemon_port() -> u16 {
     if let Ok(happy_home) = SettingsManager::happy_home() {
@@ -192,3 +377,57 @@ pub async fn get_daemon_port() -> u16 {
     // Default port (uncommon port to avoid conflicts)
     16790
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use happy_core::AIProvider;
+
+    fn profile(name: &str, api_key: Option<&str>) -> AIProfile {
+        AIProfile {
+            api_key: api_key.map(str::to_string),
+            env_vars: [("ANTHROPIC_AUTH_TOKEN".to_string(), "sk-env".to_string())].into(),
+            ..AIProfile::new(name, AIProvider::Anthropic)
+        }
+    }
+
+    fn local_settings() -> Settings {
+        Settings {
+            access_token: Some("access".to_string()),
+            password: Some("hunter2".to_string()),
+            profiles: vec![profile("work", Some("sk-local"))],
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn test_export_redacts_secrets() {
+        let exported = toml::to_string_pretty(&redacted(local_settings())).unwrap();
+        assert!(!exported.contains("sk-local"));
+        assert!(!exported.contains("sk-env"));
+        assert!(!exported.contains("hunter2"));
+        assert!(!exported.contains("access"));
+
+        let parsed: Settings = toml::from_str(&exported).unwrap();
+        assert_eq!(parsed.profiles[0].api_key.as_deref(), Some(REDACTED));
+    }
+
+    #[test]
+    fn test_import_keeps_local_secrets() {
+        let mut template = redacted(local_settings());
+        template.profiles[0].model = Some("claude-sonnet".to_string());
+        template.profiles.push(profile("team", Some(REDACTED)));
+
+        let merged = merge_settings(local_settings(), template.clone()).unwrap();
+        assert_eq!(merged.profiles[0].api_key.as_deref(), Some("sk-local"));
+        assert_eq!(merged.profiles[0].env_vars["ANTHROPIC_AUTH_TOKEN"], "sk-env");
+        assert_eq!(merged.profiles[0].model.as_deref(), Some("claude-sonnet"));
+        assert_eq!(merged.profiles[1].api_key, None);
+        assert_eq!(merged.access_token.as_deref(), Some("access"));
+
+        let replaced = replace_settings(local_settings(), template);
+        assert_eq!(replaced.profiles[0].api_key, None);
+        assert!(replaced.profiles[0].env_vars.is_empty());
+        assert_eq!(replaced.access_token.as_deref(), Some("access"));
+    }
+}
@@ -1,62 +1,146 @@
 //! Connect to AI vendors
 
 use crate::config::SettingsManager;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::Colorize;
+use happy_adapters::{GenericOpenAIAdapter, OllamaAdapter, OllamaError, OpenAIError};
 use happy_core::{AIProfile, AIProvider};
 
-pub async fn execute(vendor: &str) -> Result<()> {
+pub(crate) const ANTHROPIC_API_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Where `ollama serve` listens unless told otherwise
+pub(crate) const OLLAMA_DEFAULT_URL: &str = happy_adapters::OLLAMA_DEFAULT_URL;
+
+/// Outcome of checking an API key against the vendor's models endpoint
+enum KeyValidation {
+    Valid(Vec<String>),
+    Invalid,
+}
+
+pub async fn execute(
+    vendor: &str,
+    model: Option<String>,
+    skip_validation: bool,
+    url: Option<String>,
+    custom_name: Option<String>,
+) -> Result<()> {
     println!("{}", format!("🔹 Connect to {}", vendor).blue().bold());
     println!();
 
-    let provider = match vendor.to_lowercase().as_str() {
+    let vendor = vendor.to_lowercase();
+    if vendor != "custom" && (url.is_some() || custom_name.is_some()) {
+        anyhow::bail!("--url and --name only apply to `happy connect custom`");
+    }
+    let provider = match vendor.as_str() {
         "anthropic" | "claude" => AIProvider::Anthropic,
         "openai" => AIProvider::OpenAI,
         "azure" => AIProvider::Azure,
         "gemini" => AIProvider::Gemini,
+        "ollama" => AIProvider::Ollama,
+        "custom" => AIProvider::Custom(match custom_name {
+            Some(name) => name,
+            None => dialoguer::Input::new()
+                .with_prompt("Provider name")
+                .default("custom".to_string())
+                .interact_text()?,
+        }),
         _ => anyhow::bail!(
-            "Unknown vendor: {}. Supported: anthropic, openai, azure, gemini",
+            "Unknown vendor: {}. Supported: anthropic, openai, azure, gemini, ollama, custom",
             vendor
         ),
     };
+    let local = matches!(provider, AIProvider::Ollama);
+    let custom = matches!(provider, AIProvider::Custom(_));
 
     // Get profile name
     let name: String = dialoguer::Input::new()
         .with_prompt("Profile name")
-        .default(format!("{}-default", vendor.to_lowercase()))
+        .default(match &provider {
+            AIProvider::Custom(custom_name) => format!("{}-default", custom_name),
+            _ => format!("{}-default", vendor),
+        })
         .interact_text()?;
 
-    // Get API key
-    let api_key: String = dialoguer::Password::new()
-        .with_prompt("API Key")
-        .interact()?;
+    // Get API key; local models don't have one, and self-hosted servers may not
+    let mut api_key = if local {
+        String::new()
+    } else if custom {
+        dialoguer::Password::new()
+            .with_prompt("API Key (optional)")
+            .allow_empty_password(true)
+            .interact()?
+    } else {
+        prompt_api_key()?
+    };
 
-    // Get optional base URL
-    let base_url: String = dialoguer::Input::new()
-        .with_prompt("Base URL (optional)")
-        .allow_empty(true)
-        .interact_text()?;
+    // Get base URL; optional except for local and self-hosted servers
+    let base_url: String = if let Some(url) = url {
+        url
+    } else if custom {
+        dialoguer::Input::new()
+            .with_prompt("Server URL (e.g. http://localhost:8000/v1)")
+            .interact_text()?
+    } else if local {
+        dialoguer::Input::new()
+            .with_prompt("Ollama URL")
+            .default(OLLAMA_DEFAULT_URL.to_string())
+            .interact_text()?
+    } else {
+        dialoguer::Input::new()
+            .with_prompt("Base URL (optional)")
+            .allow_empty(true)
+            .interact_text()?
+    };
 
-    // Get optional model
-    let model: String = dialoguer::Input::new()
-        .with_prompt("Model (optional)")
-        .allow_empty(true)
-        .interact_text()?;
+    // Validate the key and pick a model from the ones it can access. An explicit
+    // --model means the caller is running non-interactively, so trust it as-is.
+    let validate = matches!(provider, AIProvider::Anthropic) && !skip_validation && model.is_none();
+    let model: String = if let Some(model) = model {
+        model
+    } else if local && !skip_validation {
+        println!("{}", "Listing local models...".dimmed());
+        select_model(&list_ollama_models(&base_url).await?)?
+    } else if custom && !skip_validation {
+        println!("{}", "Listing server models...".dimmed());
+        select_model(&list_custom_models(&base_url, &api_key).await?)?
+    } else if validate {
+        loop {
+            println!("{}", "Validating API key...".dimmed());
+            match validate_anthropic_key(&api_key, &base_url).await? {
+                KeyValidation::Valid(models) => {
+                    println!("{}", "✅ API key is valid".green());
+                    break select_model(&models)?;
+                }
+                KeyValidation::Invalid => {
+                    println!("{}", "❌ Invalid API key".red());
+                    api_key = prompt_api_key()?;
+                }
+            }
+        }
+    } else {
+        dialoguer::Input::new()
+            .with_prompt("Model (optional)")
+            .allow_empty(true)
+            .interact_text()?
+    };
 
     // Create profile
     let profile = AIProfile {
-        name: name.clone(),
-        provider,
-        api_key: Some(api_key),
+        api_key: if api_key.is_empty() {
+            None
+        } else {
+            Some(api_key)
+        },
         base_url: if base_url.is_empty() {
             None
         } else {
             Some(base_url)
         },
         model: if model.is_empty() { None } else { Some(model) },
-        default: false,
-        env_vars: std::collections::HashMap::new(),
+        // Generation parameters stay at provider defaults until `happy profile set-param`
+        ..AIProfile::new(name.clone(), provider)
     };
+    profile.validate()?;
 
     // Save profile
     let mut settings = SettingsManager::load()?;
@@ -88,3 +172,113 @@ pub async fn execute(vendor: &str) -> Result<()> {
 
     Ok(())
 }
+
+fn prompt_api_key() -> Result<String> {
+    Ok(dialoguer::Password::new()
+        .with_prompt("API Key")
+        .interact()?)
+}
+
+/// Check an Anthropic API key by listing the models it can access
+async fn validate_anthropic_key(api_key: &str, base_url: &str) -> Result<KeyValidation> {
+    let base = if base_url.is_empty() {
+        ANTHROPIC_API_URL
+    } else {
+        base_url.trim_end_matches('/')
+    };
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/v1/models", base))
+        .header("X-Api-Key", api_key)
+        .header("anthropic-version", ANTHROPIC_VERSION)
+        .send()
+        .await
+        .context("Failed to reach the Anthropic API (use --skip-validation when offline)")?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return Ok(KeyValidation::Invalid);
+    }
+    if !status.is_success() {
+        anyhow::bail!("API key validation failed with status {}", status);
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .context("Failed to parse models response")?;
+    let models = body["data"]
+        .as_array()
+        .map(|data| {
+            data.iter()
+                .filter_map(|m| m["id"].as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(KeyValidation::Valid(models))
+}
+
+/// Models pulled into the Ollama server at `base_url`
+async fn list_ollama_models(base_url: &str) -> Result<Vec<String>> {
+    OllamaAdapter::with_base_url(base_url)
+        .list_models()
+        .await
+        .map_err(|e| match e {
+            OllamaError::Offline(_) => anyhow::anyhow!("{} (or use --skip-validation)", e),
+            e => anyhow::Error::new(e).context("Failed to list Ollama models"),
+        })
+}
+
+/// Models served by the OpenAI-compatible server at `base_url`
+async fn list_custom_models(base_url: &str, api_key: &str) -> Result<Vec<String>> {
+    let mut adapter = GenericOpenAIAdapter::with_base_url(base_url);
+    if !api_key.is_empty() {
+        adapter = adapter.with_api_key(api_key);
+    }
+    adapter.list_models().await.map_err(|e| match e {
+        OpenAIError::Offline(_) => anyhow::anyhow!("{} (or use --skip-validation)", e),
+        e => anyhow::Error::new(e).context("Failed to list server models"),
+    })
+}
+
+/// Show the available models and let the user confirm one
+fn select_model(models: &[String]) -> Result<String> {
+    if models.is_empty() {
+        println!("{}", "⚠️  No models available".yellow());
+        return Ok(String::new());
+    }
+
+    println!("{}", "Available models:".green());
+    for model in models {
+        println!("   • {}", model);
+    }
+    println!();
+
+    let selection = dialoguer::Select::new()
+        .with_prompt("Select model")
+        .items(models)
+        .default(0)
+        .interact()?;
+
+    Ok(models[selection].clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ollama_offline() {
+        // Nothing listens on a port that was just released
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let err = list_ollama_models(&format!("http://127.0.0.1:{}/", port))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("ollama serve"), "{}", err);
+    }
+}
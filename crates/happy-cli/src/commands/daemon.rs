@@ -174,6 +174,9 @@ pub async fn run() -> Result<()> {
     // 5. Run servers
     println!("DEBUG: Starting servers...");
 
+    // Count token usage of profile sessions and enforce their daily budgets
+    tokio::spawn(session_manager.multiplexer().budget().run());
+
     // Spawn session recovery in background
     let session_manager_clone = session_manager.clone();
     tokio::spawn(async move {
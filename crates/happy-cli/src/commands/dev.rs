@@ -2,10 +2,10 @@
 
 use anyhow::Result;
 use colored::Colorize;
-use happy_core::{Builder, BuildOptions, ConfigManager, Platform, watcher::{Watcher, WatchEvent, is_config_file}};
+use happy_core::{Builder, BuildOptions, ConfigManager, Platform, watcher::{Watcher, WatcherConfig, WatchEvent, is_config_file}};
 use happy_adapters::create_adapter_factory;
 
-pub async fn run(target: Option<String>) -> Result<()> {
+pub async fn run(target: Option<String>, debounce: Option<u64>, exclude: Vec<String>) -> Result<()> {
     println!("{}", "🔧 Starting development mode...".cyan().bold());
 
     let project_dir = std::env::current_dir()?;
@@ -23,6 +23,8 @@ pub async fn run(target: Option<String>) -> Result<()> {
             "claude" => Platform::Claude,
             "codex" => Platform::Codex,
             "antigravity" => Platform::Antigravity,
+            "ollama" => Platform::Ollama,
+            "openai" => Platform::OpenAI,
             _ => return Err(anyhow::anyhow!("Unknown platform: {}", t)),
         })
     } else {
@@ -37,10 +39,12 @@ pub async fn run(target: Option<String>) -> Result<()> {
         target: target_platform,
         watch: false,
         clean: false,
+        release: false,
     };
 
     println!("{}", "📦 Running initial build...".yellow());
-    let summary = builder.build(&config, &project_dir, &options).await
+    let ctx = super::build::build_context(&project_dir, target_platform)?;
+    let summary = builder.build(&ctx, &config, &options).await
         .map_err(|e| anyhow::anyhow!("Build failed: {}", e))?;
 
     if summary.success {
@@ -53,7 +57,13 @@ pub async fn run(target: Option<String>) -> Result<()> {
     println!();
     println!("{}", "👀 Watching for changes... (Ctrl+C to stop)".cyan().bold());
 
-    let mut watcher = Watcher::new().with_debounce(500);
+    let mut watcher_config = WatcherConfig::default();
+    if let Some(ms) = debounce {
+        watcher_config.debounce_ms = ms;
+    }
+    watcher_config.exclude_globs.extend(exclude);
+
+    let mut watcher = Watcher::new().with_config(watcher_config);
     watcher.watch(&project_dir)
         .map_err(|e| anyhow::anyhow!("Failed to start watcher: {}", e))?;
 
@@ -64,8 +74,10 @@ pub async fn run(target: Option<String>) -> Result<()> {
                 println!("{}", "👋 Stopping development mode...".yellow());
                 break;
             }
-            _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
-                if let Some(event) = watcher.try_next_event() {
+            batch = watcher.next_batch() => {
+                // One rebuild per burst of changes
+                let mut rebuild = false;
+                for event in batch {
                     match event {
                         WatchEvent::Changed(path) | WatchEvent::Created(path) => {
                             // Skip build outputs
@@ -84,30 +96,7 @@ pub async fn run(target: Option<String>) -> Result<()> {
                             if is_config_file(&path) {
                                 config_manager.clear_cache();
                             }
-
-                            // Rebuild
-                            let adapter_factory = create_adapter_factory();
-                            let builder = Builder::new(adapter_factory);
-                            
-                            match config_manager.load_from_directory(&project_dir) {
-                                Ok((new_config, _)) => {
-                                    match builder.build(&new_config, &project_dir, &options).await {
-                                        Ok(summary) => {
-                                            if summary.success {
-                                                println!("{}", "✅ Rebuild completed!".green());
-                                            } else {
-                                                println!("{}", "⚠️ Rebuild had errors".yellow());
-                                            }
-                                        }
-                                        Err(e) => {
-                                            println!("{} {}", "❌ Rebuild failed:".red(), e);
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    println!("{} {}", "❌ Config error:".red(), e);
-                                }
-                            }
+                            rebuild = true;
                         }
                         WatchEvent::Removed(path) => {
                             println!("{} {}", "🗑️ Removed:".yellow(), path.display());
@@ -117,6 +106,40 @@ pub async fn run(target: Option<String>) -> Result<()> {
                         }
                     }
                 }
+                if !rebuild {
+                    continue;
+                }
+
+                // Rebuild
+                let adapter_factory = create_adapter_factory();
+                let builder = Builder::new(adapter_factory);
+                
+                let ctx = match super::build::build_context(&project_dir, target_platform) {
+                    Ok(ctx) => ctx,
+                    Err(e) => {
+                        println!("{} {}", "❌ Config error:".red(), e);
+                        continue;
+                    }
+                };
+                match config_manager.load_from_directory(&project_dir) {
+                    Ok((new_config, _)) => {
+                        match builder.build(&ctx, &new_config, &options).await {
+                            Ok(summary) => {
+                                if summary.success {
+                                    println!("{}", "✅ Rebuild completed!".green());
+                                } else {
+                                    println!("{}", "⚠️ Rebuild had errors".yellow());
+                                }
+                            }
+                            Err(e) => {
+                                println!("{} {}", "❌ Rebuild failed:".red(), e);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        println!("{} {}", "❌ Config error:".red(), e);
+                    }
+                }
             }
         }
     }
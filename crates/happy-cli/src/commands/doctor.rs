@@ -1,21 +1,82 @@
 //! Doctor command - Diagnostics
 
+use super::connect::{ANTHROPIC_API_URL, OLLAMA_DEFAULT_URL};
+use crate::config::{keychain, SettingsManager};
 use anyhow::Result;
 use colored::Colorize;
+use happy_core::{AIProfile, AIProvider};
+use std::time::Duration;
 
+/// Port the daemon's RPC server listens on
+const RPC_PORT: u16 = 16792;
+
+/// How long a provider endpoint has to answer before it counts as unreachable
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of one diagnostic check
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: String,
+    pub passed: bool,
+    pub message: String,
+    /// What to do about a failure
+    pub fix_hint: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            passed: true,
+            message: message.into(),
+            fix_hint: None,
+        }
+    }
+
+    fn fail(
+        name: impl Into<String>,
+        message: impl Into<String>,
+        fix_hint: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            passed: false,
+            message: message.into(),
+            fix_hint: Some(fix_hint.into()),
+        }
+    }
+
+    fn print(&self) {
+        if self.passed {
+            println!("   {} {} - {}", "✓".green(), self.name, self.message.dimmed());
+        } else {
+            println!("   {} {} - {}", "✗".red(), self.name, self.message.red());
+            if let Some(hint) = &self.fix_hint {
+                println!("      Fix: {}", hint.dimmed());
+            }
+        }
+    }
+}
+
+/// Run every check; fails if any check does, so CI pipelines can gate on it
 pub async fn execute() -> Result<()> {
     println!("{}", "🔍 Happy Remote Diagnostics".blue().bold());
     println!();
 
+    let mut results = Vec::new();
+
     // Check OS
     println!("{}", "System:".cyan());
     println!("   OS: {} {}", std::env::consts::OS, std::env::consts::ARCH);
+    results.push(report(check_happy_home_writable()));
     println!();
 
     // Check for claude
     println!("{}", "Dependencies:".cyan());
-    check_binary("claude", "Claude Code CLI").await;
-    check_binary("tmux", "Tmux (optional, for session persistence)").await;
+    results.push(report(check_binary("claude", "Claude Code CLI")));
+    // No profile launches Codex yet, so a missing binary shouldn't fail the run
+    print_optional_binary("codex", "Codex CLI (optional)");
+    print_optional_binary("tmux", "Tmux (optional, for session persistence)");
     println!();
 
     // Check daemon
@@ -27,11 +88,15 @@ pub async fn execute() -> Result<()> {
         println!("   {}", "✗ Daemon is not running".red());
         println!("      Run: {}", "happy daemon start".dimmed());
     }
+    let daemon_pid = daemon_pid().await;
+    results.push(report(check_port("RPC port", RPC_PORT, daemon_pid)));
+    let ws_port = super::config::get_daemon_port().await;
+    results.push(report(check_port("WebSocket port", ws_port, daemon_pid)));
     println!();
 
     // Check settings
     println!("{}", "Configuration:".cyan());
-    match crate::config::SettingsManager::load() {
+    match SettingsManager::load() {
         Ok(settings) => {
             if settings.access_token.is_some() {
                 println!("   {}", "✓ Authenticated".green());
@@ -40,25 +105,66 @@ pub async fn execute() -> Result<()> {
                 println!("      Run: {}", "happy auth login".dimmed());
             }
 
+            // Only counts once credentials depend on it
+            let keyring = check_keyring(settings.use_keyring);
+            if settings.use_keyring {
+                results.push(report(keyring));
+            } else {
+                keyring.print();
+            }
+
             if !settings.profiles.is_empty() {
                 println!("   {} {} AI profile(s) configured", "✓".green(), settings.profiles.len());
             } else {
                 println!("   {}", "✗ No AI profiles configured".red());
                 println!("      Run: {}", "happy connect anthropic".dimmed());
             }
+
+            let client = reqwest::Client::builder()
+                .timeout(REACHABILITY_TIMEOUT)
+                .build()?;
+            for profile in &settings.profiles {
+                results.push(report(check_profile_reachable(&client, profile).await));
+            }
         }
         Err(e) => {
-            println!("   {} Failed to load settings: {}", "✗".red(), e);
+            results.push(report(CheckResult::fail(
+                "Settings",
+                format!("Failed to load settings: {}", e),
+                "Fix or remove the settings file, then run `happy config show`",
+            )));
         }
     }
     println!();
 
+    let failed = results.iter().filter(|r| !r.passed).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} checks failed", failed, results.len());
+    }
+
     println!("{}", "Done!".green().bold());
 
     Ok(())
 }
 
-async fn check_binary(name: &str, description: &str) {
+fn report(result: CheckResult) -> CheckResult {
+    result.print();
+    result
+}
+
+fn check_binary(name: &str, description: &str) -> CheckResult {
+    match which::which(name) {
+        Ok(path) => CheckResult::pass(description, path.display().to_string()),
+        Err(_) => CheckResult::fail(
+            description,
+            format!("`{}` not found on PATH", name),
+            format!("Install {} or add its directory to PATH", name),
+        ),
+    }
+}
+
+/// Tools that only enable extras don't count towards the result
+fn print_optional_binary(name: &str, description: &str) {
     match which::which(name) {
         Ok(path) => {
             let path_str = path.display().to_string();
@@ -69,3 +175,224 @@ async fn check_binary(name: &str, description: &str) {
         }
     }
 }
+
+/// Sessions, logs and settings all live under the happy home directory
+fn check_happy_home_writable() -> CheckResult {
+    let name = "Happy home";
+    let home = match SettingsManager::happy_home() {
+        Ok(home) => home,
+        Err(e) => return CheckResult::fail(name, e.to_string(), "Set $HOME"),
+    };
+
+    let probe = home.join(format!(".doctor-{}", std::process::id()));
+    let written = std::fs::create_dir_all(&home).and_then(|_| std::fs::write(&probe, b"ok"));
+    let _ = std::fs::remove_file(&probe);
+    match written {
+        Ok(()) => CheckResult::pass(name, format!("{} is writable", home.display())),
+        Err(e) => CheckResult::fail(
+            name,
+            format!("Cannot write to {}: {}", home.display(), e),
+            format!("Make {} writable by the current user", home.display()),
+        ),
+    }
+}
+
+/// `use_keyring` needs a reachable OS keychain, or credentials stay in settings.json
+fn check_keyring(in_use: bool) -> CheckResult {
+    let name = "OS keychain";
+    match keychain::probe() {
+        Ok(()) if in_use => CheckResult::pass(name, "holds tokens and API keys"),
+        Ok(()) => CheckResult::pass(name, "available, run `happy auth keyring enable` to use it"),
+        Err(e) => CheckResult::fail(
+            name,
+            format!("unavailable: {}", e),
+            "Start a Secret Service provider such as gnome-keyring, \
+             or run `happy auth keyring disable`",
+        ),
+    }
+}
+
+async fn daemon_pid() -> Option<u32> {
+    let pid_path = SettingsManager::pid_path().ok()?;
+    let pid = tokio::fs::read_to_string(pid_path).await.ok()?.trim().parse().ok()?;
+    crate::daemon::process::is_running(pid).then_some(pid)
+}
+
+/// The port must be free, or held by the running daemon itself
+fn check_port(name: &str, port: u16, daemon_pid: Option<u32>) -> CheckResult {
+    let name = format!("{} {}", name, port);
+    let err = match std::net::TcpListener::bind(("127.0.0.1", port)) {
+        Ok(_) => return CheckResult::pass(name, "available"),
+        Err(e) => e,
+    };
+    if err.kind() != std::io::ErrorKind::AddrInUse {
+        let hint = "Check what is restricting the port";
+        return CheckResult::fail(name, format!("cannot bind: {}", err), hint);
+    }
+
+    match listening_pid(port) {
+        Some(pid) if Some(pid) == daemon_pid => {
+            CheckResult::pass(name, format!("in use by the happy daemon (PID {})", pid))
+        }
+        Some(pid) => CheckResult::fail(
+            name,
+            format!("in use by PID {}", pid),
+            format!("Stop process {} or free port {}", pid, port),
+        ),
+        None if daemon_pid.is_some() => {
+            CheckResult::pass(name, "in use, presumably by the happy daemon")
+        }
+        None => CheckResult::fail(
+            name,
+            "in use by another process",
+            format!("Free port {} before starting the daemon", port),
+        ),
+    }
+}
+
+/// PID of the process listening on `port`, if it can be found
+#[cfg(target_os = "linux")]
+fn listening_pid(port: u16) -> Option<u32> {
+    let inodes: Vec<u64> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|table| listening_inodes(&table, port))
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+
+    // A socket's inode shows up as a `socket:[inode]` link under its owner's fds
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = std::fs::read_link(fd.path()) else {
+                continue;
+            };
+            let target = target.to_string_lossy();
+            let inode = target
+                .strip_prefix("socket:[")
+                .and_then(|s| s.strip_suffix(']'))
+                .and_then(|s| s.parse::<u64>().ok());
+            if inode.is_some_and(|inode| inodes.contains(&inode)) {
+                return Some(pid);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn listening_pid(port: u16) -> Option<u32> {
+    let output = std::process::Command::new("lsof")
+        .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN", "-t"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn listening_pid(_port: u16) -> Option<u32> {
+    None
+}
+
+/// Inodes of sockets listening on `port` in a `/proc/net/tcp` style table
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn listening_inodes(table: &str, port: u16) -> Vec<u64> {
+    const TCP_LISTEN: &str = "0A";
+
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let local_port = fields.get(1)?.rsplit(':').next()?;
+            let listening = *fields.get(3)? == TCP_LISTEN;
+            (listening && u16::from_str_radix(local_port, 16).ok()? == port)
+                .then(|| fields.get(9)?.parse().ok())
+                .flatten()
+        })
+        .collect()
+}
+
+/// The endpoint `profile` talks to, or `None` if it has to be configured
+fn profile_endpoint(profile: &AIProfile) -> Option<String> {
+    if let Some(url) = &profile.base_url {
+        return Some(url.clone());
+    }
+    match profile.provider {
+        AIProvider::Anthropic => Some(ANTHROPIC_API_URL.to_string()),
+        AIProvider::OpenAI => Some("https://api.openai.com".to_string()),
+        AIProvider::Gemini => Some("https://generativelanguage.googleapis.com".to_string()),
+        AIProvider::Ollama => Some(OLLAMA_DEFAULT_URL.to_string()),
+        // Every Azure resource has its own endpoint, and custom servers always set one
+        AIProvider::Azure | AIProvider::Custom(_) => None,
+    }
+}
+
+/// Any answer short of a server error means the API can be reached; 401s are
+/// expected since no key is sent
+async fn check_profile_reachable(client: &reqwest::Client, profile: &AIProfile) -> CheckResult {
+    let name = format!("Profile '{}'", profile.name);
+    let Some(url) = profile_endpoint(profile) else {
+        return CheckResult::fail(
+            name,
+            "no base URL configured",
+            format!("Set base_url for profile '{}' in the settings file", profile.name),
+        );
+    };
+
+    match client.head(&url).send().await {
+        Ok(response) if !response.status().is_server_error() => {
+            CheckResult::pass(name, format!("{} reachable ({})", url, response.status()))
+        }
+        Ok(response) => CheckResult::fail(
+            name,
+            format!("{} answered {}", url, response.status()),
+            "The provider may be having an outage; try again later",
+        ),
+        Err(e) => {
+            let reason = if e.is_timeout() {
+                "timed out".to_string()
+            } else if e.is_connect() {
+                "connection refused".to_string()
+            } else {
+                e.to_string()
+            };
+            CheckResult::fail(
+                name,
+                format!("{} unreachable: {}", url, reason),
+                "Check your network, proxy settings and the profile's base_url",
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listening_inodes() {
+        let table = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:41AE 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 52411
+   1: 0100007F:41B0 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 52412
+   2: 0100007F:41AE 0100007F:9C40 01 00000000:00000000 00:00000000 00000000  1000        0 52499";
+
+        // 0x41AE is 16814; the established connection on it isn't a listener
+        assert_eq!(listening_inodes(table, 16814), vec![52411]);
+        assert_eq!(listening_inodes(table, 16816), vec![52412]);
+        assert!(listening_inodes(table, RPC_PORT).is_empty());
+    }
+}
@@ -5,11 +5,14 @@
 use anyhow::Result;
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use std::collections::{BTreeMap, HashMap};
 use std::io::{self, Write};
 use std::path::PathBuf;
 use std::process::Command;
 
+use crate::OutputFormat;
+
 const API_TIMEOUT_MS: u64 = 3000000;
 
 /// Claude settings.json structure
@@ -61,7 +64,7 @@ fn save_claude_settings(settings: &ClaudeSettings) -> Result<()> {
     Ok(())
 }
 
-fn mask_key(key: &str) -> String {
+pub(crate) fn mask_key(key: &str) -> String {
     if key.len() > 8 {
         format!("{}****{}", &key[..4], &key[key.len() - 4..])
     } else {
@@ -259,17 +262,9 @@ pub async fn run(name: Option<&str>, args: Vec<String>) -> Result<()> {
     );
 
     let mut cmd = Command::new("claude");
-    cmd.args(&args)
-        .env("ANTHROPIC_AUTH_TOKEN", provider.api_key.trim())
-        .env("ANTHROPIC_BASE_URL", &provider.base_url)
-        .env("API_TIMEOUT_MS", API_TIMEOUT_MS.to_string())
-        .env("CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC", "1");
+    cmd.args(&args).envs(provider_env(provider));
 
     if let Some(ref model) = provider.model {
-        cmd.env("ANTHROPIC_MODEL", model);
-        cmd.env("ANTHROPIC_DEFAULT_HAIKU_MODEL", model);
-        cmd.env("ANTHROPIC_DEFAULT_SONNET_MODEL", model);
-        cmd.env("ANTHROPIC_DEFAULT_OPUS_MODEL", model);
         println!("{}", format!("   Model: {}", model).dimmed());
     }
 
@@ -285,6 +280,157 @@ pub async fn run(name: Option<&str>, args: Vec<String>) -> Result<()> {
     Ok(())
 }
 
+/// One variable's fate when switching between two environments
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+enum EnvChange {
+    Removed { key: String, value: String },
+    Added { key: String, value: String },
+    Changed { key: String, from: String, to: String },
+    Unchanged { key: String, value: String },
+}
+
+impl EnvChange {
+    fn key(&self) -> &str {
+        match self {
+            EnvChange::Removed { key, .. }
+            | EnvChange::Added { key, .. }
+            | EnvChange::Changed { key, .. }
+            | EnvChange::Unchanged { key, .. } => key,
+        }
+    }
+}
+
+/// Show how the variables `run` sets differ between two environments
+pub async fn diff(from: &str, to: &str, output: OutputFormat) -> Result<()> {
+    let settings = load_claude_settings()?;
+    let env_of = |name: &str| {
+        settings
+            .providers
+            .get(name)
+            .map(|provider| provider_env(provider).into_iter().collect::<BTreeMap<_, _>>())
+            .ok_or_else(|| anyhow::anyhow!("Environment '{}' not found", name))
+    };
+    let changes = diff_env(&env_of(from)?, &env_of(to)?);
+
+    if output == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        format!("=== Environment diff: {} → {} ===", from, to).cyan().bold()
+    );
+    let width = changes.iter().map(|c| c.key().len()).max().unwrap_or(0);
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in &changes {
+        // Pad before colouring so escape codes don't skew the columns
+        let line = match change {
+            EnvChange::Removed { key, value } => {
+                removed += 1;
+                format!("- {:<width$}  {}", key, value).red()
+            }
+            EnvChange::Added { key, value } => {
+                added += 1;
+                format!("+ {:<width$}  {}", key, value).green()
+            }
+            EnvChange::Changed { key, from, to } => {
+                changed += 1;
+                format!("~ {:<width$}  {} → {}", key, from, to).yellow()
+            }
+            EnvChange::Unchanged { key, value } => {
+                format!("  {:<width$}  {}", key, value).dimmed()
+            }
+        };
+        println!("{}", line);
+    }
+    println!();
+    println!(
+        "{}",
+        format!(
+            "{} added, {} removed, {} changed, {} unchanged",
+            added,
+            removed,
+            changed,
+            changes.len() - added - removed - changed
+        )
+        .dimmed()
+    );
+
+    Ok(())
+}
+
+/// Diff two variable maps, pairing a removal and an addition of the same key
+/// into a change. Secrets are masked in the result.
+fn diff_env(from: &BTreeMap<String, String>, to: &BTreeMap<String, String>) -> Vec<EnvChange> {
+    let old: Vec<(&String, &String)> = from.iter().collect();
+    let new: Vec<(&String, &String)> = to.iter().collect();
+    let shown = |key: &str, value: &str| {
+        if key == "ANTHROPIC_AUTH_TOKEN" {
+            mask_key(value)
+        } else {
+            value.to_string()
+        }
+    };
+
+    let mut changes = Vec::new();
+    let mut removed = BTreeMap::new();
+    let mut added = BTreeMap::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old, &new) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            changes.extend(old[old_range].iter().map(|(key, value)| EnvChange::Unchanged {
+                key: key.to_string(),
+                value: shown(key, value),
+            }));
+            continue;
+        }
+        removed.extend(old[old_range].iter().copied());
+        added.extend(new[new_range].iter().copied());
+    }
+
+    for (key, old_value) in removed {
+        changes.push(match added.remove(key) {
+            Some(new_value) => EnvChange::Changed {
+                key: key.clone(),
+                from: shown(key, old_value),
+                to: shown(key, new_value),
+            },
+            None => EnvChange::Removed {
+                key: key.clone(),
+                value: shown(key, old_value),
+            },
+        });
+    }
+    changes.extend(added.into_iter().map(|(key, value)| EnvChange::Added {
+        key: key.clone(),
+        value: shown(key, value),
+    }));
+
+    changes.sort_by(|a, b| a.key().cmp(b.key()));
+    changes
+}
+
+/// Variables `run` sets for `provider`
+fn provider_env(provider: &ProviderConfig) -> Vec<(String, String)> {
+    let mut vars = vec![
+        ("ANTHROPIC_AUTH_TOKEN", provider.api_key.trim().to_string()),
+        ("ANTHROPIC_BASE_URL", provider.base_url.clone()),
+        ("API_TIMEOUT_MS", API_TIMEOUT_MS.to_string()),
+        ("CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC", "1".to_string()),
+    ];
+    if let Some(ref model) = provider.model {
+        vars.extend([
+            ("ANTHROPIC_MODEL", model.clone()),
+            ("ANTHROPIC_DEFAULT_HAIKU_MODEL", model.clone()),
+            ("ANTHROPIC_DEFAULT_SONNET_MODEL", model.clone()),
+            ("ANTHROPIC_DEFAULT_OPUS_MODEL", model.clone()),
+        ]);
+    }
+    vars.into_iter().map(|(key, value)| (key.to_string(), value)).collect()
+}
+
 fn update_env_from_active(settings: &mut ClaudeSettings) {
     if let Some(ref name) = settings.active_provider {
         if let Some(provider) = settings.providers.get(name) {
@@ -331,3 +477,52 @@ fn update_env_from_active(settings: &mut ClaudeSettings) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_env() {
+        let from = vars(&[
+            ("ANTHROPIC_AUTH_TOKEN", "sk-old-0123456789"),
+            ("ANTHROPIC_MODEL", "opus"),
+            ("API_TIMEOUT_MS", "3000000"),
+        ]);
+        let to = vars(&[
+            ("ANTHROPIC_AUTH_TOKEN", "sk-new-9876543210"),
+            ("ANTHROPIC_BASE_URL", "https://example.com"),
+            ("API_TIMEOUT_MS", "3000000"),
+        ]);
+
+        assert_eq!(
+            diff_env(&from, &to),
+            vec![
+                EnvChange::Changed {
+                    key: "ANTHROPIC_AUTH_TOKEN".to_string(),
+                    from: "sk-o****6789".to_string(),
+                    to: "sk-n****3210".to_string(),
+                },
+                EnvChange::Added {
+                    key: "ANTHROPIC_BASE_URL".to_string(),
+                    value: "https://example.com".to_string(),
+                },
+                EnvChange::Removed {
+                    key: "ANTHROPIC_MODEL".to_string(),
+                    value: "opus".to_string(),
+                },
+                EnvChange::Unchanged {
+                    key: "API_TIMEOUT_MS".to_string(),
+                    value: "3000000".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -3,7 +3,10 @@
 use std::path::Path;
 use anyhow::Result;
 use colored::Colorize;
-use happy_core::{ConfigManager, ProjectConfig, TargetConfig, TargetsConfig};
+use happy_core::{
+    ConfigManager, ProjectConfig, Settings, SettingsBuilder, TargetConfig, TargetsConfig,
+};
+use crate::config::SettingsManager;
 
 pub async fn run(name: &str, skip_prompts: bool) -> Result<()> {
     println!("{}", "🚀 Initializing Happy Coding project...".cyan().bold());
@@ -44,6 +47,8 @@ pub async fn run(name: &str, skip_prompts: bool) -> Result<()> {
             claude: Some(TargetConfig::default()),
             codex: Some(TargetConfig::default()),
             antigravity: None,
+            ollama: None,
+            openai: None,
         },
         skills: vec![],
         workflows: vec![],
@@ -91,6 +96,10 @@ When this skill is invoked, greet the user warmly and ask how you can help them
 
     tokio::fs::write(example_skill_dir.join("SKILL.md"), example_skill).await?;
 
+    if !skip_prompts {
+        set_up_settings()?;
+    }
+
     println!("{}", "✅ Project initialized successfully!".green().bold());
     println!();
     println!("Created files:");
@@ -104,3 +113,82 @@ When this skill is invoked, greet the user warmly and ask how you can help them
 
     Ok(())
 }
+
+/// Offer a first-time user to set the server URLs, asking until they are valid
+///
+/// Any earlier command may have written `settings.json` with the defaults, so
+/// a first run is recognized by nothing being configured yet.
+fn set_up_settings() -> Result<()> {
+    let current = SettingsManager::load()?;
+    if !is_unconfigured(&current) {
+        return Ok(());
+    }
+    let set_up = dialoguer::Confirm::new()
+        .with_prompt("Set up Happy Remote settings now?")
+        .default(false)
+        .interact()?;
+    if !set_up {
+        return Ok(());
+    }
+
+    let defaults = Settings::default();
+    let settings = loop {
+        let server_url: String = dialoguer::Input::new()
+            .with_prompt("Server URL")
+            .default(defaults.server_url.clone())
+            .interact_text()?;
+        let webapp_url: String = dialoguer::Input::new()
+            .with_prompt("Web app URL")
+            .default(defaults.webapp_url.clone())
+            .interact_text()?;
+
+        match SettingsBuilder::from_settings(current.clone())
+            .server_url(&server_url)
+            .webapp_url(&webapp_url)
+            .build()
+        {
+            Ok(settings) => break settings,
+            Err(e) => println!("{} {}", "❌".red(), e),
+        }
+    };
+
+    SettingsManager::save(&settings)?;
+    println!(
+        "{} Saved {}",
+        "✅".green(),
+        SettingsManager::settings_path()?.display()
+    );
+    println!("   Add an AI profile with {}", "happy connect anthropic".cyan());
+    Ok(())
+}
+
+/// Still the default URLs, with no profiles and nobody logged in
+fn is_unconfigured(settings: &Settings) -> bool {
+    let defaults = Settings::default();
+    settings.server_url == defaults.server_url
+        && settings.webapp_url == defaults.webapp_url
+        && settings.profiles.is_empty()
+        && settings.access_token.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_unconfigured() {
+        assert!(is_unconfigured(&Settings::default()));
+
+        let self_hosted = Settings {
+            server_url: "https://happy.example.com".to_string(),
+            ..Settings::default()
+        };
+        assert!(!is_unconfigured(&self_hosted));
+
+        let logged_in = Settings {
+            access_token: Some("token".to_string()),
+            ..Settings::default()
+        };
+        assert!(!is_unconfigured(&logged_in));
+    }
+}
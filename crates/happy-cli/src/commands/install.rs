@@ -1,11 +1,24 @@
 //! Install command - Install built artifacts to target location
+//!
+//! `--self` installs the `happy` binary instead: into `~/.local/bin` by default,
+//! which needs no `sudo`, or `/usr/local/bin` with `--global`.
 
-use anyhow::Result;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
 use colored::Colorize;
 use happy_core::{ConfigManager, InstallTarget, Platform};
 use happy_adapters::create_adapter_factory;
 
-pub async fn run(global: bool, target: Option<String>) -> Result<()> {
+/// System-wide binary directory used by `--self --global`
+const GLOBAL_BIN_DIR: &str = "/usr/local/bin";
+
+pub async fn run(global: bool, target: Option<String>, self_binary: bool) -> Result<()> {
+    if self_binary {
+        return install_binary(global).await;
+    }
+
     println!("{}", "📦 Installing Happy Coding artifacts...".cyan().bold());
 
     let project_dir = std::env::current_dir()?;
@@ -21,6 +34,8 @@ pub async fn run(global: bool, target: Option<String>) -> Result<()> {
             "claude" => Platform::Claude,
             "codex" => Platform::Codex,
             "antigravity" => Platform::Antigravity,
+            "ollama" => Platform::Ollama,
+            "openai" => Platform::OpenAI,
             _ => return Err(anyhow::anyhow!("Unknown platform: {}", t)),
         }]
     } else {
@@ -83,3 +98,171 @@ pub async fn run(global: bool, target: Option<String>) -> Result<()> {
 
     Ok(())
 }
+
+/// Copy the running `happy` executable into a bin directory on `$PATH`
+async fn install_binary(global: bool) -> Result<()> {
+    println!("{}", "📦 Installing the happy binary...".cyan().bold());
+
+    let source = std::env::current_exe().context("Cannot locate the running happy binary")?;
+    let bin_dir = if global {
+        PathBuf::from(GLOBAL_BIN_DIR)
+    } else {
+        user_bin_dir().context("Cannot determine home directory")?
+    };
+    let file_name = source.file_name().context("Invalid executable path")?;
+    let dest = bin_dir.join(file_name);
+
+    if let Err(e) = copy_executable(&source, &dest).await {
+        if global && is_permission_denied(&e) {
+            anyhow::bail!(
+                "No permission to write {}. Re-run with sudo, or drop --global to install \
+                 into ~/.local/bin",
+                bin_dir.display()
+            );
+        }
+        return Err(e);
+    }
+    println!("  {} {}", "✅".green(), dest.display().to_string().dimmed());
+
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    if !global && !path_contains(&path_var, &bin_dir) {
+        offer_path_update(&bin_dir)?;
+    }
+
+    Ok(())
+}
+
+/// `~/.local/bin`, the XDG location for user executables
+fn user_bin_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".local/bin"))
+}
+
+/// Copy through a temp file and rename, since the target may be the binary that is running
+async fn copy_executable(source: &Path, dest: &Path) -> Result<()> {
+    let dir = dest.parent().context("Invalid install path")?;
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let tmp = dest.with_extension("tmp");
+    tokio::fs::copy(source, &tmp)
+        .await
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+    if let Err(e) = tokio::fs::rename(&tmp, dest).await {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(e).with_context(|| format!("Failed to replace {}", dest.display()));
+    }
+    Ok(())
+}
+
+fn is_permission_denied(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+}
+
+/// Whether `dir` is one of the entries of a `$PATH`-style value
+fn path_contains(path_var: &OsStr, dir: &Path) -> bool {
+    std::env::split_paths(path_var).any(|entry| entry == dir)
+}
+
+/// The startup file and `$PATH` line for the shell named by `$SHELL`
+fn shell_rc(shell: &str, home: &Path) -> Option<(PathBuf, &'static str)> {
+    let name = Path::new(shell).file_name()?.to_str()?;
+    match name {
+        "bash" => Some((home.join(".bashrc"), r#"export PATH="$HOME/.local/bin:$PATH""#)),
+        "zsh" => Some((home.join(".zshrc"), r#"export PATH="$HOME/.local/bin:$PATH""#)),
+        // fish keeps PATH as a list rather than a colon-separated string
+        "fish" => Some((
+            home.join(".config/fish/config.fish"),
+            "set -gx PATH $HOME/.local/bin $PATH",
+        )),
+        _ => None,
+    }
+}
+
+/// Ask to add `bin_dir` to `$PATH` in the user's shell startup file
+fn offer_path_update(bin_dir: &Path) -> Result<()> {
+    use std::io::{IsTerminal, Write};
+
+    println!(
+        "  {} {} is not on your PATH",
+        "⚠️".yellow(),
+        bin_dir.display()
+    );
+
+    let shell = std::env::var("SHELL").unwrap_or_default();
+    let rc = dirs::home_dir().and_then(|home| shell_rc(&shell, &home));
+    let Some((rc_path, line)) = rc else {
+        println!("  Add it to PATH in your shell's startup file to run `happy` directly.");
+        return Ok(());
+    };
+
+    let existing = std::fs::read_to_string(&rc_path).unwrap_or_default();
+    if existing.contains(".local/bin") {
+        println!(
+            "  {} already sets it; open a new shell to pick it up.",
+            rc_path.display()
+        );
+        return Ok(());
+    }
+
+    let confirm = std::io::stdin().is_terminal()
+        && dialoguer::Confirm::new()
+            .with_prompt(format!("Add it to PATH in {}?", rc_path.display()))
+            .default(true)
+            .interact()?;
+    if !confirm {
+        println!("  Add this line to {}:", rc_path.display());
+        println!("    {}", line.cyan());
+        return Ok(());
+    }
+
+    if let Some(dir) = rc_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&rc_path)
+        .with_context(|| format!("Failed to open {}", rc_path.display()))?;
+    writeln!(file, "\n# Added by happy install\n{}", line)?;
+    println!(
+        "  {} Updated {}; open a new shell to pick it up.",
+        "✅".green(),
+        rc_path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_contains() {
+        let path = std::env::join_paths(["/usr/bin", "/home/me/.local/bin"]).unwrap();
+        assert!(path_contains(&path, Path::new("/home/me/.local/bin")));
+        assert!(!path_contains(&path, Path::new("/home/me/bin")));
+    }
+
+    #[test]
+    fn test_shell_rc() {
+        let home = Path::new("/home/me");
+        let (rc, line) = shell_rc("/usr/bin/zsh", home).unwrap();
+        assert_eq!(rc, home.join(".zshrc"));
+        assert!(line.starts_with("export PATH="));
+
+        let (rc, line) = shell_rc("/opt/homebrew/bin/fish", home).unwrap();
+        assert_eq!(rc, home.join(".config/fish/config.fish"));
+        assert!(line.starts_with("set -gx PATH"));
+
+        assert!(shell_rc("/bin/tcsh", home).is_none());
+    }
+}
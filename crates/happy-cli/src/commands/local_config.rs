@@ -4,7 +4,8 @@
 
 use anyhow::Result;
 use colored::Colorize;
-use std::path::PathBuf;
+use similar::{ChangeTag, TextDiff};
+use std::path::{Path, PathBuf};
 
 fn local_config_path() -> PathBuf {
     std::env::current_dir()
@@ -135,47 +136,195 @@ pub async fn pull() -> Result<()> {
 }
 
 /// Show diff between local and system config
-pub async fn diff() -> Result<()> {
-    let local = local_config_path();
-    let system = claude_config_path();
+///
+/// Every file directly under ~/.claude/ that has a local copy is compared as a
+/// unified diff, e.g. `settings.json` against `./claude_settings.json`.
+pub async fn diff(no_color: bool, stat: bool) -> Result<()> {
+    if no_color {
+        colored::control::set_override(false);
+    }
 
-    if !local.exists() {
-        println!("{}", "本地配置文件不存在".yellow());
+    let local_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let system_dir = claude_config_path()
+        .parent()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    if !system_dir.is_dir() {
+        println!(
+            "{}",
+            format!("Claude 配置目录不存在: {}", system_dir.display()).yellow()
+        );
+        println!("  运行一次 claude 或 `happy local-config push` 以创建");
         return Ok(());
     }
 
-    if !system.exists() {
-        println!("{}", "Claude 配置文件不存在".yellow());
+    let pairs = config_pairs(&system_dir, &local_dir)?;
+    if pairs.is_empty() {
+        println!("{}", "本地配置文件不存在".yellow());
+        println!("  运行 `happy local-config pull` 以创建本地副本");
         return Ok(());
     }
 
-    let local_content = std::fs::read_to_string(&local)?;
-    let system_content = std::fs::read_to_string(&system)?;
+    let mut stats = Vec::new();
+    for pair in &pairs {
+        let local_content = std::fs::read_to_string(&pair.local)?;
+        let system_content = std::fs::read_to_string(&pair.system)?;
+        let (insertions, deletions) = count_changes(&local_content, &system_content);
+        if insertions + deletions == 0 {
+            continue;
+        }
 
-    if local_content == system_content {
-        println!("{}", "✓ 配置文件一致，无差异".green());
-    } else {
-        println!("{}", "配置文件存在差异:".yellow());
-
-        // Simple line-by-line diff
-        let local_lines: Vec<&str> = local_content.lines().collect();
-        let system_lines: Vec<&str> = system_content.lines().collect();
-
-        let max_lines = local_lines.len().max(system_lines.len());
-        for i in 0..max_lines {
-            let local_line = local_lines.get(i).unwrap_or(&"");
-            let system_line = system_lines.get(i).unwrap_or(&"");
-
-            if local_line != system_line {
-                if !local_line.is_empty() {
-                    println!("{} {}", "- ".red(), local_line.red());
-                }
-                if !system_line.is_empty() {
-                    println!("{} {}", "+ ".green(), system_line.green());
-                }
-            }
+        let system_label = format!("~/.claude/{}", pair.system_name);
+        if !stat {
+            print_colored_diff(&unified_diff(
+                &pair.local_name,
+                &system_label,
+                &local_content,
+                &system_content,
+            ));
         }
+        stats.push((system_label, insertions, deletions));
+    }
+
+    if stats.is_empty() {
+        println!("{}", "✓ 配置文件一致，无差异".green());
+    } else if stat {
+        print_stat(&stats);
     }
 
     Ok(())
 }
+
+/// A file under ~/.claude/ and its local copy
+struct ConfigPair {
+    system_name: String,
+    local_name: String,
+    system: PathBuf,
+    local: PathBuf,
+}
+
+/// Files directly under `system_dir` that have a `claude_`-prefixed copy in `local_dir`
+fn config_pairs(system_dir: &Path, local_dir: &Path) -> Result<Vec<ConfigPair>> {
+    let mut pairs = Vec::new();
+    for entry in std::fs::read_dir(system_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let Some(system_name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let local_name = format!("claude_{}", system_name);
+        let local = local_dir.join(&local_name);
+        if local.is_file() {
+            pairs.push(ConfigPair {
+                system: entry.path(),
+                local,
+                system_name,
+                local_name,
+            });
+        }
+    }
+    pairs.sort_by(|a, b| a.system_name.cmp(&b.system_name));
+    Ok(pairs)
+}
+
+/// Unified diff with three lines of context, as `git diff` prints it
+fn unified_diff(old_label: &str, new_label: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", old_label), &format!("b/{}", new_label))
+        .to_string()
+}
+
+/// Lines added and removed going from `old` to `new`
+fn count_changes(old: &str, new: &str) -> (usize, usize) {
+    let diff = TextDiff::from_lines(old, new);
+    diff.iter_all_changes()
+        .fold((0, 0), |(insertions, deletions), change| match change.tag() {
+            ChangeTag::Insert => (insertions + 1, deletions),
+            ChangeTag::Delete => (insertions, deletions + 1),
+            ChangeTag::Equal => (insertions, deletions),
+        })
+}
+
+fn print_colored_diff(diff: &str) {
+    for (i, line) in diff.lines().enumerate() {
+        if i < 2 {
+            println!("{}", line.bold());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// `git diff --stat` style summary of `(file, insertions, deletions)`
+fn print_stat(stats: &[(String, usize, usize)]) {
+    let width = stats.iter().map(|(name, _, _)| name.len()).max().unwrap_or(0);
+    for (name, insertions, deletions) in stats {
+        println!(
+            " {:width$} | {} {}{}",
+            name,
+            insertions + deletions,
+            "+".repeat(*insertions).green(),
+            "-".repeat(*deletions).red(),
+            width = width
+        );
+    }
+    let insertions: usize = stats.iter().map(|(_, i, _)| i).sum();
+    let deletions: usize = stats.iter().map(|(_, _, d)| d).sum();
+    println!(
+        " {} file(s) changed, {} insertions(+), {} deletions(-)",
+        stats.len(),
+        insertions,
+        deletions
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "{\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": 3,\n  \"d\": 4,\n  \"e\": 5\n}\n";
+        let new = "{\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": 30,\n  \"d\": 4,\n  \"e\": 5\n}\n";
+        let diff = unified_diff("claude_settings.json", "~/.claude/settings.json", old, new);
+        assert_eq!(
+            diff,
+            "--- a/claude_settings.json\n\
+             +++ b/~/.claude/settings.json\n\
+             @@ -1,7 +1,7 @@\n \
+             {\n   \"a\": 1,\n   \"b\": 2,\n\
+             -  \"c\": 3,\n\
+             +  \"c\": 30,\n   \"d\": 4,\n   \"e\": 5\n }\n"
+        );
+        assert_eq!(count_changes(old, new), (1, 1));
+        assert_eq!(count_changes(old, old), (0, 0));
+    }
+
+    #[test]
+    fn test_config_pairs() {
+        let system = tempfile::tempdir().unwrap();
+        let local = tempfile::tempdir().unwrap();
+        std::fs::write(system.path().join("settings.json"), "{}").unwrap();
+        std::fs::write(system.path().join("settings.local.json"), "{}").unwrap();
+        std::fs::write(system.path().join("CLAUDE.md"), "").unwrap();
+        std::fs::create_dir(system.path().join("projects")).unwrap();
+        std::fs::write(local.path().join("claude_settings.json"), "{}").unwrap();
+        std::fs::write(local.path().join("claude_CLAUDE.md"), "").unwrap();
+
+        let pairs = config_pairs(system.path(), local.path()).unwrap();
+        let names: Vec<_> = pairs.iter().map(|p| p.system_name.as_str()).collect();
+        assert_eq!(names, ["CLAUDE.md", "settings.json"]);
+        assert_eq!(pairs[1].local, local.path().join("claude_settings.json"));
+    }
+}
@@ -0,0 +1,204 @@
+//! Machine commands - Manage machines registered to the account
+
+use crate::api::Client;
+use crate::OutputFormat;
+use anyhow::Result;
+use chrono::Local;
+use colored::Colorize;
+use happy_types::{Capability, MachineInfo};
+
+/// Length of the ID prefix shown in the table and accepted as an argument
+const SHORT_ID_LEN: usize = 8;
+
+pub async fn list(
+    search: Option<&str>,
+    require: &[Capability],
+    output: OutputFormat,
+) -> Result<()> {
+    let token = access_token().await?;
+    let mut machines = Client::new().list_machines(&token, search).await?;
+    machines.retain(|m| m.capabilities.contains_all(require));
+
+    if output == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&machines)?);
+        return Ok(());
+    }
+
+    println!("{}", "💻 Machines".blue().bold());
+    println!();
+
+    if machines.is_empty() {
+        if let Some(query) = search {
+            println!("   (No machines match '{}')", query);
+            return Ok(());
+        }
+        if !require.is_empty() {
+            let names: Vec<String> = require.iter().map(|c| c.to_string()).collect();
+            println!("   (No machines have {})", names.join(", "));
+            return Ok(());
+        }
+        println!("   (No machines registered)");
+        println!();
+        println!(
+            "   Machines register when a daemon starts: {}",
+            "happy daemon start".dimmed()
+        );
+        return Ok(());
+    }
+
+    let name_width = machines
+        .iter()
+        .map(|m| m.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Name".len());
+
+    // Pad before colouring so escape codes don't skew the columns
+    println!(
+        "   {}  {}  {}  {}  {}",
+        format!("{:<8}", "ID").bold(),
+        format!("{:<name_width$}", "Name").bold(),
+        format!("{:<8}", "Platform").bold(),
+        // Status emoji render two columns wide
+        format!("{:<11}", "Status").bold(),
+        "Last Seen".bold(),
+    );
+    for machine in &machines {
+        let status = if machine.is_online {
+            "🟢 online "
+        } else {
+            "🔴 offline"
+        };
+        println!(
+            "   {}  {}  {:<8}  {}  {}",
+            format!("{:<8}", short_id(&machine.id)).dimmed(),
+            format!("{:<name_width$}", machine.name).cyan(),
+            machine.platform.to_string(),
+            status,
+            machine
+                .last_seen
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn rename(id: &str, name: &str, output: OutputFormat) -> Result<()> {
+    let token = access_token().await?;
+    let client = Client::new();
+    let machine = resolve(&client, &token, id).await?;
+
+    client.rename_machine(&token, &machine.id, name).await?;
+
+    if output == OutputFormat::Json {
+        println!(
+            "{}",
+            serde_json::json!({ "id": machine.id, "name": name, "renamed": true })
+        );
+    } else {
+        println!(
+            "{}",
+            format!("✅ Machine '{}' renamed to '{}'", machine.name, name).green()
+        );
+    }
+    Ok(())
+}
+
+pub async fn remove(id: &str, yes: bool, output: OutputFormat) -> Result<()> {
+    let token = access_token().await?;
+    let client = Client::new();
+    let machine = resolve(&client, &token, id).await?;
+
+    if !yes {
+        let confirm: bool = dialoguer::Confirm::new()
+            .with_prompt(format!(
+                "Remove machine '{}' ({}) and all of its sessions?",
+                machine.name,
+                short_id(&machine.id)
+            ))
+            .default(false)
+            .interact()?;
+
+        if !confirm {
+            println!("{}", "Cancelled".dimmed());
+            return Ok(());
+        }
+    }
+
+    client.delete_machine(&token, &machine.id).await?;
+
+    if output == OutputFormat::Json {
+        println!(
+            "{}",
+            serde_json::json!({ "id": machine.id, "removed": true })
+        );
+    } else {
+        println!(
+            "{}",
+            format!("✅ Machine '{}' removed", machine.name).green()
+        );
+        if machine.is_online {
+            println!(
+                "   {}",
+                "Its daemon is still running and will register again on reconnect".dimmed()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub async fn merge(
+    admin_token: &str,
+    old_id: &str,
+    new_id: &str,
+    output: OutputFormat,
+) -> Result<()> {
+    let moved = Client::new()
+        .admin_merge_machines(admin_token, old_id, new_id)
+        .await?;
+
+    if output == OutputFormat::Json {
+        println!(
+            "{}",
+            serde_json::json!({ "old_id": old_id, "new_id": new_id, "sessions_moved": moved })
+        );
+    } else {
+        println!(
+            "{}",
+            format!(
+                "✅ Merged machine {} into {} ({} sessions moved)",
+                short_id(old_id),
+                short_id(new_id),
+                moved
+            )
+            .green()
+        );
+    }
+    Ok(())
+}
+
+/// The saved access token, refreshed if it is about to expire
+async fn access_token() -> Result<String> {
+    Client::new().access_token().await
+}
+
+/// Find a machine by full ID or unique ID prefix
+pub(crate) async fn resolve(client: &Client, token: &str, id: &str) -> Result<MachineInfo> {
+    let machines = client.list_machines(token, None).await?;
+    if let Some(machine) = machines.iter().find(|m| m.id == id) {
+        return Ok(machine.clone());
+    }
+
+    let mut matches = machines.into_iter().filter(|m| m.id.starts_with(id));
+    match (matches.next(), matches.next()) {
+        (Some(machine), None) => Ok(machine),
+        (Some(_), Some(_)) => anyhow::bail!("Machine ID '{}' is ambiguous", id),
+        (None, _) => anyhow::bail!("Machine '{}' not found", id),
+    }
+}
+
+fn short_id(id: &str) -> &str {
+    id.get(..SHORT_ID_LEN).unwrap_or(id)
+}
@@ -1,5 +1,7 @@
+pub mod admin;
 pub mod auth;
 pub mod build;
+pub mod completions;
 pub mod config;
 pub mod connect;
 pub mod daemon;
@@ -9,7 +11,10 @@ pub mod env;
 pub mod init;
 pub mod install;
 pub mod local_config;
+pub mod machine;
 pub mod notify;
 pub mod profile;
 pub mod run;
+pub mod session;
+pub mod token_usage;
 pub mod validate;
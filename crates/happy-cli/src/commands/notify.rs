@@ -1,9 +1,19 @@
 //! Notify command - Push notifications
 
-use crate::api::Client;
+use crate::api::{is_offline_error, Client};
 use crate::config::SettingsManager;
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Queued notifications older than this are no longer worth delivering
+pub const PENDING_NOTIFICATION_MAX_AGE: Duration = Duration::from_secs(24 * 3600);
+
+/// Queue length beyond which `happy notify` warns that the server has been unreachable a while
+const PENDING_NOTIFICATION_WARN_LEN: usize = 10;
 
 pub async fn execute(message: &str) -> Result<()> {
     let settings = SettingsManager::load()?;
@@ -14,12 +24,36 @@ pub async fn execute(message: &str) -> Result<()> {
     }
 
     let client = Client::new();
-    let token = settings.access_token.unwrap();
+    // A stale token still lets the notification be queued while offline
+    let token = match client.access_token().await {
+        Ok(token) => token,
+        Err(e) if is_offline_error(&e) => settings.access_token.unwrap(),
+        Err(e) => return Err(e),
+    };
 
     match client.send_notification(&token, message).await {
         Ok(_) => {
             println!("{}", "✅ Notification sent".green());
         }
+        Err(e) if is_offline_error(&e) => {
+            let queue = NotificationQueue::open()?;
+            let pending = queue.enqueue(NotifyRequest::new(message))?;
+            println!(
+                "{}",
+                "📥 Server unreachable; notification queued for the next connection".yellow()
+            );
+            if pending > PENDING_NOTIFICATION_WARN_LEN {
+                println!(
+                    "{}",
+                    format!(
+                        "⚠️  {} notifications are waiting to be delivered ({})",
+                        pending,
+                        queue.path.display()
+                    )
+                    .yellow()
+                );
+            }
+        }
         Err(e) => {
             println!("{}", format!("⚠️  Failed to send notification: {}", e).yellow());
         }
@@ -27,3 +61,131 @@ pub async fn execute(message: &str) -> Result<()> {
 
     Ok(())
 }
+
+/// A notification waiting to be delivered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotifyRequest {
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl NotifyRequest {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Notifications that couldn't be sent while offline, kept in
+/// `~/.happy/pending_notifications.json` oldest first
+pub struct NotificationQueue {
+    path: PathBuf,
+}
+
+impl NotificationQueue {
+    pub fn open() -> Result<Self> {
+        Ok(Self::at(
+            SettingsManager::happy_home()?.join("pending_notifications.json"),
+        ))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Add a notification to the end of the queue, returning the new length
+    pub fn enqueue(&self, request: NotifyRequest) -> Result<usize> {
+        self.enqueue_all(vec![request])
+    }
+
+    /// Add several notifications, e.g. ones a drain failed to deliver
+    pub fn enqueue_all(&self, requests: Vec<NotifyRequest>) -> Result<usize> {
+        let mut pending = self.load()?;
+        pending.extend(requests);
+        pending.retain(|r| !is_expired(r, PENDING_NOTIFICATION_MAX_AGE));
+        pending.sort_by_key(|r| r.timestamp);
+        self.save(&pending)?;
+        Ok(pending.len())
+    }
+
+    /// Take every queued notification that hasn't expired, leaving the queue empty
+    pub fn drain(&self) -> Result<Vec<NotifyRequest>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        self.purge_older_than(PENDING_NOTIFICATION_MAX_AGE)?;
+        let pending = self.load()?;
+        self.save(&[])?;
+        Ok(pending)
+    }
+
+    /// Drop notifications queued longer than `max_age` ago, returning how many were removed
+    pub fn purge_older_than(&self, max_age: Duration) -> Result<usize> {
+        let mut pending = self.load()?;
+        let before = pending.len();
+        pending.retain(|r| !is_expired(r, max_age));
+        if pending.len() != before {
+            self.save(&pending)?;
+        }
+        Ok(before - pending.len())
+    }
+
+    fn load(&self) -> Result<Vec<NotifyRequest>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, pending: &[NotifyRequest]) -> Result<()> {
+        if pending.is_empty() {
+            match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => return Ok(()),
+            }
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(pending)?)?;
+        Ok(())
+    }
+}
+
+fn is_expired(request: &NotifyRequest, max_age: Duration) -> bool {
+    chrono::Duration::from_std(max_age)
+        .map(|max_age| Utc::now() - request.timestamp > max_age)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_round_trip_and_purge() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = NotificationQueue::at(dir.path().join("pending_notifications.json"));
+
+        let stale = NotifyRequest {
+            message: "stale".to_string(),
+            timestamp: Utc::now() - chrono::Duration::hours(2),
+        };
+        assert_eq!(queue.enqueue(stale).unwrap(), 1);
+        assert_eq!(queue.enqueue(NotifyRequest::new("fresh")).unwrap(), 2);
+
+        assert_eq!(
+            queue.purge_older_than(Duration::from_secs(3600)).unwrap(),
+            1
+        );
+
+        let drained = queue.drain().unwrap();
+        assert_eq!(drained.len(), 1);
+        assert_eq!(drained[0].message, "fresh");
+        assert!(queue.drain().unwrap().is_empty());
+        assert!(!queue.path.exists());
+    }
+}
@@ -1,8 +1,12 @@
 //! Profile management commands
 
+use crate::commands::env::mask_key;
+use crate::commands::run::profile_env_lookup;
+use crate::commands::token_usage::TokenUsage;
 use crate::config::SettingsManager;
 use anyhow::Result;
 use colored::Colorize;
+use happy_core::{AIProfile, HappyError};
 
 pub async fn list() -> Result<()> {
     let settings = SettingsManager::load()?;
@@ -20,24 +24,43 @@ pub async fn list() -> Result<()> {
             let marker = if is_active { "*" } else { " " };
 
             println!(
-                "   [{}] {} - {:?}",
+                "   [{}] {} - {}",
                 marker,
                 profile.name.cyan(),
                 profile.provider
             );
-
-            if let Some(ref model) = profile.model {
-                println!("       Model: {}", model.dimmed());
-            }
-            if let Some(ref base_url) = profile.base_url {
-                println!("       URL: {}", base_url.dimmed());
-            }
+            print_details(profile);
         }
     }
 
     Ok(())
 }
 
+/// Model, endpoint and generation parameters that are set on `profile`
+fn print_details(profile: &AIProfile) {
+    if let Some(ref model) = profile.model {
+        println!("       Model: {}", model.dimmed());
+    }
+    if let Some(ref base_url) = profile.base_url {
+        println!("       URL: {}", base_url.dimmed());
+    }
+    if let Some(max_tokens) = profile.max_tokens {
+        println!("       Max tokens: {}", max_tokens.to_string().dimmed());
+    }
+    if let Some(temperature) = profile.temperature {
+        println!("       Temperature: {}", temperature.to_string().dimmed());
+    }
+    if let Some(top_p) = profile.top_p {
+        println!("       Top P: {}", top_p.to_string().dimmed());
+    }
+    if let Some(ref system_prompt) = profile.system_prompt {
+        println!("       System prompt: {}", system_prompt.dimmed());
+    }
+    if let Some(budget) = profile.max_tokens_per_day {
+        println!("       Daily token budget: {}", budget.to_string().dimmed());
+    }
+}
+
 pub async fn add(_name: &str) -> Result<()> {
     println!("{}", "Use 'happy connect <vendor>' to add a profile".yellow());
     Ok(())
@@ -87,3 +110,177 @@ pub async fn remove(name: &str) -> Result<()> {
     println!("{}", format!("✅ Profile '{}' deleted", name).green());
     Ok(())
 }
+
+/// Copy `source` to a new profile named `dest`, optionally with its own API key
+pub async fn clone(
+    source: &str,
+    dest: &str,
+    overwrite: bool,
+    api_key: Option<String>,
+) -> Result<()> {
+    let mut settings = SettingsManager::load()?;
+
+    let mut profile = settings
+        .profiles
+        .iter()
+        .find(|p| p.name == source)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Profile '{}' not found", source))?;
+
+    if settings.profiles.iter().any(|p| p.name == dest) {
+        if !overwrite {
+            println!(
+                "{}",
+                format!("⚠️  Profile '{}' already exists", dest).yellow()
+            );
+            anyhow::bail!("Pass --overwrite to replace '{}'", dest);
+        }
+        settings.profiles.retain(|p| p.name != dest);
+    }
+
+    profile.name = dest.to_string();
+    // The copy is a variation, never the default
+    profile.default = false;
+    if let Some(key) = api_key {
+        profile.api_key = Some(key);
+    }
+    settings.profiles.push(profile.clone());
+
+    SettingsManager::save(&settings)?;
+
+    println!(
+        "{}",
+        format!("✅ Profile '{}' cloned to '{}'", source, dest).green()
+    );
+    println!();
+    println!("   {} - {}", profile.name.cyan(), profile.provider);
+    if let Some(ref key) = profile.api_key {
+        println!("       API key: {}", mask_key(key).dimmed());
+    }
+    print_details(&profile);
+    if !profile.env_vars.is_empty() {
+        let mut names: Vec<&str> = profile.env_vars.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        println!("       Env vars: {}", names.join(", ").dimmed());
+    }
+    Ok(())
+}
+
+pub async fn set_param(name: &str, param: &str, value: &str) -> Result<()> {
+    let mut settings = SettingsManager::load()?;
+
+    let profile = settings
+        .profiles
+        .iter_mut()
+        .find(|p| p.name == name)
+        .ok_or_else(|| anyhow::anyhow!("Profile '{}' not found", name))?;
+    profile.set_param(param, value)?;
+
+    SettingsManager::save(&settings)?;
+
+    if value == "unset" {
+        println!(
+            "{}",
+            format!("✅ {} on '{}' reset to the provider default", param, name).green()
+        );
+    } else {
+        println!(
+            "{}",
+            format!("✅ {} on '{}' set to {}", param, name, value).green()
+        );
+        if matches!(param, "temperature" | "top_p" | "system_prompt") {
+            println!(
+                "{}",
+                "   Only used by API providers; `happy run` can't pass it to Claude Code"
+                    .dimmed()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Tokens each profile has used today against its daily budget
+pub async fn usage() -> Result<()> {
+    let settings = SettingsManager::load()?;
+    let today = TokenUsage::open()?.today()?;
+
+    println!("{}", "📊 Token usage today (UTC)".blue().bold());
+    println!();
+
+    if settings.profiles.is_empty() {
+        println!("   (No profiles configured)");
+        return Ok(());
+    }
+
+    for profile in &settings.profiles {
+        let used = today.get(&profile.name).copied().unwrap_or(0);
+        let line = match profile.max_tokens_per_day {
+            Some(budget) if used >= budget => {
+                format!("{} / {} (budget exceeded)", used, budget).red()
+            }
+            Some(budget) => format!("{} / {}", used, budget).normal(),
+            None => format!("{} (no budget)", used).dimmed(),
+        };
+        println!("   {} - {}", profile.name.cyan(), line);
+    }
+    println!();
+    println!(
+        "   Set a budget with: {}",
+        "happy profile set-param <name> max_tokens_per_day <tokens>".dimmed()
+    );
+    Ok(())
+}
+
+/// Report `${VAR}` references in a profile's env vars that don't resolve
+pub async fn validate(name: Option<&str>) -> Result<()> {
+    let settings = SettingsManager::load()?;
+    let name = name
+        .map(str::to_string)
+        .or(settings.active_profile.clone())
+        .ok_or_else(|| anyhow::anyhow!("No active profile; pass --profile <name>"))?;
+    let profile = settings
+        .profiles
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| anyhow::anyhow!("Profile '{}' not found", name))?;
+
+    let mut names: Vec<&str> = profile.env_vars.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    // Entry by entry, so every problem is reported rather than the first
+    let mut problems: Vec<String> = Vec::new();
+    for key in &names {
+        let Err(e) = profile.resolve_env_var_with(key, true, profile_env_lookup) else {
+            continue;
+        };
+        let problem = match e {
+            HappyError::Validation(message) => message,
+            e => e.to_string(),
+        };
+        // Entries referring to a broken one report its problem again
+        if !problems.contains(&problem) {
+            problems.push(problem);
+        }
+    }
+
+    if problems.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "✅ All {} env vars in '{}' resolve",
+                names.len(),
+                profile.name
+            )
+            .green()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        format!("❌ Unresolvable env vars in '{}':", profile.name).red()
+    );
+    for problem in &problems {
+        println!("   {}", problem);
+    }
+    anyhow::bail!("Profile '{}' has env vars that don't resolve", profile.name)
+}
@@ -1,21 +1,35 @@
 //! Run command - Start Claude Code with remote capabilities
 
 use crate::commands::auth;
+use crate::commands::token_usage::{
+    check_budget, claude_projects_dir, claude_tokens_since, TokenUsage,
+};
 use crate::config::SettingsManager;
+use crate::daemon::persistence::{resolve_session_env, SESSION_ID_PLACEHOLDER};
+use crate::daemon::multiplexer::SessionStatus;
 use crate::daemon::{DaemonClient, DaemonManager};
 use anyhow::{Context, Result};
 use colored::Colorize;
+use happy_core::utils::sandbox::{Sandbox, SandboxMode};
+use happy_core::AIProfile;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 pub struct RunOptions {
     pub agent: String,
     /// Enable remote sync mode (default: false = local-only)
     pub remote: bool,
     pub tag: Option<String>,
-    #[allow(dead_code)]
     pub profile: Option<String>,
+    /// Apply the active profile's env vars even when no `--profile` is given
+    pub profile_env: bool,
+    /// Overrides the profile's `sandbox_policy`
+    pub sandbox: Option<SandboxMode>,
+    /// Where the agent starts; the shell's directory when unset
+    pub cwd: Option<PathBuf>,
     #[allow(dead_code)]
     pub args: Vec<String>,
 }
@@ -35,22 +49,96 @@ pub async fn execute(options: RunOptions) -> Result<()> {
     }
 }
 
+/// Environment variables defined by an AI profile, in a stable order.
+///
+/// Of the generation parameters only `max_tokens` is passed, under the name
+/// Claude Code reads; it has no setting for temperature, top_p or a system
+/// prompt, so those only apply to API-backed profiles. Entries in the
+/// profile's `env_vars` take precedence, with
+/// `${VAR}` references expanded by [`profile_env_lookup`], so from the
+/// user's shell rather than the daemon's environment. Undefined ones become
+/// empty.
+pub(crate) fn profile_env_vars(profile: &AIProfile) -> Result<Vec<(String, String)>> {
+    let mut env_vars: std::collections::BTreeMap<String, String> = profile
+        .max_tokens
+        .map(|v| ("CLAUDE_CODE_MAX_OUTPUT_TOKENS".to_string(), v.to_string()))
+        .into_iter()
+        .collect();
+    let resolved = profile
+        .resolve_env_vars_with(false, profile_env_lookup)
+        .with_context(|| format!("Invalid env vars in profile '{}'", profile.name))?;
+    env_vars.extend(resolved);
+    Ok(env_vars.into_iter().collect())
+}
+
+/// Where profile env var references are looked up: this process's
+/// environment, except `${HAPPY_SESSION_ID}`, which the daemon fills in
+pub(crate) fn profile_env_lookup(name: &str) -> Option<String> {
+    match name {
+        "HAPPY_SESSION_ID" => Some(SESSION_ID_PLACEHOLDER.to_string()),
+        _ => std::env::var(name).ok(),
+    }
+}
+
+/// The profile whose env vars are injected into the agent process.
+///
+/// An explicit `--profile` always applies; otherwise the active profile is
+/// used only with `--profile-env`.
+fn selected_profile(options: &RunOptions) -> Result<Option<AIProfile>> {
+    if options.profile.is_none() && !options.profile_env {
+        return Ok(None);
+    }
+
+    let settings = SettingsManager::load().context("Failed to load settings")?;
+    let Some(name) = options.profile.clone().or(settings.active_profile) else {
+        return Ok(None);
+    };
+
+    settings
+        .profiles
+        .into_iter()
+        .find(|p| p.name == name)
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("Profile '{}' not found", name))
+}
+
+/// Sandbox for a local run: `--sandbox` wins, then the selected profile's policy
+fn selected_sandbox(options: &RunOptions) -> Result<Option<SandboxMode>> {
+    if options.sandbox.is_some() {
+        return Ok(options.sandbox);
+    }
+
+    let settings = SettingsManager::load().context("Failed to load settings")?;
+    let Some(name) = options.profile.clone().or(settings.active_profile) else {
+        return Ok(None);
+    };
+
+    Ok(settings
+        .profiles
+        .iter()
+        .find(|p| p.name == name)
+        .and_then(|p| p.sandbox_policy))
+}
+
 /// Ensure user is authenticated, auto-login from config or prompt if needed
 async fn ensure_authenticated() -> Result<()> {
     let settings = SettingsManager::load().context("Failed to load settings")?;
 
-    // First, check if we have a valid token
+    // First, check if we have a valid token, refreshing it if it's about to expire
     if settings.access_token.is_some() {
         let client = crate::api::Client::new();
-        if let Some(ref token) = settings.access_token {
-            match client.get_user_info(token).await {
+        if let Ok(token) = client.access_token().await {
+            match client.get_user_info(&token).await {
                 Ok(_) => return Ok(()),
                 Err(_) => {
                     println!(
                         "{}",
                         "⚠️  Token expired, trying to re-authenticate...".yellow()
                     );
-                    // Token expired, try auto-login from config
+                    if client.refresh_token().await.is_ok() {
+                        return Ok(());
+                    }
+                    // Refresh failed, try auto-login from config
                 }
             }
         }
@@ -70,15 +158,15 @@ async fn ensure_authenticated() -> Result<()> {
         match client.login(&email, &password).await {
             Ok(tokens) => {
                 // Save tokens
+                let access_token = tokens.access_token.clone();
                 let mut new_settings = SettingsManager::load()?;
-                new_settings.access_token = Some(tokens.access_token.clone());
-                new_settings.refresh_token = Some(tokens.refresh_token);
+                new_settings.store_tokens(tokens);
                 new_settings.email = Some(email);
                 new_settings.password = Some(password);
                 SettingsManager::save(&new_settings)?;
 
                 // Get user info
-                if let Ok(user) = client.get_user_info(&tokens.access_token).await {
+                if let Ok(user) = client.get_user_info(&access_token).await {
                     new_settings.user_id = Some(user.id);
                     SettingsManager::save(&new_settings)?;
                 }
@@ -176,15 +264,15 @@ async fn register_interactive() -> Result<()> {
         .context("Registration failed")?;
 
     // Save tokens and credentials
+    let access_token = tokens.access_token.clone();
     let mut settings = SettingsManager::load()?;
-    settings.access_token = Some(tokens.access_token.clone());
-    settings.refresh_token = Some(tokens.refresh_token);
+    settings.store_tokens(tokens);
     settings.email = Some(email.clone());
     settings.password = Some(password.clone());
     SettingsManager::save(&settings)?;
 
     // Get user info
-    match client.get_user_info(&tokens.access_token).await {
+    match client.get_user_info(&access_token).await {
         Ok(user) => {
             settings.user_id = Some(user.id.clone());
             SettingsManager::save(&settings)?;
@@ -206,30 +294,113 @@ async fn register_interactive() -> Result<()> {
     Ok(())
 }
 
+/// The session's working directory: `--cwd` if given, else the shell's.
+///
+/// A relative `--cwd` is resolved here, since the daemon has its own current
+/// directory; absolute paths are passed on as written.
+fn session_cwd(cwd: Option<&Path>) -> Result<PathBuf> {
+    let Some(cwd) = cwd else {
+        // PWD keeps the path as the user typed it, symlinks included
+        return Ok(std::env::var("PWD")
+            .map(PathBuf::from)
+            .or_else(|_| std::env::current_dir())
+            .unwrap_or_else(|_| PathBuf::from("/")));
+    };
+
+    if !cwd.exists() {
+        anyhow::bail!(
+            "Working directory {} does not exist. Create it with: mkdir -p {}",
+            cwd.display(),
+            cwd.display()
+        );
+    }
+    if !cwd.is_dir() {
+        anyhow::bail!("Working directory {} is not a directory", cwd.display());
+    }
+    if cwd.is_absolute() {
+        Ok(cwd.to_path_buf())
+    } else {
+        Ok(std::env::current_dir()?.join(cwd))
+    }
+}
+
 async fn run_claude(options: RunOptions) -> Result<()> {
-    // Generate session tag
-    let tag = options.tag.clone().unwrap_or_else(generate_tag);
+    let profile = selected_profile(&options)?;
+    let usage = TokenUsage::open()?;
+    if let Some(ref profile) = profile {
+        check_budget(profile, &usage)?;
+    }
+    // `${HAPPY_SESSION_ID}` is left unexpanded
+    let env_vars = profile
+        .as_ref()
+        .map(profile_env_vars)
+        .transpose()?
+        .unwrap_or_default();
+    let cwd = session_cwd(options.cwd.as_deref())?;
 
     if options.remote {
+        // Sessions are spawned by the daemon, which doesn't sandbox yet
+        if options.sandbox.is_some() {
+            anyhow::bail!("--sandbox is not supported with --remote yet");
+        }
+
         // Remote mode: authenticate, start daemon, sync to cloud
-        run_claude_remote(&tag, options).await
+        let profile = profile.as_ref().map(|p| p.name.as_str());
+        run_claude_remote(options, &cwd, env_vars, profile).await
     } else {
         // Local mode: just run Claude in PTY directly
-        run_claude_local(&tag).await
+        let sandbox = selected_sandbox(&options)?;
+        let daemon_client = DaemonClient::connect().await?;
+        let tag = session_tag(&options, &cwd, &daemon_client).await;
+        let started = chrono::Utc::now();
+        let result = run_claude_local(&tag, &cwd, env_vars, sandbox).await;
+        if let Some(profile) = profile {
+            record_claude_usage(&usage, &profile.name, &cwd, started);
+        }
+        result
+    }
+}
+
+/// Add what a finished local session used to its profile's daily count
+fn record_claude_usage(
+    usage: &TokenUsage,
+    profile: &str,
+    cwd: &Path,
+    started: chrono::DateTime<chrono::Utc>,
+) {
+    let Some(projects_dir) = claude_projects_dir() else {
+        return;
+    };
+    let tokens = claude_tokens_since(&projects_dir, cwd, started);
+    if tokens == 0 {
+        return;
+    }
+    match usage.record(profile, tokens) {
+        Ok(total) => debug!("Profile {} has used {} tokens today", profile, total),
+        Err(e) => warn!("Failed to record token usage for {}: {}", profile, e),
     }
 }
 
 /// Local mode: Spawn Claude in PTY and interact directly in terminal
-async fn run_claude_local(_tag: &str) -> Result<()> {
+async fn run_claude_local(
+    _tag: &str,
+    cwd: &Path,
+    env_vars: Vec<(String, String)>,
+    sandbox: Option<SandboxMode>,
+) -> Result<()> {
     println!("{}", "🔹 Starting Claude Code...".blue());
-    println!();
 
     // Spawn PTY with claude process
-    run_local_pty(_tag).await
+    run_local_pty(_tag, cwd, env_vars, sandbox).await
 }
 
 /// Remote mode: Run with cloud sync
-async fn run_claude_remote(tag: &str, options: RunOptions) -> Result<()> {
+async fn run_claude_remote(
+    options: RunOptions,
+    cwd: &Path,
+    env_vars: Vec<(String, String)>,
+    profile: Option<&str>,
+) -> Result<()> {
     // Ensure user is authenticated
     ensure_authenticated().await?;
 
@@ -253,6 +424,9 @@ async fn run_claude_remote(tag: &str, options: RunOptions) -> Result<()> {
         .await
         .context("Failed to connect to daemon")?;
 
+    let tag = session_tag(&options, cwd, &daemon_client).await;
+    let tag = tag.as_str();
+
     println!(
         "{}",
         format!("🔹 Starting Claude Code session: {}", tag).blue()
@@ -261,14 +435,14 @@ async fn run_claude_remote(tag: &str, options: RunOptions) -> Result<()> {
     // Get machine name dynamically (use macOS ComputerName if available)
     let machine_name = get_machine_name();
 
-    // Get shell's current working directory (PWD env var is more reliable than current_dir)
-    let cwd = std::env::var("PWD")
-        .or_else(|_| std::env::current_dir().map(|p| p.to_string_lossy().to_string()))
-        .unwrap_or_else(|_| "/".to_string());
+    let cwd = cwd.to_string_lossy();
 
     // Register with cloud
     println!("{}", "🔹 Registering session with cloud...".blue().dimmed());
-    let api_client = crate::api::Client::new();
+    let api_client = crate::api::Client::new().with_retry(
+        crate::api::DEFAULT_RETRY_ATTEMPTS,
+        crate::api::DEFAULT_RETRY_BASE_DELAY_MS,
+    );
     let cloud_id = match api_client
         .create_session(
             settings.access_token.as_deref().unwrap_or_default(),
@@ -287,14 +461,9 @@ async fn run_claude_remote(tag: &str, options: RunOptions) -> Result<()> {
         }
     };
 
-    // Get shell's current working directory (PWD env var is more reliable than current_dir)
-    let cwd = std::env::var("PWD")
-        .or_else(|_| std::env::current_dir().map(|p| p.to_string_lossy().to_string()))
-        .unwrap_or_else(|_| "/".to_string());
-
     // Start session via daemon
     let session = daemon_client
-        .start_session(cloud_id, tag, &cwd)
+        .start_session(cloud_id, tag, &cwd, env_vars, profile)
         .await
         .context("Failed to start session")?;
 
@@ -323,7 +492,12 @@ async fn run_claude_remote(tag: &str, options: RunOptions) -> Result<()> {
 }
 
 /// Run a local PTY session with Claude
-async fn run_local_pty(tag: &str) -> Result<()> {
+async fn run_local_pty(
+    tag: &str,
+    cwd: &Path,
+    env_vars: Vec<(String, String)>,
+    sandbox: Option<SandboxMode>,
+) -> Result<()> {
     use nix::sys::termios::{self, SetArg};
     use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
     use std::io::{Read, Write};
@@ -341,14 +515,35 @@ async fn run_local_pty(tag: &str) -> Result<()> {
         pixel_height: 0,
     })?;
 
-    // Build command
-    let mut cmd = CommandBuilder::new("claude");
-    cmd.cwd(std::env::current_dir().unwrap_or_default());
+    // Build command, wrapped in the sandbox launcher if requested
+    let mut cmd = match sandbox {
+        Some(mode) => {
+            let (program, args) = Sandbox::new(mode)
+                .wrap("claude".as_ref(), &[])
+                .context("Failed to set up sandbox")?;
+            let mut cmd = CommandBuilder::new(program);
+            cmd.args(args);
+            cmd
+        }
+        None => CommandBuilder::new("claude"),
+    };
+    set_working_dir(&mut cmd, cwd);
     cmd.env("HAPPY_SESSION_TAG", tag);
     cmd.env("TERM", "xterm-256color");
 
+    // Local sessions aren't registered anywhere, so mint an ID for the profile env
+    let session_id = uuid::Uuid::new_v4().to_string();
+    for (key, value) in resolve_session_env(env_vars, &session_id) {
+        cmd.env(key, value);
+    }
+
     // Spawn the process
     let mut child = pair.slave.spawn_command(cmd)?;
+    // Only claim the sandbox once the launcher is actually running
+    if let Some(mode) = sandbox {
+        println!("{}", format!("🔒 Sandbox: {}", mode).blue().dimmed());
+    }
+    println!();
 
     // Get PTY reader/writer
     let mut reader = pair.master.try_clone_reader()?;
@@ -442,6 +637,13 @@ async fn run_local_pty(tag: &str) -> Result<()> {
     Ok(())
 }
 
+/// Start `cmd` in `cwd`. `PWD` is set to match, since agents read it and the
+/// inherited value names the shell's directory.
+fn set_working_dir(cmd: &mut portable_pty::CommandBuilder, cwd: &Path) {
+    cmd.cwd(cwd);
+    cmd.env("PWD", cwd);
+}
+
 /// Get current terminal size
 fn get_terminal_size() -> Result<(u16, u16)> {
     use std::io::IsTerminal;
@@ -465,30 +667,144 @@ async fn run_codex(_options: RunOptions) -> Result<()> {
     Ok(())
 }
 
-fn generate_tag() -> String {
-    use rand::Rng;
+/// Longest tag derived from a branch name, before any `-N` suffix
+const BRANCH_TAG_MAX_LEN: usize = 32;
+
+/// `--tag` when given, otherwise one derived from the current git branch
+async fn session_tag(options: &RunOptions, cwd: &Path, daemon_client: &DaemonClient) -> String {
+    if let Some(tag) = &options.tag {
+        return tag.clone();
+    }
+
+    let Some(base) = current_git_branch(cwd)
+        .map(|branch| branch_to_tag(&branch))
+        .filter(|tag| !tag.is_empty())
+    else {
+        let tag = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+        info!("Not on a git branch, using session tag: {}", tag);
+        return tag;
+    };
+
+    // The daemon reuses the tag of an exited session, so only live ones collide
+    let taken: HashSet<String> = match daemon_client.list_sessions().await {
+        Ok(sessions) => sessions
+            .into_iter()
+            .filter(|s| !matches!(s.status, SessionStatus::Exited))
+            .map(|s| s.tag)
+            .collect(),
+        Err(e) => {
+            debug!("Could not list daemon sessions: {}", e);
+            HashSet::new()
+        }
+    };
 
-    const ADJECTIVES: &[&str] = &[
-        "happy", "sunny", "bright", "calm", "cool", "swift", "brave", "wise", "kind", "bold",
-        "quick", "smart", "sharp", "fresh", "sweet", "warm", "purple", "golden", "silver",
-        "crimson", "azure", "vivid", "gentle",
-    ];
+    let tag = unique_tag(base, &taken);
+    info!("Using session tag from git branch: {}", tag);
+    tag
+}
 
-    const NOUNS: &[&str] = &[
-        "dog", "cat", "bird", "fish", "wolf", "bear", "lion", "tiger", "eagle", "hawk", "owl",
-        "fox", "deer", "rabbit", "turtle", "whale", "dolphin", "panda", "koala", "sloth", "otter",
-        "seal", "swan", "crane",
-    ];
+/// The checked-out branch in `cwd`, or `None` outside a repo or on a detached HEAD
+fn current_git_branch(cwd: &Path) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(cwd)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let branch = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!branch.is_empty() && branch != "HEAD").then_some(branch)
+}
 
-    let mut rng = rand::thread_rng();
-    let adj = ADJECTIVES[rng.gen_range(0..ADJECTIVES.len())];
-    let noun = NOUNS[rng.gen_range(0..NOUNS.len())];
-    let num: u16 = rng.gen_range(1..100);
+/// `feature/Login_v2!` → `feature-Login_v2`
+fn branch_to_tag(branch: &str) -> String {
+    let tag: String = branch
+        .replace('/', "-")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .take(BRANCH_TAG_MAX_LEN)
+        .collect();
+    tag.trim_matches('-').to_string()
+}
 
-    format!("{}-{}-{}", adj, noun, num)
+/// `base`, or `base-2`, `base-3`, ... for the first one not in `taken`
+fn unique_tag(base: String, taken: &HashSet<String>) -> String {
+    if !taken.contains(&base) {
+        return base;
+    }
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|tag| !taken.contains(tag))
+        .expect("unbounded suffixes")
 }
 
 /// Get machine name - prefer macOS ComputerName for user-friendly name
 fn get_machine_name() -> String {
     happy_core::utils::get_machine_name()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_to_tag() {
+        assert_eq!(branch_to_tag("main"), "main");
+        assert_eq!(branch_to_tag("feature/Login_v2!"), "feature-Login_v2");
+        assert_eq!(branch_to_tag("/fix/äöü"), "fix");
+        assert_eq!(branch_to_tag(&"x".repeat(40)).len(), BRANCH_TAG_MAX_LEN);
+    }
+
+    #[test]
+    fn test_unique_tag_appends_suffix() {
+        let taken: HashSet<String> = ["main", "main-2"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(unique_tag("dev".to_string(), &taken), "dev");
+        assert_eq!(unique_tag("main".to_string(), &taken), "main-3");
+    }
+
+    #[test]
+    fn test_profile_env_vars_only_pass_max_tokens() {
+        let mut profile = AIProfile::new("test", happy_core::AIProvider::Anthropic);
+        profile.max_tokens = Some(4096);
+        profile.temperature = Some(0.5);
+        profile.system_prompt = Some("Be terse".to_string());
+
+        assert_eq!(
+            profile_env_vars(&profile).unwrap(),
+            vec![("CLAUDE_CODE_MAX_OUTPUT_TOKENS".to_string(), "4096".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_agent_starts_in_cwd() {
+        use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = session_cwd(Some(dir.path())).unwrap();
+
+        let pair = NativePtySystem::default()
+            .openpty(PtySize::default())
+            .unwrap();
+        let mut cmd = CommandBuilder::new("printenv");
+        cmd.arg("PWD");
+        set_working_dir(&mut cmd, &cwd);
+        let mut child = pair.slave.spawn_command(cmd).unwrap();
+        drop(pair.slave);
+        child.wait().unwrap();
+
+        let mut reader = pair.master.try_clone_reader().unwrap();
+        let mut output = Vec::new();
+        // Linux reports EIO once the child side has closed
+        let _ = reader.read_to_end(&mut output);
+        assert_eq!(String::from_utf8_lossy(&output).trim(), cwd.to_string_lossy());
+
+        let missing = dir.path().join("missing");
+        let err = session_cwd(Some(&missing)).unwrap_err().to_string();
+        assert!(err.contains("mkdir -p"), "{}", err);
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(session_cwd(Some(&file)).is_err());
+    }
+}
@@ -0,0 +1,549 @@
+//! Session commands - Manage remote sessions
+
+use crate::api::Client;
+use crate::config::SettingsManager;
+use crate::daemon::multiplexer::{SessionStatus as LocalStatus, SessionSummary};
+use crate::daemon::recording::{self, CastEvent};
+use crate::daemon::DaemonClient;
+use crate::OutputFormat;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use colored::Colorize;
+use futures::StreamExt;
+use happy_types::{Session, SessionEvent, SessionStatus};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Length of the ID prefix shown in the table
+const SHORT_ID_LEN: usize = 8;
+
+/// Which sessions `happy session list` shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StatusFilter {
+    /// Everything that hasn't exited
+    Running,
+    /// Sessions whose process has exited
+    Terminated,
+    All,
+}
+
+/// A session from the local daemon, the server, or both
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionRow {
+    pub id: String,
+    pub tag: String,
+    pub status: String,
+    pub cwd: String,
+    pub created_at: DateTime<Utc>,
+    /// Known to the daemon on this machine
+    pub local: bool,
+    /// Registered with the server
+    pub remote: bool,
+}
+
+impl SessionRow {
+    fn from_local(session: SessionSummary) -> Self {
+        let status = match session.status {
+            LocalStatus::Running => "running",
+            LocalStatus::Detached => "detached",
+            LocalStatus::Exited => "terminated",
+        };
+        Self {
+            created_at: DateTime::parse_from_rfc3339(&session.created_at)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            id: session.id,
+            tag: session.tag,
+            status: status.to_string(),
+            cwd: session.working_dir.display().to_string(),
+            local: true,
+            remote: false,
+        }
+    }
+
+    fn from_remote(session: Session) -> Self {
+        Self {
+            status: match session.status {
+                SessionStatus::Terminated => "terminated".to_string(),
+                status => status.to_string(),
+            },
+            id: session.id,
+            tag: session.tag,
+            cwd: session.metadata.cwd,
+            created_at: session.created_at,
+            local: false,
+            remote: true,
+        }
+    }
+
+    fn matches(&self, filter: StatusFilter) -> bool {
+        let terminated = self.status == "terminated";
+        match filter {
+            StatusFilter::Running => !terminated,
+            StatusFilter::Terminated => terminated,
+            StatusFilter::All => true,
+        }
+    }
+}
+
+/// Local and remote sessions as one list, newest first.
+///
+/// Sessions the daemon relays to the server appear in both under the same ID;
+/// the daemon's entry wins since it knows whether the process is still alive.
+fn merge_sessions(
+    local: Vec<SessionSummary>,
+    remote: Vec<Session>,
+    filter: StatusFilter,
+) -> Vec<SessionRow> {
+    let mut rows: Vec<SessionRow> = local.into_iter().map(SessionRow::from_local).collect();
+    for session in remote {
+        match rows.iter_mut().find(|row| row.id == session.id) {
+            Some(row) => row.remote = true,
+            None => rows.push(SessionRow::from_remote(session)),
+        }
+    }
+    rows.retain(|row| row.matches(filter));
+    rows.sort_by_key(|row| std::cmp::Reverse(row.created_at));
+    rows
+}
+
+/// List sessions from the local daemon and, when logged in, the server
+pub async fn list(filter: StatusFilter, output: OutputFormat) -> Result<()> {
+    let local = match DaemonClient::connect().await?.list_sessions().await {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            tracing::debug!("Daemon unavailable: {}", e);
+            Vec::new()
+        }
+    };
+
+    let remote = if SettingsManager::load()?.access_token.is_some() {
+        let client = Client::new();
+        let result = match client.access_token().await {
+            Ok(token) => client.list_sessions(&token).await,
+            Err(e) => Err(e),
+        };
+        result.unwrap_or_else(|e| {
+            if output != OutputFormat::Json {
+                println!("{}", format!("⚠️  Remote sessions unavailable: {}", e).yellow());
+            }
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
+
+    let rows = merge_sessions(local, remote, filter);
+
+    if output == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+
+    println!("{}", "📋 Sessions".blue().bold());
+    println!();
+
+    if rows.is_empty() {
+        println!("   (No sessions)");
+        println!();
+        println!("   Start one with: {}", "happy run --remote".dimmed());
+        return Ok(());
+    }
+
+    let tag_width = column_width(rows.iter().map(|r| r.tag.as_str()), "Tag");
+    let status_width = column_width(rows.iter().map(|r| r.status.as_str()), "Status");
+    let cwd_width = column_width(rows.iter().map(|r| r.cwd.as_str()), "Directory");
+
+    // Pad before colouring so escape codes don't skew the columns
+    println!(
+        "   {}  {}  {}  {}  {}",
+        format!("{:<SHORT_ID_LEN$}", "ID").bold(),
+        format!("{:<tag_width$}", "Tag").bold(),
+        format!("{:<status_width$}", "Status").bold(),
+        format!("{:<cwd_width$}", "Directory").bold(),
+        "Uptime".bold(),
+    );
+    let now = Utc::now();
+    for row in &rows {
+        let status = format!("{:<status_width$}", row.status);
+        let status = match row.status.as_str() {
+            "terminated" => status.dimmed(),
+            "running" => status.green(),
+            _ => status.yellow(),
+        };
+        println!(
+            "   {}  {}  {}  {:<cwd_width$}  {}",
+            format!("{:<SHORT_ID_LEN$}", short_id(&row.id)).dimmed(),
+            format!("{:<tag_width$}", row.tag).cyan(),
+            status,
+            row.cwd,
+            format_uptime(now - row.created_at),
+        );
+    }
+
+    Ok(())
+}
+
+/// Kill a session running on this machine's daemon
+pub async fn kill(id_or_tag: &str, output: OutputFormat) -> Result<()> {
+    let daemon = DaemonClient::connect().await?;
+    let session = resolve_local(&daemon, id_or_tag).await?;
+    daemon.stop_session(&session.id).await?;
+
+    if output == OutputFormat::Json {
+        println!("{}", serde_json::json!({ "id": session.id, "killed": true }));
+    } else {
+        println!("{}", format!("✅ Session '{}' killed", session.tag).green());
+    }
+    Ok(())
+}
+
+/// Attach this terminal to a session running on this machine's daemon
+pub async fn attach(id_or_tag: &str, reconnect_attempts: u32) -> Result<()> {
+    let daemon = DaemonClient::connect()
+        .await?
+        .with_max_reconnect_attempts(reconnect_attempts);
+    let session = resolve_local(&daemon, id_or_tag).await?;
+    println!(
+        "{}",
+        format!("🔗 Attaching to '{}'...", session.tag).blue().dimmed()
+    );
+    daemon.attach_session(&session.id).await
+}
+
+pub async fn rename(id_or_tag: &str, new_tag: &str, output: OutputFormat) -> Result<()> {
+    let token = access_token().await?;
+    let client = Client::new();
+    let session = resolve(&client, &token, id_or_tag).await?;
+
+    client.rename_session(&token, &session.id, new_tag).await?;
+
+    if output == OutputFormat::Json {
+        println!(
+            "{}",
+            serde_json::json!({ "id": session.id, "tag": new_tag, "renamed": true })
+        );
+    } else {
+        println!(
+            "{}",
+            format!("✅ Session '{}' renamed to '{}'", session.tag, new_tag).green()
+        );
+    }
+    Ok(())
+}
+
+/// Move a session to another machine through this machine's daemon
+pub async fn transfer(id_or_tag: &str, machine: &str, output: OutputFormat) -> Result<()> {
+    let token = access_token().await?;
+    let client = Client::new();
+    let session = resolve(&client, &token, id_or_tag).await?;
+    if session.status == SessionStatus::Terminated {
+        anyhow::bail!("Session '{}' has ended and can't be transferred", session.tag);
+    }
+    let target = crate::commands::machine::resolve(&client, &token, machine).await?;
+    if !target.is_online {
+        anyhow::bail!("Machine '{}' is offline", target.name);
+    }
+
+    if output != OutputFormat::Json {
+        println!(
+            "{}",
+            format!("🚚 Moving '{}' to {}...", session.tag, target.name)
+                .blue()
+                .dimmed()
+        );
+    }
+    let daemon = DaemonClient::connect().await?;
+    let new_id = daemon.transfer_session(&session.id, &target.id).await?;
+
+    if output == OutputFormat::Json {
+        println!(
+            "{}",
+            serde_json::json!({ "id": session.id, "new_id": new_id, "machine_id": target.id })
+        );
+    } else {
+        let moved = format!(
+            "✅ Session '{}' now runs on {} as {}",
+            session.tag,
+            target.name,
+            short_id(&new_id)
+        );
+        println!("{}", moved.green());
+        println!(
+            "   The old session is still running; stop it with `happy session kill {}` on {}",
+            short_id(&session.id),
+            session.machine_name
+        );
+    }
+    Ok(())
+}
+
+/// Record a session on this machine's daemon until Ctrl+C
+pub async fn record(id_or_tag: &str, file: Option<PathBuf>, output: OutputFormat) -> Result<()> {
+    let daemon = DaemonClient::connect().await?;
+    let session = resolve_local(&daemon, id_or_tag).await?;
+    // The daemon has its own working directory
+    let file = file.map(std::path::absolute).transpose()?;
+    let path = daemon.start_recording(&session.id, file).await?;
+
+    if output != OutputFormat::Json {
+        let started = format!("⏺ Recording '{}' to {}", session.tag, path.display());
+        println!("{}", started.red());
+        println!("{}", "   Press Ctrl+C to stop".dimmed());
+    }
+    tokio::signal::ctrl_c().await?;
+
+    // The recording is already complete if the session ended first
+    let path = daemon.stop_recording(&session.id).await.unwrap_or(path);
+    if output == OutputFormat::Json {
+        println!("{}", serde_json::json!({ "id": session.id, "path": path }));
+    } else {
+        println!("{}", format!("✅ Recording saved to {}", path.display()).green());
+    }
+    Ok(())
+}
+
+/// Longest pause `--no-idle` leaves in a replay, in seconds
+const NO_IDLE_LIMIT: f64 = 1.0;
+
+/// Replay a cast file with its original timing, `speed` times faster
+pub async fn play(file: &Path, speed: f64, no_idle: bool) -> Result<()> {
+    if !(speed > 0.0 && speed.is_finite()) {
+        anyhow::bail!("--speed must be a positive number");
+    }
+    let text = tokio::fs::read_to_string(file)
+        .await
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let (header, events) = recording::parse_cast(&text)?;
+
+    let idle_limit = match (no_idle, header.idle_time_limit) {
+        (true, Some(limit)) => Some(limit.min(NO_IDLE_LIMIT)),
+        (true, None) => Some(NO_IDLE_LIMIT),
+        (false, limit) => limit,
+    };
+
+    let mut stdout = std::io::stdout();
+    for (event, delay) in events.iter().zip(playback_delays(&events, speed, idle_limit)) {
+        tokio::time::sleep(delay).await;
+        stdout.write_all(event.data.as_bytes())?;
+        stdout.flush()?;
+    }
+    println!();
+    Ok(())
+}
+
+/// Pause before each event, with gaps capped at `idle_limit` seconds and then
+/// divided by `speed`
+fn playback_delays(events: &[CastEvent], speed: f64, idle_limit: Option<f64>) -> Vec<Duration> {
+    let mut last_time = 0.0;
+    events
+        .iter()
+        .map(|event| {
+            let mut gap = (event.time - last_time).max(0.0);
+            last_time = event.time;
+            if let Some(limit) = idle_limit {
+                gap = gap.min(limit);
+            }
+            Duration::from_secs_f64(gap / speed)
+        })
+        .collect()
+}
+
+/// Follow the server's session event stream; JSON output is one event per line
+pub async fn events(output: OutputFormat) -> Result<()> {
+    let token = access_token().await?;
+    let client = Client::new();
+    let events = client.stream_session_events(&token).await?;
+    futures::pin_mut!(events);
+
+    if output != OutputFormat::Json {
+        println!("{}", "👀 Watching session events... (Ctrl+C to stop)".cyan());
+    }
+
+    while let Some(event) = events.next().await {
+        let event = event?;
+        if output == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&event)?);
+            continue;
+        }
+        match event {
+            SessionEvent::Created { session } => {
+                println!("{} {} ({})", "➕".green(), session.tag, session.status)
+            }
+            SessionEvent::Updated { session } => {
+                println!("{} {} ({})", "🔄".cyan(), session.tag, session.status)
+            }
+            SessionEvent::Deleted { session_id } => {
+                println!("{} {}", "🗑️".red(), session_id.dimmed())
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The saved access token, refreshed if it is about to expire
+async fn access_token() -> Result<String> {
+    Client::new().access_token().await
+}
+
+/// Find a server session by full ID, tag, or unique ID prefix
+async fn resolve(client: &Client, token: &str, id_or_tag: &str) -> Result<Session> {
+    let sessions = client.list_sessions(token).await?;
+    find_session(sessions, id_or_tag, |s| (&s.id, &s.tag))
+}
+
+/// Find a daemon session by full ID, tag, or unique ID prefix
+async fn resolve_local(daemon: &DaemonClient, id_or_tag: &str) -> Result<SessionSummary> {
+    let sessions = daemon.list_sessions().await.map_err(|e| {
+        anyhow::anyhow!("Daemon not reachable ({}). Start it with `happy daemon start`", e)
+    })?;
+    find_session(sessions, id_or_tag, |s| (&s.id, &s.tag))
+}
+
+fn find_session<T>(
+    sessions: Vec<T>,
+    id_or_tag: &str,
+    id_and_tag: impl Fn(&T) -> (&String, &String),
+) -> Result<T> {
+    let exact = sessions.iter().position(|s| {
+        let (id, tag) = id_and_tag(s);
+        id == id_or_tag || tag == id_or_tag
+    });
+    if let Some(index) = exact {
+        return Ok(sessions.into_iter().nth(index).expect("index in range"));
+    }
+
+    let mut matches = sessions
+        .into_iter()
+        .filter(|s| id_and_tag(s).0.starts_with(id_or_tag));
+    match (matches.next(), matches.next()) {
+        (Some(session), None) => Ok(session),
+        (Some(_), Some(_)) => anyhow::bail!("Session ID '{}' is ambiguous", id_or_tag),
+        (None, _) => anyhow::bail!("Session '{}' not found", id_or_tag),
+    }
+}
+
+/// Widest entry of a column, at least as wide as its header
+fn column_width<'a>(values: impl Iterator<Item = &'a str>, header: &str) -> usize {
+    values
+        .map(|v| v.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(header.len())
+}
+
+fn short_id(id: &str) -> &str {
+    id.get(..SHORT_ID_LEN).unwrap_or(id)
+}
+
+/// "3d 4h", "2h 5m", "12m" or "45s"
+fn format_uptime(uptime: chrono::Duration) -> String {
+    let secs = uptime.num_seconds().max(0);
+    let (days, hours, minutes) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(id: &str, status: LocalStatus, created_at: &str) -> SessionSummary {
+        SessionSummary {
+            id: id.to_string(),
+            tag: format!("tag-{}", id),
+            command: "claude".to_string(),
+            status,
+            created_at: created_at.to_string(),
+            last_activity: created_at.to_string(),
+            has_clients: false,
+            pid: None,
+            exit_code: None,
+            working_dir: "/work/app".into(),
+        }
+    }
+
+    fn remote(id: &str, status: SessionStatus) -> Session {
+        let mut session = Session::new(
+            id.to_string(),
+            format!("tag-{}", id),
+            "user".to_string(),
+            "machine".to_string(),
+            "laptop".to_string(),
+        );
+        session.status = status;
+        session.metadata.cwd = "/srv/other".to_string();
+        session
+    }
+
+    #[test]
+    fn test_merge_sessions_dedupes_and_filters() {
+        let local_sessions = vec![
+            local("aaaa", LocalStatus::Running, "2024-05-01T10:00:00Z"),
+            local("bbbb", LocalStatus::Exited, "2024-05-01T09:00:00Z"),
+        ];
+        let remote_sessions = vec![
+            remote("aaaa", SessionStatus::Running),
+            remote("cccc", SessionStatus::Terminated),
+        ];
+
+        let all = merge_sessions(
+            local_sessions.clone(),
+            remote_sessions.clone(),
+            StatusFilter::All,
+        );
+        let ids: Vec<&str> = all.iter().map(|r| r.id.as_str()).collect();
+        // The remote-only session was created just now, so it sorts first
+        assert_eq!(ids, ["cccc", "aaaa", "bbbb"]);
+        assert!(all[1].local && all[1].remote);
+        assert_eq!(all[1].cwd, "/work/app");
+        assert_eq!(all[0].cwd, "/srv/other");
+
+        let running = merge_sessions(
+            local_sessions.clone(),
+            remote_sessions.clone(),
+            StatusFilter::Running,
+        );
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].id, "aaaa");
+
+        let terminated = merge_sessions(local_sessions, remote_sessions, StatusFilter::Terminated);
+        assert_eq!(terminated.len(), 2);
+    }
+
+    #[test]
+    fn test_playback_delays() {
+        let events: Vec<CastEvent> = [0.5, 1.0, 31.0]
+            .iter()
+            .map(|&time| CastEvent {
+                time,
+                data: String::new(),
+            })
+            .collect();
+
+        let secs = |delays: Vec<Duration>| -> Vec<f64> {
+            delays.iter().map(Duration::as_secs_f64).collect()
+        };
+        assert_eq!(secs(playback_delays(&events, 1.0, None)), [0.5, 0.5, 30.0]);
+        assert_eq!(secs(playback_delays(&events, 2.0, None)), [0.25, 0.25, 15.0]);
+        assert_eq!(secs(playback_delays(&events, 2.0, Some(1.0))), [0.25, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(chrono::Duration::seconds(45)), "45s");
+        assert_eq!(format_uptime(chrono::Duration::seconds(12 * 60 + 5)), "12m");
+        assert_eq!(format_uptime(chrono::Duration::minutes(125)), "2h 5m");
+        assert_eq!(format_uptime(chrono::Duration::hours(76)), "3d 4h");
+    }
+}
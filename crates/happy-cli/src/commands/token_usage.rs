@@ -0,0 +1,235 @@
+//! Daily token use per profile, for `max_tokens_per_day`
+//!
+//! Agents talk to their provider directly, so usage is read back from Claude
+//! Code's transcripts and added to the profile it ran under: once a local
+//! `happy run` exits, and every minute for daemon sessions (see
+//! `daemon::budget`).
+
+use crate::config::SettingsManager;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use happy_core::AIProfile;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DailyUsage {
+    /// UTC day the counts belong to
+    date: Option<NaiveDate>,
+    profiles: BTreeMap<String, u64>,
+}
+
+/// Tokens used today by each profile, kept in `~/.happy/token_usage.json`.
+///
+/// Counts from an earlier UTC day read as zero, so budgets reset at midnight
+/// UTC without anything having to run then.
+pub struct TokenUsage {
+    path: PathBuf,
+}
+
+impl TokenUsage {
+    pub fn open() -> Result<Self> {
+        Ok(Self::at(SettingsManager::happy_home()?.join("token_usage.json")))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Tokens `profile` has used today
+    pub fn used_today(&self, profile: &str) -> Result<u64> {
+        Ok(self.today()?.get(profile).copied().unwrap_or(0))
+    }
+
+    /// Every profile with usage today
+    pub fn today(&self) -> Result<BTreeMap<String, u64>> {
+        let usage = self.load()?;
+        if usage.date == Some(Utc::now().date_naive()) {
+            Ok(usage.profiles)
+        } else {
+            Ok(BTreeMap::new())
+        }
+    }
+
+    /// Add `tokens` to today's count for `profile`, returning the new total
+    pub fn record(&self, profile: &str, tokens: u64) -> Result<u64> {
+        let mut profiles = self.today()?;
+        let used = profiles.entry(profile.to_string()).or_insert(0);
+        *used = used.saturating_add(tokens);
+        let total = *used;
+
+        self.save(&DailyUsage {
+            date: Some(Utc::now().date_naive()),
+            profiles,
+        })?;
+        Ok(total)
+    }
+
+    fn load(&self) -> Result<DailyUsage> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DailyUsage::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, usage: &DailyUsage) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(usage)?)?;
+        Ok(())
+    }
+}
+
+/// Refuse to start an agent under a profile that has spent its daily budget
+pub fn check_budget(profile: &AIProfile, usage: &TokenUsage) -> Result<()> {
+    let Some(budget) = profile.max_tokens_per_day else {
+        return Ok(());
+    };
+    let used = usage.used_today(&profile.name)?;
+    if used >= budget {
+        anyhow::bail!(
+            "budget_exceeded: profile '{}' has used {} of its {} tokens today. \
+             The budget resets at midnight UTC",
+            profile.name,
+            used,
+            budget
+        );
+    }
+    Ok(())
+}
+
+/// Where Claude Code keeps its transcripts
+pub fn claude_projects_dir() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("CLAUDE_CONFIG_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::home_dir()?.join(".claude"),
+    };
+    Some(config_dir.join("projects"))
+}
+
+/// Tokens Claude Code reported for turns in `cwd` since `since`.
+///
+/// Input and output tokens are counted; cache reads are not, since every turn
+/// re-reads the whole conversation. Transcripts that can't be read are skipped.
+pub fn claude_tokens_since(projects_dir: &Path, cwd: &Path, since: DateTime<Utc>) -> u64 {
+    // Claude Code names each project's directory after its path
+    let slug: String = cwd
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let Ok(entries) = std::fs::read_dir(projects_dir.join(slug)) else {
+        return 0;
+    };
+
+    // A response is logged once per content block, each with the same usage
+    let mut seen = HashSet::new();
+    let mut total = 0u64;
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().is_none_or(|ext| ext != "jsonl") {
+            continue;
+        }
+        let Ok(file) = std::fs::File::open(&path) else {
+            continue;
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            let Ok(entry) = serde_json::from_str::<serde_json::Value>(&line) else {
+                continue;
+            };
+            let after_start = entry["timestamp"]
+                .as_str()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .is_some_and(|t| t >= since);
+            let usage = &entry["message"]["usage"];
+            if !after_start || usage.is_null() {
+                continue;
+            }
+            if let Some(id) = entry["message"]["id"].as_str() {
+                if !seen.insert(id.to_string()) {
+                    continue;
+                }
+            }
+            let tokens = |field: &str| usage[field].as_u64().unwrap_or(0);
+            total = total.saturating_add(tokens("input_tokens") + tokens("output_tokens"));
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let usage = TokenUsage::at(dir.path().join("token_usage.json"));
+        let mut profile: AIProfile = serde_json::from_value(serde_json::json!({
+            "name": "work",
+            "provider": "anthropic",
+            "api_key": null,
+            "base_url": null,
+            "model": null,
+        }))
+        .unwrap();
+
+        assert_eq!(usage.used_today("work").unwrap(), 0);
+        assert_eq!(usage.record("work", 600).unwrap(), 600);
+        assert_eq!(usage.record("work", 500).unwrap(), 1100);
+        assert!(check_budget(&profile, &usage).is_ok());
+
+        profile.max_tokens_per_day = Some(1000);
+        let err = check_budget(&profile, &usage).unwrap_err();
+        assert!(err.to_string().starts_with("budget_exceeded"));
+
+        // Yesterday's counts don't carry over
+        std::fs::write(
+            &usage.path,
+            r#"{"date": "2000-01-01", "profiles": {"work": 5000}}"#,
+        )
+        .unwrap();
+        assert_eq!(usage.used_today("work").unwrap(), 0);
+        assert!(check_budget(&profile, &usage).is_ok());
+    }
+
+    #[test]
+    fn test_claude_tokens_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("-work-my-app");
+        std::fs::create_dir_all(&project).unwrap();
+        let turn = |id: &str, timestamp: &str, input: u64, output: u64| {
+            serde_json::json!({
+                "type": "assistant",
+                "timestamp": timestamp,
+                "message": {
+                    "id": id,
+                    "usage": {
+                        "input_tokens": input,
+                        "output_tokens": output,
+                        "cache_read_input_tokens": 90000,
+                    },
+                },
+            })
+            .to_string()
+        };
+        let transcript = [
+            turn("msg_old", "2024-05-01T09:00:00Z", 1000, 1000),
+            turn("msg_1", "2024-05-01T10:00:01Z", 100, 20),
+            // Second content block of the same response
+            turn("msg_1", "2024-05-01T10:00:02Z", 100, 20),
+            r#"{"type": "user", "timestamp": "2024-05-01T10:00:03Z"}"#.to_string(),
+            "not json".to_string(),
+            turn("msg_2", "2024-05-01T10:05:00Z", 300, 50),
+        ];
+        std::fs::write(project.join("session.jsonl"), transcript.join("\n")).unwrap();
+
+        let since = "2024-05-01T10:00:00Z".parse().unwrap();
+        let cwd = Path::new("/work/my_app");
+        assert_eq!(claude_tokens_since(dir.path(), cwd, since), 470);
+        assert_eq!(claude_tokens_since(dir.path(), Path::new("/elsewhere"), since), 0);
+    }
+}
@@ -0,0 +1,210 @@
+//! OS keychain storage for the secrets in `settings.json` (`use_keyring`)
+//!
+//! Tokens are kept under `happy-coding`/<user id> and profile API keys under
+//! `happy-coding-profile`/<profile name>. Whatever can't be stored stays in the
+//! file, so a machine without a keychain keeps working.
+//!
+//! Each save prunes the entries of a user or profile the new file no longer
+//! stashes, which is how logging out or deleting a profile clears its secret.
+//! `happy auth keyring disable` forgets every entry after writing the secrets
+//! back to the file.
+
+use happy_core::Settings;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+/// Keychain service holding a user's access and refresh tokens
+pub const TOKEN_SERVICE: &str = "happy-coding";
+
+/// Keychain service holding profile API keys
+pub const PROFILE_SERVICE: &str = "happy-coding-profile";
+
+/// Account looked up by `probe`; never written
+const PROBE_ACCOUNT: &str = "happy-doctor-probe";
+
+/// Both tokens, stored as one JSON keychain entry
+#[derive(Serialize, Deserialize)]
+struct StoredTokens {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+}
+
+/// Check that the keychain can be reached, without writing to it
+pub fn probe() -> Result<(), keyring::Error> {
+    match keyring::Entry::new(TOKEN_SERVICE, PROBE_ACCOUNT)?.get_password() {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Move the secrets in `settings` to the keychain, clearing each one that was
+/// stored. Whatever is left is written to the file as before; returns whether
+/// everything was stored.
+pub fn stash(settings: &mut Settings) -> bool {
+    let result = (|| {
+        if let Some(user_id) = &settings.user_id {
+            if settings.access_token.is_some() || settings.refresh_token.is_some() {
+                let tokens = StoredTokens {
+                    access_token: settings.access_token.take(),
+                    refresh_token: settings.refresh_token.take(),
+                };
+                if let Err(e) = set(TOKEN_SERVICE, user_id, &serde_json::to_string(&tokens)?) {
+                    settings.access_token = tokens.access_token;
+                    settings.refresh_token = tokens.refresh_token;
+                    return Err(e.into());
+                }
+            }
+        }
+        for profile in &mut settings.profiles {
+            if let Some(api_key) = &profile.api_key {
+                set(PROFILE_SERVICE, &profile.name, api_key)?;
+                profile.api_key = None;
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    })();
+
+    if let Err(e) = &result {
+        warn!(
+            "OS keychain unavailable ({}); keeping credentials in settings.json",
+            e
+        );
+    }
+    result.is_ok()
+}
+
+/// Fill the secrets `stash` moved out of `settings` back in
+pub fn restore(settings: &mut Settings) {
+    let result = (|| {
+        if let Some(user_id) = &settings.user_id {
+            if settings.access_token.is_none() && settings.refresh_token.is_none() {
+                if let Some(json) = get(TOKEN_SERVICE, user_id)? {
+                    if let Ok(tokens) = serde_json::from_str::<StoredTokens>(&json) {
+                        settings.access_token = tokens.access_token;
+                        settings.refresh_token = tokens.refresh_token;
+                    }
+                }
+            }
+        }
+        for profile in &mut settings.profiles {
+            if profile.api_key.is_none() {
+                profile.api_key = get(PROFILE_SERVICE, &profile.name)?;
+            }
+        }
+        Ok::<_, keyring::Error>(())
+    })();
+
+    if let Err(e) = result {
+        warn!("Failed to read credentials from the OS keychain: {}", e);
+    }
+}
+
+/// Remove the entries of the user and profiles in `previous` (the settings file
+/// about to be replaced) that `settings` no longer keeps in the keychain
+pub fn prune(previous: &Value, settings: &Settings) {
+    let (user_id, profiles) = stale_entries(previous, settings);
+    if let Some(user_id) = user_id {
+        delete(TOKEN_SERVICE, &user_id);
+    }
+    for name in profiles {
+        delete(PROFILE_SERVICE, &name);
+    }
+}
+
+/// Remove every entry `settings` could have in the keychain
+pub fn forget(settings: &Settings) {
+    if let Some(user_id) = &settings.user_id {
+        delete(TOKEN_SERVICE, user_id);
+    }
+    for profile in &settings.profiles {
+        delete(PROFILE_SERVICE, &profile.name);
+    }
+}
+
+/// The user and profile names `previous` could have stored that `settings`
+/// (already stashed) no longer does
+fn stale_entries(previous: &Value, settings: &Settings) -> (Option<String>, Vec<String>) {
+    let tokens_stashed = settings.access_token.is_none()
+        && settings.refresh_token.is_none()
+        && settings.user_id.is_some();
+    let user_id = previous["user_id"]
+        .as_str()
+        .filter(|id| !tokens_stashed || settings.user_id.as_deref() != Some(*id))
+        .map(String::from);
+
+    let profiles = previous["profiles"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|p| p["name"].as_str())
+        .filter(|name| {
+            !settings
+                .profiles
+                .iter()
+                .any(|p| p.name == *name && p.api_key.is_none())
+        })
+        .map(String::from)
+        .collect();
+
+    (user_id, profiles)
+}
+
+fn set(service: &str, account: &str, secret: &str) -> Result<(), keyring::Error> {
+    keyring::Entry::new(service, account)?.set_password(secret)
+}
+
+fn get(service: &str, account: &str) -> Result<Option<String>, keyring::Error> {
+    match keyring::Entry::new(service, account)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn delete(service: &str, account: &str) {
+    let result = keyring::Entry::new(service, account).and_then(|e| e.delete_credential());
+    if let Err(e) = result {
+        if !matches!(e, keyring::Error::NoEntry) {
+            warn!("Failed to remove {}/{} from the OS keychain: {}", service, account, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use happy_core::{AIProfile, AIProvider};
+    use serde_json::json;
+
+    fn profile(name: &str, api_key: Option<&str>) -> AIProfile {
+        AIProfile {
+            api_key: api_key.map(String::from),
+            ..AIProfile::new(name, AIProvider::Anthropic)
+        }
+    }
+
+    #[test]
+    fn test_stale_entries() {
+        let previous = json!({
+            "user_id": "u1",
+            "profiles": [{ "name": "work" }, { "name": "old" }, { "name": "plain" }],
+        });
+
+        // Still logged in, "old" was removed and "plain" no longer uses the keychain
+        let settings = Settings {
+            user_id: Some("u1".to_string()),
+            profiles: vec![profile("work", None), profile("plain", Some("sk-plain"))],
+            ..Settings::default()
+        };
+        let (user_id, profiles) = stale_entries(&previous, &settings);
+        assert_eq!(user_id, None);
+        assert_eq!(profiles, ["old", "plain"]);
+
+        // Logged out
+        let (user_id, _) = stale_entries(&previous, &Settings::default());
+        assert_eq!(user_id.as_deref(), Some("u1"));
+
+        assert_eq!(stale_entries(&json!({}), &settings), (None, vec![]));
+    }
+}
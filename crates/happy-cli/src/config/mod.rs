@@ -1,9 +1,46 @@
 //! Configuration management
 
+pub mod keychain;
+
 use anyhow::{Context, Result};
-use happy_core::Settings;
+use happy_core::{Settings, SETTINGS_VERSION};
+use serde_json::Value;
 use std::path::PathBuf;
 
+/// Version assumed for files written before `version` was tracked
+const UNVERSIONED: &str = "1.0.0";
+
+/// Rewrites settings JSON from one version's shape to the next
+type Migration = fn(Value) -> Value;
+
+/// Upgrade steps, each taking settings of the first version to the second
+const MIGRATIONS: &[(&str, &str, Migration)] = &[("1.0.0", "1.1.0", v1_0_0_to_v1_1_0)];
+
+/// Bring settings JSON written at `from_version` up to `SETTINGS_VERSION`
+pub fn migrate(mut raw: Value, from_version: &str) -> Result<Value> {
+    let mut version = from_version.to_string();
+    while version != SETTINGS_VERSION {
+        let Some((_, to, step)) = MIGRATIONS.iter().find(|(from, _, _)| *from == version) else {
+            anyhow::bail!(
+                "Settings version {} is not supported by this version of happy",
+                version
+            );
+        };
+        raw = step(raw);
+        raw["version"] = Value::from(*to);
+        version = to.to_string();
+    }
+    Ok(raw)
+}
+
+/// 1.1.0 keeps `machine_id` in its own file; `load` moves the old value there
+fn v1_0_0_to_v1_1_0(mut raw: Value) -> Value {
+    if let Some(settings) = raw.as_object_mut() {
+        settings.remove("machine_id");
+    }
+    raw
+}
+
 pub struct SettingsManager;
 
 impl SettingsManager {
@@ -44,6 +81,11 @@ impl SettingsManager {
         Ok(Self::happy_home()?.join("machine_id"))
     }
 
+    /// Get the path of the Ed25519 key that identifies this machine to the server
+    pub fn machine_signing_key_path() -> Result<PathBuf> {
+        Ok(Self::happy_home()?.join("machine_signing.key"))
+    }
+
     /// Load settings from disk
     pub fn load() -> Result<Settings> {
         let path = Self::settings_path()?;
@@ -57,14 +99,16 @@ impl SettingsManager {
                 .with_context(|| format!("Failed to read settings from {:?}", path))?
         };
 
-        // Check if we need migration (raw JSON contains machine_id)
-        let raw_val: serde_json::Value = serde_json::from_str(&content)?;
-        let is_old_format = raw_val.get("machine_id").is_some();
-
-        let mut settings: Settings = serde_json::from_str(&content)
+        let raw: Value = serde_json::from_str(&content)
             .with_context(|| format!("Failed to parse settings from {:?}", path))?;
+        let from_version = raw["version"].as_str().unwrap_or(UNVERSIONED).to_string();
+        // Files before 1.1.0 kept the machine ID inline
+        let inline_machine_id = raw["machine_id"].as_str().map(String::from);
 
-        // The machine_id is already skipped by #[serde(skip)]
+        let raw = migrate(raw, &from_version)
+            .with_context(|| format!("Failed to migrate settings in {:?}", path))?;
+        let mut settings: Settings = serde_json::from_value(raw)
+            .with_context(|| format!("Failed to parse settings from {:?}", path))?;
 
         // Load or generate machine_id
         let id_path = Self::machine_id_path()?;
@@ -74,19 +118,25 @@ impl SettingsManager {
                 .trim()
                 .to_string();
         } else {
-            settings.machine_id = uuid::Uuid::new_v4().to_string();
+            settings.machine_id = inline_machine_id
+                .filter(|id| !id.is_empty())
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
             std::fs::write(&id_path, &settings.machine_id)
                 .with_context(|| format!("Failed to write machine_id to {:?}", id_path))?;
         }
 
-        if !path.exists() || is_old_format {
+        if !path.exists() || from_version != SETTINGS_VERSION {
             Self::save(&settings)?;
         }
 
+        if settings.use_keyring {
+            keychain::restore(&mut settings);
+        }
+
         Ok(settings)
     }
 
-    /// Save settings to disk
+    /// Save settings to disk; with `use_keyring`, secrets go to the OS keychain instead
     pub fn save(settings: &Settings) -> Result<()> {
         let path = Self::settings_path()?;
 
@@ -96,8 +146,19 @@ impl SettingsManager {
                 .with_context(|| format!("Failed to create directory {:?}", parent))?;
         }
 
+        let mut on_disk = settings.clone();
+        if settings.use_keyring && keychain::stash(&mut on_disk) {
+            // Drop entries of a user who logged out or profiles that are gone
+            let previous = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<Value>(&content).ok());
+            if let Some(previous) = previous {
+                keychain::prune(&previous, &on_disk);
+            }
+        }
+
         let content =
-            serde_json::to_string_pretty(settings).context("Failed to serialize settings")?;
+            serde_json::to_string_pretty(&on_disk).context("Failed to serialize settings")?;
 
         std::fs::write(&path, content)
             .with_context(|| format!("Failed to write settings to {:?}", path))?;
@@ -117,3 +178,30 @@ impl SettingsManager {
 
 // Re-export from happy_core for convenience
 pub use happy_core::AIProvider;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_v1_0_0_to_v1_1_0_drops_inline_machine_id() {
+        let raw = json!({ "version": "1.0.0", "machine_id": "m-1", "profiles": [] });
+        assert_eq!(v1_0_0_to_v1_1_0(raw), json!({ "version": "1.0.0", "profiles": [] }));
+    }
+
+    #[test]
+    fn test_migrate_chain() {
+        let mut current = serde_json::to_value(Settings::default()).unwrap();
+        assert_eq!(migrate(current.clone(), SETTINGS_VERSION).unwrap(), current);
+
+        current["machine_id"] = json!("m-1");
+        current["version"] = json!("1.0.0");
+        let migrated = migrate(current, "1.0.0").unwrap();
+        assert_eq!(migrated["version"], SETTINGS_VERSION);
+        assert!(migrated.get("machine_id").is_none());
+        serde_json::from_value::<Settings>(migrated).unwrap();
+
+        assert!(migrate(json!({}), "9.0.0").is_err());
+    }
+}
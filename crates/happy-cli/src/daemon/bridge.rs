@@ -10,7 +10,46 @@ use tokio::sync::broadcast;
 use tracing::{debug, error, info, warn};
 
 // Import shared message types from happy_types
-use happy_types::{ClientMessage, ServerMessage};
+use happy_types::{Capability, CapabilitySet, ClientMessage, MachineInfo, Platform, ServerMessage};
+
+/// What this daemon offers, plus names listed in `HAPPY_CAPABILITIES`
+/// (comma-separated) by tools running alongside it
+fn local_capabilities() -> CapabilitySet {
+    let mut capabilities = CapabilitySet::from([Capability::Terminal, Capability::FileSystem]);
+    let extra = std::env::var("HAPPY_CAPABILITIES").unwrap_or_default();
+    for name in extra.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        match name.parse() {
+            Ok(capability) => {
+                capabilities.insert(capability);
+            }
+            Err(e) => warn!("Ignoring HAPPY_CAPABILITIES entry: {}", e),
+        }
+    }
+    capabilities
+}
+
+/// Describe this host for the server's machine registry
+fn local_machine_info(machine_id: &str, machine_name: &str) -> MachineInfo {
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+
+    MachineInfo {
+        id: machine_id.to_string(),
+        name: machine_name.to_string(),
+        platform: Platform::current(),
+        last_seen: chrono::Utc::now(),
+        is_online: true,
+        capabilities: local_capabilities(),
+        last_heartbeat: Some(chrono::Utc::now()),
+        daemon_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        arch: Some(std::env::consts::ARCH.to_string()),
+        hostname: sysinfo::System::host_name(),
+        cpu_count: std::thread::available_parallelism()
+            .ok()
+            .map(|n| n.get() as u32),
+        memory_mb: Some(system.total_memory() / (1024 * 1024)),
+    }
+}
 
 /// Bridge between Multiplexer and Remote WebSocket
 pub struct RemoteRelayBridge {
@@ -114,6 +153,7 @@ impl RemoteRelayBridge {
         // 3. Authenticate with WebSocket
         let auth_msg = ClientMessage::Authenticate {
             token: self.token.clone(),
+            binary_frames: true,
         };
         ws_sender
             .send(tokio_tungstenite::tungstenite::Message::Text(
@@ -140,12 +180,42 @@ impl RemoteRelayBridge {
 
         // 4. Attach to session as CLI bridge (Server side logic)
         // Use the cwd passed from CLI (user's shell PWD)
+        // The tag may have been renamed since this bridge was created
+        let (tag, agent_version) = match multiplexer.get_session(&self.session_id).await {
+            Some(session) => {
+                let metadata = session.read().await.get_metadata().await;
+                (metadata.tag, metadata.agent_version)
+            }
+            None => (self.tag.clone(), None),
+        };
+        // Sign so the server can tell this machine apart from anyone else holding the token
+        let (signing_key, signature) = match super::identity::load_or_create() {
+            Ok(keypair) => {
+                let (key, signature) = super::identity::sign_attach(
+                    &keypair,
+                    &self.session_id,
+                    &self.machine_id,
+                    &self.cwd,
+                );
+                (Some(key), Some(signature))
+            }
+            Err(e) => {
+                warn!("Attaching unsigned, machine signing key unavailable: {}", e);
+                (None, None)
+            }
+        };
         let attach_msg = ClientMessage::AttachSession {
             session_id: self.session_id.clone(),
-            tag: self.tag.clone(),
+            tag: tag.clone(),
             cwd: self.cwd.clone(),
             machine_id: Some(self.machine_id.clone()),
             machine_name: Some(self.machine_name.clone()),
+            agent_version,
+            home_dir: dirs::home_dir().map(|p| p.to_string_lossy().to_string()),
+            machine_info: Some(local_machine_info(&self.machine_id, &self.machine_name)),
+            capabilities: local_capabilities(),
+            signing_key,
+            signature,
         };
         ws_sender
             .send(tokio_tungstenite::tungstenite::Message::Text(
@@ -154,7 +224,7 @@ impl RemoteRelayBridge {
             .await?;
         info!(
             "Attaching to remote session: {} ({}) with cwd: {}",
-            self.tag, self.session_id, self.cwd
+            tag, self.session_id, self.cwd
         );
 
         // Wait for server response to AttachSession
@@ -205,6 +275,7 @@ impl RemoteRelayBridge {
         let ws_sender = Arc::new(tokio::sync::Mutex::new(ws_sender));
         let multiplexer_clone = multiplexer.clone();
         let session_id = self.session_id.clone();
+        let mut budget_rx = multiplexer.budget().subscribe();
 
         // Main bridge loop
         info!("Starting main bridge loop for session {}", session_id);
@@ -239,6 +310,27 @@ impl RemoteRelayBridge {
                     }
                 }
 
+                // Tell the web clients when this session runs out of budget
+                result = budget_rx.recv() => {
+                    if let Ok(notice) = result {
+                        if notice.session_id == session_id {
+                            let msg = ClientMessage::BudgetExceeded {
+                                session_id: notice.session_id,
+                                message: notice.message,
+                            };
+                            let msg_text = serde_json::to_string(&msg).unwrap_or_default();
+                            let mut sender = ws_sender.lock().await;
+                            if let Err(e) = sender
+                                .send(tokio_tungstenite::tungstenite::Message::Text(msg_text))
+                                .await
+                            {
+                                error!("Failed to send to WebSocket: {}", e);
+                                return Err(anyhow::anyhow!("Lost connection to server"));
+                            }
+                        }
+                    }
+                }
+
                 // Read from WebSocket and forward to Multiplexer
                 msg_opt = ws_receiver.next() => {
                     match msg_opt {
@@ -255,13 +347,20 @@ impl RemoteRelayBridge {
                                             let _ = multiplexer.send_input(&session_id, data).await;
                                         }
                                     }
-                                    ServerMessage::StartRemoteSession { request_id, machine_id, cwd, args } => {
+                                    ServerMessage::TerminalResize { session_id, cols, rows } => {
+                                        info!("Bridge resizing session {} to {}x{}", session_id, cols, rows);
+                                        if let Err(e) = multiplexer.resize_session(&session_id, cols, rows).await {
+                                            error!("Failed to resize session {}: {}", session_id, e);
+                                        }
+                                    }
+                                    ServerMessage::StartRemoteSession { request_id, machine_id, cwd, args, history } => {
                                         info!("Received StartRemoteSession request: request_id={}, machine_id={}, cwd={:?}", request_id, machine_id, cwd);
                                         handle_remote_session_request(
                                             request_id,
                                             machine_id,
                                             cwd,
                                             args,
+                                            history,
                                             &multiplexer_clone,
                                             ws_sender.clone(),
                                             self.bridge_spawner_tx.clone(),
@@ -278,6 +377,18 @@ impl RemoteRelayBridge {
                                         info!("Received SessionDeleted for session {}. Killing local session.", session_id);
                                         let _ = multiplexer.kill_session(&session_id).await;
                                     }
+                                    ServerMessage::SessionUpdated { session } if session.id == session_id => {
+                                        // Renamed from the CLI or web; keep the local tag in step
+                                        let current_tag = match multiplexer.get_session(&session_id).await {
+                                            Some(local) => Some(local.read().await.tag.clone()),
+                                            None => None,
+                                        };
+                                        if current_tag.is_some_and(|t| t != session.tag) {
+                                            if let Err(e) = multiplexer.rename_session(&session_id, &session.tag).await {
+                                                warn!("Failed to rename local session {}: {}", session_id, e);
+                                            }
+                                        }
+                                    }
                                     ServerMessage::GitStatusRequest { session_id, requester_id } => {
                                         info!("Received GitStatusRequest for session {} from {}", session_id, requester_id);
                                         handle_git_status_request(&session_id, &requester_id, &multiplexer_clone, ws_sender.clone()).await;
@@ -290,15 +401,59 @@ impl RemoteRelayBridge {
                                         info!("Received GitCommitRequest for session {} amend={} from {}", session_id, amend, requester_id);
                                         handle_git_commit_request(&session_id, &message, amend, &requester_id, &multiplexer_clone, ws_sender.clone()).await;
                                     }
+                                    ServerMessage::GitStageAllRequest { session_id, requester_id } => {
+                                        info!("Received GitStageAllRequest for session {} from {}", session_id, requester_id);
+                                        handle_git_stage_all_request(&session_id, true, &requester_id, &multiplexer_clone, ws_sender.clone()).await;
+                                    }
+                                    ServerMessage::GitUnstageAllRequest { session_id, requester_id } => {
+                                        info!("Received GitUnstageAllRequest for session {} from {}", session_id, requester_id);
+                                        handle_git_stage_all_request(&session_id, false, &requester_id, &multiplexer_clone, ws_sender.clone()).await;
+                                    }
+                                    ServerMessage::GitPushRequest { session_id, remote, branch, requester_id } => {
+                                        info!("Received GitPushRequest for session {} from {}", session_id, requester_id);
+                                        handle_git_sync_request(&session_id, GitSync::Push, remote, branch, &multiplexer_clone, ws_sender.clone()).await;
+                                    }
+                                    ServerMessage::GitPullRequest { session_id, remote, branch, requester_id } => {
+                                        info!("Received GitPullRequest for session {} from {}", session_id, requester_id);
+                                        handle_git_sync_request(&session_id, GitSync::Pull, remote, branch, &multiplexer_clone, ws_sender.clone()).await;
+                                    }
+                                    ServerMessage::StartRecordingRequest { session_id, requester_id } => {
+                                        info!("Received StartRecordingRequest for session {} from {}", session_id, requester_id);
+                                        handle_recording_request(&session_id, true, &multiplexer_clone, ws_sender.clone()).await;
+                                    }
+                                    ServerMessage::StopRecordingRequest { session_id, requester_id } => {
+                                        info!("Received StopRecordingRequest for session {} from {}", session_id, requester_id);
+                                        handle_recording_request(&session_id, false, &multiplexer_clone, ws_sender.clone()).await;
+                                    }
+                                    ServerMessage::WriteFileRequest { session_id, path, content, content_type, encoding, request_id } => {
+                                        info!("Received WriteFileRequest for session {} path {}", session_id, path);
+                                        handle_write_file_request(&session_id, &path, content, content_type, encoding, request_id, &multiplexer_clone, ws_sender.clone()).await;
+                                    }
+                                    ServerMessage::ServerUrlChanged { url } => {
+                                        // Takes effect the next time the daemon connects
+                                        match crate::commands::config::save_server_url(&url) {
+                                            Ok(_) => info!("Server URL updated remotely to {}", url),
+                                            Err(e) => warn!("Rejected remote server URL {}: {}", url, e),
+                                        }
+                                    }
                                     _ => {}
                                 }
                             } else if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
                                 handle_client_message(client_msg, &multiplexer, &session_id, ws_sender.clone()).await;
                             }
                         }
-                        Some(Ok(tokio_tungstenite::tungstenite::Message::Binary(_data))) => {
-                            // Binary messages should not be used for input anymore
-                            warn!("Bridge received unexpected binary message from server");
+                        Some(Ok(tokio_tungstenite::tungstenite::Message::Binary(frame))) => {
+                            match happy_types::wire::decode_binary(&frame) {
+                                Ok(ServerMessage::TerminalOutput { session_id, data }) => {
+                                    if let Ok(client_msg) = serde_json::from_slice::<ClientMessage>(&data) {
+                                        handle_client_message(client_msg, &multiplexer, &session_id, ws_sender.clone()).await;
+                                    } else {
+                                        let _ = multiplexer.send_input(&session_id, data).await;
+                                    }
+                                }
+                                Ok(_) => {}
+                                Err(e) => warn!("Bridge received malformed binary frame: {}", e),
+                            }
                         }
                         Some(Ok(tokio_tungstenite::tungstenite::Message::Close(_))) => {
                             info!("WebSocket closed by server");
@@ -328,11 +483,16 @@ impl RemoteRelayBridge {
 }
 
 /// Handle a remote session creation request from the web UI
+///
+/// `history` is the output of a session being transferred here; it becomes
+/// the start of the new session's scrollback.
+#[allow(clippy::too_many_arguments)]
 async fn handle_remote_session_request(
     request_id: String,
     machine_id: String,
     cwd: Option<String>,
     _args: Option<String>,
+    history: Vec<u8>,
     multiplexer: &Arc<super::multiplexer::SessionMultiplexer>,
     ws_sender: Arc<
         tokio::sync::Mutex<
@@ -381,6 +541,9 @@ async fn handle_remote_session_request(
     let response_msg = match result {
         Ok(session) => {
             let session_guard = session.read().await;
+            if !history.is_empty() {
+                session_guard.seed_history(&history).await;
+            }
             let session_id = session_guard.id.clone();
             let session_tag = session_guard.tag.clone();
             let metadata = session_guard.get_metadata().await;
@@ -400,8 +563,11 @@ async fn handle_remote_session_request(
                 last_activity: metadata.last_activity,
                 metadata: happy_types::SessionMetadata {
                     cwd: metadata.working_dir.to_string_lossy().to_string(),
+                    cwd_history: Vec::new(),
                     env: metadata.env_vars.into_iter().collect(),
                     claude_version: None,
+                    agent_version: metadata.agent_version,
+                    home_dir: dirs::home_dir().map(|p| p.to_string_lossy().to_string()),
                     shell: std::env::var("SHELL").unwrap_or_default(),
                 },
             };
@@ -477,7 +643,7 @@ fn generate_session_tag() -> String {
     format!("{}-{}-{}", adj, noun, num)
 }
 
-fn build_ws_url(server_url: &str) -> String {
+pub(crate) fn build_ws_url(server_url: &str) -> String {
     let mut ws_url = server_url.trim_end_matches('/').to_string();
     ws_url = ws_url
         .replace("https://", "wss://")
@@ -715,6 +881,255 @@ async fn handle_git_commit_request(
     }
 }
 
+/// Handle a file write relayed from a web client
+#[allow(clippy::too_many_arguments)]
+async fn handle_write_file_request(
+    session_id: &str,
+    path: &str,
+    content: Vec<u8>,
+    content_type: Option<String>,
+    encoding: Option<String>,
+    request_id: String,
+    multiplexer: &Arc<super::multiplexer::SessionMultiplexer>,
+    ws_sender: Arc<
+        tokio::sync::Mutex<
+            futures::stream::SplitSink<
+                tokio_tungstenite::WebSocketStream<
+                    tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+                >,
+                tokio_tungstenite::tungstenite::Message,
+            >,
+        >,
+    >,
+) {
+    let result = match multiplexer.get_session_cwd(session_id).await {
+        Ok(cwd) => {
+            super::file_write::write_file(
+                &cwd,
+                path,
+                content,
+                content_type.as_deref(),
+                encoding.as_deref(),
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    let (success, bytes_written, error) = match result {
+        Ok(bytes) => (true, bytes, None),
+        Err(e) => {
+            warn!("Failed to write {} for session {}: {:#}", path, session_id, e);
+            (false, 0, Some(format!("{:#}", e)))
+        }
+    };
+
+    let response = ClientMessage::FileWriteResponse {
+        session_id: session_id.to_string(),
+        path: path.to_string(),
+        success,
+        bytes_written,
+        request_id,
+        error,
+    };
+
+    let mut sender = ws_sender.lock().await;
+    if let Err(e) = sender
+        .send(tokio_tungstenite::tungstenite::Message::Text(
+            serde_json::to_string(&response).unwrap_or_default(),
+        ))
+        .await
+    {
+        error!("Failed to send file write result: {}", e);
+    }
+}
+
+/// Handle stage all (`stage == true`) or unstage all request
+async fn handle_git_stage_all_request(
+    session_id: &str,
+    stage: bool,
+    _requester_id: &str,
+    multiplexer: &Arc<super::multiplexer::SessionMultiplexer>,
+    ws_sender: Arc<
+        tokio::sync::Mutex<
+            futures::stream::SplitSink<
+                tokio_tungstenite::WebSocketStream<
+                    tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+                >,
+                tokio_tungstenite::tungstenite::Message,
+            >,
+        >,
+    >,
+) {
+    let cwd = match multiplexer.get_session_cwd(session_id).await {
+        Ok(path) => path,
+        Err(e) => {
+            error!("Failed to get session {} cwd: {}", session_id, e);
+            return;
+        }
+    };
+
+    let response = if stage {
+        let (success, message) = match git_operations::git_stage_all(&cwd).await {
+            Ok(output) => (true, output),
+            Err(e) => (false, format!("Stage all failed: {}", e)),
+        };
+        ClientMessage::GitStageAllResponse {
+            session_id: session_id.to_string(),
+            success,
+            message,
+        }
+    } else {
+        let (success, message) = match git_operations::git_unstage_all(&cwd).await {
+            Ok(output) => (true, output),
+            Err(e) => (false, format!("Unstage all failed: {}", e)),
+        };
+        ClientMessage::GitUnstageAllResponse {
+            session_id: session_id.to_string(),
+            success,
+            message,
+        }
+    };
+
+    let mut sender = ws_sender.lock().await;
+    if let Err(e) = sender
+        .send(tokio_tungstenite::tungstenite::Message::Text(
+            serde_json::to_string(&response).unwrap_or_default(),
+        ))
+        .await
+    {
+        error!("Failed to send git stage result: {}", e);
+    }
+}
+
+/// Start or stop recording a session to `~/.happy/recordings/` and report back
+async fn handle_recording_request(
+    session_id: &str,
+    start: bool,
+    multiplexer: &Arc<super::multiplexer::SessionMultiplexer>,
+    ws_sender: Arc<
+        tokio::sync::Mutex<
+            futures::stream::SplitSink<
+                tokio_tungstenite::WebSocketStream<
+                    tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+                >,
+                tokio_tungstenite::tungstenite::Message,
+            >,
+        >,
+    >,
+) {
+    let result = if start {
+        multiplexer.start_recording(session_id, None).await
+    } else {
+        multiplexer.stop_recording(session_id).await
+    };
+
+    let response = match result {
+        Ok(path) => ClientMessage::RecordingResponse {
+            session_id: session_id.to_string(),
+            recording: start,
+            path: Some(path.display().to_string()),
+            error: None,
+        },
+        Err(e) => {
+            warn!("Recording request for session {} failed: {}", session_id, e);
+            ClientMessage::RecordingResponse {
+                session_id: session_id.to_string(),
+                recording: multiplexer.is_recording(session_id).await,
+                path: None,
+                error: Some(e.to_string()),
+            }
+        }
+    };
+
+    let mut sender = ws_sender.lock().await;
+    if let Err(e) = sender
+        .send(tokio_tungstenite::tungstenite::Message::Text(
+            serde_json::to_string(&response).unwrap_or_default(),
+        ))
+        .await
+    {
+        error!("Failed to send recording result: {}", e);
+    }
+}
+
+/// Direction of a [`handle_git_sync_request`]
+#[derive(Debug, Clone, Copy)]
+enum GitSync {
+    Push,
+    Pull,
+}
+
+/// Handle push or pull request
+async fn handle_git_sync_request(
+    session_id: &str,
+    sync: GitSync,
+    remote: Option<String>,
+    branch: Option<String>,
+    multiplexer: &Arc<super::multiplexer::SessionMultiplexer>,
+    ws_sender: Arc<
+        tokio::sync::Mutex<
+            futures::stream::SplitSink<
+                tokio_tungstenite::WebSocketStream<
+                    tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+                >,
+                tokio_tungstenite::tungstenite::Message,
+            >,
+        >,
+    >,
+) {
+    let cwd = match multiplexer.get_session_cwd(session_id).await {
+        Ok(path) => path,
+        Err(e) => {
+            error!("Failed to get session {} cwd: {}", session_id, e);
+            return;
+        }
+    };
+
+    let remote = remote.as_deref();
+    let branch = branch.as_deref();
+    let response = match sync {
+        GitSync::Push => {
+            let (success, message) = match git_operations::git_push(&cwd, remote, branch).await {
+                Ok(output) => (true, output),
+                Err(e) => (false, format!("Push failed: {}", e)),
+            };
+            ClientMessage::GitPushResponse {
+                session_id: session_id.to_string(),
+                success,
+                message,
+                conflicts: Vec::new(),
+            }
+        }
+        GitSync::Pull => {
+            let (success, message, conflicts) =
+                match git_operations::git_pull(&cwd, remote, branch).await {
+                    Ok(output) => (true, output, Vec::new()),
+                    Err(e) => {
+                        let conflicts = git_operations::unmerged_files(&cwd).await;
+                        (false, format!("Pull failed: {}", e), conflicts)
+                    }
+                };
+            ClientMessage::GitPullResponse {
+                session_id: session_id.to_string(),
+                success,
+                message,
+                conflicts,
+            }
+        }
+    };
+
+    let mut sender = ws_sender.lock().await;
+    if let Err(e) = sender
+        .send(tokio_tungstenite::tungstenite::Message::Text(
+            serde_json::to_string(&response).unwrap_or_default(),
+        ))
+        .await
+    {
+        error!("Failed to send git {:?} result: {}", sync, e);
+    }
+}
+
 /// Git operations module
 mod git_operations {
     use std::path::Path;
@@ -768,23 +1183,27 @@ mod git_operations {
 
             let index_status = line.chars().next().unwrap_or(' ');
             let worktree_status = line.chars().nth(1).unwrap_or(' ');
-            let file_path = &line[3..];
+            // Renames and copies are reported as "old -> new"
+            let (source, file_path) = match line[3..].split_once(" -> ") {
+                Some((from, to)) => (Some(from), to),
+                None => (None, &line[3..]),
+            };
 
             match (index_status, worktree_status) {
-                ('M', ' ') | ('A', ' ') | ('D', ' ') | ('R', ' ') => {
+                ('M', ' ') | ('A', ' ') | ('D', ' ') | ('R', ' ') | ('C', ' ') => {
                     // Staged changes
                     staged.push(happy_types::ModifiedFile {
                         path: file_path.to_string(),
-                        change_type: map_change_type(index_status),
+                        change_type: map_change_type(index_status, source),
                         additions: 0,
                         deletions: 0,
                     });
                 }
-                ('M', 'M') | ('A', 'M') | ('D', 'M') => {
+                ('M', 'M') | ('A', 'M') | ('D', 'M') | ('R', 'M') | ('C', 'M') => {
                     // Both staged and unstaged
                     staged.push(happy_types::ModifiedFile {
                         path: file_path.to_string(),
-                        change_type: map_change_type(index_status),
+                        change_type: map_change_type(index_status, source),
                         additions: 0,
                         deletions: 0,
                     });
@@ -849,20 +1268,26 @@ mod git_operations {
         }
     }
 
-    fn map_change_type(status: char) -> happy_types::ChangeType {
+    fn map_change_type(status: char, source: Option<&str>) -> happy_types::ChangeType {
+        let from = source.unwrap_or_default().to_string();
         match status {
             'A' => happy_types::ChangeType::Added,
             'M' => happy_types::ChangeType::Modified,
             'D' => happy_types::ChangeType::Deleted,
-            'R' => happy_types::ChangeType::Renamed,
+            'R' => happy_types::ChangeType::Renamed { from },
+            'C' => happy_types::ChangeType::Copied { from },
+            'U' => happy_types::ChangeType::Unmerged,
+            '?' => happy_types::ChangeType::Untracked,
+            '!' => happy_types::ChangeType::Ignored,
             _ => happy_types::ChangeType::Modified,
         }
     }
 
     /// Get git diff for a specific file
     pub async fn get_git_diff(cwd: &Path, path: &str) -> anyhow::Result<String> {
+        // `--` keeps a path like `--output=...` from being read as an option
         let output = Command::new("git")
-            .args(["-C", cwd.to_str().unwrap_or("."), "diff", path])
+            .args(["-C", cwd.to_str().unwrap_or("."), "diff", "--", path])
             .output()
             .await?;
 
@@ -900,4 +1325,155 @@ mod git_operations {
 
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
+
+    /// Stage every change in the working tree, including untracked files
+    pub async fn git_stage_all(cwd: &Path) -> anyhow::Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(cwd)
+            .args(["add", "-A"])
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("{}", stderr);
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Push the current branch, or `branch` to `remote`
+    pub async fn git_push(
+        cwd: &Path,
+        remote: Option<&str>,
+        branch: Option<&str>,
+    ) -> anyhow::Result<String> {
+        run_remote_command(cwd, &["push"], remote, branch).await
+    }
+
+    /// Pull into the current branch, merging without opening an editor
+    pub async fn git_pull(
+        cwd: &Path,
+        remote: Option<&str>,
+        branch: Option<&str>,
+    ) -> anyhow::Result<String> {
+        run_remote_command(cwd, &["pull", "--no-edit"], remote, branch).await
+    }
+
+    /// Files with unresolved merge conflicts
+    pub async fn unmerged_files(cwd: &Path) -> Vec<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(cwd)
+            .args(["diff", "--name-only", "--diff-filter=U"])
+            .output()
+            .await;
+        match output {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Refuse a remote or branch name from a client that git would take for an
+    /// option, such as `--receive-pack=...`
+    fn check_ref_arg(kind: &str, name: &str) -> anyhow::Result<()> {
+        if name.starts_with('-') {
+            anyhow::bail!("Invalid {} name: {}", kind, name);
+        }
+        Ok(())
+    }
+
+    /// Run a push or pull; a `branch` without a `remote` goes to `origin`
+    async fn run_remote_command(
+        cwd: &Path,
+        args: &[&str],
+        remote: Option<&str>,
+        branch: Option<&str>,
+    ) -> anyhow::Result<String> {
+        if let Some(remote) = remote {
+            check_ref_arg("remote", remote)?;
+        }
+        if let Some(branch) = branch {
+            check_ref_arg("branch", branch)?;
+        }
+
+        let mut cmd = Command::new("git");
+        cmd.arg("-C").arg(cwd).args(args);
+        match (remote, branch) {
+            (Some(remote), branch) => {
+                cmd.arg(remote).args(branch);
+            }
+            (None, Some(branch)) => {
+                cmd.args(["origin", branch]);
+            }
+            (None, None) => {}
+        }
+        // No one is at the terminal to answer a credential prompt
+        cmd.env("GIT_TERMINAL_PROMPT", "0");
+
+        let output = cmd.output().await?;
+        // Push and pull report progress and results on stderr
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let combined = format!("{}{}", stdout, stderr).trim().to_string();
+
+        if !output.status.success() {
+            anyhow::bail!("{}", combined);
+        }
+
+        Ok(combined)
+    }
+
+    /// Move everything in the index back to the working tree
+    pub async fn git_unstage_all(cwd: &Path) -> anyhow::Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(cwd)
+            .args(["reset", "-q"])
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("{}", stderr);
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_client_args_are_not_options() {
+            let dir = tempfile::tempdir().unwrap();
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .arg("init")
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success());
+
+            // Read as a path, so nothing is written
+            let _ = get_git_diff(dir.path(), "--output=pwned").await;
+            assert!(!dir.path().join("pwned").exists());
+
+            let err = git_push(dir.path(), Some("--receive-pack=touch pwned"), None)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().starts_with("Invalid remote name"));
+            let err = git_pull(dir.path(), None, Some("--upload-pack=touch pwned"))
+                .await
+                .unwrap_err();
+            assert!(err.to_string().starts_with("Invalid branch name"));
+            assert!(!dir.path().join("pwned").exists());
+        }
+    }
 }
@@ -0,0 +1,219 @@
+//! Daily token budgets for daemon sessions
+//!
+//! Sessions started under a profile are tracked here. Every minute their
+//! usage is read back from Claude Code's transcripts and added to the
+//! profile's daily count in `token_usage.json`. Once a profile has spent its
+//! `max_tokens_per_day`, the multiplexer stops forwarding input to its
+//! sessions and their bridges tell the web clients with a `budget_exceeded`
+//! error. The block is lifted at midnight UTC, when the counts reset.
+
+use crate::commands::token_usage::{
+    check_budget, claude_projects_dir, claude_tokens_since, TokenUsage,
+};
+use crate::config::SettingsManager;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use happy_core::AIProfile;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{debug, info, warn};
+
+/// How often running sessions' usage is counted
+const UPDATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A session that ran out of budget, for its bridge to pass on
+#[derive(Debug, Clone)]
+pub struct BudgetExceeded {
+    pub session_id: String,
+    pub message: String,
+}
+
+struct TrackedSession {
+    profile: String,
+    cwd: PathBuf,
+    /// Transcript entries before this have been counted
+    counted_until: DateTime<Utc>,
+    exceeded: bool,
+}
+
+pub struct BudgetTracker {
+    usage: TokenUsage,
+    sessions: RwLock<HashMap<String, TrackedSession>>,
+    exceeded_tx: broadcast::Sender<BudgetExceeded>,
+}
+
+impl BudgetTracker {
+    pub fn new(usage: TokenUsage) -> Self {
+        let (exceeded_tx, _) = broadcast::channel(16);
+        Self {
+            usage,
+            sessions: RwLock::new(HashMap::new()),
+            exceeded_tx,
+        }
+    }
+
+    /// Refuse to start a session under a profile that has spent its budget
+    pub fn check(&self, profile: &AIProfile) -> Result<()> {
+        check_budget(profile, &self.usage)
+    }
+
+    /// Count the session's usage towards `profile` from now on
+    pub async fn track(&self, session_id: &str, profile: &str, cwd: &Path) {
+        self.sessions.write().await.insert(
+            session_id.to_string(),
+            TrackedSession {
+                profile: profile.to_string(),
+                cwd: cwd.to_path_buf(),
+                counted_until: Utc::now(),
+                exceeded: false,
+            },
+        );
+    }
+
+    pub async fn untrack(&self, session_id: &str) {
+        self.sessions.write().await.remove(session_id);
+    }
+
+    /// Whether input to the session is being held back
+    pub async fn is_exceeded(&self, session_id: &str) -> bool {
+        self.sessions
+            .read()
+            .await
+            .get(session_id)
+            .is_some_and(|s| s.exceeded)
+    }
+
+    /// Sessions that run out of budget from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<BudgetExceeded> {
+        self.exceeded_tx.subscribe()
+    }
+
+    /// Count what tracked sessions used since the last pass, then block the
+    /// ones whose profile is now over budget
+    pub async fn update(&self, projects_dir: &Path, profiles: &[AIProfile]) {
+        let mut sessions = self.sessions.write().await;
+        for session in sessions.values_mut() {
+            let since = std::mem::replace(&mut session.counted_until, Utc::now());
+            let tokens = claude_tokens_since(projects_dir, &session.cwd, since);
+            if tokens == 0 {
+                continue;
+            }
+            match self.usage.record(&session.profile, tokens) {
+                Ok(total) => debug!("Profile {} has used {} tokens today", session.profile, total),
+                Err(e) => warn!("Failed to record token usage for {}: {}", session.profile, e),
+            }
+        }
+
+        for (session_id, session) in sessions.iter_mut().filter(|(_, s)| !s.exceeded) {
+            let Some(profile) = profiles.iter().find(|p| p.name == session.profile) else {
+                continue;
+            };
+            if let Err(e) = check_budget(profile, &self.usage) {
+                info!("Holding input to session {}: {}", session_id, e);
+                session.exceeded = true;
+                // Nobody listening just means no bridge is connected
+                let _ = self.exceeded_tx.send(BudgetExceeded {
+                    session_id: session_id.clone(),
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Let input through again; the daily counts have reset
+    pub async fn reset(&self) {
+        for session in self.sessions.write().await.values_mut() {
+            session.exceeded = false;
+        }
+    }
+
+    /// Count usage every minute and lift blocks at midnight UTC, forever
+    pub async fn run(self: Arc<Self>) {
+        let resets = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(until_midnight_utc(Utc::now())).await;
+                info!("Resetting daily token budgets");
+                resets.reset().await;
+            }
+        });
+
+        let mut interval = tokio::time::interval(UPDATE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(projects_dir) = claude_projects_dir() else {
+                continue;
+            };
+            let profiles = match SettingsManager::load() {
+                Ok(settings) => settings.profiles,
+                Err(e) => {
+                    warn!("Failed to load profiles for budget check: {}", e);
+                    continue;
+                }
+            };
+            self.update(&projects_dir, &profiles).await;
+        }
+    }
+}
+
+/// Time left until the next midnight UTC after `now`
+fn until_midnight_utc(now: DateTime<Utc>) -> Duration {
+    let midnight = (now.date_naive() + chrono::Days::new(1))
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight is a valid time")
+        .and_utc();
+    (midnight - now).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_update_blocks_over_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let tracker = BudgetTracker::new(TokenUsage::at(dir.path().join("token_usage.json")));
+        let mut exceeded_rx = tracker.subscribe();
+        let mut profile = AIProfile::new("work", happy_core::AIProvider::Anthropic);
+        profile.max_tokens_per_day = Some(1000);
+
+        let cwd = Path::new("/work/app");
+        tracker.track("s1", "work", cwd).await;
+        tracker.track("s2", "other", cwd).await;
+
+        let projects = dir.path().join("projects");
+        let transcript = projects.join("-work-app");
+        std::fs::create_dir_all(&transcript).unwrap();
+        let turn = serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "message": {"id": "msg_1", "usage": {"input_tokens": 900, "output_tokens": 200}},
+        });
+        std::fs::write(transcript.join("session.jsonl"), turn.to_string()).unwrap();
+
+        tracker.update(&projects, std::slice::from_ref(&profile)).await;
+        assert!(tracker.is_exceeded("s1").await);
+        // A profile without a budget is only counted
+        assert!(!tracker.is_exceeded("s2").await);
+        let notice = exceeded_rx.try_recv().unwrap();
+        assert_eq!(notice.session_id, "s1");
+        assert!(notice.message.starts_with("budget_exceeded"));
+        assert!(exceeded_rx.try_recv().is_err());
+
+        // Already counted turns aren't counted again
+        tracker.update(&projects, &[profile]).await;
+        assert_eq!(tracker.usage.used_today("work").unwrap(), 1100);
+        assert!(exceeded_rx.try_recv().is_err());
+
+        tracker.reset().await;
+        assert!(!tracker.is_exceeded("s1").await);
+    }
+
+    #[test]
+    fn test_until_midnight_utc() {
+        let now = "2024-05-01T23:59:00Z".parse().unwrap();
+        assert_eq!(until_midnight_utc(now), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,207 @@
+//! File writes requested by web clients
+//!
+//! Content arrives as raw bytes or base64, may name a `charset` in its content
+//! type, and reaches disk through a temp file renamed over the target so readers
+//! never see a partial write. Paths are relative to the session's working
+//! directory and may not leave it.
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use std::path::{Component, Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// Decode `content`, write it below `cwd` and return the number of bytes on disk
+pub async fn write_file(
+    cwd: &Path,
+    path: &str,
+    content: Vec<u8>,
+    content_type: Option<&str>,
+    encoding: Option<&str>,
+) -> Result<u64> {
+    let target = resolve_in_cwd(cwd, path)?;
+    let bytes = apply_charset(decode_content(content, encoding)?, content_type)?;
+    write_atomic(&target, &bytes).await?;
+    Ok(bytes.len() as u64)
+}
+
+/// Resolve `path` against `cwd`, refusing anything that ends up outside it
+///
+/// The parent directory is canonicalized, so `..` and symlinked directories
+/// can't be used to escape either.
+fn resolve_in_cwd(cwd: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+    if relative.is_absolute() || relative.components().any(|c| c == Component::ParentDir) {
+        bail!("Path must stay inside the session directory: {}", path);
+    }
+    let file_name = relative
+        .file_name()
+        .with_context(|| format!("Not a file path: {}", path))?;
+
+    let root = cwd
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", cwd.display()))?;
+    let parent = cwd.join(relative.parent().unwrap_or(Path::new("")));
+    let parent = parent
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", parent.display()))?;
+    if !parent.starts_with(&root) {
+        bail!("Path must stay inside the session directory: {}", path);
+    }
+    Ok(parent.join(file_name))
+}
+
+/// Undo the wire encoding; no encoding means the bytes are used as-is
+fn decode_content(content: Vec<u8>, encoding: Option<&str>) -> Result<Vec<u8>> {
+    match encoding.map(str::to_ascii_lowercase).as_deref() {
+        None | Some("utf-8") | Some("utf8") | Some("binary") => Ok(content),
+        Some("base64") => {
+            let text: Vec<u8> = content
+                .into_iter()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(text)
+                .context("Invalid base64 content")
+        }
+        Some(other) => bail!("Unsupported encoding: {}", other),
+    }
+}
+
+/// Re-encode UTF-8 text into the `charset` named by `content_type`
+///
+/// `utf-16` gets a little-endian BOM; the explicit `utf-16le`/`utf-16be`
+/// labels are written without one.
+fn apply_charset(content: Vec<u8>, content_type: Option<&str>) -> Result<Vec<u8>> {
+    let Some(charset) = content_type.and_then(charset_param) else {
+        return Ok(content);
+    };
+    let (little_endian, bom) = match charset.as_str() {
+        "utf-8" | "utf8" | "us-ascii" => return Ok(content),
+        "utf-16" => (true, true),
+        "utf-16le" => (true, false),
+        "utf-16be" => (false, false),
+        other => bail!("Unsupported charset: {}", other),
+    };
+
+    let text = String::from_utf8(content).context("Content is not valid UTF-8 text")?;
+    let mut out = Vec::with_capacity(text.len() * 2 + 2);
+    let units = bom.then_some(0xFEFF).into_iter().chain(text.encode_utf16());
+    for unit in units {
+        if little_endian {
+            out.extend_from_slice(&unit.to_le_bytes());
+        } else {
+            out.extend_from_slice(&unit.to_be_bytes());
+        }
+    }
+    Ok(out)
+}
+
+/// The lowercased `charset` parameter of a MIME type, if present
+fn charset_param(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"').to_ascii_lowercase())
+    })
+}
+
+/// Write `content` to a sibling temp file, then rename it over `path`
+async fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Not a file path: {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let tmp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        uuid::Uuid::new_v4().simple()
+    ));
+
+    let result = async {
+        let mut file = tokio::fs::File::create(&tmp_path)
+            .await
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        file.write_all(content).await?;
+        file.sync_all().await?;
+        drop(file);
+
+        // Keep the mode of the file being replaced
+        if let Ok(meta) = tokio::fs::metadata(path).await {
+            tokio::fs::set_permissions(&tmp_path, meta.permissions()).await?;
+        }
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .with_context(|| format!("Failed to replace {}", path.display()))
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_base64() {
+        let decoded = decode_content(b"aGVs\nbG8=".to_vec(), Some("base64")).unwrap();
+        assert_eq!(decoded, b"hello");
+        assert!(decode_content(b"hello".to_vec(), Some("rot13")).is_err());
+    }
+
+    #[test]
+    fn test_apply_charset() {
+        let plain = apply_charset(b"hi".to_vec(), Some("text/plain; charset=UTF-8")).unwrap();
+        assert_eq!(plain, b"hi");
+
+        let utf16 = apply_charset(b"hi".to_vec(), Some("text/plain; charset=\"utf-16\"")).unwrap();
+        assert_eq!(utf16, [0xFF, 0xFE, b'h', 0, b'i', 0]);
+
+        let be = apply_charset(b"hi".to_vec(), Some("text/plain;charset=utf-16be")).unwrap();
+        assert_eq!(be, [0, b'h', 0, b'i']);
+
+        assert!(apply_charset(b"hi".to_vec(), Some("text/plain; charset=latin1")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_file_replaces_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("note.txt"), "old").unwrap();
+
+        let written = write_file(dir.path(), "note.txt", b"bmV3".to_vec(), None, Some("base64"))
+            .await
+            .unwrap();
+
+        assert_eq!(written, 3);
+        assert_eq!(std::fs::read_to_string(dir.path().join("note.txt")).unwrap(), "new");
+        // No temp file left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_resolve_in_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = dir.path().join("project");
+        std::fs::create_dir_all(cwd.join("src")).unwrap();
+        let root = cwd.canonicalize().unwrap();
+
+        assert_eq!(resolve_in_cwd(&cwd, "src/main.rs").unwrap(), root.join("src/main.rs"));
+        assert_eq!(resolve_in_cwd(&cwd, "./notes.md").unwrap(), root.join("notes.md"));
+
+        assert!(resolve_in_cwd(&cwd, "/etc/passwd").is_err());
+        assert!(resolve_in_cwd(&cwd, "../outside.txt").is_err());
+        assert!(resolve_in_cwd(&cwd, "src/../../outside.txt").is_err());
+        assert!(resolve_in_cwd(&cwd, ".").is_err());
+
+        // A symlink inside the session can't lead out of it
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), cwd.join("escape")).unwrap();
+            assert!(resolve_in_cwd(&cwd, "escape/outside.txt").is_err());
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Ed25519 identity the daemon signs `AttachSession` with
+//!
+//! The server pins the first key it sees for a machine, so a leaked access token
+//! alone cannot attach sessions as that machine.
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use happy_remote_core::crypto::EncryptionEngine;
+use happy_remote_core::NaClEngine;
+use happy_types::{attach_signing_payload, EdKeyPair};
+use std::path::Path;
+
+use crate::config::SettingsManager;
+
+/// Load the machine's signing key, generating and saving one on first use
+pub fn load_or_create() -> Result<EdKeyPair> {
+    load_or_create_at(&SettingsManager::machine_signing_key_path()?)
+}
+
+fn load_or_create_at(path: &Path) -> Result<EdKeyPair> {
+    if path.exists() {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read signing key from {:?}", path))?;
+        let Ok(secret) = <[u8; 64]>::try_from(bytes.as_slice()) else {
+            bail!("Signing key {:?} is corrupt; delete it to generate a new one", path);
+        };
+        // libsodium secret keys end with the public key
+        let mut public = [0u8; 32];
+        public.copy_from_slice(&secret[32..]);
+        return Ok(EdKeyPair { public, secret });
+    }
+
+    let keypair = NaClEngine::new().generate_signing_keypair();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, keypair.secret)
+        .with_context(|| format!("Failed to write signing key to {:?}", path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(keypair)
+}
+
+/// Base64 `(signing_key, signature)` for an `AttachSession` message
+pub fn sign_attach(
+    keypair: &EdKeyPair,
+    session_id: &str,
+    machine_id: &str,
+    cwd: &str,
+) -> (String, String) {
+    let payload = attach_signing_payload(session_id, machine_id, cwd);
+    let signature = NaClEngine::new().sign(&payload, &keypair.secret);
+    let b64 = base64::engine::general_purpose::STANDARD;
+    (b64.encode(keypair.public), b64.encode(signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_persists_and_signs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("machine_signing.key");
+
+        let first = load_or_create_at(&path).unwrap();
+        let again = load_or_create_at(&path).unwrap();
+        assert_eq!(first.public, again.public);
+
+        let (key, signature) = sign_attach(&again, "s1", "m1", "/tmp");
+        let b64 = base64::engine::general_purpose::STANDARD;
+        let key: [u8; 32] = b64.decode(key).unwrap().try_into().unwrap();
+        let signature: [u8; 64] = b64.decode(signature).unwrap().try_into().unwrap();
+        let payload = attach_signing_payload("s1", "m1", "/tmp");
+        assert!(NaClEngine::new().verify(&payload, &signature, &key).is_ok());
+    }
+}
@@ -4,15 +4,24 @@ use anyhow::{Context, Result};
 use std::process::Stdio;
 use tokio::process::Command;
 
+use multiplexer::SessionSummary;
+
 pub mod bridge;
+pub mod budget;
 pub mod error;
+pub mod file_write;
+pub mod identity;
 pub mod metrics;
 pub mod multiplexer;
 pub mod persistence;
+pub mod process;
+pub mod reconnect;
+pub mod recording;
 pub mod rpc;
 pub mod rpc_server;
 pub mod server;
 pub mod session_manager;
+pub mod transfer;
 
 pub struct DaemonManager;
 
@@ -25,17 +34,7 @@ impl DaemonManager {
         if let Ok(pid_path) = crate::config::SettingsManager::pid_path() {
             if let Ok(pid_str) = tokio::fs::read_to_string(&pid_path).await {
                 if let Ok(pid) = pid_str.trim().parse::<u32>() {
-                    // Check if process exists
-                    #[cfg(unix)]
-                    {
-                        return unsafe { libc::kill(pid as i32, 0) == 0 };
-                    }
-                    #[cfg(windows)]
-                    {
-                        use sysinfo::{ProcessExt, System, SystemExt};
-                        let s = System::new_all();
-                        return s.process(sysinfo::Pid::from(pid as usize)).is_some();
-                    }
+                    return process::is_running(pid);
                 }
             }
         }
@@ -66,6 +65,14 @@ impl DaemonManager {
         {
             cmd.process_group(0);
         }
+        // Own process group and no console, so Ctrl+C in this terminal doesn't reach it
+        #[cfg(windows)]
+        {
+            use windows_sys::Win32::System::Threading::{
+                CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS,
+            };
+            cmd.creation_flags(CREATE_NEW_PROCESS_GROUP | DETACHED_PROCESS);
+        }
 
         let child = cmd.spawn().context("Failed to spawn daemon process")?;
 
@@ -84,21 +91,7 @@ impl DaemonManager {
 
         if let Ok(pid_str) = tokio::fs::read_to_string(&pid_path).await {
             if let Ok(pid) = pid_str.trim().parse::<u32>() {
-                #[cfg(unix)]
-                {
-                    unsafe {
-                        libc::kill(pid as i32, libc::SIGTERM);
-                    }
-                }
-                #[cfg(windows)]
-                {
-                    use sysinfo::{ProcessExt, System, SystemExt};
-                    let mut s = System::new_all();
-                    s.refresh_all();
-                    if let Some(process) = s.process(sysinfo::Pid::from(pid as usize)) {
-                        process.kill();
-                    }
-                }
+                process::terminate(pid);
             }
         }
 
@@ -112,17 +105,32 @@ impl DaemonManager {
 /// Client for communicating with the daemon
 pub struct DaemonClient {
     rpc_port: u16,
+    /// Times `attach_session` tries to reconnect before giving up
+    max_reconnect_attempts: u32,
 }
 
 impl DaemonClient {
     pub async fn connect() -> Result<Self> {
         Ok(Self {
             rpc_port: 16792, // TODO: Get from settings
+            max_reconnect_attempts: reconnect::DEFAULT_MAX_ATTEMPTS,
         })
     }
 
+    pub fn with_max_reconnect_attempts(mut self, attempts: u32) -> Self {
+        self.max_reconnect_attempts = attempts;
+        self
+    }
+
     /// Start a new session on the server via Daemon RPC
-    pub async fn start_session(&self, id: Option<String>, tag: &str, cwd: &str) -> Result<SessionInfo> {
+    pub async fn start_session(
+        &self,
+        id: Option<String>,
+        tag: &str,
+        cwd: &str,
+        env_vars: Vec<(String, String)>,
+        profile: Option<&str>,
+    ) -> Result<SessionInfo> {
         // We need to resolve the token locally first to send it to Daemon
         // Or should Daemon resolve it?
         // The Daemon might be running as a different user (system service)? No, usually same user.
@@ -131,9 +139,7 @@ impl DaemonClient {
 
         let settings = crate::config::SettingsManager::load().context("Failed to load settings")?;
 
-        let token = settings
-            .access_token
-            .ok_or_else(|| anyhow::anyhow!("Not authenticated"))?;
+        let token = crate::api::Client::new().access_token().await?;
 
         // Also need server URL
         let server_url = settings.server_url; // Assuming this field exists or similar
@@ -144,6 +150,8 @@ impl DaemonClient {
             token,
             server_url,
             cwd: cwd.to_string(),
+            env_vars,
+            profile: profile.map(str::to_string),
         };
 
         match self.send_rpc(request).await? {
@@ -155,6 +163,73 @@ impl DaemonClient {
         }
     }
 
+    /// Sessions the daemon currently knows about
+    pub async fn list_sessions(&self) -> Result<Vec<SessionSummary>> {
+        match self.send_rpc(rpc::DaemonRequest::ListSessions).await? {
+            rpc::DaemonResponse::Sessions(sessions) => Ok(sessions),
+            rpc::DaemonResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+            _ => anyhow::bail!("Unexpected response from daemon"),
+        }
+    }
+
+    /// Kill a session and stop relaying it to the server
+    pub async fn stop_session(&self, session_id: &str) -> Result<()> {
+        let request = rpc::DaemonRequest::StopSession {
+            session_id: session_id.to_string(),
+        };
+        match self.send_rpc(request).await? {
+            rpc::DaemonResponse::Ok => Ok(()),
+            rpc::DaemonResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+            _ => anyhow::bail!("Unexpected response from daemon"),
+        }
+    }
+
+    /// Move a session to another machine, returning the replacement session's ID
+    pub async fn transfer_session(
+        &self,
+        session_id: &str,
+        target_machine_id: &str,
+    ) -> Result<String> {
+        let request = rpc::DaemonRequest::TransferSession {
+            session_id: session_id.to_string(),
+            target_machine_id: target_machine_id.to_string(),
+        };
+        match self.send_rpc(request).await? {
+            rpc::DaemonResponse::SessionStarted { session_id } => Ok(session_id),
+            rpc::DaemonResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+            _ => anyhow::bail!("Unexpected response from daemon"),
+        }
+    }
+
+    /// Start recording a session, returning the cast file being written
+    pub async fn start_recording(
+        &self,
+        session_id: &str,
+        output: Option<std::path::PathBuf>,
+    ) -> Result<std::path::PathBuf> {
+        let request = rpc::DaemonRequest::StartRecording {
+            session_id: session_id.to_string(),
+            output,
+        };
+        match self.send_rpc(request).await? {
+            rpc::DaemonResponse::Recording { path } => Ok(path),
+            rpc::DaemonResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+            _ => anyhow::bail!("Unexpected response from daemon"),
+        }
+    }
+
+    /// Stop recording a session, returning the finished cast file
+    pub async fn stop_recording(&self, session_id: &str) -> Result<std::path::PathBuf> {
+        let request = rpc::DaemonRequest::StopRecording {
+            session_id: session_id.to_string(),
+        };
+        match self.send_rpc(request).await? {
+            rpc::DaemonResponse::Recording { path } => Ok(path),
+            rpc::DaemonResponse::Error(e) => anyhow::bail!("Daemon error: {}", e),
+            _ => anyhow::bail!("Unexpected response from daemon"),
+        }
+    }
+
     async fn send_rpc(&self, request: rpc::DaemonRequest) -> Result<rpc::DaemonResponse> {
         use tokio::io::{AsyncReadExt, AsyncWriteExt};
         use tokio::net::TcpStream;
@@ -177,6 +252,34 @@ impl DaemonClient {
         Ok(response)
     }
 
+    /// Open a WebSocket to the daemon and attach it to `session_id`
+    async fn open_attach_socket(
+        ws_url: &str,
+        session_id: &str,
+    ) -> Result<
+        tokio_tungstenite::WebSocketStream<
+            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+        >,
+    > {
+        use futures::SinkExt;
+        use tokio_tungstenite::tungstenite::Message;
+
+        let (mut ws_stream, _) = tokio_tungstenite::connect_async(ws_url)
+            .await
+            .context("Failed to connect to daemon WebSocket")?;
+        let attach_msg = crate::daemon::server::ClientMessage::AttachSession {
+            session_id: session_id.to_string(),
+        };
+        ws_stream
+            .send(Message::Text(serde_json::to_string(&attach_msg)?))
+            .await?;
+        Ok(ws_stream)
+    }
+
+    /// Attach this terminal to a session until the user detaches or it ends.
+    ///
+    /// If the connection to the daemon drops, it is reopened with backoff and
+    /// the screen redrawn from the session's buffer.
     pub async fn attach_session(&self, session_id: &str) -> Result<()> {
         use futures::{SinkExt, StreamExt};
         use nix::sys::termios::{self, SetArg};
@@ -187,17 +290,9 @@ impl DaemonClient {
         let port = crate::commands::config::get_daemon_port().await;
         let ws_url = format!("ws://127.0.0.1:{}", port);
 
-        let (ws_stream, _) = tokio_tungstenite::connect_async(&ws_url)
-            .await
-            .context("Failed to connect to daemon WebSocket")?;
+        let ws_stream = Self::open_attach_socket(&ws_url, session_id).await?;
         let (mut ws_tx, mut ws_rx) = ws_stream.split();
-
-        let attach_msg = crate::daemon::server::ClientMessage::AttachSession {
-            session_id: session_id.to_string(),
-        };
-        ws_tx
-            .send(Message::Text(serde_json::to_string(&attach_msg)?))
-            .await?;
+        let mut reconnect = reconnect::ReconnectState::new(self.max_reconnect_attempts);
 
         let stdin_handle = std::io::stdin();
         let stdin_fd = stdin_handle.as_fd();
@@ -230,102 +325,142 @@ impl DaemonClient {
         let mut stdout = tokio::io::stdout();
         let mut input_buf = [0u8; 4096];
 
-        loop {
-            tokio::select! {
-                msg = ws_rx.next() => {
-                    let msg = match msg {
-                        Some(Ok(m)) => m,
-                        Some(Err(e)) => {
-                            tracing::error!("WebSocket receive error: {}", e);
-                            break;
-                        }
-                        None => {
-                            tracing::info!("WebSocket stream ended");
-                            break;
-                        }
-                    };
-                    match msg {
-                        Message::Text(text) => {
-                            if let Ok(server_msg) = serde_json::from_str::<crate::daemon::server::ServerMessage>(&text) {
-                                match server_msg {
-                                    crate::daemon::server::ServerMessage::SessionAttached { buffer, .. } => {
-                                        tracing::info!("Session attached, buffer len: {}", buffer.len());
-                                        if !buffer.is_empty() {
-                                            stdout.write_all(buffer.as_bytes()).await?;
+        'session: loop {
+            loop {
+                tokio::select! {
+                    msg = ws_rx.next() => {
+                        let msg = match msg {
+                            Some(Ok(m)) => m,
+                            Some(Err(e)) => {
+                                tracing::error!("WebSocket receive error: {}", e);
+                                break;
+                            }
+                            None => {
+                                tracing::info!("WebSocket stream ended");
+                                break;
+                            }
+                        };
+                        match msg {
+                            Message::Text(text) => {
+                                if let Ok(server_msg) = serde_json::from_str::<crate::daemon::server::ServerMessage>(&text) {
+                                    match server_msg {
+                                        crate::daemon::server::ServerMessage::SessionAttached { buffer, .. } => {
+                                            tracing::info!("Session attached, buffer len: {}", buffer.len());
+                                            if reconnect.is_reconnecting() {
+                                                // Redraw from the buffer rather than after what's on screen
+                                                stdout.write_all(b"\x1b[2J\x1b[H").await?;
+                                                reconnect.reset();
+                                            }
+                                            if !buffer.is_empty() {
+                                                stdout.write_all(buffer.as_bytes()).await?;
+                                                stdout.flush().await?;
+                                            }
+                                        }
+                                        crate::daemon::server::ServerMessage::Output { data } => {
+                                            stdout.write_all(data.as_bytes()).await?;
                                             stdout.flush().await?;
                                         }
+                                        crate::daemon::server::ServerMessage::Error { message } => {
+                                            eprintln!("Error: {}", message);
+                                        }
+                                        crate::daemon::server::ServerMessage::SessionDetached { .. } => {
+                                            tracing::info!("Session detached");
+                                            break 'session;
+                                        }
+                                        crate::daemon::server::ServerMessage::SessionKilled { .. } => {
+                                            tracing::info!("Session killed");
+                                            break 'session;
+                                        }
+                                        _ => {}
                                     }
-                                    crate::daemon::server::ServerMessage::Output { data } => {
-                                        stdout.write_all(data.as_bytes()).await?;
-                                        stdout.flush().await?;
-                                    }
-                                    crate::daemon::server::ServerMessage::Error { message } => {
-                                        eprintln!("Error: {}", message);
-                                    }
-                                    crate::daemon::server::ServerMessage::SessionDetached { .. } => {
-                                        tracing::info!("Session detached");
-                                        break;
-                                    }
-                                    crate::daemon::server::ServerMessage::SessionKilled { .. } => {
-                                        tracing::info!("Session killed");
-                                        break;
-                                    }
-                                    _ => {}
                                 }
                             }
-                        }
-                        Message::Binary(data) => {
-                            stdout.write_all(&data).await?;
-                            stdout.flush().await?;
-                        }
-                        Message::Close(_) => {
-                            tracing::info!("WebSocket close received");
-                            break;
-                        }
-                        _ => {}
-                    }
-                }
-                read = stdin.read(&mut input_buf) => {
-                    match read {
-                        Ok(n) => {
-                            if n == 0 {
-                                tracing::info!("stdin EOF");
+                            Message::Binary(data) => {
+                                stdout.write_all(&data).await?;
+                                stdout.flush().await?;
+                            }
+                            Message::Close(_) => {
+                                tracing::info!("WebSocket close received");
                                 break;
                             }
+                            _ => {}
+                        }
+                    }
+                    read = stdin.read(&mut input_buf) => {
+                        match read {
+                            Ok(n) => {
+                                if n == 0 {
+                                    tracing::info!("stdin EOF");
+                                    break 'session;
+                                }
 
-                            // Check for Ctrl+C (0x03) in raw mode for double-press exit
-                            let data = &input_buf[..n];
-                            let now = std::time::Instant::now();
-
-                            // Detect Ctrl+C: single byte 0x03
-                            if data.len() == 1 && data[0] == 0x03 {
-                                if let Some(last_time) = last_ctrl_c_time {
-                                    if now.duration_since(last_time) < ctrl_c_timeout {
-                                        // Double Ctrl+C - exit
-                                        tracing::info!("Double Ctrl+C detected, detaching...");
-                                        println!("\r\nDetaching from session...");
-                                        break;
+                                // Check for Ctrl+C (0x03) in raw mode for double-press exit
+                                let data = &input_buf[..n];
+                                let now = std::time::Instant::now();
+
+                                // Detect Ctrl+C: single byte 0x03
+                                if data.len() == 1 && data[0] == 0x03 {
+                                    if let Some(last_time) = last_ctrl_c_time {
+                                        if now.duration_since(last_time) < ctrl_c_timeout {
+                                            // Double Ctrl+C - exit
+                                            tracing::info!("Double Ctrl+C detected, detaching...");
+                                            println!("\r\nDetaching from session...");
+                                            break 'session;
+                                        }
                                     }
+                                    last_ctrl_c_time = Some(now);
+                                    // Still forward the Ctrl+C to the PTY so Claude can handle it
+                                } else {
+                                    // Reset Ctrl+C timer on other input
+                                    last_ctrl_c_time = None;
                                 }
-                                last_ctrl_c_time = Some(now);
-                                // Still forward the Ctrl+C to the PTY so Claude can handle it
-                            } else {
-                                // Reset Ctrl+C timer on other input
-                                last_ctrl_c_time = None;
-                            }
 
-                            if let Err(e) = ws_tx.send(Message::Binary(data.to_vec())).await {
-                                tracing::error!("Failed to send to WebSocket: {}", e);
-                                break;
+                                if let Err(e) = ws_tx.send(Message::Binary(data.to_vec())).await {
+                                    tracing::error!("Failed to send to WebSocket: {}", e);
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                tracing::error!("stdin read error: {}", e);
+                                break 'session;
                             }
-                        }
-                        Err(e) => {
-                            tracing::error!("stdin read error: {}", e);
-                            break;
                         }
                     }
                 }
             }
+
+            // The connection to the daemon dropped; reattach
+            let ws_stream = loop {
+                let Some(delay) = reconnect.next_delay() else {
+                    anyhow::bail!(
+                        "Lost connection to the daemon after {} reconnect attempts",
+                        reconnect.max_attempts()
+                    );
+                };
+                eprint!(
+                    "\r\nReconnecting… attempt {}/{}\r\n",
+                    reconnect.attempt(),
+                    reconnect.max_attempts()
+                );
+                // Ctrl+C or EOF while waiting detaches instead
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    read = stdin.read(&mut input_buf) => {
+                        let n = read.unwrap_or(0);
+                        if n == 0 || input_buf[..n].contains(&0x03) {
+                            println!("\r\nDetaching from session...");
+                            break 'session;
+                        }
+                    }
+                }
+                match Self::open_attach_socket(&ws_url, session_id).await {
+                    Ok(ws_stream) => break ws_stream,
+                    Err(e) => {
+                        tracing::warn!("Reconnect attempt {} failed: {:#}", reconnect.attempt(), e)
+                    }
+                }
+            };
+            (ws_tx, ws_rx) = ws_stream.split();
         }
 
         tracing::info!("attach_session loop ended");
@@ -347,19 +482,8 @@ impl DaemonClient {
                             serde_json::from_str::<persistence::SessionMetadata>(&content)
                         {
                             if let Some(pid) = metadata.pid {
-                                #[cfg(unix)]
-                                {
-                                    if unsafe { libc::kill(pid as i32, 0) } == 0 {
-                                        active_count += 1;
-                                    }
-                                }
-                                #[cfg(windows)]
-                                {
-                                    use sysinfo::{ProcessExt, System, SystemExt};
-                                    let s = System::new_all();
-                                    if s.process(sysinfo::Pid::from(pid as usize)).is_some() {
-                                        active_count += 1;
-                                    }
+                                if process::is_running(pid) {
+                                    active_count += 1;
                                 }
                             }
                         }
@@ -5,6 +5,7 @@
 //! - Attach/detach from sessions
 //! - Create new sessions
 //! - Kill sessions
+//! - Record sessions to asciinema cast files
 
 use anyhow::{Context, Result};
 use bytes::Bytes;
@@ -15,13 +16,20 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info};
 
+use super::budget::BudgetTracker;
+use crate::commands::token_usage::TokenUsage;
 use super::persistence::{PersistenceManager, PersistentSession};
+use super::recording::{CastHeader, RecordingSession};
 
 /// Session multiplexer - central hub for all terminal sessions
 pub struct SessionMultiplexer {
     persistence: Arc<PersistenceManager>,
     /// Active WebSocket connections per session
     connections: Arc<RwLock<HashMap<String, Vec<ConnectionHandle>>>>,
+    /// Recordings in progress, keyed by session ID
+    recordings: Arc<RwLock<HashMap<String, RecordingSession>>>,
+    /// Daily token budgets of sessions started under a profile
+    budget: Arc<BudgetTracker>,
 }
 
 /// Handle to a client connection
@@ -54,6 +62,9 @@ pub struct SessionSummary {
     pub has_clients: bool,
     pub pid: Option<u32>,
     pub exit_code: Option<i32>,
+    /// Missing from daemons older than `happy session list`
+    #[serde(default)]
+    pub working_dir: PathBuf,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -66,14 +77,21 @@ pub enum SessionStatus {
 
 impl SessionMultiplexer {
     pub fn new(state_dir: PathBuf) -> Result<Self> {
+        let usage = TokenUsage::at(state_dir.join("token_usage.json"));
         let persistence = Arc::new(PersistenceManager::new(state_dir)?);
 
         Ok(Self {
             persistence,
             connections: Arc::new(RwLock::new(HashMap::new())),
+            recordings: Arc::new(RwLock::new(HashMap::new())),
+            budget: Arc::new(BudgetTracker::new(usage)),
         })
     }
 
+    pub fn budget(&self) -> Arc<BudgetTracker> {
+        self.budget.clone()
+    }
+
     /// Initialize and recover existing sessions
     pub async fn initialize(&self) -> Result<()> {
         let recovered = self.persistence.recover_sessions().await?;
@@ -161,6 +179,7 @@ impl SessionMultiplexer {
                 has_clients,
                 pid: metadata.pid,
                 exit_code: metadata.exit_code,
+                working_dir: metadata.working_dir.clone(),
             });
         }
 
@@ -170,6 +189,12 @@ impl SessionMultiplexer {
         summaries
     }
 
+    /// Change a session's tag
+    pub async fn rename_session(&self, id: &str, tag: &str) -> Result<()> {
+        info!("Renaming session {} to {}", id, tag);
+        self.persistence.rename_session(id, tag).await
+    }
+
     /// Kill a session
     pub async fn kill_session(&self, id_or_tag: &str) -> Result<()> {
         let session_id = {
@@ -187,11 +212,95 @@ impl SessionMultiplexer {
             info!("Disconnecting {} clients", connections.len());
         }
 
+        if let Some(recording) = self.recordings.write().await.remove(&session_id) {
+            match recording.stop().await {
+                Ok(path) => info!("Saved recording of {} to {}", session_id, path.display()),
+                Err(e) => error!("Failed to finish recording of {}: {}", session_id, e),
+            }
+        }
+
+        self.budget.untrack(&session_id).await;
         self.persistence.kill_session(&session_id).await?;
 
         Ok(())
     }
 
+    /// Start recording a session's output to `path`, or to a new file under
+    /// `~/.happy/recordings/` when `None`
+    pub async fn start_recording(
+        &self,
+        id_or_tag: &str,
+        path: Option<PathBuf>,
+    ) -> Result<PathBuf> {
+        let session = self
+            .persistence
+            .get_session(id_or_tag)
+            .await
+            .context("Session not found")?;
+        let session = session.read().await;
+
+        let mut recordings = self.recordings.write().await;
+        // A recording whose session has ended can make way for a new one
+        if let Some(existing) = recordings.get(&session.id) {
+            if !existing.is_finished() {
+                anyhow::bail!(
+                    "Session '{}' is already being recorded to {}",
+                    session.tag,
+                    existing.path().display()
+                );
+            }
+        }
+
+        let path = match path {
+            Some(path) => path,
+            None => super::recording::default_recording_path(&session.tag)?,
+        };
+        let metadata = session.get_metadata().await;
+        let header = CastHeader {
+            version: 2,
+            width: metadata.cols,
+            height: metadata.rows,
+            timestamp: Some(chrono::Utc::now().timestamp()),
+            idle_time_limit: None,
+            title: Some(session.tag.clone()),
+        };
+        let recording =
+            RecordingSession::start(session.subscribe_output(), header, path.clone()).await?;
+
+        info!("Recording session {} to {}", session.id, path.display());
+        if let Some(previous) = recordings.insert(session.id.clone(), recording) {
+            let _ = previous.stop().await;
+        }
+        Ok(path)
+    }
+
+    /// Whether a recording of the session is still being written
+    pub async fn is_recording(&self, session_id: &str) -> bool {
+        self.recordings
+            .read()
+            .await
+            .get(session_id)
+            .is_some_and(|r| !r.is_finished())
+    }
+
+    /// Stop recording a session, returning the finished file's path
+    pub async fn stop_recording(&self, id_or_tag: &str) -> Result<PathBuf> {
+        let session_id = match self.persistence.get_session(id_or_tag).await {
+            Some(session) => session.read().await.id.clone(),
+            None => id_or_tag.to_string(),
+        };
+        let recording = self
+            .recordings
+            .write()
+            .await
+            .remove(&session_id)
+            .with_context(|| format!("Session {} is not being recorded", id_or_tag))?;
+
+        let path = recording.stop().await?;
+        info!("Saved recording of {} to {}", session_id, path.display());
+        Ok(path)
+    }
+
     /// Register a client connection to a session
     pub async fn attach_client(
         &self,
@@ -286,6 +395,12 @@ impl SessionMultiplexer {
             .await
             .context("Session not found")?;
 
+        if self.budget.is_exceeded(session_id).await {
+            anyhow::bail!(
+                "budget_exceeded: input to session {} is held until midnight UTC",
+                session_id
+            );
+        }
         session.read().await.write(data).await?;
         Ok(())
     }
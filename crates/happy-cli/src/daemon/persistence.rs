@@ -18,12 +18,17 @@ use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio::time::{interval, Duration};
 use tracing::{debug, error, info, warn};
 
+use super::process;
+
 /// Size of the output ring buffer (10MB)
 const BUFFER_SIZE: usize = 10 * 1024 * 1024;
 
 /// Maximum number of lines to keep in scrollback
 const MAX_SCROLLBACK_LINES: usize = 10000;
 
+/// Placeholder in env var values that expands to the session ID at spawn time
+pub const SESSION_ID_PLACEHOLDER: &str = "${HAPPY_SESSION_ID}";
+
 /// Persistent terminal session that survives disconnections
 pub struct PersistentSession {
     /// Session ID
@@ -59,6 +64,9 @@ pub struct SessionMetadata {
     pub pid: Option<u32>,
     /// Exit status if completed
     pub exit_code: Option<i32>,
+    /// Agent version captured from `<command> --version` at spawn time
+    #[serde(default)]
+    pub agent_version: Option<String>,
 }
 
 /// Ring buffer for terminal output with scrollback
@@ -178,7 +186,13 @@ impl PersistenceManager {
         let session_id = id.unwrap_or_else(|| format!("{}", uuid::Uuid::new_v4()));
         info!("Creating persistent session: {} (tag: {})", session_id, tag);
 
-        // Create PTY
+        let env_vars = resolve_session_env(env_vars, &session_id);
+
+        // Capture the agent version before the PTY takes over the process
+        let agent_version = detect_agent_version(command).await;
+        info!("Agent version for {}: {:?}", command, agent_version);
+
+        // Create PTY (a ConPTY from CreatePseudoConsole on Windows)
         let pty_system = NativePtySystem::default();
         let pair = pty_system.openpty(size)?;
 
@@ -215,6 +229,7 @@ impl PersistenceManager {
             rows: size.rows,
             pid: child_pid,
             exit_code: None,
+            agent_version,
         }));
 
         // Start PTY handler in a blocking task
@@ -286,6 +301,22 @@ impl PersistenceManager {
         None
     }
 
+    /// Change a session's tag, keeping its state file in step
+    pub async fn rename_session(&self, id: &str, tag: &str) -> Result<()> {
+        let Some(session) = self.sessions.read().await.get(id).cloned() else {
+            anyhow::bail!("Session not found: {}", id);
+        };
+
+        let mut guard = session.write().await;
+        guard.tag = tag.to_string();
+        let meta = {
+            let mut metadata = guard.metadata.write().await;
+            metadata.tag = tag.to_string();
+            metadata.clone()
+        };
+        save_session_state(&self.state_dir, &meta).await
+    }
+
     /// List all active sessions
     pub async fn list_sessions(&self) -> Vec<SessionMetadata> {
         let mut result = Vec::new();
@@ -311,24 +342,13 @@ impl PersistenceManager {
         if self.sessions.write().await.remove(&id).is_some() {
             // Actually kill the process first
             if let Some(pid) = pid {
-                #[cfg(unix)]
-                unsafe {
-                    info!("Killing process {} for session {}", pid, id);
-                    libc::kill(pid as i32, libc::SIGTERM);
-                    // Give it a moment to terminate gracefully
-                    tokio::time::sleep(Duration::from_millis(100)).await;
-                    // Force kill if still running
-                    libc::kill(pid as i32, libc::SIGKILL);
-                }
-                #[cfg(windows)]
-                {
-                    use sysinfo::{ProcessExt, System, SystemExt};
-                    let mut s = System::new_all();
-                    s.refresh_all();
-                    if let Some(process) = s.process(sysinfo::Pid::from(pid as usize)) {
-                        info!("Killing process {} for session {}", pid, id);
-                        process.kill();
-                    }
+                info!("Killing process {} for session {}", pid, id);
+                process::terminate(pid);
+                // Give it a moment to terminate gracefully
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                // Force kill if still running
+                if process::is_running(pid) {
+                    process::kill(pid);
                 }
             }
 
@@ -351,20 +371,7 @@ impl PersistenceManager {
         let session_id = metadata.id.clone();
 
         // Check if the process is still running
-        let is_running = if let Some(pid) = metadata.pid {
-            #[cfg(unix)]
-            unsafe {
-                libc::kill(pid as i32, 0) == 0
-            }
-            #[cfg(windows)]
-            {
-                use sysinfo::{ProcessExt, System, SystemExt};
-                let s = System::new_all();
-                s.process(sysinfo::Pid::from(pid as usize)).is_some()
-            }
-        } else {
-            false
-        };
+        let is_running = metadata.pid.map(process::is_running).unwrap_or(false);
 
         // Create channels
         let (cmd_tx, cmd_rx) = mpsc::channel(100);
@@ -409,12 +416,7 @@ impl PersistenceManager {
                 session_id
             );
             if let Some(pid) = metadata.pid {
-                #[cfg(unix)]
-                unsafe {
-                    libc::kill(pid as i32, libc::SIGKILL);
-                }
-                #[cfg(windows)]
-                { /* Windows kill logic */ }
+                process::kill(pid);
             }
         }
 
@@ -500,7 +502,7 @@ impl PersistenceManager {
                             Ok(metadata) => {
                                 // Check if process is still running
                                 if metadata.pid.is_some()
-                                    && is_process_running(metadata.pid.unwrap())
+                                    && process::is_running(metadata.pid.unwrap())
                                 {
                                     info!(
                                         "Found running session: {} (tag: {})",
@@ -736,6 +738,15 @@ impl PersistentSession {
         self.buffer.read().await.get_contents()
     }
 
+    /// Put `history` ahead of the buffered output, e.g. the scrollback of a
+    /// session this one replaces
+    pub async fn seed_history(&self, history: &[u8]) {
+        let mut buffer = self.buffer.write().await;
+        let mut contents = history.to_vec();
+        contents.extend(buffer.get_contents());
+        buffer.restore(&contents);
+    }
+
     /// Get session metadata
     pub async fn get_metadata(&self) -> SessionMetadata {
         self.metadata.read().await.clone()
@@ -747,6 +758,35 @@ impl PersistentSession {
     }
 }
 
+/// Run `<command> --version` and extract the version number from its stdout
+async fn detect_agent_version(command: &str) -> Option<String> {
+    let output = tokio::time::timeout(
+        Duration::from_secs(5),
+        tokio::process::Command::new(command)
+            .arg("--version")
+            .stdin(std::process::Stdio::null())
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_agent_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Extract the first version-looking token, e.g. "1.0.3 (Claude Code)" or "codex-cli 0.46.0"
+fn parse_agent_version(stdout: &str) -> Option<String> {
+    stdout
+        .split_whitespace()
+        .map(|token| token.trim_start_matches('v'))
+        .find(|token| token.starts_with(|c: char| c.is_ascii_digit()) && token.contains('.'))
+        .map(|token| token.to_string())
+}
+
 /// Save session state to disk
 async fn save_session_state(state_dir: &PathBuf, metadata: &SessionMetadata) -> Result<()> {
     let state_file = state_dir.join(format!("{}.json", metadata.id));
@@ -829,6 +869,77 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_profile_env_vars_reach_child() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let manager = PersistenceManager::new(temp_dir.path().to_path_buf())?;
+
+        let profile = happy_core::AIProfile {
+            env_vars: [
+                ("MY_MODEL".to_string(), "claude-opus-4".to_string()),
+                (
+                    "MY_SESSION".to_string(),
+                    "id-${HAPPY_SESSION_ID}".to_string(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..happy_core::AIProfile::new("test", happy_core::AIProvider::Anthropic)
+        };
+
+        // `env` stands in for the agent: it prints its environment and exits
+        let session = manager
+            .create_session(
+                Some("env-session".to_string()),
+                "env-tag",
+                "env",
+                std::env::current_dir()?,
+                crate::commands::run::profile_env_vars(&profile)?,
+                PtySize::default(),
+            )
+            .await?;
+
+        let mut output = String::new();
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let contents = session.read().await.get_buffer_contents().await;
+            output = String::from_utf8_lossy(&contents).to_string();
+            if output.contains("MY_SESSION=") {
+                break;
+            }
+        }
+
+        assert!(output.contains("MY_MODEL=claude-opus-4"), "{}", output);
+        assert!(output.contains("MY_SESSION=id-env-session"), "{}", output);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_agent_version() {
+        assert_eq!(
+            parse_agent_version("1.0.3 (Claude Code)\n"),
+            Some("1.0.3".to_string())
+        );
+        assert_eq!(
+            parse_agent_version("codex-cli 0.46.0"),
+            Some("0.46.0".to_string())
+        );
+        assert_eq!(parse_agent_version("v2.1.0"), Some("2.1.0".to_string()));
+        assert_eq!(parse_agent_version("unknown"), None);
+    }
+}
+
+/// Expand `${HAPPY_SESSION_ID}` in env var values
+pub fn resolve_session_env(
+    env_vars: Vec<(String, String)>,
+    session_id: &str,
+) -> Vec<(String, String)> {
+    env_vars
+        .into_iter()
+        .map(|(key, value)| (key, value.replace(SESSION_ID_PLACEHOLDER, session_id)))
+        .collect()
 }
 
 /// Load session output log from disk
@@ -871,19 +982,6 @@ async fn load_session_log(state_dir: &PathBuf, session_id: &str) -> Result<Vec<u
     }
 }
 
-/// Check if a process is still running
-#[cfg(unix)]
-fn is_process_running(pid: u32) -> bool {
-    unsafe { libc::kill(pid as i32, 0) == 0 }
-}
-
-#[cfg(windows)]
-fn is_process_running(pid: u32) -> bool {
-    use sysinfo::{ProcessExt, System, SystemExt};
-    let s = System::new_all();
-    s.process(sysinfo::Pid::from(pid as usize)).is_some()
-}
-
 /// Fork and create a detached daemon process (Unix only)
 #[cfg(unix)]
 #[allow(dead_code)]
@@ -931,4 +1029,5 @@ pub fn daemonize() -> Result<bool> {
     Ok(true)
 }
 
+#[cfg(unix)]
 use std::os::unix::io::AsRawFd;
@@ -0,0 +1,108 @@
+//! Liveness checks and termination for daemon and session PIDs
+//!
+//! Unix signals the PID directly. Windows opens a handle with `OpenProcess`,
+//! reads `GetExitCodeProcess` for liveness and ends the process with
+//! `TerminateProcess`, which has no graceful stage.
+
+/// Whether `pid` refers to a running process
+#[cfg(unix)]
+pub fn is_running(pid: u32) -> bool {
+    unsafe { libc::kill(pid as i32, 0) == 0 }
+}
+
+/// Ask `pid` to exit (SIGTERM)
+#[cfg(unix)]
+pub fn terminate(pid: u32) {
+    unsafe {
+        libc::kill(pid as i32, libc::SIGTERM);
+    }
+}
+
+/// Kill `pid` without giving it a chance to clean up (SIGKILL)
+#[cfg(unix)]
+pub fn kill(pid: u32) {
+    unsafe {
+        libc::kill(pid as i32, libc::SIGKILL);
+    }
+}
+
+#[cfg(windows)]
+pub fn is_running(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::STILL_ACTIVE;
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    let Some(process) = ProcessHandle::open(pid, PROCESS_QUERY_LIMITED_INFORMATION) else {
+        return false;
+    };
+    let mut exit_code = 0u32;
+    let queried = unsafe { GetExitCodeProcess(process.0, &mut exit_code) } != 0;
+    queried && exit_code == STILL_ACTIVE as u32
+}
+
+/// Windows has no polite equivalent of SIGTERM for a detached process
+#[cfg(windows)]
+pub fn terminate(pid: u32) {
+    kill(pid);
+}
+
+#[cfg(windows)]
+pub fn kill(pid: u32) {
+    use windows_sys::Win32::System::Threading::{TerminateProcess, PROCESS_TERMINATE};
+
+    if let Some(process) = ProcessHandle::open(pid, PROCESS_TERMINATE) {
+        unsafe {
+            TerminateProcess(process.0, 1);
+        }
+    }
+}
+
+/// A process handle closed on drop
+#[cfg(windows)]
+struct ProcessHandle(windows_sys::Win32::Foundation::HANDLE);
+
+#[cfg(windows)]
+impl ProcessHandle {
+    fn open(
+        pid: u32,
+        access: windows_sys::Win32::System::Threading::PROCESS_ACCESS_RIGHTS,
+    ) -> Option<Self> {
+        let handle = unsafe { windows_sys::Win32::System::Threading::OpenProcess(access, 0, pid) };
+        (handle != 0).then_some(Self(handle))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        unsafe {
+            windows_sys::Win32::Foundation::CloseHandle(self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_process_is_running() {
+        assert!(is_running(std::process::id()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_stops_child() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        assert!(is_running(pid));
+
+        kill(pid);
+        child.wait().unwrap();
+        assert!(!is_running(pid));
+    }
+}
@@ -0,0 +1,91 @@
+//! Backoff for reattaching to a session after the daemon connection drops
+
+use rand::Rng;
+use std::time::Duration;
+
+/// Attempts `happy session attach` makes before giving up
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 10;
+
+const INITIAL_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// Delays are spread ±20% so clients dropped together don't return together
+const JITTER: f64 = 0.2;
+
+/// Reconnect attempts made since the connection was last healthy
+#[derive(Debug)]
+pub struct ReconnectState {
+    attempt: u32,
+    max_attempts: u32,
+}
+
+impl ReconnectState {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            attempt: 0,
+            max_attempts,
+        }
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Whether the connection has dropped since it was last healthy
+    pub fn is_reconnecting(&self) -> bool {
+        self.attempt > 0
+    }
+
+    /// Start the next attempt, returning how long to wait before it, or `None`
+    /// once every attempt has been used
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.attempt >= self.max_attempts {
+            return None;
+        }
+        self.attempt += 1;
+        let jitter = rand::thread_rng().gen_range(1.0 - JITTER..=1.0 + JITTER);
+        Some(Self::base_delay(self.attempt).mul_f64(jitter))
+    }
+
+    /// The session is attached again; the next drop starts from the first attempt
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Delay before `attempt` (1-based) without jitter
+    fn base_delay(attempt: u32) -> Duration {
+        INITIAL_DELAY
+            .checked_mul(1 << (attempt - 1).min(16))
+            .map_or(MAX_DELAY, |delay| delay.min(MAX_DELAY))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_to_cap_and_gives_up() {
+        assert_eq!(ReconnectState::base_delay(1), Duration::from_millis(500));
+        assert_eq!(ReconnectState::base_delay(2), Duration::from_secs(1));
+        assert_eq!(ReconnectState::base_delay(4), Duration::from_secs(4));
+        assert_eq!(ReconnectState::base_delay(7), MAX_DELAY);
+        assert_eq!(ReconnectState::base_delay(40), MAX_DELAY);
+
+        let mut state = ReconnectState::new(3);
+        for attempt in 1..=3 {
+            let delay = state.next_delay().unwrap();
+            let base = ReconnectState::base_delay(attempt);
+            assert!(delay >= base.mul_f64(0.8) && delay <= base.mul_f64(1.2));
+            assert_eq!(state.attempt(), attempt);
+        }
+        assert!(state.next_delay().is_none());
+
+        state.reset();
+        assert!(!state.is_reconnecting());
+        assert!(state.next_delay().unwrap() <= Duration::from_millis(600));
+    }
+}
@@ -0,0 +1,268 @@
+//! Terminal recording in asciinema v2 (`.cast`) format
+//!
+//! A header object on the first line, then one `[time, "o", data]` array per
+//! line for every chunk of PTY output.
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Header line of a cast file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CastHeader {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    /// Unix time the recording started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    /// Longest pause kept on replay, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_time_limit: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// One output event of a cast file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastEvent {
+    /// Seconds since the recording started
+    pub time: f64,
+    pub data: String,
+}
+
+/// Where recordings go when no output file is given (`~/.happy/recordings/`)
+pub fn recordings_dir() -> Result<PathBuf> {
+    Ok(crate::config::SettingsManager::happy_home()?.join("recordings"))
+}
+
+/// A fresh file name in [`recordings_dir`] for a recording of `tag`
+pub fn default_recording_path(tag: &str) -> Result<PathBuf> {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    Ok(recordings_dir()?.join(format!("{}-{}.cast", tag, stamp)))
+}
+
+/// Tees a session's output into a cast file until stopped or the session ends
+pub struct RecordingSession {
+    path: PathBuf,
+    stop_tx: oneshot::Sender<()>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl RecordingSession {
+    /// Write the header to `path` and record everything sent on `output` from now on
+    pub async fn start(
+        output: broadcast::Receiver<Bytes>,
+        header: CastHeader,
+        path: PathBuf,
+    ) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let file = File::create(&path)
+            .await
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(format!("{}\n", serde_json::to_string(&header)?).as_bytes())
+            .await?;
+
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let task = tokio::spawn(record(output, writer, stop_rx));
+
+        Ok(Self {
+            path,
+            stop_tx,
+            task,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the session ended and the file is already complete
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Stop recording and flush the file, returning its path
+    pub async fn stop(self) -> Result<PathBuf> {
+        let _ = self.stop_tx.send(());
+        self.task.await.context("Recording task panicked")??;
+        Ok(self.path)
+    }
+}
+
+async fn record(
+    mut output: broadcast::Receiver<Bytes>,
+    mut writer: BufWriter<File>,
+    mut stop_rx: oneshot::Receiver<()>,
+) -> Result<()> {
+    let started = Instant::now();
+    // Bytes of a UTF-8 character split across two chunks
+    let mut pending = Vec::new();
+
+    loop {
+        tokio::select! {
+            _ = &mut stop_rx => break,
+            chunk = output.recv() => match chunk {
+                Ok(chunk) => {
+                    pending.extend_from_slice(&chunk);
+                    let data = take_utf8(&mut pending);
+                    if !data.is_empty() {
+                        writer.write_all(event_line(started.elapsed(), &data)?.as_bytes()).await?;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Recording fell behind and lost {} output chunks", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    if !pending.is_empty() {
+        let data = String::from_utf8_lossy(&pending).into_owned();
+        writer.write_all(event_line(started.elapsed(), &data)?.as_bytes()).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// `[time, "o", data]` with the time rounded to microseconds
+fn event_line(elapsed: Duration, data: &str) -> Result<String> {
+    let time = (elapsed.as_secs_f64() * 1e6).round() / 1e6;
+    Ok(format!("{}\n", serde_json::to_string(&(time, "o", data))?))
+}
+
+/// Decode `pending` as UTF-8, leaving an incomplete trailing character behind
+/// for the next chunk
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let complete = match std::str::from_utf8(pending) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => pending.len(),
+    };
+    let rest = pending.split_off(complete);
+    let data = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    data
+}
+
+/// Parse a cast file into its header and output events
+pub fn parse_cast(text: &str) -> Result<(CastHeader, Vec<CastEvent>)> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+
+    let header: CastHeader = lines
+        .next()
+        .context("Recording is empty")
+        .and_then(|l| serde_json::from_str(l).context("Missing asciicast header"))?;
+    if header.version != 2 {
+        anyhow::bail!(
+            "Unsupported asciicast version {}; only v2 can be replayed",
+            header.version
+        );
+    }
+
+    let mut events = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let (time, kind, data): (f64, String, String) = serde_json::from_str(line)
+            .with_context(|| format!("Invalid event on line {}", index + 2))?;
+        if kind == "o" {
+            events.push(CastEvent { time, data });
+        }
+    }
+    Ok((header, events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn header() -> CastHeader {
+        CastHeader {
+            version: 2,
+            width: 80,
+            height: 24,
+            timestamp: Some(1_700_000_000),
+            idle_time_limit: None,
+            title: Some("demo".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_take_utf8_keeps_split_character() {
+        let snowman = "☃".as_bytes();
+        let mut pending = b"hi ".to_vec();
+        pending.extend_from_slice(&snowman[..2]);
+
+        assert_eq!(take_utf8(&mut pending), "hi ");
+        assert_eq!(pending, &snowman[..2]);
+
+        pending.extend_from_slice(&snowman[2..]);
+        assert_eq!(take_utf8(&mut pending), "☃");
+        assert!(pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_recording_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("demo.cast");
+        let (tx, rx) = broadcast::channel(16);
+
+        let recording = RecordingSession::start(rx, header(), path.clone())
+            .await
+            .unwrap();
+        tx.send(Bytes::from_static(b"$ ls\r\n")).unwrap();
+        tx.send(Bytes::from_static("caf\u{e9}\r\n".as_bytes())).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(recording.stop().await.unwrap(), path);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let (parsed, events) = parse_cast(&text).unwrap();
+        assert_eq!(parsed, header());
+        let data: Vec<&str> = events.iter().map(|e| e.data.as_str()).collect();
+        assert_eq!(data, ["$ ls\r\n", "caf\u{e9}\r\n"]);
+        assert!(events[0].time <= events[1].time);
+    }
+
+    #[tokio::test]
+    async fn test_recording_finishes_with_session() {
+        let dir = TempDir::new().unwrap();
+        let (tx, rx) = broadcast::channel(16);
+        let recording = RecordingSession::start(rx, header(), dir.path().join("a.cast"))
+            .await
+            .unwrap();
+        drop(tx);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(recording.is_finished());
+        recording.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_parse_cast_rejects_other_versions() {
+        assert!(parse_cast("").is_err());
+        assert!(parse_cast("{\"version\":1,\"width\":80,\"height\":24}\n").is_err());
+        let (_, events) = parse_cast(
+            "{\"version\":2,\"width\":80,\"height\":24}\n[0.5,\"i\",\"x\"]\n[1.0,\"o\",\"y\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            events,
+            [CastEvent {
+                time: 1.0,
+                data: "y".to_string()
+            }]
+        );
+    }
+}
@@ -1,4 +1,6 @@
+use crate::daemon::multiplexer::SessionSummary;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum DaemonRequest {
@@ -8,10 +10,30 @@ pub enum DaemonRequest {
         token: String,
         server_url: String,
         cwd: String,
+        /// Extra environment for the agent process (from the AI profile)
+        #[serde(default)]
+        env_vars: Vec<(String, String)>,
+        /// Profile whose daily token budget the session counts against
+        #[serde(default)]
+        profile: Option<String>,
     },
     StopSession {
         session_id: String,
     },
+    /// Move a session to another machine through the server
+    TransferSession {
+        session_id: String,
+        target_machine_id: String,
+    },
+    /// Tee a session's output into an asciinema cast file
+    StartRecording {
+        session_id: String,
+        /// `None` picks a file under `~/.happy/recordings/`
+        output: Option<PathBuf>,
+    },
+    StopRecording {
+        session_id: String,
+    },
     ListSessions,
     Shutdown,
 }
@@ -20,6 +42,8 @@ pub enum DaemonRequest {
 pub enum DaemonResponse {
     Ok,
     SessionStarted { session_id: String },
-    Sessions(Vec<String>),
+    Sessions(Vec<SessionSummary>),
+    /// Cast file being written, or just finished
+    Recording { path: PathBuf },
     Error(String),
 }
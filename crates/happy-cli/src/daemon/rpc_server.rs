@@ -62,8 +62,10 @@ async fn handle_connection(
             token,
             server_url,
             cwd,
+            env_vars,
+            profile,
         } => match session_manager
-            .start_session(id, tag, token, server_url, cwd)
+            .start_session(id, tag, token, server_url, cwd, env_vars, profile)
             .await
         {
             Ok(session_id) => DaemonResponse::SessionStarted { session_id },
@@ -75,6 +77,28 @@ async fn handle_connection(
                 Err(e) => DaemonResponse::Error(e.to_string()),
             }
         }
+        DaemonRequest::TransferSession {
+            session_id,
+            target_machine_id,
+        } => match crate::daemon::transfer::request_transfer(&session_id, &target_machine_id).await
+        {
+            Ok(session) => DaemonResponse::SessionStarted {
+                session_id: session.id,
+            },
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonRequest::StartRecording { session_id, output } => {
+            match session_manager.start_recording(&session_id, output).await {
+                Ok(path) => DaemonResponse::Recording { path },
+                Err(e) => DaemonResponse::Error(e.to_string()),
+            }
+        }
+        DaemonRequest::StopRecording { session_id } => {
+            match session_manager.stop_recording(&session_id).await {
+                Ok(path) => DaemonResponse::Recording { path },
+                Err(e) => DaemonResponse::Error(e.to_string()),
+            }
+        }
         DaemonRequest::ListSessions => {
             let sessions = session_manager.list_sessions().await;
             DaemonResponse::Sessions(sessions)
@@ -1,5 +1,5 @@
 use crate::daemon::bridge::RemoteRelayBridge;
-use crate::daemon::multiplexer::{CreateSessionRequest, SessionMultiplexer};
+use crate::daemon::multiplexer::{CreateSessionRequest, SessionMultiplexer, SessionSummary};
 use anyhow::Result;
 use portable_pty::PtySize;
 use std::collections::HashMap;
@@ -36,6 +36,11 @@ impl DaemonSessionManager {
         self.multiplexer.clone()
     }
 
+    /// Start (or reuse) the session tagged `tag` and bridge it to the server.
+    ///
+    /// A new session under `profile` is refused once the profile has spent its
+    /// daily token budget, and its usage is counted towards it from then on.
+    #[allow(clippy::too_many_arguments)]
     pub async fn start_session(
         &self,
         id: Option<String>,
@@ -43,9 +48,18 @@ impl DaemonSessionManager {
         token: String,
         server_url: String,
         cwd: String,
+        env_vars: Vec<(String, String)>,
+        profile: Option<String>,
     ) -> Result<String> {
         // Keep a clone of tag for later use in bridge
         let tag_for_bridge = tag.clone();
+        let budget = self.multiplexer.budget();
+        if let Some(name) = &profile {
+            let settings = crate::config::SettingsManager::load()?;
+            if let Some(profile) = settings.profiles.iter().find(|p| &p.name == name) {
+                budget.check(profile)?;
+            }
+        }
 
         // 1. Check if session with this tag already exists AND is running
         let session_id = {
@@ -70,14 +84,20 @@ impl DaemonSessionManager {
                     );
                     let _ = self.multiplexer.kill_session(&existing_id).await;
                     // Fall through to create new session with provided cwd
-                    self.create_new_session(Some(existing_id), tag, cwd.clone())
+                    self.create_new_session(Some(existing_id), tag, cwd.clone(), env_vars)
                         .await?
                 }
             } else {
                 // 2. Create new session with provided cwd
-                self.create_new_session(id, tag, cwd.clone()).await?
+                self.create_new_session(id, tag, cwd.clone(), env_vars)
+                    .await?
             }
         };
+        if let Some(name) = &profile {
+            budget
+                .track(&session_id, name, std::path::Path::new(&cwd))
+                .await;
+        }
 
         // 3. Start Relay Bridge if not running
         self.clone()
@@ -204,9 +224,20 @@ impl DaemonSessionManager {
         Ok(())
     }
 
-    pub async fn list_sessions(&self) -> Vec<String> {
-        let sessions = self.multiplexer.list_sessions().await;
-        sessions.into_iter().map(|s| s.id).collect()
+    pub async fn list_sessions(&self) -> Vec<SessionSummary> {
+        self.multiplexer.list_sessions().await
+    }
+
+    pub async fn start_recording(
+        &self,
+        session_id: &str,
+        output: Option<PathBuf>,
+    ) -> Result<PathBuf> {
+        self.multiplexer.start_recording(session_id, output).await
+    }
+
+    pub async fn stop_recording(&self, session_id: &str) -> Result<PathBuf> {
+        self.multiplexer.stop_recording(session_id).await
     }
 
     /// Helper to create a new session
@@ -215,16 +246,20 @@ impl DaemonSessionManager {
         id: Option<String>,
         tag: String,
         cwd: String,
+        extra_env: Vec<(String, String)>,
     ) -> Result<String> {
+        let mut env_vars = vec![
+            ("HAPPY_SESSION_TAG".to_string(), tag.clone()),
+            ("TERM".to_string(), "xterm-256color".to_string()),
+        ];
+        env_vars.extend(extra_env);
+
         let request = CreateSessionRequest {
             id,
             tag: tag.clone(),
             command: "claude".to_string(), // TODO: Make configurable via RPC
             working_dir: std::path::PathBuf::from(&cwd),
-            env_vars: vec![
-                ("HAPPY_SESSION_TAG".to_string(), tag.clone()),
-                ("TERM".to_string(), "xterm-256color".to_string()),
-            ],
+            env_vars,
             size: PtySize {
                 rows: 24,
                 cols: 80,
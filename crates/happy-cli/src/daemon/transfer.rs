@@ -0,0 +1,74 @@
+//! Moving a session to another machine
+//!
+//! The server does the work: it starts a replacement session on the target
+//! machine with the old session's output as scrollback, then moves the web
+//! clients watching the old session over. The daemon only asks, over a
+//! short-lived WebSocket, and waits for the outcome.
+
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use happy_types::{ClientMessage, ServerMessage, Session};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::info;
+
+/// How long the target machine has to start the replacement session
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Ask the server to move `session_id` to `target_machine_id`, returning the new session
+pub async fn request_transfer(session_id: &str, target_machine_id: &str) -> Result<Session> {
+    let settings = crate::config::SettingsManager::load().context("Failed to load settings")?;
+    let token = crate::api::Client::new().access_token().await?;
+    let ws_url = super::bridge::build_ws_url(&settings.server_url);
+
+    let (ws_stream, _) = tokio_tungstenite::connect_async(&ws_url)
+        .await
+        .context("Failed to connect to server")?;
+    let (mut ws_tx, mut ws_rx) = ws_stream.split();
+
+    for msg in [
+        ClientMessage::Authenticate {
+            token,
+            binary_frames: false,
+        },
+        ClientMessage::TransferSession {
+            session_id: session_id.to_string(),
+            target_machine_id: target_machine_id.to_string(),
+        },
+    ] {
+        ws_tx.send(Message::Text(serde_json::to_string(&msg)?)).await?;
+    }
+    info!(
+        "Requested transfer of session {} to machine {}",
+        session_id, target_machine_id
+    );
+
+    let outcome = tokio::time::timeout(TRANSFER_TIMEOUT, async {
+        while let Some(frame) = ws_rx.next().await {
+            let Message::Text(text) = frame? else {
+                continue;
+            };
+            match serde_json::from_str::<ServerMessage>(&text) {
+                Ok(ServerMessage::RemoteSessionResponse {
+                    success: true,
+                    session: Some(session),
+                    ..
+                }) => return Ok(session),
+                Ok(ServerMessage::RemoteSessionResponse { error, .. }) => anyhow::bail!(
+                    "Target machine failed to start the session: {}",
+                    error.unwrap_or_else(|| "unknown error".to_string())
+                ),
+                Ok(ServerMessage::Error { code, message }) => {
+                    anyhow::bail!("{} ({})", message, code)
+                }
+                _ => {}
+            }
+        }
+        anyhow::bail!("Server closed the connection before the transfer finished")
+    })
+    .await
+    .context("Timed out waiting for the target machine")?;
+
+    let _ = ws_tx.close().await;
+    outcome
+}
@@ -8,7 +8,7 @@ mod config;
 mod daemon;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
 use tracing::{error, info};
 
@@ -22,6 +22,19 @@ struct Cli {
     /// Enable verbose logging
     #[arg(short, long, global = true)]
     verbose: bool,
+
+    /// Output format for command results
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+}
+
+/// How commands report their results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable output with progress indicators
+    Text,
+    /// Machine-readable JSON on stdout
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -50,6 +63,14 @@ enum Commands {
         /// Clean output directories before build
         #[arg(long)]
         clean: bool,
+
+        /// Minify generated output (the default)
+        #[arg(long, overrides_with = "dev")]
+        release: bool,
+
+        /// Keep generated output readable instead of minifying it
+        #[arg(long, overrides_with = "release")]
+        dev: bool,
     },
 
     /// Start development mode (watch + build)
@@ -57,6 +78,14 @@ enum Commands {
         /// Target specific platform
         #[arg(short, long)]
         target: Option<String>,
+
+        /// Quiet time in milliseconds before a burst of changes triggers a rebuild
+        #[arg(long, value_name = "MS")]
+        debounce: Option<u64>,
+
+        /// Glob of paths to ignore, on top of .git, target and node_modules (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
     },
 
     /// Install built artifacts
@@ -68,6 +97,10 @@ enum Commands {
         /// Target platform
         #[arg(short, long)]
         target: Option<String>,
+
+        /// Install the happy binary into ~/.local/bin (/usr/local/bin with --global)
+        #[arg(long = "self", conflicts_with = "target")]
+        self_binary: bool,
     },
 
     /// Validate configuration
@@ -76,6 +109,13 @@ enum Commands {
     /// Diagnose environment and dependencies
     Doctor,
 
+    /// Print a shell completion script, e.g. `eval "$(happy completions zsh)"`
+    Completions {
+        /// Shell to generate the script for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
     /// Manage AI agent environments (Claude/Codex)
     Env {
         #[command(subcommand)]
@@ -107,6 +147,24 @@ enum Commands {
         #[arg(short, long)]
         profile: Option<String>,
 
+        /// Inject the active profile's env vars (always on when --profile is set)
+        #[arg(long)]
+        profile_env: bool,
+
+        /// Run the agent in an OS sandbox (standard, or strict to also cut off the network)
+        #[arg(
+            long,
+            value_name = "MODE",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "standard"
+        )]
+        sandbox: Option<happy_core::utils::sandbox::SandboxMode>,
+
+        /// Working directory for the session (defaults to the current directory)
+        #[arg(long, value_name = "PATH")]
+        cwd: Option<std::path::PathBuf>,
+
         /// Additional arguments for the agent
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -129,8 +187,24 @@ enum Commands {
     /// Connect to AI vendors
     #[command(name = "connect")]
     Connect {
-        /// Vendor to connect (anthropic, openai, azure)
+        /// Vendor to connect (anthropic, openai, azure, gemini, ollama, custom)
         vendor: String,
+
+        /// Model to use (skips interactive model selection and key validation)
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Don't validate the API key against the vendor API (for offline use)
+        #[arg(long)]
+        skip_validation: bool,
+
+        /// URL of a self-hosted OpenAI-compatible server (custom vendor only)
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Display name for the self-hosted server, e.g. vllm (custom vendor only)
+        #[arg(long)]
+        name: Option<String>,
     },
 
     /// Manage AI profiles (remote mode)
@@ -140,6 +214,31 @@ enum Commands {
         action: ProfileAction,
     },
 
+    /// Manage registered machines (remote mode)
+    #[command(name = "machine")]
+    Machine {
+        #[command(subcommand)]
+        action: MachineAction,
+    },
+
+    /// Manage remote sessions (remote mode)
+    #[command(name = "session")]
+    Session {
+        #[command(subcommand)]
+        action: SessionAction,
+    },
+
+    /// Server operator tools (remote mode)
+    #[command(name = "admin")]
+    Admin {
+        /// The server's ADMIN_TOKEN
+        #[arg(long, env = "HAPPY_ADMIN_TOKEN", hide_env_values = true)]
+        token: String,
+
+        #[command(subcommand)]
+        action: AdminAction,
+    },
+
     /// Push notifications (remote mode)
     #[command(name = "notify")]
     Notify { message: String },
@@ -171,6 +270,13 @@ enum EnvAction {
         /// Environment name
         name: String,
     },
+    /// Show how environment variables differ between two environments
+    Diff {
+        /// Environment switched from
+        from: String,
+        /// Environment switched to
+        to: String,
+    },
     /// Run Claude with a specific environment
     Run {
         /// Environment name (optional, uses default if not specified)
@@ -188,7 +294,14 @@ enum LocalConfigAction {
     /// Pull config from ~/.claude/ to local
     Pull,
     /// Show diff between local and system config
-    Diff,
+    Diff {
+        /// Print the diff without colors
+        #[arg(long)]
+        no_color: bool,
+        /// Only show the number of changed lines per file
+        #[arg(long)]
+        stat: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -222,13 +335,38 @@ enum AuthAction {
         /// Password (optional - will prompt if not provided)
         #[arg(short, long)]
         password: Option<String>,
+        /// Sign in through your company's identity provider in the browser
+        #[arg(long, conflicts_with_all = ["email", "password"])]
+        sso: bool,
     },
     /// Logout
     Logout,
-    /// Show current user
+    /// Show the logged-in user (with --verbose: token expiry, server and access keys)
     Whoami,
     /// List access keys
     Keys,
+    /// Keep tokens and profile API keys in the OS keychain instead of settings.json
+    Keyring {
+        #[command(subcommand)]
+        action: KeyringAction,
+    },
+    /// Show the server's audit log of sign-ins and session changes (admins only)
+    Audit {
+        /// Entries to show
+        #[arg(long, default_value_t = 50)]
+        limit: u32,
+        /// Only entries older than this ID, to page back through the log
+        #[arg(long, value_name = "ID")]
+        before: Option<i64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeyringAction {
+    /// Move stored credentials into the OS keychain
+    Enable,
+    /// Move stored credentials back into settings.json
+    Disable,
 }
 
 #[derive(Subcommand)]
@@ -241,6 +379,136 @@ enum ProfileAction {
     Use { name: String },
     /// Delete a profile
     Remove { name: String },
+    /// Copy a profile under a new name
+    Clone {
+        source: String,
+        dest: String,
+        /// Replace `dest` if it already exists
+        #[arg(long)]
+        overwrite: bool,
+        /// API key for the copy instead of the source's
+        #[arg(long, value_name = "KEY")]
+        api_key: Option<String>,
+    },
+    /// Set a generation parameter (max_tokens, temperature, top_p, system_prompt,
+    /// max_tokens_per_day)
+    #[command(name = "set-param")]
+    SetParam {
+        name: String,
+        param: String,
+        /// New value, or `unset` for the provider default
+        value: String,
+    },
+    /// Show tokens each profile has used today against its daily budget
+    Usage,
+    /// Check that every `${VAR}` reference in a profile's env vars resolves
+    Validate {
+        /// Profile to check (default: the active profile)
+        #[arg(long)]
+        profile: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum MachineAction {
+    /// List machines and whether their daemon is online
+    List {
+        /// Only machines whose name or hostname contain words starting with this text
+        #[arg(long)]
+        search: Option<String>,
+        /// Only machines with all of these capabilities, e.g. `terminal,file_system`
+        #[arg(long, value_delimiter = ',')]
+        require: Vec<happy_types::Capability>,
+    },
+    /// Rename a machine
+    Rename {
+        /// Machine ID (or unique prefix)
+        id: String,
+        /// New display name
+        name: String,
+    },
+    /// Remove a machine
+    Remove {
+        /// Machine ID (or unique prefix)
+        id: String,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Move a reinstalled machine's old sessions onto its new ID (server operators)
+    Merge {
+        /// Full ID of the orphaned machine, deleted afterwards
+        old_id: String,
+        /// Full ID of the machine that replaces it
+        new_id: String,
+        /// The server's ADMIN_TOKEN
+        #[arg(long, env = "HAPPY_ADMIN_TOKEN", hide_env_values = true)]
+        token: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionAction {
+    /// List sessions on this machine's daemon and, when logged in, the server
+    List {
+        #[arg(long, value_enum, default_value_t = commands::session::StatusFilter::All)]
+        status: commands::session::StatusFilter,
+    },
+    /// Kill a session running on this machine
+    Kill {
+        /// Session ID (or unique prefix) or tag
+        id: String,
+    },
+    /// Attach this terminal to a session running on this machine
+    Attach {
+        /// Session ID (or unique prefix) or tag
+        id: String,
+        /// Times to try reconnecting if the daemon connection drops
+        #[arg(long, default_value_t = daemon::reconnect::DEFAULT_MAX_ATTEMPTS)]
+        reconnect_attempts: u32,
+    },
+    /// Give a session a new tag
+    #[command(name = "tag", alias = "rename")]
+    Rename {
+        /// Session ID (or unique prefix) or current tag
+        id_or_tag: String,
+        /// New tag (letters, digits, '_' or '-', at most 64 characters)
+        new_tag: String,
+    },
+    /// Print session creations, updates and deletions as they happen
+    Events,
+    /// Move a session to another machine, keeping its terminal history
+    Transfer {
+        /// Session ID (or unique prefix) or tag
+        id: String,
+        /// Target machine ID (or unique prefix)
+        machine: String,
+    },
+    /// Record a session's output to an asciinema cast file until Ctrl+C
+    Record {
+        /// Session ID (or unique prefix) or tag
+        id: String,
+        /// Cast file to write (default: a new file in ~/.happy/recordings/)
+        #[arg(short = 'o', long = "out")]
+        file: Option<std::path::PathBuf>,
+    },
+    /// Replay an asciinema cast file in this terminal
+    Play {
+        /// Cast file to replay
+        file: std::path::PathBuf,
+        /// Playback speed multiplier
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+        /// Cut pauses in the recording down to a second
+        #[arg(long)]
+        no_idle: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AdminAction {
+    /// Show which database migrations have run on the server
+    DbStatus,
 }
 
 #[derive(Subcommand)]
@@ -259,21 +527,36 @@ enum ConfigAction {
     Show,
     /// Reset to default configuration
     Reset,
+    /// Write settings to a TOML file with API keys and login tokens redacted
+    Export {
+        /// Destination file
+        path: std::path::PathBuf,
+    },
+    /// Load settings from a TOML file written by `config export`
+    Import {
+        /// File to import
+        input: std::path::PathBuf,
+        /// Merge into the current settings, keeping local API keys
+        #[arg(long)]
+        merge: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Initialize logging (except for daemon run which has its own file logger)
+    // Initialize logging (except for daemon run which has its own file logger,
+    // and completions whose stdout gets eval'd)
     let is_daemon_run = matches!(
         cli.command,
         Commands::Daemon {
             action: DaemonAction::Run
         }
     );
+    let is_completions = matches!(cli.command, Commands::Completions { .. });
 
-    if !is_daemon_run {
+    if !is_daemon_run && !is_completions {
         let subscriber = tracing_subscriber::fmt()
             .with_env_filter(if cli.verbose {
                 "happy_cli=debug,happy_core=debug"
@@ -297,10 +580,17 @@ async fn main() -> Result<()> {
             target,
             watch,
             clean,
-        } => commands::build::run(target, watch, clean).await,
-        Commands::Dev { target } => commands::dev::run(target).await,
-        Commands::Install { global, target } => commands::install::run(global, target).await,
+            release: _,
+            dev,
+        } => commands::build::run(target, watch, clean, !dev, cli.output).await,
+        Commands::Dev { target, debounce, exclude } => commands::dev::run(target, debounce, exclude).await,
+        Commands::Install {
+            global,
+            target,
+            self_binary,
+        } => commands::install::run(global, target, self_binary).await,
         Commands::Validate => commands::validate::run().await,
+        Commands::Completions { shell } => commands::completions::execute(shell, Cli::command()),
         Commands::Doctor => {
             // Try remote doctor first, or fallback?
             // Remote doctor::execute() seems generic.
@@ -311,12 +601,15 @@ async fn main() -> Result<()> {
             EnvAction::Add { name } => commands::env::add(&name).await,
             EnvAction::Use { name } => commands::env::switch(&name).await,
             EnvAction::Delete { name } => commands::env::delete(&name).await,
+            EnvAction::Diff { from, to } => commands::env::diff(&from, &to, cli.output).await,
             EnvAction::Run { name, args } => commands::env::run(name.as_deref(), args).await,
         },
         Commands::LocalConfig { action } => match action {
             LocalConfigAction::Push => commands::local_config::push().await,
             LocalConfigAction::Pull => commands::local_config::pull().await,
-            LocalConfigAction::Diff => commands::local_config::diff().await,
+            LocalConfigAction::Diff { no_color, stat } => {
+                commands::local_config::diff(no_color, stat).await
+            }
         },
 
         // Unified Run command
@@ -325,6 +618,9 @@ async fn main() -> Result<()> {
             remote,
             tag,
             profile,
+            profile_env,
+            sandbox,
+            cwd,
             args,
         } => {
             commands::run::execute(commands::run::RunOptions {
@@ -332,6 +628,9 @@ async fn main() -> Result<()> {
                 remote,
                 tag,
                 profile,
+                profile_env,
+                sandbox,
+                cwd,
                 args,
             })
             .await
@@ -347,20 +646,92 @@ async fn main() -> Result<()> {
             DaemonAction::Run => commands::daemon::run().await,
         },
         Commands::Auth { action } => match action {
-            AuthAction::Login { email, password } => match (email, password) {
+            AuthAction::Login {
+                email,
+                password,
+                sso,
+            } => match (email, password) {
+                _ if sso => commands::auth::login_sso().await,
                 (Some(e), Some(p)) => commands::auth::login_non_interactive(&e, &p).await,
                 _ => commands::auth::login_interactive().await,
             },
             AuthAction::Logout => commands::auth::logout().await,
-            AuthAction::Whoami => commands::auth::whoami().await,
+            AuthAction::Whoami => commands::auth::whoami(cli.verbose).await,
             AuthAction::Keys => commands::auth::keys().await,
+            AuthAction::Keyring { action } => {
+                commands::auth::keyring(matches!(action, KeyringAction::Enable)).await
+            }
+            AuthAction::Audit { limit, before } => {
+                commands::auth::audit(limit, before, cli.output).await
+            }
         },
-        Commands::Connect { vendor } => commands::connect::execute(&vendor).await,
+        Commands::Connect {
+            vendor,
+            model,
+            skip_validation,
+            url,
+            name,
+        } => commands::connect::execute(&vendor, model, skip_validation, url, name).await,
         Commands::Profile { action } => match action {
             ProfileAction::List => commands::profile::list().await,
             ProfileAction::Add { name } => commands::profile::add(&name).await,
             ProfileAction::Use { name } => commands::profile::use_profile(&name).await,
             ProfileAction::Remove { name } => commands::profile::remove(&name).await,
+            ProfileAction::Clone {
+                source,
+                dest,
+                overwrite,
+                api_key,
+            } => commands::profile::clone(&source, &dest, overwrite, api_key).await,
+            ProfileAction::SetParam { name, param, value } => {
+                commands::profile::set_param(&name, &param, &value).await
+            }
+            ProfileAction::Usage => commands::profile::usage().await,
+            ProfileAction::Validate { profile } => {
+                commands::profile::validate(profile.as_deref()).await
+            }
+        },
+        Commands::Machine { action } => match action {
+            MachineAction::List { search, require } => {
+                commands::machine::list(search.as_deref(), &require, cli.output).await
+            }
+            MachineAction::Rename { id, name } => {
+                commands::machine::rename(&id, &name, cli.output).await
+            }
+            MachineAction::Remove { id, yes } => {
+                commands::machine::remove(&id, yes, cli.output).await
+            }
+            MachineAction::Merge {
+                old_id,
+                new_id,
+                token,
+            } => commands::machine::merge(&token, &old_id, &new_id, cli.output).await,
+        },
+        Commands::Session { action } => match action {
+            SessionAction::List { status } => commands::session::list(status, cli.output).await,
+            SessionAction::Kill { id } => commands::session::kill(&id, cli.output).await,
+            SessionAction::Attach {
+                id,
+                reconnect_attempts,
+            } => commands::session::attach(&id, reconnect_attempts).await,
+            SessionAction::Rename { id_or_tag, new_tag } => {
+                commands::session::rename(&id_or_tag, &new_tag, cli.output).await
+            }
+            SessionAction::Events => commands::session::events(cli.output).await,
+            SessionAction::Transfer { id, machine } => {
+                commands::session::transfer(&id, &machine, cli.output).await
+            }
+            SessionAction::Record { id, file } => {
+                commands::session::record(&id, file, cli.output).await
+            }
+            SessionAction::Play {
+                file,
+                speed,
+                no_idle,
+            } => commands::session::play(&file, speed, no_idle).await,
+        },
+        Commands::Admin { token, action } => match action {
+            AdminAction::DbStatus => commands::admin::db_status(&token, cli.output).await,
         },
         Commands::Notify { message } => commands::notify::execute(&message).await,
         Commands::Config { action } => match action {
@@ -368,6 +739,8 @@ async fn main() -> Result<()> {
             ConfigAction::SetDaemonPort { port } => commands::config::set_daemon_port(port).await,
             ConfigAction::Show => commands::config::show().await,
             ConfigAction::Reset => commands::config::reset().await,
+            ConfigAction::Export { path } => commands::config::export(&path).await,
+            ConfigAction::Import { input, merge } => commands::config::import(&input, merge).await,
         },
     };
 
@@ -379,3 +752,51 @@ async fn main() -> Result<()> {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    /// Subcommand arguments named like a global flag (say `output`) pass
+    /// `debug_assert` but panic when the command line is parsed
+    #[test]
+    fn test_no_argument_shadows_a_global() {
+        fn check(command: &clap::Command, globals: &[String], path: &str) {
+            for arg in command.get_arguments().filter(|a| !a.is_global_set()) {
+                let id = arg.get_id().as_str();
+                assert!(
+                    !globals.iter().any(|g| g == id),
+                    "`{}` argument `{}` shadows the global flag",
+                    path,
+                    id
+                );
+            }
+            for sub in command.get_subcommands() {
+                check(sub, globals, &format!("{} {}", path, sub.get_name()));
+            }
+        }
+
+        let cli = Cli::command();
+        let globals: Vec<String> = cli
+            .get_arguments()
+            .filter(|a| a.is_global_set())
+            .map(|a| a.get_id().to_string())
+            .collect();
+        check(&cli, &globals, "happy");
+    }
+
+    #[test]
+    fn test_config_export_parses() {
+        let cli = Cli::try_parse_from(["happy", "config", "export", "x.toml"]).unwrap();
+        assert_eq!(cli.output, OutputFormat::Text);
+        assert!(matches!(
+            cli.command,
+            Commands::Config { action: ConfigAction::Export { .. } }
+        ));
+    }
+}
@@ -0,0 +1,29 @@
+//! `happy completions <shell>` produces a script covering the subcommands
+
+use std::process::Command;
+
+fn completions(shell: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_happy"))
+        .args(["completions", shell])
+        .output()
+        .expect("run happy");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_completions() {
+    for shell in ["bash", "zsh", "fish"] {
+        let script = completions(shell);
+        for keyword in ["daemon", "auth", "profile", "run", "local-config"] {
+            assert!(script.contains(keyword), "{} script lacks {}", shell, keyword);
+        }
+        // Agents come from the registered adapters; clap_complete's fish
+        // scripts don't complete positional values
+        if shell != "fish" {
+            assert!(script.contains("antigravity"), "{} script lacks agent names", shell);
+        }
+        // Nothing but the script, so it can be eval'd
+        assert!(!script.contains("Starting Happy Coding CLI"));
+    }
+}
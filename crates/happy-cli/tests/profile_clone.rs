@@ -0,0 +1,72 @@
+//! `happy profile clone` against a throwaway `HAPPY_HOME`
+
+use happy_core::{AIProfile, Settings};
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn happy(home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_happy"))
+        .args(args)
+        .env("HAPPY_HOME", home)
+        .output()
+        .expect("run happy")
+}
+
+fn profiles(home: &Path) -> Vec<AIProfile> {
+    let json = std::fs::read_to_string(home.join("settings.json")).unwrap();
+    serde_json::from_str::<Settings>(&json).unwrap().profiles
+}
+
+#[test]
+fn test_profile_clone() {
+    let home = tempfile::tempdir().unwrap();
+    let mut settings = Settings::default();
+    settings.profiles.push(
+        serde_json::from_value(serde_json::json!({
+            "name": "work",
+            "provider": "anthropic",
+            "api_key": "sk-ant-original-0000",
+            "base_url": null,
+            "model": "claude-sonnet",
+            "env_vars": { "HTTP_PROXY": "http://proxy:8080" },
+            "temperature": 0.2,
+        }))
+        .unwrap(),
+    );
+    std::fs::write(
+        home.path().join("settings.json"),
+        serde_json::to_string(&settings).unwrap(),
+    )
+    .unwrap();
+
+    let output = happy(
+        home.path(),
+        &["profile", "clone", "work", "work-eu", "--api-key", "sk-ant-copy-1111"],
+    );
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("sk-a****1111"), "{}", stdout);
+    assert!(!stdout.contains("sk-ant-copy-1111"));
+
+    let cloned = profiles(home.path());
+    assert_eq!(cloned.len(), 2);
+    assert_eq!(cloned[0].api_key.as_deref(), Some("sk-ant-original-0000"));
+    assert_eq!(cloned[1].name, "work-eu");
+    assert_eq!(cloned[1].api_key.as_deref(), Some("sk-ant-copy-1111"));
+    assert_eq!(cloned[1].model, cloned[0].model);
+    assert_eq!(cloned[1].temperature, Some(0.2));
+    assert_eq!(cloned[1].env_vars, cloned[0].env_vars);
+
+    // An existing destination is only replaced on request
+    let output = happy(home.path(), &["profile", "clone", "work", "work-eu"]);
+    assert!(!output.status.success());
+    assert_eq!(profiles(home.path())[1].api_key.as_deref(), Some("sk-ant-copy-1111"));
+
+    let output = happy(home.path(), &["profile", "clone", "work", "work-eu", "--overwrite"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let profiles = profiles(home.path());
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[1].api_key.as_deref(), Some("sk-ant-original-0000"));
+
+    assert!(!happy(home.path(), &["profile", "clone", "missing", "x"]).status.success());
+}
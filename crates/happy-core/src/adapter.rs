@@ -2,6 +2,7 @@
 
 use std::path::Path;
 use async_trait::async_trait;
+use crate::builder::BuildContext;
 use crate::error::Result;
 use crate::types::{
     Platform, Feature, ProjectConfig, BuildResult, ValidationResult, InstallTarget,
@@ -22,8 +23,8 @@ pub trait Adapter: Send + Sync {
     /// Get the limitations of this platform
     fn limitations(&self) -> &[&str];
 
-    /// Build the configuration for this platform
-    async fn build(&self, config: &ProjectConfig, output_dir: &Path) -> Result<BuildResult>;
+    /// Build the configuration for `ctx.target` into `ctx.output_dir(config)`
+    async fn build(&self, ctx: &BuildContext, config: &ProjectConfig) -> Result<BuildResult>;
 
     /// Install built artifacts to target location
     async fn install(&self, source: &Path, target: &InstallTarget) -> Result<()>;
@@ -40,6 +41,17 @@ pub trait Adapter: Send + Sync {
     fn global_install_path(&self) -> Option<std::path::PathBuf>;
 }
 
+/// Progress reported while a build runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildEvent {
+    /// Step `current` of `total` (1-based) has started
+    Step {
+        name: String,
+        total: usize,
+        current: usize,
+    },
+}
+
 /// Adapter factory for creating and managing platform adapters
 pub struct AdapterFactory {
     adapters: std::collections::HashMap<Platform, Box<dyn Adapter>>,
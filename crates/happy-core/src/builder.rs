@@ -1,10 +1,137 @@
 //! Build coordinator for Happy Coding
 
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
-use crate::adapter::AdapterFactory;
-use crate::error::{HappyError, Result};
-use crate::types::{BuildOptions, BuildResult, BuildSummary, Platform, ProjectConfig};
+use serde::Deserialize;
+use crate::adapter::{AdapterFactory, BuildEvent};
+use crate::error::{HappyError, Result, SettingsValidationError};
+use crate::types::{
+    AIProfile, AIProvider, BuildOptions, BuildResult, BuildSummary, Platform, ProjectConfig,
+    Settings, SETTINGS_VERSION,
+};
+
+/// Per-project overrides, relative to the working directory
+pub const PROJECT_OVERRIDES_PATH: &str = ".happy/project.toml";
+
+/// Everything an adapter needs to know about the build it is running
+#[derive(Debug, Clone)]
+pub struct BuildContext {
+    pub settings: Settings,
+    pub working_dir: PathBuf,
+    pub target: Platform,
+    /// Environment the build runs with, normally from `resolve_env`
+    pub env: HashMap<String, String>,
+}
+
+/// `.happy/project.toml`
+#[derive(Debug, Default, Deserialize)]
+struct ProjectOverrides {
+    #[serde(default)]
+    env: HashMap<String, String>,
+}
+
+impl BuildContext {
+    /// Context for building `target` in `working_dir`, with the environment resolved.
+    ///
+    /// Fails if `.happy/project.toml` exists but can't be read.
+    pub fn new(settings: Settings, working_dir: PathBuf, target: Platform) -> Result<Self> {
+        let overrides = project_overrides(&working_dir)?;
+        let mut ctx = Self {
+            settings,
+            working_dir,
+            target,
+            env: HashMap::new(),
+        };
+        ctx.env = ctx.layer_env(std::env::vars(), overrides.env);
+        Ok(ctx)
+    }
+
+    /// Merge the build environment; later layers win:
+    ///
+    /// 1. the process environment
+    /// 2. the active profile's `env_vars`
+    /// 3. the active profile's key, base URL and model, under its provider's names
+    /// 4. `[env]` in `.happy/project.toml`
+    ///
+    /// An unreadable `project.toml` is skipped here; `new` reports it.
+    pub fn resolve_env(&self) -> HashMap<String, String> {
+        let overrides = project_overrides(&self.working_dir).unwrap_or_default();
+        self.layer_env(std::env::vars(), overrides.env)
+    }
+
+    /// The same build aimed at another platform
+    pub fn with_target(&self, target: Platform) -> Self {
+        Self {
+            target,
+            ..self.clone()
+        }
+    }
+
+    /// Where `target`'s output goes
+    pub fn output_dir(&self, config: &ProjectConfig) -> PathBuf {
+        self.working_dir.join(config.output_dir(self.target))
+    }
+
+    /// The profile named by `settings.active_profile`
+    pub fn active_profile(&self) -> Option<&AIProfile> {
+        let name = self.settings.active_profile.as_deref()?;
+        self.settings.profiles.iter().find(|p| p.name == name)
+    }
+
+    fn layer_env(
+        &self,
+        system: impl IntoIterator<Item = (String, String)>,
+        project: HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        let mut env: HashMap<String, String> = system.into_iter().collect();
+        if let Some(profile) = self.active_profile() {
+            env.extend(profile.env_vars.clone());
+            env.extend(profile_vars(profile));
+        }
+        env.extend(project);
+        env
+    }
+}
+
+/// The profile's connection settings under the names its provider's tools read
+fn profile_vars(profile: &AIProfile) -> Vec<(String, String)> {
+    let (key, url, model) = match profile.provider {
+        AIProvider::Anthropic => ("ANTHROPIC_API_KEY", "ANTHROPIC_BASE_URL", "ANTHROPIC_MODEL"),
+        AIProvider::OpenAI => ("OPENAI_API_KEY", "OPENAI_BASE_URL", "OPENAI_MODEL"),
+        AIProvider::Azure => (
+            "AZURE_OPENAI_API_KEY",
+            "AZURE_OPENAI_ENDPOINT",
+            "AZURE_OPENAI_DEPLOYMENT",
+        ),
+        AIProvider::Gemini => ("GEMINI_API_KEY", "GEMINI_BASE_URL", "GEMINI_MODEL"),
+        AIProvider::Ollama => ("OLLAMA_API_KEY", "OLLAMA_HOST", "OLLAMA_MODEL"),
+        // Self-hosted servers speak the OpenAI API, so OpenAI clients drive them
+        AIProvider::Custom(_) => ("OPENAI_API_KEY", "OPENAI_BASE_URL", "OPENAI_MODEL"),
+    };
+    [
+        (key, &profile.api_key),
+        (url, &profile.base_url),
+        (model, &profile.model),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name.to_string(), value.clone()?)))
+    .collect()
+}
+
+/// Read `.happy/project.toml` under `working_dir`; a missing file means no overrides
+fn project_overrides(working_dir: &Path) -> Result<ProjectOverrides> {
+    let path = working_dir.join(PROJECT_OVERRIDES_PATH);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(ProjectOverrides::default())
+        }
+        Err(e) => return Err(e.into()),
+    };
+    toml::from_str(&content)
+        .map_err(|e| HappyError::InvalidConfig(format!("{}: {}", path.display(), e)))
+}
 
 /// Build coordinator that orchestrates builds across multiple platforms
 pub struct Builder {
@@ -18,11 +145,24 @@ impl Builder {
     }
 
     /// Build for all enabled platforms
+    ///
+    /// `ctx` is retargeted at each platform in turn before it reaches the adapter.
     pub async fn build(
         &self,
+        ctx: &BuildContext,
+        config: &ProjectConfig,
+        options: &BuildOptions,
+    ) -> Result<BuildSummary> {
+        self.build_with_progress(ctx, config, options, |_| {}).await
+    }
+
+    /// Build for all enabled platforms, reporting a step per platform
+    pub async fn build_with_progress(
+        &self,
+        ctx: &BuildContext,
         config: &ProjectConfig,
-        project_dir: &Path,
         options: &BuildOptions,
+        on_event: impl Fn(BuildEvent),
     ) -> Result<BuildSummary> {
         let start = Instant::now();
 
@@ -49,18 +189,29 @@ impl Builder {
 
         // Run builds
         let mut results = Vec::new();
-        for platform in &platforms {
-            let output_dir = project_dir.join(config.output_dir(*platform));
-            
+        for (index, platform) in platforms.iter().enumerate() {
+            on_event(BuildEvent::Step {
+                name: platform.to_string(),
+                total: platforms.len(),
+                current: index + 1,
+            });
+
+            let ctx = ctx.with_target(*platform);
+            let output_dir = ctx.output_dir(config);
+
             // Clean if requested
             if options.clean && output_dir.exists() {
                 std::fs::remove_dir_all(&output_dir)?;
             }
 
             let result = match self.adapter_factory.get(*platform) {
-                Some(adapter) => adapter.build(config, &output_dir).await,
+                Some(adapter) => adapter.build(&ctx, config).await,
                 None => Err(HappyError::AdapterNotFound(platform.to_string())),
-            };
+            }
+            .and_then(|mut result| {
+                measure_artifacts(&mut result, &output_dir, options.release)?;
+                Ok(result)
+            });
 
             results.push(result.unwrap_or_else(|e| {
                 BuildResult::failure(*platform, vec![e.to_string()])
@@ -78,20 +229,21 @@ impl Builder {
         })
     }
 
-    /// Build for a single platform
+    /// Build for the single platform `ctx` targets
     pub async fn build_platform(
         &self,
+        ctx: &BuildContext,
         config: &ProjectConfig,
-        project_dir: &Path,
-        platform: Platform,
     ) -> Result<BuildResult> {
         let adapter = self
             .adapter_factory
-            .get(platform)
-            .ok_or_else(|| HappyError::AdapterNotFound(platform.to_string()))?;
+            .get(ctx.target)
+            .ok_or_else(|| HappyError::AdapterNotFound(ctx.target.to_string()))?;
 
-        let output_dir = project_dir.join(config.output_dir(platform));
-        adapter.build(config, &output_dir).await
+        let output_dir = ctx.output_dir(config);
+        let mut result = adapter.build(ctx, config).await?;
+        measure_artifacts(&mut result, &output_dir, false)?;
+        Ok(result)
     }
 
     /// Validate configuration for all enabled platforms
@@ -150,3 +302,344 @@ impl Builder {
         lines.join("\n")
     }
 }
+
+/// Fill in `size_bytes`, minifying JSON files first when `release` is set
+fn measure_artifacts(result: &mut BuildResult, output_dir: &Path, release: bool) -> Result<()> {
+    for file in &result.files {
+        let path = output_dir.join(file);
+        if release && path.extension().is_some_and(|ext| ext == "json") {
+            result.saved_bytes += minify_json(&path)?;
+        }
+        result.size_bytes += std::fs::metadata(&path)?.len();
+    }
+    Ok(())
+}
+
+/// Rewrite a JSON file without whitespace, returning the bytes saved
+fn minify_json(path: &Path) -> Result<u64> {
+    let pretty = std::fs::read_to_string(path)?;
+    let value: serde_json::Value = serde_json::from_str(&pretty)?;
+    let compact = serde_json::to_string(&value)?;
+    if compact.len() >= pretty.len() {
+        return Ok(0);
+    }
+    std::fs::write(path, &compact)?;
+    Ok((pretty.len() - compact.len()) as u64)
+}
+
+/// Assembles `Settings` and checks them before they are saved
+#[derive(Debug, Clone, Default)]
+pub struct SettingsBuilder {
+    settings: Settings,
+}
+
+impl SettingsBuilder {
+    /// Start from the defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from existing settings, e.g. ones read from disk
+    pub fn from_settings(settings: Settings) -> Self {
+        Self { settings }
+    }
+
+    pub fn server_url(mut self, url: &str) -> Self {
+        self.settings.server_url = url.trim().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn webapp_url(mut self, url: &str) -> Self {
+        self.settings.webapp_url = url.trim().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn add_profile(mut self, profile: AIProfile) -> Self {
+        self.settings.profiles.push(profile);
+        self
+    }
+
+    pub fn active_profile(mut self, name: &str) -> Self {
+        self.settings.active_profile = Some(name.to_string());
+        self
+    }
+
+    /// The settings, if they pass every check:
+    ///
+    /// - both URLs are https (plain http only for a server on this machine)
+    /// - the active profile, if any, exists
+    /// - no profile has an empty API key
+    /// - `version` is the schema this build writes
+    pub fn build(self) -> std::result::Result<Settings, SettingsValidationError> {
+        let settings = self.settings;
+        validate_url("server_url", &settings.server_url)?;
+        validate_url("webapp_url", &settings.webapp_url)?;
+
+        if let Some(active) = &settings.active_profile {
+            if !settings.profiles.iter().any(|p| &p.name == active) {
+                return Err(SettingsValidationError::UnknownActiveProfile(active.clone()));
+            }
+        }
+        if let Some(profile) = settings
+            .profiles
+            .iter()
+            .find(|p| p.api_key.as_deref().is_some_and(|key| key.trim().is_empty()))
+        {
+            return Err(SettingsValidationError::EmptyApiKey(profile.name.clone()));
+        }
+        if settings.version != SETTINGS_VERSION {
+            return Err(SettingsValidationError::VersionMismatch {
+                found: settings.version,
+                expected: SETTINGS_VERSION,
+            });
+        }
+
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+impl SettingsBuilder {
+    /// Builder with an active Anthropic profile called `name`
+    pub(crate) fn stub(name: &str) -> Self {
+        Self::new()
+            .add_profile(Self::stub_profile(name, Some("sk-test")))
+            .active_profile(name)
+    }
+
+    /// Anthropic profile with nothing but a name and key
+    pub(crate) fn stub_profile(name: &str, api_key: Option<&str>) -> AIProfile {
+        AIProfile {
+            api_key: api_key.map(String::from),
+            ..AIProfile::new(name, AIProvider::Anthropic)
+        }
+    }
+}
+
+/// `url` parses and uses https, or http to a loopback host
+fn validate_url(
+    field: &'static str,
+    url: &str,
+) -> std::result::Result<(), SettingsValidationError> {
+    let parsed = url::Url::parse(url).map_err(|e| SettingsValidationError::InvalidUrl {
+        field,
+        url: url.to_string(),
+        reason: e.to_string(),
+    })?;
+    let loopback = match parsed.host() {
+        Some(url::Host::Domain(host)) => host == "localhost",
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    };
+    match parsed.scheme() {
+        "https" => Ok(()),
+        "http" if loopback => Ok(()),
+        _ => Err(SettingsValidationError::InsecureUrl {
+            field,
+            url: url.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_artifacts_minifies_json_in_release() {
+        let dir = std::env::temp_dir().join(format!("happy-builder-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pretty = serde_json::to_string_pretty(&serde_json::json!({ "mcpServers": { "a": { "args": [1, 2] } } })).unwrap();
+        std::fs::write(dir.join("mcp.json"), &pretty).unwrap();
+        std::fs::write(dir.join("SKILL.md"), "# Skill\n").unwrap();
+
+        let files = vec!["mcp.json".to_string(), "SKILL.md".to_string()];
+        let mut dev = BuildResult::success(Platform::Claude, dir.display().to_string(), files.clone());
+        measure_artifacts(&mut dev, &dir, false).unwrap();
+        assert_eq!(dev.saved_bytes, 0);
+        assert_eq!(dev.size_bytes, pretty.len() as u64 + 8);
+
+        let mut release = BuildResult::success(Platform::Claude, dir.display().to_string(), files);
+        measure_artifacts(&mut release, &dir, true).unwrap();
+        assert!(release.saved_bytes > 0);
+        assert_eq!(release.size_bytes + release.saved_bytes, dev.size_bytes);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("mcp.json")).unwrap(),
+            r#"{"mcpServers":{"a":{"args":[1,2]}}}"#
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("happy-builder-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_resolve_env_layers() {
+        let dir = temp_dir();
+        std::fs::create_dir_all(dir.join(".happy")).unwrap();
+        std::fs::write(
+            dir.join(PROJECT_OVERRIDES_PATH),
+            "[env]\nANTHROPIC_MODEL = \"project-model\"\n",
+        )
+        .unwrap();
+
+        let profile = AIProfile {
+            model: Some("profile-model".to_string()),
+            env_vars: HashMap::from([
+                ("ANTHROPIC_API_KEY".to_string(), "sk-env-vars".to_string()),
+                ("EXTRA".to_string(), "1".to_string()),
+            ]),
+            ..SettingsBuilder::stub_profile("work", Some("sk-profile"))
+        };
+        let settings = Settings {
+            profiles: vec![profile],
+            active_profile: Some("work".to_string()),
+            ..Settings::default()
+        };
+
+        let ctx = BuildContext::new(settings, dir.clone(), Platform::Claude).unwrap();
+        assert_eq!(ctx.env["EXTRA"], "1");
+        // The profile's own key beats its env_vars, and the project beats both
+        assert_eq!(ctx.env["ANTHROPIC_API_KEY"], "sk-profile");
+        assert_eq!(ctx.env["ANTHROPIC_MODEL"], "project-model");
+        assert!(std::env::vars().all(|(k, _)| ctx.env.contains_key(&k)));
+        assert_eq!(ctx.resolve_env(), ctx.env);
+
+        std::fs::write(dir.join(PROJECT_OVERRIDES_PATH), "[env\n").unwrap();
+        assert!(BuildContext::new(Settings::default(), dir.clone(), Platform::Claude).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    type Seen = std::sync::Arc<std::sync::Mutex<Vec<(Platform, HashMap<String, String>)>>>;
+
+    /// Records the context of every build
+    struct RecordingAdapter {
+        seen: Seen,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::adapter::Adapter for RecordingAdapter {
+        fn platform(&self) -> Platform {
+            Platform::Codex
+        }
+
+        fn supported_features(&self) -> &[crate::types::Feature] {
+            &[]
+        }
+
+        fn limitations(&self) -> &[&str] {
+            &[]
+        }
+
+        async fn build(&self, ctx: &BuildContext, config: &ProjectConfig) -> Result<BuildResult> {
+            self.seen.lock().unwrap().push((ctx.target, ctx.env.clone()));
+            let output_dir = ctx.output_dir(config).display().to_string();
+            Ok(BuildResult::success(ctx.target, output_dir, Vec::new()))
+        }
+
+        async fn install(
+            &self,
+            _source: &Path,
+            _target: &crate::types::InstallTarget,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn validate(&self, _config: &ProjectConfig) -> crate::types::ValidationResult {
+            crate::types::ValidationResult::default()
+        }
+
+        fn global_install_path(&self) -> Option<PathBuf> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn test_adapter_receives_context_env() {
+        let seen = Seen::default();
+        let mut factory = AdapterFactory::new();
+        factory.register(Box::new(RecordingAdapter { seen: seen.clone() }));
+        let builder = Builder::new(factory);
+
+        let ctx = BuildContext {
+            settings: Settings::default(),
+            working_dir: temp_dir(),
+            target: Platform::Claude,
+            env: HashMap::from([("KNOWN".to_string(), "value".to_string())]),
+        };
+        let options = BuildOptions {
+            target: Some(Platform::Codex),
+            ..BuildOptions::default()
+        };
+        let summary = builder
+            .build(&ctx, &ProjectConfig::new("test"), &options)
+            .await
+            .unwrap();
+
+        assert!(summary.success);
+        assert_eq!(*seen.lock().unwrap(), vec![(Platform::Codex, ctx.env.clone())]);
+
+        let _ = std::fs::remove_dir_all(&ctx.working_dir);
+    }
+
+    #[test]
+    fn test_settings_builder_validation() {
+        let settings = SettingsBuilder::stub("work")
+            .server_url("https://happy.example.com/api/v1/")
+            .build()
+            .unwrap();
+        assert_eq!(settings.server_url, "https://happy.example.com/api/v1");
+        assert_eq!(settings.active_profile.as_deref(), Some("work"));
+        SettingsBuilder::new().server_url("http://127.0.0.1:3000").build().unwrap();
+
+        let blank_key = SettingsBuilder::stub_profile("work", Some(" "));
+        let old_version = Settings {
+            version: "1.0.0".to_string(),
+            ..Settings::default()
+        };
+        let err = SettingsBuilder::new().server_url("not a url").build().unwrap_err();
+        assert!(
+            matches!(err, SettingsValidationError::InvalidUrl { field: "server_url", .. }),
+            "{:?}",
+            err
+        );
+
+        let err = SettingsBuilder::new()
+            .webapp_url("http://happy.example.com")
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, SettingsValidationError::InsecureUrl { field: "webapp_url", .. }),
+            "{:?}",
+            err
+        );
+
+        let err = SettingsBuilder::stub("work").active_profile("home").build().unwrap_err();
+        assert!(
+            matches!(&err, SettingsValidationError::UnknownActiveProfile(name) if name == "home"),
+            "{:?}",
+            err
+        );
+
+        let err = SettingsBuilder::new().add_profile(blank_key).build().unwrap_err();
+        assert!(
+            matches!(&err, SettingsValidationError::EmptyApiKey(name) if name == "work"),
+            "{:?}",
+            err
+        );
+
+        let err = SettingsBuilder::from_settings(old_version).build().unwrap_err();
+        assert!(
+            matches!(&err, SettingsValidationError::VersionMismatch { found, .. }
+                if found == "1.0.0"),
+            "{:?}",
+            err
+        );
+    }
+}
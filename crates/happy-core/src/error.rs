@@ -35,8 +35,37 @@ pub enum HappyError {
     #[error("Watch error: {0}")]
     Watch(String),
 
+    #[error("Sandbox error: {0}")]
+    Sandbox(String),
+
     #[error("{0}")]
     Other(String),
 }
 
 pub type Result<T> = std::result::Result<T, HappyError>;
+
+/// Why `SettingsBuilder::build` rejected the settings
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SettingsValidationError {
+    #[error("Invalid {field} '{url}': {reason}")]
+    InvalidUrl {
+        field: &'static str,
+        url: String,
+        reason: String,
+    },
+
+    #[error("{field} must use https: {url}")]
+    InsecureUrl { field: &'static str, url: String },
+
+    #[error("Active profile '{0}' does not exist")]
+    UnknownActiveProfile(String),
+
+    #[error("Profile '{0}' has an empty API key")]
+    EmptyApiKey(String),
+
+    #[error("Settings version {found} does not match {expected}")]
+    VersionMismatch {
+        found: String,
+        expected: &'static str,
+    },
+}
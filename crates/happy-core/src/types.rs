@@ -9,12 +9,22 @@ pub enum Platform {
     Claude,
     Codex,
     Antigravity,
+    /// Local models served by Ollama
+    Ollama,
+    /// Any OpenAI-compatible server, such as vLLM or LocalAI
+    OpenAI,
 }
 
 impl Platform {
     /// Get all available platforms
     pub fn all() -> &'static [Platform] {
-        &[Platform::Claude, Platform::Codex, Platform::Antigravity]
+        &[
+            Platform::Claude,
+            Platform::Codex,
+            Platform::Antigravity,
+            Platform::Ollama,
+            Platform::OpenAI,
+        ]
     }
 
     /// Get the platform name as a string
@@ -23,6 +33,8 @@ impl Platform {
             Platform::Claude => "claude",
             Platform::Codex => "codex",
             Platform::Antigravity => "antigravity",
+            Platform::Ollama => "ollama",
+            Platform::OpenAI => "openai",
         }
     }
 
@@ -32,6 +44,8 @@ impl Platform {
             Platform::Claude => ".claude",
             Platform::Codex => ".codex",
             Platform::Antigravity => ".agent",
+            Platform::Ollama => ".ollama",
+            Platform::OpenAI => ".openai",
         }
     }
 }
@@ -189,6 +203,10 @@ pub struct TargetsConfig {
     pub codex: Option<TargetConfig>,
     #[serde(default)]
     pub antigravity: Option<TargetConfig>,
+    #[serde(default)]
+    pub ollama: Option<TargetConfig>,
+    #[serde(default)]
+    pub openai: Option<TargetConfig>,
 }
 
 impl TargetsConfig {
@@ -198,6 +216,8 @@ impl TargetsConfig {
             Platform::Claude => self.claude.as_ref(),
             Platform::Codex => self.codex.as_ref(),
             Platform::Antigravity => self.antigravity.as_ref(),
+            Platform::Ollama => self.ollama.as_ref(),
+            Platform::OpenAI => self.openai.as_ref(),
         }
     }
 
@@ -218,6 +238,12 @@ impl TargetsConfig {
         {
             platforms.push(Platform::Antigravity);
         }
+        if self.ollama.as_ref().map(|t| t.enabled).unwrap_or(false) {
+            platforms.push(Platform::Ollama);
+        }
+        if self.openai.as_ref().map(|t| t.enabled).unwrap_or(false) {
+            platforms.push(Platform::OpenAI);
+        }
 
         platforms
     }
@@ -285,6 +311,8 @@ impl ProjectConfig {
                 claude: Some(TargetConfig::default()),
                 codex: Some(TargetConfig::default()),
                 antigravity: None,
+                ollama: None,
+                openai: None,
             },
             skills: Vec::new(),
             workflows: Vec::new(),
@@ -303,7 +331,7 @@ impl ProjectConfig {
 }
 
 /// Build result for a single platform
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BuildResult {
     pub success: bool,
     pub platform: Platform,
@@ -311,6 +339,10 @@ pub struct BuildResult {
     pub files: Vec<String>,
     pub warnings: Vec<String>,
     pub errors: Vec<String>,
+    /// Total size of `files` on disk
+    pub size_bytes: u64,
+    /// Bytes removed by release minification
+    pub saved_bytes: u64,
 }
 
 impl BuildResult {
@@ -323,6 +355,8 @@ impl BuildResult {
             files,
             warnings: Vec::new(),
             errors: Vec::new(),
+            size_bytes: 0,
+            saved_bytes: 0,
         }
     }
 
@@ -335,6 +369,8 @@ impl BuildResult {
             files: Vec::new(),
             warnings: Vec::new(),
             errors,
+            size_bytes: 0,
+            saved_bytes: 0,
         }
     }
 }
@@ -396,10 +432,12 @@ pub struct BuildOptions {
     pub target: Option<Platform>,
     pub watch: bool,
     pub clean: bool,
+    /// Minify generated JSON; dev builds keep it pretty-printed for reading
+    pub release: bool,
 }
 
 /// Build summary across all platforms
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BuildSummary {
     pub success: bool,
     pub results: Vec<BuildResult>,
@@ -409,6 +447,7 @@ pub struct BuildSummary {
 
 // =============== Remote Control Types ===============
 
+use crate::error::HappyError;
 use chrono::{DateTime, Utc};
 
 /// User account information
@@ -453,6 +492,24 @@ pub enum AIProvider {
     OpenAI,
     Azure,
     Gemini,
+    /// Models served locally by Ollama; no API key needed
+    Ollama,
+    /// A self-hosted OpenAI-compatible server (vLLM, LocalAI, ...), by display name.
+    /// Profiles using it must set `base_url`.
+    Custom(String),
+}
+
+impl std::fmt::Display for AIProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AIProvider::Anthropic => write!(f, "anthropic"),
+            AIProvider::OpenAI => write!(f, "openai"),
+            AIProvider::Azure => write!(f, "azure"),
+            AIProvider::Gemini => write!(f, "gemini"),
+            AIProvider::Ollama => write!(f, "ollama"),
+            AIProvider::Custom(name) => write!(f, "{}", name),
+        }
+    }
 }
 
 /// AI profile configuration
@@ -467,6 +524,275 @@ pub struct AIProfile {
     pub default: bool,
     #[serde(default)]
     pub env_vars: std::collections::HashMap<String, String>,
+    /// Sandbox `happy run` uses for this profile unless `--sandbox` is given
+    #[serde(default)]
+    pub sandbox_policy: Option<crate::utils::sandbox::SandboxMode>,
+    /// Generation parameters; `None` leaves the provider default. `happy run`
+    /// only applies `max_tokens`, Claude Code has no way to take the others.
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Tokens `happy run` may use per UTC day before refusing to start
+    #[serde(default)]
+    pub max_tokens_per_day: Option<u64>,
+}
+
+impl AIProfile {
+    /// Generation parameters accepted by `set_param`
+    pub const PARAMS: &'static [&'static str] =
+        &["max_tokens", "temperature", "top_p", "system_prompt", "max_tokens_per_day"];
+
+    /// Profile with no key, URL, model or overrides; provider defaults apply
+    pub fn new(name: impl Into<String>, provider: AIProvider) -> Self {
+        Self {
+            name: name.into(),
+            provider,
+            api_key: None,
+            base_url: None,
+            model: None,
+            default: false,
+            env_vars: std::collections::HashMap::new(),
+            sandbox_policy: None,
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            system_prompt: None,
+            max_tokens_per_day: None,
+        }
+    }
+
+    /// Set a generation parameter from its string form; `unset` restores the provider default.
+    ///
+    /// The profile is left unchanged if the value doesn't parse or is out of range.
+    pub fn set_param(&mut self, param: &str, value: &str) -> crate::Result<()> {
+        let unset = value == "unset";
+        let invalid = |e: &dyn std::fmt::Display| {
+            HappyError::Validation(format!("Invalid value for {}: {}", param, e))
+        };
+
+        let mut updated = self.clone();
+        match param {
+            "max_tokens" if unset => updated.max_tokens = None,
+            "max_tokens" => updated.max_tokens = Some(value.parse().map_err(|e| invalid(&e))?),
+            "temperature" if unset => updated.temperature = None,
+            "temperature" => updated.temperature = Some(value.parse().map_err(|e| invalid(&e))?),
+            "top_p" if unset => updated.top_p = None,
+            "top_p" => updated.top_p = Some(value.parse().map_err(|e| invalid(&e))?),
+            "system_prompt" if unset => updated.system_prompt = None,
+            "system_prompt" => updated.system_prompt = Some(value.to_string()),
+            "max_tokens_per_day" if unset => updated.max_tokens_per_day = None,
+            "max_tokens_per_day" => {
+                updated.max_tokens_per_day = Some(value.parse().map_err(|e| invalid(&e))?)
+            }
+            _ => {
+                return Err(HappyError::Validation(format!(
+                    "Unknown parameter '{}'. Supported: {}",
+                    param,
+                    Self::PARAMS.join(", ")
+                )))
+            }
+        }
+
+        updated.validate()?;
+        *self = updated;
+        Ok(())
+    }
+
+    /// Check generation parameters are in range for every provider, and that a
+    /// custom provider has an endpoint
+    pub fn validate(&self) -> crate::Result<()> {
+        if let AIProvider::Custom(name) = &self.provider {
+            if self.base_url.as_deref().is_none_or(|url| url.trim().is_empty()) {
+                return Err(HappyError::Validation(format!(
+                    "custom provider '{}' requires a base_url",
+                    name
+                )));
+            }
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(HappyError::Validation(format!(
+                    "temperature must be between 0.0 and 2.0, got {}",
+                    temperature
+                )));
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                return Err(HappyError::Validation(format!(
+                    "top_p must be between 0.0 and 1.0, got {}",
+                    top_p
+                )));
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err(HappyError::Validation(
+                "max_tokens must be greater than 0".to_string(),
+            ));
+        }
+        if self.max_tokens_per_day == Some(0) {
+            return Err(HappyError::Validation(
+                "max_tokens_per_day must be greater than 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `env_vars` with references expanded from the process environment.
+    ///
+    /// Values may use `${VAR}`, `${VAR:-default}` (used when `VAR` is unset or
+    /// empty) and `$$` for a literal `$`. A reference to another entry of the
+    /// profile expands that entry; a reference to the entry's own name reads
+    /// the environment, so `PATH = ${PATH}:/opt/bin` extends it. Undefined
+    /// variables are an error when `strict`, otherwise empty. Malformed
+    /// references and chains of entries deeper than
+    /// [`MAX_ENV_REFERENCE_DEPTH`], which are taken to be circular, are always
+    /// errors.
+    pub fn resolve_env_vars(
+        &self,
+        strict: bool,
+    ) -> crate::Result<std::collections::HashMap<String, String>> {
+        self.resolve_env_vars_with(strict, |name| std::env::var(name).ok())
+    }
+
+    /// [`resolve_env_vars`](Self::resolve_env_vars) looking variables up with `lookup`
+    pub fn resolve_env_vars_with(
+        &self,
+        strict: bool,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> crate::Result<std::collections::HashMap<String, String>> {
+        self.env_vars
+            .iter()
+            .map(|(name, value)| {
+                let value = self.expand_env_value(name, value, strict, &lookup, 0)?;
+                Ok((name.clone(), value))
+            })
+            .collect()
+    }
+
+    /// One entry of `env_vars` expanded as by
+    /// [`resolve_env_vars_with`](Self::resolve_env_vars_with), or `None` if
+    /// the profile doesn't set it
+    pub fn resolve_env_var_with(
+        &self,
+        name: &str,
+        strict: bool,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> crate::Result<Option<String>> {
+        self.env_vars
+            .get(name)
+            .map(|value| self.expand_env_value(name, value, strict, &lookup, 0))
+            .transpose()
+    }
+
+    fn expand_env_value(
+        &self,
+        name: &str,
+        value: &str,
+        strict: bool,
+        lookup: &dyn Fn(&str) -> Option<String>,
+        depth: usize,
+    ) -> crate::Result<String> {
+        if depth > MAX_ENV_REFERENCE_DEPTH {
+            return Err(HappyError::Validation(format!(
+                "{}: references nest more than {} deep; are they circular?",
+                name, MAX_ENV_REFERENCE_DEPTH
+            )));
+        }
+
+        let mut expanded = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(start) = rest.find('$') {
+            expanded.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+            if let Some(after) = rest.strip_prefix('$') {
+                expanded.push('$');
+                rest = after;
+                continue;
+            }
+            // A `$` not starting a reference is kept as is
+            let Some(body) = rest.strip_prefix('{') else {
+                expanded.push('$');
+                continue;
+            };
+            let end = closing_brace(body).ok_or_else(|| {
+                HappyError::Validation(format!("{}: unterminated '${{' in '{}'", name, value))
+            })?;
+            rest = &body[end + 1..];
+
+            let (reference, default) = match body[..end].split_once(":-") {
+                Some((reference, default)) => (reference, Some(default)),
+                None => (&body[..end], None),
+            };
+            if !is_env_var_name(reference) {
+                return Err(HappyError::Validation(format!(
+                    "{}: '${{{}}}' is not a valid variable reference",
+                    name,
+                    &body[..end]
+                )));
+            }
+
+            let resolved = match self.env_vars.get(reference) {
+                Some(other) if reference != name => {
+                    Some(self.expand_env_value(reference, other, strict, lookup, depth + 1)?)
+                }
+                _ => lookup(reference),
+            };
+            match (resolved, default) {
+                (Some(resolved), Some(_)) if !resolved.is_empty() => expanded.push_str(&resolved),
+                (Some(resolved), None) => expanded.push_str(&resolved),
+                (_, Some(default)) => {
+                    expanded.push_str(&self.expand_env_value(name, default, strict, lookup, depth)?)
+                }
+                (None, None) if strict => {
+                    return Err(HappyError::Validation(format!(
+                        "{}: '{}' is not defined",
+                        name, reference
+                    )))
+                }
+                (None, None) => {}
+            }
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+/// Longest chain of profile env vars referring to one another
+pub const MAX_ENV_REFERENCE_DEPTH: usize = 10;
+
+/// Index of the `}` closing a reference whose body starts `text`, skipping nested ones
+fn closing_brace(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut nested = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                nested += 1;
+                i += 1;
+            }
+            b'}' if nested == 0 => return Some(i),
+            b'}' => nested -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Letters, digits and underscores, not starting with a digit
+fn is_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 /// Registered machine
@@ -480,6 +806,9 @@ pub struct Machine {
     pub created_at: DateTime<Utc>,
 }
 
+/// Shape of `settings.json` written by this build; older files are migrated on load
+pub const SETTINGS_VERSION: &str = "1.1.0";
+
 /// User settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
@@ -489,12 +818,18 @@ pub struct Settings {
     pub password: Option<String>,
     pub access_token: Option<String>,
     pub refresh_token: Option<String>,
+    /// When `access_token` expires; unknown for tokens saved by older builds
+    #[serde(default)]
+    pub token_expires_at: Option<DateTime<Utc>>,
     pub server_url: String,
     pub webapp_url: String,
     pub profiles: Vec<AIProfile>,
     pub active_profile: Option<String>,
     #[serde(default)]
     pub machines: Vec<Machine>,
+    /// Keep tokens and profile API keys in the OS keychain instead of this file
+    #[serde(default)]
+    pub use_keyring: bool,
     /// Unique machine identifier (loaded from separate file, not synced in settings.json)
     #[serde(skip)]
     pub machine_id: String,
@@ -503,18 +838,178 @@ pub struct Settings {
 impl Default for Settings {
     fn default() -> Self {
         Self {
-            version: "1.0.0".to_string(),
+            version: SETTINGS_VERSION.to_string(),
             user_id: None,
             email: None,
             password: None,
             access_token: None,
             refresh_token: None,
+            token_expires_at: None,
             server_url: "https://api.happy-remote.dev".to_string(),
             webapp_url: "https://app.happy-remote.dev".to_string(),
             profiles: Vec::new(),
             active_profile: None,
             machines: Vec::new(),
+            use_keyring: false,
             machine_id: String::new(),
         }
     }
 }
+
+impl Settings {
+    /// Keep tokens from a login, registration or refresh
+    pub fn store_tokens(&mut self, tokens: AuthTokens) {
+        self.token_expires_at = (tokens.expires_in > 0)
+            .then(|| Utc::now() + chrono::Duration::seconds(tokens.expires_in));
+        self.access_token = Some(tokens.access_token);
+        self.refresh_token = Some(tokens.refresh_token);
+    }
+
+    /// Whether the access token expires within `margin`
+    pub fn token_expires_within(&self, margin: chrono::Duration) -> bool {
+        self.token_expires_at
+            .is_some_and(|expires_at| expires_at - Utc::now() < margin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> AIProfile {
+        AIProfile::new("test", AIProvider::Anthropic)
+    }
+
+    #[test]
+    fn test_set_param() {
+        let mut profile = profile();
+        profile.set_param("max_tokens", "4096").unwrap();
+        profile.set_param("temperature", "0.7").unwrap();
+        profile.set_param("system_prompt", "Be terse").unwrap();
+        assert_eq!(profile.max_tokens, Some(4096));
+        assert_eq!(profile.temperature, Some(0.7));
+        assert_eq!(profile.system_prompt.as_deref(), Some("Be terse"));
+
+        profile.set_param("max_tokens", "unset").unwrap();
+        assert_eq!(profile.max_tokens, None);
+
+        assert!(profile.set_param("max_tokens", "lots").is_err());
+        assert!(profile.set_param("seed", "1").is_err());
+    }
+
+    #[test]
+    fn test_validate_ranges() {
+        let mut profile = profile();
+        assert!(profile.set_param("temperature", "2.0").is_ok());
+        assert!(profile.set_param("temperature", "2.1").is_err());
+        assert!(profile.set_param("top_p", "1.0").is_ok());
+        assert!(profile.set_param("top_p", "-0.1").is_err());
+        assert!(profile.set_param("max_tokens", "0").is_err());
+        assert!(profile.set_param("max_tokens_per_day", "0").is_err());
+        assert!(profile.set_param("max_tokens_per_day", "200000").is_ok());
+    }
+
+    #[test]
+    fn test_ollama_profile_round_trip() {
+        let mut profile = profile();
+        profile.provider = AIProvider::Ollama;
+        profile.base_url = Some("http://localhost:11434".to_string());
+
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["provider"], "ollama");
+        let parsed: AIProfile = serde_json::from_value(json).unwrap();
+        assert!(matches!(parsed.provider, AIProvider::Ollama));
+        assert_eq!(parsed.base_url, profile.base_url);
+    }
+
+    #[test]
+    fn test_custom_provider() {
+        let mut profile = profile();
+        profile.provider = AIProvider::Custom("vllm".to_string());
+        assert!(profile.validate().is_err());
+        profile.base_url = Some("http://gpu-box:8000/v1".to_string());
+        assert!(profile.validate().is_ok());
+        assert_eq!(profile.provider.to_string(), "vllm");
+
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["provider"], serde_json::json!({ "custom": "vllm" }));
+        let parsed: AIProfile = serde_json::from_value(json).unwrap();
+        assert!(matches!(parsed.provider, AIProvider::Custom(ref name) if name == "vllm"));
+    }
+
+    fn with_env(entries: &[(&str, &str)]) -> AIProfile {
+        let mut profile = profile();
+        profile.env_vars = entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        profile
+    }
+
+    fn system(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/me".to_string()),
+            "PATH" => Some("/usr/bin".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_env_vars() {
+        let profile = with_env(&[
+            ("HOME_DIR", "${HOME}"),
+            ("CONFIG", "${HOME_DIR}/.config"),
+            ("PATH", "${PATH}:${HOME_DIR}/bin"),
+            ("PRICE", "$$5 and $ alone"),
+            ("REGION", "${REGION_OVERRIDE:-us-east-1}"),
+            ("FALLBACK", "${EMPTY:-${HOME}}"),
+            ("PLAIN", "no references"),
+        ]);
+
+        let env = profile.resolve_env_vars_with(true, system).unwrap();
+        assert_eq!(env["HOME_DIR"], "/home/me");
+        assert_eq!(env["CONFIG"], "/home/me/.config");
+        assert_eq!(env["PATH"], "/usr/bin:/home/me/bin");
+        assert_eq!(env["PRICE"], "$5 and $ alone");
+        assert_eq!(env["REGION"], "us-east-1");
+        assert_eq!(env["FALLBACK"], "/home/me");
+        assert_eq!(env["PLAIN"], "no references");
+    }
+
+    #[test]
+    fn test_resolve_env_vars_undefined() {
+        let profile = with_env(&[("KEY", "${VAULT_KEY}-suffix")]);
+        let err = profile.resolve_env_vars_with(true, system).unwrap_err();
+        assert!(err.to_string().contains("VAULT_KEY"), "{}", err);
+
+        let env = profile.resolve_env_vars_with(false, system).unwrap();
+        assert_eq!(env["KEY"], "-suffix");
+        assert_eq!(
+            profile.resolve_env_var_with("KEY", false, system).unwrap(),
+            Some("-suffix".to_string())
+        );
+        assert_eq!(profile.resolve_env_var_with("OTHER", true, system).unwrap(), None);
+    }
+
+    #[test]
+    fn test_resolve_env_vars_errors() {
+        let circular = with_env(&[("A", "${B}"), ("B", "x${A}")]);
+        let err = circular.resolve_env_vars_with(false, system).unwrap_err();
+        assert!(err.to_string().contains("circular"), "{}", err);
+
+        for value in ["${HOME", "${}", "${1X}", "${HOME DIR}"] {
+            let profile = with_env(&[("BAD", value)]);
+            assert!(profile.resolve_env_vars_with(false, system).is_err(), "{}", value);
+        }
+
+        // Ten entries in a chain are fine
+        let chain: Vec<(String, String)> = (0..10)
+            .map(|i| (format!("V{}", i), format!("${{V{}}}", i + 1)))
+            .chain([("V10".to_string(), "end".to_string())])
+            .collect();
+        let refs: Vec<(&str, &str)> = chain.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let env = with_env(&refs).resolve_env_vars_with(true, system).unwrap();
+        assert_eq!(env["V0"], "end");
+    }
+}
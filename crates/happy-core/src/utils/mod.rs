@@ -1,5 +1,7 @@
 //! Core utilities for Happy Coding
 
+pub mod sandbox;
+
 /// Get machine name - prefer macOS ComputerName for user-friendly name
 pub fn get_machine_name() -> String {
     #[cfg(target_os = "macos")]
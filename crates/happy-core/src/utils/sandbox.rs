@@ -0,0 +1,333 @@
+//! OS-level sandboxing for agent processes
+//!
+//! - Linux: `unshare` into fresh user, PID and mount namespaces (plus a network namespace
+//!   in strict mode). The home directory is hidden behind a tmpfs, and only the working
+//!   directory, `$HAPPY_HOME` and the agent's own config are bind-mounted back in.
+//! - macOS: `sandbox-exec` with a generated `.sb` profile that allows reads in the
+//!   working directory and writes only there and under `$HAPPY_HOME`.
+//!
+//! Other platforms have no sandbox; asking for one there is an error rather than an
+//! unconfined run.
+
+use crate::{HappyError, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// How tightly the agent is confined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxMode {
+    /// Files outside the working directory are off limits; the network stays reachable
+    Standard,
+    /// Standard, plus no network access
+    Strict,
+}
+
+impl SandboxMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SandboxMode::Standard => "standard",
+            SandboxMode::Strict => "strict",
+        }
+    }
+}
+
+impl std::fmt::Display for SandboxMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for SandboxMode {
+    type Err = HappyError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "standard" => Ok(SandboxMode::Standard),
+            "strict" => Ok(SandboxMode::Strict),
+            _ => Err(HappyError::Sandbox(format!(
+                "Unknown sandbox mode: {}. Supported: standard, strict",
+                s
+            ))),
+        }
+    }
+}
+
+/// Files under the home directory the agent needs to keep working
+#[cfg(any(target_os = "linux", target_os = "macos", test))]
+const AGENT_CONFIG_PATHS: [&str; 2] = [".claude", ".claude.json"];
+
+/// Runs inside the new namespaces: hide `$HOME` behind a tmpfs, bind the allowed
+/// paths back in, then exec the agent from the working directory.
+///
+/// Arguments: `<work dir> <allowed path>... -- <program> <args>...`
+#[cfg(any(target_os = "linux", test))]
+const LINUX_SETUP_SCRIPT: &str = r#"set -e
+work_dir="$1"; shift
+real_home=$(mktemp -d)
+mount --rbind "$HOME" "$real_home"
+mount -t tmpfs tmpfs "$HOME"
+while [ "$1" != "--" ]; do
+    rel="${1#"$HOME"/}"
+    if [ -d "$real_home/$rel" ]; then
+        mkdir -p "$HOME/$rel"
+    else
+        mkdir -p "$(dirname "$HOME/$rel")"
+        touch "$HOME/$rel"
+    fi
+    mount --rbind "$real_home/$rel" "$HOME/$rel"
+    shift
+done
+shift
+umount -l "$real_home"
+cd "$work_dir"
+exec "$@""#;
+
+// Unsupported platforms only need the mode
+#[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+pub struct Sandbox {
+    mode: SandboxMode,
+    work_dir: PathBuf,
+    happy_home: PathBuf,
+    home: Option<PathBuf>,
+}
+
+impl Sandbox {
+    /// Sandbox the current directory, with `$HAPPY_HOME` (default `~/.happy`) writable
+    pub fn new(mode: SandboxMode) -> Self {
+        let home = dirs::home_dir();
+        let happy_home = std::env::var_os("HAPPY_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|h| h.join(".happy")))
+            .unwrap_or_else(|| PathBuf::from(".happy"));
+
+        Self {
+            mode,
+            work_dir: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            happy_home,
+            home,
+        }
+    }
+
+    pub fn with_work_dir(mut self, work_dir: impl Into<PathBuf>) -> Self {
+        self.work_dir = work_dir.into();
+        self
+    }
+
+    pub fn mode(&self) -> SandboxMode {
+        self.mode
+    }
+
+    /// The program and arguments that run `program args` inside the sandbox.
+    ///
+    /// Fails on platforms without a sandbox, so callers never run unconfined.
+    pub fn wrap(&self, program: &OsStr, args: &[OsString]) -> Result<(OsString, Vec<OsString>)> {
+        #[cfg(target_os = "linux")]
+        {
+            let mut wrapped = self.linux_args();
+            wrapped.push(program.to_os_string());
+            wrapped.extend(args.iter().cloned());
+            Ok(("unshare".into(), wrapped))
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            let profile = self.write_macos_profile()?;
+            let mut wrapped: Vec<OsString> = vec!["-f".into(), profile.into_os_string()];
+            wrapped.push(program.to_os_string());
+            wrapped.extend(args.iter().cloned());
+            Ok(("sandbox-exec".into(), wrapped))
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        {
+            let _ = (program, args);
+            Err(HappyError::Sandbox(format!(
+                "{} sandbox is not supported on this platform",
+                self.mode
+            )))
+        }
+    }
+
+    /// Paths under the home directory that stay visible
+    #[cfg(any(target_os = "linux", test))]
+    fn allowed_home_paths(&self) -> Vec<PathBuf> {
+        let Some(home) = &self.home else {
+            return Vec::new();
+        };
+
+        let mut paths = vec![self.work_dir.clone(), self.happy_home.clone()];
+        paths.extend(AGENT_CONFIG_PATHS.iter().map(|p| home.join(p)));
+        paths.retain(|p| p.starts_with(home) && p != home && p.exists());
+        paths
+    }
+
+    #[cfg(any(target_os = "linux", test))]
+    fn linux_args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = [
+            "--user",
+            "--map-root-user",
+            "--fork",
+            "--pid",
+            "--mount-proc",
+            "--mount",
+        ]
+        .iter()
+        .map(OsString::from)
+        .collect();
+        if self.mode == SandboxMode::Strict {
+            args.push("--net".into());
+        }
+
+        // Hiding the home directory only makes sense when working somewhere inside it
+        let hide_home = self
+            .home
+            .as_ref()
+            .is_some_and(|home| self.work_dir.starts_with(home) && self.work_dir != *home);
+
+        args.push("--".into());
+        if hide_home {
+            args.extend(["sh", "-c", LINUX_SETUP_SCRIPT, "sh"].map(OsString::from));
+            args.push(self.work_dir.clone().into_os_string());
+            args.extend(
+                self.allowed_home_paths()
+                    .into_iter()
+                    .map(PathBuf::into_os_string),
+            );
+            args.push("--".into());
+        } else {
+            args.extend(["sh", "-c", r#"cd "$1" && shift && exec "$@""#, "sh"].map(OsString::from));
+            args.push(self.work_dir.clone().into_os_string());
+        }
+        args
+    }
+
+    #[cfg(target_os = "macos")]
+    fn write_macos_profile(&self) -> Result<PathBuf> {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.work_dir.hash(&mut hasher);
+        let dir = self.happy_home.join("sandbox");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}-{:016x}.sb", self.mode, hasher.finish()));
+        std::fs::write(&path, self.macos_profile())?;
+        Ok(path)
+    }
+
+    #[cfg(any(target_os = "macos", test))]
+    fn macos_profile(&self) -> String {
+        let work_dir = sb_string(&self.work_dir);
+        let happy_home = sb_string(&self.happy_home);
+        let agent_paths: String = self
+            .home
+            .iter()
+            .flat_map(|home| AGENT_CONFIG_PATHS.iter().map(move |p| home.join(p)))
+            .map(|p| format!("\n    (subpath {})", sb_string(&p)))
+            .collect();
+
+        let mut profile = format!(
+            r#"(version 1)
+(allow default)
+
+; Writes only in the project, Happy's home, the agent's config and temp dirs
+(deny file-write*)
+(allow file-write*
+    (subpath {work_dir})
+    (subpath {happy_home}){agent_paths}
+    (subpath "/private/tmp")
+    (subpath "/private/var/folders")
+    (subpath "/dev"))
+"#
+        );
+
+        if let Some(home) = &self.home {
+            profile.push_str(&format!(
+                r#"
+; Nothing else under the home directory is readable
+(deny file-read* (subpath {home}))
+(allow file-read*
+    (literal {home})
+    (subpath {work_dir})
+    (subpath {happy_home}){agent_paths})
+"#,
+                home = sb_string(home),
+            ));
+        }
+
+        if self.mode == SandboxMode::Strict {
+            profile.push_str(
+                r#"
+(deny network*)
+(allow network* (remote unix-socket))
+"#,
+            );
+        }
+
+        profile
+    }
+}
+
+/// A path as an SBPL string literal
+#[cfg(any(target_os = "macos", test))]
+fn sb_string(path: &std::path::Path) -> String {
+    let escaped = path
+        .to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandbox(mode: SandboxMode) -> Sandbox {
+        Sandbox {
+            mode,
+            work_dir: PathBuf::from("/home/dev/project"),
+            happy_home: PathBuf::from("/home/dev/.happy"),
+            home: Some(PathBuf::from("/home/dev")),
+        }
+    }
+
+    #[test]
+    fn test_linux_args() {
+        let standard = sandbox(SandboxMode::Standard).linux_args();
+        let strict = sandbox(SandboxMode::Strict).linux_args();
+        assert!(!standard.contains(&OsString::from("--net")));
+        assert!(strict.contains(&OsString::from("--net")));
+        assert!(standard.contains(&OsString::from(LINUX_SETUP_SCRIPT)));
+        assert_eq!(standard.last(), Some(&OsString::from("--")));
+
+        let outside_home = sandbox(SandboxMode::Standard).with_work_dir("/srv/project");
+        assert!(!outside_home
+            .linux_args()
+            .contains(&OsString::from(LINUX_SETUP_SCRIPT)));
+    }
+
+    #[test]
+    fn test_macos_profile() {
+        let profile = sandbox(SandboxMode::Strict).macos_profile();
+        assert!(profile.contains(r#"(subpath "/home/dev/project")"#));
+        assert!(profile.contains(r#"(deny file-read* (subpath "/home/dev"))"#));
+        assert!(profile.contains("(deny network*)"));
+        assert!(!sandbox(SandboxMode::Standard)
+            .macos_profile()
+            .contains("(deny network*)"));
+    }
+
+    #[test]
+    fn test_mode_serde() {
+        assert_eq!(
+            serde_json::to_string(&SandboxMode::Strict).unwrap(),
+            "\"strict\""
+        );
+        assert_eq!(
+            "Standard".parse::<SandboxMode>().unwrap(),
+            SandboxMode::Standard
+        );
+        assert!("none".parse::<SandboxMode>().is_err());
+    }
+}
@@ -1,12 +1,44 @@
 //! File watcher for development mode
 
 use crate::error::{HappyError, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use notify::RecursiveMode;
 use notify_debouncer_mini::{new_debouncer, DebouncedEventKind};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::time::Duration;
 
+/// Paths skipped unless the caller overrides `exclude_globs`
+pub const DEFAULT_EXCLUDE_GLOBS: &[&str] = &["**/.git/**", "**/target/**", "**/node_modules/**"];
+
+/// Window in which the underlying notifier merges raw events for one path;
+/// the configured debounce is applied on top of this by [`Watcher::next_batch`]
+const EVENT_COALESCE_MS: u64 = 50;
+
+/// How often [`Watcher::next_batch`] checks the event channel
+const POLL_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Debounce and path filtering for a [`Watcher`]
+#[derive(Debug, Clone)]
+pub struct WatcherConfig {
+    /// Quiet time after the last event before a batch is released
+    pub debounce_ms: u64,
+    /// Only report paths matching one of these; empty means every path
+    pub include_globs: Vec<String>,
+    /// Never report paths matching one of these
+    pub exclude_globs: Vec<String>,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            debounce_ms: 500,
+            include_globs: Vec::new(),
+            exclude_globs: DEFAULT_EXCLUDE_GLOBS.iter().map(|g| g.to_string()).collect(),
+        }
+    }
+}
+
 /// Events emitted by the file watcher
 #[derive(Debug, Clone)]
 pub enum WatchEvent {
@@ -20,7 +52,7 @@ pub enum WatchEvent {
 pub struct Watcher {
     tx: Option<Sender<WatchEvent>>,
     rx: Option<Receiver<WatchEvent>>,
-    debounce_ms: u64,
+    config: WatcherConfig,
 }
 
 impl Default for Watcher {
@@ -35,32 +67,41 @@ impl Watcher {
         Self {
             tx: None,
             rx: None,
-            debounce_ms: 500,
+            config: WatcherConfig::default(),
         }
     }
 
     /// Set debounce duration in milliseconds
     pub fn with_debounce(mut self, ms: u64) -> Self {
-        self.debounce_ms = ms;
+        self.config.debounce_ms = ms;
+        self
+    }
+
+    /// Replace the debounce and glob settings
+    pub fn with_config(mut self, config: WatcherConfig) -> Self {
+        self.config = config;
         self
     }
 
     /// Start watching a directory
     pub fn watch(&mut self, path: &Path) -> Result<()> {
+        let filter = PathFilter::new(path, &self.config)?;
+
         let (tx, rx) = channel();
         self.tx = Some(tx.clone());
         self.rx = Some(rx);
 
-        let debounce_duration = Duration::from_millis(self.debounce_ms);
-
         let mut debouncer = new_debouncer(
-            debounce_duration,
+            Duration::from_millis(EVENT_COALESCE_MS),
             move |res: std::result::Result<
                 Vec<notify_debouncer_mini::DebouncedEvent>,
                 notify::Error,
             >| match res {
                 Ok(events) => {
                     for event in events {
+                        if !filter.matches(&event.path) {
+                            continue;
+                        }
                         let watch_event = match event.kind {
                             DebouncedEventKind::Any => WatchEvent::Changed(event.path.clone()),
                             DebouncedEventKind::AnyContinuous => continue,
@@ -98,6 +139,38 @@ impl Watcher {
         self.rx.as_ref().and_then(|rx| rx.try_recv().ok())
     }
 
+    /// Wait for the next batch of events
+    ///
+    /// Resolves once no new event has arrived for `debounce_ms`, so a burst of
+    /// writes (an editor saving several files) comes back as one batch.
+    pub async fn next_batch(&self) -> Vec<WatchEvent> {
+        let Some(rx) = self.rx.as_ref() else {
+            return std::future::pending().await;
+        };
+
+        let mut batch: Vec<WatchEvent> = Vec::new();
+        while batch.is_empty() {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            batch.extend(rx.try_iter());
+        }
+
+        let window = Duration::from_millis(self.config.debounce_ms);
+        let quiet = tokio::time::sleep(window);
+        tokio::pin!(quiet);
+        loop {
+            tokio::select! {
+                _ = &mut quiet => return batch,
+                _ = tokio::time::sleep(POLL_INTERVAL) => {
+                    let before = batch.len();
+                    batch.extend(rx.try_iter());
+                    if batch.len() > before {
+                        quiet.as_mut().reset(tokio::time::Instant::now() + window);
+                    }
+                }
+            }
+        }
+    }
+
     /// Check if there are pending events
     pub fn has_pending(&self) -> bool {
         self.rx
@@ -107,6 +180,52 @@ impl Watcher {
     }
 }
 
+/// Compiled include/exclude globs, matched against paths relative to the watched root
+struct PathFilter {
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl PathFilter {
+    fn new(root: &Path, config: &WatcherConfig) -> Result<Self> {
+        let include = if config.include_globs.is_empty() {
+            None
+        } else {
+            Some(compile_globs(&config.include_globs)?)
+        };
+        Ok(Self {
+            // Events carry canonical paths, so `strip_prefix` needs the same form
+            root: root.canonicalize().unwrap_or_else(|_| root.to_path_buf()),
+            include,
+            exclude: compile_globs(&config.exclude_globs)?,
+        })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if self.exclude.is_match(relative) {
+            return false;
+        }
+        self.include
+            .as_ref()
+            .map(|include| include.is_match(relative))
+            .unwrap_or(true)
+    }
+}
+
+fn compile_globs(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| HappyError::Watch(format!("Invalid glob '{}': {}", pattern, e)))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| HappyError::Watch(e.to_string()))
+}
+
 /// Filter for config file changes
 pub fn is_config_file(path: &Path) -> bool {
     path.file_name()
@@ -124,3 +243,45 @@ pub fn is_source_file(path: &Path) -> bool {
     let ext = path.extension().and_then(|e| e.to_str());
     matches!(ext, Some("yaml") | Some("yml") | Some("md") | Some("json"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(config: WatcherConfig) -> PathFilter {
+        PathFilter::new(Path::new("/project"), &config).unwrap()
+    }
+
+    #[test]
+    fn default_excludes_skip_vcs_and_build_dirs() {
+        let filter = filter(WatcherConfig::default());
+
+        assert!(filter.matches(Path::new("/project/skills/review.md")));
+        assert!(filter.matches(Path::new("/project/happy.config.yaml")));
+        assert!(!filter.matches(Path::new("/project/.git/index")));
+        assert!(!filter.matches(Path::new("/project/target/debug/build.log")));
+        assert!(!filter.matches(Path::new("/project/web/node_modules/pkg/index.js")));
+    }
+
+    #[test]
+    fn include_globs_narrow_and_exclude_wins() {
+        let filter = filter(WatcherConfig {
+            include_globs: vec!["skills/**".into()],
+            exclude_globs: vec!["**/*.bak".into()],
+            ..WatcherConfig::default()
+        });
+
+        assert!(filter.matches(Path::new("/project/skills/review.md")));
+        assert!(!filter.matches(Path::new("/project/skills/review.md.bak")));
+        assert!(!filter.matches(Path::new("/project/workflows/ship.yaml")));
+    }
+
+    #[test]
+    fn invalid_glob_is_rejected() {
+        let config = WatcherConfig {
+            exclude_globs: vec!["skills/[".into()],
+            ..WatcherConfig::default()
+        };
+        assert!(PathFilter::new(Path::new("/project"), &config).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+//! ANSI escape sequence handling for terminal output
+
+/// Remove escape sequences and control characters from terminal output, keeping
+/// the printable text, newlines and tabs.
+pub fn strip_ansi(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+
+    while i < input.len() {
+        match input[i] {
+            0x1b => {
+                i += 1;
+                match input.get(i) {
+                    // CSI: parameters until a final byte in 0x40..=0x7e
+                    Some(b'[') => {
+                        i += 1;
+                        while i < input.len() && !(0x40..=0x7e).contains(&input[i]) {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    // OSC / DCS: until BEL or ST (ESC \)
+                    Some(b']') | Some(b'P') => {
+                        i += 1;
+                        while i < input.len() {
+                            if input[i] == 0x07 {
+                                i += 1;
+                                break;
+                            }
+                            if input[i] == 0x1b && input.get(i + 1) == Some(&b'\\') {
+                                i += 2;
+                                break;
+                            }
+                            i += 1;
+                        }
+                    }
+                    // Two-byte sequences like ESC 7 / ESC =
+                    Some(_) => i += 1,
+                    None => {}
+                }
+            }
+            b'\n' | b'\t' => {
+                out.push(input[i]);
+                i += 1;
+            }
+            // Other C0 controls (including a bare \r) and DEL carry no text
+            b if b < 0x20 || b == 0x7f => i += 1,
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        let input = b"\x1b[1;32mok\x1b[0m done\r\n\x1b]0;title\x07next\x1b7\tline\x1b[";
+        assert_eq!(strip_ansi(input), b"ok done\nnext\tline");
+        assert_eq!(strip_ansi("héllo".as_bytes()), "héllo".as_bytes());
+    }
+}
@@ -0,0 +1,109 @@
+//! Passphrase-protected key pair storage
+//!
+//! The secret key is sealed with XSalsa20-Poly1305 under a key derived from the
+//! passphrase with Argon2id; the public key is stored in the clear.
+
+use super::{KeyPair, PublicKey};
+use crate::{HappyError, Result};
+use argon2::Argon2;
+use crypto_box::aead::{Aead, OsRng};
+use rand::RngCore;
+use xsalsa20poly1305::{KeyInit, XSalsa20Poly1305};
+use zeroize::Zeroizing;
+
+/// Format version, bumped if the layout or KDF parameters change
+const KEYSTORE_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// Secret key plus the Poly1305 tag
+const SEALED_LEN: usize = 32 + 16;
+const KEYSTORE_LEN: usize = 1 + 32 + SALT_LEN + NONCE_LEN + SEALED_LEN;
+
+/// Encrypt a key pair for storage: `version | public key | salt | nonce | sealed secret key`
+pub fn serialize_keypair(keypair: &KeyPair, passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt)?;
+    let sealed = XSalsa20Poly1305::new(key.as_ref().into())
+        .encrypt(
+            xsalsa20poly1305::Nonce::from_slice(&nonce),
+            keypair.secret_key.as_ref(),
+        )
+        .map_err(|_| HappyError::Encryption("Failed to seal secret key".to_string()))?;
+
+    let mut out = Vec::with_capacity(KEYSTORE_LEN);
+    out.push(KEYSTORE_VERSION);
+    out.extend_from_slice(&keypair.public_key);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+/// Decrypt a key pair written by [`serialize_keypair`]
+pub fn deserialize_keypair(bytes: &[u8], passphrase: &str) -> Result<KeyPair> {
+    if bytes.len() != KEYSTORE_LEN {
+        return Err(HappyError::Decryption(
+            "Invalid key file length".to_string(),
+        ));
+    }
+    if bytes[0] != KEYSTORE_VERSION {
+        return Err(HappyError::Decryption(format!(
+            "Unsupported key file version {}",
+            bytes[0]
+        )));
+    }
+
+    let (public_key, rest) = bytes[1..].split_at(32);
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+
+    let key = derive_key(passphrase, salt)?;
+    let secret = Zeroizing::new(
+        XSalsa20Poly1305::new(key.as_ref().into())
+            .decrypt(xsalsa20poly1305::Nonce::from_slice(nonce), sealed)
+            .map_err(|_| {
+                HappyError::Decryption("Wrong passphrase or corrupted key file".to_string())
+            })?,
+    );
+
+    let mut keypair = KeyPair {
+        public_key: PublicKey::try_from(public_key).map_err(|_| HappyError::InvalidPublicKey)?,
+        secret_key: [0u8; 32],
+    };
+    keypair.secret_key.copy_from_slice(&secret);
+    Ok(keypair)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|e| HappyError::Encryption(format!("Key derivation failed: {}", e)))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{EncryptionEngine, NaClEngine};
+
+    #[test]
+    fn test_keypair_roundtrip() {
+        let keypair = NaClEngine::new().generate_keypair();
+        let stored = serialize_keypair(&keypair, "correct horse").unwrap();
+        assert!(!stored
+            .windows(32)
+            .any(|w| w == keypair.secret_key.as_slice()));
+
+        let restored = deserialize_keypair(&stored, "correct horse").unwrap();
+        assert_eq!(restored.public_key, keypair.public_key);
+        assert_eq!(restored.secret_key, keypair.secret_key);
+
+        assert!(deserialize_keypair(&stored, "wrong").is_err());
+        assert!(deserialize_keypair(&stored[1..], "correct horse").is_err());
+    }
+}
@@ -1,12 +1,18 @@
 //! Encryption module using NaCl (libsodium)
 //!
-//! Provides X25519 key exchange and XSalsa20-Poly1305 authenticated encryption
+//! Provides X25519 key exchange and XSalsa20-Poly1305 authenticated encryption,
+//! plus Ed25519 signatures for messages that are authenticated but not encrypted
 
+mod keystore;
 mod nacl;
 
+pub use keystore::{deserialize_keypair, serialize_keypair};
 pub use nacl::NaClEngine;
 
-use happy_types::{DataKey, EncryptedMessage, KeyExchange, KeyPair, Nonce, PublicKey, SecretKey};
+use happy_types::{
+    DataKey, EdKeyPair, EdPublicKey, EdSecretKey, EncryptedMessage, KeyExchange, KeyPair, Nonce,
+    PublicKey, SecretKey, Signature,
+};
 use crate::{HappyError, Result};
 
 /// Encryption engine trait for E2E encryption
@@ -60,6 +66,15 @@ pub trait EncryptionEngine: Send + Sync {
 
     /// Decrypt with a symmetric data key (for session data)
     fn decrypt_symmetric(&self, ciphertext: &[u8], key: &DataKey, nonce: &Nonce) -> Result<Vec<u8>>;
+
+    /// Generate a new Ed25519 signing key pair
+    fn generate_signing_keypair(&self) -> EdKeyPair;
+
+    /// Produce a detached Ed25519 signature over `message`
+    fn sign(&self, message: &[u8], secret_key: &EdSecretKey) -> Signature;
+
+    /// Check a detached Ed25519 signature, failing with [`HappyError::InvalidSignature`]
+    fn verify(&self, message: &[u8], signature: &Signature, public_key: &EdPublicKey) -> Result<()>;
 }
 
 /// Initialize libsodium
@@ -1,13 +1,17 @@
 //! NaCl/libsodium encryption implementation using crypto_box (XSalsa20Poly1305)
+//!
+//! Signatures go through libsodium's `crypto_sign` (Ed25519).
 
 use super::{
-    DataKey, EncryptedMessage, EncryptionEngine, KeyExchange, KeyPair, Nonce, PublicKey, SecretKey,
+    DataKey, EdKeyPair, EdPublicKey, EdSecretKey, EncryptedMessage, EncryptionEngine,
+    KeyExchange, KeyPair, Nonce, PublicKey, SecretKey, Signature,
 };
 use crate::{HappyError, Result};
 use crypto_box::{
     aead::{Aead, OsRng},
     SalsaBox,
 };
+use sodiumoxide::crypto::sign;
 use xsalsa20poly1305::XSalsa20Poly1305;
 
 /// NaCl encryption engine
@@ -217,6 +221,33 @@ impl EncryptionEngine for NaClEngine {
             .decrypt(nonce_obj, ciphertext)
             .map_err(|_| HappyError::Decryption("Symmetric decryption failed".to_string()))
     }
+
+    fn generate_signing_keypair(&self) -> EdKeyPair {
+        let (public, secret) = sign::gen_keypair();
+        EdKeyPair {
+            public: public.0,
+            secret: secret.0,
+        }
+    }
+
+    fn sign(&self, message: &[u8], secret_key: &EdSecretKey) -> Signature {
+        sign::sign_detached(message, &sign::SecretKey(*secret_key)).to_bytes()
+    }
+
+    fn verify(
+        &self,
+        message: &[u8],
+        signature: &Signature,
+        public_key: &EdPublicKey,
+    ) -> Result<()> {
+        let signature =
+            sign::Signature::from_bytes(signature).map_err(|_| HappyError::InvalidSignature)?;
+        if sign::verify_detached(&signature, message, &sign::PublicKey(*public_key)) {
+            Ok(())
+        } else {
+            Err(HappyError::InvalidSignature)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -312,4 +343,22 @@ mod tests {
 
         assert_eq!(decrypted, plaintext);
     }
+
+    #[test]
+    fn test_sign_verify() {
+        let engine = NaClEngine::new();
+        let machine = engine.generate_signing_keypair();
+        let other = engine.generate_signing_keypair();
+
+        let message = b"attach session s1";
+        let signature = engine.sign(message, &machine.secret);
+        assert!(engine.verify(message, &signature, &machine.public).is_ok());
+
+        // Another key, or a changed message, must not verify
+        assert!(matches!(
+            engine.verify(message, &signature, &other.public),
+            Err(HappyError::InvalidSignature)
+        ));
+        assert!(engine.verify(b"attach session s2", &signature, &machine.public).is_err());
+    }
 }
@@ -24,6 +24,9 @@ pub enum HappyError {
     #[error("Invalid secret key")]
     InvalidSecretKey,
 
+    #[error("Invalid signature")]
+    InvalidSignature,
+
     #[error("Session not found: {0}")]
     SessionNotFound(String),
 
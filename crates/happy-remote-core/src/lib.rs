@@ -5,6 +5,7 @@
 // Re-export pure types from happy-types
 pub use happy_types::*;
 
+pub mod ansi;
 #[cfg(feature = "crypto")]
 pub mod crypto;
 pub mod error;
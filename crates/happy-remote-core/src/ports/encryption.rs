@@ -1,6 +1,9 @@
 //! Encryption port trait
 
-use happy_types::{DataKey, EncryptedMessage, KeyExchange, KeyPair, Nonce, PublicKey, SecretKey};
+use happy_types::{
+    DataKey, EdKeyPair, EdPublicKey, EdSecretKey, EncryptedMessage, KeyExchange, KeyPair, Nonce,
+    PublicKey, SecretKey, Signature,
+};
 use crate::Result;
 
 /// Port for encryption operations (wrapper around EncryptionEngine)
@@ -14,4 +17,7 @@ pub trait EncryptionPort: Send + Sync {
     fn decrypt_data_key(&self, key_exchange: &KeyExchange, sender_pk: &PublicKey, recipient_sk: &SecretKey) -> Result<DataKey>;
     fn encrypt_symmetric(&self, plaintext: &[u8], key: &DataKey, nonce: &Nonce) -> Result<Vec<u8>>;
     fn decrypt_symmetric(&self, ciphertext: &[u8], key: &DataKey, nonce: &Nonce) -> Result<Vec<u8>>;
+    fn generate_signing_keypair(&self) -> EdKeyPair;
+    fn sign(&self, message: &[u8], secret_key: &EdSecretKey) -> Signature;
+    fn verify(&self, message: &[u8], signature: &Signature, public_key: &EdPublicKey) -> Result<()>;
 }
@@ -0,0 +1,74 @@
+//! Sequential vs `FuturesUnordered` fan-out to WebSocket senders
+//!
+//! Every connection owns an unbounded sender, so `send` never waits on a slow
+//! client and fanning out concurrently only adds overhead. This is why
+//! `ConnectionManager::broadcast_to_all_users` sends in a plain loop.
+//!
+//! Run with `cargo bench -p happy-server --bench broadcast`.
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use happy_types::ServerMessage;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const RECEIVERS: usize = 1000;
+const ROUNDS: u32 = 200;
+
+fn message() -> ServerMessage {
+    ServerMessage::SessionStopped {
+        session_id: "7f9c2ba4-e88f-4a6f-9c4f-3c1b2a0e5d11".to_string(),
+    }
+}
+
+fn sequential(senders: &[mpsc::UnboundedSender<ServerMessage>], msg: &ServerMessage) {
+    for tx in senders {
+        let _ = tx.send(msg.clone());
+    }
+}
+
+async fn concurrent(senders: &[mpsc::UnboundedSender<ServerMessage>], msg: &ServerMessage) {
+    let sends: FuturesUnordered<_> = senders
+        .iter()
+        .map(|tx| {
+            let msg = msg.clone();
+            async move { tx.send(msg) }
+        })
+        .collect();
+    let _results: Vec<_> = sends.collect().await;
+}
+
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{:<18} {:>10.1?} per broadcast to {} receivers",
+        name,
+        elapsed / ROUNDS,
+        RECEIVERS
+    );
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let (senders, mut receivers): (Vec<_>, Vec<_>) =
+        (0..RECEIVERS).map(|_| mpsc::unbounded_channel()).unzip();
+    let msg = message();
+
+    let drain = |receivers: &mut Vec<mpsc::UnboundedReceiver<ServerMessage>>| {
+        for rx in receivers.iter_mut() {
+            while rx.try_recv().is_ok() {}
+        }
+    };
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        sequential(&senders, &msg);
+        drain(&mut receivers);
+    }
+    report("sequential", start.elapsed());
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        concurrent(&senders, &msg).await;
+        drain(&mut receivers);
+    }
+    report("FuturesUnordered", start.elapsed());
+}
@@ -0,0 +1,158 @@
+//! Concurrent SQLite reads and writes through the connection pool
+//!
+//! Mirrors the connect options `Database::new` applies for the default
+//! `DatabaseConfig`: 50 tasks each make 100 point reads and 10 updates, as
+//! WebSocket handlers do when many sessions are active. Checks that the median
+//! query stays under 5 ms and that no query fails with `SQLITE_BUSY`, then runs
+//! the same load with a rollback journal for comparison.
+//!
+//! Run with `cargo bench -p happy-server --bench db_concurrency`.
+
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous,
+};
+use sqlx::SqlitePool;
+use std::time::{Duration, Instant};
+
+const TASKS: usize = 50;
+const READS_PER_TASK: usize = 100;
+const WRITES_PER_TASK: usize = 10;
+const ROWS: i64 = 1000;
+
+/// Latencies and `SQLITE_BUSY` failures from one run
+#[derive(Default)]
+struct Run {
+    reads: Vec<Duration>,
+    writes: Vec<Duration>,
+    busy: usize,
+}
+
+async fn open(path: &std::path::Path, wal: bool) -> SqlitePool {
+    let (journal_mode, synchronous) = if wal {
+        (SqliteJournalMode::Wal, SqliteSynchronous::Normal)
+    } else {
+        (SqliteJournalMode::Delete, SqliteSynchronous::Full)
+    };
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .journal_mode(journal_mode)
+        .synchronous(synchronous)
+        .busy_timeout(Duration::from_secs(5))
+        .pragma("cache_size", "-4096");
+    let max_connections = std::thread::available_parallelism()
+        .map_or(4, |n| n.get())
+        .clamp(4, 32) as u32;
+    let pool = SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .connect_with(options)
+        .await
+        .unwrap();
+
+    sqlx::query("CREATE TABLE kv (id INTEGER PRIMARY KEY, value TEXT NOT NULL)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let mut tx = pool.begin().await.unwrap();
+    for id in 0..ROWS {
+        sqlx::query("INSERT INTO kv (id, value) VALUES (?1, ?2)")
+            .bind(id)
+            .bind("x".repeat(200))
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+    }
+    tx.commit().await.unwrap();
+    pool
+}
+
+fn is_busy(e: &sqlx::Error) -> bool {
+    // SQLITE_BUSY and its extended codes all end in 5
+    e.as_database_error()
+        .and_then(|e| e.code())
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| code & 0xff == 5)
+}
+
+async fn worker(pool: SqlitePool, task: usize) -> Run {
+    let mut run = Run::default();
+    for i in 0..READS_PER_TASK {
+        let id = ((task * READS_PER_TASK + i) as i64 * 7919) % ROWS;
+        let start = Instant::now();
+        match sqlx::query_as::<_, (String,)>("SELECT value FROM kv WHERE id = ?1")
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+        {
+            Ok(_) => run.reads.push(start.elapsed()),
+            Err(e) if is_busy(&e) => run.busy += 1,
+            Err(e) => panic!("read failed: {}", e),
+        }
+
+        if i % (READS_PER_TASK / WRITES_PER_TASK) == 0 {
+            let start = Instant::now();
+            match sqlx::query("UPDATE kv SET value = ?1 WHERE id = ?2")
+                .bind(format!("task {} write {}", task, i))
+                .bind(id)
+                .execute(&pool)
+                .await
+            {
+                Ok(_) => run.writes.push(start.elapsed()),
+                Err(e) if is_busy(&e) => run.busy += 1,
+                Err(e) => panic!("write failed: {}", e),
+            }
+        }
+    }
+    run
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    sorted[((sorted.len() - 1) as f64 * p) as usize]
+}
+
+/// Median query latency and the number of `SQLITE_BUSY` failures
+async fn bench(name: &str, wal: bool) -> (Duration, usize) {
+    let dir = std::env::temp_dir().join(format!("happy-db-bench-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let pool = open(&dir.join("bench.db"), wal).await;
+
+    let start = Instant::now();
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|task| tokio::spawn(worker(pool.clone(), task)))
+        .collect();
+    let mut total = Run::default();
+    for task in tasks {
+        let run = task.await.unwrap();
+        total.reads.extend(run.reads);
+        total.writes.extend(run.writes);
+        total.busy += run.busy;
+    }
+    let elapsed = start.elapsed();
+    pool.close().await;
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut all: Vec<Duration> = total.reads.iter().chain(&total.writes).copied().collect();
+    all.sort();
+    total.reads.sort();
+    total.writes.sort();
+    let median = percentile(&all, 0.5);
+    println!(
+        "{:<8} {:>8.1?} total  median {:>8.1?}  read p99 {:>8.1?}  write p99 {:>8.1?}  \
+         busy {}",
+        name,
+        elapsed,
+        median,
+        percentile(&total.reads, 0.99),
+        percentile(&total.writes, 0.99),
+        total.busy,
+    );
+    (median, total.busy)
+}
+
+#[tokio::main]
+async fn main() {
+    let (median, busy) = bench("wal", true).await;
+    assert_eq!(busy, 0, "queries failed with SQLITE_BUSY");
+    assert!(median < Duration::from_millis(5), "median {:?} over 5ms", median);
+    bench("rollback", false).await;
+}
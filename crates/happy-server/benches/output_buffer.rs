@@ -0,0 +1,65 @@
+//! Trimming session history: `Vec::drain` vs a `VecDeque` ring
+//!
+//! Mirrors `OutputBuffer::push` in `handlers/ws.rs` at the default 512 KiB
+//! limit. Each iteration feeds 1 MiB of terminal output in 4 KiB chunks to a
+//! buffer that is already full, which is where a long-lived session spends
+//! its time. Criterion reports both as throughput; a session writing
+//! 100 MB/s needs well over 100 MiB/s here.
+//!
+//! Run with `cargo bench -p happy-server --bench output_buffer`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use std::collections::VecDeque;
+
+const MAX_BYTES: usize = 512 * 1024;
+const CHUNK: usize = 4096;
+const PER_ITER: usize = 1024 * 1024;
+
+fn push_vec(data: &mut Vec<u8>, bytes: &[u8]) {
+    data.extend_from_slice(bytes);
+    if data.len() > MAX_BYTES {
+        let excess = data.len() - MAX_BYTES;
+        data.drain(0..excess);
+    }
+}
+
+fn push_ring(data: &mut VecDeque<u8>, bytes: &[u8]) {
+    let kept = &bytes[bytes.len().saturating_sub(MAX_BYTES)..];
+    let excess = (data.len() + kept.len()).saturating_sub(MAX_BYTES);
+    data.drain(..excess);
+    data.extend(kept);
+}
+
+fn output_buffer(c: &mut Criterion) {
+    let chunk: Vec<u8> = (0..CHUNK).map(|i| b' ' + (i % 95) as u8).collect();
+
+    let mut vec = Vec::new();
+    let mut ring = VecDeque::new();
+    for _ in 0..2 * MAX_BYTES / CHUNK {
+        push_vec(&mut vec, &chunk);
+        push_ring(&mut ring, &chunk);
+    }
+    // Both keep the same bytes, so only the trimming differs
+    assert!(vec.iter().eq(ring.iter()));
+
+    let mut group = c.benchmark_group("output_buffer");
+    group.throughput(Throughput::Bytes(PER_ITER as u64));
+    group.bench_function("Vec", |b| {
+        b.iter(|| {
+            for _ in 0..PER_ITER / CHUNK {
+                push_vec(&mut vec, black_box(&chunk));
+            }
+        })
+    });
+    group.bench_function("VecDeque", |b| {
+        b.iter(|| {
+            for _ in 0..PER_ITER / CHUNK {
+                push_ring(&mut ring, black_box(&chunk));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, output_buffer);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+-- Initial schema
+--
+-- Databases created before migrations were introduced already have these
+-- tables; `Database::new` brings their columns up to date first, so every
+-- statement here must stay idempotent. Later changes get their own
+-- numbered migration instead of editing this file.
+
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    email TEXT UNIQUE NOT NULL,
+    name TEXT,
+    password_hash TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    deleted_at DATETIME
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    tag TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    machine_id TEXT NOT NULL,
+    machine_name TEXT DEFAULT 'Unknown',
+    status TEXT DEFAULT 'initializing',
+    encrypted_data_key BLOB,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    last_activity DATETIME DEFAULT CURRENT_TIMESTAMP,
+    cwd TEXT DEFAULT '/',
+    env TEXT DEFAULT '{}',
+    claude_version TEXT,
+    agent_version TEXT,
+    shell TEXT DEFAULT '/bin/bash',
+    idempotency_key TEXT
+);
+
+-- Unique per key; SQLite treats NULLs as distinct so keyless sessions are unaffected
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_idempotency_key
+ON sessions(idempotency_key);
+
+-- Per-machine session listing
+CREATE INDEX IF NOT EXISTS idx_sessions_user_machine
+ON sessions(user_id, machine_id);
+
+CREATE TABLE IF NOT EXISTS machines (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    public_key BLOB NOT NULL,
+    platform TEXT DEFAULT 'linux',
+    capabilities TEXT DEFAULT 'terminal,file_system',
+    last_seen DATETIME DEFAULT CURRENT_TIMESTAMP,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    hostname TEXT,
+    last_heartbeat DATETIME,
+    daemon_version TEXT,
+    arch TEXT,
+    cpu_count INTEGER,
+    memory_mb INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS access_keys (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    permissions TEXT DEFAULT '',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME,
+    last_used_at DATETIME
+);
+
+CREATE TABLE IF NOT EXISTS push_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    token TEXT NOT NULL UNIQUE,
+    platform TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_tokens_user ON push_tokens(user_id);
@@ -0,0 +1,3 @@
+-- Keyset pagination of a user's sessions, newest first
+CREATE INDEX IF NOT EXISTS idx_sessions_user_created
+ON sessions(user_id, created_at DESC, id DESC);
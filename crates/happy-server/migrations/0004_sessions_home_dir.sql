@@ -0,0 +1,2 @@
+-- Home directory of the user running each session, so clients can show `~`
+ALTER TABLE sessions ADD COLUMN home_dir TEXT;
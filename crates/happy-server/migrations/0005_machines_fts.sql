@@ -0,0 +1,27 @@
+-- Full-text search over machine names and hostnames, kept in sync by triggers
+CREATE VIRTUAL TABLE IF NOT EXISTS machines_fts USING fts5(
+    name,
+    hostname,
+    content = 'machines',
+    content_rowid = 'rowid'
+);
+
+INSERT INTO machines_fts(machines_fts) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS machines_fts_insert AFTER INSERT ON machines BEGIN
+    INSERT INTO machines_fts(rowid, name, hostname)
+    VALUES (new.rowid, new.name, new.hostname);
+END;
+
+CREATE TRIGGER IF NOT EXISTS machines_fts_delete AFTER DELETE ON machines BEGIN
+    INSERT INTO machines_fts(machines_fts, rowid, name, hostname)
+    VALUES ('delete', old.rowid, old.name, old.hostname);
+END;
+
+-- Heartbeats touch other columns constantly; only re-index on searchable ones
+CREATE TRIGGER IF NOT EXISTS machines_fts_update AFTER UPDATE OF name, hostname ON machines BEGIN
+    INSERT INTO machines_fts(machines_fts, rowid, name, hostname)
+    VALUES ('delete', old.rowid, old.name, old.hostname);
+    INSERT INTO machines_fts(rowid, name, hostname)
+    VALUES (new.rowid, new.name, new.hostname);
+END;
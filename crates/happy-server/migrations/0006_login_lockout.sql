@@ -0,0 +1,2 @@
+-- Accounts locked after repeated failed logins, so lockouts survive restarts
+ALTER TABLE users ADD COLUMN locked_until DATETIME;
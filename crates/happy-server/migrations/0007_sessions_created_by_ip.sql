@@ -0,0 +1,4 @@
+-- Client address that created each session, kept for incident response.
+-- IPv4 or IPv6 text; may be a private address when the client is behind NAT.
+ALTER TABLE sessions ADD COLUMN created_by_ip TEXT;
+CREATE INDEX IF NOT EXISTS idx_sessions_created_by_ip ON sessions(created_by_ip);
@@ -0,0 +1,3 @@
+-- Ed25519 key a daemon signs AttachSession with, pinned on first use.
+-- Deleting the machine row clears it, e.g. after reinstalling the CLI.
+ALTER TABLE machines ADD COLUMN signing_key BLOB;
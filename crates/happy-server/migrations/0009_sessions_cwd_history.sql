@@ -0,0 +1,2 @@
+-- Directories each session has been in, oldest first, as a JSON array of paths
+ALTER TABLE sessions ADD COLUMN cwd_history TEXT NOT NULL DEFAULT '[]';
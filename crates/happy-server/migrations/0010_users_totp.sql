@@ -0,0 +1,7 @@
+-- TOTP two-factor authentication
+--
+-- `totp_secret` is encrypted with a key derived from the JWT secret. It is
+-- set by setup and only required at login once `totp_enabled` is set by a
+-- verified code.
+ALTER TABLE users ADD COLUMN totp_secret TEXT;
+ALTER TABLE users ADD COLUMN totp_enabled INTEGER NOT NULL DEFAULT 0;
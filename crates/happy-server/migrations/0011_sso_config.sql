@@ -0,0 +1,13 @@
+-- SAML single sign-on configuration
+--
+-- At most one row. `certificate` is the IdP's signing certificate in PEM;
+-- users whose email is in `domain` sign in through the IdP instead of with
+-- a password.
+CREATE TABLE IF NOT EXISTS sso_config (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    idp_metadata_url TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    certificate TEXT NOT NULL,
+    domain TEXT NOT NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,3 @@
+-- When the user archived the session; NULL for sessions still listed.
+-- Archived sessions are kept until purged so they can be restored.
+ALTER TABLE sessions ADD COLUMN archived_at TEXT;
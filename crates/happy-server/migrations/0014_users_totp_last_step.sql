@@ -0,0 +1,4 @@
+-- The time step of the last TOTP code accepted for the user, so a code
+-- can't be used again within the window it stays valid for. NULL until
+-- the first code is accepted.
+ALTER TABLE users ADD COLUMN totp_last_step INTEGER;
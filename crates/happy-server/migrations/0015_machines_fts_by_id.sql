@@ -0,0 +1,31 @@
+-- Key the machine search index on machines.id instead of the implicit rowid,
+-- which VACUUM may renumber on a table with a TEXT primary key
+DROP TRIGGER IF EXISTS machines_fts_insert;
+DROP TRIGGER IF EXISTS machines_fts_delete;
+DROP TRIGGER IF EXISTS machines_fts_update;
+DROP TABLE IF EXISTS machines_fts;
+
+CREATE VIRTUAL TABLE machines_fts USING fts5(
+    id UNINDEXED,
+    name,
+    hostname
+);
+
+INSERT INTO machines_fts(id, name, hostname)
+SELECT id, name, hostname FROM machines;
+
+CREATE TRIGGER machines_fts_insert AFTER INSERT ON machines BEGIN
+    INSERT INTO machines_fts(id, name, hostname)
+    VALUES (new.id, new.name, new.hostname);
+END;
+
+CREATE TRIGGER machines_fts_delete AFTER DELETE ON machines BEGIN
+    DELETE FROM machines_fts WHERE id = old.id;
+END;
+
+-- Heartbeats touch other columns constantly; only re-index on searchable ones
+CREATE TRIGGER machines_fts_update AFTER UPDATE OF id, name, hostname ON machines BEGIN
+    DELETE FROM machines_fts WHERE id = old.id;
+    INSERT INTO machines_fts(id, name, hostname)
+    VALUES (new.id, new.name, new.hostname);
+END;
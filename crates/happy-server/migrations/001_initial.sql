@@ -1,133 +0,0 @@
--- Initial database schema for Happy Remote
-
--- Users table
-CREATE TABLE users (
-    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-    email VARCHAR(255) UNIQUE NOT NULL,
-    name VARCHAR(255),
-    avatar_url TEXT,
-    password_hash VARCHAR(255),
-    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-);
-
--- Machines table (registered devices)
-CREATE TABLE machines (
-    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-    name VARCHAR(255) NOT NULL,
-    public_key BYTEA NOT NULL, -- X25519 public key (32 bytes)
-    platform VARCHAR(20) NOT NULL CHECK (platform IN ('macos', 'linux', 'windows')),
-    capabilities TEXT[] DEFAULT '{}',
-    ip_address INET,
-    hostname VARCHAR(255),
-    last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-);
-
--- Sessions table
-CREATE TABLE sessions (
-    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-    tag VARCHAR(255) NOT NULL,
-    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-    machine_id UUID NOT NULL REFERENCES machines(id) ON DELETE CASCADE,
-    status VARCHAR(20) NOT NULL CHECK (status IN ('initializing', 'running', 'paused', 'terminated')),
-    encrypted_data_key BYTEA, -- Per-session encryption key (encrypted with server's public key)
-    cwd TEXT NOT NULL DEFAULT '/',
-    env JSONB DEFAULT '{}',
-    claude_version VARCHAR(50),
-    shell VARCHAR(255) DEFAULT '/bin/bash',
-    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    last_activity TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    terminated_at TIMESTAMPTZ,
-    UNIQUE(user_id, tag)
-);
-
--- Access keys table (for API authentication)
-CREATE TABLE access_keys (
-    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-    name VARCHAR(255) NOT NULL,
-    key_hash VARCHAR(255) NOT NULL, -- Hashed key (argon2)
-    key_prefix VARCHAR(8) NOT NULL, -- First 8 chars for display
-    permissions TEXT[] DEFAULT '{}',
-    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    expires_at TIMESTAMPTZ,
-    last_used_at TIMESTAMPTZ,
-    is_revoked BOOLEAN NOT NULL DEFAULT FALSE
-);
-
--- Artifacts table (file artifacts)
-CREATE TABLE artifacts (
-    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-    session_id UUID NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
-    path TEXT NOT NULL,
-    name VARCHAR(255) NOT NULL,
-    content_hash VARCHAR(64),
-    content_type VARCHAR(255),
-    size BIGINT,
-    storage_key TEXT, -- S3/minio storage key
-    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    UNIQUE(session_id, path)
-);
-
--- Push tokens table (for mobile push notifications)
-CREATE TABLE push_tokens (
-    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-    token TEXT NOT NULL,
-    platform VARCHAR(20) NOT NULL CHECK (platform IN ('ios', 'android', 'web')),
-    device_info JSONB,
-    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    last_used_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-);
-
--- KV store for session data
-CREATE TABLE kv_store (
-    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-    session_id UUID NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
-    key VARCHAR(255) NOT NULL,
-    value JSONB NOT NULL,
-    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    UNIQUE(session_id, key)
-);
-
--- Indexes
-CREATE INDEX idx_users_email ON users(email);
-CREATE INDEX idx_machines_user_id ON machines(user_id);
-CREATE INDEX idx_machines_last_seen ON machines(last_seen);
-CREATE INDEX idx_sessions_user_id ON sessions(user_id);
-CREATE INDEX idx_sessions_machine_id ON sessions(machine_id);
-CREATE INDEX idx_sessions_status ON sessions(status);
-CREATE INDEX idx_sessions_tag ON sessions(tag);
-CREATE INDEX idx_access_keys_user_id ON access_keys(user_id);
-CREATE INDEX idx_access_keys_key_hash ON access_keys(key_hash);
-CREATE INDEX idx_artifacts_session_id ON artifacts(session_id);
-CREATE INDEX idx_kv_store_session_id ON kv_store(session_id);
-
--- Update timestamps trigger
-CREATE OR REPLACE FUNCTION update_updated_at_column()
-RETURNS TRIGGER AS $$
-BEGIN
-    NEW.updated_at = NOW();
-    RETURN NEW;
-END;
-$$ language 'plpgsql';
-
-CREATE TRIGGER update_users_updated_at BEFORE UPDATE ON users
-    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
-
-CREATE TRIGGER update_machines_updated_at BEFORE UPDATE ON machines
-    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
-
-CREATE TRIGGER update_sessions_updated_at BEFORE UPDATE ON sessions
-    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
-
-CREATE TRIGGER update_artifacts_updated_at BEFORE UPDATE ON artifacts
-    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
-
-CREATE TRIGGER update_kv_store_updated_at BEFORE UPDATE ON kv_store
-    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
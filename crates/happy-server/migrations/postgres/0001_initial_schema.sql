@@ -0,0 +1,142 @@
+-- Initial schema for the Postgres backend
+--
+-- Equivalent to the SQLite migrations up to 0013, which stay the reference
+-- for what each column means. Timestamps are TIMESTAMPTZ in UTC and blobs
+-- BYTEA. Later changes get their own numbered migration here as well as in
+-- the SQLite directory.
+
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    email TEXT UNIQUE NOT NULL,
+    name TEXT,
+    password_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    deleted_at TIMESTAMPTZ,
+    token_version BIGINT NOT NULL DEFAULT 0,
+    locked_until TIMESTAMPTZ,
+    totp_secret TEXT,
+    totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    is_admin BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    tag TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    machine_id TEXT NOT NULL,
+    machine_name TEXT DEFAULT 'Unknown',
+    status TEXT DEFAULT 'initializing',
+    encrypted_data_key BYTEA,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    last_activity TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    cwd TEXT DEFAULT '/',
+    cwd_history TEXT NOT NULL DEFAULT '[]',
+    env TEXT DEFAULT '{}',
+    claude_version TEXT,
+    agent_version TEXT,
+    home_dir TEXT,
+    shell TEXT DEFAULT '/bin/bash',
+    idempotency_key TEXT,
+    created_by_ip TEXT,
+    archived_at TIMESTAMPTZ
+);
+
+-- NULLs are distinct, so keyless sessions are unaffected
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_idempotency_key
+ON sessions(idempotency_key);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_user_machine
+ON sessions(user_id, machine_id);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_user_created
+ON sessions(user_id, created_at DESC, id DESC);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_created_by_ip ON sessions(created_by_ip);
+
+CREATE TABLE IF NOT EXISTS machines (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    public_key BYTEA NOT NULL,
+    platform TEXT DEFAULT 'linux',
+    capabilities TEXT DEFAULT 'terminal,file_system',
+    last_seen TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    hostname TEXT,
+    last_heartbeat TIMESTAMPTZ,
+    daemon_version TEXT,
+    arch TEXT,
+    cpu_count BIGINT,
+    memory_mb BIGINT,
+    signing_key BYTEA
+);
+
+-- Machine search; `search_machines_by_user` must use the same expression
+CREATE INDEX IF NOT EXISTS idx_machines_search ON machines
+USING GIN (to_tsvector('simple', name || ' ' || COALESCE(hostname, '')));
+
+CREATE TABLE IF NOT EXISTS access_keys (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    permissions TEXT DEFAULT '',
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS push_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    token TEXT NOT NULL UNIQUE,
+    platform TEXT NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_tokens_user ON push_tokens(user_id);
+
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    token_hash TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_password_reset_tokens_user
+ON password_reset_tokens(user_id);
+
+CREATE TABLE IF NOT EXISTS sso_config (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    idp_metadata_url TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    certificate TEXT NOT NULL,
+    domain TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- `timestamp` stays RFC 3339 text, as written by the server
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    user_id TEXT,
+    event_type TEXT NOT NULL,
+    resource_type TEXT,
+    resource_id TEXT,
+    ip_address TEXT,
+    metadata TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_audit_log_user ON audit_log(user_id);
+CREATE INDEX IF NOT EXISTS idx_audit_log_event_type ON audit_log(event_type);
+
+CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_no_update_or_delete BEFORE UPDATE OR DELETE ON audit_log
+FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
+
+CREATE TRIGGER audit_log_no_truncate BEFORE TRUNCATE ON audit_log
+FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
@@ -0,0 +1,2 @@
+-- See the SQLite migration of the same number
+ALTER TABLE users ADD COLUMN totp_last_step BIGINT;
@@ -0,0 +1,158 @@
+//! Client address behind trusted reverse proxies
+//!
+//! The socket peer is the client unless it is one of `TRUSTED_PROXIES`. For a
+//! trusted peer, `X-Forwarded-For` is read right to left and the first address
+//! outside the trusted list is the client, so entries a client prepends itself
+//! are never believed.
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap, StatusCode},
+};
+use ipnet::IpNet;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use crate::AppState;
+
+/// Networks trusted to set `X-Forwarded-For` unless `TRUSTED_PROXIES` says otherwise
+pub const DEFAULT_TRUSTED_PROXIES: &str = "127.0.0.1/8,::1/128";
+
+/// Comma-separated list of proxy networks; bare addresses count as single hosts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|net| net.contains(ip))
+    }
+
+    /// The originating client of a request received from `peer`.
+    ///
+    /// Falls back to `peer` when it is not trusted, or when the header is
+    /// missing or holds anything that is not an IP address.
+    pub fn resolve(&self, headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+        if !self.contains(&peer) {
+            return peer;
+        }
+        let Some(hops) = forwarded_for(headers) else {
+            return peer;
+        };
+        // Every hop being a proxy leaves the leftmost as the best guess
+        hops.iter()
+            .rev()
+            .find(|ip| !self.contains(ip))
+            .or(hops.first())
+            .copied()
+            .unwrap_or(peer)
+    }
+}
+
+impl Default for TrustedProxies {
+    fn default() -> Self {
+        DEFAULT_TRUSTED_PROXIES.parse().expect("valid default proxy list")
+    }
+}
+
+impl FromStr for TrustedProxies {
+    type Err = String;
+
+    fn from_str(list: &str) -> Result<Self, Self::Err> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("invalid network {:?}", entry))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl fmt::Display for TrustedProxies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nets: Vec<String> = self.0.iter().map(IpNet::to_string).collect();
+        f.write_str(&nets.join(","))
+    }
+}
+
+/// Every address in `X-Forwarded-For`, or `None` if absent or malformed
+fn forwarded_for(headers: &HeaderMap) -> Option<Vec<IpAddr>> {
+    let mut hops = Vec::new();
+    for value in headers.get_all("x-forwarded-for") {
+        for hop in value.to_str().ok()?.split(',') {
+            hops.push(hop.trim().parse().ok()?);
+        }
+    }
+    (!hops.is_empty()).then_some(hops)
+}
+
+/// The originating client IP. It may be a private address when the client sits
+/// behind NAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(peer) = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .copied()
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(Self(state.trusted_proxies.resolve(&parts.headers, peer.ip())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_resolve_skips_trusted_hops() {
+        let trusted: TrustedProxies = "10.0.0.0/8, ::1".parse().unwrap();
+        let proxy = ip("10.0.0.2");
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(trusted.resolve(&headers, proxy), proxy);
+
+        // A spoofed leftmost entry is ignored in favour of what the proxy saw
+        headers.insert("x-forwarded-for", "1.2.3.4, 203.0.113.7, 10.0.0.1".parse().unwrap());
+        assert_eq!(trusted.resolve(&headers, proxy), ip("203.0.113.7"));
+
+        headers.insert("x-forwarded-for", "2001:db8::1".parse().unwrap());
+        assert_eq!(trusted.resolve(&headers, proxy), ip("2001:db8::1"));
+
+        // Untrusted peers cannot choose their own address
+        assert_eq!(trusted.resolve(&headers, ip("198.51.100.9")), ip("198.51.100.9"));
+
+        // Garbage is ignored rather than stored
+        headers.insert("x-forwarded-for", "203.0.113.7, unknown".parse().unwrap());
+        assert_eq!(trusted.resolve(&headers, proxy), proxy);
+    }
+
+    #[test]
+    fn test_parse_trusted_proxies() {
+        let defaults = TrustedProxies::default();
+        assert!(defaults.contains(&ip("127.0.0.5")));
+        assert!(defaults.contains(&ip("::1")));
+        assert!(!defaults.contains(&ip("10.0.0.1")));
+
+        assert_eq!("".parse::<TrustedProxies>().unwrap().to_string(), "");
+        assert!("10.0.0.0/8,bogus".parse::<TrustedProxies>().is_err());
+    }
+}
@@ -1,5 +1,7 @@
 //! Request extractors
 
 pub mod auth;
+pub mod client_ip;
 
 pub use auth::AuthUser;
+pub use client_ip::{ClientIp, TrustedProxies};
@@ -0,0 +1,307 @@
+//! Operator endpoints, enabled by setting `ADMIN_TOKEN`
+
+use crate::handlers::ws::{broadcast_machine_list, ConnectionsSnapshot};
+use crate::storage::db::{DatabaseStats, MigrationRecord};
+#[cfg(feature = "sso")]
+use crate::storage::SsoConfig;
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use happy_core::Session;
+use serde::{Deserialize, Serialize};
+
+/// Everyone connected right now: CLI bridges, web clients and machines
+pub async fn connections(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ConnectionsSnapshot>, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    let mut snapshot = state.conn_manager.snapshot().await;
+    for machine in &mut snapshot.machines {
+        machine.name = state
+            .machine_registry
+            .get_machine(&machine.machine_id)
+            .await
+            .ok()
+            .flatten()
+            .map(|m| m.name);
+    }
+
+    Ok(Json(snapshot))
+}
+
+#[derive(Debug, Serialize)]
+pub struct DbStatusResponse {
+    migrations: Vec<MigrationRecord>,
+}
+
+/// Database migrations that have run, oldest first
+pub async fn db_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<DbStatusResponse>, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    match state.db.migration_status().await {
+        Ok(migrations) => Ok(Json(DbStatusResponse { migrations })),
+        Err(e) => {
+            tracing::error!("Failed to read migration status: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Rebuild the database file; responds with its size afterwards
+pub async fn vacuum_db(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<DatabaseStats>, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    if let Err(e) = state.db.vacuum().await {
+        tracing::error!("Failed to vacuum database: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    match state.db.stats().await {
+        Ok(stats) => {
+            tracing::info!("Vacuumed database, now {:.1} MB", stats.size_mb);
+            Ok(Json(stats))
+        }
+        Err(e) => {
+            tracing::error!("Failed to read database stats: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Refresh query planner statistics now instead of waiting for the schedule
+pub async fn analyze_db(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    match state.db.analyze().await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            tracing::error!("Failed to analyze database: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeMachinesRequest {
+    old_id: String,
+    new_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MergeMachinesResponse {
+    sessions_moved: u64,
+}
+
+/// Reparent an orphaned machine's sessions onto its replacement and delete it
+pub async fn merge_machines(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<MergeMachinesRequest>,
+) -> Result<Json<MergeMachinesResponse>, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    if req.old_id == req.new_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut owners = Vec::with_capacity(2);
+    for id in [&req.old_id, &req.new_id] {
+        match state.machine_registry.get_machine(id).await {
+            Ok(Some(machine)) => owners.push(machine.user_id),
+            Ok(None) => return Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                tracing::error!("Failed to get machine {}: {}", id, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+    // Sessions must not change hands between accounts
+    if owners[0] != owners[1] {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let sessions_moved = match state
+        .machine_registry
+        .merge_duplicate_machines(&req.old_id, &req.new_id)
+        .await
+    {
+        Ok(moved) => moved,
+        Err(e) => {
+            tracing::error!(
+                "Failed to merge machine {} into {}: {}",
+                req.old_id,
+                req.new_id,
+                e
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    broadcast_machine_list(&state, &owners[1]).await;
+    Ok(Json(MergeMachinesResponse { sessions_moved }))
+}
+
+/// Lift a failed-login lockout before it expires
+pub async fn unlock_user(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(email): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    match state.auth_service.unlock(&email).await {
+        Ok(true) => {
+            tracing::info!("Unlocked account {}", email);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to unlock {}: {}", email, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminSessionResponse {
+    session: Session,
+    /// IPv4 or IPv6 text; may be a private address when the client is behind NAT
+    created_by_ip: Option<String>,
+}
+
+/// A session with the audit fields ordinary clients don't see
+pub async fn get_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<AdminSessionResponse>, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    let session = match state.session_manager.get_session(&id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get session {}: {}", id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    match state.session_manager.get_session_created_by_ip(&id).await {
+        Ok(created_by_ip) => Ok(Json(AdminSessionResponse {
+            session,
+            created_by_ip,
+        })),
+        Err(e) => {
+            tracing::error!("Failed to get creator address of session {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionsByIpQuery {
+    ip: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminSessionListResponse {
+    sessions: Vec<Session>,
+}
+
+/// Every session created from one address, newest first
+pub async fn sessions_by_ip(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<SessionsByIpQuery>,
+) -> Result<Json<AdminSessionListResponse>, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    match state.session_manager.get_sessions_by_ip(&query.ip).await {
+        Ok(sessions) => Ok(Json(AdminSessionListResponse { sessions })),
+        Err(e) => {
+            tracing::error!("Failed to list sessions from {}: {}", query.ip, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Admin endpoints don't exist unless `ADMIN_TOKEN` is set
+/// The stored SAML configuration
+#[cfg(feature = "sso")]
+pub async fn get_sso_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<SsoConfig>, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    match state.db.get_sso_config().await {
+        Ok(Some(config)) => Ok(Json(config)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to load SSO configuration: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Store the SAML configuration. The IdP metadata is fetched at startup,
+/// so it takes effect when the server restarts.
+#[cfg(feature = "sso")]
+pub async fn set_sso_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(config): Json<SsoConfig>,
+) -> Result<StatusCode, StatusCode> {
+    check_admin_token(&state, &headers)?;
+
+    if config.domain.is_empty() || config.domain.contains('@') {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if let Err(e) = crate::services::saml::certificate_der(&config.certificate) {
+        tracing::warn!("Rejected SSO configuration: {:#}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    match state.db.set_sso_config(&config).await {
+        Ok(()) => {
+            tracing::info!("Stored SSO configuration for {}; restart to apply", config.domain);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(e) => {
+            tracing::error!("Failed to store SSO configuration: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn check_admin_token(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let expected = state.admin_token.as_deref().ok_or(StatusCode::NOT_FOUND)?;
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if constant_time_eq(token.as_bytes(), expected.as_bytes()) {
+        Ok(())
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Compare without leaking how much of the token matched
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,80 @@
+//! Audit log handlers
+
+use crate::storage::AuditRecord;
+use crate::AppState;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+/// Entries per page unless `limit` says otherwise
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Largest `limit` accepted
+const MAX_PAGE_SIZE: u32 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    /// Only entries older than this ID, from a previous page's `next_before`
+    before: Option<i64>,
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditListResponse {
+    entries: Vec<AuditRecord>,
+    /// Pass as `before` for the next page; absent on the last one
+    next_before: Option<i64>,
+}
+
+/// Audit log entries, newest first. Only for users with `is_admin` set.
+pub async fn list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditListResponse>, StatusCode> {
+    let token = headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let user_id = state
+        .auth_service
+        .validate_token(token)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    match state.db.is_user_admin(&user_id).await {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::FORBIDDEN),
+        Err(e) => {
+            tracing::error!("Failed to check admin status of {}: {}", user_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let entries = state
+        .db
+        .list_audit_entries(query.before, limit)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to list audit entries: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let next_before = if entries.len() == limit as usize {
+        entries.last().map(|entry| entry.id)
+    } else {
+        None
+    };
+    Ok(Json(AuditListResponse {
+        entries,
+        next_before,
+    }))
+}
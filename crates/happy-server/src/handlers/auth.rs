@@ -1,9 +1,17 @@
 //! Authentication handlers
 
+use crate::extractors::ClientIp;
+use crate::services::auth::{LoginError, LoginOutcome};
+use crate::services::oidc::ProviderInfo;
 use crate::AppState;
-use axum::{Json, extract::State, http::StatusCode};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
@@ -26,6 +34,9 @@ pub struct UserInfo {
     name: Option<String>,
 }
 
+/// Shortest password accepted at registration and reset
+const MIN_PASSWORD_LEN: usize = 6;
+
 #[derive(Debug, Deserialize)]
 pub struct RegisterRequest {
     email: String,
@@ -33,30 +44,88 @@ pub struct RegisterRequest {
     password: String,
 }
 
+/// Body of the 423 returned while an email is locked out
+#[derive(Debug, Serialize)]
+pub struct AccountLockedResponse {
+    error: &'static str,
+    unlock_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Body of the 403 returned for a password login to an SSO account
+#[derive(Debug, Serialize)]
+pub struct SsoRequiredResponse {
+    error: &'static str,
+    /// Where to start the SSO login instead
+    login_url: &'static str,
+}
+
+/// Returned by `login` instead of tokens when the account has two-factor login enabled
+#[derive(Debug, Serialize)]
+pub struct TotpRequiredResponse {
+    require_totp: bool,
+    partial_token: String,
+}
+
+/// `account` is the email, or what was being attempted when it isn't known yet
+fn login_error_response(e: LoginError, account: &str, ip: std::net::IpAddr) -> Response {
+    match e {
+        LoginError::Locked(unlock_at) => {
+            warn!("Login refused for locked account: {} from {}", account, ip);
+            let body = AccountLockedResponse {
+                error: "account_locked",
+                unlock_at,
+            };
+            (StatusCode::LOCKED, Json(body)).into_response()
+        }
+        LoginError::SsoRequired => {
+            info!("Password login refused for SSO account: {} from {}", account, ip);
+            let body = SsoRequiredResponse {
+                error: "sso_required",
+                login_url: "/api/v1/auth/sso/saml/login",
+            };
+            (StatusCode::FORBIDDEN, Json(body)).into_response()
+        }
+        e => {
+            error!("Login error for {} from {}: {}", account, ip, e);
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+    }
+}
+
 pub async fn login(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     Json(req): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, StatusCode> {
-    info!("Login attempt for: {}", req.email);
+) -> Result<Response, Response> {
+    info!("Login attempt for: {} from {}", req.email, ip);
 
     // Use AuthService to login
-    let tokens = state
+    let ip_text = ip.to_string();
+    let tokens = match state
         .auth_service
-        .login(&req.email, &req.password)
+        .login(&req.email, &req.password, Some(&ip_text))
         .await
-        .map_err(|e| {
-            error!("Login error: {}", e);
-            StatusCode::UNAUTHORIZED
-        })?;
+    {
+        Ok(LoginOutcome::Tokens(tokens)) => tokens,
+        Ok(LoginOutcome::TotpRequired { partial_token }) => {
+            info!("Password accepted for {} from {}, awaiting TOTP code", req.email, ip);
+            return Ok(Json(TotpRequiredResponse {
+                require_totp: true,
+                partial_token,
+            })
+            .into_response());
+        }
+        Err(e) => return Err(login_error_response(e, &req.email, ip)),
+    };
 
     // Get user info from token
     let user_id = state
         .auth_service
         .validate_token(&tokens.access_token)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
 
-    info!("Login successful for: {}", req.email);
+    info!("Login successful for: {} from {}", req.email, ip);
 
     Ok(Json(LoginResponse {
         access_token: tokens.access_token,
@@ -67,9 +136,123 @@ pub async fn login(
             email: req.email,
             name: None, // We don't have name in login response for now
         },
+    })
+    .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TotpChallengeRequest {
+    partial_token: String,
+    code: String,
+}
+
+/// Finish a two-factor login with the partial token from `login` and a TOTP code
+pub async fn totp_challenge(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    Json(req): Json<TotpChallengeRequest>,
+) -> Result<Json<LoginResponse>, Response> {
+    let (user_id, tokens) = match state
+        .auth_service
+        .totp_challenge(&req.partial_token, &req.code, Some(&ip.to_string()))
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => return Err(login_error_response(e, "TOTP challenge", ip)),
+    };
+
+    let (id, email, name) = match state.db.get_user_by_id(&user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED.into_response()),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+
+    info!("Two-factor login successful for: {} from {}", email, ip);
+
+    Ok(Json(LoginResponse {
+        access_token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        expires_in: tokens.expires_in,
+        user: UserInfo { id, email, name },
     }))
 }
 
+#[derive(Debug, Serialize)]
+pub struct TotpSetupResponse {
+    secret: String,
+    otpauth_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TotpVerifyRequest {
+    code: String,
+}
+
+async fn bearer_user_id(state: &AppState, headers: &HeaderMap) -> Result<String, StatusCode> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    state
+        .auth_service
+        .validate_token(token)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)
+}
+
+/// Generate a TOTP secret for the current user. Answers 409 once two-factor
+/// login is enabled, so a stolen token can't swap the secret.
+pub async fn totp_setup(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<TotpSetupResponse>, StatusCode> {
+    let user_id = bearer_user_id(&state, &headers).await?;
+
+    match state.auth_service.totp_setup(&user_id).await {
+        Ok(Some(setup)) => {
+            info!("TOTP setup started for user {}", user_id);
+            Ok(Json(TotpSetupResponse {
+                secret: setup.secret,
+                otpauth_uri: setup.otpauth_uri,
+            }))
+        }
+        Ok(None) => Err(StatusCode::CONFLICT),
+        Err(e) => {
+            error!("TOTP setup failed for user {}: {}", user_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Enable two-factor login once the user proves their app has the secret
+pub async fn totp_verify(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<TotpVerifyRequest>,
+) -> StatusCode {
+    let user_id = match bearer_user_id(&state, &headers).await {
+        Ok(user_id) => user_id,
+        Err(status) => return status,
+    };
+
+    match state.auth_service.totp_verify(&user_id, &req.code).await {
+        Ok(true) => {
+            info!("Two-factor login enabled for user {}", user_id);
+            StatusCode::NO_CONTENT
+        }
+        Ok(false) => StatusCode::BAD_REQUEST,
+        Err(e) => {
+            error!("TOTP verification failed for user {}: {}", user_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
 pub async fn register(
     State(state): State<AppState>,
     Json(req): Json<RegisterRequest>,
@@ -82,7 +265,7 @@ pub async fn register(
     }
 
     // Validate password length
-    if req.password.len() < 6 {
+    if req.password.len() < MIN_PASSWORD_LEN {
         return Err(StatusCode::BAD_REQUEST);
     }
 
@@ -100,6 +283,10 @@ pub async fn register(
         return Err(StatusCode::CONFLICT);
     }
 
+    if state.auth_service.requires_sso(&req.email) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     // Use AuthService to register
     let tokens = state
         .auth_service
@@ -131,7 +318,258 @@ pub async fn register(
     }))
 }
 
-pub async fn refresh(State(_state): State<AppState>) -> Result<Json<LoginResponse>, StatusCode> {
-    // TODO: Implement token refresh with refresh token validation
-    Err(StatusCode::NOT_IMPLEMENTED)
+#[derive(Debug, Serialize)]
+pub struct ProvidersResponse {
+    providers: Vec<ProviderInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeQuery {
+    redirect: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// List external sign-in providers and whether they are configured
+pub async fn providers(State(state): State<AppState>) -> Json<ProvidersResponse> {
+    Json(ProvidersResponse {
+        providers: state.oidc_service.providers(),
+    })
+}
+
+/// Start an OIDC login by redirecting to the provider
+pub async fn oidc_authorize(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<AuthorizeQuery>,
+) -> Result<Redirect, StatusCode> {
+    // Only allow same-site redirects after login
+    let redirect = query.redirect.filter(|r| r.starts_with('/') && !r.starts_with("//"));
+
+    let url = state
+        .oidc_service
+        .authorize_url(&provider, redirect)
+        .map_err(|e| {
+            warn!("OIDC authorize failed for {}: {}", provider, e);
+            StatusCode::NOT_FOUND
+        })?;
+
+    Ok(Redirect::to(&url))
+}
+
+/// Finish an OIDC login and hand the tokens to the web app's login page
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    Path(provider): Path<String>,
+    Query(query): Query<CallbackQuery>,
+) -> Redirect {
+    if let Some(error) = query.error {
+        warn!("OIDC provider {} returned error: {}", provider, error);
+        return login_error_redirect("Sign-in was cancelled or denied");
+    }
+
+    let (Some(code), Some(csrf_state)) = (query.code, query.state) else {
+        return login_error_redirect("Invalid sign-in response");
+    };
+
+    let (identity, redirect) = match state
+        .oidc_service
+        .complete_login(&provider, &code, &csrf_state)
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            error!("OIDC login via {} failed: {:#}", provider, e);
+            return login_error_redirect("Sign-in failed");
+        }
+    };
+
+    let tokens = match state
+        .auth_service
+        .login_external(
+            &identity.email,
+            identity.name.as_deref(),
+            &format!("oidc:{}", provider),
+            Some(&ip.to_string()),
+        )
+        .await
+    {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            error!("Failed to sign in {} via {}: {}", identity.email, provider, e);
+            return login_error_redirect("Sign-in failed");
+        }
+    };
+
+    let user_id = match state.auth_service.validate_token(&tokens.access_token).await {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            error!("Failed to validate issued token: {}", e);
+            return login_error_redirect("Sign-in failed");
+        }
+    };
+
+    info!("OIDC login successful for {} via {}", identity.email, provider);
+
+    // Tokens travel in the fragment so they never reach server logs
+    let mut fragment = url_encode_pairs(&[
+        ("access_token", &tokens.access_token),
+        ("refresh_token", &tokens.refresh_token),
+        ("user_id", &user_id),
+        ("email", &identity.email),
+    ]);
+    if let Some(redirect) = redirect {
+        fragment.push_str(&format!("&redirect={}", url_encode(&redirect)));
+    }
+
+    Redirect::to(&format!("/login#{}", fragment))
+}
+
+pub(crate) fn login_error_redirect(message: &str) -> Redirect {
+    Redirect::to(&format!("/login#error={}", url_encode(message)))
+}
+
+pub(crate) fn url_encode_pairs(pairs: &[(&str, &str)]) -> String {
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, url_encode(v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+pub(crate) fn url_encode(value: &str) -> String {
+    openidconnect::url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+pub async fn refresh(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    Json(req): Json<RefreshRequest>,
+) -> Result<Json<LoginResponse>, StatusCode> {
+    let (user_id, tokens) = state
+        .auth_service
+        .refresh(&req.refresh_token, Some(&ip.to_string()))
+        .await
+        .map_err(|e| {
+            warn!("Token refresh rejected: {}", e);
+            StatusCode::UNAUTHORIZED
+        })?;
+
+    let (id, email, name) = match state.db.get_user_by_id(&user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok(Json(LoginResponse {
+        access_token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        expires_in: tokens.expires_in,
+        user: UserInfo { id, email, name },
+    }))
+}
+
+/// Record a sign-out; tokens are stateless, so the client discards its own
+pub async fn logout(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = bearer_user_id(&state, &headers).await?;
+    state.auth_service.logout(&user_id, Some(&ip.to_string()));
+    info!("Logout for user {} from {}", user_id, ip);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    token: String,
+    new_password: String,
+}
+
+/// Email a password reset link. Answers 202 whether or not the account
+/// exists or the mail went out, so the endpoint can't be used to probe for
+/// registered emails; failures are only logged.
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Json(req): Json<ForgotPasswordRequest>,
+) -> StatusCode {
+    if !state.mail_service.is_enabled() {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+
+    let token = match state.auth_service.create_password_reset(&req.email).await {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            info!("Password reset requested for unknown email: {}", req.email);
+            return StatusCode::ACCEPTED;
+        }
+        Err(e) => {
+            error!("Failed to create password reset token: {}", e);
+            return StatusCode::ACCEPTED;
+        }
+    };
+
+    let link = format!("{}/reset-password?token={}", state.public_url, token);
+    let text = format!(
+        "Someone asked to reset the password of your Happy Coding account.\n\n\
+         Open this link within {} minutes to choose a new one:\n{}\n\n\
+         If it wasn't you, ignore this email; your password stays the same.",
+        crate::services::auth::PASSWORD_RESET_TTL_MINUTES,
+        link
+    );
+    match state
+        .mail_service
+        .send(&req.email, "Reset your Happy Coding password", &text)
+        .await
+    {
+        Ok(()) => info!("Password reset email sent to: {}", req.email),
+        Err(e) => error!("Failed to send password reset email: {:#}", e),
+    }
+    StatusCode::ACCEPTED
+}
+
+/// Set a new password with a token from `forgot_password`, signing the user out everywhere
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> StatusCode {
+    if req.new_password.len() < MIN_PASSWORD_LEN {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    match state
+        .auth_service
+        .reset_password(&req.token, &req.new_password)
+        .await
+    {
+        Ok(true) => {
+            info!("Password reset completed");
+            StatusCode::NO_CONTENT
+        }
+        Ok(false) => StatusCode::BAD_REQUEST,
+        Err(e) => {
+            error!("Password reset error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
 }
@@ -0,0 +1,78 @@
+//! Daemon configuration handlers
+
+use crate::AppState;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use happy_types::ServerMessage;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateConfigRequest {
+    server_url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdateConfigResponse {
+    server_url: String,
+    /// Online daemons the new URL was pushed to
+    daemons_updated: usize,
+}
+
+/// Point the user's daemons at a new server, like `happy config set-server` on each machine
+pub async fn update(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<UpdateConfigRequest>,
+) -> Result<Json<UpdateConfigResponse>, StatusCode> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let user_id = state
+        .auth_service
+        .validate_token(token)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let url = req.server_url.trim().trim_end_matches('/');
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let machines = state
+        .machine_registry
+        .list_user_machines(&user_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to list machines for {}: {}", user_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut daemons_updated = 0;
+    for machine in machines {
+        if let Some(tx) = state.conn_manager.get_machine_tx(&machine.id).await {
+            let msg = ServerMessage::ServerUrlChanged {
+                url: url.to_string(),
+            };
+            if tx.send(msg).is_ok() {
+                daemons_updated += 1;
+            }
+        }
+    }
+
+    info!(
+        "User {} set server URL to {} ({} daemons updated)",
+        user_id, url, daemons_updated
+    );
+
+    Ok(Json(UpdateConfigResponse {
+        server_url: url.to_string(),
+        daemons_updated,
+    }))
+}
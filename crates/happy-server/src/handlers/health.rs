@@ -1,17 +1,32 @@
 //! Health check handler
 
-use axum::Json;
+use crate::handlers::ws::ConnectionCounts;
+use crate::storage::db::DatabaseStats;
+use crate::AppState;
+use axum::{extract::State, Json};
 use serde::Serialize;
 
 #[derive(Serialize)]
 pub struct HealthResponse {
     status: String,
     version: String,
+    /// Terminal history currently buffered across all sessions
+    history_total_mb: f64,
+    sessions_with_history: usize,
+    connections: ConnectionCounts,
+    /// Absent when the database could not be queried
+    #[serde(skip_serializing_if = "Option::is_none")]
+    database: Option<DatabaseStats>,
 }
 
-pub async fn health() -> Json<HealthResponse> {
+pub async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
+    let history = state.conn_manager.history_stats().await;
     Json(HealthResponse {
         status: "ok".to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
+        history_total_mb: history.total_bytes as f64 / (1024.0 * 1024.0),
+        sessions_with_history: history.sessions,
+        connections: state.conn_manager.connection_counts().await,
+        database: state.db.stats().await.ok(),
     })
 }
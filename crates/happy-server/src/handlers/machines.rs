@@ -1,20 +1,86 @@
 //! Machine handlers
 
+use crate::handlers::ws::broadcast_machine_list;
 use crate::AppState;
-use axum::{Json, extract::{State, Path}, http::StatusCode};
+use axum::{Json, extract::{State, Path, Query}, http::{HeaderMap, StatusCode}};
 use happy_core::{Machine, MachineInfo};
+use happy_types::ServerMessage;
 use serde::{Deserialize, Serialize};
 
+fn extract_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+}
+
+/// Validate the token and load a machine owned by its user
+async fn owned_machine(
+    state: &AppState,
+    headers: &HeaderMap,
+    id: &str,
+) -> Result<(String, Machine), StatusCode> {
+    let token = extract_token(headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    let user_id = state
+        .auth_service
+        .validate_token(token)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    match state.machine_registry.get_machine(id).await {
+        Ok(Some(machine)) if machine.user_id == user_id => Ok((user_id, machine)),
+        Ok(Some(_)) => Err(StatusCode::FORBIDDEN),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get machine {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct MachineListResponse {
     machines: Vec<MachineInfo>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MachineListQuery {
+    /// Full-text search over machine names and hostnames
+    q: Option<String>,
+}
+
 pub async fn list(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<MachineListQuery>,
 ) -> Result<Json<MachineListResponse>, StatusCode> {
-    // TODO: Implement list machines
-    Ok(Json(MachineListResponse { machines: vec![] }))
+    let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    // Validate token
+    let user_id = match state.auth_service.validate_token(token).await {
+        Ok(id) => id,
+        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    let machines = match query.q.as_deref() {
+        Some(q) => state.machine_registry.list_user_machines_search(&user_id, q).await,
+        None => state.machine_registry.list_user_machines(&user_id).await,
+    };
+    match machines {
+        Ok(machines) => {
+            // Online means a daemon is connected right now
+            let mut result = Vec::with_capacity(machines.len());
+            for mut m in machines {
+                m.is_online = state.conn_manager.has_machine(&m.id).await;
+                result.push(m);
+            }
+            Ok(Json(MachineListResponse { machines: result }))
+        }
+        Err(e) => {
+            tracing::error!("Failed to list machines: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,3 +109,55 @@ pub async fn get(
     // TODO: Implement get machine
     Err(StatusCode::NOT_IMPLEMENTED)
 }
+
+#[derive(Debug, Deserialize)]
+pub struct RenameMachineRequest {
+    name: String,
+}
+
+pub async fn rename(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<RenameMachineRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let (user_id, _) = owned_machine(&state, &headers, &id).await?;
+
+    if let Err(e) = state.machine_registry.rename_machine(&id, name).await {
+        tracing::error!("Failed to rename machine {}: {}", id, e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    broadcast_machine_list(&state, &user_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let (user_id, _) = owned_machine(&state, &headers, &id).await?;
+
+    let session_ids = match state.machine_registry.unregister_machine(&id).await {
+        Ok(session_ids) => session_ids,
+        Err(e) => {
+            tracing::error!("Failed to remove machine {}: {}", id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    // The machine's sessions went with it
+    for session_id in session_ids {
+        let msg = ServerMessage::SessionDeleted { session_id: session_id.clone() };
+        state.conn_manager.send_to_user(&user_id, msg.clone()).await;
+        state.conn_manager.forward_to_cli(&session_id, msg).await;
+    }
+    broadcast_machine_list(&state, &user_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
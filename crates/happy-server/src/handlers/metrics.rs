@@ -0,0 +1,93 @@
+//! Prometheus scrape endpoint
+
+use crate::handlers::admin::constant_time_eq;
+use crate::metrics::{self, Gauges};
+use crate::AppState;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+/// Metrics in the Prometheus text format. Open unless `METRICS_TOKEN` is
+/// set, in which case scrapers must send it as a bearer token.
+pub async fn metrics(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Some(expected) = state.metrics_token.as_deref() {
+        let token = headers
+            .get("authorization")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "));
+        if !token.is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes())) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+
+    let gauges = Gauges {
+        ws_connections_active: state.conn_manager.user_connection_count().await,
+        sessions_active: state.conn_manager.active_session_count().await,
+        cache: state.cache.stats(),
+    };
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::global().render(&gauges),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn state(dir: &std::path::Path, metrics_token: Option<&str>) -> AppState {
+        AppState {
+            metrics_token: metrics_token.map(Arc::from),
+            ..crate::handlers::test_state(dir).await
+        }
+    }
+
+    async fn scrape(state: AppState, token: Option<&str>) -> (StatusCode, String) {
+        let mut request = Request::get("/metrics");
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        let response = crate::api_routes()
+            .with_state(state)
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let dir = std::env::temp_dir().join(format!("happy-metrics-{}", uuid::Uuid::new_v4()));
+
+        let (status, body) = scrape(state(&dir, None).await, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("# HELP"), "{}", body);
+        assert!(body.contains("happy_ws_connections_active 0\n"));
+        // The histogram is listed even before any query has been timed
+        assert!(body.contains("happy_db_query_duration_seconds_count"));
+
+        // Active connections are the authenticated ones the manager tracks
+        let connected = state(&dir, None).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let now = std::time::Instant::now();
+        connected.conn_manager.register_user("alice", "tab-1", now, tx).await.unwrap();
+        let (_, body) = scrape(connected, None).await;
+        assert!(body.contains("happy_ws_connections_active 1\n"), "{}", body);
+
+        let protected = state(&dir, Some("scrape-me")).await;
+        assert_eq!(scrape(protected.clone(), None).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(scrape(protected.clone(), Some("wrong")).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(scrape(protected, Some("scrape-me")).await.0, StatusCode::OK);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
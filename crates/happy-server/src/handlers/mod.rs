@@ -1,10 +1,76 @@
 //! HTTP handlers
 
+pub mod admin;
+pub mod audit;
 pub mod auth;
+pub mod config;
 pub mod health;
 pub mod machines;
+pub mod metrics;
+pub mod push;
+pub mod session_updates;
 pub mod sessions;
+#[cfg(feature = "sso")]
+pub mod sso;
 pub mod users;
 pub mod ws;
+pub mod ws_rate_limit;
 
 pub use health::health;
+
+/// An `AppState` over a fresh database in `dir`, for handler tests
+#[cfg(test)]
+pub(crate) async fn test_state(dir: &std::path::Path) -> crate::AppState {
+    use crate::extractors::TrustedProxies;
+    use crate::middleware::RateLimiter;
+    use crate::services::{
+        AuthService, MachineRegistry, MailService, OidcService, PushService, SessionManager,
+    };
+    use crate::storage::{Database, DatabaseConfig, MemoryCache};
+    use session_updates::DebouncedBroadcaster;
+    use std::sync::Arc;
+    use ws::ConnectionManager;
+    use ws_rate_limit::{
+        MessageRateLimiter, DEFAULT_REMOTE_SESSION_RATE, DEFAULT_TERMINAL_INPUT_RATE,
+    };
+
+    let db = Arc::new(
+        Database::new(dir.join("test.db").to_str().unwrap(), DatabaseConfig::default())
+            .await
+            .unwrap(),
+    );
+    let cache = Arc::new(MemoryCache::new());
+    let conn_manager = Arc::new(ConnectionManager::new());
+    crate::AppState {
+        session_manager: Arc::new(SessionManager::new(db.clone(), cache.clone())),
+        machine_registry: Arc::new(MachineRegistry::new(db.clone(), cache.clone())),
+        auth_service: Arc::new(AuthService::new(
+            db.clone(),
+            cache.clone(),
+            "secret".into(),
+            None,
+        )),
+        oidc_service: Arc::new(OidcService::from_env(cache.clone(), "http://localhost").await),
+        #[cfg(feature = "sso")]
+        saml_service: None,
+        push_service: Arc::new(PushService::from_env(db.clone())),
+        mail_service: Arc::new(MailService::from_env()),
+        session_updates: Arc::new(DebouncedBroadcaster::new(
+            conn_manager.clone(),
+            std::time::Duration::from_millis(100),
+        )),
+        ws_rate_limiter: Arc::new(MessageRateLimiter::new(
+            cache.clone(),
+            DEFAULT_TERMINAL_INPUT_RATE,
+            DEFAULT_REMOTE_SESSION_RATE,
+        )),
+        conn_manager,
+        db,
+        cache,
+        admin_token: None,
+        metrics_token: None,
+        rate_limiter: Arc::new(RateLimiter::new(300)),
+        trusted_proxies: Arc::new(TrustedProxies::default()),
+        public_url: Arc::from("http://localhost"),
+    }
+}
@@ -0,0 +1,107 @@
+//! Push notification handlers
+
+use crate::AppState;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+/// Platforms a device token can come from
+const PUSH_PLATFORMS: [&str; 3] = ["web", "android", "ios"];
+
+/// Title used when the sender doesn't give one
+const DEFAULT_TITLE: &str = "Happy Remote";
+
+fn extract_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterPushTokenRequest {
+    token: String,
+    #[serde(default = "default_platform")]
+    platform: String,
+}
+
+fn default_platform() -> String {
+    "web".to_string()
+}
+
+/// Register the caller's FCM device token
+pub async fn register(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<RegisterPushTokenRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    let user_id = state
+        .auth_service
+        .validate_token(token)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let platform = req.platform.to_lowercase();
+    if req.token.trim().is_empty() || !PUSH_PLATFORMS.contains(&platform.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    state
+        .db
+        .save_push_token(&user_id, req.token.trim(), &platform)
+        .await
+        .map_err(|e| {
+            error!("Failed to save push token for {}: {}", user_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    info!("Registered {} push token for user {}", platform, user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendPushRequest {
+    message: String,
+    title: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SendPushResponse {
+    /// Devices FCM accepted the notification for
+    delivered: usize,
+}
+
+/// Notify all of the caller's registered devices
+pub async fn send(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<SendPushRequest>,
+) -> Result<Json<SendPushResponse>, StatusCode> {
+    let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    let user_id = state
+        .auth_service
+        .validate_token(token)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    if !state.push_service.is_enabled() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    let title = req.title.as_deref().unwrap_or(DEFAULT_TITLE);
+    let delivered = state
+        .push_service
+        .send_to_user(&user_id, title, &req.message)
+        .await
+        .map_err(|e| {
+            error!("Failed to send push notification to {}: {:#}", user_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(SendPushResponse { delivered }))
+}
@@ -0,0 +1,145 @@
+//! Debounced `SessionUpdated` broadcasts
+//!
+//! A single attach can change a session's machine, cwd, agent version and
+//! status in quick succession. Updates are held per session for a short
+//! window and only the latest version is sent to the owner's clients.
+
+use crate::handlers::ws::ConnectionManager;
+use happy_types::{ServerMessage, Session};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Default debounce window, overridden by `SESSION_UPDATE_DEBOUNCE_MS`
+pub const DEFAULT_SESSION_UPDATE_DEBOUNCE_MS: u64 = 50;
+
+/// Coalesces `SessionUpdated` events before they are sent to the session's owner
+pub struct DebouncedBroadcaster {
+    conn_manager: Arc<ConnectionManager>,
+    /// Latest session per id, with the time its first pending update was queued
+    pending: Mutex<HashMap<String, (Session, Instant)>>,
+    window: Duration,
+}
+
+impl DebouncedBroadcaster {
+    pub fn new(conn_manager: Arc<ConnectionManager>, window: Duration) -> Self {
+        Self {
+            conn_manager,
+            pending: Mutex::new(HashMap::new()),
+            window,
+        }
+    }
+
+    /// Queue an update, replacing any pending one for the same session
+    pub async fn queue(&self, session: Session) {
+        let mut pending = self.pending.lock().await;
+        match pending.get_mut(&session.id) {
+            Some((latest, _)) => *latest = session,
+            None => {
+                pending.insert(session.id.clone(), (session, Instant::now()));
+            }
+        }
+    }
+
+    /// Send every pending update to its session's owner, oldest first.
+    /// Returns how many were sent.
+    pub async fn flush(&self) -> usize {
+        let mut updates: Vec<_> = self.pending.lock().await.drain().map(|(_, v)| v).collect();
+        updates.sort_by_key(|(_, queued_at)| *queued_at);
+
+        let count = updates.len();
+        for (session, _) in updates {
+            let user_id = session.user_id.clone();
+            self.conn_manager
+                .send_to_user(&user_id, ServerMessage::SessionUpdated { session })
+                .await;
+        }
+        count
+    }
+
+    /// Flush pending updates once per window for the life of the server
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.window);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                self.flush().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use happy_types::SessionStatus;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_flush_sends_latest_version_once() {
+        let conn_manager = Arc::new(ConnectionManager::new());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        conn_manager
+            .register_user("user-1", "conn-1", Instant::now(), tx)
+            .await
+            .unwrap();
+
+        let broadcaster = DebouncedBroadcaster::new(conn_manager, Duration::from_millis(50));
+        let mut session = Session::new(
+            "s1".to_string(),
+            "demo".to_string(),
+            "user-1".to_string(),
+            "m1".to_string(),
+            "laptop".to_string(),
+        );
+        broadcaster.queue(session.clone()).await;
+        session.status = SessionStatus::Running;
+        broadcaster.queue(session).await;
+
+        assert_eq!(broadcaster.flush().await, 1);
+        match rx.try_recv().unwrap() {
+            ServerMessage::SessionUpdated { session } => {
+                assert_eq!(session.status, SessionStatus::Running)
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(rx.try_recv().is_err());
+        assert_eq!(broadcaster.flush().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_sends_only_to_owner() {
+        let conn_manager = Arc::new(ConnectionManager::new());
+        let (owner_tx, mut owner_rx) = mpsc::unbounded_channel();
+        let (other_tx, mut other_rx) = mpsc::unbounded_channel();
+        conn_manager
+            .register_user("user-1", "conn-1", Instant::now(), owner_tx)
+            .await
+            .unwrap();
+        conn_manager
+            .register_user("user-2", "conn-2", Instant::now(), other_tx)
+            .await
+            .unwrap();
+
+        let broadcaster = DebouncedBroadcaster::new(conn_manager, Duration::from_millis(50));
+        broadcaster
+            .queue(Session::new(
+                "s1".to_string(),
+                "demo".to_string(),
+                "user-1".to_string(),
+                "m1".to_string(),
+                "laptop".to_string(),
+            ))
+            .await;
+
+        assert_eq!(broadcaster.flush().await, 1);
+        assert!(matches!(
+            owner_rx.try_recv(),
+            Ok(ServerMessage::SessionUpdated { .. })
+        ));
+        // Another user's sessions never reach this one
+        assert!(other_rx.try_recv().is_err());
+    }
+}
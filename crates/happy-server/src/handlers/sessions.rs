@@ -1,14 +1,28 @@
 //! Session handlers
 
+use crate::extractors::ClientIp;
 use crate::AppState;
 use axum::{
-    extract::{Path, State},
-    http::header::HeaderMap,
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::header::{self, HeaderMap},
     http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
-use happy_core::Session;
+use futures::Stream;
+use happy_core::ansi::strip_ansi;
+use happy_core::{Session, SessionEvent};
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+use crate::handlers::ws::ConnectionManager;
 
 #[derive(Debug, Serialize)]
 pub struct SessionListResponse {
@@ -73,6 +87,7 @@ pub struct SessionResponse {
 
 pub async fn create(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     headers: HeaderMap,
     Json(req_body): Json<CreateSessionRequest>,
 ) -> Result<Json<SessionResponse>, StatusCode> {
@@ -104,10 +119,11 @@ pub async fn create(
 
     // Get cwd from request, default to "/" if not provided
     let cwd = req_body.cwd.unwrap_or_else(|| "/".to_string());
+    let ip = ip.to_string();
 
     match state
         .session_manager
-        .create_session(&user_id, &machine_id, &machine_name, &req_body.tag, &cwd)
+        .create_session(&user_id, &machine_id, &machine_name, &req_body.tag, &cwd, Some(&ip))
         .await
     {
         Ok(session) => Ok(Json(SessionResponse { session })),
@@ -147,8 +163,84 @@ pub async fn get(
     }
 }
 
+/// Longest tag a session can be renamed to
+const MAX_TAG_LEN: usize = 64;
+
+/// Tags are 1-64 ASCII letters, digits, `_` or `-`
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.len() <= MAX_TAG_LEN
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameSessionRequest {
+    tag: String,
+}
+
+pub async fn rename(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<RenameSessionRequest>,
+) -> Result<Json<SessionResponse>, StatusCode> {
+    if !is_valid_tag(&req.tag) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    // Validate token
+    let user_id = match state.auth_service.validate_token(token).await {
+        Ok(id) => id,
+        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    match state.session_manager.get_session(&id).await {
+        Ok(Some(session)) => {
+            if session.user_id != user_id {
+                return Err(StatusCode::FORBIDDEN);
+            }
+        }
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get session: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    match state
+        .session_manager
+        .rename_session(&id, &user_id, &req.tag)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::CONFLICT),
+        Err(e) => {
+            tracing::error!("Failed to rename session {}: {}", id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    match state.session_manager.get_session(&id).await {
+        Ok(Some(session)) => {
+            // Web clients and the session's CLI bridge pick up the new tag
+            state.session_updates.queue(session.clone()).await;
+            Ok(Json(SessionResponse { session }))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get session: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 pub async fn delete(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     Path(id): Path<String>,
     headers: HeaderMap,
 ) -> Result<StatusCode, StatusCode> {
@@ -175,15 +267,46 @@ pub async fn delete(
         }
     };
 
-    // If session is running, terminate it first. Otherwise, permanently remove it.
+    // Deleting an archived session purges it for good
+    let ip = ip.to_string();
+    match state.session_manager.is_archived(&id).await {
+        Ok(true) => {
+            return match state
+                .session_manager
+                .purge_session(&id, &user_id, Some(&ip))
+                .await
+            {
+                Ok(_) => Ok(StatusCode::NO_CONTENT),
+                Err(e) => {
+                    tracing::error!("Failed to purge session: {}", e);
+                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            };
+        }
+        Ok(false) => {}
+        Err(e) => {
+            tracing::error!("Failed to get session: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    // If session is running, terminate it first. Otherwise, archive it.
     use happy_core::SessionStatus;
     use happy_types::ServerMessage;
 
     match session.status {
         SessionStatus::Running | SessionStatus::Paused => {
             // Soft delete - mark as terminated
-            match state.session_manager.terminate_session(&id).await {
+            match state
+                .session_manager
+                .terminate_session(&id, &user_id, Some(&ip))
+                .await
+            {
                 Ok(_) => {
+                    let mut stopped = session;
+                    stopped.status = SessionStatus::Terminated;
+                    state.session_updates.queue(stopped).await;
+
                     // Notify CLI bridge to stop the session
                     state
                         .conn_manager
@@ -203,19 +326,19 @@ pub async fn delete(
             }
         }
         SessionStatus::Initializing | SessionStatus::Terminated => {
-            // Hard delete - permanently remove zombie/completed sessions
-            match state.session_manager.remove_session(&id).await {
+            // Archive zombie/completed sessions; they can be restored over WebSocket
+            match state
+                .session_manager
+                .remove_session(&id, &user_id, Some(&ip))
+                .await
+            {
                 Ok(_) => {
+                    let msg = ServerMessage::SessionDeleted {
+                        session_id: id.clone(),
+                    };
+                    state.conn_manager.send_to_user(&user_id, msg.clone()).await;
                     // Notify CLI bridge to delete the session
-                    state
-                        .conn_manager
-                        .forward_to_cli(
-                            &id,
-                            ServerMessage::SessionDeleted {
-                                session_id: id.clone(),
-                            },
-                        )
-                        .await;
+                    state.conn_manager.forward_to_cli(&id, msg).await;
                     Ok(StatusCode::NO_CONTENT)
                 }
                 Err(e) => {
@@ -226,3 +349,171 @@ pub async fn delete(
         }
     }
 }
+
+/// Chunk size used when streaming history
+const HISTORY_CHUNK_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryFormat {
+    /// Terminal output exactly as recorded
+    #[default]
+    Raw,
+    /// Output with ANSI escape sequences removed
+    Text,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    #[serde(default)]
+    format: HistoryFormat,
+    /// Only return the last N bytes
+    tail: Option<usize>,
+}
+
+/// Stream a session's buffered terminal output
+pub async fn history(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<HistoryQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    // Validate token
+    let user_id = match state.auth_service.validate_token(token).await {
+        Ok(id) => id,
+        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    match state.session_manager.get_session(&id).await {
+        Ok(Some(session)) => {
+            if session.user_id != user_id {
+                return Err(StatusCode::FORBIDDEN);
+            }
+        }
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get session: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let mut data = state
+        .conn_manager
+        .get_output_buffer(&id, query.tail.unwrap_or(usize::MAX))
+        .await
+        .map(|history| history.data)
+        .unwrap_or_default();
+    let content_type = match query.format {
+        HistoryFormat::Raw => "application/octet-stream",
+        HistoryFormat::Text => {
+            data = strip_ansi(&data);
+            "text/plain; charset=utf-8"
+        }
+    };
+
+    let chunks: Vec<Result<Bytes, std::io::Error>> = data
+        .chunks(HISTORY_CHUNK_BYTES)
+        .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+        .collect();
+    let body = Body::from_stream(futures::stream::iter(chunks));
+
+    Ok(([(header::CONTENT_TYPE, content_type)], body).into_response())
+}
+
+/// Session changes as server-sent events, for integrations that don't speak WebSocket
+///
+/// The stream registers with the connection manager like a WebSocket client
+/// and forwards the session broadcasts that belong to the caller.
+pub async fn events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    // Validate token
+    let user_id = match state.auth_service.validate_token(token).await {
+        Ok(id) => id,
+        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let connection_id = uuid::Uuid::new_v4().to_string();
+    state
+        .conn_manager
+        .register_user(&user_id, &connection_id, Instant::now(), tx)
+        .await
+        .map_err(|_| StatusCode::TOO_MANY_REQUESTS)?;
+    let subscription = EventSubscription {
+        conn_manager: state.conn_manager.clone(),
+        connection_id,
+    };
+    let closing = state.conn_manager.closing();
+
+    let stream = futures::stream::unfold(
+        (rx, closing, subscription),
+        move |(mut rx, mut closing, subscription)| {
+            let user_id = user_id.clone();
+            async move {
+                loop {
+                    let msg = tokio::select! {
+                        msg = rx.recv() => msg?,
+                        _ = closing.wait_for(|closing| *closing) => return None,
+                    };
+                    let Some(event) = SessionEvent::from_server_message(msg) else {
+                        continue;
+                    };
+                    // Deletions only carry an id, but are only sent to the owner
+                    let visible = match &event {
+                        SessionEvent::Created { session } | SessionEvent::Updated { session } => {
+                            session.user_id == user_id
+                        }
+                        SessionEvent::Deleted { .. } => true,
+                    };
+                    if !visible {
+                        continue;
+                    }
+                    let Ok(data) = serde_json::to_string(&event) else {
+                        continue;
+                    };
+                    return Some((Ok(Event::default().data(data)), (rx, closing, subscription)));
+                }
+            }
+        },
+    );
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Unregisters an event stream from the connection manager once the client goes away
+struct EventSubscription {
+    conn_manager: Arc<ConnectionManager>,
+    connection_id: String,
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        let conn_manager = self.conn_manager.clone();
+        let connection_id = std::mem::take(&mut self.connection_id);
+        tokio::spawn(async move {
+            conn_manager.unregister_user(&connection_id).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_tag() {
+        assert!(is_valid_tag("happy-otter_42"));
+        assert!(is_valid_tag(&"a".repeat(MAX_TAG_LEN)));
+
+        assert!(!is_valid_tag(""));
+        assert!(!is_valid_tag(&"a".repeat(MAX_TAG_LEN + 1)));
+        assert!(!is_valid_tag("has space"));
+        assert!(!is_valid_tag("ünïcode"));
+    }
+}
@@ -1,11 +1,14 @@
 //! User handlers
 
+use crate::services::auth::ACCOUNT_PURGE_DELAY_DAYS;
 use crate::AppState;
 use axum::{
     Json, extract::State, http::{StatusCode, HeaderMap},
 };
-use chrono::Utc;
-use serde::Serialize;
+use chrono::{DateTime, Duration, Utc};
+use happy_types::ServerMessage;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Serialize)]
 pub struct UserResponse {
@@ -48,3 +51,74 @@ pub async fn me(
         updated_at: now,
     }))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountRequest {
+    /// Left out by accounts created through SSO, which confirm by having
+    /// signed in recently instead
+    #[serde(default)]
+    password: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteAccountResponse {
+    purge_at: DateTime<Utc>,
+}
+
+/// Delete the current user's account and all data they own
+pub async fn delete_me(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<DeleteAccountRequest>,
+) -> Result<(StatusCode, Json<DeleteAccountResponse>), StatusCode> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let user_id = state.auth_service.validate_token(token)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    // Require the current password, or a recent sign-in for SSO accounts
+    let confirmed = state.auth_service.confirm_owner(token, req.password.as_deref())
+        .await
+        .map_err(|e| {
+            error!("Failed to confirm account deletion for {}: {}", user_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if !confirmed {
+        warn!("Account deletion for {} rejected: not confirmed", user_id);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let (session_ids, machine_ids) = state.db.soft_delete_user(&user_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to delete account {}: {}", user_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    for id in &session_ids {
+        state.cache.delete(&format!("session:{}", id));
+    }
+    for id in &machine_ids {
+        state.cache.delete(&format!("machine:{}", id));
+        state.cache.delete(&format!("machine:{}:online", id));
+    }
+
+    // Open web clients treat this as a forced logout
+    state.conn_manager.send_to_user(&user_id, ServerMessage::AccountDeleted).await;
+
+    let purge_at = Utc::now() + Duration::days(ACCOUNT_PURGE_DELAY_DAYS);
+    info!(
+        "Account {} deleted ({} sessions, {} machines), purge at {}",
+        user_id,
+        session_ids.len(),
+        machine_ids.len(),
+        purge_at
+    );
+
+    Ok((StatusCode::ACCEPTED, Json(DeleteAccountResponse { purge_at })))
+}
@@ -4,34 +4,295 @@
 //! - CLI daemon (PTY bridge) - sends TerminalOutput, receives TerminalInput
 //! - Web clients - sends TerminalInput, receives TerminalOutput
 
+use crate::extractors::ClientIp;
+use crate::handlers::ws_rate_limit::LimitedMessage;
 use crate::AppState;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::State;
 use axum::response::IntoResponse;
+use chrono::{DateTime, Utc};
 use futures::{sink::SinkExt, stream::StreamExt};
-use happy_types::{ClientMessage, ServerMessage, SessionStatus};
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use happy_types::{ClientMessage, ServerMessage, Session, SessionStatus};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch, RwLock};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+/// Verbosity of per-connection WebSocket logging, set by `WS_LOG_LEVEL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WsLogLevel {
+    /// Log nothing
+    Quiet,
+    /// Connection open/close and errors only
+    Normal,
+    /// Every message except terminal output and pings, with tokens redacted
+    Verbose,
+    /// Everything, including binary frames
+    Trace,
+}
+
+static WS_LOG_LEVEL: OnceLock<WsLogLevel> = OnceLock::new();
+
+impl WsLogLevel {
+    /// Read `WS_LOG_LEVEL`, defaulting to `verbose` when `RUST_LOG` asks for debug output
+    pub fn from_env() -> Self {
+        match std::env::var("WS_LOG_LEVEL").as_deref() {
+            Ok("quiet") => Self::Quiet,
+            Ok("normal") => Self::Normal,
+            Ok("verbose") => Self::Verbose,
+            Ok("trace") => Self::Trace,
+            other => {
+                if let Ok(value) = other {
+                    warn!("Unknown WS_LOG_LEVEL '{}', using default", value);
+                }
+                let rust_log = std::env::var("RUST_LOG").unwrap_or_default();
+                if rust_log.contains("debug") || rust_log.contains("trace") {
+                    Self::Verbose
+                } else {
+                    Self::Normal
+                }
+            }
+        }
+    }
+
+    /// Set the process-wide level; only the first call takes effect
+    pub fn init(level: Self) {
+        let _ = WS_LOG_LEVEL.set(level);
+    }
+
+    pub fn current() -> Self {
+        *WS_LOG_LEVEL.get_or_init(Self::from_env)
+    }
+}
+
+/// Log through `tracing` only if the WebSocket log level is at least `$min`
+macro_rules! ws_log {
+    ($min:ident, $log:ident, $($arg:tt)+) => {
+        if WsLogLevel::current() >= WsLogLevel::$min {
+            tracing::$log!($($arg)+);
+        }
+    };
+}
+
+/// Preview of an incoming message for `verbose` logging.
+///
+/// Returns `None` for high-volume message types and redacts auth tokens.
+fn verbose_preview(text: &str) -> Option<String> {
+    let mut value: serde_json::Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        // Unparseable input is worth seeing; it gets reported as an error below
+        Err(_) => return Some(truncate_preview(text)),
+    };
+
+    match value["type"].as_str() {
+        Some("terminal_output") | Some("ping") => return None,
+        Some("authenticate") => {
+            if let Some(token) = value.get_mut("token") {
+                *token = serde_json::Value::String("[REDACTED]".to_string());
+            }
+        }
+        _ => {}
+    }
+
+    Some(truncate_preview(&value.to_string()))
+}
+
+/// First 100 characters of a message
+fn truncate_preview(text: &str) -> String {
+    match text.char_indices().nth(100) {
+        Some((idx, _)) => format!("{}...", &text[..idx]),
+        None => text.to_string(),
+    }
+}
+
+/// Output kept per session for replay to joining web clients, unless configured otherwise
+pub const DEFAULT_MAX_HISTORY_BYTES: usize = 512 * 1024;
+/// Browser connections one user may hold open, unless configured otherwise
+pub const DEFAULT_MAX_USER_CONNECTIONS: usize = 20;
+/// Replayed on join when the client doesn't ask for a specific amount
+const DEFAULT_HISTORY_TAIL_BYTES: usize = 64 * 1024;
+
+/// How long a web client waits for the daemon to confirm a `WriteFile`
+const FILE_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Rolling window over a session's output stream
+struct OutputBuffer {
+    /// Stream offset of `data[0]`; grows as old output is dropped
+    start_offset: u64,
+    /// Ring of the newest output; once full, dropping old bytes doesn't move the rest
+    data: VecDeque<u8>,
+    updated_at: Instant,
+    /// Set once the buffer has been reported as close to its limit
+    near_limit_warned: bool,
+}
+
+impl OutputBuffer {
+    fn new() -> Self {
+        Self {
+            start_offset: 0,
+            data: VecDeque::new(),
+            updated_at: Instant::now(),
+            near_limit_warned: false,
+        }
+    }
+
+    fn end_offset(&self) -> u64 {
+        self.start_offset + self.data.len() as u64
+    }
+
+    fn push(&mut self, bytes: &[u8], max_bytes: usize) {
+        // Only the newest `max_bytes` survive, however much arrives at once
+        let kept = &bytes[bytes.len().saturating_sub(max_bytes)..];
+        let excess = (self.data.len() + kept.len()).saturating_sub(max_bytes);
+        self.data.drain(..excess);
+        self.data.extend(kept);
+        self.start_offset += (excess + bytes.len() - kept.len()) as u64;
+        self.updated_at = Instant::now();
+    }
+
+    /// Clamp the requested range to what is still buffered
+    fn range(&self, offset: u64, length: usize) -> HistorySlice {
+        let offset = offset.clamp(self.start_offset, self.end_offset());
+        let start = (offset - self.start_offset) as usize;
+        let end = (start + length).min(self.data.len());
+        HistorySlice {
+            offset,
+            data: self.data.range(start..end).copied().collect(),
+            has_more: offset > self.start_offset,
+        }
+    }
+}
+
+/// A piece of buffered output and where it sits in the stream
+#[derive(Debug, PartialEq)]
+pub struct HistorySlice {
+    pub offset: u64,
+    pub data: Vec<u8>,
+    /// Whether older output is still buffered
+    pub has_more: bool,
+}
+
+/// Totals over all buffered session output
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HistoryStats {
+    pub total_bytes: usize,
+    pub sessions: usize,
+}
+
+/// Live connection counts, reported by `/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionCounts {
+    /// Open daemon connections across all machines
+    pub cli_bridges: usize,
+    /// Authenticated web connections
+    pub web_clients: usize,
+}
+
+/// A CLI bridge attached to a session
+struct CliConnection {
+    tx: mpsc::UnboundedSender<ServerMessage>,
+    machine_id: String,
+    user_id: String,
+    connected_at: Instant,
+}
+
+/// A web client watching a session
+struct WebConnection {
+    connection_id: String,
+    tx: mpsc::UnboundedSender<ServerMessage>,
+}
+
+/// Any authenticated connection, web or CLI
+struct UserConnection {
+    user_id: String,
+    connection_id: String,
+    connected_at: Instant,
+    tx: mpsc::UnboundedSender<ServerMessage>,
+    /// Counts toward the user's limit; daemons and CLI bridges never do
+    web: bool,
+}
+
+/// Everyone connected right now, for `GET /admin/connections`
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionsSnapshot {
+    pub cli_bridges: Vec<CliBridgeInfo>,
+    pub web_clients: Vec<WebClientInfo>,
+    pub machines: Vec<MachineConnectionInfo>,
+    /// Connections each user holds against `max_user_connections`
+    pub users: Vec<UserConnectionCount>,
+    pub max_user_connections: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserConnectionCount {
+    pub user_id: String,
+    pub connection_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CliBridgeInfo {
+    pub session_id: String,
+    pub machine_id: String,
+    pub user_id: String,
+    pub connected_since: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WebClientInfo {
+    pub user_id: String,
+    /// Sessions this connection has joined
+    pub session_count: usize,
+    pub connected_since: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MachineConnectionInfo {
+    pub machine_id: String,
+    /// Filled in from the machine registry by the caller
+    pub name: Option<String>,
+    pub connection_count: usize,
+}
+
+/// Why `register_user` turned a connection away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionError {
+    /// The user already holds `limit` connections
+    TooManyConnections { limit: usize },
+}
+
+/// Wall-clock time of a monotonic instant
+fn wall_clock(instant: Instant) -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::from_std(instant.elapsed()).unwrap_or_default()
+}
+
 /// Connection manager for routing messages between CLI and web clients
 #[derive(Clone)]
 pub struct ConnectionManager {
     /// Maps session_id to the CLI bridge connection (daemon)
-    cli_connections: Arc<RwLock<HashMap<String, mpsc::UnboundedSender<ServerMessage>>>>,
+    cli_connections: Arc<RwLock<HashMap<String, CliConnection>>>,
     /// Maps session_id to list of web client connections
-    web_connections: Arc<RwLock<HashMap<String, Vec<mpsc::UnboundedSender<ServerMessage>>>>>,
+    web_connections: Arc<RwLock<HashMap<String, Vec<WebConnection>>>>,
     /// Maps machine_id to the CLI daemon connection (for remote session creation)
     /// Maps machine_id to map of connection_id -> CLI daemon connection
     machine_connections:
         Arc<RwLock<HashMap<String, HashMap<String, mpsc::UnboundedSender<ServerMessage>>>>>,
     /// Maps request_id to web client connection (for remote session responses)
     pending_requests: Arc<RwLock<HashMap<String, mpsc::UnboundedSender<ServerMessage>>>>,
-    output_buffers: Arc<RwLock<HashMap<String, Vec<u8>>>>,
+    /// Maps request_id of a transfer's remote session request to the session it replaces
+    pending_transfers: Arc<RwLock<HashMap<String, String>>>,
+    output_buffers: Arc<RwLock<HashMap<String, OutputBuffer>>>,
+    /// Per-session cap on `output_buffers`
+    max_history_bytes: usize,
     /// All authenticated user connections (for broadcasting global updates like MachineList)
-    user_connections: Arc<RwLock<Vec<(String, mpsc::UnboundedSender<ServerMessage>)>>>,
+    user_connections: Arc<RwLock<Vec<UserConnection>>>,
+    /// Per-user cap on the web clients in `user_connections`
+    max_user_connections: usize,
+    /// Set to true to make every open socket close itself
+    closing: Arc<watch::Sender<bool>>,
 }
 
 impl ConnectionManager {
@@ -41,39 +302,150 @@ impl ConnectionManager {
             web_connections: Arc::new(RwLock::new(HashMap::new())),
             machine_connections: Arc::new(RwLock::new(HashMap::new())),
             pending_requests: Arc::new(RwLock::new(HashMap::new())),
+            pending_transfers: Arc::new(RwLock::new(HashMap::new())),
             output_buffers: Arc::new(RwLock::new(HashMap::new())),
+            max_history_bytes: DEFAULT_MAX_HISTORY_BYTES,
             user_connections: Arc::new(RwLock::new(Vec::new())),
+            max_user_connections: DEFAULT_MAX_USER_CONNECTIONS,
+            closing: Arc::new(watch::channel(false).0),
         }
     }
 
-    /// Register a user connection for global broadcasts
-    pub async fn register_user(&self, user_id: &str, tx: mpsc::UnboundedSender<ServerMessage>) {
+    /// Set how much output is kept per session
+    pub fn with_history_limit(mut self, max_bytes: usize) -> Self {
+        self.max_history_bytes = max_bytes;
+        self
+    }
+
+    /// Set how many connections each user may hold
+    pub fn with_user_connection_limit(mut self, max_connections: usize) -> Self {
+        self.max_user_connections = max_connections;
+        self
+    }
+
+    /// Register a web client connection for global broadcasts
+    ///
+    /// Fails once the user holds `max_user_connections` web connections.
+    pub async fn register_user(
+        &self,
+        user_id: &str,
+        connection_id: &str,
+        connected_at: Instant,
+        tx: mpsc::UnboundedSender<ServerMessage>,
+    ) -> Result<(), ConnectionError> {
         let mut conns = self.user_connections.write().await;
-        conns.push((user_id.to_string(), tx));
+        self.check_user_limit(&mut conns, user_id)?;
+        conns.push(UserConnection {
+            user_id: user_id.to_string(),
+            connection_id: connection_id.to_string(),
+            connected_at,
+            tx,
+            web: true,
+        });
+        info!("User registered for global broadcasts: {}", user_id);
+        Ok(())
+    }
+
+    /// Register a connection whose role isn't known yet for global broadcasts
+    ///
+    /// Daemons and CLI bridges authenticate like browsers do, so a socket only
+    /// counts toward the limit once `count_as_web` is called for it.
+    pub async fn register_connection(
+        &self,
+        user_id: &str,
+        connection_id: &str,
+        connected_at: Instant,
+        tx: mpsc::UnboundedSender<ServerMessage>,
+    ) {
+        self.user_connections.write().await.push(UserConnection {
+            user_id: user_id.to_string(),
+            connection_id: connection_id.to_string(),
+            connected_at,
+            tx,
+            web: false,
+        });
         info!("User registered for global broadcasts: {}", user_id);
     }
 
+    /// Count a connection from `register_connection` as a web client
+    ///
+    /// Fails once its user holds `max_user_connections` web connections.
+    pub async fn count_as_web(&self, connection_id: &str) -> Result<(), ConnectionError> {
+        let mut conns = self.user_connections.write().await;
+        let Some(user_id) = conns
+            .iter()
+            .find(|c| c.connection_id == connection_id && !c.web)
+            .map(|c| c.user_id.clone())
+        else {
+            return Ok(());
+        };
+        self.check_user_limit(&mut conns, &user_id)?;
+        if let Some(conn) = conns.iter_mut().find(|c| c.connection_id == connection_id) {
+            conn.web = true;
+        }
+        Ok(())
+    }
+
+    fn check_user_limit(
+        &self,
+        conns: &mut Vec<UserConnection>,
+        user_id: &str,
+    ) -> Result<(), ConnectionError> {
+        // Closed channels belong to sockets that are gone but not yet cleaned up
+        conns.retain(|c| !c.tx.is_closed());
+        let open = conns.iter().filter(|c| c.user_id == user_id && c.web).count();
+        if open >= self.max_user_connections {
+            warn!(
+                "User {} hit the limit of {} connections",
+                user_id, self.max_user_connections
+            );
+            return Err(ConnectionError::TooManyConnections {
+                limit: self.max_user_connections,
+            });
+        }
+        Ok(())
+    }
+
     /// Unregister a user connection
-    pub async fn unregister_user(&self, user_id: &str) {
+    pub async fn unregister_user(&self, connection_id: &str) {
         let mut conns = self.user_connections.write().await;
-        conns.retain(|(id, _)| id != user_id);
-        info!("User unregistered from global broadcasts: {}", user_id);
+        if let Some(index) = conns.iter().position(|c| c.connection_id == connection_id) {
+            let conn = conns.remove(index);
+            info!("User unregistered from global broadcasts: {}", conn.user_id);
+        }
     }
 
     /// Broadcast a message to all connected users (for global updates like MachineList, SessionDeleted)
+    ///
+    /// Senders are unbounded, so a backed-up connection never holds up the rest.
     pub async fn broadcast_to_all_users(&self, msg: ServerMessage) {
         let conns = self.user_connections.read().await;
-        for (user_id, tx) in conns.iter() {
-            if let Err(_) = tx.send(msg.clone()) {
-                tracing::debug!("Failed to send to user {}", user_id);
+        for conn in conns.iter() {
+            if conn.tx.send(msg.clone()).is_err() {
+                tracing::debug!("Failed to send to user {}", conn.user_id);
             }
         }
     }
 
     /// Register CLI bridge connection for a session
-    pub async fn register_cli(&self, session_id: &str, tx: mpsc::UnboundedSender<ServerMessage>) {
+    pub async fn register_cli(
+        &self,
+        session_id: &str,
+        machine_id: &str,
+        user_id: &str,
+        connected_at: Instant,
+        tx: mpsc::UnboundedSender<ServerMessage>,
+    ) {
         let mut conns = self.cli_connections.write().await;
-        conns.insert(session_id.to_string(), tx);
+        conns.insert(
+            session_id.to_string(),
+            CliConnection {
+                tx,
+                machine_id: machine_id.to_string(),
+                user_id: user_id.to_string(),
+                connected_at,
+            },
+        );
         info!("CLI bridge registered for session {}", session_id);
     }
 
@@ -161,13 +533,63 @@ impl ConnectionManager {
         reqs.remove(request_id)
     }
 
+    /// Remember that remote session `request_id` replaces `from_session_id`
+    pub async fn register_pending_transfer(&self, request_id: &str, from_session_id: &str) {
+        let mut transfers = self.pending_transfers.write().await;
+        transfers.insert(request_id.to_string(), from_session_id.to_string());
+    }
+
+    /// The session that remote session `request_id` replaces, if it was a transfer
+    pub async fn take_pending_transfer(&self, request_id: &str) -> Option<String> {
+        let mut transfers = self.pending_transfers.write().await;
+        transfers.remove(request_id)
+    }
+
+    /// Move the web clients watching `from_session_id` over to `to`
+    ///
+    /// The clients are told with `SessionTransferred` and receive `to`'s output from
+    /// now on. Returns how many were moved.
+    pub async fn transfer_web(&self, from_session_id: &str, to: &Session) -> usize {
+        let mut conns = self.web_connections.write().await;
+        let Some(moved) = conns.remove(from_session_id) else {
+            return 0;
+        };
+        let msg = ServerMessage::SessionTransferred {
+            from_session_id: from_session_id.to_string(),
+            session: to.clone(),
+        };
+        let target = conns.entry(to.id.clone()).or_default();
+        let count = moved.len();
+        for conn in moved {
+            let _ = conn.tx.send(msg.clone());
+            if !target.iter().any(|c| c.connection_id == conn.connection_id) {
+                target.push(conn);
+            }
+        }
+        info!(
+            "Moved {} web clients from session {} to {}",
+            count, from_session_id, to.id
+        );
+        count
+    }
+
     /// Register web client connection for a session
-    pub async fn register_web(&self, session_id: &str, tx: mpsc::UnboundedSender<ServerMessage>) {
+    pub async fn register_web(
+        &self,
+        session_id: &str,
+        connection_id: &str,
+        tx: mpsc::UnboundedSender<ServerMessage>,
+    ) {
         let mut conns = self.web_connections.write().await;
-        conns
-            .entry(session_id.to_string())
-            .or_insert_with(Vec::new)
-            .push(tx);
+        let clients = conns.entry(session_id.to_string()).or_insert_with(Vec::new);
+        // Clients moved here by a transfer rejoin the session themselves
+        if clients.iter().any(|c| c.connection_id == connection_id) {
+            return;
+        }
+        clients.push(WebConnection {
+            connection_id: connection_id.to_string(),
+            tx,
+        });
         info!("Web client registered for session {}", session_id);
     }
 
@@ -181,8 +603,8 @@ impl ConnectionManager {
     /// Forward TerminalInput from web client to CLI bridge
     pub async fn forward_to_cli(&self, session_id: &str, msg: ServerMessage) {
         let conns = self.cli_connections.read().await;
-        if let Some(tx) = conns.get(session_id) {
-            let _ = tx.send(msg);
+        if let Some(cli) = conns.get(session_id) {
+            let _ = cli.tx.send(msg);
         }
     }
 
@@ -190,39 +612,188 @@ impl ConnectionManager {
     pub async fn broadcast_to_web(&self, session_id: &str, msg: ServerMessage) {
         let conns = self.web_connections.read().await;
         if let Some(clients) = conns.get(session_id) {
-            for tx in clients {
-                let _ = tx.send(msg.clone());
+            for client in clients {
+                let _ = client.tx.send(msg.clone());
             }
         }
     }
 
     pub async fn append_output(&self, session_id: &str, data: &[u8]) {
-        const MAX_BUFFER_BYTES: usize = 64 * 1024;
         let mut buffers = self.output_buffers.write().await;
-        let entry = buffers.entry(session_id.to_string()).or_default();
-        entry.extend_from_slice(data);
-        if entry.len() > MAX_BUFFER_BYTES {
-            let excess = entry.len() - MAX_BUFFER_BYTES;
-            entry.drain(0..excess);
-        }
+        let buffer = buffers
+            .entry(session_id.to_string())
+            .or_insert_with(OutputBuffer::new);
+        buffer.push(data, self.max_history_bytes);
+        self.warn_if_near_limit(session_id, buffer);
     }
 
     pub async fn set_output_buffer(&self, session_id: &str, data: Vec<u8>) {
-        const MAX_BUFFER_BYTES: usize = 64 * 1024;
         let mut buffers = self.output_buffers.write().await;
-        // Truncate if too large
-        let data = if data.len() > MAX_BUFFER_BYTES {
-            let excess = data.len() - MAX_BUFFER_BYTES;
-            data[excess..].to_vec()
-        } else {
-            data
+        // History replaces the stream, so offsets start over
+        let mut buffer = OutputBuffer::new();
+        buffer.push(&data, self.max_history_bytes);
+        self.warn_if_near_limit(session_id, &mut buffer);
+        buffers.insert(session_id.to_string(), buffer);
+    }
+
+    fn warn_if_near_limit(&self, session_id: &str, buffer: &mut OutputBuffer) {
+        if !buffer.near_limit_warned && buffer.data.len() * 5 >= self.max_history_bytes * 4 {
+            buffer.near_limit_warned = true;
+            warn!(
+                "Session {} history is at {} of its {} byte limit; older output will be dropped",
+                session_id,
+                buffer.data.len(),
+                self.max_history_bytes
+            );
+        }
+    }
+
+    /// Drop history for sessions that have had no output for `max_idle`
+    pub async fn prune_history(&self, max_idle: Duration) -> usize {
+        let mut buffers = self.output_buffers.write().await;
+        let before = buffers.len();
+        buffers.retain(|_, buffer| buffer.updated_at.elapsed() < max_idle);
+        before - buffers.len()
+    }
+
+    pub async fn history_stats(&self) -> HistoryStats {
+        let buffers = self.output_buffers.read().await;
+        HistoryStats {
+            total_bytes: buffers.values().map(|b| b.data.len()).sum(),
+            sessions: buffers.len(),
+        }
+    }
+
+    /// Close every open WebSocket, running the usual disconnect cleanup for each
+    pub fn close_all(&self) {
+        self.closing.send_replace(true);
+    }
+
+    /// Flips to true when `close_all` is called, for streams other than sockets
+    pub fn closing(&self) -> watch::Receiver<bool> {
+        self.closing.subscribe()
+    }
+
+    /// Copy out every live connection; each map is read under its own short lock
+    pub async fn snapshot(&self) -> ConnectionsSnapshot {
+        let cli_bridges: Vec<CliBridgeInfo> = self
+            .cli_connections
+            .read()
+            .await
+            .iter()
+            .map(|(session_id, cli)| CliBridgeInfo {
+                session_id: session_id.clone(),
+                machine_id: cli.machine_id.clone(),
+                user_id: cli.user_id.clone(),
+                connected_since: wall_clock(cli.connected_at),
+            })
+            .collect();
+
+        let mut sessions_per_connection: HashMap<String, usize> = HashMap::new();
+        for clients in self.web_connections.read().await.values() {
+            for client in clients {
+                *sessions_per_connection
+                    .entry(client.connection_id.clone())
+                    .or_default() += 1;
+            }
+        }
+
+        let (machines, daemon_connections) = {
+            let conns = self.machine_connections.read().await;
+            let machines: Vec<MachineConnectionInfo> = conns
+                .iter()
+                .map(|(machine_id, conns)| MachineConnectionInfo {
+                    machine_id: machine_id.clone(),
+                    name: None,
+                    connection_count: conns.len(),
+                })
+                .collect();
+            let daemon_connections: HashSet<String> =
+                conns.values().flat_map(|c| c.keys().cloned()).collect();
+            (machines, daemon_connections)
         };
-        buffers.insert(session_id.to_string(), data);
+
+        // Daemons authenticate too; everyone else on the user list is a web client
+        let web_clients = self
+            .user_connections
+            .read()
+            .await
+            .iter()
+            .filter(|c| !daemon_connections.contains(&c.connection_id))
+            .map(|c| WebClientInfo {
+                user_id: c.user_id.clone(),
+                session_count: sessions_per_connection
+                    .get(&c.connection_id)
+                    .copied()
+                    .unwrap_or(0),
+                connected_since: wall_clock(c.connected_at),
+            })
+            .collect();
+
+        let mut per_user: HashMap<String, usize> = HashMap::new();
+        for conn in self.user_connections.read().await.iter().filter(|c| c.web) {
+            *per_user.entry(conn.user_id.clone()).or_default() += 1;
+        }
+        let users = per_user
+            .into_iter()
+            .map(|(user_id, connection_count)| UserConnectionCount {
+                user_id,
+                connection_count,
+            })
+            .collect();
+
+        ConnectionsSnapshot {
+            cli_bridges,
+            web_clients,
+            machines,
+            users,
+            max_user_connections: self.max_user_connections,
+        }
+    }
+
+    pub async fn connection_counts(&self) -> ConnectionCounts {
+        let machines = self.machine_connections.read().await;
+        ConnectionCounts {
+            cli_bridges: machines.values().map(|conns| conns.len()).sum(),
+            web_clients: self.user_connections.read().await.len(),
+        }
+    }
+
+    /// Authenticated connections, i.e. the length of `user_connections`
+    pub async fn user_connection_count(&self) -> usize {
+        self.user_connections.read().await.len()
+    }
+
+    /// Sessions with a CLI bridge attached
+    pub async fn active_session_count(&self) -> usize {
+        self.cli_connections.read().await.len()
     }
 
-    pub async fn get_output_buffer(&self, session_id: &str) -> Option<Vec<u8>> {
+    /// The last `tail_bytes` of buffered output
+    pub async fn get_output_buffer(
+        &self,
+        session_id: &str,
+        tail_bytes: usize,
+    ) -> Option<HistorySlice> {
+        let buffers = self.output_buffers.read().await;
+        buffers.get(session_id).map(|buffer| {
+            let length = tail_bytes.min(self.max_history_bytes);
+            let offset = buffer.end_offset().saturating_sub(length as u64);
+            buffer.range(offset, length)
+        })
+    }
+
+    /// Up to `length` bytes of buffered output starting at stream `offset`
+    pub async fn get_history_range(
+        &self,
+        session_id: &str,
+        offset: u64,
+        length: usize,
+    ) -> Option<HistorySlice> {
         let buffers = self.output_buffers.read().await;
-        buffers.get(session_id).cloned()
+        buffers
+            .get(session_id)
+            .map(|buffer| buffer.range(offset, length.min(self.max_history_bytes)))
     }
 
     /// Check if a session has an active CLI bridge
@@ -235,8 +806,8 @@ impl ConnectionManager {
     pub async fn broadcast_to_all_web(&self, msg: ServerMessage) {
         let conns = self.web_connections.read().await;
         for (session_id, clients) in conns.iter() {
-            for tx in clients {
-                if let Err(_) = tx.send(msg.clone()) {
+            for client in clients {
+                if client.tx.send(msg.clone()).is_err() {
                     tracing::debug!("Failed to send to client in session {}", session_id);
                 }
             }
@@ -246,11 +817,9 @@ impl ConnectionManager {
     /// Send a message to all connections of a specific user
     pub async fn send_to_user(&self, user_id: &str, msg: ServerMessage) {
         let conns = self.user_connections.read().await;
-        for (id, tx) in conns.iter() {
-            if id == user_id {
-                if let Err(_) = tx.send(msg.clone()) {
-                    tracing::debug!("Failed to send to user {}", user_id);
-                }
+        for conn in conns.iter() {
+            if conn.user_id == user_id && conn.tx.send(msg.clone()).is_err() {
+                tracing::debug!("Failed to send to user {}", user_id);
             }
         }
     }
@@ -275,15 +844,116 @@ struct ClientState {
     machine_id: Option<String>,
     /// Machine name for daemon connections
     machine_name: Option<String>,
+    /// When the socket was opened
+    connected_at: Instant,
+    /// Client address at upgrade time, recorded on sessions this socket creates
+    remote_ip: String,
+    /// Terminal data goes out as binary frames; set by `Authenticate`
+    binary_frames: Arc<AtomicBool>,
+    /// Counted toward the user's connection limit as a web client
+    counted_as_web: bool,
+}
+
+/// Check `kind` against the sender's rate limit, telling the client when it can retry
+///
+/// Connections that haven't authenticated are limited by connection instead of user.
+fn rate_limited(
+    state: &AppState,
+    client_state: &ClientState,
+    kind: LimitedMessage,
+    tx: &mpsc::UnboundedSender<ServerMessage>,
+) -> bool {
+    let key = client_state
+        .user_id
+        .as_deref()
+        .unwrap_or(&client_state.connection_id);
+    let Err(retry_after) = state.ws_rate_limiter.check(key, kind) else {
+        return false;
+    };
+    let retry_after_secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    debug!("Rate limited {} from {}", kind.as_str(), key);
+    let _ = tx.send(ServerMessage::Error {
+        code: "rate_limited".to_string(),
+        message: format!(
+            "Too many {} messages, retry in {}s",
+            kind.as_str(),
+            retry_after_secs
+        ),
+    });
+    let _ = tx.send(ServerMessage::RateLimited {
+        message_type: kind.as_str().to_string(),
+        retry_after_secs,
+    });
+    true
+}
+
+/// Check an `AttachSession` signature against the key pinned for `machine_id`
+///
+/// Returns the key to pin when a valid signature comes from a machine with no
+/// key yet. Unsigned attaches are only accepted until a key is pinned.
+async fn verify_attach_signature(
+    state: &AppState,
+    session_id: &str,
+    machine_id: &str,
+    cwd: &str,
+    signing_key: Option<&str>,
+    signature: Option<&str>,
+) -> Result<Option<Vec<u8>>, (&'static str, &'static str)> {
+    use base64::Engine;
+    use happy_core::crypto::EncryptionEngine;
+
+    let pinned = state.machine_registry.signing_key(machine_id).await.map_err(|e| {
+        error!("Failed to read signing key for machine {}: {}", machine_id, e);
+        ("internal_error", "Failed to verify machine identity")
+    })?;
+
+    let (Some(signing_key), Some(signature)) = (signing_key, signature) else {
+        return match pinned {
+            Some(_) => Err(("signature_required", "This machine must sign its attach requests")),
+            None => Ok(None),
+        };
+    };
+
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let invalid = ("invalid_signature", "Attach request signature is invalid");
+    let key: [u8; 32] = b64
+        .decode(signing_key)
+        .ok()
+        .and_then(|k| k.try_into().ok())
+        .ok_or(invalid)?;
+    let signature: [u8; 64] = b64
+        .decode(signature)
+        .ok()
+        .and_then(|s| s.try_into().ok())
+        .ok_or(invalid)?;
+    let payload = happy_types::attach_signing_payload(session_id, machine_id, cwd);
+    happy_core::NaClEngine::new()
+        .verify(&payload, &signature, &key)
+        .map_err(|_| invalid)?;
+
+    match pinned {
+        Some(pinned) if pinned != key => Err((
+            "machine_key_mismatch",
+            "Signing key does not match this machine; remove the machine to re-register it",
+        )),
+        Some(_) => Ok(None),
+        None => Ok(Some(key.to_vec())),
+    }
 }
 
 /// Handle WebSocket upgrade
-pub async fn handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+pub async fn handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+) -> impl IntoResponse {
+    let remote_ip = ip.to_string();
+    ws.on_upgrade(move |socket| handle_socket(socket, state, remote_ip))
 }
 
-async fn handle_socket(socket: WebSocket, state: AppState) {
-    info!("New WebSocket connection");
+async fn handle_socket(socket: WebSocket, state: AppState, remote_ip: String) {
+    ws_log!(Normal, info, "New WebSocket connection");
+    crate::metrics::global().ws_connection_opened();
 
     let (mut sender, mut receiver) = socket.split();
     let mut client_state = ClientState {
@@ -294,34 +964,59 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
         connection_id: Uuid::new_v4().to_string(),
         machine_id: None,
         machine_name: None,
+        connected_at: Instant::now(),
+        remote_ip,
+        binary_frames: Arc::new(AtomicBool::new(false)),
+        counted_as_web: false,
     };
 
     // Create channel for sending messages to this client
     let (tx, mut rx) = mpsc::unbounded_channel::<ServerMessage>();
 
     // Spawn task to forward messages from channel to WebSocket
+    let binary_frames = client_state.binary_frames.clone();
     let _forward_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&msg) {
-                if sender.send(Message::Text(json)).await.is_err() {
-                    break;
-                }
+            let binary = if binary_frames.load(Ordering::Relaxed) {
+                happy_types::wire::encode_binary(&msg)
+            } else {
+                None
+            };
+            let frame = match binary {
+                Some(bytes) => Message::Binary(bytes),
+                None => match serde_json::to_string(&msg) {
+                    Ok(json) => Message::Text(json),
+                    Err(_) => continue,
+                },
+            };
+            if sender.send(frame).await.is_err() {
+                break;
             }
         }
     });
 
+    let mut closing = state.conn_manager.closing.subscribe();
+
     // Handle incoming messages
-    while let Some(msg) = receiver.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => msg,
+            _ = closing.wait_for(|closing| *closing) => {
+                ws_log!(Normal, info, "Closing WebSocket for server shutdown");
+                break;
+            }
+        };
+        let Some(msg) = msg else {
+            break;
+        };
+
         match msg {
             Ok(Message::Text(text)) => {
-                debug!("Received text message: {}", text);
-                // Log the first 100 chars of message for debugging input issues
-                let preview = if text.len() > 100 {
-                    &text[0..100]
-                } else {
-                    &text
-                };
-                info!("WS Input: {}", preview);
+                if WsLogLevel::current() == WsLogLevel::Trace {
+                    info!("WS Input: {}", text);
+                } else if let Some(preview) = verbose_preview(&text) {
+                    ws_log!(Verbose, info, "WS Input: {}", preview);
+                }
 
                 match serde_json::from_str::<ClientMessage>(&text) {
                     Ok(client_msg) => {
@@ -332,7 +1027,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                         }
                     }
                     Err(e) => {
-                        warn!("Failed to parse message: {}", e);
+                        ws_log!(Normal, warn, "Failed to parse message: {}", e);
                         let error = ServerMessage::Error {
                             code: "invalid_message".to_string(),
                             message: format!("Failed to parse message: {}", e),
@@ -342,7 +1037,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                 }
             }
             Ok(Message::Binary(bin)) => {
-                debug!("Received binary message: {} bytes", bin.len());
+                ws_log!(Trace, info, "WS Binary Input ({} bytes): {:02x?}", bin.len(), bin);
                 // TODO: Handle encrypted binary messages
             }
             Ok(Message::Ping(_)) => {
@@ -352,11 +1047,11 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                 // Axum handles pongs automatically
             }
             Ok(Message::Close(_)) => {
-                info!("WebSocket connection closed");
+                ws_log!(Normal, info, "WebSocket connection closed");
                 break;
             }
             Err(e) => {
-                error!("WebSocket error: {}", e);
+                ws_log!(Normal, error, "WebSocket error: {}", e);
                 break;
             }
         }
@@ -380,6 +1075,9 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                     "Session {} marked as terminated due to CLI disconnect",
                     session_id
                 );
+                if let Ok(Some(session)) = state.session_manager.get_session(session_id).await {
+                    state.session_updates.queue(session).await;
+                }
             }
             // Broadcast update to web clients so dashboard refreshes live
             let _ = state
@@ -413,8 +1111,11 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     }
 
     // Unregister user connection
-    if let Some(user_id) = &client_state.user_id {
-        state.conn_manager.unregister_user(user_id).await;
+    if client_state.user_id.is_some() {
+        state
+            .conn_manager
+            .unregister_user(&client_state.connection_id)
+            .await;
     }
 
     // Do NOT abort the forward_task immediately.
@@ -423,7 +1124,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     // because `rx.recv()` will return `None`.
     // forward_task.abort();
 
-    info!("WebSocket connection ended");
+    ws_log!(Normal, info, "WebSocket connection ended");
 }
 
 /// Handle a client message
@@ -434,22 +1135,64 @@ async fn handle_message(
     client_state: &mut ClientState,
     tx: &mpsc::UnboundedSender<ServerMessage>,
 ) -> bool {
+    // The limit is on web clients; a socket that attaches as a CLI bridge first never counts
+    if client_state.user_id.is_some()
+        && !client_state.is_cli_bridge
+        && !client_state.counted_as_web
+        && !matches!(
+            msg,
+            ClientMessage::Ping
+                | ClientMessage::Authenticate { .. }
+                | ClientMessage::AttachSession { .. }
+        )
+    {
+        let counted = state.conn_manager.count_as_web(&client_state.connection_id).await;
+        if let Err(ConnectionError::TooManyConnections { limit }) = counted {
+            let _ = tx.send(ServerMessage::Error {
+                code: "too_many_connections".to_string(),
+                message: format!(
+                    "Too many open connections (limit {}); close other tabs",
+                    limit
+                ),
+            });
+            return false;
+        }
+        client_state.counted_as_web = true;
+    }
+
     match msg {
         ClientMessage::Ping => {
             let _ = tx.send(ServerMessage::Pong);
         }
-        ClientMessage::Authenticate { token } => {
+        ClientMessage::Authenticate {
+            token,
+            binary_frames,
+        } => {
             // Validate JWT token
             match state.auth_service.validate_token(&token).await {
                 Ok(user_id) => {
+                    // Register this connection for global broadcasts
+                    state
+                        .conn_manager
+                        .register_connection(
+                            &user_id,
+                            &client_state.connection_id,
+                            client_state.connected_at,
+                            tx.clone(),
+                        )
+                        .await;
                     client_state.user_id = Some(user_id.clone());
+                    client_state.binary_frames.store(binary_frames, Ordering::Relaxed);
                     info!("User authenticated: {}", user_id);
-                    // Register this connection for global broadcasts
-                    state.conn_manager.register_user(&user_id, tx.clone()).await;
                     let _ = tx.send(ServerMessage::Authenticated {
                         user_id: user_id.clone(),
                     });
 
+                    let _ = tx.send(ServerMessage::Welcome {
+                        server_version: env!("CARGO_PKG_VERSION").to_string(),
+                        daemon_version: online_daemon_version(state, &user_id).await,
+                    });
+
                     // Send current machine list immediately so frontend knows which machines are online
                     send_machine_list_to_user(state, &user_id, tx.clone()).await;
                 }
@@ -463,7 +1206,7 @@ async fn handle_message(
                 }
             }
         }
-        ClientMessage::JoinSession { tag } => {
+        ClientMessage::JoinSession { tag, tail_bytes } => {
             info!(
                 "JoinSession request: tag={}, user_id={:?}",
                 tag, client_state.user_id
@@ -485,7 +1228,7 @@ async fn handle_message(
                         if is_new {
                             state
                                 .conn_manager
-                                .register_web(&session_id, tx.clone())
+                                .register_web(&session_id, &client_state.connection_id, tx.clone())
                                 .await;
                             info!("Registered web client for session {}", session_id);
                         } else {
@@ -499,18 +1242,28 @@ async fn handle_message(
                         let _ = tx.send(ServerMessage::TerminalReady {
                             session_id: session_id.clone(),
                         });
-                        if let Some(buffer) =
-                            state.conn_manager.get_output_buffer(&session.id).await
+                        let tail_bytes = tail_bytes.unwrap_or(DEFAULT_HISTORY_TAIL_BYTES);
+                        if let Some(history) = state
+                            .conn_manager
+                            .get_output_buffer(&session.id, tail_bytes)
+                            .await
                         {
                             info!(
-                                "Sending buffered output for session {}: {} bytes",
+                                "Sending buffered output for session {}: {} bytes from offset {}",
                                 session.id,
-                                buffer.len()
+                                history.data.len(),
+                                history.offset
                             );
-                            if !buffer.is_empty() {
+                            if !history.data.is_empty() {
                                 let _ = tx.send(ServerMessage::TerminalOutput {
                                     session_id: session.id.clone(),
-                                    data: buffer,
+                                    data: history.data,
+                                });
+                            }
+                            if history.has_more {
+                                let _ = tx.send(ServerMessage::HistoryTruncated {
+                                    session_id: session.id.clone(),
+                                    offset: history.offset,
                                 });
                             }
                         }
@@ -544,11 +1297,45 @@ async fn handle_message(
             cwd,
             machine_id,
             machine_name,
+            agent_version,
+            home_dir,
+            machine_info,
+            capabilities,
+            signing_key,
+            signature,
         } => {
-            info!("AttachSession request: session_id={}, tag={}, cwd={}, machine_id={:?}, machine_name={:?}, user_id={:?}",
-                session_id, tag, cwd, machine_id, machine_name, client_state.user_id);
+            info!("AttachSession request: session_id={}, tag={}, cwd={}, machine_id={:?}, machine_name={:?}, agent_version={:?}, user_id={:?}",
+                session_id, tag, cwd, machine_id, machine_name, agent_version, client_state.user_id);
             // CLI daemon uses AttachSession to register as the bridge
             if let Some(user_id) = &client_state.user_id {
+                // Prove the daemon is the machine it claims to be, when it signs
+                let mut key_to_pin = None;
+                if let Some(ref remote_machine_id) = machine_id {
+                    match verify_attach_signature(
+                        state,
+                        &session_id,
+                        remote_machine_id,
+                        &cwd,
+                        signing_key.as_deref(),
+                        signature.as_deref(),
+                    )
+                    .await
+                    {
+                        Ok(key) => key_to_pin = key,
+                        Err((code, message)) => {
+                            warn!(
+                                "AttachSession rejected for machine {}: {}",
+                                remote_machine_id, message
+                            );
+                            let _ = tx.send(ServerMessage::Error {
+                                code: code.to_string(),
+                                message: message.to_string(),
+                            });
+                            return true;
+                        }
+                    }
+                }
+
                 // Check if session exists
                 let session = match state.session_manager.get_session(&session_id).await {
                     Ok(Some(session)) if session.user_id == *user_id => {
@@ -560,19 +1347,39 @@ async fn handle_message(
                                 .clone()
                                 .unwrap_or_else(|| "Unknown Machine".to_string());
 
+                            let platform = machine_info
+                                .as_ref()
+                                .map(|info| info.platform)
+                                .unwrap_or_else(happy_core::Platform::current);
+
                             // Sync machine with registry
                             if let Err(e) = state
                                 .machine_registry
-                                .register_machine(
-                                    user_id,
-                                    remote_machine_id,
-                                    &name,
-                                    happy_core::Platform::current(),
-                                )
+                                .register_machine(user_id, remote_machine_id, &name, platform)
                                 .await
                             {
                                 error!("Failed to register machine in registry: {}", e);
                             } else {
+                                if let Some(key) = key_to_pin.take() {
+                                    if let Err(e) = state
+                                        .machine_registry
+                                        .pin_signing_key(remote_machine_id, &key)
+                                        .await
+                                    {
+                                        warn!("Failed to pin machine signing key: {}", e);
+                                    }
+                                }
+                                if let Some(mut info) = machine_info.clone() {
+                                    info.id = remote_machine_id.clone();
+                                    if !capabilities.is_empty() {
+                                        info.capabilities = capabilities.clone();
+                                    }
+                                    if let Err(e) =
+                                        state.machine_registry.update_machine_info(&info).await
+                                    {
+                                        warn!("Failed to update machine info: {}", e);
+                                    }
+                                }
                                 // Broadcast updated machine list to web clients
                                 broadcast_machine_list(&state, user_id).await;
                             }
@@ -686,7 +1493,33 @@ async fn handle_message(
                 };
 
                 // If we have a session (existing or newly created), proceed with attachment
-                if let Some(session) = session {
+                if let Some(mut session) = session {
+                    // Record the agent version reported by the CLI
+                    if let Some(version) = agent_version {
+                        if session.metadata.agent_version.as_ref() != Some(&version) {
+                            match state
+                                .session_manager
+                                .update_session_agent_version(&session_id, &version)
+                                .await
+                            {
+                                Ok(_) => session.metadata.agent_version = Some(version),
+                                Err(e) => warn!("Failed to update session agent version: {}", e),
+                            }
+                        }
+                    }
+                    if let Some(home_dir) = home_dir {
+                        if session.metadata.home_dir.as_ref() != Some(&home_dir) {
+                            match state
+                                .session_manager
+                                .update_session_home_dir(&session_id, &home_dir)
+                                .await
+                            {
+                                Ok(_) => session.metadata.home_dir = Some(home_dir),
+                                Err(e) => warn!("Failed to update session home dir: {}", e),
+                            }
+                        }
+                    }
+
                     client_state.session_id = Some(session_id.clone());
                     client_state.is_cli_bridge = true;
                     client_state.machine_id = Some(session.machine_id.clone());
@@ -695,7 +1528,13 @@ async fn handle_message(
                     // Register as CLI bridge
                     state
                         .conn_manager
-                        .register_cli(&session_id, tx.clone())
+                        .register_cli(
+                            &session_id,
+                            &session.machine_id,
+                            user_id,
+                            client_state.connected_at,
+                            tx.clone(),
+                        )
                         .await;
 
                     // Register machine connection for remote session creation
@@ -711,11 +1550,16 @@ async fn handle_message(
                     // Broadcast updated machine list to all clients
                     broadcast_machine_list(state, user_id).await;
 
-                    // Update session status to Running
-                    let _ = state
+                    // Update session status to Running and let every client know
+                    if state
                         .session_manager
                         .update_session_status(&session_id, SessionStatus::Running)
-                        .await;
+                        .await
+                        .is_ok()
+                    {
+                        session.status = SessionStatus::Running;
+                        state.session_updates.queue(session.clone()).await;
+                    }
 
                     info!(
                         "CLI bridge attached to session {} (tag={}, machine={})",
@@ -733,6 +1577,9 @@ async fn handle_message(
             }
         }
         ClientMessage::TerminalInput { session_id, data } => {
+            if rate_limited(state, client_state, LimitedMessage::TerminalInput, tx) {
+                return true;
+            }
             info!(
                 "Received TerminalInput for session {} ({} bytes), client_sessions: {:?}",
                 session_id,
@@ -756,8 +1603,9 @@ async fn handle_message(
 
                 // Forward to CLI bridge
                 let conns = state.conn_manager.cli_connections.read().await;
-                if let Some(cli_tx) = conns.get(&session_id) {
+                if let Some(cli) = conns.get(&session_id) {
                     info!("Forwarding input to CLI bridge for session {}", session_id);
+                    crate::metrics::global().terminal_bytes_out(&cli.machine_id, data.len());
                     // Serialize the ClientMessage and send as a special wrapper
                     // CLI bridge will parse the JSON and handle it
                     let forward_msg = ClientMessage::TerminalInput {
@@ -766,7 +1614,7 @@ async fn handle_message(
                     };
                     if let Ok(json) = serde_json::to_string(&forward_msg) {
                         // Send as a custom ServerMessage that CLI can parse
-                        let _ = cli_tx.send(ServerMessage::TerminalOutput {
+                        let _ = cli.tx.send(ServerMessage::TerminalOutput {
                             session_id: session_id.clone(),
                             data: json.into_bytes(),
                         });
@@ -817,8 +1665,8 @@ async fn handle_message(
                             session_id: session_id.clone(),
                             data: data.clone(),
                         };
-                        for tx in clients {
-                            let _ = tx.send(msg.clone());
+                        for client in clients {
+                            let _ = client.tx.send(msg.clone());
                         }
                     }
                 } else {
@@ -835,6 +1683,9 @@ async fn handle_message(
             // Strict validation: must be CLI bridge AND session_id matches
             if client_state.is_cli_bridge {
                 if client_state.session_id.as_ref() == Some(&session_id) {
+                    if let Some(machine_id) = &client_state.machine_id {
+                        crate::metrics::global().terminal_bytes_in(machine_id, data.len());
+                    }
                     state.conn_manager.append_output(&session_id, &data).await;
                     let conns = state.conn_manager.web_connections.read().await;
                     info!(
@@ -847,8 +1698,8 @@ async fn handle_message(
                             session_id: session_id.clone(),
                             data: data.clone(),
                         };
-                        for tx in clients {
-                            let _ = tx.send(msg.clone());
+                        for client in clients {
+                            let _ = client.tx.send(msg.clone());
                         }
                         info!("Sent TerminalOutput to {} web clients", clients.len());
                     }
@@ -885,18 +1736,52 @@ async fn handle_message(
             );
             // Forward to CLI bridge
             if client_state.session_ids.contains(&session_id) {
-                let conns = state.conn_manager.cli_connections.read().await;
-                if let Some(_cli_tx) = conns.get(&session_id) {
-                    // Send resize to CLI - we'd need a message type for this
-                    // For now, just log it
-                    debug!("Forwarding resize to CLI bridge");
-                }
+                state
+                    .conn_manager
+                    .forward_to_cli(
+                        &session_id,
+                        ServerMessage::TerminalResize {
+                            session_id: session_id.clone(),
+                            cols,
+                            rows,
+                        },
+                    )
+                    .await;
             }
         }
-        ClientMessage::ListSessions => {
-            if let Some(user_id) = &client_state.user_id {
-                match state.session_manager.list_user_sessions(user_id).await {
-                    Ok(sessions) => {
+        ClientMessage::ListSessions {
+            machine_id,
+            limit,
+            offset,
+            cursor,
+        } => {
+            if let Some(user_id) = &client_state.user_id {
+                let sessions = match (machine_id, limit) {
+                    (Some(machine_id), _) => state
+                        .session_manager
+                        .list_sessions_by_machine(user_id, &machine_id, limit, offset.unwrap_or(0))
+                        .await
+                        .map(|sessions| (sessions, None)),
+                    // Keyset paging stays fast however deep the client scrolls
+                    (None, Some(limit)) => state
+                        .session_manager
+                        .list_user_sessions_after(user_id, cursor, limit)
+                        .await
+                        .map(|sessions| {
+                            let next_cursor = sessions
+                                .last()
+                                .filter(|_| sessions.len() == limit as usize)
+                                .map(|s| s.id.clone());
+                            (sessions, next_cursor)
+                        }),
+                    (None, None) => state
+                        .session_manager
+                        .list_user_sessions(user_id)
+                        .await
+                        .map(|sessions| (sessions, None)),
+                };
+                match sessions {
+                    Ok((sessions, next_cursor)) => {
                         info!(
                             "ListSessions: returning {} sessions for user {}",
                             sessions.len(),
@@ -908,7 +1793,10 @@ async fn handle_message(
                                 s.id, s.tag, s.status
                             );
                         }
-                        let _ = tx.send(ServerMessage::SessionsList { sessions });
+                        let _ = tx.send(ServerMessage::SessionsList {
+                            sessions,
+                            next_cursor,
+                        });
                     }
                     Err(e) => {
                         error!("Failed to list sessions: {}", e);
@@ -932,7 +1820,14 @@ async fn handle_message(
                 let machine_name = "Web Client".to_string();
                 match state
                     .session_manager
-                    .create_session(user_id, &machine_id, &machine_name, &tag, "/")
+                    .create_session(
+                        user_id,
+                        &machine_id,
+                        &machine_name,
+                        &tag,
+                        "/",
+                        Some(&client_state.remote_ip),
+                    )
                     .await
                 {
                     Ok(session) => {
@@ -955,9 +1850,18 @@ async fn handle_message(
             }
         }
         ClientMessage::StopSession { session_id } => {
-            if let Some(_user_id) = &client_state.user_id {
-                match state.session_manager.terminate_session(&session_id).await {
+            if let Some(user_id) = &client_state.user_id {
+                match state
+                    .session_manager
+                    .terminate_session(&session_id, user_id, Some(&client_state.remote_ip))
+                    .await
+                {
                     Ok(_) => {
+                        if let Ok(Some(session)) =
+                            state.session_manager.get_session(&session_id).await
+                        {
+                            state.session_updates.queue(session).await;
+                        }
                         let msg = ServerMessage::SessionStopped {
                             session_id: session_id.clone(),
                         };
@@ -982,48 +1886,27 @@ async fn handle_message(
             }
         }
         ClientMessage::DeleteSession { session_id } => {
+            change_archive_state(state, client_state, &session_id, ArchiveChange::Delete, tx).await;
+        }
+        ClientMessage::ArchiveSession { session_id } => {
+            change_archive_state(state, client_state, &session_id, ArchiveChange::Archive, tx)
+                .await;
+        }
+        ClientMessage::RestoreSession { session_id } => {
+            change_archive_state(state, client_state, &session_id, ArchiveChange::Restore, tx)
+                .await;
+        }
+        ClientMessage::ListArchivedSessions => {
             if let Some(user_id) = &client_state.user_id {
-                // Verify ownership first
-                match state.session_manager.get_session(&session_id).await {
-                    Ok(Some(session)) if session.user_id == *user_id => {
-                        match state.session_manager.remove_session(&session_id).await {
-                            Ok(_) => {
-                                info!("Session deleted: {}", session_id);
-                                // Broadcast to all connected users
-                                let msg = ServerMessage::SessionDeleted {
-                                    session_id: session_id.clone(),
-                                };
-                                // Notify all users (for UI updates)
-                                state.conn_manager.broadcast_to_all_users(msg.clone()).await;
-                                // Notify the specific CLI bridge to cleanup
-                                state.conn_manager.forward_to_cli(&session_id, msg).await;
-                            }
-                            Err(e) => {
-                                error!("Failed to delete session: {}", e);
-                                let _ = tx.send(ServerMessage::Error {
-                                    code: "delete_failed".to_string(),
-                                    message: "Failed to delete session".to_string(),
-                                });
-                            }
-                        }
-                    }
-                    Ok(Some(_)) => {
-                        let _ = tx.send(ServerMessage::Error {
-                            code: "access_denied".to_string(),
-                            message: "Session belongs to another user".to_string(),
-                        });
-                    }
-                    Ok(None) => {
-                        let _ = tx.send(ServerMessage::Error {
-                            code: "not_found".to_string(),
-                            message: "Session not found".to_string(),
-                        });
+                match state.session_manager.list_archived_sessions(user_id).await {
+                    Ok(sessions) => {
+                        let _ = tx.send(ServerMessage::ArchivedSessionsList { sessions });
                     }
                     Err(e) => {
-                        error!("Failed to get session: {}", e);
+                        error!("Failed to list archived sessions: {}", e);
                         let _ = tx.send(ServerMessage::Error {
-                            code: "error".to_string(),
-                            message: format!("Error: {}", e).to_string(),
+                            code: "list_failed".to_string(),
+                            message: "Failed to retrieve archived sessions".to_string(),
                         });
                     }
                 }
@@ -1045,6 +1928,39 @@ async fn handle_message(
                 state.conn_manager.unregister_web(&session_id).await;
             }
         }
+        ClientMessage::FetchHistoryRange {
+            session_id,
+            offset,
+            length,
+        } => {
+            if client_state.session_ids.contains(&session_id) {
+                let history = state
+                    .conn_manager
+                    .get_history_range(&session_id, offset, length)
+                    .await;
+                match history {
+                    Some(history) => {
+                        let _ = tx.send(ServerMessage::HistoryRange {
+                            session_id,
+                            offset: history.offset,
+                            data: history.data,
+                            has_more: history.has_more,
+                        });
+                    }
+                    None => {
+                        let _ = tx.send(ServerMessage::Error {
+                            code: "history_unavailable".to_string(),
+                            message: format!("No history buffered for session {}", session_id),
+                        });
+                    }
+                }
+            } else {
+                let _ = tx.send(ServerMessage::Error {
+                    code: "session_mismatch".to_string(),
+                    message: format!("You are not connected to session {}", session_id),
+                });
+            }
+        }
         ClientMessage::ListFiles { session_id, path } => {
             debug!("List files request: session={}, path={}", session_id, path);
             // TODO: Implement file listing
@@ -1057,6 +1973,8 @@ async fn handle_message(
             session_id,
             path,
             content,
+            content_type,
+            encoding,
         } => {
             debug!(
                 "Write file request: session={}, path={}, {} bytes",
@@ -1064,7 +1982,67 @@ async fn handle_message(
                 path,
                 content.len()
             );
-            // TODO: Implement file writing
+            if !client_state.session_ids.contains(&session_id) {
+                let _ = tx.send(ServerMessage::Error {
+                    code: "session_mismatch".to_string(),
+                    message: format!("You are not connected to session {}", session_id),
+                });
+            } else if !state.conn_manager.has_cli(&session_id).await {
+                let _ = tx.send(ServerMessage::Error {
+                    code: "no_cli".to_string(),
+                    message: "No CLI bridge connected for this session".to_string(),
+                });
+            } else {
+                // The daemon's answer goes back to this client only
+                let request_id = Uuid::new_v4().to_string();
+                state
+                    .conn_manager
+                    .register_pending_request(&request_id, tx.clone())
+                    .await;
+                let msg = ServerMessage::WriteFileRequest {
+                    session_id: session_id.clone(),
+                    path: path.clone(),
+                    content,
+                    content_type,
+                    encoding,
+                    request_id: request_id.clone(),
+                };
+                state.conn_manager.forward_to_cli(&session_id, msg).await;
+
+                let conn_manager = state.conn_manager.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(FILE_WRITE_TIMEOUT).await;
+                    if let Some(client_tx) = conn_manager.take_pending_request(&request_id).await {
+                        let _ = client_tx.send(ServerMessage::FileWriteResult {
+                            session_id,
+                            path,
+                            success: false,
+                            bytes_written: 0,
+                            error: Some("The daemon did not answer in time".to_string()),
+                        });
+                    }
+                });
+            }
+        }
+        ClientMessage::FileWriteResponse {
+            session_id,
+            path,
+            success,
+            bytes_written,
+            request_id,
+            error,
+        } => {
+            if client_state.is_cli_bridge {
+                if let Some(client_tx) = state.conn_manager.take_pending_request(&request_id).await {
+                    let _ = client_tx.send(ServerMessage::FileWriteResult {
+                        session_id,
+                        path,
+                        success,
+                        bytes_written,
+                        error,
+                    });
+                }
+            }
         }
         ClientMessage::RegisterMachine { name, public_key } => {
             info!(
@@ -1089,6 +2067,9 @@ async fn handle_message(
             cwd,
             args,
         } => {
+            if rate_limited(state, client_state, LimitedMessage::RemoteSession, tx) {
+                return true;
+            }
             info!(
                 "RequestRemoteSession: machine_id={}, cwd={:?}, args={:?}",
                 machine_id, cwd, args
@@ -1111,6 +2092,7 @@ async fn handle_message(
                     machine_id: machine_id.clone(),
                     cwd,
                     args,
+                    history: Vec::new(),
                 };
 
                 if machine_tx.send(request).is_err() {
@@ -1134,6 +2116,26 @@ async fn handle_message(
                 });
             }
         }
+        ClientMessage::TransferSession {
+            session_id,
+            target_machine_id,
+        } => {
+            if rate_limited(state, client_state, LimitedMessage::RemoteSession, tx) {
+                return true;
+            }
+            info!(
+                "TransferSession: session_id={}, target_machine_id={}",
+                session_id, target_machine_id
+            );
+            if let Some(user_id) = &client_state.user_id {
+                start_transfer(state, user_id, &session_id, &target_machine_id, tx).await;
+            } else {
+                let _ = tx.send(ServerMessage::Error {
+                    code: "not_authenticated".to_string(),
+                    message: "Please authenticate first".to_string(),
+                });
+            }
+        }
         ClientMessage::RemoteSessionResult {
             request_id,
             success,
@@ -1144,6 +2146,9 @@ async fn handle_message(
                 "RemoteSessionResult: request_id={}, success={}",
                 request_id, success
             );
+            let transfer_from = state.conn_manager.take_pending_transfer(&request_id).await;
+            // A retried request answers with the session the first attempt created
+            let mut retried = None;
 
             if success {
                 if let Some(ref session_info) = session {
@@ -1174,10 +2179,19 @@ async fn handle_message(
                             &session_info.tag,
                             &session_info.id,
                             &session_info.metadata.cwd,
+                            Some(&request_id),
+                            Some(&client_state.remote_ip),
                         )
                         .await
                     {
-                        Ok(saved_session) => {
+                        Ok((saved_session, false)) => {
+                            info!(
+                                "Remote session result for request {} is a retry, keeping session {}",
+                                request_id, saved_session.id
+                            );
+                            retried = Some(saved_session);
+                        }
+                        Ok((saved_session, true)) => {
                             info!("Remote session saved to database: id={}, tag={}, machine={}, cwd={}",
                                 saved_session.id, saved_session.tag, saved_session.machine_name, saved_session.metadata.cwd);
 
@@ -1201,9 +2215,17 @@ async fn handle_message(
                             state
                                 .conn_manager
                                 .broadcast_to_all_users(ServerMessage::SessionStarted {
-                                    session: running_session,
+                                    session: running_session.clone(),
                                 })
                                 .await;
+
+                            // The new session replaces a transferred one; move its viewers
+                            if let Some(from_session_id) = transfer_from {
+                                state
+                                    .conn_manager
+                                    .transfer_web(&from_session_id, &running_session)
+                                    .await;
+                            }
                         }
                         Err(e) => {
                             error!("Failed to save remote session to database: {}", e);
@@ -1217,7 +2239,7 @@ async fn handle_message(
                 let response = ServerMessage::RemoteSessionResponse {
                     request_id,
                     success,
-                    session,
+                    session: retried.or(session),
                     error,
                 };
                 let _ = client_tx.send(response);
@@ -1310,6 +2332,115 @@ async fn handle_message(
             }
         }
 
+        ClientMessage::GitStageAll { session_id } => {
+            if client_state.user_id.is_some() {
+                if state.conn_manager.has_cli(&session_id).await {
+                    let msg = ServerMessage::GitStageAllRequest {
+                        session_id: session_id.clone(),
+                        requester_id: client_state.connection_id.clone(),
+                    };
+                    state.conn_manager.forward_to_cli(&session_id, msg).await;
+                } else {
+                    let _ = tx.send(ServerMessage::Error {
+                        code: "no_cli".to_string(),
+                        message: "No CLI bridge connected for this session".to_string(),
+                    });
+                }
+            }
+        }
+        ClientMessage::GitUnstageAll { session_id } => {
+            if client_state.user_id.is_some() {
+                if state.conn_manager.has_cli(&session_id).await {
+                    let msg = ServerMessage::GitUnstageAllRequest {
+                        session_id: session_id.clone(),
+                        requester_id: client_state.connection_id.clone(),
+                    };
+                    state.conn_manager.forward_to_cli(&session_id, msg).await;
+                } else {
+                    let _ = tx.send(ServerMessage::Error {
+                        code: "no_cli".to_string(),
+                        message: "No CLI bridge connected for this session".to_string(),
+                    });
+                }
+            }
+        }
+        ClientMessage::StartRecording { session_id } => {
+            if client_state.user_id.is_some() {
+                if state.conn_manager.has_cli(&session_id).await {
+                    let msg = ServerMessage::StartRecordingRequest {
+                        session_id: session_id.clone(),
+                        requester_id: client_state.connection_id.clone(),
+                    };
+                    state.conn_manager.forward_to_cli(&session_id, msg).await;
+                } else {
+                    let _ = tx.send(ServerMessage::Error {
+                        code: "no_cli".to_string(),
+                        message: "No CLI bridge connected for this session".to_string(),
+                    });
+                }
+            }
+        }
+        ClientMessage::StopRecording { session_id } => {
+            if client_state.user_id.is_some() {
+                if state.conn_manager.has_cli(&session_id).await {
+                    let msg = ServerMessage::StopRecordingRequest {
+                        session_id: session_id.clone(),
+                        requester_id: client_state.connection_id.clone(),
+                    };
+                    state.conn_manager.forward_to_cli(&session_id, msg).await;
+                } else {
+                    let _ = tx.send(ServerMessage::Error {
+                        code: "no_cli".to_string(),
+                        message: "No CLI bridge connected for this session".to_string(),
+                    });
+                }
+            }
+        }
+        ClientMessage::GitPush {
+            session_id,
+            remote,
+            branch,
+        } => {
+            if client_state.user_id.is_some() {
+                if state.conn_manager.has_cli(&session_id).await {
+                    let msg = ServerMessage::GitPushRequest {
+                        session_id: session_id.clone(),
+                        remote,
+                        branch,
+                        requester_id: client_state.connection_id.clone(),
+                    };
+                    state.conn_manager.forward_to_cli(&session_id, msg).await;
+                } else {
+                    let _ = tx.send(ServerMessage::Error {
+                        code: "no_cli".to_string(),
+                        message: "No CLI bridge connected for this session".to_string(),
+                    });
+                }
+            }
+        }
+        ClientMessage::GitPull {
+            session_id,
+            remote,
+            branch,
+        } => {
+            if client_state.user_id.is_some() {
+                if state.conn_manager.has_cli(&session_id).await {
+                    let msg = ServerMessage::GitPullRequest {
+                        session_id: session_id.clone(),
+                        remote,
+                        branch,
+                        requester_id: client_state.connection_id.clone(),
+                    };
+                    state.conn_manager.forward_to_cli(&session_id, msg).await;
+                } else {
+                    let _ = tx.send(ServerMessage::Error {
+                        code: "no_cli".to_string(),
+                        message: "No CLI bridge connected for this session".to_string(),
+                    });
+                }
+            }
+        }
+
         // Git operation responses from CLI daemon - forward to web clients
         ClientMessage::GitStatusResponse {
             session_id,
@@ -1362,13 +2493,107 @@ async fn handle_message(
                 state.conn_manager.broadcast_to_web(&session_id_clone, msg).await;
             }
         }
+        ClientMessage::GitStageAllResponse {
+            session_id,
+            success,
+            message,
+        } => {
+            if client_state.is_cli_bridge {
+                let session_id_clone = session_id.clone();
+                let msg = ServerMessage::GitStageAllResult {
+                    session_id,
+                    success,
+                    message,
+                };
+                state.conn_manager.broadcast_to_web(&session_id_clone, msg).await;
+            }
+        }
+        ClientMessage::GitUnstageAllResponse {
+            session_id,
+            success,
+            message,
+        } => {
+            if client_state.is_cli_bridge {
+                let session_id_clone = session_id.clone();
+                let msg = ServerMessage::GitUnstageAllResult {
+                    session_id,
+                    success,
+                    message,
+                };
+                state.conn_manager.broadcast_to_web(&session_id_clone, msg).await;
+            }
+        }
+        ClientMessage::GitPushResponse {
+            session_id,
+            success,
+            message,
+            conflicts,
+        } => {
+            if client_state.is_cli_bridge {
+                let session_id_clone = session_id.clone();
+                let msg = ServerMessage::GitPushResult {
+                    session_id,
+                    success,
+                    message,
+                    conflicts,
+                };
+                state.conn_manager.broadcast_to_web(&session_id_clone, msg).await;
+            }
+        }
+        ClientMessage::GitPullResponse {
+            session_id,
+            success,
+            message,
+            conflicts,
+        } => {
+            if client_state.is_cli_bridge {
+                let session_id_clone = session_id.clone();
+                let msg = ServerMessage::GitPullResult {
+                    session_id,
+                    success,
+                    message,
+                    conflicts,
+                };
+                state.conn_manager.broadcast_to_web(&session_id_clone, msg).await;
+            }
+        }
+        ClientMessage::RecordingResponse {
+            session_id,
+            recording,
+            path,
+            error,
+        } => {
+            if client_state.is_cli_bridge {
+                let session_id_clone = session_id.clone();
+                let msg = ServerMessage::RecordingResult {
+                    session_id,
+                    recording,
+                    path,
+                    error,
+                };
+                state.conn_manager.broadcast_to_web(&session_id_clone, msg).await;
+            }
+        }
+
+        ClientMessage::BudgetExceeded {
+            session_id,
+            message,
+        } => {
+            if client_state.is_cli_bridge {
+                let msg = ServerMessage::Error {
+                    code: "budget_exceeded".to_string(),
+                    message,
+                };
+                state.conn_manager.broadcast_to_web(&session_id, msg).await;
+            }
+        }
     }
 
     true
 }
 
 /// Broadcast updated machine list to all connected clients for a user
-async fn broadcast_machine_list(state: &AppState, user_id: &str) {
+pub(crate) async fn broadcast_machine_list(state: &AppState, user_id: &str) {
     match state.machine_registry.list_user_machines(user_id).await {
         Ok(machines) => {
             // Update online status based on active connections
@@ -1388,6 +2613,216 @@ async fn broadcast_machine_list(state: &AppState, user_id: &str) {
     }
 }
 
+/// What `DeleteSession`, `ArchiveSession` and `RestoreSession` ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveChange {
+    Archive,
+    Restore,
+    /// Archive a listed session, purge an archived one
+    Delete,
+}
+
+/// Archive, restore or purge one of the client's sessions, then send the
+/// user's connections the updated archive
+async fn change_archive_state(
+    state: &AppState,
+    client_state: &ClientState,
+    session_id: &str,
+    change: ArchiveChange,
+    tx: &mpsc::UnboundedSender<ServerMessage>,
+) {
+    let send_error = |code: &str, message: &str| {
+        let _ = tx.send(ServerMessage::Error {
+            code: code.to_string(),
+            message: message.to_string(),
+        });
+    };
+    let Some(user_id) = &client_state.user_id else {
+        send_error("not_authenticated", "Please authenticate first");
+        return;
+    };
+
+    match state.session_manager.get_session(session_id).await {
+        Ok(Some(session)) if session.user_id == *user_id => {}
+        Ok(Some(_)) => return send_error("access_denied", "Session belongs to another user"),
+        Ok(None) => return send_error("not_found", "Session not found"),
+        Err(e) => {
+            error!("Failed to get session: {}", e);
+            return send_error("error", "Failed to look up session");
+        }
+    }
+    let archived = match state.session_manager.is_archived(session_id).await {
+        Ok(archived) => archived,
+        Err(e) => {
+            error!("Failed to get session: {}", e);
+            return send_error("error", "Failed to look up session");
+        }
+    };
+
+    let ip = Some(client_state.remote_ip.as_str());
+    let manager = &state.session_manager;
+    match (change, archived) {
+        (ArchiveChange::Archive | ArchiveChange::Delete, false) => {
+            if let Err(e) = manager.remove_session(session_id, user_id, ip).await {
+                error!("Failed to archive session: {}", e);
+                return send_error("delete_failed", "Failed to archive session");
+            }
+            info!("Session archived: {}", session_id);
+            let msg = ServerMessage::SessionDeleted {
+                session_id: session_id.to_string(),
+            };
+            // Gone from the owner's session lists, and the CLI bridge shuts it down
+            state.conn_manager.send_to_user(user_id, msg.clone()).await;
+            state.conn_manager.forward_to_cli(session_id, msg).await;
+        }
+        (ArchiveChange::Delete, true) => {
+            if let Err(e) = manager.purge_session(session_id, user_id, ip).await {
+                error!("Failed to delete session: {}", e);
+                return send_error("delete_failed", "Failed to delete session");
+            }
+            info!("Session purged: {}", session_id);
+        }
+        (ArchiveChange::Restore, true) => {
+            if let Err(e) = manager.restore_session(session_id, user_id, ip).await {
+                error!("Failed to restore session: {}", e);
+                return send_error("restore_failed", "Failed to restore session");
+            }
+            info!("Session restored: {}", session_id);
+            if let Ok(Some(session)) = manager.get_session(session_id).await {
+                let msg = ServerMessage::SessionUpdated { session };
+                state.conn_manager.send_to_user(user_id, msg).await;
+            }
+        }
+        // Already where the client wants it
+        (ArchiveChange::Archive, true) | (ArchiveChange::Restore, false) => {}
+    }
+
+    match manager.list_archived_sessions(user_id).await {
+        Ok(sessions) => {
+            let msg = ServerMessage::ArchivedSessionsList { sessions };
+            state.conn_manager.send_to_user(user_id, msg).await;
+        }
+        Err(e) => warn!("Failed to list archived sessions for {}: {}", user_id, e),
+    }
+}
+
+/// Ask `target_machine_id`'s daemon for a session to replace `session_id`
+///
+/// The session's buffered output goes along to seed the new session's
+/// scrollback. Its viewers are moved over once the daemon reports back with
+/// `RemoteSessionResult`; the old session keeps running until it is stopped.
+async fn start_transfer(
+    state: &AppState,
+    user_id: &str,
+    session_id: &str,
+    target_machine_id: &str,
+    tx: &mpsc::UnboundedSender<ServerMessage>,
+) {
+    let send_error = |code: &str, message: String| {
+        let _ = tx.send(ServerMessage::Error {
+            code: code.to_string(),
+            message,
+        });
+    };
+
+    let session = match state.session_manager.get_session(session_id).await {
+        Ok(Some(session)) if session.user_id == user_id => session,
+        Ok(Some(_)) => {
+            send_error("access_denied", "Session belongs to another user".to_string());
+            return;
+        }
+        Ok(None) => {
+            send_error("not_found", "Session not found".to_string());
+            return;
+        }
+        Err(e) => {
+            error!("Failed to get session: {}", e);
+            send_error("transfer_failed", "Failed to look up session".to_string());
+            return;
+        }
+    };
+    if session.status == SessionStatus::Terminated {
+        send_error(
+            "session_terminated",
+            format!("Session {} has ended and can't be transferred", session.tag),
+        );
+        return;
+    }
+    if session.machine_id == target_machine_id {
+        send_error(
+            "invalid_transfer",
+            format!("Session {} is already on that machine", session.tag),
+        );
+        return;
+    }
+
+    match state.machine_registry.get_machine(target_machine_id).await {
+        Ok(Some(machine)) if machine.user_id == user_id => {}
+        Ok(Some(_)) => {
+            send_error("access_denied", "Machine belongs to another user".to_string());
+            return;
+        }
+        Ok(None) => {
+            send_error("not_found", format!("Machine {} not found", target_machine_id));
+            return;
+        }
+        Err(e) => {
+            error!("Failed to get machine: {}", e);
+            send_error("transfer_failed", "Failed to look up machine".to_string());
+            return;
+        }
+    }
+    let Some(machine_tx) = state.conn_manager.get_machine_tx(target_machine_id).await else {
+        warn!("No active daemon for machine {}", target_machine_id);
+        send_error(
+            "machine_offline",
+            format!(
+                "Machine {} is offline or has no active daemon",
+                target_machine_id
+            ),
+        );
+        return;
+    };
+
+    let history = state
+        .conn_manager
+        .get_output_buffer(session_id, usize::MAX)
+        .await
+        .map(|history| history.data)
+        .unwrap_or_default();
+    let request_id = Uuid::new_v4().to_string();
+    state
+        .conn_manager
+        .register_pending_request(&request_id, tx.clone())
+        .await;
+    state
+        .conn_manager
+        .register_pending_transfer(&request_id, session_id)
+        .await;
+
+    let history_len = history.len();
+    let request = ServerMessage::StartRemoteSession {
+        request_id: request_id.clone(),
+        machine_id: target_machine_id.to_string(),
+        cwd: Some(session.metadata.cwd.clone()),
+        args: None,
+        history,
+    };
+    if machine_tx.send(request).is_err() {
+        let _ = state.conn_manager.take_pending_request(&request_id).await;
+        let _ = state.conn_manager.take_pending_transfer(&request_id).await;
+        send_error(
+            "machine_unreachable",
+            format!("Machine {} is not reachable", target_machine_id),
+        );
+    } else {
+        info!(
+            "Transfer request {} sent to machine {} for session {} ({} bytes of history)",
+            request_id, target_machine_id, session_id, history_len
+        );
+    }
+}
+
 /// Send machine list to a specific user connection (for initial load)
 async fn send_machine_list_to_user(
     state: &AppState,
@@ -1411,3 +2846,280 @@ async fn send_machine_list_to_user(
         }
     }
 }
+
+/// Daemon version of the user's most recently seen online machine
+async fn online_daemon_version(state: &AppState, user_id: &str) -> Option<String> {
+    let machines = state
+        .machine_registry
+        .list_user_machines(user_id)
+        .await
+        .ok()?;
+    let mut online = Vec::new();
+    for m in machines {
+        if m.daemon_version.is_some() && state.conn_manager.has_machine(&m.id).await {
+            online.push(m);
+        }
+    }
+    online
+        .into_iter()
+        .max_by_key(|m| m.last_seen)
+        .and_then(|m| m.daemon_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbose_preview() {
+        let auth = verbose_preview(r#"{"type":"authenticate","token":"secret-jwt"}"#).unwrap();
+        assert!(!auth.contains("secret-jwt"));
+        assert!(auth.contains("[REDACTED]"));
+
+        assert!(verbose_preview(r#"{"type":"ping"}"#).is_none());
+        let output = r#"{"type":"terminal_output","session_id":"s","data":[1]}"#;
+        assert!(verbose_preview(output).is_none());
+
+        let long = format!(r#"{{"type":"list_sessions","pad":"{}"}}"#, "é".repeat(200));
+        assert_eq!(verbose_preview(&long).unwrap().chars().count(), 103);
+    }
+
+    #[test]
+    fn test_output_buffer_ranges() {
+        let mut buffer = OutputBuffer::new();
+        buffer.push(&[b'a'; 64], 64);
+        buffer.push(b"0123456789", 64);
+        assert_eq!(buffer.start_offset, 10);
+        assert_eq!(buffer.end_offset(), 74);
+
+        let tail = buffer.range(buffer.end_offset() - 4, 4);
+        assert_eq!(tail.data, b"6789");
+        assert!(tail.has_more);
+
+        // Offsets that were already dropped clamp to the oldest buffered byte
+        let head = buffer.range(0, 3);
+        assert_eq!(head.offset, 10);
+        assert_eq!(head.data, b"aaa");
+        assert!(!head.has_more);
+
+        let past_end = buffer.range(u64::MAX, 10);
+        assert!(past_end.data.is_empty());
+
+        // A single push larger than the limit keeps only its tail
+        let mut buffer = OutputBuffer::new();
+        buffer.push(b"abc", 8);
+        buffer.push(b"0123456789", 8);
+        assert_eq!(buffer.start_offset, 5);
+        assert_eq!(buffer.range(0, 8).data, b"23456789");
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_separates_bridges_from_web_clients() {
+        let manager = ConnectionManager::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let now = Instant::now();
+
+        manager.register_connection("alice", "daemon-conn", now, tx.clone()).await;
+        manager.register_machine("m1", "daemon-conn", tx.clone()).await;
+        manager.register_cli("s1", "m1", "alice", now, tx.clone()).await;
+
+        manager.register_user("alice", "web-conn", now, tx.clone()).await.unwrap();
+        manager.register_web("s1", "web-conn", tx.clone()).await;
+        manager.register_web("s2", "web-conn", tx.clone()).await;
+
+        let snapshot = manager.snapshot().await;
+        assert_eq!(snapshot.cli_bridges.len(), 1);
+        assert_eq!(snapshot.cli_bridges[0].machine_id, "m1");
+        assert_eq!(snapshot.web_clients.len(), 1);
+        assert_eq!(snapshot.web_clients[0].session_count, 2);
+        assert_eq!(snapshot.machines[0].connection_count, 1);
+
+        manager.unregister_user("web-conn").await;
+        assert!(manager.snapshot().await.web_clients.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unregister_user_keeps_other_connections() {
+        let manager = ConnectionManager::new();
+        let now = Instant::now();
+        let (tab_1, mut rx_1) = mpsc::unbounded_channel();
+        let (tab_2, mut rx_2) = mpsc::unbounded_channel();
+        manager.register_user("alice", "tab-1", now, tab_1).await.unwrap();
+        manager.register_user("alice", "tab-2", now, tab_2).await.unwrap();
+
+        // Closing one tab leaves the user's other tab receiving broadcasts
+        manager.unregister_user("tab-1").await;
+        manager.unregister_user("unknown").await;
+        manager.broadcast_to_all_users(ServerMessage::Pong).await;
+        assert!(rx_1.try_recv().is_err());
+        assert!(matches!(rx_2.try_recv(), Ok(ServerMessage::Pong)));
+        assert_eq!(manager.user_connection_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_register_user_enforces_limit() {
+        let manager = ConnectionManager::new().with_user_connection_limit(2);
+        let now = Instant::now();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let (closed_tx, closed_rx) = mpsc::unbounded_channel();
+        drop(closed_rx);
+
+        manager.register_user("alice", "tab-1", now, tx.clone()).await.unwrap();
+        // A socket that went away without unregistering doesn't count
+        manager.register_user("alice", "tab-2", now, closed_tx).await.unwrap();
+        manager.register_user("alice", "tab-3", now, tx.clone()).await.unwrap();
+        assert_eq!(
+            manager.register_user("alice", "tab-4", now, tx.clone()).await,
+            Err(ConnectionError::TooManyConnections { limit: 2 })
+        );
+        // Other users have their own allowance
+        manager.register_user("bob", "tab-5", now, tx.clone()).await.unwrap();
+
+        // Daemons and bridges connecting at the limit aren't turned away
+        manager.register_connection("alice", "daemon-conn", now, tx.clone()).await;
+        // until they act as web clients
+        assert_eq!(
+            manager.count_as_web("daemon-conn").await,
+            Err(ConnectionError::TooManyConnections { limit: 2 })
+        );
+        manager.unregister_user("tab-1").await;
+        manager.count_as_web("daemon-conn").await.unwrap();
+        manager.count_as_web("daemon-conn").await.unwrap();
+
+        let snapshot = manager.snapshot().await;
+        let alice = snapshot.users.iter().find(|u| u.user_id == "alice").unwrap();
+        assert_eq!(alice.connection_count, 2);
+        assert_eq!(snapshot.max_user_connections, 2);
+    }
+
+    #[tokio::test]
+    async fn test_retried_remote_session_result() {
+        let dir = std::env::temp_dir().join(format!("happy-ws-{}", uuid::Uuid::new_v4()));
+        let state = crate::handlers::test_state(&dir).await;
+        let (cli_tx, _cli_rx) = mpsc::unbounded_channel();
+        let mut cli = ClientState {
+            user_id: Some("alice".into()),
+            session_id: None,
+            session_ids: HashSet::new(),
+            is_cli_bridge: true,
+            connection_id: "daemon-conn".into(),
+            machine_id: Some("m1".into()),
+            machine_name: Some("workstation".into()),
+            connected_at: Instant::now(),
+            remote_ip: "127.0.0.1".into(),
+            binary_frames: Arc::new(AtomicBool::new(false)),
+            counted_as_web: false,
+        };
+        let result = |session_id: &str| ClientMessage::RemoteSessionResult {
+            request_id: "r1".into(),
+            success: true,
+            session: Some(Session::new(
+                session_id.into(),
+                "tag".into(),
+                "alice".into(),
+                "m1".into(),
+                "workstation".into(),
+            )),
+            error: None,
+        };
+
+        let (web_tx, mut web_rx) = mpsc::unbounded_channel();
+        state.conn_manager.register_pending_request("r1", web_tx.clone()).await;
+        handle_message(result("s1"), &state, &mut cli, &cli_tx).await;
+        assert!(matches!(
+            web_rx.try_recv(),
+            Ok(ServerMessage::RemoteSessionResponse { session: Some(s), .. }) if s.id == "s1"
+        ));
+
+        // The CLI retried with a session of its own; the web client gets the saved one
+        state.conn_manager.register_pending_request("r1", web_tx).await;
+        handle_message(result("s2"), &state, &mut cli, &cli_tx).await;
+        assert!(matches!(
+            web_rx.try_recv(),
+            Ok(ServerMessage::RemoteSessionResponse { session: Some(s), .. }) if s.id == "s1"
+        ));
+        assert!(state.session_manager.get_session("s2").await.unwrap().is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_delete_session_archives_then_purges() {
+        let dir = std::env::temp_dir().join(format!("happy-ws-{}", uuid::Uuid::new_v4()));
+        let state = crate::handlers::test_state(&dir).await;
+        let session = state
+            .session_manager
+            .create_session("alice", "m1", "workstation", "tag", "/work", None)
+            .await
+            .unwrap();
+
+        let (alice_tx, mut alice_rx) = mpsc::unbounded_channel();
+        let (bob_tx, mut bob_rx) = mpsc::unbounded_channel();
+        let now = Instant::now();
+        let manager = &state.conn_manager;
+        manager.register_user("alice", "alice-tab", now, alice_tx.clone()).await.unwrap();
+        manager.register_user("bob", "bob-tab", now, bob_tx).await.unwrap();
+        let web = ClientState {
+            user_id: Some("alice".into()),
+            session_id: None,
+            session_ids: HashSet::new(),
+            is_cli_bridge: false,
+            connection_id: "alice-tab".into(),
+            machine_id: None,
+            machine_name: None,
+            connected_at: now,
+            remote_ip: "127.0.0.1".into(),
+            binary_frames: Arc::new(AtomicBool::new(false)),
+            counted_as_web: true,
+        };
+
+        change_archive_state(&state, &web, &session.id, ArchiveChange::Delete, &alice_tx).await;
+        assert!(state.session_manager.is_archived(&session.id).await.unwrap());
+        assert!(matches!(
+            alice_rx.try_recv(),
+            Ok(ServerMessage::SessionDeleted { session_id }) if session_id == session.id
+        ));
+        // Other users aren't told about the session
+        assert!(bob_rx.try_recv().is_err());
+
+        change_archive_state(&state, &web, &session.id, ArchiveChange::Delete, &alice_tx).await;
+        assert!(state.session_manager.get_session(&session.id).await.unwrap().is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_web_moves_viewers() {
+        let manager = ConnectionManager::new();
+        let (tab1_tx, mut tab1_rx) = mpsc::unbounded_channel();
+        let (tab2_tx, mut tab2_rx) = mpsc::unbounded_channel();
+        manager.register_web("old", "tab-1", tab1_tx.clone()).await;
+        manager.register_web("old", "tab-2", tab2_tx.clone()).await;
+        manager.register_web("new", "tab-1", tab1_tx).await;
+
+        let new = Session::new(
+            "new".into(),
+            "moved".into(),
+            "alice".into(),
+            "m2".into(),
+            "workstation".into(),
+        );
+        assert_eq!(manager.transfer_web("old", &new).await, 2);
+        assert_eq!(manager.transfer_web("old", &new).await, 0);
+        for rx in [&mut tab1_rx, &mut tab2_rx] {
+            assert!(matches!(
+                rx.try_recv(),
+                Ok(ServerMessage::SessionTransferred { from_session_id, session })
+                    if from_session_id == "old" && session.id == "new"
+            ));
+        }
+
+        // Rejoining after the transfer doesn't register a tab twice
+        manager.register_web("new", "tab-2", tab2_tx).await;
+        manager.broadcast_to_web("new", ServerMessage::Pong).await;
+        for rx in [&mut tab1_rx, &mut tab2_rx] {
+            assert!(matches!(rx.try_recv(), Ok(ServerMessage::Pong)));
+            assert!(rx.try_recv().is_err());
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Per-user rate limits on WebSocket messages
+//!
+//! Terminal input and remote session requests are metered with a token
+//! bucket per user and message type: a client may send `burst_capacity`
+//! messages at once, after which tokens come back at `max_per_second`. The
+//! buckets live in [`MemoryCache`] and are updated in place under the map's
+//! shard lock, so the hot path takes no extra lock.
+
+use crate::storage::MemoryCache;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// `TerminalInput` limit, overridden by `RATE_LIMIT_TERMINAL_INPUT`
+pub const DEFAULT_TERMINAL_INPUT_RATE: MessageRate = MessageRate {
+    max_per_second: 100.0,
+    burst_capacity: 200,
+};
+
+/// `RequestRemoteSession` limit, overridden by `RATE_LIMIT_REMOTE_SESSION`
+pub const DEFAULT_REMOTE_SESSION_RATE: MessageRate = MessageRate {
+    max_per_second: 1.0,
+    burst_capacity: 5,
+};
+
+/// Messages that are rate limited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitedMessage {
+    TerminalInput,
+    RemoteSession,
+}
+
+impl LimitedMessage {
+    /// The message's `type` tag on the wire
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TerminalInput => "terminal_input",
+            Self::RemoteSession => "request_remote_session",
+        }
+    }
+}
+
+/// Sustained rate and burst size, written `rate/burst` or just `rate` for a
+/// burst of the same size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MessageRate {
+    pub max_per_second: f64,
+    pub burst_capacity: u32,
+}
+
+impl FromStr for MessageRate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rate, burst) = match s.split_once('/') {
+            Some((rate, burst)) => (rate.trim(), Some(burst.trim())),
+            None => (s.trim(), None),
+        };
+        let max_per_second: f64 = rate
+            .parse()
+            .map_err(|_| format!("invalid rate {:?}", rate))?;
+        if !max_per_second.is_finite() || max_per_second <= 0.0 {
+            return Err(format!("rate must be positive, got {}", rate));
+        }
+        let burst_capacity = match burst {
+            Some(burst) => burst
+                .parse()
+                .map_err(|_| format!("invalid burst {:?}", burst))?,
+            None => max_per_second.ceil() as u32,
+        };
+        if burst_capacity == 0 {
+            return Err("burst must be at least 1".to_string());
+        }
+        Ok(Self {
+            max_per_second,
+            burst_capacity,
+        })
+    }
+}
+
+impl fmt::Display for MessageRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.max_per_second, self.burst_capacity)
+    }
+}
+
+/// Token buckets keyed by user and message type
+pub struct MessageRateLimiter {
+    cache: Arc<MemoryCache>,
+    terminal_input: MessageRate,
+    remote_session: MessageRate,
+    /// Bucket timestamps are stored relative to this
+    epoch: Instant,
+}
+
+impl MessageRateLimiter {
+    pub fn new(
+        cache: Arc<MemoryCache>,
+        terminal_input: MessageRate,
+        remote_session: MessageRate,
+    ) -> Self {
+        Self {
+            cache,
+            terminal_input,
+            remote_session,
+            epoch: Instant::now(),
+        }
+    }
+
+    /// Take a token for `user_id`'s `kind` message, or return how long until one is free
+    pub fn check(&self, user_id: &str, kind: LimitedMessage) -> Result<(), Duration> {
+        self.check_at(user_id, kind, Instant::now())
+    }
+
+    fn check_at(&self, user_id: &str, kind: LimitedMessage, now: Instant) -> Result<(), Duration> {
+        let rate = match kind {
+            LimitedMessage::TerminalInput => self.terminal_input,
+            LimitedMessage::RemoteSession => self.remote_session,
+        };
+        let burst = rate.burst_capacity as f64;
+        let now_micros = now.saturating_duration_since(self.epoch).as_micros() as u64;
+        // An idle bucket is full again after this long, so it can be forgotten
+        let ttl = Duration::from_secs_f64(burst / rate.max_per_second).max(Duration::from_secs(1));
+
+        let key = format!("ws_rate:{}:{}", kind.as_str(), user_id);
+        self.cache.update_with_ttl(&key, ttl, |current| {
+            let (tokens, last_micros) =
+                current.and_then(decode_bucket).unwrap_or((burst, now_micros));
+            let elapsed = now_micros.saturating_sub(last_micros) as f64 / 1e6;
+            let tokens = (tokens + elapsed * rate.max_per_second).min(burst);
+            if tokens >= 1.0 {
+                (encode_bucket(tokens - 1.0, now_micros), Ok(()))
+            } else {
+                let wait = (1.0 - tokens) / rate.max_per_second;
+                (encode_bucket(tokens, now_micros), Err(Duration::from_secs_f64(wait)))
+            }
+        })
+    }
+}
+
+fn encode_bucket(tokens: f64, at_micros: u64) -> Vec<u8> {
+    let mut bytes = tokens.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&at_micros.to_le_bytes());
+    bytes
+}
+
+fn decode_bucket(bytes: &[u8]) -> Option<(f64, u64)> {
+    let tokens = f64::from_le_bytes(bytes.get(..8)?.try_into().ok()?);
+    let at_micros = u64::from_le_bytes(bytes.get(8..16)?.try_into().ok()?);
+    Some((tokens, at_micros))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_burst_then_limited() {
+        let rate = MessageRate {
+            max_per_second: 5.0,
+            burst_capacity: 20,
+        };
+        let limiter = MessageRateLimiter::new(Arc::new(MemoryCache::new()), rate, rate);
+        let start = limiter.epoch;
+
+        // 200 messages over 100ms refill half a token, so only the burst gets through
+        let allowed = (0..200)
+            .filter(|i| {
+                let at = start + Duration::from_micros(i * 500);
+                limiter.check_at("u1", LimitedMessage::TerminalInput, at).is_ok()
+            })
+            .count();
+        assert_eq!(allowed, 20);
+
+        // Other users and message types have their own buckets
+        assert!(limiter.check_at("u2", LimitedMessage::TerminalInput, start).is_ok());
+        assert!(limiter.check_at("u1", LimitedMessage::RemoteSession, start).is_ok());
+
+        // The retry hint is when the next token comes back
+        let at = start + Duration::from_millis(100);
+        let retry = limiter.check_at("u1", LimitedMessage::TerminalInput, at).unwrap_err();
+        assert!(retry > Duration::from_millis(90) && retry <= Duration::from_millis(100));
+        assert!(limiter.check_at("u1", LimitedMessage::TerminalInput, at + retry).is_ok());
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(
+            "50/100".parse::<MessageRate>().unwrap(),
+            MessageRate {
+                max_per_second: 50.0,
+                burst_capacity: 100
+            }
+        );
+        assert_eq!("2.5".parse::<MessageRate>().unwrap().burst_capacity, 3);
+        let default = DEFAULT_TERMINAL_INPUT_RATE;
+        assert_eq!(default.to_string().parse(), Ok(default));
+        assert!("0".parse::<MessageRate>().is_err());
+        assert!("10/0".parse::<MessageRate>().is_err());
+        assert!("fast".parse::<MessageRate>().is_err());
+    }
+}
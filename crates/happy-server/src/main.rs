@@ -3,16 +3,19 @@
 //! The cloud server component for Happy Remote - handles WebSocket connections,
 //! session management, and real-time synchronization.
 //!
-//! Uses SQLite (embedded) instead of PostgreSQL for simplicity.
+//! Uses SQLite (embedded) by default, or PostgreSQL when built with the
+//! `postgres` feature and given a Postgres `DATABASE_URL`.
 
 mod extractors;
 mod handlers;
+mod metrics;
+mod middleware;
 mod services;
 mod storage;
 
 use anyhow::{Context, Result};
 use axum::{
-    routing::{get, post},
+    routing::{get, patch, post},
     Router,
 };
 use std::net::SocketAddr;
@@ -20,15 +23,37 @@ use std::net::SocketAddr;
 use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::trace::TraceLayer;
 use tracing::{error, info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
-use handlers::ws::ConnectionManager;
-use services::{AuthService, MachineRegistry, SessionManager};
-use storage::{Database, MemoryCache};
+use extractors::TrustedProxies;
+use handlers::session_updates::{DebouncedBroadcaster, DEFAULT_SESSION_UPDATE_DEBOUNCE_MS};
+use handlers::ws::{
+    ConnectionManager, WsLogLevel, DEFAULT_MAX_HISTORY_BYTES, DEFAULT_MAX_USER_CONNECTIONS,
+};
+use handlers::ws_rate_limit::{
+    MessageRate, MessageRateLimiter, DEFAULT_REMOTE_SESSION_RATE, DEFAULT_TERMINAL_INPUT_RATE,
+};
+use middleware::{RateLimitHeaderLayer, RateLimiter};
+use services::{
+    AuthService, MachineRegistry, MailService, OidcService, PushService, SessionManager,
+};
+#[cfg(feature = "sso")]
+use services::saml::SamlService;
+use storage::{Database, DatabaseConfig, DatabaseLocation, MemoryCache};
+
+/// How often the shutdown drain checks for remaining CLI bridges
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the shutdown drain reports its progress
+const DRAIN_LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long force-closed connections get to run their disconnect cleanup
+const FORCE_CLOSE_GRACE: Duration = Duration::from_secs(5);
 
 /// Application state shared across handlers
 #[derive(Clone)]
@@ -38,7 +63,25 @@ pub struct AppState {
     pub session_manager: Arc<SessionManager>,
     pub machine_registry: Arc<MachineRegistry>,
     pub auth_service: Arc<AuthService>,
+    pub oidc_service: Arc<OidcService>,
+    /// SAML single sign-on; `None` unless configured and the IdP was reachable
+    #[cfg(feature = "sso")]
+    pub saml_service: Option<Arc<SamlService>>,
+    pub push_service: Arc<PushService>,
+    pub mail_service: Arc<MailService>,
     pub conn_manager: Arc<ConnectionManager>,
+    pub session_updates: Arc<DebouncedBroadcaster>,
+    /// Bearer token for `/api/v1/admin/*`; those routes 404 when unset
+    pub admin_token: Option<Arc<str>>,
+    /// Bearer token for `/api/v1/metrics`; the endpoint is open when unset
+    pub metrics_token: Option<Arc<str>>,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Per-user limits on terminal input and remote session requests
+    pub ws_rate_limiter: Arc<MessageRateLimiter>,
+    /// Proxies whose `X-Forwarded-For` is believed when resolving client IPs
+    pub trusted_proxies: Arc<TrustedProxies>,
+    /// Externally reachable base URL, used in emailed links
+    pub public_url: Arc<str>,
 }
 
 #[tokio::main]
@@ -87,33 +130,125 @@ async fn run_server() -> Result<()> {
         .await
         .context("Failed to load configuration")?;
     info!(
-        "Config loaded: bind={}, db={}",
-        config.bind_address, config.database_path
+        "Config loaded: bind={}, db={}, ws_log_level={:?}",
+        config.bind_address, config.database_location, config.ws_log_level
     );
+    WsLogLevel::init(config.ws_log_level);
 
-    // Initialize SQLite database
-    info!("Initializing SQLite database...");
+    // Initialize database
+    info!("Initializing database...");
     let db = Arc::new(
-        Database::new(&config.database_path)
+        Database::open(&config.database_location, config.database.clone())
             .await
             .context("Failed to initialize database")?,
     );
-    info!("SQLite database initialized at: {}", config.database_path);
+    info!("Database initialized at: {}", config.database_location);
 
     // Initialize in-memory cache (replaces Redis)
     info!("Initializing in-memory cache...");
-    let cache = Arc::new(MemoryCache::new());
+    let cache = Arc::new(MemoryCache::with_max_entries(config.cache_max_entries));
     info!("In-memory cache initialized");
 
     // Initialize services
     info!("Initializing services...");
     let session_manager = Arc::new(SessionManager::new(db.clone(), cache.clone()));
     let machine_registry = Arc::new(MachineRegistry::new(db.clone(), cache.clone()));
-    let auth_service = Arc::new(AuthService::new(db.clone(), config.jwt_secret.clone()));
+    #[cfg(feature = "sso")]
+    let saml_service = SamlService::from_db(&db, cache.clone(), &config.public_url)
+        .await
+        .map(Arc::new);
+    // Without a working IdP, SSO accounts fall back to their passwords
+    #[cfg(feature = "sso")]
+    let sso_config = saml_service.as_ref().map(|saml| saml.config().clone());
+    #[cfg(not(feature = "sso"))]
+    let sso_config = None;
+    let auth_service = Arc::new(AuthService::new(
+        db.clone(),
+        cache.clone(),
+        config.jwt_secret.clone(),
+        sso_config,
+    ));
+    let oidc_service = Arc::new(OidcService::from_env(cache.clone(), &config.public_url).await);
+    let push_service = Arc::new(PushService::from_env(db.clone()));
+    let mail_service = Arc::new(MailService::from_env());
     info!("Services initialized");
 
+    // Permanently remove accounts once their grace period has passed
+    let purge_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let cutoff =
+                chrono::Utc::now() - chrono::Duration::days(services::auth::ACCOUNT_PURGE_DELAY_DAYS);
+            match purge_db.purge_deleted_users(cutoff).await {
+                Ok(0) => {}
+                Ok(n) => info!("Purged {} deleted accounts", n),
+                Err(e) => error!("Failed to purge deleted accounts: {}", e),
+            }
+        }
+    });
+
+    // Keep query planner statistics fresh as the data grows
+    let analyze_db = db.clone();
+    let analyze_interval = Duration::from_secs(config.db_analyze_interval_hours * 3600);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(analyze_interval);
+        // The first tick completes immediately; startup is not the time for it
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(e) = analyze_db.analyze().await {
+                error!("Failed to analyze database: {}", e);
+            }
+        }
+    });
+
     // Create connection manager
-    let conn_manager = Arc::new(ConnectionManager::new());
+    let conn_manager = Arc::new(
+        ConnectionManager::new()
+            .with_history_limit(config.max_history_bytes_per_session)
+            .with_user_connection_limit(config.max_user_connections),
+    );
+
+    // Forget terminal history of sessions that have gone quiet
+    let history_conn_manager = conn_manager.clone();
+    let history_retention =
+        std::time::Duration::from_secs(config.history_retention_days * 24 * 3600);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match history_conn_manager.prune_history(history_retention).await {
+                0 => {}
+                n => info!("Dropped terminal history for {} idle sessions", n),
+            }
+        }
+    });
+
+    // Coalesce bursts of SessionUpdated events
+    let session_updates = Arc::new(DebouncedBroadcaster::new(
+        conn_manager.clone(),
+        config.session_update_debounce,
+    ));
+    session_updates.clone().spawn();
+
+    // Forget rate limit windows of clients that have gone quiet
+    let rate_limiter = Arc::new(RateLimiter::new(config.rate_limit_per_minute));
+    let prune_limiter = rate_limiter.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            prune_limiter.prune();
+        }
+    });
+
+    let ws_rate_limiter = Arc::new(MessageRateLimiter::new(
+        cache.clone(),
+        config.terminal_input_rate,
+        config.remote_session_rate,
+    ));
 
     // Create app state
     let state = AppState {
@@ -122,7 +257,19 @@ async fn run_server() -> Result<()> {
         session_manager,
         machine_registry,
         auth_service,
-        conn_manager,
+        oidc_service,
+        #[cfg(feature = "sso")]
+        saml_service,
+        push_service,
+        mail_service,
+        conn_manager: conn_manager.clone(),
+        session_updates,
+        admin_token: config.admin_token.as_deref().map(Arc::from),
+        metrics_token: config.metrics_token.as_deref().map(Arc::from),
+        rate_limiter,
+        ws_rate_limiter,
+        trusted_proxies: Arc::new(config.trusted_proxies),
+        public_url: Arc::from(config.public_url.trim_end_matches('/')),
     };
 
     // Static files directory
@@ -141,7 +288,15 @@ async fn run_server() -> Result<()> {
         // WebSocket endpoint
         .route("/ws", get(handlers::ws::handler))
         // REST API routes
-        .nest("/api/v1", api_routes())
+        .nest(
+            "/api/v1",
+            api_routes()
+                .layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    middleware::rate_limit,
+                ))
+                .layer(RateLimitHeaderLayer),
+        )
         // Static files
         .nest_service(
             "/pkg",
@@ -175,37 +330,200 @@ async fn run_server() -> Result<()> {
         .context("Failed to bind to address")?;
 
     info!("Server ready to accept connections");
-    axum::serve(listener, app).await.context("Server error")?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .context("Server error")?;
+
+    // WebSockets outlive the HTTP server, so give the CLI bridges time to leave
+    drain_cli_bridges(&conn_manager, config.shutdown_drain_timeout).await;
+    info!("Shutdown complete");
 
     Ok(())
 }
 
+/// Resolves on SIGTERM or SIGINT (Ctrl+C), which both start the shutdown drain
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm =
+            signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+        let mut sigint =
+            signal(SignalKind::interrupt()).expect("Failed to install SIGINT handler");
+        tokio::select! {
+            _ = sigterm.recv() => info!("Received SIGTERM, shutting down"),
+            _ = sigint.recv() => info!("Received SIGINT, shutting down"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+        info!("Received Ctrl+C, shutting down");
+    }
+}
+
+/// Wait up to `timeout` for CLI bridges to disconnect, then close whatever is left
+async fn drain_cli_bridges(conn_manager: &ConnectionManager, timeout: Duration) {
+    let started = tokio::time::Instant::now();
+    let mut last_log = None;
+
+    loop {
+        let remaining = conn_manager.connection_counts().await.cli_bridges;
+        if remaining == 0 {
+            info!("All CLI bridges disconnected");
+            break;
+        }
+        if started.elapsed() >= timeout {
+            warn!(
+                "Drain timed out after {}s, closing {} CLI bridges",
+                timeout.as_secs(),
+                remaining
+            );
+            break;
+        }
+        if last_log.is_none_or(|at: tokio::time::Instant| at.elapsed() >= DRAIN_LOG_INTERVAL) {
+            info!("Waiting for {} CLI bridges to disconnect...", remaining);
+            last_log = Some(tokio::time::Instant::now());
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+
+    // Close web clients too, and let every handler record its disconnect
+    conn_manager.close_all();
+    let closed = tokio::time::timeout(FORCE_CLOSE_GRACE, async {
+        while conn_manager.connection_counts().await.cli_bridges > 0 {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    })
+    .await;
+    if closed.is_err() {
+        warn!("Some connections did not finish closing");
+    }
+}
+
 fn api_routes() -> Router<AppState> {
-    Router::new()
+    let router = Router::new()
         .route("/auth/login", post(handlers::auth::login))
         .route("/auth/register", post(handlers::auth::register))
         .route("/auth/refresh", post(handlers::auth::refresh))
-        .route("/users/me", get(handlers::users::me))
+        .route("/auth/logout", post(handlers::auth::logout))
+        .route(
+            "/auth/forgot-password",
+            post(handlers::auth::forgot_password),
+        )
+        .route("/auth/reset-password", post(handlers::auth::reset_password))
+        .route("/auth/totp/setup", post(handlers::auth::totp_setup))
+        .route("/auth/totp/verify", post(handlers::auth::totp_verify))
+        .route(
+            "/auth/totp/challenge",
+            post(handlers::auth::totp_challenge),
+        )
+        .route("/auth/providers", get(handlers::auth::providers))
+        .route("/metrics", get(handlers::metrics::metrics))
+        .route(
+            "/auth/oidc/:provider/authorize",
+            get(handlers::auth::oidc_authorize),
+        )
+        .route(
+            "/auth/oidc/:provider/callback",
+            get(handlers::auth::oidc_callback),
+        )
+        .route(
+            "/users/me",
+            get(handlers::users::me).delete(handlers::users::delete_me),
+        )
         .route(
             "/sessions",
             get(handlers::sessions::list).post(handlers::sessions::create),
         )
+        .route("/sessions/events", get(handlers::sessions::events))
         .route(
             "/sessions/:id",
-            get(handlers::sessions::get).delete(handlers::sessions::delete),
+            get(handlers::sessions::get)
+                .patch(handlers::sessions::rename)
+                .delete(handlers::sessions::delete),
         )
+        .route("/sessions/:id/history", get(handlers::sessions::history))
         .route(
             "/machines",
             get(handlers::machines::list).post(handlers::machines::register),
         )
-        .route("/machines/:id", get(handlers::machines::get))
+        .route(
+            "/machines/:id",
+            get(handlers::machines::get)
+                .patch(handlers::machines::rename)
+                .delete(handlers::machines::delete),
+        )
+        .route("/audit", get(handlers::audit::list))
+        .route("/config", patch(handlers::config::update))
+        .route("/push/register", post(handlers::push::register))
+        .route("/push/send", post(handlers::push::send))
+        .route("/admin/connections", get(handlers::admin::connections))
+        .route("/admin/db-status", get(handlers::admin::db_status))
+        .route("/admin/db/vacuum", post(handlers::admin::vacuum_db))
+        .route("/admin/db/analyze", post(handlers::admin::analyze_db))
+        .route("/admin/machines/merge", post(handlers::admin::merge_machines))
+        .route("/admin/users/:email/unlock", post(handlers::admin::unlock_user))
+        .route("/admin/sessions", get(handlers::admin::sessions_by_ip))
+        .route("/admin/sessions/:id", get(handlers::admin::get_session));
+
+    #[cfg(feature = "sso")]
+    let router = router
+        .route("/auth/sso/saml/metadata", get(handlers::sso::metadata))
+        .route("/auth/sso/saml/login", get(handlers::sso::login))
+        .route("/auth/sso/saml/callback", post(handlers::sso::callback))
+        .route(
+            "/admin/sso",
+            get(handlers::admin::get_sso_config).put(handlers::admin::set_sso_config),
+        );
+
+    router
 }
 
 #[derive(Debug, Clone)]
 struct Config {
     bind_address: String,
-    database_path: String,
+    /// SQLite file or Postgres URL
+    database_location: DatabaseLocation,
+    database: DatabaseConfig,
     jwt_secret: String,
+    /// Externally reachable base URL, used for OIDC callbacks
+    public_url: String,
+    /// Verbosity of WebSocket message logging
+    ws_log_level: WsLogLevel,
+    /// Days of inactivity after which a session's terminal history is dropped
+    history_retention_days: u64,
+    max_history_bytes_per_session: usize,
+    /// WebSocket connections one user may hold, not counting daemons and CLI bridges
+    max_user_connections: usize,
+    /// Longest the shutdown waits for CLI bridges before closing them
+    shutdown_drain_timeout: Duration,
+    /// How long SessionUpdated events are held so bursts collapse into one
+    session_update_debounce: Duration,
+    /// Enables the admin endpoints when set
+    admin_token: Option<String>,
+    /// Required as a bearer token by `/api/v1/metrics` when set
+    metrics_token: Option<String>,
+    /// REST API requests allowed per client IP per minute
+    rate_limit_per_minute: u32,
+    /// Per-user `TerminalInput` rate over WebSocket
+    terminal_input_rate: MessageRate,
+    /// Per-user `RequestRemoteSession` rate over WebSocket
+    remote_session_rate: MessageRate,
+    /// Reverse proxies allowed to report the client address
+    trusted_proxies: TrustedProxies,
+    /// Hours between scheduled `PRAGMA optimize` (or `ANALYZE`) runs
+    db_analyze_interval_hours: u64,
+    /// Keys the in-memory cache holds before evicting the oldest
+    cache_max_entries: usize,
     data_dir: PathBuf,
 }
 
@@ -240,10 +558,26 @@ async fn load_config() -> Result<Config> {
         }
     }
 
-    let database_path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| {
-        let path = data_dir.join("happy_remote.db");
-        path.to_string_lossy().to_string()
-    });
+    // A Postgres URL in DATABASE_URL switches backends; DATABASE_PATH only names a SQLite file
+    let database_location = match std::env::var("DATABASE_URL") {
+        Ok(url) if !url.is_empty() => DatabaseLocation::parse(&url),
+        _ => DatabaseLocation::Sqlite(std::env::var("DATABASE_PATH").unwrap_or_else(|_| {
+            let path = data_dir.join("happy_remote.db");
+            path.to_string_lossy().to_string()
+        })),
+    };
+
+    let database_defaults = DatabaseConfig::default();
+    let database = DatabaseConfig {
+        max_connections: env_or("DATABASE_MAX_CONNECTIONS", database_defaults.max_connections)
+            .max(1),
+        wal: env_or("DATABASE_WAL", database_defaults.wal),
+        busy_timeout: Duration::from_millis(env_or(
+            "DATABASE_BUSY_TIMEOUT_MS",
+            database_defaults.busy_timeout.as_millis() as u64,
+        )),
+        cache_size_kib: env_or("DATABASE_CACHE_SIZE_KIB", database_defaults.cache_size_kib),
+    };
 
     let bind_address =
         std::env::var("BIND_ADDRESS").unwrap_or_else(|_| "0.0.0.0:16789".to_string());
@@ -253,10 +587,72 @@ async fn load_config() -> Result<Config> {
         "change-me-in-production".to_string()
     });
 
+    let public_url = std::env::var("PUBLIC_URL").unwrap_or_else(|_| {
+        let port = bind_address.rsplit(':').next().unwrap_or("16789");
+        format!("http://localhost:{}", port)
+    });
+
+    let history_retention_days = env_or("SESSION_HISTORY_RETENTION_DAYS", 30);
+    let max_history_bytes_per_session =
+        env_or("SESSION_MAX_HISTORY_BYTES_PER_SESSION", DEFAULT_MAX_HISTORY_BYTES);
+    let max_user_connections =
+        env_or("MAX_USER_CONNECTIONS", DEFAULT_MAX_USER_CONNECTIONS).max(1);
+    let shutdown_drain_timeout = Duration::from_secs(env_or("SHUTDOWN_DRAIN_TIMEOUT_SECS", 60));
+    let session_update_debounce = Duration::from_millis(
+        env_or(
+            "SESSION_UPDATE_DEBOUNCE_MS",
+            DEFAULT_SESSION_UPDATE_DEBOUNCE_MS,
+        )
+        .max(1),
+    );
+
+    let rate_limit_per_minute =
+        env_or("RATE_LIMIT_PER_MINUTE", middleware::rate_limit::DEFAULT_RATE_LIMIT_PER_MINUTE)
+            .max(1);
+    let terminal_input_rate = env_or("RATE_LIMIT_TERMINAL_INPUT", DEFAULT_TERMINAL_INPUT_RATE);
+    let remote_session_rate = env_or("RATE_LIMIT_REMOTE_SESSION", DEFAULT_REMOTE_SESSION_RATE);
+    let trusted_proxies = env_or("TRUSTED_PROXIES", TrustedProxies::default());
+    let db_analyze_interval_hours = env_or("DB_ANALYZE_INTERVAL_HOURS", 24).max(1);
+    let cache_max_entries =
+        env_or("CACHE_MAX_ENTRIES", storage::memory::DEFAULT_MAX_ENTRIES).max(1);
+
+    let admin_token = std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
+    if admin_token.is_some() {
+        info!("Admin endpoints enabled");
+    }
+    let metrics_token = std::env::var("METRICS_TOKEN").ok().filter(|t| !t.is_empty());
+
     Ok(Config {
         bind_address,
-        database_path,
+        database_location,
+        database,
         jwt_secret,
+        public_url,
+        ws_log_level: WsLogLevel::from_env(),
+        history_retention_days,
+        max_history_bytes_per_session,
+        max_user_connections,
+        shutdown_drain_timeout,
+        session_update_debounce,
+        admin_token,
+        metrics_token,
+        rate_limit_per_minute,
+        terminal_input_rate,
+        remote_session_rate,
+        trusted_proxies,
+        db_analyze_interval_hours,
+        cache_max_entries,
         data_dir,
     })
 }
+
+/// Parse a numeric env var, falling back to `default` when unset or invalid
+fn env_or<T: std::str::FromStr + std::fmt::Display>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            warn!("Invalid {}={:?}, using {}", name, value, default);
+            default
+        }),
+        Err(_) => default,
+    }
+}
@@ -0,0 +1,229 @@
+//! Prometheus metrics
+//!
+//! Counters live in one process-wide registry so storage and handlers can
+//! record without threading it through every call. Gauges are read from the
+//! connection manager, and cache statistics from the cache, when `/metrics`
+//! is scraped.
+
+use crate::storage::CacheStats;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the `happy_db_query_duration_seconds` buckets; most
+/// SQLite queries finish well under a millisecond
+const DB_QUERY_BUCKETS: [f64; 11] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 1.0,
+];
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// The process-wide registry
+pub fn global() -> &'static Metrics {
+    &METRICS
+}
+
+/// Records one database query's duration when dropped
+pub struct DbQueryTimer(Instant);
+
+impl Drop for DbQueryTimer {
+    fn drop(&mut self) {
+        global().observe_db_query(self.0.elapsed());
+    }
+}
+
+/// Start timing a database query; hold the timer until the query is done
+pub fn time_db_query() -> DbQueryTimer {
+    DbQueryTimer(Instant::now())
+}
+
+/// Values sampled at scrape time
+pub struct Gauges {
+    /// Length of `ConnectionManager::user_connections`
+    pub ws_connections_active: usize,
+    pub sessions_active: usize,
+    pub cache: CacheStats,
+}
+
+pub struct Metrics {
+    registry: Registry,
+    ws_connections_total: IntCounter,
+    ws_connections_active: IntGauge,
+    sessions_total: IntCounter,
+    sessions_active: IntGauge,
+    /// Terminal output received from CLI bridges, by machine
+    terminal_bytes_in: IntCounterVec,
+    /// Terminal input forwarded to CLI bridges, by machine
+    terminal_bytes_out: IntCounterVec,
+    db_query_duration: Histogram,
+    cache_hits: IntCounter,
+    cache_misses: IntCounter,
+    cache_evictions: IntCounter,
+    cache_entries: IntGauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let registry = Registry::new();
+        let metrics = Self {
+            ws_connections_total: counter(
+                "happy_ws_connections_total",
+                "WebSocket connections accepted",
+            ),
+            ws_connections_active: gauge(
+                "happy_ws_connections_active",
+                "Authenticated WebSocket connections currently open",
+            ),
+            sessions_total: counter("happy_sessions_total", "Sessions created"),
+            sessions_active: gauge("happy_sessions_active", "Sessions with a CLI bridge attached"),
+            terminal_bytes_in: counter_vec(
+                "happy_terminal_bytes_in_total",
+                "Terminal output bytes received from CLI bridges",
+            ),
+            terminal_bytes_out: counter_vec(
+                "happy_terminal_bytes_out_total",
+                "Terminal input bytes forwarded to CLI bridges",
+            ),
+            db_query_duration: Histogram::with_opts(
+                HistogramOpts::new(
+                    "happy_db_query_duration_seconds",
+                    "Duration of database queries",
+                )
+                .buckets(DB_QUERY_BUCKETS.to_vec()),
+            )
+            .expect("valid histogram"),
+            cache_hits: counter(
+                "happy_cache_hits_total",
+                "In-memory cache lookups that found a live entry",
+            ),
+            cache_misses: counter(
+                "happy_cache_misses_total",
+                "In-memory cache lookups for missing or expired keys",
+            ),
+            cache_evictions: counter(
+                "happy_cache_evictions_total",
+                "In-memory cache entries evicted to stay under the size cap",
+            ),
+            cache_entries: gauge("happy_cache_entries", "Entries in the in-memory cache"),
+            registry,
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 11] = [
+            Box::new(metrics.ws_connections_total.clone()),
+            Box::new(metrics.ws_connections_active.clone()),
+            Box::new(metrics.sessions_total.clone()),
+            Box::new(metrics.sessions_active.clone()),
+            Box::new(metrics.terminal_bytes_in.clone()),
+            Box::new(metrics.terminal_bytes_out.clone()),
+            Box::new(metrics.db_query_duration.clone()),
+            Box::new(metrics.cache_hits.clone()),
+            Box::new(metrics.cache_misses.clone()),
+            Box::new(metrics.cache_evictions.clone()),
+            Box::new(metrics.cache_entries.clone()),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector).expect("metric registered once");
+        }
+        metrics
+    }
+}
+
+impl Metrics {
+    pub fn ws_connection_opened(&self) {
+        self.ws_connections_total.inc();
+    }
+
+    pub fn session_created(&self) {
+        self.sessions_total.inc();
+    }
+
+    pub fn terminal_bytes_in(&self, machine_id: &str, bytes: usize) {
+        self.terminal_bytes_in.with_label_values(&[machine_id]).inc_by(bytes as u64);
+    }
+
+    pub fn terminal_bytes_out(&self, machine_id: &str, bytes: usize) {
+        self.terminal_bytes_out.with_label_values(&[machine_id]).inc_by(bytes as u64);
+    }
+
+    fn observe_db_query(&self, duration: Duration) {
+        self.db_query_duration.observe(duration.as_secs_f64());
+    }
+
+    /// Everything in the Prometheus text exposition format
+    pub fn render(&self, gauges: &Gauges) -> String {
+        self.ws_connections_active.set(gauges.ws_connections_active as i64);
+        self.sessions_active.set(gauges.sessions_active as i64);
+        catch_up(&self.cache_hits, gauges.cache.hits);
+        catch_up(&self.cache_misses, gauges.cache.misses);
+        catch_up(&self.cache_evictions, gauges.cache.evictions);
+        self.cache_entries.set(gauges.cache.entries as i64);
+
+        let mut out = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut out) {
+            tracing::error!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(out).unwrap_or_default()
+    }
+}
+
+fn counter(name: &str, help: &str) -> IntCounter {
+    IntCounter::new(name, help).expect("valid counter")
+}
+
+fn gauge(name: &str, help: &str) -> IntGauge {
+    IntGauge::new(name, help).expect("valid gauge")
+}
+
+fn counter_vec(name: &str, help: &str) -> IntCounterVec {
+    IntCounterVec::new(Opts::new(name, help), &["machine"]).expect("valid counter")
+}
+
+/// Advance a counter mirroring a total kept elsewhere (the cache's) to `total`
+fn catch_up(counter: &IntCounter, total: u64) {
+    counter.inc_by(total.saturating_sub(counter.get()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.ws_connection_opened();
+        metrics.session_created();
+        metrics.terminal_bytes_in("m1", 100);
+        metrics.terminal_bytes_in("m1", 20);
+        metrics.terminal_bytes_out("m\"2", 5);
+        metrics.observe_db_query(Duration::from_micros(300));
+        metrics.observe_db_query(Duration::from_secs(2));
+
+        let text = metrics.render(&Gauges {
+            ws_connections_active: 3,
+            sessions_active: 1,
+            cache: CacheStats {
+                hits: 7,
+                misses: 2,
+                evictions: 1,
+                entries: 40,
+            },
+        });
+        assert!(text.contains("happy_ws_connections_total 1\n"));
+        assert!(text.contains("happy_ws_connections_active 3\n"));
+        assert!(text.contains("happy_sessions_active 1\n"));
+        assert!(text.contains("happy_terminal_bytes_in_total{machine=\"m1\"} 120\n"));
+        assert!(text.contains("happy_terminal_bytes_out_total{machine=\"m\\\"2\"} 5\n"));
+        // Buckets are cumulative; the slow query only shows in +Inf
+        assert!(text.contains("happy_db_query_duration_seconds_bucket{le=\"0.00025\"} 0\n"));
+        assert!(text.contains("happy_db_query_duration_seconds_bucket{le=\"0.0005\"} 1\n"));
+        assert!(text.contains("happy_db_query_duration_seconds_bucket{le=\"1\"} 1\n"));
+        assert!(text.contains("happy_db_query_duration_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("happy_db_query_duration_seconds_count 2\n"));
+        assert!(text.contains("happy_cache_hits_total 7\n"));
+        assert!(text.contains("happy_cache_evictions_total 1\n"));
+        assert!(text.contains("happy_cache_entries 40\n"));
+    }
+}
@@ -0,0 +1,5 @@
+//! HTTP middleware
+
+pub mod rate_limit;
+
+pub use rate_limit::{rate_limit, RateLimitHeaderLayer, RateLimiter};
@@ -0,0 +1,181 @@
+//! Per-client rate limiting for the REST API
+//!
+//! [`rate_limit`] counts requests per client IP in fixed one-minute windows and
+//! records the outcome as a [`RateLimitState`] response extension, which
+//! [`RateLimitHeaderLayer`] turns into `X-RateLimit-*` headers.
+
+use std::net::IpAddr;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use tower::{util::MapResponse, Layer};
+
+use crate::extractors::ClientIp;
+use crate::AppState;
+
+/// Requests allowed per client per window, overridden by `RATE_LIMIT_PER_MINUTE`
+pub const DEFAULT_RATE_LIMIT_PER_MINUTE: u32 = 300;
+
+const WINDOW_SECS: i64 = 60;
+
+/// Where a client stands in its current window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitState {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Unix time (seconds) at which the window resets
+    pub reset_at: i64,
+}
+
+struct Window {
+    started: i64,
+    count: u32,
+}
+
+/// Fixed-window request counter keyed by client IP
+pub struct RateLimiter {
+    limit: u32,
+    windows: DashMap<IpAddr, Window>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            windows: DashMap::new(),
+        }
+    }
+
+    /// Count a request from `ip`, or return `Err` when it is over the limit
+    pub fn check(&self, ip: IpAddr) -> Result<RateLimitState, RateLimitState> {
+        self.check_at(ip, chrono::Utc::now().timestamp())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: i64) -> Result<RateLimitState, RateLimitState> {
+        let mut window = self.windows.entry(ip).or_insert(Window {
+            started: now,
+            count: 0,
+        });
+        if now - window.started >= WINDOW_SECS {
+            *window = Window {
+                started: now,
+                count: 0,
+            };
+        }
+
+        let mut state = RateLimitState {
+            limit: self.limit,
+            remaining: 0,
+            reset_at: window.started + WINDOW_SECS,
+        };
+        if window.count >= self.limit {
+            return Err(state);
+        }
+        window.count += 1;
+        state.remaining = self.limit - window.count;
+        Ok(state)
+    }
+
+    /// Forget clients whose window has ended; returns how many were dropped
+    pub fn prune(&self) -> usize {
+        let now = chrono::Utc::now().timestamp();
+        let before = self.windows.len();
+        self.windows.retain(|_, w| now - w.started < WINDOW_SECS);
+        before - self.windows.len()
+    }
+}
+
+/// Reject clients over their limit with 429 and tag every response with a `RateLimitState`
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (mut response, limit) = match state.rate_limiter.check(ip) {
+        Ok(limit) => (next.run(request).await, limit),
+        Err(limit) => (StatusCode::TOO_MANY_REQUESTS.into_response(), limit),
+    };
+    response.extensions_mut().insert(limit);
+    response
+}
+
+/// Adds `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset`
+/// to responses carrying a `RateLimitState`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimitHeaderLayer;
+
+impl<S> Layer<S> for RateLimitHeaderLayer {
+    type Service = MapResponse<S, fn(Response) -> Response>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MapResponse::new(inner, add_rate_limit_headers)
+    }
+}
+
+fn add_rate_limit_headers(mut response: Response) -> Response {
+    if let Some(state) = response.extensions().get::<RateLimitState>().copied() {
+        let headers = response.headers_mut();
+        headers.insert("x-ratelimit-limit", HeaderValue::from(state.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(state.remaining));
+        headers.insert("x-ratelimit-reset", HeaderValue::from(state.reset_at));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::{service_fn, ServiceExt};
+
+    #[test]
+    fn test_window_limits_and_resets() {
+        let limiter = RateLimiter::new(2);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+
+        assert_eq!(limiter.check_at(ip, 100).unwrap().remaining, 1);
+        assert_eq!(limiter.check_at(ip, 110).unwrap().remaining, 0);
+        let denied = limiter.check_at(ip, 120).unwrap_err();
+        assert_eq!(denied.remaining, 0);
+        assert_eq!(denied.reset_at, 160);
+
+        // Clients are counted separately
+        assert_eq!(limiter.check_at(other, 120).unwrap().remaining, 1);
+
+        // A new window starts once the old one has run out
+        let state = limiter.check_at(ip, 160).unwrap();
+        assert_eq!(state.remaining, 1);
+        assert_eq!(state.reset_at, 220);
+    }
+
+    #[tokio::test]
+    async fn test_header_layer_copies_state_into_headers() {
+        let service = RateLimitHeaderLayer.layer(service_fn(|tagged: bool| async move {
+            let mut response = Response::new(Body::empty());
+            if tagged {
+                response.extensions_mut().insert(RateLimitState {
+                    limit: 300,
+                    remaining: 7,
+                    reset_at: 1_700_000_060,
+                });
+            }
+            Ok::<_, std::convert::Infallible>(response)
+        }));
+
+        let response = service.clone().oneshot(true).await.unwrap();
+        let headers = response.headers();
+        assert_eq!(headers["x-ratelimit-limit"], "300");
+        assert_eq!(headers["x-ratelimit-remaining"], "7");
+        assert_eq!(headers["x-ratelimit-reset"], "1700000060");
+
+        let response = service.oneshot(false).await.unwrap();
+        assert!(response.headers().get("x-ratelimit-limit").is_none());
+    }
+}
@@ -0,0 +1,97 @@
+//! Audit trail of sign-ins and session lifecycle events
+//!
+//! Entries are written in the background on the database's dedicated audit
+//! connection: a failed write is logged and never fails the request that
+//! caused it.
+
+use crate::storage::{AuditEntry, Database};
+use chrono::{SecondsFormat, Utc};
+use std::sync::Arc;
+use tracing::error;
+
+/// Events recorded in `audit_log`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEvent {
+    LoginSuccess,
+    LoginFailure,
+    TokenRefresh,
+    Logout,
+    /// A two-factor login finishes with this rather than `LoginSuccess`
+    TwoFactorSuccess,
+    TwoFactorFailure,
+    SessionCreate,
+    SessionArchive,
+    SessionRestore,
+    /// A session purged for good
+    SessionDelete,
+    SessionTerminate,
+}
+
+impl AuditEvent {
+    /// The `event_type` column
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::LoginSuccess => "login_success",
+            Self::LoginFailure => "login_failure",
+            Self::TokenRefresh => "token_refresh",
+            Self::Logout => "logout",
+            Self::TwoFactorSuccess => "2fa_success",
+            Self::TwoFactorFailure => "2fa_failure",
+            Self::SessionCreate => "session_create",
+            Self::SessionArchive => "session_archive",
+            Self::SessionRestore => "session_restore",
+            Self::SessionDelete => "session_delete",
+            Self::SessionTerminate => "session_terminate",
+        }
+    }
+
+    fn resource_type(self) -> &'static str {
+        match self {
+            Self::SessionCreate
+            | Self::SessionArchive
+            | Self::SessionRestore
+            | Self::SessionDelete
+            | Self::SessionTerminate => "session",
+            _ => "user",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuditLog {
+    db: Arc<Database>,
+}
+
+impl AuditLog {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Record `event` on `resource_id` (a user or session ID) by `user_id`.
+    ///
+    /// Returns immediately; the write happens on a spawned task.
+    pub fn record(
+        &self,
+        event: AuditEvent,
+        user_id: Option<&str>,
+        resource_id: Option<&str>,
+        ip_address: Option<&str>,
+        metadata: Option<serde_json::Value>,
+    ) {
+        let entry = AuditEntry {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            user_id: user_id.map(str::to_string),
+            event_type: event.as_str().to_string(),
+            resource_type: Some(event.resource_type().to_string()),
+            resource_id: resource_id.map(str::to_string),
+            ip_address: ip_address.map(str::to_string),
+            metadata: metadata.map(|m| m.to_string()),
+        };
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            if let Err(e) = db.insert_audit_entry(&entry).await {
+                error!("Failed to write audit entry {:?}: {:#}", entry, e);
+            }
+        });
+    }
+}
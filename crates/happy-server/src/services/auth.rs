@@ -1,23 +1,133 @@
 //! Authentication service
 
-use crate::storage::Database;
+use crate::services::audit::{AuditEvent, AuditLog};
+use crate::services::totp;
+use crate::storage::{Database, MemoryCache, SsoConfig};
 use anyhow::{Context, Result};
 use argon2::password_hash::SaltString;
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use rand::rngs::OsRng;
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
 
+/// How long a deleted account's user row is kept before it is purged
+pub const ACCOUNT_PURGE_DELAY_DAYS: i64 = 30;
+
+/// How long a password reset link stays valid
+pub const PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+
+/// Consecutive failed logins for one email before it is locked
+pub const MAX_FAILED_LOGINS: u32 = 5;
+
+/// How long a lockout lasts, and how long a failed login is remembered
+pub const LOGIN_LOCKOUT_MINUTES: i64 = 15;
+
+/// How long a partial token from a password login can be exchanged for real tokens
+pub const TOTP_CHALLENGE_TTL_MINUTES: i64 = 5;
+/// How recently an account without a password must have signed in to confirm
+/// a destructive action
+pub const RECENT_LOGIN_MINUTES: i64 = 10;
+
+/// `token_type` of the partial token issued before the TOTP code is checked
+const TOTP_TOKEN_TYPE: &str = "totp";
+
+/// A password login that succeeded, possibly pending a second factor
+#[derive(Debug)]
+pub enum LoginOutcome {
+    Tokens(AuthTokens),
+    /// Two-factor login is enabled; exchange `partial_token` and a code at
+    /// `AuthService::totp_challenge`
+    TotpRequired { partial_token: String },
+}
+
+/// Why a password login was refused
+#[derive(Debug)]
+pub enum LoginError {
+    InvalidCredentials,
+    /// Too many failed attempts; logins are refused until this time
+    Locked(DateTime<Utc>),
+    /// The email's domain signs in through SSO, never with a password
+    SsoRequired,
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for LoginError {
+    fn from(e: anyhow::Error) -> Self {
+        LoginError::Internal(e)
+    }
+}
+
+impl LoginError {
+    /// Recorded as the `reason` of a failed login's audit entry
+    fn reason(&self) -> &'static str {
+        match self {
+            LoginError::InvalidCredentials => "invalid_credentials",
+            LoginError::Locked(_) => "locked",
+            LoginError::SsoRequired => "sso_required",
+            LoginError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl std::fmt::Display for LoginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoginError::InvalidCredentials => write!(f, "Invalid credentials"),
+            LoginError::Locked(until) => write!(f, "Account locked until {}", until),
+            LoginError::SsoRequired => write!(f, "Sign in through SSO"),
+            LoginError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 pub struct AuthService {
     db: Arc<Database>,
+    /// Failed login counters and lockouts, keyed by email
+    cache: Arc<MemoryCache>,
     jwt_secret: String,
+    totp_key: totp::SecretKey,
+    /// Lowercased email domain that must sign in through SSO
+    sso_domain: Option<String>,
+    audit: AuditLog,
+}
+
+/// A TOTP secret to enroll in an authenticator app
+#[derive(Debug)]
+pub struct TotpSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
 }
 
 impl AuthService {
-    pub fn new(db: Arc<Database>, jwt_secret: String) -> Self {
-        Self { db, jwt_secret }
+    /// With `sso`, passwords are refused for emails in its domain
+    pub fn new(
+        db: Arc<Database>,
+        cache: Arc<MemoryCache>,
+        jwt_secret: String,
+        sso: Option<SsoConfig>,
+    ) -> Self {
+        Self {
+            audit: AuditLog::new(db.clone()),
+            db,
+            cache,
+            totp_key: totp::SecretKey::from_jwt_secret(&jwt_secret),
+            jwt_secret,
+            sso_domain: sso.map(|config| config.domain.to_ascii_lowercase()),
+        }
+    }
+
+    /// Whether `email` belongs to the SSO domain, so its password is never checked
+    pub fn requires_sso(&self, email: &str) -> bool {
+        let Some(domain) = &self.sso_domain else {
+            return false;
+        };
+        email
+            .rsplit_once('@')
+            .is_some_and(|(_, email_domain)| email_domain.eq_ignore_ascii_case(domain))
     }
 
     pub async fn register(
@@ -26,28 +136,55 @@ impl AuthService {
         password: &str,
         name: Option<&str>,
     ) -> Result<AuthTokens> {
-        // Hash password
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?
-            .to_string();
+        if self.requires_sso(email) {
+            anyhow::bail!("{} must sign in through SSO", email);
+        }
+        let password_hash = hash_password(password)?;
 
         // Create user in database
         let user_id = self.db.create_user(email, &password_hash, name).await?;
 
         // Generate tokens
-        self.generate_tokens(&user_id).await
+        self.generate_tokens(&user_id, Utc::now().timestamp()).await
     }
 
-    pub async fn login(&self, email: &str, password: &str) -> Result<AuthTokens> {
+    /// Check an email and password, locking the email after
+    /// `MAX_FAILED_LOGINS` consecutive failures.
+    ///
+    /// Unknown emails count failures and lock like real accounts, so the
+    /// responses don't reveal which emails are registered. Accounts with
+    /// two-factor login enabled get a partial token instead of real ones,
+    /// and emails in the SSO domain are refused outright.
+    ///
+    /// Audited as `login_success` or `login_failure`; a password accepted
+    /// pending a second factor is audited by `totp_challenge` instead.
+    pub async fn login(
+        &self,
+        email: &str,
+        password: &str,
+        ip: Option<&str>,
+    ) -> Result<LoginOutcome, LoginError> {
+        if self.requires_sso(email) {
+            let error = LoginError::SsoRequired;
+            self.audit_failure(AuditEvent::LoginFailure, None, email, ip, &error);
+            return Err(error);
+        }
+
         // Get user from database
         let user = self.db.get_user_by_email(email).await?;
+        let user_id = user.as_ref().map(|(user_id, _)| user_id.as_str());
+
+        let stored_lock = self.db.get_user_locked_until(email).await?;
+        let lock = self.cached_lock(email).max(stored_lock);
+        if let Some(until) = lock.filter(|until| *until > Utc::now()) {
+            let error = LoginError::Locked(until);
+            self.audit_failure(AuditEvent::LoginFailure, user_id, email, ip, &error);
+            return Err(error);
+        }
 
-        if let Some((user_id, password_hash)) = user {
+        if let Some((user_id, password_hash)) = &user {
             // Verify password
-            let parsed_hash = PasswordHash::new(&password_hash)
+            let parsed_hash = PasswordHash::new(password_hash)
                 .map_err(|e| anyhow::anyhow!("Invalid password hash: {}", e))?;
             let argon2 = Argon2::default();
 
@@ -55,14 +192,298 @@ impl AuthService {
                 .verify_password(password.as_bytes(), &parsed_hash)
                 .is_ok()
             {
-                return self.generate_tokens(&user_id).await;
+                self.cache.delete(&failed_logins_key(email));
+                if stored_lock.is_some() {
+                    self.db.set_user_locked_until(email, None).await?;
+                }
+                if matches!(self.db.get_user_totp(user_id).await?, Some((_, true))) {
+                    let partial_token = self.generate_totp_token(user_id).await?;
+                    return Ok(LoginOutcome::TotpRequired { partial_token });
+                }
+                let tokens = self.generate_tokens(user_id, Utc::now().timestamp()).await?;
+                self.audit.record(
+                    AuditEvent::LoginSuccess,
+                    Some(user_id),
+                    Some(user_id),
+                    ip,
+                    Some(serde_json::json!({ "method": "password" })),
+                );
+                return Ok(LoginOutcome::Tokens(tokens));
             }
         }
 
-        anyhow::bail!("Invalid credentials")
+        let error = self.login_failed(email).await?;
+        self.audit_failure(AuditEvent::LoginFailure, user_id, email, ip, &error);
+        Err(error)
+    }
+
+    fn audit_failure(
+        &self,
+        event: AuditEvent,
+        user_id: Option<&str>,
+        email: &str,
+        ip: Option<&str>,
+        error: &LoginError,
+    ) {
+        self.audit.record(
+            event,
+            user_id,
+            user_id,
+            ip,
+            Some(serde_json::json!({ "email": email, "reason": error.reason() })),
+        );
+    }
+
+    /// Exchange the partial token from `login` and a TOTP code for real tokens,
+    /// returning the user's ID with them.
+    ///
+    /// Wrong codes count towards the same lockout as wrong passwords.
+    /// Audited as `2fa_success` or `2fa_failure`; a bad partial token isn't,
+    /// since it doesn't say who tried.
+    pub async fn totp_challenge(
+        &self,
+        partial_token: &str,
+        code: &str,
+        ip: Option<&str>,
+    ) -> Result<(String, AuthTokens), LoginError> {
+        let claims = self
+            .validated_claims(partial_token)
+            .await
+            .map_err(|_| LoginError::InvalidCredentials)?;
+        if claims.token_type != TOTP_TOKEN_TYPE {
+            return Err(LoginError::InvalidCredentials);
+        }
+        let Some((_, email, _)) = self.db.get_user_by_id(&claims.sub).await? else {
+            return Err(LoginError::InvalidCredentials);
+        };
+        let user_id = Some(claims.sub.as_str());
+
+        let stored_lock = self.db.get_user_locked_until(&email).await?;
+        let lock = self.cached_lock(&email).max(stored_lock);
+        if let Some(until) = lock.filter(|until| *until > Utc::now()) {
+            let error = LoginError::Locked(until);
+            self.audit_failure(AuditEvent::TwoFactorFailure, user_id, &email, ip, &error);
+            return Err(error);
+        }
+
+        if !self.check_totp_code(&claims.sub, code).await? {
+            let error = self.login_failed(&email).await?;
+            self.audit_failure(AuditEvent::TwoFactorFailure, user_id, &email, ip, &error);
+            return Err(error);
+        }
+
+        self.cache.delete(&failed_logins_key(&email));
+        let tokens = self.generate_tokens(&claims.sub, Utc::now().timestamp()).await?;
+        self.audit
+            .record(AuditEvent::TwoFactorSuccess, user_id, user_id, ip, None);
+        Ok((claims.sub, tokens))
+    }
+
+    /// Count a failed login for `email`, locking it once there have been too many
+    async fn login_failed(&self, email: &str) -> Result<LoginError> {
+        let failures = self.record_failed_login(email);
+        if failures < MAX_FAILED_LOGINS {
+            return Ok(LoginError::InvalidCredentials);
+        }
+
+        let until = Utc::now() + Duration::minutes(LOGIN_LOCKOUT_MINUTES);
+        self.cache.delete(&failed_logins_key(email));
+        self.cache.insert_pinned_with_ttl(
+            login_lock_key(email),
+            until.timestamp().to_string().into_bytes(),
+            lockout_duration(),
+        );
+        self.db.set_user_locked_until(email, Some(until)).await?;
+        Ok(LoginError::Locked(until))
+    }
+
+    /// Start enrolling a new TOTP secret; it isn't required at login until
+    /// `totp_verify` accepts a code from it.
+    ///
+    /// Returns `None` if two-factor login is already enabled.
+    pub async fn totp_setup(&self, user_id: &str) -> Result<Option<TotpSetup>> {
+        if matches!(self.db.get_user_totp(user_id).await?, Some((_, true))) {
+            return Ok(None);
+        }
+        let (_, email, _) = self.db.get_user_by_id(user_id).await?.context("User not found")?;
+
+        let secret = totp::generate_secret();
+        self.db
+            .set_user_totp_secret(user_id, &self.totp_key.seal(&secret)?)
+            .await?;
+
+        Ok(Some(TotpSetup {
+            otpauth_uri: totp::otpauth_uri(&secret, &email),
+            secret,
+        }))
+    }
+
+    /// Enable two-factor login if `code` matches the secret from `totp_setup`
+    pub async fn totp_verify(&self, user_id: &str, code: &str) -> Result<bool> {
+        if !self.check_totp_code(user_id, code).await? {
+            return Ok(false);
+        }
+        self.db.enable_user_totp(user_id).await
+    }
+
+    async fn check_totp_code(&self, user_id: &str, code: &str) -> Result<bool> {
+        let Some((sealed, _)) = self.db.get_user_totp(user_id).await? else {
+            return Ok(false);
+        };
+        let secret = self.totp_key.open(&sealed)?;
+        let Some(step) = totp::verify(&secret, code, Utc::now().timestamp() as u64) else {
+            return Ok(false);
+        };
+        // Each code works once: replaying it, or an older one, is refused
+        self.db.use_user_totp_step(user_id, step as i64).await
+    }
+
+    /// Lift a lockout early and forget past failures.
+    ///
+    /// Returns `false` if there is no account for `email`.
+    pub async fn unlock(&self, email: &str) -> Result<bool> {
+        self.cache.delete(&failed_logins_key(email));
+        self.cache.delete(&login_lock_key(email));
+        self.db.set_user_locked_until(email, None).await
+    }
+
+    /// Count one more failure for `email`, returning the new total
+    fn record_failed_login(&self, email: &str) -> u32 {
+        let key = failed_logins_key(email);
+        let failures = self
+            .cache
+            .get(&key)
+            .and_then(|v| String::from_utf8(v).ok())
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(0)
+            + 1;
+        self.cache
+            .insert_pinned_with_ttl(key, failures.to_string().into_bytes(), lockout_duration());
+        failures
+    }
+
+    fn cached_lock(&self, email: &str) -> Option<DateTime<Utc>> {
+        let secs = String::from_utf8(self.cache.get(&login_lock_key(email))?).ok()?;
+        DateTime::from_timestamp(secs.parse().ok()?, 0)
+    }
+
+    /// Sign in a user whose identity was verified by an external provider,
+    /// creating the account on first login.
+    ///
+    /// `method` names the provider in the `login_success` audit entry.
+    pub async fn login_external(
+        &self,
+        email: &str,
+        name: Option<&str>,
+        method: &str,
+        ip: Option<&str>,
+    ) -> Result<AuthTokens> {
+        let user_id = match self.db.get_user_by_email(email).await? {
+            Some((user_id, _)) => user_id,
+            // No usable password hash: the account can only sign in through the provider
+            None => self.db.create_user(email, "", name).await?,
+        };
+
+        let tokens = self.generate_tokens(&user_id, Utc::now().timestamp()).await?;
+        self.audit.record(
+            AuditEvent::LoginSuccess,
+            Some(&user_id),
+            Some(&user_id),
+            ip,
+            Some(serde_json::json!({ "method": method })),
+        );
+        Ok(tokens)
+    }
+
+    /// Confirm a destructive action by the holder of `token`: the user's
+    /// current password or, for accounts created through SSO that have none,
+    /// a sign-in within the last `RECENT_LOGIN_MINUTES`
+    pub async fn confirm_owner(&self, token: &str, password: Option<&str>) -> Result<bool> {
+        let claims = self.validated_claims(token).await?;
+        let Some(password_hash) = self.db.get_user_password_hash(&claims.sub).await? else {
+            return Ok(false);
+        };
+
+        let Ok(parsed_hash) = PasswordHash::new(&password_hash) else {
+            let signed_in = DateTime::from_timestamp(claims.auth_time, 0).unwrap_or_default();
+            return Ok(Utc::now() - signed_in <= Duration::minutes(RECENT_LOGIN_MINUTES));
+        };
+
+        Ok(password.is_some_and(|password| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed_hash)
+                .is_ok()
+        }))
+    }
+
+    /// Create a password reset token for `email`, or `None` if there is no such account.
+    ///
+    /// Only the token's SHA-256 is stored; the returned token goes into the emailed link.
+    pub async fn create_password_reset(&self, email: &str) -> Result<Option<String>> {
+        let Some((user_id, _)) = self.db.get_user_by_email(email).await? else {
+            return Ok(None);
+        };
+
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+
+        let expires_at = Utc::now() + Duration::minutes(PASSWORD_RESET_TTL_MINUTES);
+        self.db
+            .create_password_reset_token(&hash_reset_token(&token), &user_id, expires_at)
+            .await?;
+
+        Ok(Some(token))
+    }
+
+    /// Set a new password with a reset token and sign the user out everywhere.
+    ///
+    /// Returns `false` if the token is unknown, used or expired.
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> Result<bool> {
+        let password_hash = hash_password(new_password)?;
+        let user_id = self
+            .db
+            .reset_password_with_token(&hash_reset_token(token), &password_hash)
+            .await?;
+
+        Ok(user_id.is_some())
     }
 
     pub async fn validate_token(&self, token: &str) -> Result<String> {
+        let claims = self.validated_claims(token).await?;
+        if claims.token_type == TOTP_TOKEN_TYPE {
+            anyhow::bail!("Two-factor authentication not completed");
+        }
+        Ok(claims.sub)
+    }
+
+    /// Exchange a refresh token for a new token pair, returning the user's ID with it
+    pub async fn refresh(
+        &self,
+        refresh_token: &str,
+        ip: Option<&str>,
+    ) -> Result<(String, AuthTokens)> {
+        let claims = self.validated_claims(refresh_token).await?;
+        if claims.token_type != "refresh" {
+            anyhow::bail!("Not a refresh token");
+        }
+
+        // Refreshing isn't signing in again, so the sign-in time carries over
+        let tokens = self.generate_tokens(&claims.sub, claims.auth_time).await?;
+        let user_id = Some(claims.sub.as_str());
+        self.audit
+            .record(AuditEvent::TokenRefresh, user_id, user_id, ip, None);
+        Ok((claims.sub, tokens))
+    }
+
+    /// Record that the user signed out. Tokens stay valid until a password
+    /// reset revokes them; the client is trusted to discard its copies.
+    pub fn logout(&self, user_id: &str, ip: Option<&str>) {
+        self.audit
+            .record(AuditEvent::Logout, Some(user_id), Some(user_id), ip, None);
+    }
+
+    async fn validated_claims(&self, token: &str) -> Result<Claims> {
         let validation = Validation::default();
         let token_data = decode::<Claims>(
             token,
@@ -70,11 +491,48 @@ impl AuthService {
             &validation,
         )?;
 
-        Ok(token_data.claims.sub)
+        // A password reset bumps the version, revoking every earlier token
+        let version = self.db.get_user_token_version(&token_data.claims.sub).await?;
+        if version != Some(token_data.claims.ver) {
+            anyhow::bail!("Token has been revoked");
+        }
+
+        Ok(token_data.claims)
+    }
+
+    /// Short-lived token proving the password was right, for `totp_challenge`
+    async fn generate_totp_token(&self, user_id: &str) -> Result<String> {
+        let now = Utc::now();
+        let ver = self
+            .db
+            .get_user_token_version(user_id)
+            .await?
+            .context("User not found")?;
+
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp: (now + Duration::minutes(TOTP_CHALLENGE_TTL_MINUTES)).timestamp(),
+            iat: now.timestamp(),
+            token_type: TOTP_TOKEN_TYPE.to_string(),
+            ver,
+            auth_time: now.timestamp(),
+        };
+
+        Ok(encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )?)
     }
 
-    async fn generate_tokens(&self, user_id: &str) -> Result<AuthTokens> {
+    /// Access and refresh tokens for a user who signed in at `auth_time`
+    async fn generate_tokens(&self, user_id: &str, auth_time: i64) -> Result<AuthTokens> {
         let now = Utc::now();
+        let ver = self
+            .db
+            .get_user_token_version(user_id)
+            .await?
+            .context("User not found")?;
 
         // Access token (permanent - 100 years)
         // Using a very long expiration instead of no expiration to maintain JWT compatibility
@@ -84,6 +542,8 @@ impl AuthService {
             exp: access_exp.timestamp(),
             iat: now.timestamp(),
             token_type: "access".to_string(),
+            ver,
+            auth_time,
         };
 
         let access_token = encode(
@@ -99,6 +559,8 @@ impl AuthService {
             exp: refresh_exp.timestamp(),
             iat: now.timestamp(),
             token_type: "refresh".to_string(),
+            ver,
+            auth_time,
         };
 
         let refresh_token = encode(
@@ -122,6 +584,37 @@ struct Claims {
     exp: i64,    // expiration time
     iat: i64,    // issued at
     token_type: String,
+    /// The user's `token_version` at issue; tokens from before it existed count as 0
+    #[serde(default)]
+    ver: i64,
+    /// When the user signed in, kept across refreshes; 0 for tokens from before it existed
+    #[serde(default)]
+    auth_time: i64,
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?
+        .to_string())
+}
+
+fn failed_logins_key(email: &str) -> String {
+    format!("login_failures:{}", email.to_lowercase())
+}
+
+fn login_lock_key(email: &str) -> String {
+    format!("login_lock:{}", email.to_lowercase())
+}
+
+fn lockout_duration() -> std::time::Duration {
+    std::time::Duration::from_secs(LOGIN_LOCKOUT_MINUTES as u64 * 60)
+}
+
+/// Reset tokens are looked up by their SHA-256, so a leaked table can't be replayed
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
 }
 
 #[derive(Debug, Clone)]
@@ -130,3 +623,251 @@ pub struct AuthTokens {
     pub refresh_token: String,
     pub expires_in: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DatabaseConfig;
+
+    #[tokio::test]
+    async fn test_login_lockout() {
+        let dir = std::env::temp_dir().join(format!("happy-auth-{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(
+            Database::new(dir.join("test.db").to_str().unwrap(), DatabaseConfig::default())
+                .await
+                .unwrap(),
+        );
+        let hash = hash_password("correct horse").unwrap();
+        db.create_user("a@example.com", &hash, None).await.unwrap();
+        let cache = Arc::new(MemoryCache::new());
+        let auth = AuthService::new(db.clone(), cache, "secret".into(), None);
+
+        for _ in 1..MAX_FAILED_LOGINS {
+            assert!(matches!(
+                auth.login("a@example.com", "wrong", None).await,
+                Err(LoginError::InvalidCredentials)
+            ));
+        }
+        assert!(matches!(
+            auth.login("a@example.com", "wrong", None).await,
+            Err(LoginError::Locked(_))
+        ));
+        // The right password doesn't help while locked
+        assert!(matches!(
+            auth.login("a@example.com", "correct horse", None).await,
+            Err(LoginError::Locked(_))
+        ));
+        // The lock survives a restart
+        let cache = Arc::new(MemoryCache::new());
+        let restarted = AuthService::new(db.clone(), cache, "secret".into(), None);
+        assert!(matches!(
+            restarted.login("a@example.com", "correct horse", None).await,
+            Err(LoginError::Locked(_))
+        ));
+
+        assert!(restarted.unlock("a@example.com").await.unwrap());
+        assert!(restarted.login("a@example.com", "correct horse", None).await.is_ok());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_login_audited() {
+        let dir = std::env::temp_dir().join(format!("happy-auth-{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(
+            Database::new(dir.join("test.db").to_str().unwrap(), DatabaseConfig::default())
+                .await
+                .unwrap(),
+        );
+        let hash = hash_password("correct horse").unwrap();
+        let user_id = db.create_user("a@example.com", &hash, None).await.unwrap();
+        let cache = Arc::new(MemoryCache::new());
+        let auth = AuthService::new(db.clone(), cache, "secret".into(), None);
+
+        let ip = Some("203.0.113.7");
+        assert!(auth.login("nobody@example.com", "wrong", ip).await.is_err());
+        assert!(auth.login("a@example.com", "correct horse", ip).await.is_ok());
+        auth.logout(&user_id, ip);
+
+        // Writes happen in the background
+        let mut entries = Vec::new();
+        for _ in 0..50 {
+            entries = db.list_audit_entries(None, 10).await.unwrap();
+            if entries.len() == 3 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let mut events: Vec<_> = entries
+            .iter()
+            .map(|e| (e.entry.event_type.as_str(), e.entry.user_id.as_deref()))
+            .collect();
+        events.sort();
+        assert_eq!(
+            events,
+            [
+                ("login_failure", None),
+                ("login_success", Some(user_id.as_str())),
+                ("logout", Some(user_id.as_str())),
+            ]
+        );
+        let failure = entries
+            .iter()
+            .find(|e| e.entry.event_type == "login_failure")
+            .unwrap();
+        assert_eq!(failure.entry.ip_address.as_deref(), ip);
+        assert!(failure.entry.metadata.as_ref().unwrap().contains("invalid_credentials"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_totp_login() {
+        let dir = std::env::temp_dir().join(format!("happy-auth-{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(
+            Database::new(dir.join("test.db").to_str().unwrap(), DatabaseConfig::default())
+                .await
+                .unwrap(),
+        );
+        let hash = hash_password("correct horse").unwrap();
+        let user_id = db.create_user("a@example.com", &hash, None).await.unwrap();
+        let cache = Arc::new(MemoryCache::new());
+        let auth = AuthService::new(db.clone(), cache, "secret".into(), None);
+
+        let setup = auth.totp_setup(&user_id).await.unwrap().unwrap();
+        assert!(setup.otpauth_uri.contains(&setup.secret));
+        // Not required until a code has been verified
+        assert!(matches!(
+            auth.login("a@example.com", "correct horse", None).await,
+            Ok(LoginOutcome::Tokens(_))
+        ));
+        let now = Utc::now().timestamp() as u64;
+        let code = totp::code_for(&setup.secret, now);
+        assert!(auth.totp_verify(&user_id, &code).await.unwrap());
+        assert!(auth.totp_setup(&user_id).await.unwrap().is_none());
+
+        let Ok(LoginOutcome::TotpRequired { partial_token }) =
+            auth.login("a@example.com", "correct horse", None).await
+        else {
+            panic!("expected a TOTP challenge");
+        };
+        // The partial token isn't an access token
+        assert!(auth.validate_token(&partial_token).await.is_err());
+        assert!(auth.refresh(&partial_token, None).await.is_err());
+
+        // The code that enabled two-factor login is spent; the next one works
+        assert!(matches!(
+            auth.totp_challenge(&partial_token, &code, None).await,
+            Err(LoginError::InvalidCredentials)
+        ));
+        let code = totp::code_for(&setup.secret, now + totp::PERIOD_SECS);
+        let (id, tokens) = auth.totp_challenge(&partial_token, &code, None).await.unwrap();
+        assert_eq!(id, user_id);
+        assert_eq!(auth.validate_token(&tokens.access_token).await.unwrap(), user_id);
+        // Access tokens can't stand in for the partial token
+        assert!(matches!(
+            auth.totp_challenge(&tokens.access_token, &code, None).await,
+            Err(LoginError::InvalidCredentials)
+        ));
+
+        // Wrong codes lock the account like wrong passwords
+        let wrong = if code == "000000" { "111111" } else { "000000" };
+        for _ in 1..MAX_FAILED_LOGINS {
+            assert!(matches!(
+                auth.totp_challenge(&partial_token, wrong, None).await,
+                Err(LoginError::InvalidCredentials)
+            ));
+        }
+        assert!(matches!(
+            auth.totp_challenge(&partial_token, wrong, None).await,
+            Err(LoginError::Locked(_))
+        ));
+        assert!(matches!(
+            auth.totp_challenge(&partial_token, &code, None).await,
+            Err(LoginError::Locked(_))
+        ));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_sso_domain_skips_password() {
+        let dir = std::env::temp_dir().join(format!("happy-auth-{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(
+            Database::new(dir.join("test.db").to_str().unwrap(), DatabaseConfig::default())
+                .await
+                .unwrap(),
+        );
+        let hash = hash_password("correct horse").unwrap();
+        db.create_user("a@Corp.example.com", &hash, None).await.unwrap();
+        db.create_user("b@example.com", &hash, None).await.unwrap();
+        let sso = SsoConfig {
+            idp_metadata_url: "https://idp.example.com/metadata".into(),
+            entity_id: "https://happy.example.com".into(),
+            certificate: String::new(),
+            domain: "corp.example.com".into(),
+        };
+        let cache = Arc::new(MemoryCache::new());
+        let auth = AuthService::new(db.clone(), cache, "secret".into(), Some(sso));
+
+        // Even the right password is refused, and doesn't count towards a lockout
+        assert!(matches!(
+            auth.login("a@Corp.example.com", "correct horse", None).await,
+            Err(LoginError::SsoRequired)
+        ));
+        assert!(auth.register("new@corp.example.com", "password", None).await.is_err());
+        assert!(!auth.requires_sso("a@notcorp.example.com"));
+        assert!(matches!(
+            auth.login("b@example.com", "correct horse", None).await,
+            Ok(LoginOutcome::Tokens(_))
+        ));
+
+        // Signing in through the IdP issues ordinary tokens
+        let tokens = auth.login_external("a@Corp.example.com", None, "saml", None).await.unwrap();
+        let user_id = auth.validate_token(&tokens.access_token).await.unwrap();
+        let (id, _) = db.get_user_by_email("a@Corp.example.com").await.unwrap().unwrap();
+        assert_eq!(id, user_id);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_confirm_owner() {
+        let dir = std::env::temp_dir().join(format!("happy-auth-{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(
+            Database::new(dir.join("test.db").to_str().unwrap(), DatabaseConfig::default())
+                .await
+                .unwrap(),
+        );
+        let hash = hash_password("correct horse").unwrap();
+        db.create_user("a@example.com", &hash, None).await.unwrap();
+        let cache = Arc::new(MemoryCache::new());
+        let auth = AuthService::new(db.clone(), cache, "secret".into(), None);
+
+        // Accounts with a password confirm with it, however recent the sign-in
+        let Ok(LoginOutcome::Tokens(tokens)) =
+            auth.login("a@example.com", "correct horse", None).await
+        else {
+            panic!("login failed");
+        };
+        let token = &tokens.access_token;
+        assert!(auth.confirm_owner(token, Some("correct horse")).await.unwrap());
+        assert!(!auth.confirm_owner(token, Some("wrong")).await.unwrap());
+        assert!(!auth.confirm_owner(token, None).await.unwrap());
+
+        // SSO accounts have none, so a fresh sign-in is the confirmation
+        let tokens = auth.login_external("sso@example.com", None, "oidc", None).await.unwrap();
+        assert!(auth.confirm_owner(&tokens.access_token, None).await.unwrap());
+        assert!(auth.confirm_owner(&tokens.access_token, Some("")).await.unwrap());
+
+        let user_id = auth.validate_token(&tokens.access_token).await.unwrap();
+        let stale = (Utc::now() - Duration::minutes(RECENT_LOGIN_MINUTES + 1)).timestamp();
+        let tokens = auth.generate_tokens(&user_id, stale).await.unwrap();
+        assert!(!auth.confirm_owner(&tokens.access_token, None).await.unwrap());
+        // Refreshing doesn't count as signing in again
+        let (_, refreshed) = auth.refresh(&tokens.refresh_token, None).await.unwrap();
+        assert!(!auth.confirm_owner(&refreshed.access_token, None).await.unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -4,8 +4,12 @@ use crate::storage::{Database, MemoryCache};
 use anyhow::Result;
 use happy_core::{Machine, MachineInfo, Platform};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{debug, info};
 
+/// How long a cached copy of a machine lives; the database stays the source of truth
+const CACHE_TTL: Duration = Duration::from_secs(600);
+
 pub struct MachineRegistry {
     db: Arc<Database>,
     cache: Arc<MemoryCache>,
@@ -40,7 +44,7 @@ impl MachineRegistry {
             // Update cache
             let machine_key = format!("machine:{}", machine_id);
             let machine_json = serde_json::to_vec(&existing)?;
-            self.cache.set(machine_key, machine_json);
+            self.cache.insert_with_ttl(machine_key, machine_json, CACHE_TTL);
 
             return Ok(existing);
         }
@@ -59,7 +63,7 @@ impl MachineRegistry {
         // Cache machine
         let machine_key = format!("machine:{}", machine.id);
         let machine_json = serde_json::to_vec(&machine)?;
-        self.cache.set(machine_key, machine_json);
+        self.cache.insert_with_ttl(machine_key, machine_json, CACHE_TTL);
 
         Ok(machine)
     }
@@ -74,7 +78,12 @@ impl MachineRegistry {
         }
 
         // Fall back to database
-        self.db.get_machine(id).await
+        let machine = self.db.get_machine(id).await?;
+        if let Some(machine) = &machine {
+            self.cache
+                .insert_with_ttl(machine_key, serde_json::to_vec(machine)?, CACHE_TTL);
+        }
+        Ok(machine)
     }
 
     pub async fn update_machine_status(&self, id: &str, is_online: bool) -> Result<()> {
@@ -88,7 +97,7 @@ impl MachineRegistry {
                 if let Ok(mut machine) = serde_json::from_slice::<Machine>(&data) {
                     machine.last_seen = chrono::Utc::now();
                     let machine_json = serde_json::to_vec(&machine)?;
-                    self.cache.set(machine_key, machine_json);
+                    self.cache.insert_with_ttl(machine_key, machine_json, CACHE_TTL);
                 }
             }
         }
@@ -96,7 +105,7 @@ impl MachineRegistry {
         // Update online status in cache
         let status_key = format!("machine:{}:online", id);
         if is_online {
-            self.cache.set(status_key, vec![1]);
+            self.cache.insert_permanent(status_key, vec![1]);
         } else {
             self.cache.delete(&status_key);
         }
@@ -104,40 +113,130 @@ impl MachineRegistry {
         Ok(())
     }
 
+    /// The Ed25519 key this machine signs attaches with, once one has been seen
+    pub async fn signing_key(&self, machine_id: &str) -> Result<Option<Vec<u8>>> {
+        self.db.get_machine_signing_key(machine_id).await
+    }
+
+    /// Trust `key` for this machine from now on; an already pinned key is kept
+    pub async fn pin_signing_key(&self, machine_id: &str, key: &[u8]) -> Result<()> {
+        if self.db.set_machine_signing_key(machine_id, key).await? {
+            info!("Pinned signing key for machine {}", machine_id);
+        }
+        Ok(())
+    }
+
+    /// Record the host details a daemon reported on attach
+    pub async fn update_machine_info(&self, info: &MachineInfo) -> Result<()> {
+        debug!("Updating machine {} info: {:?}", info.id, info);
+
+        self.db.update_machine_info(info).await?;
+
+        // Update cache if present
+        let machine_key = format!("machine:{}", info.id);
+        if let Some(data) = self.cache.get(&machine_key) {
+            if let Ok(mut machine) = serde_json::from_slice::<Machine>(&data) {
+                let now = chrono::Utc::now();
+                machine.platform = info.platform;
+                machine.capabilities = info.capabilities.clone();
+                machine.hostname = info.hostname.clone();
+                machine.daemon_version = info.daemon_version.clone();
+                machine.arch = info.arch.clone();
+                machine.cpu_count = info.cpu_count;
+                machine.memory_mb = info.memory_mb;
+                machine.last_heartbeat = Some(now);
+                machine.last_seen = now;
+                let machine_json = serde_json::to_vec(&machine)?;
+                self.cache.insert_with_ttl(machine_key, machine_json, CACHE_TTL);
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn list_user_machines(&self, user_id: &str) -> Result<Vec<MachineInfo>> {
         let machines = self.db.list_machines_by_user(user_id).await?;
+        Ok(machines.into_iter().map(|m| self.machine_info(m)).collect())
+    }
 
-        // Convert to MachineInfo
-        let infos: Vec<MachineInfo> = machines
-            .into_iter()
-            .map(|m| {
-                let status_key = format!("machine:{}:online", m.id);
-                let is_online = self.cache.exists(&status_key);
-                MachineInfo {
-                    id: m.id,
-                    name: m.name,
-                    platform: m.platform,
-                    last_seen: m.last_seen,
-                    is_online,
-                    capabilities: m.capabilities,
-                }
-            })
-            .collect();
+    /// A user's machines whose name or hostname match `query` as word prefixes
+    pub async fn list_user_machines_search(
+        &self,
+        user_id: &str,
+        query: &str,
+    ) -> Result<Vec<MachineInfo>> {
+        let machines = self.db.search_machines_by_user(user_id, query).await?;
+        Ok(machines.into_iter().map(|m| self.machine_info(m)).collect())
+    }
+
+    fn machine_info(&self, m: Machine) -> MachineInfo {
+        let status_key = format!("machine:{}:online", m.id);
+        let is_online = self.cache.exists(&status_key);
+        MachineInfo {
+            id: m.id,
+            name: m.name,
+            platform: m.platform,
+            last_seen: m.last_seen,
+            is_online,
+            capabilities: m.capabilities,
+            last_heartbeat: m.last_heartbeat,
+            daemon_version: m.daemon_version,
+            arch: m.arch,
+            hostname: m.hostname,
+            cpu_count: m.cpu_count,
+            memory_mb: m.memory_mb,
+        }
+    }
+
+    pub async fn rename_machine(&self, id: &str, name: &str) -> Result<()> {
+        info!("Renaming machine {} to {}", id, name);
+
+        self.db.update_machine_name(id, name).await?;
 
-        Ok(infos)
+        // Next read reloads the renamed machine from the database
+        self.cache.delete(&format!("machine:{}", id));
+
+        Ok(())
     }
 
-    pub async fn unregister_machine(&self, id: &str) -> Result<()> {
+    /// Fold a machine that re-registered under a new ID into that new row.
+    ///
+    /// Sessions on `old_id` move to `new_id` and `old_id` is deleted. Both
+    /// machines must exist; returns the number of sessions moved.
+    pub async fn merge_duplicate_machines(&self, old_id: &str, new_id: &str) -> Result<u64> {
+        if old_id == new_id {
+            anyhow::bail!("Cannot merge machine {} into itself", old_id);
+        }
+        info!("Merging machine {} into {}", old_id, new_id);
+
+        let moved = self.db.merge_machines(old_id, new_id).await?;
+
+        // Moved sessions and the surviving machine reload from the database
+        for session_id in &moved {
+            self.cache.delete(&format!("session:{}", session_id));
+        }
+        self.cache.delete(&format!("machine:{}", new_id));
+        self.cache.delete(&format!("machine:{}", old_id));
+        self.cache.delete(&format!("machine:{}:online", old_id));
+
+        Ok(moved.len() as u64)
+    }
+
+    /// Delete a machine along with its sessions, returning the deleted session ids
+    pub async fn unregister_machine(&self, id: &str) -> Result<Vec<String>> {
         info!("Unregistering machine: {}", id);
 
-        self.db.delete_machine(id).await?;
+        let session_ids = self.db.delete_machine(id).await?;
 
         // Remove from cache
         let machine_key = format!("machine:{}", id);
         let status_key = format!("machine:{}:online", id);
         self.cache.delete(&machine_key);
         self.cache.delete(&status_key);
+        for session_id in &session_ids {
+            self.cache.delete(&format!("session:{}", session_id));
+        }
 
-        Ok(())
+        Ok(session_ids)
     }
 }
@@ -0,0 +1,58 @@
+//! Outgoing email through an HTTP relay
+//!
+//! The server has no SMTP client of its own. `MAIL_WEBHOOK_URL` points at a
+//! relay (a transactional mail API or a small bridge) that accepts
+//! `{ "to", "subject", "text" }` as a JSON POST.
+
+use anyhow::{Context, Result};
+use tracing::info;
+
+pub struct MailService {
+    http: reqwest::Client,
+    webhook_url: Option<String>,
+}
+
+impl MailService {
+    /// Configure delivery from `MAIL_WEBHOOK_URL`; without it no mail is sent
+    pub fn from_env() -> Self {
+        let webhook_url = std::env::var("MAIL_WEBHOOK_URL")
+            .ok()
+            .filter(|v| !v.is_empty());
+        if webhook_url.is_some() {
+            info!("Email delivery enabled");
+        } else {
+            info!("Email delivery disabled: MAIL_WEBHOOK_URL is not set");
+        }
+
+        Self {
+            http: reqwest::Client::new(),
+            webhook_url,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.webhook_url.is_some()
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, text: &str) -> Result<()> {
+        let url = self
+            .webhook_url
+            .as_deref()
+            .context("Email delivery is not configured")?;
+
+        self.http
+            .post(url)
+            .json(&serde_json::json!({
+                "to": to,
+                "subject": subject,
+                "text": text,
+            }))
+            .send()
+            .await
+            .context("Failed to reach mail relay")?
+            .error_for_status()
+            .context("Mail relay rejected the message")?;
+
+        Ok(())
+    }
+}
@@ -1,9 +1,19 @@
 //! Business logic services
 
+pub mod audit;
 pub mod auth;
 pub mod machine_registry;
+pub mod mail;
+pub mod oidc;
+pub mod push;
+#[cfg(feature = "sso")]
+pub mod saml;
 pub mod session_manager;
+pub mod totp;
 
 pub use auth::AuthService;
 pub use machine_registry::MachineRegistry;
+pub use mail::MailService;
+pub use oidc::OidcService;
+pub use push::PushService;
 pub use session_manager::SessionManager;
@@ -0,0 +1,324 @@
+//! Single sign-on through external OpenID Connect / OAuth2 providers
+
+use crate::storage::MemoryCache;
+use anyhow::{Context, Result};
+use openidconnect::core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, JsonWebKeySet, Nonce,
+    OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+    TokenUrl,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How long a user has to complete the provider's consent screen
+const LOGIN_STATE_TTL: Duration = Duration::from_secs(600);
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ProviderKind {
+    /// Standard OIDC: identity comes from the verified ID token
+    Google,
+    /// Plain OAuth2: identity comes from the GitHub user API
+    GitHub,
+}
+
+struct OidcProvider {
+    kind: ProviderKind,
+    client: CoreClient,
+}
+
+/// A provider as advertised to the login page
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderInfo {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// The user identity a provider vouched for
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub email: String,
+    pub name: Option<String>,
+}
+
+/// Login attempt in flight, keyed by its CSRF state
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLogin {
+    provider: String,
+    nonce: String,
+    pkce_verifier: String,
+    redirect: Option<String>,
+}
+
+pub struct OidcService {
+    providers: HashMap<String, OidcProvider>,
+    cache: Arc<MemoryCache>,
+    http: reqwest::Client,
+}
+
+impl OidcService {
+    /// Configure providers from `<PROVIDER>_CLIENT_ID` / `<PROVIDER>_CLIENT_SECRET`.
+    ///
+    /// Providers without credentials (or whose discovery fails) stay disabled.
+    pub async fn from_env(cache: Arc<MemoryCache>, public_url: &str) -> Self {
+        let mut providers = HashMap::new();
+
+        if let Some((client_id, client_secret)) = credentials("GOOGLE") {
+            match google_client(client_id, client_secret).await {
+                Ok(client) => {
+                    providers.insert(
+                        "google".to_string(),
+                        OidcProvider {
+                            kind: ProviderKind::Google,
+                            client,
+                        },
+                    );
+                }
+                Err(e) => warn!("Google sign-in disabled: {:#}", e),
+            }
+        }
+
+        if let Some((client_id, client_secret)) = credentials("GITHUB") {
+            match github_client(client_id, client_secret) {
+                Ok(client) => {
+                    providers.insert(
+                        "github".to_string(),
+                        OidcProvider {
+                            kind: ProviderKind::GitHub,
+                            client,
+                        },
+                    );
+                }
+                Err(e) => warn!("GitHub sign-in disabled: {:#}", e),
+            }
+        }
+
+        // Callbacks come back through the public API URL
+        let base = public_url.trim_end_matches('/');
+        for (id, provider) in providers.iter_mut() {
+            let redirect = format!("{}/api/v1/auth/oidc/{}/callback", base, id);
+            match RedirectUrl::new(redirect) {
+                Ok(url) => provider.client = provider.client.clone().set_redirect_uri(url),
+                Err(e) => warn!("Invalid OIDC redirect URL for {}: {}", id, e),
+            }
+        }
+
+        info!(
+            "OIDC providers enabled: {:?}",
+            providers.keys().collect::<Vec<_>>()
+        );
+
+        Self {
+            providers,
+            cache,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// All supported providers and whether they are configured
+    pub fn providers(&self) -> Vec<ProviderInfo> {
+        [("google", "Google"), ("github", "GitHub")]
+            .into_iter()
+            .map(|(id, name)| ProviderInfo {
+                id: id.to_string(),
+                name: name.to_string(),
+                enabled: self.providers.contains_key(id),
+            })
+            .collect()
+    }
+
+    /// Build the provider's authorization URL and remember the login attempt
+    pub fn authorize_url(&self, provider_id: &str, redirect: Option<String>) -> Result<String> {
+        let provider = self
+            .providers
+            .get(provider_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown or disabled provider: {}", provider_id))?;
+
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let mut request = provider
+            .client
+            .authorize_url(
+                CoreAuthenticationFlow::AuthorizationCode,
+                CsrfToken::new_random,
+                Nonce::new_random,
+            )
+            .set_pkce_challenge(pkce_challenge);
+
+        request = match provider.kind {
+            ProviderKind::Google => request
+                .add_scope(Scope::new("email".to_string()))
+                .add_scope(Scope::new("profile".to_string())),
+            ProviderKind::GitHub => request
+                .add_scope(Scope::new("read:user".to_string()))
+                .add_scope(Scope::new("user:email".to_string())),
+        };
+
+        let (url, csrf_token, nonce) = request.url();
+
+        let pending = PendingLogin {
+            provider: provider_id.to_string(),
+            nonce: nonce.secret().clone(),
+            pkce_verifier: pkce_verifier.secret().clone(),
+            redirect,
+        };
+        self.cache.insert_with_ttl(
+            format!("oidc_state:{}", csrf_token.secret()),
+            serde_json::to_vec(&pending)?,
+            LOGIN_STATE_TTL,
+        );
+
+        Ok(url.to_string())
+    }
+
+    /// Complete a login: check the state, exchange the code and resolve the identity.
+    ///
+    /// Also returns the post-login redirect requested when the flow started.
+    pub async fn complete_login(
+        &self,
+        provider_id: &str,
+        code: &str,
+        state: &str,
+    ) -> Result<(ExternalIdentity, Option<String>)> {
+        let data = self
+            .cache
+            .take(&format!("oidc_state:{}", state))
+            .ok_or_else(|| anyhow::anyhow!("Unknown or expired login state"))?;
+        let pending: PendingLogin = serde_json::from_slice(&data)?;
+        if pending.provider != provider_id {
+            anyhow::bail!("Login state was issued for another provider");
+        }
+
+        let provider = self
+            .providers
+            .get(provider_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown or disabled provider: {}", provider_id))?;
+
+        let token_response = provider
+            .client
+            .exchange_code(AuthorizationCode::new(code.to_string()))
+            .set_pkce_verifier(PkceCodeVerifier::new(pending.pkce_verifier))
+            .request_async(async_http_client)
+            .await
+            .context("Failed to exchange authorization code")?;
+
+        let identity = match provider.kind {
+            ProviderKind::Google => {
+                let id_token = token_response
+                    .id_token()
+                    .ok_or_else(|| anyhow::anyhow!("Provider did not return an ID token"))?;
+                let claims = id_token
+                    .claims(
+                        &provider.client.id_token_verifier(),
+                        &Nonce::new(pending.nonce),
+                    )
+                    .context("Invalid ID token")?;
+
+                if claims.email_verified() != Some(true) {
+                    anyhow::bail!("Email address is not verified");
+                }
+                let email = claims
+                    .email()
+                    .ok_or_else(|| anyhow::anyhow!("ID token has no email claim"))?;
+
+                ExternalIdentity {
+                    email: email.to_string(),
+                    name: claims
+                        .name()
+                        .and_then(|n| n.get(None))
+                        .map(|n| n.to_string()),
+                }
+            }
+            ProviderKind::GitHub => {
+                self.github_identity(token_response.access_token().secret())
+                    .await?
+            }
+        };
+
+        Ok((identity, pending.redirect))
+    }
+
+    async fn github_identity(&self, access_token: &str) -> Result<ExternalIdentity> {
+        let user: serde_json::Value = self
+            .http
+            .get(format!("{}/user", GITHUB_API_URL))
+            .bearer_auth(access_token)
+            .header("User-Agent", "happy-server")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // The profile email may be hidden; fall back to the primary verified address
+        let emails: Vec<serde_json::Value> = self
+            .http
+            .get(format!("{}/user/emails", GITHUB_API_URL))
+            .bearer_auth(access_token)
+            .header("User-Agent", "happy-server")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let email = emails
+            .iter()
+            .find(|e| e["primary"].as_bool() == Some(true) && e["verified"].as_bool() == Some(true))
+            .and_then(|e| e["email"].as_str())
+            .ok_or_else(|| anyhow::anyhow!("GitHub account has no verified primary email"))?;
+
+        Ok(ExternalIdentity {
+            email: email.to_string(),
+            name: user["name"]
+                .as_str()
+                .or_else(|| user["login"].as_str())
+                .map(|s| s.to_string()),
+        })
+    }
+}
+
+fn credentials(prefix: &str) -> Option<(String, String)> {
+    let client_id = std::env::var(format!("{}_CLIENT_ID", prefix)).ok()?;
+    let client_secret = std::env::var(format!("{}_CLIENT_SECRET", prefix)).ok()?;
+    if client_id.is_empty() || client_secret.is_empty() {
+        return None;
+    }
+    Some((client_id, client_secret))
+}
+
+async fn google_client(client_id: String, client_secret: String) -> Result<CoreClient> {
+    let metadata = CoreProviderMetadata::discover_async(
+        IssuerUrl::new("https://accounts.google.com".to_string())?,
+        async_http_client,
+    )
+    .await
+    .context("OIDC discovery failed")?;
+
+    Ok(CoreClient::from_provider_metadata(
+        metadata,
+        ClientId::new(client_id),
+        Some(ClientSecret::new(client_secret)),
+    ))
+}
+
+fn github_client(client_id: String, client_secret: String) -> Result<CoreClient> {
+    // GitHub has no discovery document or ID tokens, so only the OAuth2 endpoints are used
+    Ok(CoreClient::new(
+        ClientId::new(client_id),
+        Some(ClientSecret::new(client_secret)),
+        IssuerUrl::new("https://github.com".to_string())?,
+        AuthUrl::new("https://github.com/login/oauth/authorize".to_string())?,
+        Some(TokenUrl::new(
+            "https://github.com/login/oauth/access_token".to_string(),
+        )?),
+        None,
+        JsonWebKeySet::default(),
+    ))
+}
@@ -0,0 +1,292 @@
+//! Push notification delivery through Firebase Cloud Messaging
+
+use crate::storage::Database;
+use anyhow::{Context, Result};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+
+/// Retries after the first failed attempt
+const MAX_RETRIES: u32 = 3;
+
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Refresh the OAuth access token this long before Google expires it
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// The fields of a Google service account key file that token exchange needs
+#[derive(Debug, Deserialize)]
+struct ServiceAccount {
+    client_email: String,
+    private_key: String,
+    #[serde(default = "default_token_uri")]
+    token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+
+#[derive(Debug, Serialize)]
+struct AssertionClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessTokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+struct Fcm {
+    project_id: String,
+    account: ServiceAccount,
+    key: EncodingKey,
+    /// OAuth access token and when it stops being usable
+    access_token: Mutex<Option<(String, Instant)>>,
+}
+
+enum DeliveryError {
+    /// FCM no longer knows the device token; it should be forgotten
+    InvalidToken,
+    Failed(anyhow::Error),
+}
+
+pub struct PushService {
+    db: Arc<Database>,
+    http: reqwest::Client,
+    fcm: Option<Fcm>,
+}
+
+impl PushService {
+    /// Configure FCM from `FIREBASE_PROJECT_ID` and `FIREBASE_SERVICE_ACCOUNT_JSON`.
+    ///
+    /// The service account may be given inline or as a path to the key file.
+    /// Without both variables the service stays disabled and sends nothing.
+    pub fn from_env(db: Arc<Database>) -> Self {
+        let fcm = match (
+            std::env::var("FIREBASE_PROJECT_ID")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            std::env::var("FIREBASE_SERVICE_ACCOUNT_JSON")
+                .ok()
+                .filter(|v| !v.is_empty()),
+        ) {
+            (Some(project_id), Some(account)) => match load_fcm(project_id, &account) {
+                Ok(fcm) => {
+                    info!("Push notifications enabled for project {}", fcm.project_id);
+                    Some(fcm)
+                }
+                Err(e) => {
+                    warn!("Push notifications disabled: {:#}", e);
+                    None
+                }
+            },
+            _ => {
+                info!("Push notifications disabled: Firebase is not configured");
+                None
+            }
+        };
+
+        Self {
+            db,
+            http: reqwest::Client::new(),
+            fcm,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.fcm.is_some()
+    }
+
+    /// Notify every registered device of a user, returning how many accepted it
+    pub async fn send_to_user(&self, user_id: &str, title: &str, body: &str) -> Result<usize> {
+        let Some(fcm) = &self.fcm else {
+            return Ok(0);
+        };
+
+        let mut delivered = 0;
+        for token in self.db.list_push_tokens(user_id).await? {
+            match self.deliver(fcm, &token, title, body).await {
+                Ok(()) => delivered += 1,
+                Err(DeliveryError::InvalidToken) => {
+                    info!("Dropping unregistered push token for user {}", user_id);
+                    self.db.delete_push_token(&token).await?;
+                }
+                Err(DeliveryError::Failed(e)) => {
+                    warn!("Push delivery to user {} failed: {:#}", user_id, e);
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Send one message, retrying transient failures with exponential back-off
+    async fn deliver(
+        &self,
+        fcm: &Fcm,
+        token: &str,
+        title: &str,
+        body: &str,
+    ) -> Result<(), DeliveryError> {
+        let url = format!(
+            "https://fcm.googleapis.com/v1/projects/{}/messages:send",
+            fcm.project_id
+        );
+        let message = serde_json::json!({
+            "message": {
+                "token": token,
+                "notification": { "title": title, "body": body },
+            }
+        });
+
+        let mut attempt = 0;
+        loop {
+            let error = match self.access_token(fcm).await {
+                Ok(access_token) => {
+                    match self
+                        .http
+                        .post(&url)
+                        .bearer_auth(access_token)
+                        .json(&message)
+                        .send()
+                        .await
+                    {
+                        Ok(response) if response.status().is_success() => return Ok(()),
+                        Ok(response) => {
+                            let status = response.status();
+                            let text = response.text().await.unwrap_or_default();
+                            if status == reqwest::StatusCode::NOT_FOUND
+                                || text.contains("UNREGISTERED")
+                            {
+                                return Err(DeliveryError::InvalidToken);
+                            }
+                            if status == reqwest::StatusCode::UNAUTHORIZED {
+                                // Force a fresh access token on the next attempt
+                                *fcm.access_token.lock().await = None;
+                            } else if status.is_client_error()
+                                && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+                            {
+                                return Err(DeliveryError::Failed(anyhow::anyhow!(
+                                    "FCM rejected message: {} {}",
+                                    status,
+                                    text
+                                )));
+                            }
+                            anyhow::anyhow!("FCM returned {}: {}", status, text)
+                        }
+                        Err(e) => anyhow::Error::new(e).context("FCM request failed"),
+                    }
+                }
+                Err(e) => e,
+            };
+
+            if attempt >= MAX_RETRIES {
+                return Err(DeliveryError::Failed(error));
+            }
+            let delay = retry_delay(attempt);
+            debug!(
+                "Push attempt {} failed, retrying in {:?}: {:#}",
+                attempt + 1,
+                delay,
+                error
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// A cached OAuth access token, exchanging a signed service account JWT when needed
+    async fn access_token(&self, fcm: &Fcm) -> Result<String> {
+        let mut cached = fcm.access_token.lock().await;
+        if let Some((token, expires_at)) = cached.as_ref() {
+            if Instant::now() < *expires_at {
+                return Ok(token.clone());
+            }
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let claims = AssertionClaims {
+            iss: &fcm.account.client_email,
+            scope: FCM_SCOPE,
+            aud: &fcm.account.token_uri,
+            iat: now,
+            exp: now + 3600,
+        };
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &fcm.key)
+            .context("Failed to sign service account JWT")?;
+
+        let response: AccessTokenResponse = self
+            .http
+            .post(&fcm.account.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await
+            .context("Failed to request access token")?
+            .error_for_status()
+            .context("Access token request rejected")?
+            .json()
+            .await?;
+
+        let lifetime =
+            Duration::from_secs(response.expires_in).saturating_sub(TOKEN_REFRESH_MARGIN);
+        *cached = Some((response.access_token.clone(), Instant::now() + lifetime));
+        Ok(response.access_token)
+    }
+}
+
+fn load_fcm(project_id: String, account: &str) -> Result<Fcm> {
+    let json = if account.trim_start().starts_with('{') {
+        account.to_string()
+    } else {
+        std::fs::read_to_string(account)
+            .with_context(|| format!("Failed to read service account file {}", account))?
+    };
+    let account: ServiceAccount =
+        serde_json::from_str(&json).context("Invalid service account JSON")?;
+    let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+        .context("Invalid service account private key")?;
+
+    Ok(Fcm {
+        project_id,
+        account,
+        key,
+        access_token: Mutex::new(None),
+    })
+}
+
+/// Back-off before retry number `attempt + 1`: 0.5s, 1s, 2s, ...
+fn retry_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY * 2u32.pow(attempt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let delays: Vec<_> = (0..MAX_RETRIES).map(retry_delay).collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(500),
+                Duration::from_secs(1),
+                Duration::from_secs(2)
+            ]
+        );
+    }
+}
@@ -0,0 +1,639 @@
+//! SAML 2.0 single sign-on against a company identity provider
+//!
+//! Logins are SP-initiated: [`SamlService::login_form`] posts an AuthnRequest
+//! to the IdP (HTTP-POST binding) and [`SamlService::complete_login`] checks
+//! the Response the IdP posts back to the callback. A response is only
+//! trusted when its assertion, or the response around it, carries an
+//! RSA-SHA256 XML signature from the configured certificate. XML-DSig itself
+//! (canonicalization, digests, signature wrapping) is left to bergshamra,
+//! which is pure Rust and so keeps libxml2/xmlsec out of the build.
+
+use crate::services::oidc::ExternalIdentity;
+use crate::storage::{Database, MemoryCache, SsoConfig};
+use anyhow::{Context, Result};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How long a user has to finish signing in at the IdP
+const LOGIN_STATE_TTL: Duration = Duration::from_secs(600);
+
+/// Clock difference tolerated between us and the IdP
+const CLOCK_SKEW_SECS: i64 = 120;
+
+/// Path of the assertion consumer service, relative to the public URL
+pub const CALLBACK_PATH: &str = "/api/v1/auth/sso/saml/callback";
+
+const PROTOCOL_NS: &str = "urn:oasis:names:tc:SAML:2.0:protocol";
+const ASSERTION_NS: &str = "urn:oasis:names:tc:SAML:2.0:assertion";
+const METADATA_NS: &str = "urn:oasis:names:tc:SAML:2.0:metadata";
+const DSIG_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+const POST_BINDING: &str = "urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST";
+const STATUS_SUCCESS: &str = "urn:oasis:names:tc:SAML:2.0:status:Success";
+const BEARER_METHOD: &str = "urn:oasis:names:tc:SAML:2.0:cm:bearer";
+const EMAIL_NAME_ID: &str = "urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress";
+
+const RSA_SHA256: &str = "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256";
+
+/// Attribute names IdPs commonly put the email address in
+const EMAIL_ATTRIBUTES: &[&str] = &[
+    "email",
+    "mail",
+    "urn:oid:0.9.2342.19200300.100.1.3",
+    "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress",
+];
+
+/// Attribute names IdPs commonly put the display name in
+const NAME_ATTRIBUTES: &[&str] = &[
+    "displayName",
+    "name",
+    "urn:oid:2.16.840.1.113730.3.1.241",
+    "http://schemas.microsoft.com/identity/claims/displayname",
+];
+
+/// Login attempt in flight, keyed by its AuthnRequest ID
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLogin {
+    redirect: Option<String>,
+}
+
+/// What the IdP's metadata says about it
+#[derive(Debug, PartialEq)]
+struct IdpMetadata {
+    entity_id: String,
+    /// Where AuthnRequests are posted
+    sso_url: String,
+}
+
+pub struct SamlService {
+    config: SsoConfig,
+    idp: IdpMetadata,
+    /// DER of the IdP certificate, the only key signatures are checked with
+    certificate: Vec<u8>,
+    acs_url: String,
+    cache: Arc<MemoryCache>,
+}
+
+impl SamlService {
+    /// Set up SSO from the stored `sso_config`, fetching the IdP's metadata.
+    ///
+    /// Returns `None` when SSO isn't configured or the IdP can't be reached.
+    pub async fn from_db(db: &Database, cache: Arc<MemoryCache>, public_url: &str) -> Option<Self> {
+        let config = match db.get_sso_config().await {
+            Ok(Some(config)) => config,
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("Failed to load SSO configuration: {}", e);
+                return None;
+            }
+        };
+
+        match Self::new(config, cache, public_url).await {
+            Ok(service) => {
+                info!(
+                    "SAML SSO enabled for {} via {}",
+                    service.config.domain, service.idp.entity_id
+                );
+                Some(service)
+            }
+            Err(e) => {
+                warn!("SAML SSO disabled: {:#}", e);
+                None
+            }
+        }
+    }
+
+    async fn new(config: SsoConfig, cache: Arc<MemoryCache>, public_url: &str) -> Result<Self> {
+        let certificate = certificate_der(&config.certificate)?;
+        let metadata = reqwest::get(&config.idp_metadata_url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .context("Failed to fetch IdP metadata")?
+            .text()
+            .await?;
+        let idp = parse_idp_metadata(&metadata)?;
+
+        Ok(Self {
+            config,
+            idp,
+            certificate,
+            acs_url: format!("{}{}", public_url.trim_end_matches('/'), CALLBACK_PATH),
+            cache,
+        })
+    }
+
+    /// The configuration this service was set up from
+    pub fn config(&self) -> &SsoConfig {
+        &self.config
+    }
+
+    /// Our service provider metadata, for registering with the IdP
+    pub fn metadata(&self) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<md:EntityDescriptor xmlns:md="{md}" entityID="{entity_id}">
+  <md:SPSSODescriptor AuthnRequestsSigned="false" WantAssertionsSigned="true"
+      protocolSupportEnumeration="{protocol}">
+    <md:NameIDFormat>{name_id}</md:NameIDFormat>
+    <md:AssertionConsumerService Binding="{binding}" Location="{acs_url}"
+        index="0" isDefault="true"/>
+  </md:SPSSODescriptor>
+</md:EntityDescriptor>
+"#,
+            md = METADATA_NS,
+            entity_id = xml_escape(&self.config.entity_id),
+            protocol = PROTOCOL_NS,
+            name_id = EMAIL_NAME_ID,
+            binding = POST_BINDING,
+            acs_url = xml_escape(&self.acs_url),
+        )
+    }
+
+    /// An HTML page that posts a new AuthnRequest to the IdP, remembering
+    /// where to send the user afterwards
+    pub fn login_form(&self, redirect: Option<String>) -> Result<String> {
+        let request_id = format!("_{}", uuid::Uuid::new_v4().simple());
+        let request = format!(
+            r#"<samlp:AuthnRequest xmlns:samlp="{protocol}" xmlns:saml="{assertion}"
+    ID="{id}" Version="2.0" IssueInstant="{now}" Destination="{destination}"
+    AssertionConsumerServiceURL="{acs_url}" ProtocolBinding="{binding}">
+  <saml:Issuer>{issuer}</saml:Issuer>
+  <samlp:NameIDPolicy Format="{name_id}" AllowCreate="true"/>
+</samlp:AuthnRequest>"#,
+            protocol = PROTOCOL_NS,
+            assertion = ASSERTION_NS,
+            id = request_id,
+            now = Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+            destination = xml_escape(&self.idp.sso_url),
+            acs_url = xml_escape(&self.acs_url),
+            binding = POST_BINDING,
+            issuer = xml_escape(&self.config.entity_id),
+            name_id = EMAIL_NAME_ID,
+        );
+
+        self.cache.insert_with_ttl(
+            format!("saml_request:{}", request_id),
+            serde_json::to_vec(&PendingLogin { redirect })?,
+            LOGIN_STATE_TTL,
+        );
+
+        let encoded = base64::engine::general_purpose::STANDARD.encode(request);
+        Ok(format!(
+            r#"<!DOCTYPE html>
+<html><body onload="document.forms[0].submit()">
+<form method="post" action="{}">
+<input type="hidden" name="SAMLRequest" value="{}">
+<input type="hidden" name="RelayState" value="{}">
+<noscript><button type="submit">Continue to sign in</button></noscript>
+</form>
+</body></html>
+"#,
+            xml_escape(&self.idp.sso_url),
+            encoded,
+            request_id
+        ))
+    }
+
+    /// Check the base64 `SAMLResponse` the IdP posted and resolve the identity.
+    ///
+    /// Also returns the post-login redirect requested when the flow started.
+    pub fn complete_login(
+        &self,
+        saml_response: &str,
+    ) -> Result<(ExternalIdentity, Option<String>)> {
+        self.complete_login_at(saml_response, Utc::now())
+    }
+
+    fn complete_login_at(
+        &self,
+        saml_response: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(ExternalIdentity, Option<String>)> {
+        let xml = base64::engine::general_purpose::STANDARD
+            .decode(strip_whitespace(saml_response))
+            .context("SAMLResponse is not base64")?;
+        let xml = String::from_utf8(xml).context("SAMLResponse is not UTF-8")?;
+        let doc = Document::parse(&xml).context("SAMLResponse is not valid XML")?;
+
+        let assertion = verified_assertion(&doc, &self.certificate)?;
+        let checked = check_assertion(assertion, &self.expected(), now)?;
+
+        // Single use: an unknown ID is a replay or an IdP-initiated login
+        let data = self
+            .cache
+            .take(&format!("saml_request:{}", checked.in_response_to))
+            .ok_or_else(|| anyhow::anyhow!("Unknown or expired login request"))?;
+        let pending: PendingLogin = serde_json::from_slice(&data)?;
+
+        Ok((checked.identity, pending.redirect))
+    }
+
+    fn expected(&self) -> Expected<'_> {
+        Expected {
+            idp_entity_id: &self.idp.entity_id,
+            audience: &self.config.entity_id,
+            acs_url: &self.acs_url,
+            domain: &self.config.domain,
+        }
+    }
+}
+
+/// Parse the IdP certificate, PEM or bare base64 DER as found in metadata,
+/// returning its DER
+pub fn certificate_der(certificate: &str) -> Result<Vec<u8>> {
+    let body: String = certificate
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    let der = base64::engine::general_purpose::STANDARD
+        .decode(strip_whitespace(&body))
+        .context("IdP certificate is not PEM or base64")?;
+    let key = bergshamra_keys::loader::load_x509_cert_der(&der)
+        .map_err(|e| anyhow::anyhow!("Invalid IdP certificate: {}", e))?;
+    if key.data.algorithm_name() != "RSA" {
+        anyhow::bail!("IdP certificate does not hold an RSA key");
+    }
+    Ok(der)
+}
+
+fn parse_idp_metadata(xml: &str) -> Result<IdpMetadata> {
+    let doc = Document::parse(xml).context("IdP metadata is not valid XML")?;
+    let descriptor = doc
+        .descendants()
+        .find(|n| n.has_tag_name((METADATA_NS, "EntityDescriptor")))
+        .context("IdP metadata has no EntityDescriptor")?;
+    let entity_id = descriptor
+        .attribute("entityID")
+        .context("IdP metadata has no entityID")?;
+    let sso_url = descriptor
+        .descendants()
+        .filter(|n| n.has_tag_name((METADATA_NS, "SingleSignOnService")))
+        .find(|n| n.attribute("Binding") == Some(POST_BINDING))
+        .and_then(|n| n.attribute("Location"))
+        .context("IdP does not accept AuthnRequests over HTTP-POST")?;
+
+    Ok(IdpMetadata {
+        entity_id: entity_id.to_string(),
+        sso_url: sso_url.to_string(),
+    })
+}
+
+/// The only assertion in `doc`, once its signature checks out
+fn verified_assertion<'a, 'input>(
+    doc: &'a Document<'input>,
+    certificate: &[u8],
+) -> Result<Node<'a, 'input>> {
+    let response = doc.root_element();
+    if !response.has_tag_name((PROTOCOL_NS, "Response")) {
+        anyhow::bail!("Not a SAML Response");
+    }
+
+    // Signature wrapping attacks hide a forged assertion next to a signed
+    // one; refuse anything but a single assertion (the verifier also
+    // refuses duplicate IDs)
+    if doc
+        .descendants()
+        .any(|n| n.has_tag_name((ASSERTION_NS, "EncryptedAssertion")))
+    {
+        anyhow::bail!("Encrypted assertions are not supported");
+    }
+    let assertions: Vec<_> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name((ASSERTION_NS, "Assertion")))
+        .collect();
+    let [assertion] = assertions[..] else {
+        anyhow::bail!("Expected one assertion, found {}", assertions.len());
+    };
+    if assertion.parent_element().map(|n| n.id()) != Some(response.id()) {
+        anyhow::bail!("Assertion is not a child of the Response");
+    }
+
+    let signed = if let Some(signature) = child(assertion, DSIG_NS, "Signature") {
+        (assertion, signature)
+    } else if let Some(signature) = child(response, DSIG_NS, "Signature") {
+        (response, signature)
+    } else {
+        anyhow::bail!("Assertion is not signed");
+    };
+    verify_signature(doc, signed, certificate)?;
+    Ok(assertion)
+}
+
+/// Check the XML signatures in `doc` with the IdP `certificate`, requiring
+/// the `(element, signature)` pair to be among them: an RSA-SHA256 signature
+/// whose only reference is `element`
+fn verify_signature(
+    doc: &Document,
+    (element, signature): (Node, Node),
+    certificate: &[u8],
+) -> Result<()> {
+    let signature_method = child(signature, DSIG_NS, "SignedInfo")
+        .and_then(|n| child(n, DSIG_NS, "SignatureMethod"))
+        .and_then(|n| n.attribute("Algorithm"));
+    if signature_method != Some(RSA_SHA256) {
+        anyhow::bail!("Unsupported signature algorithm {:?}", signature_method);
+    }
+
+    let mut keys = bergshamra_keys::KeysManager::new();
+    keys.add_key(
+        bergshamra_keys::loader::load_x509_cert_der(certificate)
+            .map_err(|e| anyhow::anyhow!("Invalid IdP certificate: {}", e))?,
+    );
+    // Only the configured key counts, whatever KeyInfo the response carries,
+    // and references must point at the signature's ancestors or siblings
+    let ctx = bergshamra_dsig::DsigContext::new(keys);
+    let results = bergshamra_dsig::verify::verify_all(&ctx, doc.input_text())
+        .map_err(|e| anyhow::anyhow!("Signature does not verify: {}", e))?;
+
+    let id = element.attribute("ID").context("Signed element has no ID")?;
+    let uri = format!("#{}", id);
+    let mut covered = false;
+    for result in results {
+        match result {
+            bergshamra_dsig::VerifyResult::Valid { references, .. } => {
+                covered |= matches!(&references[..], [r] if r.uri == uri && r.digest_verified);
+            }
+            bergshamra_dsig::VerifyResult::Invalid { reason } => {
+                anyhow::bail!("Signature does not verify with the IdP certificate: {}", reason)
+            }
+        }
+    }
+    if !covered {
+        anyhow::bail!("Signature does not cover the signed element");
+    }
+    Ok(())
+}
+
+/// What a valid assertion has to say
+struct Expected<'a> {
+    idp_entity_id: &'a str,
+    audience: &'a str,
+    acs_url: &'a str,
+    domain: &'a str,
+}
+
+#[derive(Debug)]
+struct CheckedAssertion {
+    identity: ExternalIdentity,
+    /// ID of the AuthnRequest this answers
+    in_response_to: String,
+}
+
+fn check_assertion(
+    assertion: Node,
+    expected: &Expected,
+    now: DateTime<Utc>,
+) -> Result<CheckedAssertion> {
+    let response = assertion.parent_element().context("Assertion has no Response")?;
+    let status = child(response, PROTOCOL_NS, "Status")
+        .and_then(|n| child(n, PROTOCOL_NS, "StatusCode"))
+        .and_then(|n| n.attribute("Value"));
+    if status != Some(STATUS_SUCCESS) {
+        anyhow::bail!("IdP refused the login: {:?}", status);
+    }
+    if let Some(destination) = response.attribute("Destination") {
+        if destination != expected.acs_url {
+            anyhow::bail!("Response is addressed to {}", destination);
+        }
+    }
+
+    let issuer = child(assertion, ASSERTION_NS, "Issuer").and_then(|n| n.text());
+    if issuer.map(str::trim) != Some(expected.idp_entity_id) {
+        anyhow::bail!("Assertion was issued by {:?}", issuer);
+    }
+
+    let conditions =
+        child(assertion, ASSERTION_NS, "Conditions").context("Assertion has no Conditions")?;
+    check_window(conditions, now)?;
+    let audience_ok = conditions
+        .children()
+        .filter(|n| n.has_tag_name((ASSERTION_NS, "AudienceRestriction")))
+        .flat_map(|n| n.children())
+        .filter(|n| n.has_tag_name((ASSERTION_NS, "Audience")))
+        .any(|n| n.text().map(str::trim) == Some(expected.audience));
+    if !audience_ok {
+        anyhow::bail!("Assertion is not meant for {}", expected.audience);
+    }
+
+    let subject = child(assertion, ASSERTION_NS, "Subject").context("Assertion has no Subject")?;
+    let confirmation = subject
+        .children()
+        .filter(|n| n.has_tag_name((ASSERTION_NS, "SubjectConfirmation")))
+        .find(|n| n.attribute("Method") == Some(BEARER_METHOD))
+        .and_then(|n| child(n, ASSERTION_NS, "SubjectConfirmationData"))
+        .context("Assertion has no bearer SubjectConfirmation")?;
+    if confirmation.attribute("Recipient") != Some(expected.acs_url) {
+        anyhow::bail!("Assertion was sent to {:?}", confirmation.attribute("Recipient"));
+    }
+    check_window(confirmation, now)?;
+    let in_response_to = confirmation
+        .attribute("InResponseTo")
+        .context("IdP-initiated logins are not supported")?;
+
+    let name_id = child(subject, ASSERTION_NS, "NameID")
+        .filter(|n| n.attribute("Format") == Some(EMAIL_NAME_ID))
+        .and_then(|n| n.text());
+    let email = name_id
+        .or_else(|| attribute_value(assertion, EMAIL_ATTRIBUTES))
+        .map(str::trim)
+        .context("Assertion has no email address")?;
+    let in_domain = email
+        .rsplit_once('@')
+        .is_some_and(|(_, domain)| domain.eq_ignore_ascii_case(expected.domain));
+    if !in_domain {
+        anyhow::bail!("IdP vouched for {}, outside {}", email, expected.domain);
+    }
+
+    Ok(CheckedAssertion {
+        identity: ExternalIdentity {
+            email: email.to_string(),
+            name: attribute_value(assertion, NAME_ATTRIBUTES).map(|n| n.trim().to_string()),
+        },
+        in_response_to: in_response_to.to_string(),
+    })
+}
+
+/// Check `NotBefore` / `NotOnOrAfter`, allowing for clock skew
+fn check_window(node: Node, now: DateTime<Utc>) -> Result<()> {
+    let skew = chrono::Duration::seconds(CLOCK_SKEW_SECS);
+    if let Some(not_before) = node.attribute("NotBefore") {
+        if now + skew < parse_time(not_before)? {
+            anyhow::bail!("Assertion is not valid until {}", not_before);
+        }
+    }
+    if let Some(not_on_or_after) = node.attribute("NotOnOrAfter") {
+        if now - skew >= parse_time(not_on_or_after)? {
+            anyhow::bail!("Assertion expired at {}", not_on_or_after);
+        }
+    }
+    Ok(())
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .with_context(|| format!("Invalid time {:?}", value))?
+        .with_timezone(&Utc))
+}
+
+/// The first value of the first attribute named in `names`
+fn attribute_value<'a>(assertion: Node<'a, '_>, names: &[&str]) -> Option<&'a str> {
+    let attributes: Vec<_> = child(assertion, ASSERTION_NS, "AttributeStatement")?
+        .children()
+        .filter(|n| n.has_tag_name((ASSERTION_NS, "Attribute")))
+        .collect();
+    names.iter().find_map(|name| {
+        let attribute = attributes.iter().find(|n| n.attribute("Name") == Some(*name))?;
+        child(*attribute, ASSERTION_NS, "AttributeValue")?.text()
+    })
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, ns: &str, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name((ns, name)))
+}
+
+fn strip_whitespace(value: &str) -> String {
+    value.chars().filter(|c| !c.is_ascii_whitespace()).collect()
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDP_CERT: &str = include_str!("testdata/saml_idp_cert.pem");
+    const RESPONSE: &str = include_str!("testdata/saml_response.xml");
+    const OTHER_CERT: &str = include_str!("testdata/saml_other_cert.pem");
+
+    fn service() -> SamlService {
+        SamlService {
+            config: SsoConfig {
+                idp_metadata_url: "https://idp.example.com/metadata".into(),
+                entity_id: "https://happy.example.com".into(),
+                certificate: IDP_CERT.into(),
+                domain: "corp.example.com".into(),
+            },
+            idp: IdpMetadata {
+                entity_id: "https://idp.example.com".into(),
+                sso_url: "https://idp.example.com/sso".into(),
+            },
+            certificate: certificate_der(IDP_CERT).unwrap(),
+            acs_url: format!("https://happy.example.com{}", CALLBACK_PATH),
+            cache: Arc::new(MemoryCache::new()),
+        }
+    }
+
+    fn pending(service: &SamlService, request_id: &str) {
+        let pending = PendingLogin {
+            redirect: Some("/sessions".into()),
+        };
+        service.cache.insert_with_ttl(
+            format!("saml_request:{}", request_id),
+            serde_json::to_vec(&pending).unwrap(),
+            LOGIN_STATE_TTL,
+        );
+    }
+
+    fn encode(xml: &str) -> String {
+        base64::engine::general_purpose::STANDARD.encode(xml)
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        parse_time(time).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_signed_response() {
+        let service = service();
+        pending(&service, "_r1");
+
+        let (identity, redirect) = service
+            .complete_login_at(&encode(RESPONSE), at("2026-10-17T12:01:00Z"))
+            .unwrap();
+        assert_eq!(identity.email, "alice@corp.example.com");
+        assert_eq!(identity.name.as_deref(), Some("Alice & Co"));
+        assert_eq!(redirect.as_deref(), Some("/sessions"));
+
+        // The request ID is spent, so the response can't be replayed
+        let replay = service.complete_login_at(&encode(RESPONSE), at("2026-10-17T12:01:00Z"));
+        assert!(replay.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_responses() {
+        let service = service();
+        let check = |xml: &str, now: &str| {
+            pending(&service, "_r1");
+            service.complete_login_at(&encode(xml), at(now)).unwrap_err().to_string()
+        };
+        let now = "2026-10-17T12:01:00Z";
+
+        // Any change to the signed assertion breaks the digest
+        let forged = RESPONSE.replace("alice@corp", "mallory@corp");
+        assert!(check(&forged, now).contains("digest does not match"));
+
+        // A second assertion can't ride along with the signed one
+        let start = RESPONSE.find("<saml:Assertion").unwrap();
+        let end = RESPONSE.find("</samlp:Response>").unwrap();
+        let copy = RESPONSE[start..end].replace("ID=\"_a1\"", "ID=\"_a2\"");
+        let wrapped = RESPONSE.replace("</samlp:Response>", &format!("{}</samlp:Response>", copy));
+        assert!(check(&wrapped, now).contains("Expected one assertion"));
+
+        assert!(check(RESPONSE, "2026-10-17T12:10:00Z").contains("expired"));
+        assert!(check(RESPONSE, "2026-10-17T11:50:00Z").contains("not valid until"));
+
+        let unsigned = RESPONSE.replace("ds:Signature", "ds:NotSignature");
+        assert!(check(&unsigned, now).contains("not signed"));
+    }
+
+    #[tokio::test]
+    async fn test_other_certificate() {
+        let mut service = service();
+        service.certificate = certificate_der(OTHER_CERT).unwrap();
+        pending(&service, "_r1");
+
+        // Only the configured certificate counts, not whichever the response names
+        let err = service
+            .complete_login_at(&encode(RESPONSE), at("2026-10-17T12:01:00Z"))
+            .unwrap_err();
+        assert!(err.to_string().contains("does not verify"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_metadata() {
+        let xml = r#"<md:EntityDescriptor xmlns:md="urn:oasis:names:tc:SAML:2.0:metadata"
+    entityID="https://idp.example.com">
+  <md:IDPSSODescriptor protocolSupportEnumeration="urn:oasis:names:tc:SAML:2.0:protocol">
+    <md:SingleSignOnService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-Redirect"
+        Location="https://idp.example.com/redirect"/>
+    <md:SingleSignOnService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST"
+        Location="https://idp.example.com/post"/>
+  </md:IDPSSODescriptor>
+</md:EntityDescriptor>"#;
+        assert_eq!(
+            parse_idp_metadata(xml).unwrap(),
+            IdpMetadata {
+                entity_id: "https://idp.example.com".into(),
+                sso_url: "https://idp.example.com/post".into(),
+            }
+        );
+
+        let service = service();
+        let sp = service.metadata();
+        assert!(Document::parse(&sp).is_ok());
+        assert!(sp.contains(&format!("Location=\"https://happy.example.com{}\"", CALLBACK_PATH)));
+        let form = service.login_form(None).unwrap();
+        assert!(form.contains("action=\"https://idp.example.com/sso\""));
+    }
+}
@@ -1,19 +1,42 @@
 //! Session management service
 
+use crate::services::audit::{AuditEvent, AuditLog};
 use crate::storage::{Database, MemoryCache};
 use anyhow::Result;
 use happy_core::{Session, SessionStatus};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{debug, info};
 
+/// How long a cached copy of a session lives; the database stays the source of truth
+const CACHE_TTL: Duration = Duration::from_secs(600);
+
 pub struct SessionManager {
     db: Arc<Database>,
     cache: Arc<MemoryCache>,
+    audit: AuditLog,
 }
 
 impl SessionManager {
     pub fn new(db: Arc<Database>, cache: Arc<MemoryCache>) -> Self {
-        Self { db, cache }
+        Self {
+            audit: AuditLog::new(db.clone()),
+            db,
+            cache,
+        }
+    }
+
+    fn audit_created(&self, session: &Session, created_by_ip: Option<&str>) {
+        self.audit.record(
+            AuditEvent::SessionCreate,
+            Some(&session.user_id),
+            Some(&session.id),
+            created_by_ip,
+            Some(serde_json::json!({
+                "tag": session.tag,
+                "machine_id": session.machine_id,
+            })),
+        );
     }
 
     pub async fn create_session(
@@ -23,6 +46,7 @@ impl SessionManager {
         machine_name: &str,
         tag: &str,
         cwd: &str,
+        created_by_ip: Option<&str>,
     ) -> Result<Session> {
         info!(
             "Creating session: user={}, tag={}, machine={}, cwd={}",
@@ -38,19 +62,23 @@ impl SessionManager {
             machine_name.to_string(),
         );
         session.metadata.cwd = cwd.to_string();
+        session.metadata.cwd_history = vec![cwd.to_string()];
 
         // Save to database
-        self.db.create_session(&session).await?;
+        self.db.create_session(&session, created_by_ip).await?;
+        crate::metrics::global().session_created();
+        self.audit_created(&session, created_by_ip);
 
         // Cache active session
         let session_key = format!("session:{}", session.id);
         let session_json = serde_json::to_vec(&session)?;
-        self.cache.set(session_key, session_json);
+        self.cache.insert_with_ttl(session_key, session_json, CACHE_TTL);
 
         Ok(session)
     }
 
     /// Create a session from remote CLI with a specific ID and cwd
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_session_from_remote(
         &self,
         user_id: &str,
@@ -59,7 +87,9 @@ impl SessionManager {
         tag: &str,
         session_id: &str,
         cwd: &str,
-    ) -> Result<Session> {
+        idempotency_key: Option<&str>,
+        created_by_ip: Option<&str>,
+    ) -> Result<(Session, bool)> {
         info!(
             "Creating remote session: id={}, user={}, tag={}, machine={}, cwd={}",
             session_id, user_id, tag, machine_name, cwd
@@ -73,16 +103,40 @@ impl SessionManager {
             machine_name.to_string(),
         );
         session.metadata.cwd = cwd.to_string();
-
-        // Save to database
-        self.db.create_session(&session).await?;
+        session.metadata.cwd_history = vec![cwd.to_string()];
+
+        // Save to database; a retried request resolves to the session it already created
+        if let Some(key) = idempotency_key {
+            if !self
+                .db
+                .create_session_with_idempotency_key(&session, key, created_by_ip)
+                .await?
+            {
+                let existing = self
+                    .db
+                    .get_session_by_idempotency_key(key)
+                    .await?
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Session for idempotency key {} vanished", key)
+                    })?;
+                info!(
+                    "Remote session for key {} already exists: id={}",
+                    key, existing.id
+                );
+                return Ok((existing, false));
+            }
+        } else {
+            self.db.create_session(&session, created_by_ip).await?;
+        }
+        crate::metrics::global().session_created();
+        self.audit_created(&session, created_by_ip);
 
         // Cache active session
         let session_key = format!("session:{}", session.id);
         let session_json = serde_json::to_vec(&session)?;
-        self.cache.set(session_key, session_json);
+        self.cache.insert_with_ttl(session_key, session_json, CACHE_TTL);
 
-        Ok(session)
+        Ok((session, true))
     }
 
     pub async fn get_session(&self, id: &str) -> Result<Option<Session>> {
@@ -95,7 +149,12 @@ impl SessionManager {
         }
 
         // Fall back to database
-        self.db.get_session(id).await
+        let session = self.db.get_session(id).await?;
+        if let Some(session) = &session {
+            self.cache
+                .insert_with_ttl(session_key, serde_json::to_vec(session)?, CACHE_TTL);
+        }
+        Ok(session)
     }
 
     pub async fn update_session_status(&self, id: &str, status: SessionStatus) -> Result<()> {
@@ -109,7 +168,7 @@ impl SessionManager {
             if let Ok(mut session) = serde_json::from_slice::<Session>(&data) {
                 session.status = status;
                 let session_json = serde_json::to_vec(&session)?;
-                self.cache.set(session_key, session_json);
+                self.cache.insert_with_ttl(session_key, session_json, CACHE_TTL);
             }
         }
 
@@ -119,15 +178,75 @@ impl SessionManager {
     pub async fn update_session_cwd(&self, id: &str, cwd: &str) -> Result<()> {
         debug!("Updating session {} cwd to {}", id, cwd);
 
-        self.db.update_session_cwd(id, cwd).await?;
+        let history = self.db.update_session_cwd(id, cwd).await?;
 
         // Update cache if present
         let session_key = format!("session:{}", id);
         if let Some(data) = self.cache.get(&session_key) {
             if let Ok(mut session) = serde_json::from_slice::<Session>(&data) {
                 session.metadata.cwd = cwd.to_string();
+                session.metadata.cwd_history = history;
+                let session_json = serde_json::to_vec(&session)?;
+                self.cache.insert_with_ttl(session_key, session_json, CACHE_TTL);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Change a session's tag; `Ok(false)` if the user has a live session with that tag
+    pub async fn rename_session(&self, id: &str, user_id: &str, tag: &str) -> Result<bool> {
+        debug!("Renaming session {} to {}", id, tag);
+
+        if !self.db.rename_session(id, user_id, tag).await? {
+            return Ok(false);
+        }
+
+        // Update cache if present
+        let session_key = format!("session:{}", id);
+        if let Some(data) = self.cache.get(&session_key) {
+            if let Ok(mut session) = serde_json::from_slice::<Session>(&data) {
+                session.tag = tag.to_string();
+                let session_json = serde_json::to_vec(&session)?;
+                self.cache.insert_with_ttl(session_key, session_json, CACHE_TTL);
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub async fn update_session_agent_version(&self, id: &str, agent_version: &str) -> Result<()> {
+        debug!("Updating session {} agent version to {}", id, agent_version);
+
+        self.db
+            .update_session_agent_version(id, agent_version)
+            .await?;
+
+        // Update cache if present
+        let session_key = format!("session:{}", id);
+        if let Some(data) = self.cache.get(&session_key) {
+            if let Ok(mut session) = serde_json::from_slice::<Session>(&data) {
+                session.metadata.agent_version = Some(agent_version.to_string());
+                let session_json = serde_json::to_vec(&session)?;
+                self.cache.insert_with_ttl(session_key, session_json, CACHE_TTL);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn update_session_home_dir(&self, id: &str, home_dir: &str) -> Result<()> {
+        debug!("Updating session {} home dir to {}", id, home_dir);
+
+        self.db.update_session_home_dir(id, home_dir).await?;
+
+        // Update cache if present
+        let session_key = format!("session:{}", id);
+        if let Some(data) = self.cache.get(&session_key) {
+            if let Ok(mut session) = serde_json::from_slice::<Session>(&data) {
+                session.metadata.home_dir = Some(home_dir.to_string());
                 let session_json = serde_json::to_vec(&session)?;
-                self.cache.set(session_key, session_json);
+                self.cache.insert_with_ttl(session_key, session_json, CACHE_TTL);
             }
         }
 
@@ -156,17 +275,61 @@ impl SessionManager {
                 session.machine_id = machine_id.to_string();
                 session.machine_name = machine_name.to_string();
                 let session_json = serde_json::to_vec(&session)?;
-                self.cache.set(session_key, session_json);
+                self.cache.insert_with_ttl(session_key, session_json, CACHE_TTL);
             }
         }
 
         Ok(())
     }
 
+    /// The client address recorded when the session was created
+    pub async fn get_session_created_by_ip(&self, id: &str) -> Result<Option<String>> {
+        self.db.get_session_created_by_ip(id).await
+    }
+
+    /// Sessions created from `ip`, newest first, for incident response
+    pub async fn get_sessions_by_ip(&self, ip: &str) -> Result<Vec<Session>> {
+        self.db.list_sessions_by_created_ip(ip).await
+    }
+
+    /// The user's sessions, leaving out archived ones
     pub async fn list_user_sessions(&self, user_id: &str) -> Result<Vec<Session>> {
         self.db.list_sessions_by_user(user_id).await
     }
 
+    /// The user's archived sessions, most recently archived first
+    pub async fn list_archived_sessions(&self, user_id: &str) -> Result<Vec<Session>> {
+        self.db.list_archived_sessions_by_user(user_id).await
+    }
+
+    pub async fn is_archived(&self, id: &str) -> Result<bool> {
+        self.db.is_session_archived(id).await
+    }
+
+    /// A page of the user's sessions older than `after_session_id`, newest first
+    pub async fn list_user_sessions_after(
+        &self,
+        user_id: &str,
+        after_session_id: Option<String>,
+        limit: u32,
+    ) -> Result<Vec<Session>> {
+        self.db
+            .list_sessions_by_user_after(user_id, after_session_id.as_deref(), limit)
+            .await
+    }
+
+    pub async fn list_sessions_by_machine(
+        &self,
+        user_id: &str,
+        machine_id: &str,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Session>> {
+        self.db
+            .list_sessions_by_user_machine(user_id, machine_id, limit, offset)
+            .await
+    }
+
     pub async fn find_session_by_tag(&self, user_id: &str, tag: &str) -> Result<Option<Session>> {
         let sessions = self.db.list_sessions_by_user(user_id).await?;
         // Include Initializing, Running, and Paused sessions
@@ -179,11 +342,14 @@ impl SessionManager {
         self.db.list_active_sessions_by_machine(machine_id).await
     }
 
-    pub async fn terminate_session(&self, id: &str) -> Result<()> {
+    /// Stop a session at `user_id`'s request, keeping its record
+    pub async fn terminate_session(&self, id: &str, user_id: &str, ip: Option<&str>) -> Result<()> {
         info!("Terminating session: {}", id);
 
         self.update_session_status(id, SessionStatus::Terminated)
             .await?;
+        self.audit
+            .record(AuditEvent::SessionTerminate, Some(user_id), Some(id), ip, None);
 
         // Remove from cache
         let session_key = format!("session:{}", id);
@@ -192,11 +358,41 @@ impl SessionManager {
         Ok(())
     }
 
-    pub async fn remove_session(&self, id: &str) -> Result<()> {
-        info!("Removing session: {}", id);
+    /// Archive a session at `user_id`'s request. It stops, drops out of the
+    /// session lists, and can be restored until it is purged.
+    pub async fn remove_session(&self, id: &str, user_id: &str, ip: Option<&str>) -> Result<()> {
+        info!("Archiving session: {}", id);
+
+        self.db.archive_session(id).await?;
+        self.audit
+            .record(AuditEvent::SessionArchive, Some(user_id), Some(id), ip, None);
+
+        // The cached copy still has the old status
+        let session_key = format!("session:{}", id);
+        self.cache.delete(&session_key);
+
+        Ok(())
+    }
+
+    /// Bring an archived session back into the session lists
+    pub async fn restore_session(&self, id: &str, user_id: &str, ip: Option<&str>) -> Result<()> {
+        info!("Restoring session: {}", id);
+
+        self.db.restore_session(id).await?;
+        self.audit
+            .record(AuditEvent::SessionRestore, Some(user_id), Some(id), ip, None);
+
+        Ok(())
+    }
+
+    /// Delete a session for good at `user_id`'s request
+    pub async fn purge_session(&self, id: &str, user_id: &str, ip: Option<&str>) -> Result<()> {
+        info!("Purging session: {}", id);
 
         // Delete from database
         self.db.delete_session(id).await?;
+        self.audit
+            .record(AuditEvent::SessionDelete, Some(user_id), Some(id), ip, None);
 
         // Remove from cache
         let session_key = format!("session:{}", id);
@@ -205,3 +401,35 @@ impl SessionManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DatabaseConfig;
+
+    #[tokio::test]
+    async fn test_cached_sessions_respect_cap() {
+        let db = Arc::new(
+            Database::new(":memory:", DatabaseConfig::default())
+                .await
+                .unwrap(),
+        );
+        let cache = Arc::new(MemoryCache::with_max_entries(10));
+        let manager = SessionManager::new(db, cache.clone());
+
+        let mut ids = Vec::new();
+        for i in 0..50 {
+            let tag = format!("tag-{}", i);
+            let session = manager
+                .create_session("u1", "m1", "laptop", &tag, "/tmp", None)
+                .await
+                .unwrap();
+            ids.push(session.id);
+        }
+        assert!(cache.stats().entries <= 10);
+
+        // Evicted sessions are still served from the database
+        let first = manager.get_session(&ids[0]).await.unwrap().unwrap();
+        assert_eq!(first.tag, "tag-0");
+    }
+}
@@ -0,0 +1,181 @@
+//! Time-based one-time passwords (RFC 6238) for two-factor login
+//!
+//! Codes are computed by `totp-lite`. Secrets are shown to the user in
+//! base32 and stored sealed with AES-256-GCM under a key derived from the
+//! JWT secret, so a copy of the database alone doesn't reveal them.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use data_encoding::BASE32_NOPAD;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use sha2::{Digest, Sha256};
+use totp_lite::{totp_custom, Sha1};
+
+/// Digits in a code
+pub const DIGITS: u32 = 6;
+
+/// Seconds each code is valid for
+pub const PERIOD_SECS: u64 = 30;
+
+/// Codes from this many periods either side of now are accepted, for clock drift
+const SKEW_PERIODS: u64 = 1;
+
+/// Shown as the account's issuer in authenticator apps
+const ISSUER: &str = "Happy Coding";
+
+/// A new random 160-bit secret, base32-encoded as authenticator apps expect
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// `otpauth://` URI for enrolling `secret` in an authenticator app, usually shown as a QR code
+pub fn otpauth_uri(secret: &str, account: &str) -> String {
+    let label = format!("{}:{}", ISSUER, account);
+    format!(
+        "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        url_encode(&label),
+        secret,
+        url_encode(ISSUER),
+        DIGITS,
+        PERIOD_SECS
+    )
+}
+
+/// Check `code` against `secret` at `unix_time`, returning the time step
+/// (`unix_time / PERIOD_SECS`, give or take the skew) it is the code for.
+///
+/// A code stays valid for its whole window; callers keep it from being used
+/// twice by refusing steps at or before the last one they accepted.
+pub fn verify(secret: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+
+    let step = unix_time / PERIOD_SECS;
+    (step.saturating_sub(SKEW_PERIODS)..=step + SKEW_PERIODS)
+        .find(|step| constant_time_eq(code_at(&key, *step).as_bytes(), code.as_bytes()))
+}
+
+/// The code for time step `step`
+fn code_at(key: &[u8], step: u64) -> String {
+    totp_custom::<Sha1>(PERIOD_SECS, DIGITS, key, step * PERIOD_SECS)
+}
+
+/// The code for a base32 `secret` at `unix_time`, as an authenticator app would show it
+#[cfg(test)]
+pub fn code_for(secret: &str, unix_time: u64) -> String {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+    code_at(&key, unix_time / PERIOD_SECS)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn url_encode(value: &str) -> String {
+    openidconnect::url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// Seals TOTP secrets for storage
+pub struct SecretKey(LessSafeKey);
+
+impl SecretKey {
+    /// Derive the key from the JWT secret, so there is no extra secret to configure
+    pub fn from_jwt_secret(jwt_secret: &str) -> Self {
+        let key = Sha256::new()
+            .chain_update(b"happy-coding totp secret key\0")
+            .chain_update(jwt_secret.as_bytes())
+            .finalize();
+        let key = UnboundKey::new(&AES_256_GCM, &key).expect("SHA-256 output is an AES-256 key");
+        Self(LessSafeKey::new(key))
+    }
+
+    /// Encrypt `secret` under a random nonce, returning base64 of nonce and ciphertext
+    pub fn seal(&self, secret: &str) -> Result<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let mut sealed = secret.as_bytes().to_vec();
+        self.0
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt TOTP secret"))?;
+
+        let mut stored = nonce.to_vec();
+        stored.extend_from_slice(&sealed);
+        Ok(base64::engine::general_purpose::STANDARD.encode(stored))
+    }
+
+    /// Decrypt a secret produced by [`SecretKey::seal`]
+    pub fn open(&self, stored: &str) -> Result<String> {
+        let stored = base64::engine::general_purpose::STANDARD
+            .decode(stored)
+            .context("Stored TOTP secret is not base64")?;
+        if stored.len() < NONCE_LEN {
+            anyhow::bail!("Stored TOTP secret is truncated");
+        }
+        let (nonce, sealed) = stored.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| anyhow::anyhow!("Invalid TOTP secret nonce"))?;
+
+        let mut sealed = sealed.to_vec();
+        let secret = self
+            .0
+            .open_in_place(nonce, Aad::empty(), &mut sealed)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt TOTP secret; did JWT_SECRET change?"))?;
+        String::from_utf8(secret.to_vec()).context("Stored TOTP secret is not UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        // RFC 6238 appendix B, SHA-1, truncated to six digits
+        let secret = BASE32_NOPAD.encode(b"12345678901234567890");
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            assert_eq!(verify(&secret, code, time), Some(time / PERIOD_SECS), "{}", code);
+            assert_eq!(code_for(&secret, time), code);
+        }
+
+        // One period of drift either way is tolerated, two are not
+        let step = 1111111109 / PERIOD_SECS;
+        assert_eq!(verify(&secret, "081804", 1111111109 + PERIOD_SECS), Some(step));
+        assert_eq!(verify(&secret, "081804", 1111111109 + 2 * PERIOD_SECS), None);
+        assert_eq!(verify(&secret, "81804", 1111111109), None);
+        assert_eq!(verify(&secret, "08180a", 1111111109), None);
+    }
+
+    #[test]
+    fn test_sealed_secret_round_trip() {
+        let key = SecretKey::from_jwt_secret("jwt-secret");
+        let secret = generate_secret();
+        let sealed = key.seal(&secret).unwrap();
+        assert!(!sealed.contains(&secret));
+        assert_ne!(sealed, key.seal(&secret).unwrap());
+        assert_eq!(key.open(&sealed).unwrap(), secret);
+
+        assert!(SecretKey::from_jwt_secret("other").open(&sealed).is_err());
+        assert!(key.open("AAAA").is_err());
+
+        let uri = otpauth_uri(&secret, "a@example.com");
+        assert!(uri.starts_with("otpauth://totp/Happy+Coding%3Aa%40example.com?secret="));
+        assert!(uri.contains(&secret));
+    }
+}
@@ -1,19 +1,411 @@
-//! SQLite database layer (embedded, no external dependencies)
+//! Database layer: embedded SQLite by default, or a shared PostgreSQL
+//! server when built with the `postgres` feature
+//!
+//! Queries are written once in SQL both backends accept and run through
+//! [`with_pool!`]; the few that can't be are matched on [`StorageBackend`].
 
+use crate::metrics;
 use anyhow::{Context, Result};
-use happy_core::{Machine, Platform, Session, SessionStatus};
+use happy_core::{Machine, MachineInfo, Platform, Session, SessionStatus};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
 use sqlx::SqlitePool;
-use std::sync::Arc;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(feature = "postgres")]
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+
+/// Served by `idx_sessions_user_machine`; pinned, since `idx_sessions_user_created`
+/// would otherwise win by avoiding the sort
+const SESSIONS_BY_USER_MACHINE_QUERY: &str = r#"
+    SELECT id, tag, user_id, machine_id, machine_name, status,
+           encrypted_data_key, created_at, last_activity,
+           cwd, cwd_history, env, claude_version, agent_version, home_dir, shell
+    FROM sessions INDEXED BY idx_sessions_user_machine
+    WHERE user_id = $1 AND machine_id = $2 AND archived_at IS NULL
+    ORDER BY created_at DESC
+    LIMIT $3 OFFSET $4
+"#;
+
+/// Postgres has no index hints; a NULL limit means no limit
+#[cfg(feature = "postgres")]
+const PG_SESSIONS_BY_USER_MACHINE_QUERY: &str = r#"
+    SELECT id, tag, user_id, machine_id, machine_name, status,
+           encrypted_data_key, created_at, last_activity,
+           cwd, cwd_history, env, claude_version, agent_version, home_dir, shell
+    FROM sessions
+    WHERE user_id = $1 AND machine_id = $2 AND archived_at IS NULL
+    ORDER BY created_at DESC
+    LIMIT $3 OFFSET $4
+"#;
+
+/// Served by `idx_sessions_user_created`; a cursor that no longer exists yields no rows
+const SESSIONS_BY_USER_AFTER_QUERY: &str = r#"
+    SELECT id, tag, user_id, machine_id, machine_name, status,
+           encrypted_data_key, created_at, last_activity,
+           cwd, cwd_history, env, claude_version, agent_version, home_dir, shell
+    FROM sessions
+    WHERE user_id = $1
+      AND archived_at IS NULL
+      AND ($2 IS NULL
+           OR (created_at, id) < (SELECT created_at, id FROM sessions WHERE id = $2))
+    ORDER BY created_at DESC, id DESC
+    LIMIT $3
+"#;
+
+/// Columns added to the initial schema before it was versioned, as `(table, column definition)`
+const LEGACY_COLUMNS: &[(&str, &str)] = &[
+    ("users", "deleted_at DATETIME"),
+    ("sessions", "machine_name TEXT DEFAULT 'Unknown'"),
+    ("sessions", "agent_version TEXT"),
+    ("sessions", "idempotency_key TEXT"),
+    ("machines", "last_heartbeat DATETIME"),
+    ("machines", "daemon_version TEXT"),
+    ("machines", "arch TEXT"),
+    ("machines", "cpu_count INTEGER"),
+    ("machines", "memory_mb INTEGER"),
+];
+
+/// A row of `_sqlx_migrations`
+#[derive(Debug, Clone, serde::Serialize, sqlx::FromRow)]
+pub struct MigrationRecord {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: chrono::DateTime<chrono::Utc>,
+}
+
+/// Size and free space of the database file
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct DatabaseStats {
+    pub size_mb: f64,
+    /// Share of pages on the freelist, reclaimable by `VACUUM`
+    pub fragmentation_ratio: f64,
+}
+
+/// The row of `sso_config`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
+pub struct SsoConfig {
+    /// Where the IdP publishes its SAML metadata
+    pub idp_metadata_url: String,
+    /// This server's SAML entity ID, as registered with the IdP
+    pub entity_id: String,
+    /// The IdP's signing certificate, PEM
+    pub certificate: String,
+    /// Email domain whose users sign in through the IdP
+    pub domain: String,
+}
+
+/// An event to append to `audit_log`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    /// RFC 3339, when the event happened rather than when it was written
+    pub timestamp: String,
+    /// Who acted, if known; failed logins for unknown emails have no user
+    pub user_id: Option<String>,
+    pub event_type: String,
+    /// `user` or `session`
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub ip_address: Option<String>,
+    /// JSON object with event-specific details
+    pub metadata: Option<String>,
+}
+
+/// A row of `audit_log`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, sqlx::FromRow)]
+pub struct AuditRecord {
+    pub id: i64,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub entry: AuditEntry,
+}
+
+/// Entries kept in a session's `cwd_history`
+const CWD_HISTORY_LIMIT: usize = 20;
+
+/// Append `cwd` unless it is already the latest entry, dropping the oldest past the limit
+fn push_cwd_history(history: &mut Vec<String>, cwd: &str) {
+    if history.last().map(String::as_str) != Some(cwd) {
+        history.push(cwd.to_string());
+    }
+    if history.len() > CWD_HISTORY_LIMIT {
+        history.drain(..history.len() - CWD_HISTORY_LIMIT);
+    }
+}
+
+/// Connection pool and SQLite settings for [`Database::new`]
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    /// Pooled connections; under WAL, readers don't wait on the writer
+    pub max_connections: u32,
+    /// Write-ahead logging; only worth disabling on filesystems without shared memory
+    pub wal: bool,
+    /// How long a connection waits on a lock before failing with `SQLITE_BUSY`
+    pub busy_timeout: Duration,
+    /// Page cache per connection, in KiB
+    pub cache_size_kib: u32,
+}
+
+impl DatabaseConfig {
+    /// One connection per CPU, between 4 and 32
+    pub fn default_max_connections() -> u32 {
+        std::thread::available_parallelism()
+            .map_or(4, |n| n.get())
+            .clamp(4, 32) as u32
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: Self::default_max_connections(),
+            wal: true,
+            busy_timeout: Duration::from_secs(5),
+            cache_size_kib: 4096,
+        }
+    }
+}
+
+/// Where the database lives, from `DATABASE_URL` or `DATABASE_PATH`
+#[derive(Clone, PartialEq)]
+pub enum DatabaseLocation {
+    /// A SQLite file, or `:memory:`
+    Sqlite(String),
+    /// A `postgres://` connection URL
+    Postgres(String),
+}
+
+impl DatabaseLocation {
+    /// `postgres://` and `postgresql://` URLs select Postgres; anything else
+    /// is a SQLite file path, with an optional `sqlite:` scheme
+    pub fn parse(value: &str) -> Self {
+        if value.starts_with("postgres://") || value.starts_with("postgresql://") {
+            return Self::Postgres(value.to_string());
+        }
+        let path = value
+            .strip_prefix("sqlite://")
+            .or_else(|| value.strip_prefix("sqlite:"))
+            .unwrap_or(value);
+        Self::Sqlite(path.to_string())
+    }
+}
+
+/// Shows Postgres URLs without their password, so the location can be logged
+impl std::fmt::Display for DatabaseLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sqlite(path) => write!(f, "sqlite:{}", path),
+            Self::Postgres(url) => match reqwest::Url::parse(url) {
+                Ok(mut url) if url.password().is_some() => {
+                    let _ = url.set_password(Some("***"));
+                    write!(f, "{}", url)
+                }
+                _ => write!(f, "{}", url),
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for DatabaseLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// The connection pool of whichever backend the server was started with
+#[derive(Clone)]
+pub enum StorageBackend {
+    Sqlite(SqlitePool),
+    #[cfg(feature = "postgres")]
+    Postgres(PgPool),
+}
+
+/// Run `$body` with `$pool` bound to the backend's pool.
+///
+/// The body is compiled once per backend, so its SQL must be valid on both.
+macro_rules! with_pool {
+    ($backend:expr, |$pool:ident| $body:expr) => {
+        match $backend {
+            StorageBackend::Sqlite($pool) => $body,
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres($pool) => $body,
+        }
+    };
+}
+
+/// A timestamp parameter in the form each backend compares natively: the
+/// `YYYY-MM-DD HH:MM:SS` text of SQLite's `CURRENT_TIMESTAMP`, or a Postgres
+/// `TIMESTAMPTZ`
+struct DbTime(chrono::DateTime<chrono::Utc>);
+
+impl sqlx::Type<sqlx::Sqlite> for DbTime {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as sqlx::Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'q> sqlx::Encode<'q, sqlx::Sqlite> for DbTime {
+    fn encode_by_ref(
+        &self,
+        buf: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> sqlx::encode::IsNull {
+        let text = self.0.format("%Y-%m-%d %H:%M:%S").to_string();
+        <String as sqlx::Encode<'q, sqlx::Sqlite>>::encode(text, buf)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl sqlx::Type<sqlx::Postgres> for DbTime {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <chrono::DateTime<chrono::Utc> as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl sqlx::Encode<'_, sqlx::Postgres> for DbTime {
+    fn encode_by_ref(&self, buf: &mut sqlx::postgres::PgArgumentBuffer) -> sqlx::encode::IsNull {
+        <chrono::DateTime<chrono::Utc> as sqlx::Encode<sqlx::Postgres>>::encode_by_ref(&self.0, buf)
+    }
+}
 
 pub struct Database {
-    pool: Arc<SqlitePool>,
+    pool: StorageBackend,
+    /// A connection of its own for audit writes, so they never queue behind
+    /// the hot path for a pooled connection
+    audit_pool: StorageBackend,
 }
 
 impl Database {
-    pub async fn new(database_path: &str) -> Result<Self> {
+    /// Open the database at `location` with the backend it calls for
+    pub async fn open(location: &DatabaseLocation, config: DatabaseConfig) -> Result<Self> {
+        match location {
+            DatabaseLocation::Sqlite(path) => Self::new(path, config).await,
+            #[cfg(feature = "postgres")]
+            DatabaseLocation::Postgres(url) => Self::new_postgres(url, config).await,
+            #[cfg(not(feature = "postgres"))]
+            DatabaseLocation::Postgres(_) => anyhow::bail!(
+                "DATABASE_URL is a Postgres URL, but this build lacks the `postgres` feature"
+            ),
+        }
+    }
+
+    /// Open the SQLite database at `database_path`, creating it if needed.
+    ///
+    /// `:memory:` opens a private in-memory database shared by the pool.
+    pub async fn new(database_path: &str, config: DatabaseConfig) -> Result<Self> {
         tracing::info!("Opening SQLite database at: {}", database_path);
 
+        let options = if database_path == ":memory:" {
+            SqliteConnectOptions::from_str("sqlite::memory:")?
+        } else {
+            Self::prepare_directory(database_path).await?;
+            SqliteConnectOptions::new()
+                .filename(database_path)
+                .create_if_missing(true)
+        };
+
+        tracing::info!("Connecting to SQLite with {:?}", config);
+
+        // NORMAL only loses the last commits on power loss when paired with WAL
+        let (journal_mode, synchronous) = if config.wal {
+            (
+                sqlx::sqlite::SqliteJournalMode::Wal,
+                sqlx::sqlite::SqliteSynchronous::Normal,
+            )
+        } else {
+            (
+                sqlx::sqlite::SqliteJournalMode::Delete,
+                sqlx::sqlite::SqliteSynchronous::Full,
+            )
+        };
+        let options = options
+            .journal_mode(journal_mode)
+            .synchronous(synchronous)
+            .busy_timeout(config.busy_timeout)
+            // Negative sizes are in KiB rather than pages
+            .pragma("cache_size", format!("-{}", config.cache_size_kib));
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect_with(options.clone())
+            .await
+            .with_context(|| {
+                format!("Failed to connect to SQLite database at: {}", database_path)
+            })?;
+
+        tracing::info!("SQLite connection established, running migrations...");
+
+        // Databases from before versioned migrations need their columns brought up to date
+        Self::upgrade_legacy_schema(&pool)
+            .await
+            .context("Failed to upgrade legacy database schema")?;
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .context("Failed to run database migrations")?;
+
+        let audit_pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .context("Failed to open the audit log connection")?;
+
+        tracing::info!("Database initialization complete");
+
+        Ok(Self {
+            pool: StorageBackend::Sqlite(pool),
+            audit_pool: StorageBackend::Sqlite(audit_pool),
+        })
+    }
+
+    /// Connect to the Postgres database at `url` and bring its schema up to date
+    #[cfg(feature = "postgres")]
+    pub async fn new_postgres(url: &str, config: DatabaseConfig) -> Result<Self> {
+        let options = PgConnectOptions::from_str(url).context("Invalid Postgres URL")?;
+        Self::connect_postgres(options, config).await
+    }
+
+    #[cfg(feature = "postgres")]
+    async fn connect_postgres(options: PgConnectOptions, config: DatabaseConfig) -> Result<Self> {
+        tracing::info!(
+            "Connecting to Postgres at {}:{} with {} connections",
+            options.get_host(),
+            options.get_port(),
+            config.max_connections
+        );
+        // Timestamps written without a zone read as UTC, as on SQLite
+        let options = options.options([("timezone", "UTC")]);
+
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect_with(options.clone())
+            .await
+            .context("Failed to connect to Postgres")?;
+
+        tracing::info!("Postgres connection established, running migrations...");
+
+        sqlx::migrate!("./migrations/postgres")
+            .run(&pool)
+            .await
+            .context("Failed to run database migrations")?;
+
+        let audit_pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .context("Failed to open the audit log connection")?;
+
+        tracing::info!("Database initialization complete");
+
+        Ok(Self {
+            pool: StorageBackend::Postgres(pool),
+            audit_pool: StorageBackend::Postgres(audit_pool),
+        })
+    }
+
+    /// Create the directory of a SQLite file and check that it is writable
+    async fn prepare_directory(database_path: &str) -> Result<()> {
         // Create parent directory if needed
         if let Some(parent) = std::path::Path::new(database_path).parent() {
             tracing::info!("Creating parent directory: {}", parent.display());
@@ -42,122 +434,137 @@ impl Database {
             }
         }
 
-        tracing::info!("Connecting to SQLite...");
-
-        // Use SqliteConnectOptions for better control
-        let options = SqliteConnectOptions::new()
-            .filename(database_path)
-            .create_if_missing(true)
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
-            .synchronous(sqlx::sqlite::SqliteSynchronous::Normal);
-
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect_with(options)
-            .await
-            .with_context(|| {
-                format!("Failed to connect to SQLite database at: {}", database_path)
-            })?;
-
-        tracing::info!("SQLite connection established, running migrations...");
-
-        // Run migrations (inline for simplicity)
-        Self::run_migrations(&pool)
-            .await
-            .context("Failed to run database migrations")?;
-
-        tracing::info!("Database initialization complete");
-
-        Ok(Self {
-            pool: Arc::new(pool),
-        })
+        Ok(())
     }
 
-    async fn run_migrations(pool: &SqlitePool) -> Result<()> {
-        // Users table
-        sqlx::query(
+    /// Bring a database created before versioned migrations up to the initial schema.
+    ///
+    /// Such databases have tables but no `_sqlx_migrations`, and older ones lack
+    /// columns that used to be added with `ALTER TABLE` on startup.
+    async fn upgrade_legacy_schema(pool: &SqlitePool) -> Result<()> {
+        let tracked: Option<(String,)> = sqlx::query_as(
             r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                email TEXT UNIQUE NOT NULL,
-                name TEXT,
-                password_hash TEXT NOT NULL,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )
+            SELECT name FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'
             "#,
         )
-        .execute(pool)
+        .fetch_optional(pool)
         .await?;
+        if tracked.is_some() {
+            return Ok(());
+        }
 
-        // Sessions table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS sessions (
-                id TEXT PRIMARY KEY,
-                tag TEXT NOT NULL,
-                user_id TEXT NOT NULL,
-                machine_id TEXT NOT NULL,
-                machine_name TEXT DEFAULT 'Unknown',
-                status TEXT DEFAULT 'initializing',
-                encrypted_data_key BLOB,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                last_activity DATETIME DEFAULT CURRENT_TIMESTAMP,
-                cwd TEXT DEFAULT '/',
-                env TEXT DEFAULT '{}',
-                claude_version TEXT,
-                shell TEXT DEFAULT '/bin/bash'
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+        for (table, column) in LEGACY_COLUMNS {
+            let _ = sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {}", table, column))
+                .execute(pool)
+                .await; // Ignore error if column already exists or the database is new
+        }
 
-        // Migration: Add machine_name column if it doesn't exist
-        let _ = sqlx::query(
-            r#"
-            ALTER TABLE sessions ADD COLUMN machine_name TEXT DEFAULT 'Unknown'
-            "#,
-        )
-        .execute(pool)
-        .await; // Ignore error if column already exists
+        Ok(())
+    }
 
-        // Machines table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS machines (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL,
-                name TEXT NOT NULL,
-                public_key BLOB NOT NULL,
-                platform TEXT DEFAULT 'linux',
-                capabilities TEXT DEFAULT 'terminal,file_system',
-                last_seen DATETIME DEFAULT CURRENT_TIMESTAMP,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                hostname TEXT
+    /// Migrations applied to this database, oldest first
+    pub async fn migration_status(&self) -> Result<Vec<MigrationRecord>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let rows = sqlx::query_as(
+                r#"
+                SELECT version, description, installed_on
+                FROM _sqlx_migrations ORDER BY installed_on, version
+                "#,
             )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+            .fetch_all(pool)
+            .await?;
 
-        // Access keys table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS access_keys (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL,
-                name TEXT NOT NULL,
-                key_prefix TEXT NOT NULL,
-                permissions TEXT DEFAULT '',
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                expires_at DATETIME,
-                last_used_at DATETIME
-            )
-            "#,
-        )
-        .execute(pool)
-        .await?;
+            Ok(rows)
+        })
+    }
 
+    /// Size and fragmentation: free pages on SQLite, dead rows on Postgres
+    pub async fn stats(&self) -> Result<DatabaseStats> {
+        let _timer = metrics::time_db_query();
+        match &self.pool {
+            StorageBackend::Sqlite(pool) => {
+                let (page_count,): (i64,) = sqlx::query_as("PRAGMA page_count")
+                    .fetch_one(pool)
+                    .await?;
+                let (freelist_count,): (i64,) = sqlx::query_as("PRAGMA freelist_count")
+                    .fetch_one(pool)
+                    .await?;
+                let (page_size,): (i64,) = sqlx::query_as("PRAGMA page_size")
+                    .fetch_one(pool)
+                    .await?;
+
+                let fragmentation_ratio = if page_count > 0 {
+                    freelist_count as f64 / page_count as f64
+                } else {
+                    0.0
+                };
+                Ok(DatabaseStats {
+                    size_mb: (page_count * page_size) as f64 / (1024.0 * 1024.0),
+                    fragmentation_ratio,
+                })
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => {
+                let (size,): (i64,) =
+                    sqlx::query_as("SELECT pg_database_size(current_database())")
+                        .fetch_one(pool)
+                        .await?;
+                let (fragmentation_ratio,): (f64,) = sqlx::query_as(
+                    r#"
+                    SELECT COALESCE(
+                        SUM(n_dead_tup)::float8 / NULLIF(SUM(n_live_tup + n_dead_tup), 0)::float8,
+                        0
+                    )
+                    FROM pg_stat_user_tables
+                    "#,
+                )
+                .fetch_one(pool)
+                .await?;
+                Ok(DatabaseStats {
+                    size_mb: size as f64 / (1024.0 * 1024.0),
+                    fragmentation_ratio,
+                })
+            }
+        }
+    }
+
+    /// Rebuild the database file to reclaim free pages.
+    ///
+    /// Runs in place rather than through `VACUUM INTO` and a rename: pooled
+    /// connections keep the old file open, so writes after the copy would be
+    /// lost. The WAL is truncated afterwards so the space is returned to disk.
+    /// On Postgres this is a plain `VACUUM` of dead rows.
+    pub async fn vacuum(&self) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        match &self.pool {
+            StorageBackend::Sqlite(pool) => {
+                sqlx::query("VACUUM").execute(pool).await?;
+                sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                    .execute(pool)
+                    .await?;
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => {
+                // Not allowed in a transaction block, so not as a prepared statement either
+                sqlx::Executor::execute(pool, "VACUUM").await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Refresh query planner statistics where the database thinks they are stale
+    pub async fn analyze(&self) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        match &self.pool {
+            StorageBackend::Sqlite(pool) => {
+                sqlx::query("PRAGMA optimize").execute(pool).await?;
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => {
+                sqlx::query("ANALYZE").execute(pool).await?;
+            }
+        }
         Ok(())
     }
 
@@ -168,299 +575,1334 @@ impl Database {
         password_hash: &str,
         name: Option<&str>,
     ) -> Result<String> {
-        let id = uuid::Uuid::new_v4().to_string();
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let id = uuid::Uuid::new_v4().to_string();
 
-        sqlx::query(
-            r#"
-            INSERT INTO users (id, email, name, password_hash)
-            VALUES (?1, ?2, ?3, ?4)
-            "#,
-        )
-        .bind(&id)
-        .bind(email)
-        .bind(name)
-        .bind(password_hash)
-        .execute(&*self.pool)
-        .await?;
+            sqlx::query(
+                r#"
+                INSERT INTO users (id, email, name, password_hash)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(&id)
+            .bind(email)
+            .bind(name)
+            .bind(password_hash)
+            .execute(pool)
+            .await?;
 
-        Ok(id)
+            Ok(id)
+        })
     }
 
     pub async fn get_user_by_email(&self, email: &str) -> Result<Option<(String, String)>> {
-        let row: Option<(String, String)> = sqlx::query_as(
-            r#"
-            SELECT id, password_hash FROM users WHERE email = ?1
-            "#,
-        )
-        .bind(email)
-        .fetch_optional(&*self.pool)
-        .await?;
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<(String, String)> = sqlx::query_as(
+                r#"
+                SELECT id, password_hash FROM users WHERE email = $1 AND deleted_at IS NULL
+                "#,
+            )
+            .bind(email)
+            .fetch_optional(pool)
+            .await?;
 
-        Ok(row)
+            Ok(row)
+        })
     }
 
     pub async fn get_user_by_id(
         &self,
         user_id: &str,
     ) -> Result<Option<(String, String, Option<String>)>> {
-        let row: Option<(String, String, Option<String>)> = sqlx::query_as(
-            r#"
-            SELECT id, email, name FROM users WHERE id = ?1
-            "#,
-        )
-        .bind(user_id)
-        .fetch_optional(&*self.pool)
-        .await?;
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<(String, String, Option<String>)> = sqlx::query_as(
+                r#"
+                SELECT id, email, name FROM users WHERE id = $1
+                "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row)
+        })
+    }
 
-        Ok(row)
+    pub async fn get_user_password_hash(&self, user_id: &str) -> Result<Option<String>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<(String,)> = sqlx::query_as(
+                r#"
+                SELECT password_hash FROM users WHERE id = $1 AND deleted_at IS NULL
+                "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row.map(|(hash,)| hash))
+        })
+    }
+
+    /// When the account for `email` stops being locked after failed logins, if ever
+    pub async fn get_user_locked_until(
+        &self,
+        email: &str,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<(Option<chrono::DateTime<chrono::Utc>>,)> = sqlx::query_as(
+                r#"
+                SELECT locked_until FROM users WHERE email = $1 AND deleted_at IS NULL
+                "#,
+            )
+            .bind(email)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row.and_then(|(until,)| until))
+        })
+    }
+
+    /// Lock the account for `email` until `until`, or unlock it with `None`.
+    ///
+    /// Returns `false` if there is no such user.
+    pub async fn set_user_locked_until(
+        &self,
+        email: &str,
+        until: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<bool> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+                r#"
+                UPDATE users SET locked_until = $1 WHERE email = $2 AND deleted_at IS NULL
+                "#,
+            )
+            .bind(until.map(DbTime))
+            .bind(email)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    /// A user's encrypted TOTP secret and whether two-factor login is enabled,
+    /// or `None` if they haven't started setting it up
+    pub async fn get_user_totp(&self, user_id: &str) -> Result<Option<(String, bool)>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<(Option<String>, bool)> = sqlx::query_as(
+                r#"
+                SELECT totp_secret, totp_enabled FROM users WHERE id = $1 AND deleted_at IS NULL
+                "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row.and_then(|(secret, enabled)| Some((secret?, enabled))))
+        })
+    }
+
+    /// Store a new encrypted TOTP secret, disabled until a code from it is verified.
+    ///
+    /// Returns `false` if there is no such user.
+    pub async fn set_user_totp_secret(&self, user_id: &str, secret: &str) -> Result<bool> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+                r#"
+                UPDATE users SET totp_secret = $1, totp_enabled = FALSE
+                WHERE id = $2 AND deleted_at IS NULL
+                "#,
+            )
+            .bind(secret)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    /// Require a TOTP code at login. Returns `false` if the user has no secret.
+    pub async fn enable_user_totp(&self, user_id: &str) -> Result<bool> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+                r#"
+                UPDATE users SET totp_enabled = TRUE
+                WHERE id = $1 AND totp_secret IS NOT NULL AND deleted_at IS NULL
+                "#,
+            )
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    /// Record that a TOTP code for time step `step` was accepted.
+    ///
+    /// Returns `false`, recording nothing, if a code for this step or a later
+    /// one was already accepted, so each code works only once.
+    pub async fn use_user_totp_step(&self, user_id: &str, step: i64) -> Result<bool> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+                r#"
+                UPDATE users SET totp_last_step = $1
+                WHERE id = $2 AND deleted_at IS NULL
+                  AND (totp_last_step IS NULL OR totp_last_step < $1)
+                "#,
+            )
+            .bind(step)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    pub async fn is_user_admin(&self, user_id: &str) -> Result<bool> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<(bool,)> = sqlx::query_as(
+                r#"
+                SELECT is_admin FROM users WHERE id = $1 AND deleted_at IS NULL
+                "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row.is_some_and(|(is_admin,)| is_admin))
+        })
+    }
+
+    /// Mark a user deleted and erase everything they own in one transaction.
+    ///
+    /// The user row itself is kept until `purge_deleted_users` removes it, its
+    /// email replaced by a tombstone so the address can sign up again at once.
+    /// Returns the ids of the deleted sessions and machines so callers can
+    /// drop them from the cache.
+    pub async fn soft_delete_user(&self, user_id: &str) -> Result<(Vec<String>, Vec<String>)> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+
+            let session_ids: Vec<(String,)> =
+                sqlx::query_as("SELECT id FROM sessions WHERE user_id = $1")
+                    .bind(user_id)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            let machine_ids: Vec<(String,)> =
+                sqlx::query_as("SELECT id FROM machines WHERE user_id = $1")
+                    .bind(user_id)
+                    .fetch_all(&mut *tx)
+                    .await?;
+
+            sqlx::query(
+                r#"
+                UPDATE users SET deleted_at = CURRENT_TIMESTAMP, email = 'deleted:' || id
+                WHERE id = $1
+                "#,
+            )
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+            for table in ["sessions", "machines", "access_keys", "push_tokens"] {
+                sqlx::query(&format!("DELETE FROM {} WHERE user_id = $1", table))
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok((
+                session_ids.into_iter().map(|(id,)| id).collect(),
+                machine_ids.into_iter().map(|(id,)| id).collect(),
+            ))
+        })
+    }
+
+    /// Permanently remove users soft-deleted before `cutoff`
+    pub async fn purge_deleted_users(&self, cutoff: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+                r#"
+                DELETE FROM users WHERE deleted_at IS NOT NULL AND deleted_at <= $1
+                "#,
+            )
+            .bind(DbTime(cutoff))
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected())
+        })
+    }
+
+    /// A user's current `token_version`; JWTs carrying another one are revoked
+    pub async fn get_user_token_version(&self, user_id: &str) -> Result<Option<i64>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<(i64,)> = sqlx::query_as(
+                r#"
+                SELECT token_version FROM users WHERE id = $1 AND deleted_at IS NULL
+                "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row.map(|(version,)| version))
+        })
+    }
+
+    /// Store the hash of a new password reset token, dropping expired ones
+    pub async fn create_password_reset_token(
+        &self,
+        token_hash: &str,
+        user_id: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query("DELETE FROM password_reset_tokens WHERE expires_at <= CURRENT_TIMESTAMP")
+                .execute(pool)
+                .await?;
+
+            sqlx::query(
+                r#"
+                INSERT INTO password_reset_tokens (token_hash, user_id, expires_at)
+                VALUES ($1, $2, $3)
+                "#,
+            )
+            .bind(token_hash)
+            .bind(user_id)
+            .bind(DbTime(expires_at))
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    /// Redeem a reset token: set the new password, revoke the user's JWTs by
+    /// bumping `token_version` and delete all of the user's reset tokens.
+    ///
+    /// Returns the user id, or `None` if the token is unknown or expired.
+    pub async fn reset_password_with_token(
+        &self,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> Result<Option<String>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+
+            let user: Option<(String,)> = sqlx::query_as(
+                r#"
+                SELECT t.user_id FROM password_reset_tokens t
+                JOIN users u ON u.id = t.user_id
+                WHERE t.token_hash = $1 AND t.expires_at > CURRENT_TIMESTAMP
+                  AND u.deleted_at IS NULL
+                "#,
+            )
+            .bind(token_hash)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let Some((user_id,)) = user else {
+                return Ok(None);
+            };
+
+            sqlx::query(
+                r#"
+                UPDATE users SET password_hash = $1, token_version = token_version + 1
+                WHERE id = $2
+                "#,
+            )
+            .bind(password_hash)
+            .bind(&user_id)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1")
+                .bind(&user_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+            Ok(Some(user_id))
+        })
     }
 
     // Session operations
-    pub async fn create_session(&self, session: &Session) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT OR REPLACE INTO sessions (id, tag, user_id, machine_id, machine_name, status, cwd, env, shell)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
-            "#,
-        )
-        .bind(&session.id)
-        .bind(&session.tag)
-        .bind(&session.user_id)
-        .bind(&session.machine_id)
-        .bind(&session.machine_name)
-        .bind(session.status.to_string())
-        .bind(&session.metadata.cwd)
-        .bind(serde_json::to_string(&session.metadata.env)?)
-        .bind(&session.metadata.shell)
-        .execute(&*self.pool)
-        .await?;
 
-        Ok(())
+    /// Insert `session`, or replace the row with its id as a fresh one,
+    /// recording the client address that created it
+    pub async fn create_session(
+        &self,
+        session: &Session,
+        created_by_ip: Option<&str>,
+    ) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                INSERT INTO sessions (id, tag, user_id, machine_id, machine_name, status, cwd, cwd_history, env, agent_version, home_dir, shell, created_by_ip)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+                ON CONFLICT(id) DO UPDATE SET
+                    tag = excluded.tag,
+                    user_id = excluded.user_id,
+                    machine_id = excluded.machine_id,
+                    machine_name = excluded.machine_name,
+                    status = excluded.status,
+                    cwd = excluded.cwd,
+                    cwd_history = excluded.cwd_history,
+                    env = excluded.env,
+                    agent_version = excluded.agent_version,
+                    home_dir = excluded.home_dir,
+                    shell = excluded.shell,
+                    created_by_ip = excluded.created_by_ip,
+                    encrypted_data_key = NULL,
+                    claude_version = NULL,
+                    idempotency_key = NULL,
+                    archived_at = NULL,
+                    created_at = CURRENT_TIMESTAMP,
+                    last_activity = CURRENT_TIMESTAMP
+                "#,
+            )
+            .bind(&session.id)
+            .bind(&session.tag)
+            .bind(&session.user_id)
+            .bind(&session.machine_id)
+            .bind(&session.machine_name)
+            .bind(session.status.to_string())
+            .bind(&session.metadata.cwd)
+            .bind(serde_json::to_string(&session.metadata.cwd_history)?)
+            .bind(serde_json::to_string(&session.metadata.env)?)
+            .bind(&session.metadata.agent_version)
+            .bind(&session.metadata.home_dir)
+            .bind(&session.metadata.shell)
+            .bind(created_by_ip)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    /// Insert a session tagged with an idempotency key.
+    ///
+    /// Returns `Ok(false)` without touching the table if a session with the
+    /// same key already exists, so retried requests don't create duplicates.
+    pub async fn create_session_with_idempotency_key(
+        &self,
+        session: &Session,
+        idempotency_key: &str,
+        created_by_ip: Option<&str>,
+    ) -> Result<bool> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO sessions (id, tag, user_id, machine_id, machine_name, status, cwd, cwd_history, env, agent_version, home_dir, shell, idempotency_key, created_by_ip)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                "#,
+            )
+            .bind(&session.id)
+            .bind(&session.tag)
+            .bind(&session.user_id)
+            .bind(&session.machine_id)
+            .bind(&session.machine_name)
+            .bind(session.status.to_string())
+            .bind(&session.metadata.cwd)
+            .bind(serde_json::to_string(&session.metadata.cwd_history)?)
+            .bind(serde_json::to_string(&session.metadata.env)?)
+            .bind(&session.metadata.agent_version)
+            .bind(&session.metadata.home_dir)
+            .bind(&session.metadata.shell)
+            .bind(idempotency_key)
+            .bind(created_by_ip)
+            .execute(pool)
+            .await;
+
+            match result {
+                Ok(_) => Ok(true),
+                Err(sqlx::Error::Database(e))
+                    if e.is_unique_violation() && e.message().contains("idempotency_key") =>
+                {
+                    Ok(false)
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    pub async fn get_session_by_idempotency_key(
+        &self,
+        idempotency_key: &str,
+    ) -> Result<Option<Session>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<SessionRow> = sqlx::query_as(
+                r#"
+                SELECT id, tag, user_id, machine_id, machine_name, status,
+                       encrypted_data_key, created_at, last_activity,
+                       cwd, cwd_history, env, claude_version, agent_version, home_dir, shell
+                FROM sessions WHERE idempotency_key = $1
+                "#,
+            )
+            .bind(idempotency_key)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row.map(|r| r.into()))
+        })
+    }
+
+    pub async fn get_session(&self, id: &str) -> Result<Option<Session>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<SessionRow> = sqlx::query_as(
+                r#"
+                SELECT id, tag, user_id, machine_id, machine_name, status,
+                       encrypted_data_key, created_at, last_activity,
+                       cwd, cwd_history, env, claude_version, agent_version, home_dir, shell
+                FROM sessions WHERE id = $1
+                "#,
+            )
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row.map(|r| r.into()))
+        })
+    }
+
+    pub async fn update_session_status(&self, id: &str, status: SessionStatus) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                UPDATE sessions SET status = $1, last_activity = CURRENT_TIMESTAMP
+                WHERE id = $2
+                "#,
+            )
+            .bind(status.to_string())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    /// Move a session to `cwd`, appending it to `cwd_history`; returns the new history
+    pub async fn update_session_cwd(&self, id: &str, cwd: &str) -> Result<Vec<String>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+
+            let row: Option<(String,)> =
+                sqlx::query_as("SELECT cwd_history FROM sessions WHERE id = $1")
+                    .bind(id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            let mut history: Vec<String> = row
+                .and_then(|(json,)| serde_json::from_str(&json).ok())
+                .unwrap_or_default();
+            push_cwd_history(&mut history, cwd);
+
+            sqlx::query(
+                r#"
+                UPDATE sessions SET cwd = $1, cwd_history = $2, last_activity = CURRENT_TIMESTAMP
+                WHERE id = $3
+                "#,
+            )
+            .bind(cwd)
+            .bind(serde_json::to_string(&history)?)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+            Ok(history)
+        })
+    }
+
+    /// Change a session's tag.
+    ///
+    /// Returns `Ok(false)` without touching the table if another of the user's
+    /// live sessions already uses the tag.
+    pub async fn rename_session(&self, id: &str, user_id: &str, tag: &str) -> Result<bool> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+                r#"
+                UPDATE sessions SET tag = $1
+                WHERE id = $2
+                  AND NOT EXISTS (
+                      SELECT 1 FROM sessions
+                      WHERE user_id = $3 AND tag = $1 AND id != $2 AND status != 'terminated'
+                  )
+                "#,
+            )
+            .bind(tag)
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    pub async fn update_session_agent_version(&self, id: &str, agent_version: &str) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                UPDATE sessions SET agent_version = $1, last_activity = CURRENT_TIMESTAMP
+                WHERE id = $2
+                "#,
+            )
+            .bind(agent_version)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    pub async fn update_session_home_dir(&self, id: &str, home_dir: &str) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                UPDATE sessions SET home_dir = $1
+                WHERE id = $2
+                "#,
+            )
+            .bind(home_dir)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    pub async fn update_session_machine(
+        &self,
+        id: &str,
+        machine_id: &str,
+        machine_name: &str,
+    ) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                UPDATE sessions SET machine_id = $1, machine_name = $2, last_activity = CURRENT_TIMESTAMP
+                WHERE id = $3
+                "#,
+            )
+            .bind(machine_id)
+            .bind(machine_name)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    /// Hide a session from listings until restored; it is stopped for good
+    pub async fn archive_session(&self, id: &str) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                UPDATE sessions
+                SET archived_at = CURRENT_TIMESTAMP, status = 'terminated'
+                WHERE id = $1 AND archived_at IS NULL
+                "#,
+            )
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    pub async fn restore_session(&self, id: &str) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query("UPDATE sessions SET archived_at = NULL WHERE id = $1")
+                .bind(id)
+                .execute(pool)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    /// Whether the session is archived; `false` if it doesn't exist
+    pub async fn is_session_archived(&self, id: &str) -> Result<bool> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let archived: Option<bool> =
+                sqlx::query_scalar("SELECT archived_at IS NOT NULL FROM sessions WHERE id = $1")
+                    .bind(id)
+                    .fetch_optional(pool)
+                    .await?;
+            Ok(archived.unwrap_or(false))
+        })
+    }
+
+    pub async fn delete_session(&self, id: &str) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                DELETE FROM sessions WHERE id = $1
+                "#,
+            )
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    /// The client address recorded when the session was created, if any
+    pub async fn get_session_created_by_ip(&self, id: &str) -> Result<Option<String>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let ip: Option<Option<String>> =
+                sqlx::query_scalar("SELECT created_by_ip FROM sessions WHERE id = $1")
+                    .bind(id)
+                    .fetch_optional(pool)
+                    .await?;
+            Ok(ip.flatten())
+        })
+    }
+
+    /// Sessions created from `ip`, newest first
+    pub async fn list_sessions_by_created_ip(&self, ip: &str) -> Result<Vec<Session>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let rows: Vec<SessionRow> = sqlx::query_as(
+                r#"
+                SELECT id, tag, user_id, machine_id, machine_name, status,
+                       encrypted_data_key, created_at, last_activity,
+                       cwd, cwd_history, env, claude_version, agent_version, home_dir, shell
+                FROM sessions WHERE created_by_ip = $1
+                ORDER BY created_at DESC
+                "#,
+            )
+            .bind(ip)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(rows.into_iter().map(|r| r.into()).collect())
+        })
+    }
+
+    pub async fn list_sessions_by_user(&self, user_id: &str) -> Result<Vec<Session>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let rows: Vec<SessionRow> = sqlx::query_as(
+                r#"
+                SELECT id, tag, user_id, machine_id, machine_name, status,
+                       encrypted_data_key, created_at, last_activity,
+                       cwd, cwd_history, env, claude_version, agent_version, home_dir, shell
+                FROM sessions WHERE user_id = $1 AND archived_at IS NULL
+                ORDER BY created_at DESC
+                "#,
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(rows.into_iter().map(|r| r.into()).collect())
+        })
+    }
+
+    /// The user's archived sessions, most recently archived first
+    pub async fn list_archived_sessions_by_user(&self, user_id: &str) -> Result<Vec<Session>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let rows: Vec<SessionRow> = sqlx::query_as(
+                r#"
+                SELECT id, tag, user_id, machine_id, machine_name, status,
+                       encrypted_data_key, created_at, last_activity,
+                       cwd, cwd_history, env, claude_version, agent_version, home_dir, shell
+                FROM sessions WHERE user_id = $1 AND archived_at IS NOT NULL
+                ORDER BY archived_at DESC
+                "#,
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(rows.into_iter().map(|r| r.into()).collect())
+        })
+    }
+
+    /// A page of the user's sessions on one machine, newest first; `limit` of `None` means no limit
+    pub async fn list_sessions_by_user_machine(
+        &self,
+        user_id: &str,
+        machine_id: &str,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Session>> {
+        let _timer = metrics::time_db_query();
+        let rows: Vec<SessionRow> = match &self.pool {
+            StorageBackend::Sqlite(pool) => {
+                sqlx::query_as(SESSIONS_BY_USER_MACHINE_QUERY)
+                    .bind(user_id)
+                    .bind(machine_id)
+                    .bind(limit.map(i64::from).unwrap_or(-1))
+                    .bind(i64::from(offset))
+                    .fetch_all(pool)
+                    .await?
+            }
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => {
+                sqlx::query_as(PG_SESSIONS_BY_USER_MACHINE_QUERY)
+                    .bind(user_id)
+                    .bind(machine_id)
+                    .bind(limit.map(i64::from))
+                    .bind(i64::from(offset))
+                    .fetch_all(pool)
+                    .await?
+            }
+        };
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Up to `limit` of the user's sessions created before `after`, newest first
+    pub async fn list_sessions_by_user_after(
+        &self,
+        user_id: &str,
+        after: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<Session>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let rows: Vec<SessionRow> = sqlx::query_as(SESSIONS_BY_USER_AFTER_QUERY)
+                .bind(user_id)
+                .bind(after)
+                .bind(i64::from(limit))
+                .fetch_all(pool)
+                .await?;
+
+            Ok(rows.into_iter().map(|r| r.into()).collect())
+        })
+    }
+
+    pub async fn list_active_sessions_by_machine(&self, machine_id: &str) -> Result<Vec<Session>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let rows: Vec<SessionRow> = sqlx::query_as(
+                r#"
+                SELECT id, tag, user_id, machine_id, machine_name, status,
+                       encrypted_data_key, created_at, last_activity,
+                       cwd, cwd_history, env, claude_version, agent_version, home_dir, shell
+                FROM sessions
+                WHERE machine_id = $1 AND status IN ('initializing', 'running', 'paused')
+                ORDER BY created_at DESC
+                "#,
+            )
+            .bind(machine_id)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(rows.into_iter().map(|r| r.into()).collect())
+        })
+    }
+
+    // Machine operations
+    pub async fn create_machine(&self, machine: &Machine) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let capabilities_str = machine
+                .capabilities
+                .names()
+                .join(",");
+
+            sqlx::query(
+                r#"
+                INSERT INTO machines (id, user_id, name, public_key, platform, capabilities, hostname)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+            )
+            .bind(&machine.id)
+            .bind(&machine.user_id)
+            .bind(&machine.name)
+            .bind(&machine.public_key)
+            .bind(machine.platform.to_string())
+            .bind(capabilities_str)
+            .bind(&machine.hostname)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    pub async fn get_machine(&self, id: &str) -> Result<Option<Machine>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row: Option<MachineRow> = sqlx::query_as(
+                r#"
+                SELECT id, user_id, name, public_key, platform,
+                       capabilities, last_seen, hostname, last_heartbeat,
+                       daemon_version, arch, cpu_count, memory_mb
+                FROM machines WHERE id = $1
+                "#,
+            )
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row.map(|r| r.into()))
+        })
+    }
+
+    pub async fn list_machines_by_user(&self, user_id: &str) -> Result<Vec<Machine>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let rows: Vec<MachineRow> = sqlx::query_as(
+                r#"
+                SELECT id, user_id, name, public_key, platform,
+                       capabilities, last_seen, hostname, last_heartbeat,
+                       daemon_version, arch, cpu_count, memory_mb
+                FROM machines WHERE user_id = $1
+                ORDER BY last_seen DESC
+                "#,
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(rows.into_iter().map(|r| r.into()).collect())
+        })
+    }
+
+    /// A user's machines whose name or hostname match `query`, best match first
+    pub async fn search_machines_by_user(
+        &self,
+        user_id: &str,
+        query: &str,
+    ) -> Result<Vec<Machine>> {
+        let _timer = metrics::time_db_query();
+        let Some(pattern) = fts_prefix_query(query) else {
+            return self.list_machines_by_user(user_id).await;
+        };
+
+        let rows: Vec<MachineRow> = match &self.pool {
+            StorageBackend::Sqlite(pool) => {
+                sqlx::query_as(
+                    r#"
+                    SELECT machines.id, machines.user_id, machines.name, machines.public_key,
+                           machines.platform, machines.capabilities, machines.last_seen,
+                           machines.hostname, machines.last_heartbeat, machines.daemon_version,
+                           machines.arch, machines.cpu_count, machines.memory_mb
+                    FROM machines
+                    JOIN machines_fts ON machines.id = machines_fts.id
+                    WHERE machines_fts MATCH $1 AND machines.user_id = $2
+                    ORDER BY machines_fts.rank, machines.last_seen DESC
+                    "#,
+                )
+                .bind(pattern)
+                .bind(user_id)
+                .fetch_all(pool)
+                .await?
+            }
+            // Matches the expression of `idx_machines_search`
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(pool) => {
+                sqlx::query_as(
+                    r#"
+                    SELECT id, user_id, name, public_key, platform,
+                           capabilities, last_seen, hostname, last_heartbeat,
+                           daemon_version, arch, cpu_count, memory_mb
+                    FROM machines
+                    WHERE to_tsvector('simple', name || ' ' || COALESCE(hostname, ''))
+                          @@ to_tsquery('simple', $1)
+                      AND user_id = $2
+                    ORDER BY ts_rank(
+                                 to_tsvector('simple', name || ' ' || COALESCE(hostname, '')),
+                                 to_tsquery('simple', $1)
+                             ) DESC,
+                             last_seen DESC
+                    "#,
+                )
+                .bind(tsquery_prefix_query(query))
+                .bind(user_id)
+                .fetch_all(pool)
+                .await?
+            }
+        };
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    pub async fn touch_machine(&self, id: &str) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                UPDATE machines SET last_seen = CURRENT_TIMESTAMP WHERE id = $1
+                "#,
+            )
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    /// Store the host details a daemon reported and record a heartbeat
+    pub async fn update_machine_info(&self, info: &MachineInfo) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let capabilities_str = info
+                .capabilities
+                .names()
+                .join(",");
+
+            sqlx::query(
+                r#"
+                UPDATE machines SET platform = $1, capabilities = $2, hostname = $3,
+                       daemon_version = $4, arch = $5, cpu_count = $6, memory_mb = $7,
+                       last_heartbeat = CURRENT_TIMESTAMP, last_seen = CURRENT_TIMESTAMP
+                WHERE id = $8
+                "#,
+            )
+            .bind(info.platform.to_string())
+            .bind(capabilities_str)
+            .bind(&info.hostname)
+            .bind(&info.daemon_version)
+            .bind(&info.arch)
+            .bind(info.cpu_count.map(i64::from))
+            .bind(info.memory_mb.map(|m| m as i64))
+            .bind(&info.id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    /// The Ed25519 key pinned to a machine by its first signed attach
+    pub async fn get_machine_signing_key(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let key: Option<Option<Vec<u8>>> =
+                sqlx::query_scalar("SELECT signing_key FROM machines WHERE id = $1")
+                    .bind(id)
+                    .fetch_optional(pool)
+                    .await?;
+            Ok(key.flatten())
+        })
+    }
+
+    /// Pin `key` to a machine unless one is already pinned; returns whether it was stored
+    pub async fn set_machine_signing_key(&self, id: &str, key: &[u8]) -> Result<bool> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+                "UPDATE machines SET signing_key = $1 WHERE id = $2 AND signing_key IS NULL",
+            )
+            .bind(key)
+            .bind(id)
+            .execute(pool)
+            .await?;
+            Ok(result.rows_affected() > 0)
+        })
     }
 
-    pub async fn get_session(&self, id: &str) -> Result<Option<Session>> {
-        let row: Option<SessionRow> = sqlx::query_as(
-            r#"
-            SELECT id, tag, user_id, machine_id, machine_name, status,
-                   encrypted_data_key, created_at, last_activity,
-                   cwd, env, claude_version, shell
-            FROM sessions WHERE id = ?1
-            "#,
-        )
-        .bind(id)
-        .fetch_optional(&*self.pool)
-        .await?;
+    pub async fn update_machine_name(&self, id: &str, name: &str) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                UPDATE machines SET name = $1, last_seen = CURRENT_TIMESTAMP WHERE id = $2
+                "#,
+            )
+            .bind(name)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+            // Also update machine_name in all sessions for this machine
+            sqlx::query(
+                r#"
+                UPDATE sessions SET machine_name = $1 WHERE machine_id = $2
+                "#,
+            )
+            .bind(name)
+            .bind(id)
+            .execute(pool)
+            .await?;
 
-        Ok(row.map(|r| r.into()))
+            Ok(())
+        })
     }
 
-    pub async fn update_session_status(&self, id: &str, status: SessionStatus) -> Result<()> {
-        sqlx::query(
-            r#"
-            UPDATE sessions SET status = ?1, last_activity = datetime('now')
-            WHERE id = ?2
-            "#,
-        )
-        .bind(status.to_string())
-        .bind(id)
-        .execute(&*self.pool)
-        .await?;
+    /// Delete a machine and every session on it, in one transaction.
+    ///
+    /// Returns the ids of the deleted sessions.
+    pub async fn delete_machine(&self, id: &str) -> Result<Vec<String>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
 
-        Ok(())
-    }
+            let session_ids: Vec<(String,)> =
+                sqlx::query_as("SELECT id FROM sessions WHERE machine_id = $1")
+                    .bind(id)
+                    .fetch_all(&mut *tx)
+                    .await?;
 
-    pub async fn update_session_cwd(&self, id: &str, cwd: &str) -> Result<()> {
-        sqlx::query(
-            r#"
-            UPDATE sessions SET cwd = ?1, last_activity = datetime('now')
-            WHERE id = ?2
-            "#,
-        )
-        .bind(cwd)
-        .bind(id)
-        .execute(&*self.pool)
-        .await?;
+            sqlx::query("DELETE FROM sessions WHERE machine_id = $1")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
 
-        Ok(())
-    }
+            sqlx::query(
+                r#"
+                DELETE FROM machines WHERE id = $1
+                "#,
+            )
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
 
-    pub async fn update_session_machine(
-        &self,
-        id: &str,
-        machine_id: &str,
-        machine_name: &str,
-    ) -> Result<()> {
-        sqlx::query(
-            r#"
-            UPDATE sessions SET machine_id = ?1, machine_name = ?2, last_activity = datetime('now')
-            WHERE id = ?3
-            "#,
-        )
-        .bind(machine_id)
-        .bind(machine_name)
-        .bind(id)
-        .execute(&*self.pool)
-        .await?;
+            tx.commit().await?;
 
-        Ok(())
+            Ok(session_ids.into_iter().map(|(id,)| id).collect())
+        })
     }
 
-    pub async fn delete_session(&self, id: &str) -> Result<()> {
-        sqlx::query(
-            r#"
-            DELETE FROM sessions WHERE id = ?1
-            "#,
-        )
-        .bind(id)
-        .execute(&*self.pool)
-        .await?;
+    /// Move every session on `old_id` to `new_id` and delete `old_id`, in one transaction.
+    ///
+    /// `new_id` takes the old row's `last_seen` and capabilities when the old
+    /// row was seen more recently. Returns the ids of the moved sessions.
+    pub async fn merge_machines(&self, old_id: &str, new_id: &str) -> Result<Vec<String>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
 
-        Ok(())
-    }
+            let session_ids: Vec<(String,)> =
+                sqlx::query_as("SELECT id FROM sessions WHERE machine_id = $1")
+                    .bind(old_id)
+                    .fetch_all(&mut *tx)
+                    .await?;
 
-    pub async fn list_sessions_by_user(&self, user_id: &str) -> Result<Vec<Session>> {
-        let rows: Vec<SessionRow> = sqlx::query_as(
-            r#"
-            SELECT id, tag, user_id, machine_id, machine_name, status,
-                   encrypted_data_key, created_at, last_activity,
-                   cwd, env, claude_version, shell
-            FROM sessions WHERE user_id = ?1
-            ORDER BY created_at DESC
-            "#,
-        )
-        .bind(user_id)
-        .fetch_all(&*self.pool)
-        .await?;
+            sqlx::query(
+                r#"
+                UPDATE sessions
+                SET machine_id = $2,
+                    machine_name = (SELECT name FROM machines WHERE id = $2)
+                WHERE machine_id = $1
+                "#,
+            )
+            .bind(old_id)
+            .bind(new_id)
+            .execute(&mut *tx)
+            .await?;
 
-        Ok(rows.into_iter().map(|r| r.into()).collect())
-    }
+            sqlx::query(
+                r#"
+                UPDATE machines
+                SET last_seen = (SELECT last_seen FROM machines WHERE id = $1),
+                    capabilities = (SELECT capabilities FROM machines WHERE id = $1)
+                WHERE id = $2
+                  AND last_seen < (SELECT last_seen FROM machines WHERE id = $1)
+                "#,
+            )
+            .bind(old_id)
+            .bind(new_id)
+            .execute(&mut *tx)
+            .await?;
 
-    pub async fn list_active_sessions_by_machine(&self, machine_id: &str) -> Result<Vec<Session>> {
-        let rows: Vec<SessionRow> = sqlx::query_as(
-            r#"
-            SELECT id, tag, user_id, machine_id, machine_name, status,
-                   encrypted_data_key, created_at, last_activity,
-                   cwd, env, claude_version, shell
-            FROM sessions
-            WHERE machine_id = ?1 AND status IN ('initializing', 'running', 'paused')
-            ORDER BY created_at DESC
-            "#,
-        )
-        .bind(machine_id)
-        .fetch_all(&*self.pool)
-        .await?;
+            sqlx::query("DELETE FROM machines WHERE id = $1")
+                .bind(old_id)
+                .execute(&mut *tx)
+                .await?;
 
-        Ok(rows.into_iter().map(|r| r.into()).collect())
-    }
+            tx.commit().await?;
 
-    // Machine operations
-    pub async fn create_machine(&self, machine: &Machine) -> Result<()> {
-        let capabilities_str = machine
-            .capabilities
-            .iter()
-            .map(|c| c.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
+            Ok(session_ids.into_iter().map(|(id,)| id).collect())
+        })
+    }
 
-        sqlx::query(
-            r#"
-            INSERT INTO machines (id, user_id, name, public_key, platform, capabilities, hostname)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-            "#,
-        )
-        .bind(&machine.id)
-        .bind(&machine.user_id)
-        .bind(&machine.name)
-        .bind(&machine.public_key)
-        .bind(machine.platform.to_string())
-        .bind(capabilities_str)
-        .bind(&machine.hostname)
-        .execute(&*self.pool)
-        .await?;
+    // Push token operations
+    /// Register a device token; a token moving to another user is reassigned
+    pub async fn save_push_token(&self, user_id: &str, token: &str, platform: &str) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                INSERT INTO push_tokens (id, user_id, token, platform)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT(token) DO UPDATE SET user_id = excluded.user_id, platform = excluded.platform
+                "#,
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(user_id)
+            .bind(token)
+            .bind(platform)
+            .execute(pool)
+            .await?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
-    pub async fn get_machine(&self, id: &str) -> Result<Option<Machine>> {
-        let row: Option<MachineRow> = sqlx::query_as(
-            r#"
-            SELECT id, user_id, name, public_key, platform,
-                   capabilities, last_seen, hostname
-            FROM machines WHERE id = ?1
-            "#,
-        )
-        .bind(id)
-        .fetch_optional(&*self.pool)
-        .await?;
+    pub async fn list_push_tokens(&self, user_id: &str) -> Result<Vec<String>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let rows: Vec<(String,)> = sqlx::query_as(
+                r#"
+                SELECT token FROM push_tokens WHERE user_id = $1
+                "#,
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
 
-        Ok(row.map(|r| r.into()))
+            Ok(rows.into_iter().map(|(token,)| token).collect())
+        })
     }
 
-    pub async fn list_machines_by_user(&self, user_id: &str) -> Result<Vec<Machine>> {
-        let rows: Vec<MachineRow> = sqlx::query_as(
-            r#"
-            SELECT id, user_id, name, public_key, platform,
-                   capabilities, last_seen, hostname
-            FROM machines WHERE user_id = ?1
-            ORDER BY last_seen DESC
-            "#,
-        )
-        .bind(user_id)
-        .fetch_all(&*self.pool)
-        .await?;
+    pub async fn delete_push_token(&self, token: &str) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                DELETE FROM push_tokens WHERE token = $1
+                "#,
+            )
+            .bind(token)
+            .execute(pool)
+            .await?;
 
-        Ok(rows.into_iter().map(|r| r.into()).collect())
+            Ok(())
+        })
     }
 
-    pub async fn touch_machine(&self, id: &str) -> Result<()> {
-        sqlx::query(
-            r#"
-            UPDATE machines SET last_seen = datetime('now') WHERE id = ?1
-            "#,
-        )
-        .bind(id)
-        .execute(&*self.pool)
-        .await?;
+    // Audit log operations
+    pub async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.audit_pool, |pool| {
+            sqlx::query(
+                r#"
+                INSERT INTO audit_log
+                    (timestamp, user_id, event_type, resource_type, resource_id, ip_address, metadata)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+            )
+            .bind(&entry.timestamp)
+            .bind(&entry.user_id)
+            .bind(&entry.event_type)
+            .bind(&entry.resource_type)
+            .bind(&entry.resource_id)
+            .bind(&entry.ip_address)
+            .bind(&entry.metadata)
+            .execute(pool)
+            .await?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
-    pub async fn update_machine_name(&self, id: &str, name: &str) -> Result<()> {
-        sqlx::query(
-            r#"
-            UPDATE machines SET name = ?1, last_seen = datetime('now') WHERE id = ?2
-            "#,
-        )
-        .bind(name)
-        .bind(id)
-        .execute(&*self.pool)
-        .await?;
+    /// Up to `limit` entries older than the `before` ID, newest first
+    pub async fn list_audit_entries(
+        &self,
+        before: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<AuditRecord>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let rows = sqlx::query_as(
+                r#"
+                SELECT id, timestamp, user_id, event_type, resource_type, resource_id,
+                       ip_address, metadata
+                FROM audit_log
+                WHERE $1 IS NULL OR id < $1
+                ORDER BY id DESC
+                LIMIT $2
+                "#,
+            )
+            .bind(before)
+            .bind(i64::from(limit))
+            .fetch_all(pool)
+            .await?;
 
-        // Also update machine_name in all sessions for this machine
-        sqlx::query(
-            r#"
-            UPDATE sessions SET machine_name = ?1 WHERE machine_id = ?2
-            "#,
-        )
-        .bind(name)
-        .bind(id)
-        .execute(&*self.pool)
-        .await?;
+            Ok(rows)
+        })
+    }
 
-        Ok(())
+    // SSO operations
+    pub async fn get_sso_config(&self) -> Result<Option<SsoConfig>> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            let row = sqlx::query_as(
+                r#"
+                SELECT idp_metadata_url, entity_id, certificate, domain FROM sso_config WHERE id = 1
+                "#,
+            )
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(row)
+        })
     }
 
-    pub async fn delete_machine(&self, id: &str) -> Result<()> {
-        sqlx::query(
-            r#"
-            DELETE FROM machines WHERE id = ?1
-            "#,
-        )
-        .bind(id)
-        .execute(&*self.pool)
-        .await?;
+    pub async fn set_sso_config(&self, config: &SsoConfig) -> Result<()> {
+        let _timer = metrics::time_db_query();
+        with_pool!(&self.pool, |pool| {
+            sqlx::query(
+                r#"
+                INSERT INTO sso_config (id, idp_metadata_url, entity_id, certificate, domain)
+                VALUES (1, $1, $2, $3, $4)
+                ON CONFLICT(id) DO UPDATE SET
+                    idp_metadata_url = excluded.idp_metadata_url,
+                    entity_id = excluded.entity_id,
+                    certificate = excluded.certificate,
+                    domain = excluded.domain,
+                    updated_at = CURRENT_TIMESTAMP
+                "#,
+            )
+            .bind(&config.idp_metadata_url)
+            .bind(&config.entity_id)
+            .bind(&config.certificate)
+            .bind(&config.domain)
+            .execute(pool)
+            .await?;
 
-        Ok(())
+            Ok(())
+        })
     }
 }
 
@@ -477,8 +1919,11 @@ struct SessionRow {
     created_at: chrono::DateTime<chrono::Utc>,
     last_activity: chrono::DateTime<chrono::Utc>,
     cwd: String,
+    cwd_history: String,
     env: String,
     claude_version: Option<String>,
+    agent_version: Option<String>,
+    home_dir: Option<String>,
     shell: String,
 }
 
@@ -496,8 +1941,11 @@ impl From<SessionRow> for Session {
             last_activity: r.last_activity,
             metadata: happy_core::SessionMetadata {
                 cwd: r.cwd,
+                cwd_history: serde_json::from_str(&r.cwd_history).unwrap_or_default(),
                 env: serde_json::from_str(&r.env).unwrap_or_default(),
                 claude_version: r.claude_version,
+                agent_version: r.agent_version,
+                home_dir: r.home_dir,
                 shell: r.shell,
             },
         }
@@ -514,6 +1962,11 @@ struct MachineRow {
     capabilities: String,
     last_seen: chrono::DateTime<chrono::Utc>,
     hostname: Option<String>,
+    last_heartbeat: Option<chrono::DateTime<chrono::Utc>>,
+    daemon_version: Option<String>,
+    arch: Option<String>,
+    cpu_count: Option<i64>,
+    memory_mb: Option<i64>,
 }
 
 impl From<MachineRow> for Machine {
@@ -521,13 +1974,7 @@ impl From<MachineRow> for Machine {
         let capabilities = r
             .capabilities
             .split(',')
-            .filter_map(|s| match s {
-                "terminal" => Some(happy_core::Capability::Terminal),
-                "file_system" => Some(happy_core::Capability::FileSystem),
-                "notifications" => Some(happy_core::Capability::Notifications),
-                "voice" => Some(happy_core::Capability::Voice),
-                _ => None,
-            })
+            .filter_map(|s| s.parse::<happy_core::Capability>().ok())
             .collect();
 
         Machine {
@@ -540,10 +1987,36 @@ impl From<MachineRow> for Machine {
             capabilities,
             ip_address: None,
             hostname: r.hostname,
+            last_heartbeat: r.last_heartbeat,
+            daemon_version: r.daemon_version,
+            arch: r.arch,
+            cpu_count: r.cpu_count.map(|c| c as u32),
+            memory_mb: r.memory_mb.map(|m| m as u64),
         }
     }
 }
 
+/// Turn free text into an FTS5 query matching every word as a prefix.
+///
+/// Each word is quoted so FTS5 operators in user input are matched literally.
+/// Returns `None` when the text has no words.
+fn fts_prefix_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// The Postgres `tsquery` counterpart of [`fts_prefix_query`]
+#[cfg(feature = "postgres")]
+fn tsquery_prefix_query(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| format!("'{}':*", word.replace('\\', "\\\\").replace('\'', "''")))
+        .collect::<Vec<_>>()
+        .join(" & ")
+}
+
 fn parse_session_status(s: &str) -> SessionStatus {
     match s {
         "initializing" => SessionStatus::Initializing,
@@ -562,3 +2035,675 @@ fn parse_platform(s: &str) -> Platform {
         _ => Platform::Linux,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run each shared test body against an in-memory SQLite database and,
+    /// with the `postgres` feature, a fresh Postgres schema
+    macro_rules! backend_tests {
+        ($($name:ident),* $(,)?) => {
+            mod sqlite {
+                $(
+                    #[tokio::test]
+                    async fn $name() {
+                        super::$name(super::sqlite_db().await).await;
+                    }
+                )*
+            }
+
+            #[cfg(feature = "postgres")]
+            mod postgres {
+                $(
+                    #[tokio::test]
+                    async fn $name() {
+                        match super::postgres_db().await {
+                            Some(db) => super::$name(db).await,
+                            None => eprintln!("HAPPY_TEST_POSTGRES_URL is not set, skipping"),
+                        }
+                    }
+                )*
+            }
+        };
+    }
+
+    backend_tests!(
+        test_idempotent_session_insert,
+        test_cwd_history,
+        test_soft_delete_user,
+        test_reset_password_with_token,
+        test_sessions_by_machine,
+        test_sessions_after_cursor,
+        test_merge_machines,
+        test_archive_session,
+        test_search_machines_by_user,
+        test_user_lockout_round_trip,
+        test_user_totp_round_trip,
+        test_sso_config_round_trip,
+        test_audit_log_append_only,
+        test_sessions_by_created_ip,
+        test_machine_signing_key_pins_once,
+        test_maintenance,
+    );
+
+    async fn sqlite_db() -> Database {
+        Database::new(":memory:", DatabaseConfig::default())
+            .await
+            .unwrap()
+    }
+
+    /// A database in a schema of its own on the server at
+    /// `HAPPY_TEST_POSTGRES_URL`, or `None` when that isn't set. The Postgres
+    /// job in CI sets it, so these tests only skip locally.
+    #[cfg(feature = "postgres")]
+    async fn postgres_db() -> Option<Database> {
+        let url = std::env::var("HAPPY_TEST_POSTGRES_URL").ok()?;
+        let schema = format!("happy_test_{}", uuid::Uuid::new_v4().simple());
+        let admin = PgPool::connect(&url).await.unwrap();
+        sqlx::query(&format!("CREATE SCHEMA {}", schema))
+            .execute(&admin)
+            .await
+            .unwrap();
+        admin.close().await;
+
+        let options = PgConnectOptions::from_str(&url)
+            .unwrap()
+            .options([("search_path", schema.as_str())]);
+        let config = DatabaseConfig {
+            max_connections: 4,
+            ..DatabaseConfig::default()
+        };
+        Some(Database::connect_postgres(options, config).await.unwrap())
+    }
+
+    fn sqlite_pool(db: &Database) -> &SqlitePool {
+        match &db.pool {
+            StorageBackend::Sqlite(pool) => pool,
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres(_) => panic!("not a SQLite database"),
+        }
+    }
+
+    /// Run a statement no `Database` method covers
+    async fn execute(db: &Database, sql: &str) -> sqlx::Result<()> {
+        with_pool!(&db.pool, |pool| sqlx::query(sql).execute(pool).await.map(|_| ()))
+    }
+
+    async fn test_maintenance(db: Database) {
+        db.create_user("a@example.com", "hash", None).await.unwrap();
+        db.vacuum().await.unwrap();
+        db.analyze().await.unwrap();
+        let stats = db.stats().await.unwrap();
+        assert!(stats.size_mb > 0.0);
+        assert!((0.0..=1.0).contains(&stats.fragmentation_ratio));
+        assert!(!db.migration_status().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_database_location() {
+        assert_eq!(
+            DatabaseLocation::parse("/data/happy.db"),
+            DatabaseLocation::Sqlite("/data/happy.db".into())
+        );
+        assert_eq!(
+            DatabaseLocation::parse("sqlite:///data/happy.db"),
+            DatabaseLocation::Sqlite("/data/happy.db".into())
+        );
+        assert_eq!(
+            DatabaseLocation::parse("sqlite::memory:"),
+            DatabaseLocation::Sqlite(":memory:".into())
+        );
+
+        let url = "postgresql://happy:secret@db:5432/happy";
+        let location = DatabaseLocation::parse(url);
+        assert_eq!(location, DatabaseLocation::Postgres(url.into()));
+        assert_eq!(location.to_string(), "postgresql://happy:***@db:5432/happy");
+    }
+
+    async fn test_idempotent_session_insert(db: Database) {
+        let first = Session::new(
+            "s1".to_string(),
+            "tag".to_string(),
+            "user".to_string(),
+            "machine".to_string(),
+            "host".to_string(),
+        );
+        assert!(db
+            .create_session_with_idempotency_key(&first, "req-1", None)
+            .await
+            .unwrap());
+
+        // A retry with the same key must not create a second row
+        let mut retry = first.clone();
+        retry.id = "s2".to_string();
+        assert!(!db
+            .create_session_with_idempotency_key(&retry, "req-1", None)
+            .await
+            .unwrap());
+        assert!(db.get_session("s2").await.unwrap().is_none());
+
+        let existing = db.get_session_by_idempotency_key("req-1").await.unwrap();
+        assert_eq!(existing.map(|s| s.id), Some("s1".to_string()));
+
+        // Keyless sessions are unaffected by the unique index
+        db.create_session(&retry, None).await.unwrap();
+        assert!(db.get_session("s2").await.unwrap().is_some());
+    }
+
+    async fn test_cwd_history(db: Database) {
+        let mut session = Session::new(
+            "s1".to_string(),
+            "tag".to_string(),
+            "user".to_string(),
+            "machine".to_string(),
+            "host".to_string(),
+        );
+        session.metadata.cwd_history = vec!["/".to_string()];
+        db.create_session(&session, None).await.unwrap();
+
+        db.update_session_cwd("s1", "/a").await.unwrap();
+        // Staying put adds nothing
+        let history = db.update_session_cwd("s1", "/a").await.unwrap();
+        assert_eq!(history, vec!["/", "/a"]);
+
+        for i in 0..CWD_HISTORY_LIMIT {
+            db.update_session_cwd("s1", &format!("/d{}", i)).await.unwrap();
+        }
+        let session = db.get_session("s1").await.unwrap().unwrap();
+        let history = session.metadata.cwd_history;
+        assert_eq!(history.len(), CWD_HISTORY_LIMIT);
+        assert_eq!(history.first().map(String::as_str), Some("/d0"));
+        assert_eq!(history.last(), Some(&session.metadata.cwd));
+    }
+
+    async fn test_soft_delete_user(db: Database) {
+        let user_id = db.create_user("a@example.com", "hash", None).await.unwrap();
+        let session = Session::new(
+            "s1".to_string(),
+            "tag".to_string(),
+            user_id.clone(),
+            "machine".to_string(),
+            "host".to_string(),
+        );
+        db.create_session(&session, None).await.unwrap();
+
+        let (sessions, _) = db.soft_delete_user(&user_id).await.unwrap();
+        assert_eq!(sessions, vec!["s1".to_string()]);
+        assert!(db.get_session("s1").await.unwrap().is_none());
+        assert!(db
+            .get_user_by_email("a@example.com")
+            .await
+            .unwrap()
+            .is_none());
+
+        // The row stays until its purge date
+        let past = chrono::Utc::now() - chrono::Duration::days(1);
+        assert_eq!(db.purge_deleted_users(past).await.unwrap(), 0);
+        assert!(db.get_user_by_id(&user_id).await.unwrap().is_some());
+
+        // but the email is free to sign up with again
+        let new_id = db.create_user("a@example.com", "hash", None).await.unwrap();
+        assert_ne!(new_id, user_id);
+        let (id, _) = db.get_user_by_email("a@example.com").await.unwrap().unwrap();
+        assert_eq!(id, new_id);
+
+        let future = chrono::Utc::now() + chrono::Duration::days(1);
+        assert_eq!(db.purge_deleted_users(future).await.unwrap(), 1);
+        assert!(db.get_user_by_id(&user_id).await.unwrap().is_none());
+        assert!(db.get_user_by_id(&new_id).await.unwrap().is_some());
+    }
+
+    async fn test_reset_password_with_token(db: Database) {
+        let user_id = db.create_user("a@example.com", "old", None).await.unwrap();
+        assert_eq!(db.get_user_token_version(&user_id).await.unwrap(), Some(0));
+
+        let now = chrono::Utc::now();
+        db.create_password_reset_token("expired", &user_id, now - chrono::Duration::minutes(1))
+            .await
+            .unwrap();
+        db.create_password_reset_token("valid", &user_id, now + chrono::Duration::hours(1))
+            .await
+            .unwrap();
+
+        assert_eq!(db.reset_password_with_token("expired", "new").await.unwrap(), None);
+        assert_eq!(db.reset_password_with_token("unknown", "new").await.unwrap(), None);
+        assert_eq!(
+            db.reset_password_with_token("valid", "new").await.unwrap(),
+            Some(user_id.clone())
+        );
+        assert_eq!(
+            db.get_user_password_hash(&user_id).await.unwrap().as_deref(),
+            Some("new")
+        );
+        assert_eq!(db.get_user_token_version(&user_id).await.unwrap(), Some(1));
+
+        // Tokens are single-use
+        assert_eq!(db.reset_password_with_token("valid", "newer").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_by_machine_use_index() {
+        let db = sqlite_db().await;
+
+        let plan: Vec<(i64, i64, i64, String)> = sqlx::query_as(&format!(
+            "EXPLAIN QUERY PLAN {}",
+            SESSIONS_BY_USER_MACHINE_QUERY
+        ))
+        .bind("user")
+        .bind("m1")
+        .bind(-1i64)
+        .bind(0i64)
+        .fetch_all(sqlite_pool(&db))
+        .await
+        .unwrap();
+        assert!(
+            plan.iter()
+                .any(|(_, _, _, detail)| detail.contains("idx_sessions_user_machine")),
+            "query plan does not use the index: {:?}",
+            plan
+        );
+    }
+
+    async fn test_sessions_by_machine(db: Database) {
+        for (id, machine) in [("s1", "m1"), ("s2", "m2"), ("s3", "m1")] {
+            let session = Session::new(
+                id.to_string(),
+                id.to_string(),
+                "user".to_string(),
+                machine.to_string(),
+                "host".to_string(),
+            );
+            db.create_session(&session, None).await.unwrap();
+        }
+        let m1 = db
+            .list_sessions_by_user_machine("user", "m1", None, 0)
+            .await
+            .unwrap();
+        assert_eq!(m1.len(), 2);
+        assert!(m1.iter().all(|s| s.machine_id == "m1"));
+        let page = db
+            .list_sessions_by_user_machine("user", "m1", Some(1), 1)
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_after_cursor_use_index() {
+        let db = sqlite_db().await;
+
+        let plan: Vec<(i64, i64, i64, String)> = sqlx::query_as(&format!(
+            "EXPLAIN QUERY PLAN {}",
+            SESSIONS_BY_USER_AFTER_QUERY
+        ))
+        .bind("user")
+        .bind("s3")
+        .bind(2i64)
+        .fetch_all(sqlite_pool(&db))
+        .await
+        .unwrap();
+        assert!(
+            plan.iter()
+                .any(|(_, _, _, detail)| detail.contains("idx_sessions_user_created")),
+            "query plan does not use the index: {:?}",
+            plan
+        );
+    }
+
+    async fn test_sessions_after_cursor(db: Database) {
+        // Same created_at for all, so the id breaks ties
+        for id in ["s1", "s2", "s3", "s4", "s5"] {
+            let session = Session::new(
+                id.to_string(),
+                id.to_string(),
+                "user".to_string(),
+                "m1".to_string(),
+                "host".to_string(),
+            );
+            db.create_session(&session, None).await.unwrap();
+        }
+        execute(&db, "UPDATE sessions SET created_at = '2030-01-01 00:00:00'")
+            .await
+            .unwrap();
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db
+                .list_sessions_by_user_after("user", cursor.as_deref(), 2)
+                .await
+                .unwrap();
+            if page.is_empty() {
+                break;
+            }
+            cursor = page.last().map(|s| s.id.clone());
+            seen.extend(page.into_iter().map(|s| s.id));
+        }
+        assert_eq!(seen, vec!["s5", "s4", "s3", "s2", "s1"]);
+    }
+
+    async fn test_merge_machines(db: Database) {
+        for (id, name) in [("old", "laptop (old)"), ("new", "laptop")] {
+            let machine = Machine::new(
+                id.to_string(),
+                "user".to_string(),
+                name.to_string(),
+                vec![],
+                Platform::Linux,
+            );
+            db.create_machine(&machine).await.unwrap();
+        }
+        // The old row was seen last, so its timestamp wins
+        execute(&db, "UPDATE machines SET last_seen = '2030-01-01 00:00:00' WHERE id = 'old'")
+            .await
+            .unwrap();
+        for (id, machine) in [("s1", "old"), ("s2", "old"), ("s3", "new")] {
+            let session = Session::new(
+                id.to_string(),
+                id.to_string(),
+                "user".to_string(),
+                machine.to_string(),
+                "host".to_string(),
+            );
+            db.create_session(&session, None).await.unwrap();
+        }
+
+        let mut moved = db.merge_machines("old", "new").await.unwrap();
+        moved.sort();
+        assert_eq!(moved, vec!["s1".to_string(), "s2".to_string()]);
+
+        assert!(db.get_machine("old").await.unwrap().is_none());
+        let merged = db.get_machine("new").await.unwrap().unwrap();
+        assert_eq!(
+            merged.last_seen.format("%Y-%m-%d").to_string(),
+            "2030-01-01"
+        );
+        let sessions = db.list_sessions_by_user("user").await.unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert!(sessions.iter().all(|s| s.machine_id == "new"));
+        assert!(sessions
+            .iter()
+            .filter(|s| moved.contains(&s.id))
+            .all(|s| s.machine_name == "laptop"));
+    }
+
+    async fn test_archive_session(db: Database) {
+        for id in ["s1", "s2"] {
+            let mut session = Session::new(
+                id.to_string(),
+                id.to_string(),
+                "user".to_string(),
+                "m1".to_string(),
+                "host".to_string(),
+            );
+            session.status = SessionStatus::Running;
+            db.create_session(&session, None).await.unwrap();
+        }
+        let ids = |sessions: Vec<Session>| sessions.into_iter().map(|s| s.id).collect::<Vec<_>>();
+
+        db.archive_session("s1").await.unwrap();
+        assert!(db.is_session_archived("s1").await.unwrap());
+        assert!(!db.is_session_archived("s2").await.unwrap());
+        assert_eq!(ids(db.list_sessions_by_user("user").await.unwrap()), vec!["s2"]);
+        let page = db.list_sessions_by_user_after("user", None, 10).await.unwrap();
+        assert_eq!(ids(page), vec!["s2"]);
+        assert_eq!(
+            ids(db.list_sessions_by_user_machine("user", "m1", None, 0).await.unwrap()),
+            vec!["s2"]
+        );
+        assert_eq!(ids(db.list_archived_sessions_by_user("user").await.unwrap()), vec!["s1"]);
+        // Archiving stops the session, and the record stays reachable by id
+        let archived = db.get_session("s1").await.unwrap().unwrap();
+        assert_eq!(archived.status, SessionStatus::Terminated);
+
+        db.restore_session("s1").await.unwrap();
+        assert!(!db.is_session_archived("s1").await.unwrap());
+        assert_eq!(db.list_sessions_by_user("user").await.unwrap().len(), 2);
+        assert!(db.list_archived_sessions_by_user("user").await.unwrap().is_empty());
+
+        db.archive_session("s1").await.unwrap();
+        db.delete_session("s1").await.unwrap();
+        assert!(db.get_session("s1").await.unwrap().is_none());
+        assert!(db.list_archived_sessions_by_user("user").await.unwrap().is_empty());
+    }
+
+    async fn test_search_machines_by_user(db: Database) {
+        for (id, user, name) in [
+            ("m1", "user", "Work Laptop"),
+            ("m2", "user", "build-server"),
+            ("m3", "other", "laptop"),
+        ] {
+            let machine = Machine::new(
+                id.to_string(),
+                user.to_string(),
+                name.to_string(),
+                vec![],
+                Platform::Linux,
+            );
+            db.create_machine(&machine).await.unwrap();
+        }
+
+        let ids = |machines: Vec<Machine>| machines.into_iter().map(|m| m.id).collect::<Vec<_>>();
+        assert_eq!(ids(db.search_machines_by_user("user", "lap").await.unwrap()), vec!["m1"]);
+        assert_eq!(ids(db.search_machines_by_user("user", "build").await.unwrap()), vec!["m2"]);
+        // Search syntax in the query is matched literally rather than parsed
+        assert!(db.search_machines_by_user("user", "NOT \"(").await.unwrap().is_empty());
+        assert_eq!(db.search_machines_by_user("user", "  ").await.unwrap().len(), 2);
+
+        // Renames are re-indexed
+        db.update_machine_name("m2", "desktop").await.unwrap();
+        assert!(db.search_machines_by_user("user", "build").await.unwrap().is_empty());
+        assert_eq!(ids(db.search_machines_by_user("user", "desk").await.unwrap()), vec!["m2"]);
+
+        // VACUUM may renumber rowids; the index follows machine ids
+        db.vacuum().await.unwrap();
+        assert_eq!(ids(db.search_machines_by_user("user", "lap").await.unwrap()), vec!["m1"]);
+
+        let session = Session::new(
+            "s1".to_string(),
+            "s1".to_string(),
+            "user".to_string(),
+            "m1".to_string(),
+            "host".to_string(),
+        );
+        db.create_session(&session, None).await.unwrap();
+        assert_eq!(db.delete_machine("m1").await.unwrap(), vec!["s1".to_string()]);
+        assert!(db.search_machines_by_user("user", "laptop").await.unwrap().is_empty());
+        assert!(db.get_session("s1").await.unwrap().is_none());
+    }
+
+    async fn test_user_lockout_round_trip(db: Database) {
+        use chrono::Timelike;
+
+        db.create_user("a@example.com", "hash", None).await.unwrap();
+
+        assert_eq!(db.get_user_locked_until("a@example.com").await.unwrap(), None);
+
+        // Stored with whole-second precision
+        let until = chrono::Utc::now().with_nanosecond(0).unwrap() + chrono::Duration::minutes(15);
+        assert!(db
+            .set_user_locked_until("a@example.com", Some(until))
+            .await
+            .unwrap());
+        assert_eq!(
+            db.get_user_locked_until("a@example.com").await.unwrap(),
+            Some(until)
+        );
+
+        assert!(db.set_user_locked_until("a@example.com", None).await.unwrap());
+        assert_eq!(db.get_user_locked_until("a@example.com").await.unwrap(), None);
+        assert!(!db
+            .set_user_locked_until("nobody@example.com", None)
+            .await
+            .unwrap());
+    }
+
+    async fn test_user_totp_round_trip(db: Database) {
+        let user_id = db.create_user("a@example.com", "hash", None).await.unwrap();
+
+        assert_eq!(db.get_user_totp(&user_id).await.unwrap(), None);
+        assert!(!db.enable_user_totp(&user_id).await.unwrap());
+
+        assert!(db.set_user_totp_secret(&user_id, "sealed-1").await.unwrap());
+        assert_eq!(
+            db.get_user_totp(&user_id).await.unwrap(),
+            Some(("sealed-1".to_string(), false))
+        );
+        assert!(db.enable_user_totp(&user_id).await.unwrap());
+        assert_eq!(
+            db.get_user_totp(&user_id).await.unwrap(),
+            Some(("sealed-1".to_string(), true))
+        );
+
+        // A new secret has to be verified again
+        assert!(db.set_user_totp_secret(&user_id, "sealed-2").await.unwrap());
+        assert_eq!(
+            db.get_user_totp(&user_id).await.unwrap(),
+            Some(("sealed-2".to_string(), false))
+        );
+        assert!(!db.set_user_totp_secret("nobody", "sealed").await.unwrap());
+
+        // Each time step is accepted once, and never after a later one
+        assert!(db.use_user_totp_step(&user_id, 100).await.unwrap());
+        assert!(!db.use_user_totp_step(&user_id, 100).await.unwrap());
+        assert!(!db.use_user_totp_step(&user_id, 99).await.unwrap());
+        assert!(db.use_user_totp_step(&user_id, 101).await.unwrap());
+        assert!(!db.use_user_totp_step("nobody", 102).await.unwrap());
+    }
+
+    async fn test_sso_config_round_trip(db: Database) {
+        assert_eq!(db.get_sso_config().await.unwrap(), None);
+
+        let mut config = SsoConfig {
+            idp_metadata_url: "https://idp.example.com/metadata".into(),
+            entity_id: "https://happy.example.com".into(),
+            certificate: "-----BEGIN CERTIFICATE-----".into(),
+            domain: "example.com".into(),
+        };
+        db.set_sso_config(&config).await.unwrap();
+        config.domain = "corp.example.com".into();
+        db.set_sso_config(&config).await.unwrap();
+        assert_eq!(db.get_sso_config().await.unwrap(), Some(config));
+    }
+
+    async fn test_audit_log_append_only(db: Database) {
+        for i in 0..3 {
+            let entry = AuditEntry {
+                timestamp: format!("2026-01-01T00:00:0{}Z", i),
+                user_id: Some("u1".into()),
+                event_type: "login_success".into(),
+                resource_type: Some("user".into()),
+                resource_id: Some("u1".into()),
+                ip_address: Some("203.0.113.7".into()),
+                metadata: None,
+            };
+            db.insert_audit_entry(&entry).await.unwrap();
+        }
+
+        // Newest first, paged by ID
+        let page = db.list_audit_entries(None, 2).await.unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].entry.timestamp, "2026-01-01T00:00:02Z");
+        let rest = db.list_audit_entries(Some(page[1].id), 2).await.unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].entry.timestamp, "2026-01-01T00:00:00Z");
+
+        assert!(execute(&db, "UPDATE audit_log SET user_id = 'u2'").await.is_err());
+        assert!(execute(&db, "DELETE FROM audit_log").await.is_err());
+    }
+
+    async fn test_sessions_by_created_ip(db: Database) {
+        for (id, ip) in [("s1", Some("203.0.113.7")), ("s2", Some("2001:db8::1")), ("s3", None)] {
+            let session = Session::new(
+                id.to_string(),
+                "tag".to_string(),
+                "user".to_string(),
+                "machine".to_string(),
+                "host".to_string(),
+            );
+            db.create_session(&session, ip).await.unwrap();
+        }
+
+        assert_eq!(
+            db.get_session_created_by_ip("s1").await.unwrap().as_deref(),
+            Some("203.0.113.7")
+        );
+        assert_eq!(db.get_session_created_by_ip("s3").await.unwrap(), None);
+        assert_eq!(db.get_session_created_by_ip("missing").await.unwrap(), None);
+
+        let sessions = db.list_sessions_by_created_ip("2001:db8::1").await.unwrap();
+        assert_eq!(sessions.into_iter().map(|s| s.id).collect::<Vec<_>>(), vec!["s2"]);
+    }
+
+    async fn test_machine_signing_key_pins_once(db: Database) {
+        let machine = Machine::new(
+            "m1".to_string(),
+            "user".to_string(),
+            "laptop".to_string(),
+            vec![],
+            Platform::Linux,
+        );
+        db.create_machine(&machine).await.unwrap();
+
+        assert_eq!(db.get_machine_signing_key("m1").await.unwrap(), None);
+        assert!(db.set_machine_signing_key("m1", &[1; 32]).await.unwrap());
+        // A later key never replaces the pinned one
+        assert!(!db.set_machine_signing_key("m1", &[2; 32]).await.unwrap());
+        assert_eq!(db.get_machine_signing_key("m1").await.unwrap(), Some(vec![1; 32]));
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_reclaims_free_pages() {
+        let dir = std::env::temp_dir().join(format!("happy-db-{}", uuid::Uuid::new_v4()));
+        let db = Database::new(dir.join("test.db").to_str().unwrap(), DatabaseConfig::default())
+            .await
+            .unwrap();
+        for i in 0..200 {
+            db.create_user(&format!("user{}@example.com", i), &"x".repeat(2000), None)
+                .await
+                .unwrap();
+        }
+        execute(&db, "DELETE FROM users").await.unwrap();
+
+        assert!(db.stats().await.unwrap().fragmentation_ratio > 0.0);
+        db.vacuum().await.unwrap();
+        db.analyze().await.unwrap();
+        let stats = db.stats().await.unwrap();
+        assert_eq!(stats.fragmentation_ratio, 0.0);
+        assert!(stats.size_mb > 0.0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_config_pragmas() {
+        let dir = std::env::temp_dir().join(format!("happy-db-{}", uuid::Uuid::new_v4()));
+        let config = DatabaseConfig {
+            max_connections: 3,
+            wal: true,
+            busy_timeout: Duration::from_millis(1500),
+            cache_size_kib: 1024,
+        };
+        let db = Database::new(dir.join("test.db").to_str().unwrap(), config)
+            .await
+            .unwrap();
+
+        let (journal_mode,): (String,) = sqlx::query_as("PRAGMA journal_mode")
+            .fetch_one(sqlite_pool(&db))
+            .await
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        let expected = [("synchronous", 1), ("busy_timeout", 1500), ("cache_size", -1024)];
+        for (pragma, expected) in expected {
+            let (value,): (i64,) = sqlx::query_as(&format!("PRAGMA {}", pragma))
+                .fetch_one(sqlite_pool(&db))
+                .await
+                .unwrap();
+            assert_eq!(value, expected, "PRAGMA {}", pragma);
+        }
+        assert_eq!(sqlite_pool(&db).options().get_max_connections(), 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
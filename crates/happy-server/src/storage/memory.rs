@@ -1,23 +1,95 @@
 //! In-memory cache using DashMap (replaces Redis for simplicity)
+//!
+//! Entries may carry a TTL: expired entries are never returned, and a
+//! background task sweeps them out every minute. The cache is also capped at
+//! `max_entries`; inserting past the cap evicts the oldest-inserted entries.
+//!
+//! Pinned entries are never evicted: permanent ones, which are the only copy of
+//! their state (e.g. a machine being online), and security state such as login
+//! lockouts, which anyone able to fill the cache could otherwise flush. They
+//! still count towards the cap, so only TTL'd, rebuildable entries make room.
 
+use dashmap::mapref::entry::Entry as MapEntry;
 use dashmap::DashMap;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 
-/// Simple in-memory cache with TTL support
-pub struct MemoryCache {
-    data: Arc<DashMap<String, CacheEntry>>,
+/// Entry cap unless overridden by `CACHE_MAX_ENTRIES`
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// How often expired entries are swept out
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A cached value and when it stops being valid
+struct Entry<T> {
+    value: T,
+    inserted_at: Instant,
+    /// `None` keeps the entry until it's deleted
+    ttl: Option<Duration>,
+    /// Exempt from eviction when the cache is over its cap
+    pinned: bool,
+}
+
+impl<T> Entry<T> {
+    fn new(value: T, ttl: Option<Duration>, pinned: bool) -> Self {
+        Self {
+            value,
+            inserted_at: Instant::now(),
+            ttl,
+            pinned,
+        }
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.ttl
+            .is_some_and(|ttl| now.saturating_duration_since(self.inserted_at) > ttl)
+    }
+
+    /// Time left before the entry expires
+    fn remaining_ttl(&self, now: Instant) -> Option<Duration> {
+        self.ttl
+            .map(|ttl| ttl.saturating_sub(now.saturating_duration_since(self.inserted_at)))
+    }
 }
 
-struct CacheEntry {
-    value: Vec<u8>,
-    expires_at: Option<Instant>,
+/// Counters since the cache was created, plus its current size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found a live entry
+    pub hits: u64,
+    /// Lookups for missing or expired keys
+    pub misses: u64,
+    /// Entries dropped to stay under the cap
+    pub evictions: u64,
+    pub entries: usize,
+}
+
+/// Simple in-memory cache with TTL support and a size cap
+pub struct MemoryCache {
+    data: Arc<DashMap<String, Entry<Vec<u8>>>>,
+    max_entries: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    /// Set while one caller trims the cache so others don't repeat the scan
+    evicting: AtomicBool,
 }
 
 impl MemoryCache {
     pub fn new() -> Self {
+        Self::with_max_entries(DEFAULT_MAX_ENTRIES)
+    }
+
+    /// A cache holding at most `max_entries` keys
+    pub fn with_max_entries(max_entries: usize) -> Self {
         let cache = Self {
             data: Arc::new(DashMap::new()),
+            max_entries: max_entries.max(1),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            evicting: AtomicBool::new(false),
         };
 
         // Start cleanup task
@@ -28,38 +100,40 @@ impl MemoryCache {
 
     /// Get a value from cache
     pub fn get(&self, key: &str) -> Option<Vec<u8>> {
-        self.data.get(key).and_then(|entry| {
-            if let Some(expires) = entry.expires_at {
-                if Instant::now() > expires {
-                    drop(entry);
-                    self.data.remove(key);
-                    return None;
-                }
+        self.get_with_ttl(key).map(|(value, _)| value)
+    }
+
+    /// Get a value along with the time it has left, `None` for permanent entries
+    pub fn get_with_ttl(&self, key: &str) -> Option<(Vec<u8>, Option<Duration>)> {
+        let now = Instant::now();
+        let found = match self.data.get(key) {
+            Some(entry) if !entry.is_expired(now) => {
+                Some((entry.value.clone(), entry.remaining_ttl(now)))
             }
-            Some(entry.value.clone())
-        })
-    }
-
-    /// Set a value in cache (no TTL)
-    pub fn set(&self, key: String, value: Vec<u8>) {
-        self.data.insert(
-            key,
-            CacheEntry {
-                value,
-                expires_at: None,
-            },
-        );
+            Some(entry) => {
+                drop(entry);
+                self.data.remove_if(key, |_, entry| entry.is_expired(now));
+                None
+            }
+            None => None,
+        };
+        self.record_lookup(found.is_some());
+        found
     }
 
-    /// Set a value with TTL
-    pub fn set_with_ttl(&self, key: String, value: Vec<u8>, ttl: Duration) {
-        self.data.insert(
-            key,
-            CacheEntry {
-                value,
-                expires_at: Some(Instant::now() + ttl),
-            },
-        );
+    /// Set a value that only goes away when deleted; it is never evicted
+    pub fn insert_permanent(&self, key: String, value: Vec<u8>) {
+        self.insert(key, Entry::new(value, None, true));
+    }
+
+    /// Set a value that expires after `ttl`, or earlier if evicted
+    pub fn insert_with_ttl(&self, key: String, value: Vec<u8>, ttl: Duration) {
+        self.insert(key, Entry::new(value, Some(ttl), false));
+    }
+
+    /// Set a value that expires after `ttl` and is never evicted before then
+    pub fn insert_pinned_with_ttl(&self, key: String, value: Vec<u8>, ttl: Duration) {
+        self.insert(key, Entry::new(value, Some(ttl), true));
     }
 
     /// Delete a key from cache
@@ -72,33 +146,123 @@ impl MemoryCache {
         self.get(key).is_some()
     }
 
+    /// Replace a key's value with one computed from the current value, atomically
+    ///
+    /// `f` sees `None` if the key is missing or expired and returns the new
+    /// value, which expires after `ttl`, along with a result for the caller.
+    pub fn update_with_ttl<R>(
+        &self,
+        key: &str,
+        ttl: Duration,
+        f: impl FnOnce(Option<&[u8]>) -> (Vec<u8>, R),
+    ) -> R {
+        let now = Instant::now();
+        let (result, added) = match self.data.entry(key.to_string()) {
+            MapEntry::Occupied(mut entry) => {
+                let current = entry.get();
+                let live = !current.is_expired(now);
+                let (value, result) = f(live.then_some(current.value.as_slice()));
+                entry.insert(Entry::new(value, Some(ttl), false));
+                (result, false)
+            }
+            MapEntry::Vacant(entry) => {
+                let (value, result) = f(None);
+                entry.insert(Entry::new(value, Some(ttl), false));
+                (result, true)
+            }
+        };
+        if added {
+            self.enforce_cap();
+        }
+        result
+    }
+
     /// Get and delete (atomic operation for session tokens)
     pub fn take(&self, key: &str) -> Option<Vec<u8>> {
-        self.data.remove(key).map(|(_, entry)| entry.value)
+        let now = Instant::now();
+        let found = self
+            .data
+            .remove(key)
+            .filter(|(_, entry)| !entry.is_expired(now))
+            .map(|(_, entry)| entry.value);
+        self.record_lookup(found.is_some());
+        found
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: self.data.len(),
+        }
+    }
+
+    fn insert(&self, key: String, entry: Entry<Vec<u8>>) {
+        if self.data.insert(key, entry).is_none() {
+            self.enforce_cap();
+        }
+    }
+
+    fn record_lookup(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Once over `max_entries`, drop expired entries and then the oldest-inserted
+    /// unpinned ones until a tenth of the cap is free, so the scan isn't repeated
+    /// on every insert
+    fn enforce_cap(&self) {
+        if self.data.len() <= self.max_entries || self.evicting.swap(true, Ordering::Acquire) {
+            return;
+        }
+
+        let now = Instant::now();
+        self.data.retain(|_, entry| !entry.is_expired(now));
+
+        let target = self.max_entries - self.max_entries / 10;
+        let excess = self.data.len().saturating_sub(target);
+        if excess > 0 {
+            let mut by_age: Vec<(Instant, String)> = self
+                .data
+                .iter()
+                .filter(|entry| !entry.pinned)
+                .map(|entry| (entry.inserted_at, entry.key().clone()))
+                .collect();
+            let excess = excess.min(by_age.len());
+            if excess > 0 {
+                by_age.select_nth_unstable_by_key(excess - 1, |(inserted_at, _)| *inserted_at);
+            }
+            for (inserted_at, key) in &by_age[..excess] {
+                // Skip keys rewritten since the scan; they're no longer the oldest
+                if self
+                    .data
+                    .remove_if(key, |_, entry| {
+                        entry.inserted_at == *inserted_at && !entry.pinned
+                    })
+                    .is_some()
+                {
+                    self.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        self.evicting.store(false, Ordering::Release);
     }
 
     fn start_cleanup_task(&self) {
-        let data = self.data.clone();
+        // Weak so the task ends along with the cache
+        let data: Weak<DashMap<String, Entry<Vec<u8>>>> = Arc::downgrade(&self.data);
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
             loop {
                 interval.tick().await;
 
+                let Some(data) = data.upgrade() else {
+                    break;
+                };
                 let now = Instant::now();
-                let keys_to_remove: Vec<String> = data
-                    .iter()
-                    .filter(|entry| {
-                        entry
-                            .expires_at
-                            .map(|expires| now > expires)
-                            .unwrap_or(false)
-                    })
-                    .map(|entry| entry.key().clone())
-                    .collect();
-
-                for key in keys_to_remove {
-                    data.remove(&key);
-                }
+                data.retain(|_, entry| !entry.is_expired(now));
             }
         });
     }
@@ -119,7 +283,7 @@ mod tests {
         let cache = MemoryCache::new();
 
         // Test set and get
-        cache.set("key1".to_string(), vec![1, 2, 3]);
+        cache.insert_permanent("key1".to_string(), vec![1, 2, 3]);
         assert_eq!(cache.get("key1"), Some(vec![1, 2, 3]));
 
         // Test non-existent key
@@ -135,21 +299,114 @@ mod tests {
         let cache = MemoryCache::new();
 
         // Set with very short TTL
-        cache.set_with_ttl("key1".to_string(), vec![1, 2, 3], Duration::from_millis(10));
-        assert_eq!(cache.get("key1"), Some(vec![1, 2, 3]));
+        cache.insert_with_ttl("key1".to_string(), vec![1, 2, 3], Duration::from_millis(10));
+        let (value, ttl) = cache.get_with_ttl("key1").unwrap();
+        assert_eq!(value, vec![1, 2, 3]);
+        assert!(ttl.unwrap() <= Duration::from_millis(10));
+
+        cache.insert_with_ttl("key2".to_string(), vec![4], Duration::from_millis(10));
+        cache.insert_permanent("key3".to_string(), vec![5]);
+        assert_eq!(cache.get_with_ttl("key3"), Some((vec![5], None)));
 
         // Wait for expiration
         tokio::time::sleep(Duration::from_millis(20)).await;
         assert_eq!(cache.get("key1"), None);
+        assert_eq!(cache.take("key2"), None);
+        assert!(!cache.exists("key1"));
+        assert_eq!(cache.get("key3"), Some(vec![5]));
     }
 
     #[tokio::test]
     async fn test_take() {
         let cache = MemoryCache::new();
 
-        cache.set("key1".to_string(), vec![1, 2, 3]);
+        cache.insert_permanent("key1".to_string(), vec![1, 2, 3]);
         let value = cache.take("key1");
         assert_eq!(value, Some(vec![1, 2, 3]));
         assert_eq!(cache.get("key1"), None);
     }
+
+    #[tokio::test]
+    async fn test_eviction() {
+        const HOUR: Duration = Duration::from_secs(3600);
+        let cache = MemoryCache::with_max_entries(10);
+        for i in 0..10 {
+            cache.insert_with_ttl(format!("key{}", i), vec![i], HOUR);
+            // Distinct insertion times so the order is unambiguous
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(cache.stats().evictions, 0);
+
+        // Rewriting a key makes it the newest rather than adding one
+        cache.insert_with_ttl("key0".to_string(), vec![0], HOUR);
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(cache.stats().entries, 10);
+
+        // Going over the cap trims back to 9, oldest first
+        cache.insert_with_ttl("key10".to_string(), vec![10], HOUR);
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 2);
+        assert_eq!(stats.entries, 9);
+        assert!(!cache.exists("key1"));
+        assert!(!cache.exists("key2"));
+        assert!(cache.exists("key0"));
+        assert!(cache.exists("key10"));
+    }
+
+    #[tokio::test]
+    async fn test_full_cache_keeps_pinned_entries() {
+        const HOUR: Duration = Duration::from_secs(3600);
+        let cache = MemoryCache::with_max_entries(10);
+        cache.insert_permanent("machine:m1:online".to_string(), vec![1]);
+        cache.insert_pinned_with_ttl("login_lock:alice".to_string(), vec![2], HOUR);
+
+        // Flood the cache far past its cap with evictable entries
+        for i in 0..100 {
+            cache.insert_with_ttl(format!("oidc_state:{}", i), vec![0], HOUR);
+        }
+        assert!(cache.exists("machine:m1:online"));
+        assert!(cache.exists("login_lock:alice"));
+        assert!(cache.stats().entries <= 10);
+
+        // Pinned entries alone may go over the cap rather than be dropped
+        let cache = MemoryCache::with_max_entries(2);
+        for i in 0..5 {
+            cache.insert_permanent(format!("machine:{}:online", i), vec![1]);
+        }
+        assert_eq!(cache.stats().entries, 5);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_go_before_live_ones() {
+        let cache = MemoryCache::with_max_entries(3);
+        cache.insert_permanent("old".to_string(), vec![1]);
+        cache.insert_with_ttl("short".to_string(), vec![2], Duration::from_millis(5));
+        cache.insert_permanent("new".to_string(), vec![3]);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        cache.insert_permanent("newest".to_string(), vec![4]);
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 0);
+        assert_eq!(stats.entries, 3);
+        assert!(cache.exists("old"));
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let cache = MemoryCache::new();
+        cache.insert_permanent("key1".to_string(), vec![1]);
+        cache.get("key1");
+        cache.get("key1");
+        cache.get("missing");
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 1,
+                evictions: 0,
+                entries: 1,
+            }
+        );
+    }
 }
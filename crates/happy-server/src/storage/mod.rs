@@ -1,10 +1,11 @@
 //! Storage layer
 //!
-//! Uses SQLite (embedded) instead of PostgreSQL for simplicity.
+//! Uses SQLite (embedded) by default; PostgreSQL with the `postgres` feature
+//! for several servers sharing one database.
 //! Uses DashMap (in-memory) instead of Redis for caching.
 
 pub mod db;
 pub mod memory;
 
-pub use db::Database;
-pub use memory::MemoryCache;
+pub use db::{AuditEntry, AuditRecord, Database, DatabaseConfig, DatabaseLocation, SsoConfig};
+pub use memory::{CacheStats, MemoryCache};
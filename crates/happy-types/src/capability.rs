@@ -0,0 +1,195 @@
+//! Machine capabilities
+//!
+//! Capabilities travel as plain strings (`"terminal"`, `"file_system"`, ...) so
+//! tools built outside this repo can advertise their own names alongside the
+//! built-in ones.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Capabilities a machine can have
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Terminal,
+    FileSystem,
+    Notifications,
+    Voice,
+    /// Advertised by a third-party tool, e.g. `"code_index"`
+    Custom(String),
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capability::Terminal => write!(f, "terminal"),
+            Capability::FileSystem => write!(f, "file_system"),
+            Capability::Notifications => write!(f, "notifications"),
+            Capability::Voice => write!(f, "voice"),
+            Capability::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid capability '{0}': expected a non-empty name without commas or spaces")]
+pub struct ParseCapabilityError(String);
+
+impl FromStr for Capability {
+    type Err = ParseCapabilityError;
+
+    /// Built-in names map to their variant; any other name is [`Capability::Custom`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Names are stored comma-separated, so they can't contain one
+        if s.is_empty() || s.contains(|c: char| c == ',' || c.is_whitespace()) {
+            return Err(ParseCapabilityError(s.to_string()));
+        }
+        Ok(match s {
+            "terminal" => Capability::Terminal,
+            "file_system" => Capability::FileSystem,
+            "notifications" => Capability::Notifications,
+            "voice" => Capability::Voice,
+            custom => Capability::Custom(custom.to_string()),
+        })
+    }
+}
+
+impl Serialize for Capability {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Capability {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Capabilities of one machine; serialized as a sorted list of names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapabilitySet(HashSet<Capability>);
+
+impl CapabilitySet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, capability: &Capability) -> bool {
+        self.0.contains(capability)
+    }
+
+    /// Returns false if `capability` was already present
+    pub fn insert(&mut self, capability: Capability) -> bool {
+        self.0.insert(capability)
+    }
+
+    /// Returns false if `capability` wasn't present
+    pub fn remove(&mut self, capability: &Capability) -> bool {
+        self.0.remove(capability)
+    }
+
+    /// Whether every capability in `required` is present
+    pub fn contains_all<'a>(&self, required: impl IntoIterator<Item = &'a Capability>) -> bool {
+        required.into_iter().all(|c| self.contains(c))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Capability> {
+        self.0.iter()
+    }
+
+    /// Names in a stable order, for storage and display
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.0.iter().map(|c| c.to_string()).collect();
+        names.sort();
+        names
+    }
+}
+
+impl FromIterator<Capability> for CapabilitySet {
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<const N: usize> From<[Capability; N]> for CapabilitySet {
+    fn from(capabilities: [Capability; N]) -> Self {
+        capabilities.into_iter().collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a CapabilitySet {
+    type Item = &'a Capability;
+    type IntoIter = std::collections::hash_set::Iter<'a, Capability>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Serialize for CapabilitySet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.names().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CapabilitySet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Vec::<Capability>::deserialize(deserializer)?.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_names_round_trip() {
+        for name in ["terminal", "file_system", "notifications", "voice", "code_index"] {
+            let capability: Capability = name.parse().unwrap();
+            assert_eq!(capability.to_string(), name);
+        }
+        assert_eq!("voice".parse::<Capability>().unwrap(), Capability::Voice);
+        assert_eq!(
+            "code_index".parse::<Capability>().unwrap(),
+            Capability::Custom("code_index".to_string())
+        );
+        for bad in ["", "a,b", "two words"] {
+            assert!(bad.parse::<Capability>().is_err());
+        }
+    }
+
+    #[test]
+    fn test_capability_set_serde() {
+        let mut set = CapabilitySet::from([
+            Capability::Voice,
+            Capability::Terminal,
+            Capability::Custom("code_index".to_string()),
+        ]);
+        assert_eq!(
+            serde_json::to_string(&set).unwrap(),
+            r#"["code_index","terminal","voice"]"#
+        );
+
+        // Same shape as the `Vec<Capability>` older builds sent
+        let parsed: CapabilitySet =
+            serde_json::from_str(r#"["terminal","file_system","terminal"]"#).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert!(parsed.contains_all(&[Capability::Terminal, Capability::FileSystem]));
+
+        assert!(set.remove(&Capability::Voice));
+        assert!(!set.contains(&Capability::Voice));
+        assert!(set.insert(Capability::FileSystem));
+        assert!(!set.insert(Capability::FileSystem));
+    }
+}
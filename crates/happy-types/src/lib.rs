@@ -4,15 +4,20 @@
 //! making it compatible with WASM targets.
 
 pub mod artifact;
+pub mod capability;
 pub mod machine;
 pub mod message;
 pub mod session;
+pub mod timestamp;
 pub mod user;
+pub mod wire;
 
 pub use artifact::*;
+pub use capability::*;
 pub use machine::*;
 pub use message::*;
 pub use session::*;
+pub use timestamp::Timestamp;
 pub use user::*;
 
 // Re-export git types
@@ -55,32 +60,29 @@ impl std::fmt::Display for Platform {
 }
 
 /// AI Provider types
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AIProvider {
     Anthropic,
     OpenAI,
     Azure,
     Gemini,
+    /// Models served locally by Ollama; no API key needed
+    Ollama,
+    /// A self-hosted OpenAI-compatible server (vLLM, LocalAI, ...), by display name.
+    /// Profiles using it must set `base_url`.
+    Custom(String),
 }
 
-/// Capabilities a machine can have
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum Capability {
-    Terminal,
-    FileSystem,
-    Notifications,
-    Voice,
-}
-
-impl std::fmt::Display for Capability {
+impl std::fmt::Display for AIProvider {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Capability::Terminal => write!(f, "terminal"),
-            Capability::FileSystem => write!(f, "file_system"),
-            Capability::Notifications => write!(f, "notifications"),
-            Capability::Voice => write!(f, "voice"),
+            AIProvider::Anthropic => write!(f, "anthropic"),
+            AIProvider::OpenAI => write!(f, "openai"),
+            AIProvider::Azure => write!(f, "azure"),
+            AIProvider::Gemini => write!(f, "gemini"),
+            AIProvider::Ollama => write!(f, "ollama"),
+            AIProvider::Custom(name) => write!(f, "{}", name),
         }
     }
 }
@@ -94,6 +96,27 @@ pub struct AIProfile {
     pub base_url: Option<String>,
     pub model: Option<String>,
     pub env_vars: HashMap<String, String>,
+    /// Generation parameters; `None` leaves the provider default
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+impl AIProfile {
+    /// Check the profile has what its provider needs to be reached
+    pub fn validate(&self) -> Result<(), String> {
+        if let AIProvider::Custom(name) = &self.provider {
+            if self.base_url.as_deref().is_none_or(|url| url.trim().is_empty()) {
+                return Err(format!("custom provider '{}' requires a base_url", name));
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Settings persisted to disk
@@ -130,11 +153,18 @@ impl Default for Settings {
 /// Encryption types (definitions only, no implementation)
 pub mod encryption {
     use serde::{Deserialize, Serialize};
+    use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
     pub type PublicKey = [u8; 32];
     pub type SecretKey = [u8; 32];
     pub type Nonce = [u8; 24];
     pub type DataKey = [u8; 32];
+    /// Ed25519 verifying key
+    pub type EdPublicKey = [u8; 32];
+    /// Ed25519 signing key in libsodium layout (seed followed by public key)
+    pub type EdSecretKey = [u8; 64];
+    /// Detached Ed25519 signature
+    pub type Signature = [u8; 64];
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct EncryptedMessage {
@@ -149,12 +179,63 @@ pub mod encryption {
         pub encrypted_data_key: Vec<u8>,
     }
 
-    #[derive(Debug, Clone)]
+    /// `secret_key` is wiped when the pair is dropped
+    #[derive(Zeroize, ZeroizeOnDrop)]
     pub struct KeyPair {
         pub public_key: PublicKey,
         pub secret_key: SecretKey,
     }
 
+    impl Clone for KeyPair {
+        fn clone(&self) -> Self {
+            // Copy through a buffer that is wiped once the new pair owns its copy
+            let mut secret_key = Zeroizing::new([0u8; 32]);
+            secret_key.copy_from_slice(&self.secret_key);
+            Self {
+                public_key: self.public_key,
+                secret_key: *secret_key,
+            }
+        }
+    }
+
+    impl std::fmt::Debug for KeyPair {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("KeyPair")
+                .field("public_key", &self.public_key)
+                .field("secret_key", &"[REDACTED]")
+                .finish()
+        }
+    }
+
+    /// Ed25519 signing pair, separate from the X25519 [`KeyPair`] used for encryption
+    ///
+    /// `secret` is wiped when the pair is dropped
+    #[derive(Zeroize, ZeroizeOnDrop)]
+    pub struct EdKeyPair {
+        pub public: EdPublicKey,
+        pub secret: EdSecretKey,
+    }
+
+    impl Clone for EdKeyPair {
+        fn clone(&self) -> Self {
+            let mut secret = Zeroizing::new([0u8; 64]);
+            secret.copy_from_slice(&self.secret);
+            Self {
+                public: self.public,
+                secret: *secret,
+            }
+        }
+    }
+
+    impl std::fmt::Debug for EdKeyPair {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("EdKeyPair")
+                .field("public", &self.public)
+                .field("secret", &"[REDACTED]")
+                .finish()
+        }
+    }
+
     impl EncryptedMessage {
         pub fn new(nonce: Nonce, ciphertext: Vec<u8>, sender_pubkey: PublicKey) -> Self {
             Self {
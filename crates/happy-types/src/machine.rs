@@ -1,6 +1,6 @@
 //! Machine types
 
-use super::{Capability, Platform};
+use super::{Capability, CapabilitySet, Platform};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -14,9 +14,21 @@ pub struct Machine {
     pub public_key: Vec<u8>,
     pub platform: Platform,
     pub last_seen: DateTime<Utc>,
-    pub capabilities: Vec<Capability>,
+    pub capabilities: CapabilitySet,
     pub ip_address: Option<String>,
     pub hostname: Option<String>,
+    /// Last time the daemon reported in
+    #[serde(default)]
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub daemon_version: Option<String>,
+    /// CPU architecture, e.g. `"aarch64"`
+    #[serde(default)]
+    pub arch: Option<String>,
+    #[serde(default)]
+    pub cpu_count: Option<u32>,
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
 }
 
 impl Machine {
@@ -34,9 +46,14 @@ impl Machine {
             public_key,
             platform,
             last_seen: Utc::now(),
-            capabilities: vec![Capability::Terminal, Capability::FileSystem],
+            capabilities: CapabilitySet::from([Capability::Terminal, Capability::FileSystem]),
             ip_address: None,
             hostname: None,
+            last_heartbeat: None,
+            daemon_version: None,
+            arch: None,
+            cpu_count: None,
+            memory_mb: None,
         }
     }
 
@@ -44,8 +61,8 @@ impl Machine {
         self.last_seen = Utc::now();
     }
 
-    pub fn has_capability(&self, cap: Capability) -> bool {
-        self.capabilities.contains(&cap)
+    pub fn has_capability(&self, cap: &Capability) -> bool {
+        self.capabilities.contains(cap)
     }
 }
 
@@ -55,7 +72,7 @@ pub struct MachineRegistration {
     pub name: String,
     pub public_key: Vec<u8>,
     pub platform: Platform,
-    pub capabilities: Vec<Capability>,
+    pub capabilities: CapabilitySet,
     pub hostname: Option<String>,
 }
 
@@ -67,5 +84,18 @@ pub struct MachineInfo {
     pub platform: Platform,
     pub last_seen: DateTime<Utc>,
     pub is_online: bool,
-    pub capabilities: Vec<Capability>,
+    pub capabilities: CapabilitySet,
+    #[serde(default)]
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub daemon_version: Option<String>,
+    /// CPU architecture, e.g. `"aarch64"`
+    #[serde(default)]
+    pub arch: Option<String>,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub cpu_count: Option<u32>,
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
 }
@@ -1,15 +1,19 @@
 //! WebSocket message protocol
 
-use super::{Artifact, FileEntry, MachineInfo, Session, SessionStatus};
+use super::{Artifact, CapabilitySet, FileEntry, MachineInfo, Session, SessionStatus};
 use serde::{Deserialize, Serialize};
 
 /// Client -> Server messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ClientMessage {
     // Authentication
     Authenticate {
         token: String,
+        /// Receive terminal output and history as binary frames (see [`crate::wire`])
+        #[serde(default)]
+        binary_frames: bool,
     },
 
     // Terminal
@@ -32,7 +36,18 @@ pub enum ClientMessage {
     },
 
     // Session control
-    ListSessions,
+    ListSessions {
+        /// Only sessions on this machine
+        #[serde(default)]
+        machine_id: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+        #[serde(default)]
+        offset: Option<u32>,
+        /// Continue after this session id (the previous page's `next_cursor`)
+        #[serde(default)]
+        cursor: Option<String>,
+    },
     StartSession {
         tag: String,
         profile: Option<String>,
@@ -40,21 +55,57 @@ pub enum ClientMessage {
     StopSession {
         session_id: String,
     },
+    /// Archive the session, or purge it for good if it is already archived
     DeleteSession {
         session_id: String,
     },
+    /// Stop the session and move it to the archive, from where it can be restored
+    ArchiveSession {
+        session_id: String,
+    },
+    RestoreSession {
+        session_id: String,
+    },
+    /// Answered with `ArchivedSessionsList`
+    ListArchivedSessions,
     AttachSession {
         session_id: String,
         tag: String,
         cwd: String,
         machine_id: Option<String>,
         machine_name: Option<String>,
+        #[serde(default)]
+        agent_version: Option<String>,
+        /// `$HOME` of the user the daemon runs as
+        #[serde(default)]
+        home_dir: Option<String>,
+        /// Host details reported by the daemon
+        #[serde(default)]
+        machine_info: Option<MachineInfo>,
+        /// What the machine offers; replaces the stored set when not empty
+        #[serde(default)]
+        capabilities: CapabilitySet,
+        /// Base64 Ed25519 key identifying the machine
+        #[serde(default)]
+        signing_key: Option<String>,
+        /// Base64 signature by `signing_key` over [`attach_signing_payload`]
+        #[serde(default)]
+        signature: Option<String>,
     },
     DetachSession {
         session_id: String,
     },
     JoinSession {
         tag: String,
+        /// Only replay the last N bytes of buffered output (server default applies when unset)
+        #[serde(default)]
+        tail_bytes: Option<usize>,
+    },
+    /// Page backwards through buffered output, by stream offset
+    FetchHistoryRange {
+        session_id: String,
+        offset: u64,
+        length: usize,
     },
 
     // Remote session creation (from web client)
@@ -72,6 +123,12 @@ pub enum ClientMessage {
         error: Option<String>,
     },
 
+    /// Move a session to another machine, keeping its terminal history
+    TransferSession {
+        session_id: String,
+        target_machine_id: String,
+    },
+
     // File operations
     ListFiles {
         session_id: String,
@@ -85,6 +142,24 @@ pub enum ClientMessage {
         session_id: String,
         path: String,
         content: Vec<u8>,
+        /// MIME type; a `charset` parameter re-encodes text before it is written
+        #[serde(default)]
+        content_type: Option<String>,
+        /// How `content` is encoded on the wire: `"utf-8"` (default) or `"base64"`
+        #[serde(default)]
+        encoding: Option<String>,
+    },
+    /// Outcome of a `WriteFileRequest` (from CLI daemon)
+    FileWriteResponse {
+        session_id: String,
+        path: String,
+        success: bool,
+        bytes_written: u64,
+        /// Echoed from the request so the server can answer the right web client
+        #[serde(default)]
+        request_id: String,
+        #[serde(default)]
+        error: Option<String>,
     },
 
     // Machine
@@ -114,6 +189,24 @@ pub enum ClientMessage {
         message: String,
         amend: bool,
     },
+    GitStageAll {
+        session_id: String,
+    },
+    GitUnstageAll {
+        session_id: String,
+    },
+    /// Push to `remote`/`branch`; `None` uses the branch's upstream
+    GitPush {
+        session_id: String,
+        remote: Option<String>,
+        branch: Option<String>,
+    },
+    /// Pull from `remote`/`branch`; `None` uses the branch's upstream
+    GitPull {
+        session_id: String,
+        remote: Option<String>,
+        branch: Option<String>,
+    },
 
     // Git operations (responses from CLI daemon)
     GitStatusResponse {
@@ -136,6 +229,63 @@ pub enum ClientMessage {
         success: bool,
         message: String,
     },
+    GitStageAllResponse {
+        session_id: String,
+        success: bool,
+        message: String,
+    },
+    GitUnstageAllResponse {
+        session_id: String,
+        success: bool,
+        message: String,
+    },
+    GitPushResponse {
+        session_id: String,
+        success: bool,
+        message: String,
+        conflicts: Vec<String>,
+    },
+    /// `conflicts` lists files left unmerged by a failed pull
+    GitPullResponse {
+        session_id: String,
+        success: bool,
+        message: String,
+        conflicts: Vec<String>,
+    },
+
+    // Terminal recording (requests from web client)
+    /// Record the session to an asciinema cast file in `~/.happy/recordings/`
+    /// on its machine
+    StartRecording {
+        session_id: String,
+    },
+    StopRecording {
+        session_id: String,
+    },
+
+    // Terminal recording (responses from CLI daemon)
+    /// Whether the session is being recorded after a start or stop request
+    RecordingResponse {
+        session_id: String,
+        recording: bool,
+        /// Cast file being written, or just finished
+        path: Option<String>,
+        error: Option<String>,
+    },
+
+    /// The session's profile has spent its daily token budget; input to it is
+    /// held until midnight UTC (from CLI daemon)
+    BudgetExceeded {
+        session_id: String,
+        message: String,
+    },
+}
+
+/// Bytes a daemon signs to prove which machine is attaching a session
+///
+/// Fields are NUL-separated so no two field combinations produce the same payload.
+pub fn attach_signing_payload(session_id: &str, machine_id: &str, cwd: &str) -> Vec<u8> {
+    format!("happy-attach-v1\0{}\0{}\0{}", session_id, machine_id, cwd).into_bytes()
 }
 
 /// Server -> Client messages
@@ -150,16 +300,48 @@ pub enum ServerMessage {
         code: String,
         message: String,
     },
+    /// The user's account was deleted; clients should log out
+    AccountDeleted,
+    /// Follows a `rate_limited` error; `message_type` messages are dropped for `retry_after_secs`
+    RateLimited {
+        message_type: String,
+        retry_after_secs: u64,
+    },
+    /// Sent after `Authenticated` with version details for the settings page
+    Welcome {
+        server_version: String,
+        /// Version reported by the user's most recently seen online daemon
+        #[serde(default)]
+        daemon_version: Option<String>,
+    },
 
     // Terminal
     TerminalOutput {
         session_id: String,
         data: Vec<u8>,
     },
+    /// A web client's terminal changed size; sent to the session's CLI bridge
+    TerminalResize {
+        session_id: String,
+        cols: u16,
+        rows: u16,
+    },
     TerminalHistory {
         session_id: String,
         data: Vec<u8>,
     },
+    /// Sent after the join replay when older output was left out; `offset` is where the replay began
+    HistoryTruncated {
+        session_id: String,
+        offset: u64,
+    },
+    HistoryRange {
+        session_id: String,
+        offset: u64,
+        data: Vec<u8>,
+        /// Whether output older than `offset` is still available
+        has_more: bool,
+    },
     TerminalReady {
         session_id: String,
     },
@@ -171,6 +353,13 @@ pub enum ServerMessage {
     // Session events
     SessionsList {
         sessions: Vec<Session>,
+        /// Pass back as `cursor` for the next page; `None` on the last page
+        #[serde(default)]
+        next_cursor: Option<String>,
+    },
+    /// The user's archived sessions; resent whenever the archive changes
+    ArchivedSessionsList {
+        sessions: Vec<Session>,
     },
     SessionStarted {
         session: Session,
@@ -195,6 +384,9 @@ pub enum ServerMessage {
         machine_id: String,
         cwd: Option<String>,
         args: Option<String>,
+        /// Output of the session being transferred, to seed the new session's scrollback
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        history: Vec<u8>,
     },
 
     // Remote session response (server to web client)
@@ -204,6 +396,11 @@ pub enum ServerMessage {
         session: Option<Session>,
         error: Option<String>,
     },
+    /// Sent to viewers of a transferred session, who now receive `session`'s output
+    SessionTransferred {
+        from_session_id: String,
+        session: Session,
+    },
 
     // File events
     FileList {
@@ -219,6 +416,13 @@ pub enum ServerMessage {
         path: String,
         message: String,
     },
+    FileWriteResult {
+        session_id: String,
+        path: String,
+        success: bool,
+        bytes_written: u64,
+        error: Option<String>,
+    },
 
     // Machine events
     MachineRegistered {
@@ -263,6 +467,35 @@ pub enum ServerMessage {
         success: bool,
         message: String,
     },
+    GitStageAllResult {
+        session_id: String,
+        success: bool,
+        message: String,
+    },
+    GitUnstageAllResult {
+        session_id: String,
+        success: bool,
+        message: String,
+    },
+    GitPushResult {
+        session_id: String,
+        success: bool,
+        message: String,
+        conflicts: Vec<String>,
+    },
+    GitPullResult {
+        session_id: String,
+        success: bool,
+        message: String,
+        conflicts: Vec<String>,
+    },
+    /// Relayed `ClientMessage::RecordingResponse`
+    RecordingResult {
+        session_id: String,
+        recording: bool,
+        path: Option<String>,
+        error: Option<String>,
+    },
 
     // Git requests (server to CLI daemon)
     GitStatusRequest {
@@ -280,6 +513,78 @@ pub enum ServerMessage {
         amend: bool,
         requester_id: String,
     },
+    GitStageAllRequest {
+        session_id: String,
+        requester_id: String,
+    },
+    GitUnstageAllRequest {
+        session_id: String,
+        requester_id: String,
+    },
+    GitPushRequest {
+        session_id: String,
+        remote: Option<String>,
+        branch: Option<String>,
+        requester_id: String,
+    },
+    GitPullRequest {
+        session_id: String,
+        remote: Option<String>,
+        branch: Option<String>,
+        requester_id: String,
+    },
+
+    // File requests (server to CLI daemon)
+    /// Relayed `ClientMessage::WriteFile`; answered with `FileWriteResponse`
+    WriteFileRequest {
+        session_id: String,
+        path: String,
+        content: Vec<u8>,
+        content_type: Option<String>,
+        encoding: Option<String>,
+        request_id: String,
+    },
+
+    // Recording requests (server to CLI daemon)
+    StartRecordingRequest {
+        session_id: String,
+        requester_id: String,
+    },
+    StopRecordingRequest {
+        session_id: String,
+        requester_id: String,
+    },
+
+    // Daemon configuration (server -> daemon)
+    /// Equivalent of `happy config set-server` on every connected daemon
+    ServerUrlChanged {
+        url: String,
+    },
+}
+
+/// Session change on the `GET /api/v1/sessions/events` server-sent event stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SessionEvent {
+    #[serde(rename = "session.created")]
+    Created { session: Session },
+    /// Status, tag, cwd or machine changed
+    #[serde(rename = "session.updated")]
+    Updated { session: Session },
+    #[serde(rename = "session.deleted")]
+    Deleted { session_id: String },
+}
+
+impl SessionEvent {
+    /// The event a broadcast carries, if it is a session change
+    pub fn from_server_message(msg: ServerMessage) -> Option<Self> {
+        match msg {
+            ServerMessage::SessionStarted { session } => Some(Self::Created { session }),
+            ServerMessage::SessionUpdated { session } => Some(Self::Updated { session }),
+            ServerMessage::SessionDeleted { session_id } => Some(Self::Deleted { session_id }),
+            _ => None,
+        }
+    }
 }
 
 /// Modified file info
@@ -291,14 +596,28 @@ pub struct ModifiedFile {
     pub deletions: u32,
 }
 
-/// Git change type
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ModifiedFile {
+    /// The original path of a renamed or copied file
+    pub fn rename_source(&self) -> Option<&str> {
+        match &self.change_type {
+            ChangeType::Renamed { from } | ChangeType::Copied { from } => Some(from),
+            _ => None,
+        }
+    }
+}
+
+/// Git change type, covering every status `git status --porcelain` reports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ChangeType {
     Added,
     Modified,
     Deleted,
-    Renamed,
+    Renamed { from: String },
+    Copied { from: String },
+    Unmerged,
+    Untracked,
+    Ignored,
 }
 
 /// RPC request/response wrapper
@@ -322,3 +641,68 @@ pub struct RpcError {
     pub message: String,
     pub data: Option<serde_json::Value>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_type_wire_format() {
+        let renamed = ModifiedFile {
+            path: "src/new.rs".to_string(),
+            change_type: ChangeType::Renamed {
+                from: "src/old.rs".to_string(),
+            },
+            additions: 0,
+            deletions: 0,
+        };
+        let json = serde_json::to_value(&renamed).unwrap();
+        assert_eq!(json["change_type"]["renamed"]["from"], "src/old.rs");
+        assert_eq!(renamed.rename_source(), Some("src/old.rs"));
+
+        assert_eq!(
+            serde_json::to_value(ChangeType::Untracked).unwrap(),
+            "untracked"
+        );
+    }
+
+    #[test]
+    fn test_session_event_wire_format() {
+        let session = Session::new(
+            "s1".to_string(),
+            "main".to_string(),
+            "u1".to_string(),
+            "m1".to_string(),
+            "laptop".to_string(),
+        );
+        let event =
+            SessionEvent::from_server_message(ServerMessage::SessionUpdated { session }).unwrap();
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "session.updated");
+        assert_eq!(json["session"]["id"], "s1");
+
+        let deleted: SessionEvent =
+            serde_json::from_str(r#"{"type":"session.deleted","session_id":"s1"}"#).unwrap();
+        assert!(matches!(deleted, SessionEvent::Deleted { session_id } if session_id == "s1"));
+
+        assert!(SessionEvent::from_server_message(ServerMessage::AccountDeleted).is_none());
+    }
+
+    #[test]
+    fn test_terminal_resize_wire_format() {
+        let msg = ServerMessage::TerminalResize {
+            session_id: "s1".to_string(),
+            cols: 120,
+            rows: 40,
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"terminal_resize","session_id":"s1","cols":120,"rows":40}"#
+        );
+        assert!(matches!(
+            serde_json::from_str(&json).unwrap(),
+            ServerMessage::TerminalResize { cols: 120, rows: 40, .. }
+        ));
+    }
+}
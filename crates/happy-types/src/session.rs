@@ -29,8 +29,17 @@ impl std::fmt::Display for SessionStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionMetadata {
     pub cwd: String,
+    /// Directories the session has been in, oldest first; the last is `cwd`
+    #[serde(default)]
+    pub cwd_history: Vec<String>,
     pub env: HashMap<String, String>,
     pub claude_version: Option<String>,
+    /// Version reported by the agent binary (`<agent> --version`)
+    #[serde(default)]
+    pub agent_version: Option<String>,
+    /// `$HOME` on the session's machine, so clients can abbreviate paths to `~`
+    #[serde(default)]
+    pub home_dir: Option<String>,
     pub shell: String,
 }
 
@@ -38,8 +47,11 @@ impl Default for SessionMetadata {
     fn default() -> Self {
         Self {
             cwd: "/".to_string(),
+            cwd_history: Vec::new(),
             env: HashMap::new(),
             claude_version: None,
+            agent_version: None,
+            home_dir: None,
             shell: "/bin/bash".to_string(),
         }
     }
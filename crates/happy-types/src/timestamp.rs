@@ -0,0 +1,127 @@
+//! Timestamps on the wire
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// A UTC instant that serializes as an RFC 3339 string.
+///
+/// Deserializes from RFC 3339 strings and from Unix timestamps in seconds, so
+/// peers that send either form can be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(DateTime<Utc>);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self(Utc::now())
+    }
+
+    /// Seconds since the Unix epoch
+    pub fn unix_seconds(&self) -> i64 {
+        self.0.timestamp()
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(datetime: DateTime<Utc>) -> Self {
+        Self(datetime)
+    }
+}
+
+/// Unix seconds; values beyond what `DateTime` can hold clamp to its range
+impl From<i64> for Timestamp {
+    fn from(seconds: i64) -> Self {
+        Self(DateTime::from_timestamp(seconds, 0).unwrap_or(if seconds < 0 {
+            DateTime::<Utc>::MIN_UTC
+        } else {
+            DateTime::<Utc>::MAX_UTC
+        }))
+    }
+}
+
+impl From<Timestamp> for DateTime<Utc> {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TimestampVisitor)
+    }
+}
+
+struct TimestampVisitor;
+
+impl Visitor<'_> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an RFC 3339 string or Unix timestamp in seconds")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Timestamp, E> {
+        DateTime::parse_from_rfc3339(value)
+            .map(|datetime| Timestamp(datetime.with_timezone(&Utc)))
+            .map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, seconds: i64) -> Result<Timestamp, E> {
+        DateTime::from_timestamp(seconds, 0)
+            .map(Timestamp)
+            .ok_or_else(|| E::custom(format!("Unix timestamp out of range: {}", seconds)))
+    }
+
+    fn visit_u64<E: de::Error>(self, seconds: u64) -> Result<Timestamp, E> {
+        let seconds = i64::try_from(seconds)
+            .map_err(|_| E::custom(format!("Unix timestamp out of range: {}", seconds)))?;
+        self.visit_i64(seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_deserialize_both_forms() {
+        let from_str: Timestamp = serde_json::from_str("\"2024-03-01T12:00:00+02:00\"").unwrap();
+        let from_unix: Timestamp = serde_json::from_str("1709287200").unwrap();
+        assert_eq!(from_str, from_unix);
+        assert_eq!(from_str.to_string(), "2024-03-01T10:00:00Z");
+        assert_eq!(serde_json::to_string(&from_str).unwrap(), "\"2024-03-01T10:00:00Z\"");
+
+        assert!(serde_json::from_str::<Timestamp>("\"yesterday\"").is_err());
+        assert!(serde_json::from_str::<Timestamp>("true").is_err());
+    }
+
+    proptest! {
+        // Years 1 through 9999, the range RFC 3339 can express
+        #[test]
+        fn test_json_round_trip(
+            seconds in -62_135_596_800i64..253_402_300_800,
+            nanos in 0u32..1_000_000_000,
+        ) {
+            let timestamp = Timestamp::from(DateTime::from_timestamp(seconds, nanos).unwrap());
+            let json = serde_json::to_string(&timestamp).unwrap();
+            prop_assert_eq!(serde_json::from_str::<Timestamp>(&json).unwrap(), timestamp);
+
+            let unix: Timestamp = serde_json::from_str(&seconds.to_string()).unwrap();
+            prop_assert_eq!(unix, Timestamp::from(seconds));
+        }
+    }
+}
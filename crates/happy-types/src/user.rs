@@ -1,8 +1,9 @@
 //! User types
 
-use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::Timestamp;
+
 /// User account
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
@@ -10,8 +11,8 @@ pub struct User {
     pub email: String,
     pub name: Option<String>,
     pub avatar_url: Option<String>,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
 }
 
 /// User registration request
@@ -34,6 +35,7 @@ pub struct UserLogin {
 pub struct AuthTokens {
     pub access_token: String,
     pub refresh_token: String,
+    /// Seconds until `access_token` expires; a duration, not a timestamp
     pub expires_in: i64,
 }
 
@@ -45,9 +47,9 @@ pub struct AccessKey {
     pub name: String,
     pub key_prefix: String,
     pub permissions: Vec<String>,
-    pub created_at: DateTime<Utc>,
-    pub expires_at: Option<DateTime<Utc>>,
-    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: Timestamp,
+    pub expires_at: Option<Timestamp>,
+    pub last_used_at: Option<Timestamp>,
 }
 
 /// Access key creation request
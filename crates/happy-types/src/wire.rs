@@ -0,0 +1,136 @@
+//! Binary WebSocket frames for terminal data
+//!
+//! JSON spells every byte of `data` out as a number, so terminal traffic is sent
+//! as a compact frame instead to clients that ask for it when authenticating:
+//!
+//! | bytes | field                               |
+//! |-------|-------------------------------------|
+//! | 1     | message type                        |
+//! | 4     | session ID length, little-endian    |
+//! | n     | session ID, UTF-8                   |
+//! | 4     | payload length, little-endian       |
+//! | m     | payload                             |
+//!
+//! Everything else stays a JSON text frame.
+
+use crate::ServerMessage;
+
+const TERMINAL_OUTPUT: u8 = 1;
+const TERMINAL_HISTORY: u8 = 2;
+
+/// Why a binary frame couldn't be decoded
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WireError {
+    #[error("frame ends early")]
+    Truncated,
+    #[error("{0} bytes after the end of the frame")]
+    TrailingBytes(usize),
+    #[error("unknown message type {0}")]
+    UnknownType(u8),
+    #[error("session ID is not UTF-8")]
+    InvalidSessionId,
+}
+
+/// The binary frame for `msg`, or `None` for messages that go as JSON
+pub fn encode_binary(msg: &ServerMessage) -> Option<Vec<u8>> {
+    let (kind, session_id, data) = match msg {
+        ServerMessage::TerminalOutput { session_id, data } => (TERMINAL_OUTPUT, session_id, data),
+        ServerMessage::TerminalHistory { session_id, data } => {
+            (TERMINAL_HISTORY, session_id, data)
+        }
+        _ => return None,
+    };
+
+    let mut frame = Vec::with_capacity(9 + session_id.len() + data.len());
+    frame.push(kind);
+    frame.extend_from_slice(&(session_id.len() as u32).to_le_bytes());
+    frame.extend_from_slice(session_id.as_bytes());
+    frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    frame.extend_from_slice(data);
+    Some(frame)
+}
+
+pub fn decode_binary(bytes: &[u8]) -> Result<ServerMessage, WireError> {
+    let (&kind, rest) = bytes.split_first().ok_or(WireError::Truncated)?;
+    let (session_id, rest) = take_field(rest)?;
+    let (data, rest) = take_field(rest)?;
+    if !rest.is_empty() {
+        return Err(WireError::TrailingBytes(rest.len()));
+    }
+
+    let session_id = std::str::from_utf8(session_id)
+        .map_err(|_| WireError::InvalidSessionId)?
+        .to_string();
+    let data = data.to_vec();
+    match kind {
+        TERMINAL_OUTPUT => Ok(ServerMessage::TerminalOutput { session_id, data }),
+        TERMINAL_HISTORY => Ok(ServerMessage::TerminalHistory { session_id, data }),
+        other => Err(WireError::UnknownType(other)),
+    }
+}
+
+/// A length-prefixed field and whatever follows it
+fn take_field(bytes: &[u8]) -> Result<(&[u8], &[u8]), WireError> {
+    let (len, rest) = bytes.split_first_chunk::<4>().ok_or(WireError::Truncated)?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if rest.len() < len {
+        return Err(WireError::Truncated);
+    }
+    Ok(rest.split_at(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let output = ServerMessage::TerminalOutput {
+            session_id: "s1".to_string(),
+            data: b"\x1b[32mok\x1b[0m\r\n".to_vec(),
+        };
+        let frame = encode_binary(&output).unwrap();
+        assert_eq!(&frame[..7], &[1, 2, 0, 0, 0, b's', b'1']);
+        assert!(matches!(
+            decode_binary(&frame).unwrap(),
+            ServerMessage::TerminalOutput { session_id, data }
+                if session_id == "s1" && data == b"\x1b[32mok\x1b[0m\r\n"
+        ));
+
+        let history = ServerMessage::TerminalHistory {
+            session_id: "s2".to_string(),
+            data: vec![0xff; 3],
+        };
+        let frame = encode_binary(&history).unwrap();
+        assert!(matches!(
+            decode_binary(&frame).unwrap(),
+            ServerMessage::TerminalHistory { data, .. } if data == [0xff; 3]
+        ));
+
+        assert!(encode_binary(&ServerMessage::Pong).is_none());
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_frames() {
+        let frame = encode_binary(&ServerMessage::TerminalOutput {
+            session_id: "s1".to_string(),
+            data: b"hello".to_vec(),
+        })
+        .unwrap();
+
+        assert_eq!(decode_binary(&[]).unwrap_err(), WireError::Truncated);
+        assert_eq!(
+            decode_binary(&frame[..frame.len() - 1]).unwrap_err(),
+            WireError::Truncated
+        );
+        let mut longer = frame.clone();
+        longer.push(0);
+        assert_eq!(decode_binary(&longer).unwrap_err(), WireError::TrailingBytes(1));
+        let mut unknown = frame.clone();
+        unknown[0] = 9;
+        assert_eq!(decode_binary(&unknown).unwrap_err(), WireError::UnknownType(9));
+        let mut bad_id = frame;
+        bad_id[5] = 0xff;
+        assert_eq!(decode_binary(&bad_id).unwrap_err(), WireError::InvalidSessionId);
+    }
+}
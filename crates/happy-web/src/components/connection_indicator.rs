@@ -0,0 +1,90 @@
+//! WebSocket connection badge with a reconnect countdown
+//!
+//! Hidden while connected. After a drop it counts down to the next attempt,
+//! shows a spinner during the attempt, and flashes green once it succeeds.
+
+use gloo_timers::callback::{Interval, Timeout};
+use yew::prelude::*;
+
+/// How long the green badge stays up after a successful reconnect
+const RECONNECTED_FLASH_MS: u32 = 2_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionState {
+    /// First connection attempt after the page loads
+    Connecting,
+    Connected,
+    /// Closed; the next attempt fires at `reconnect_at` (ms since the epoch)
+    Disconnected {
+        reconnect_at: f64,
+    },
+    Reconnecting,
+    Error(String),
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ConnectionIndicatorProps {
+    pub state: ConnectionState,
+}
+
+#[function_component(ConnectionIndicator)]
+pub fn connection_indicator(props: &ConnectionIndicatorProps) -> Html {
+    let force_update = use_force_update();
+    let show_reconnected = use_state(|| false);
+    let previous = use_mut_ref(|| props.state.clone());
+
+    {
+        let show_reconnected = show_reconnected.clone();
+        use_effect_with(props.state.clone(), move |state| {
+            let was = std::mem::replace(&mut *previous.borrow_mut(), state.clone());
+            let recovered = *state == ConnectionState::Connected
+                && !matches!(
+                    was,
+                    ConnectionState::Connecting | ConnectionState::Connected
+                );
+            show_reconnected.set(recovered);
+
+            // Tick once a second so the countdown re-renders
+            let ticker = matches!(state, ConnectionState::Disconnected { .. })
+                .then(|| Interval::new(1_000, move || force_update.force_update()));
+            let flash = recovered
+                .then(|| Timeout::new(RECONNECTED_FLASH_MS, move || show_reconnected.set(false)));
+
+            move || {
+                drop(ticker);
+                drop(flash);
+            }
+        });
+    }
+
+    match &props.state {
+        ConnectionState::Connected if *show_reconnected => html! {
+            <span class="connection-indicator reconnected">{ "● Connected" }</span>
+        },
+        ConnectionState::Connected => html! {},
+        ConnectionState::Connecting => html! {
+            <span class="status connecting">{ "○ Connecting..." }</span>
+        },
+        ConnectionState::Disconnected { reconnect_at } => {
+            let seconds = ((reconnect_at - js_sys::Date::now()) / 1_000.0)
+                .ceil()
+                .max(0.0);
+            html! {
+                <span class="connection-indicator offline">
+                    { format!("⚠ Reconnecting in {}s", seconds) }
+                </span>
+            }
+        }
+        ConnectionState::Reconnecting => html! {
+            <span class="connection-indicator offline">
+                <span class="connection-spinner"></span>
+                { "Reconnecting..." }
+            </span>
+        },
+        ConnectionState::Error(message) => html! {
+            <span class="connection-indicator offline" title={message.clone()}>
+                { format!("⚠ {}", message) }
+            </span>
+        },
+    }
+}
@@ -0,0 +1,105 @@
+//! Page-wide keyboard shortcuts
+//!
+//! - `Alt+1` … `Alt+9`: select the Nth session in the sidebar
+//! - `Alt+←` / `Alt+→`: select the previous / next session
+//!
+//! Keys typed into the terminal or a form field belong to it, so shortcuts are
+//! ignored while one of those has focus.
+
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Element, KeyboardEvent};
+use yew::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shortcut {
+    /// Select the session at this zero-based position in the list
+    Select(usize),
+    /// Select the session before the current one
+    Previous,
+    /// Select the session after the current one
+    Next,
+}
+
+impl Shortcut {
+    fn from_event(e: &KeyboardEvent) -> Option<Self> {
+        if !e.alt_key() || e.ctrl_key() || e.meta_key() || e.shift_key() {
+            return None;
+        }
+        // `code` names the physical key; `key` is a symbol like "¡" with Option on macOS
+        match e.code().as_str() {
+            "ArrowLeft" => Some(Shortcut::Previous),
+            "ArrowRight" => Some(Shortcut::Next),
+            code => {
+                let digit: usize = code.strip_prefix("Digit")?.parse().ok()?;
+                (1..=9)
+                    .contains(&digit)
+                    .then(|| Shortcut::Select(digit - 1))
+            }
+        }
+    }
+}
+
+/// Whether the focused element takes its own keyboard input
+fn focus_captures_keys() -> bool {
+    let Some(active) = web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.active_element())
+    else {
+        return false;
+    };
+    let in_terminal = active.closest(".xterm-container").ok().flatten().is_some();
+    in_terminal || matches!(active.tag_name().as_str(), "INPUT" | "TEXTAREA" | "SELECT")
+}
+
+/// Listen for shortcuts on the window for as long as the component is mounted
+#[hook]
+pub fn use_keyboard_shortcuts(on_shortcut: Callback<Shortcut>) {
+    // The listener outlives renders, so it reads the latest callback through a ref
+    let latest = use_mut_ref(|| on_shortcut.clone());
+    *latest.borrow_mut() = on_shortcut;
+
+    use_effect_with((), move |_| {
+        let listener = Closure::<dyn Fn(KeyboardEvent)>::new(move |e: KeyboardEvent| {
+            let Some(shortcut) = Shortcut::from_event(&e) else {
+                return;
+            };
+            if focus_captures_keys() {
+                return;
+            }
+            e.prevent_default();
+            latest.borrow().emit(shortcut);
+        });
+
+        let window = web_sys::window();
+        if let Some(window) = &window {
+            let _ = window
+                .add_event_listener_with_callback("keydown", listener.as_ref().unchecked_ref());
+        }
+
+        move || {
+            if let Some(window) = window {
+                let _ = window.remove_event_listener_with_callback(
+                    "keydown",
+                    listener.as_ref().unchecked_ref(),
+                );
+            }
+        }
+    });
+}
+
+/// Scroll the sidebar so the session's entry is visible
+pub fn scroll_session_into_view(session_id: &str) {
+    let selector = format!(".chat-session-item[data-session-id=\"{}\"]", session_id);
+    let Some(item) = web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.query_selector(&selector).ok().flatten())
+    else {
+        return;
+    };
+
+    let options = web_sys::ScrollIntoViewOptions::new();
+    options.set_block(web_sys::ScrollLogicalPosition::Nearest);
+    options.set_behavior(web_sys::ScrollBehavior::Smooth);
+    Element::scroll_into_view_with_scroll_into_view_options(&item, &options);
+}
@@ -3,10 +3,19 @@
 //! A floating console log viewer with filtering and copy functionality.
 
 use yew::prelude::*;
-use crate::utils::logger::{LogLevel, LogEntry, get_logs, clear_logs, copy_logs_to_clipboard, get_logs_filtered};
-use web_sys::HtmlElement;
+use crate::utils::logger::{self, LogLevel, LogEntry, LogSubscription, get_logs, clear_logs, copy_logs_to_clipboard, get_logs_filtered};
+use web_sys::{HtmlElement, HtmlInputElement, HtmlSelectElement};
 use wasm_bindgen::JsValue;
 
+/// Options of the level dropdown; each level shows itself and anything more severe
+const LEVEL_OPTIONS: [(&str, Option<LogLevel>); 5] = [
+    ("ALL", None),
+    ("ERROR", Some(LogLevel::Error)),
+    ("WARN", Some(LogLevel::Warn)),
+    ("INFO", Some(LogLevel::Info)),
+    ("DEBUG", Some(LogLevel::Debug)),
+];
+
 #[derive(Properties, PartialEq, Clone)]
 pub struct LogViewerProps {
     /// Whether the viewer is visible
@@ -23,17 +32,23 @@ pub enum LogViewerMsg {
     Refresh,
     Clear,
     Copy,
-    SetFilter(LogLevel),
-    ToggleLevel(LogLevel),
+    SetFilter(Option<LogLevel>),
+    SetQuery(String),
+    /// An entry was captured or the logs were cleared
+    LogsChanged,
     ScrollToBottom,
 }
 
 pub struct LogViewer {
+    /// Captured entries at or above `filter`
     logs: Vec<LogEntry>,
-    filter: LogLevel,
+    filter: Option<LogLevel>,
+    /// Lowercased substring the message must contain
+    query: String,
     auto_scroll: bool,
     log_container_ref: NodeRef,
     visible: bool,
+    _subscription: LogSubscription,
 }
 
 impl Component for LogViewer {
@@ -42,12 +57,16 @@ impl Component for LogViewer {
 
     fn create(ctx: &Context<Self>) -> Self {
         let logs = get_logs();
+        let link = ctx.link().clone();
+        let subscription = logger::subscribe(move || link.send_message(LogViewerMsg::LogsChanged));
         Self {
             logs,
-            filter: LogLevel::Debug,
+            filter: None,
+            query: String::new(),
             auto_scroll: true,
             log_container_ref: NodeRef::default(),
             visible: ctx.props().visible,
+            _subscription: subscription,
         }
     }
 
@@ -74,11 +93,7 @@ impl Component for LogViewer {
                 true
             }
             LogViewerMsg::Refresh => {
-                self.logs = if self.filter == LogLevel::Debug {
-                    get_logs()
-                } else {
-                    get_logs_filtered(self.filter)
-                };
+                self.reload();
                 self.auto_scroll = true;
                 true
             }
@@ -97,25 +112,20 @@ impl Component for LogViewer {
             }
             LogViewerMsg::SetFilter(level) => {
                 self.filter = level;
-                self.logs = if self.filter == LogLevel::Debug {
-                    get_logs()
-                } else {
-                    get_logs_filtered(self.filter)
-                };
+                self.reload();
                 true
             }
-            LogViewerMsg::ToggleLevel(level) => {
-                // Toggle between showing this level and above vs just this level and above
-                if self.filter == level {
-                    self.filter = LogLevel::Debug;
-                } else {
-                    self.filter = level;
+            LogViewerMsg::SetQuery(query) => {
+                self.query = query.to_lowercase();
+                true
+            }
+            LogViewerMsg::LogsChanged => {
+                if !self.visible {
+                    return false;
                 }
-                self.logs = if self.filter == LogLevel::Debug {
-                    get_logs()
-                } else {
-                    get_logs_filtered(self.filter)
-                };
+                // Follow new entries only if the user hasn't scrolled up
+                self.auto_scroll = self.is_scrolled_to_bottom();
+                self.reload();
                 true
             }
             LogViewerMsg::ScrollToBottom => {
@@ -132,8 +142,13 @@ impl Component for LogViewer {
             return html! {};
         }
 
-        let filtered_logs = &self.logs;
+        let filtered_logs: Vec<&LogEntry> = self
+            .logs
+            .iter()
+            .filter(|entry| self.query.is_empty() || entry.message.to_lowercase().contains(&self.query))
+            .collect();
         let log_count = filtered_logs.len();
+        let filter_value = self.filter.map_or("ALL", |level| level.as_str());
 
         html! {
             <div class="log-viewer-container">
@@ -143,13 +158,30 @@ impl Component for LogViewer {
                         <span class="log-count">{format!("({})", log_count)}</span>
                     </div>
                     <div class="log-viewer-controls">
-                        // Level filters
-                        <div class="log-level-filters">
-                            {self.render_level_filter(ctx, LogLevel::Debug, "DEBUG")}
-                            {self.render_level_filter(ctx, LogLevel::Info, "INFO")}
-                            {self.render_level_filter(ctx, LogLevel::Warn, "WARN")}
-                            {self.render_level_filter(ctx, LogLevel::Error, "ERROR")}
-                        </div>
+                        <input
+                            type="search"
+                            class="log-search"
+                            placeholder="Filter..."
+                            oninput={ctx.link().callback(|e: InputEvent| {
+                                let input: HtmlInputElement = e.target_unchecked_into();
+                                LogViewerMsg::SetQuery(input.value())
+                            })}
+                        />
+                        <select
+                            class="log-level-select"
+                            onchange={ctx.link().callback(|e: Event| {
+                                let select: HtmlSelectElement = e.target_unchecked_into();
+                                let level = LEVEL_OPTIONS
+                                    .iter()
+                                    .find(|(label, _)| *label == select.value())
+                                    .and_then(|(_, level)| *level);
+                                LogViewerMsg::SetFilter(level)
+                            })}
+                        >
+                            {for LEVEL_OPTIONS.iter().map(|(label, _)| html! {
+                                <option value={*label} selected={*label == filter_value}>{*label}</option>
+                            })}
+                        </select>
                         <button
                             class="log-btn log-btn-refresh"
                             onclick={ctx.link().callback(|_| LogViewerMsg::Refresh)}
@@ -222,22 +254,17 @@ impl Component for LogViewer {
 }
 
 impl LogViewer {
-    fn render_level_filter(&self, ctx: &Context<Self>, level: LogLevel, label: &str) -> Html {
-        let is_active = self.filter == level;
-        let class = if is_active {
-            format!("log-level-btn active {}", level.as_str().to_lowercase())
-        } else {
-            format!("log-level-btn {}", level.as_str().to_lowercase())
+    fn reload(&mut self) {
+        self.logs = match self.filter {
+            Some(level) => get_logs_filtered(level),
+            None => get_logs(),
         };
+    }
 
-        html! {
-            <button
-                class={class}
-                onclick={ctx.link().callback(move |_| LogViewerMsg::ToggleLevel(level))}
-            >
-                {label}
-            </button>
-        }
+    fn is_scrolled_to_bottom(&self) -> bool {
+        self.log_container_ref
+            .cast::<HtmlElement>()
+            .is_none_or(|c| c.scroll_height() - c.scroll_top() - c.client_height() < 20)
     }
 
     fn render_log_entry(&self, entry: &LogEntry) -> Html {
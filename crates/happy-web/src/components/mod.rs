@@ -1,11 +1,21 @@
 //! UI components
 
+pub mod connection_indicator;
+pub mod keyboard_shortcuts;
 pub mod log_viewer;
 pub mod protected_route;
+pub mod recording_player;
+pub mod server_info;
+pub mod session_expired_banner;
 pub mod session_list;
 pub mod terminal;
 pub mod xterm;
 
+pub use connection_indicator::{ConnectionIndicator, ConnectionState};
+pub use keyboard_shortcuts::{scroll_session_into_view, use_keyboard_shortcuts, Shortcut};
 pub use log_viewer::LogViewer;
 pub use protected_route::{use_auth, AuthState, ProtectedRoute};
+pub use recording_player::RecordingPlayer;
+pub use server_info::{use_server_info, ServerInfo, ServerInfoContext};
+pub use session_expired_banner::SessionExpiredBanner;
 pub use xterm::{XTerm, XTermInstance, XTermProps};
@@ -1,22 +1,22 @@
 //! Protected Route Component
 //!
-//! Ensures only authenticated users can access certain routes
+//! Ensures only authenticated users can access certain routes. An expired
+//! access token is refreshed once; if that fails the user is sent back to
+//! the login page after a short banner.
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use futures::channel::oneshot;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{ProgressEvent, XmlHttpRequest};
 use yew::prelude::*;
 use yew_router::prelude::*;
 
+use crate::components::SessionExpiredBanner;
 use crate::Route;
 
-/// Checks if user is authenticated by looking for token in localStorage
-fn is_authenticated() -> bool {
-    web_sys::window()
-        .and_then(|w| w.local_storage().ok())
-        .flatten()
-        .and_then(|storage| storage.get_item("happy_token").ok())
-        .flatten()
-        .is_some()
-}
-
 /// Get auth state from localStorage
 fn get_auth_state() -> AuthState {
     web_sys::window()
@@ -26,8 +26,13 @@ fn get_auth_state() -> AuthState {
             let token = storage.get_item("happy_token").ok().flatten();
             let user_id = storage.get_item("happy_user_id").ok().flatten();
             let user_email = storage.get_item("happy_user_email").ok().flatten();
+            let token_status = match &token {
+                Some(token) if is_expired(token) => TokenStatus::Refreshing,
+                _ => TokenStatus::Valid,
+            };
             AuthState {
                 is_authenticated: token.is_some(),
+                token_status,
                 token,
                 user_id,
                 user_email,
@@ -35,31 +40,128 @@ fn get_auth_state() -> AuthState {
         })
         .unwrap_or_else(|| AuthState {
             is_authenticated: false,
+            token_status: TokenStatus::Valid,
             token: None,
             user_id: None,
             user_email: None,
         })
 }
 
+/// Expiry (`exp`, seconds since the epoch) from a JWT's payload; the signature is not checked
+fn token_expiry(token: &str) -> Option<u64> {
+    let payload = token.split('.').nth(1)?;
+    // JWTs use unpadded base64url; `atob` wants standard base64
+    let mut base64 = payload.replace('-', "+").replace('_', "/");
+    while base64.len() % 4 != 0 {
+        base64.push('=');
+    }
+    let json = web_sys::window()?.atob(&base64).ok()?;
+    serde_json::from_str::<serde_json::Value>(&json).ok()?["exp"].as_u64()
+}
+
+/// Tokens without a readable `exp` are left for the server to judge
+fn is_expired(token: &str) -> bool {
+    token_expiry(token)
+        .map(|exp| exp as f64 <= js_sys::Date::now() / 1000.0)
+        .unwrap_or(false)
+}
+
+fn clear_auth_storage() {
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        let _ = storage.remove_item("happy_token");
+        let _ = storage.remove_item("happy_refresh_token");
+        let _ = storage.remove_item("happy_user_id");
+        let _ = storage.remove_item("happy_user_email");
+    }
+}
+
+/// Completes a pending XHR with the request, or `None` on a network error
+type Responder = Rc<RefCell<Option<oneshot::Sender<Option<XmlHttpRequest>>>>>;
+
+fn respond_on(sender: Responder, loaded: bool) -> wasm_bindgen::JsValue {
+    Closure::once_into_js(move |e: ProgressEvent| {
+        let xhr: XmlHttpRequest = e.target().unwrap().dyn_into().unwrap();
+        if let Some(sender) = sender.borrow_mut().take() {
+            let _ = sender.send(loaded.then_some(xhr));
+        }
+    })
+}
+
+/// Trade the stored refresh token for a new token pair and store it
+async fn refresh_tokens() -> Result<(), String> {
+    let storage = web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .ok_or("localStorage unavailable")?;
+    let refresh_token = storage
+        .get_item("happy_refresh_token")
+        .ok()
+        .flatten()
+        .ok_or("No refresh token")?;
+
+    let request = XmlHttpRequest::new().map_err(|e| format!("XHR error: {:?}", e))?;
+    request
+        .open("POST", "/api/v1/auth/refresh")
+        .map_err(|e| format!("Open error: {:?}", e))?;
+    request
+        .set_request_header("Content-Type", "application/json")
+        .map_err(|e| format!("Header error: {:?}", e))?;
+
+    // Resolved by whichever of onload/onerror fires first
+    let (sender, receiver) = oneshot::channel();
+    let sender: Responder = Rc::new(RefCell::new(Some(sender)));
+    let onload = respond_on(sender.clone(), true);
+    let onerror = respond_on(sender, false);
+    request.set_onload(Some(onload.as_ref().unchecked_ref()));
+    request.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+
+    let body = serde_json::json!({ "refresh_token": refresh_token }).to_string();
+    request
+        .send_with_opt_str(Some(&body))
+        .map_err(|e| format!("Send error: {:?}", e))?;
+
+    let xhr = receiver
+        .await
+        .map_err(|e| format!("Response error: {:?}", e))?
+        .ok_or("Network error")?;
+    let status = xhr.status().map_err(|e| format!("Status error: {:?}", e))?;
+    if status != 200 {
+        return Err(format!("Refresh failed with status {}", status));
+    }
+
+    let text = xhr
+        .response_text()
+        .map_err(|e| format!("Text error: {:?}", e))?
+        .unwrap_or_default();
+    let json: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("JSON parse error: {}", e))?;
+    let access_token = json["access_token"].as_str().ok_or("No access token in response")?;
+
+    let _ = storage.set_item("happy_token", access_token);
+    if let Some(refresh_token) = json["refresh_token"].as_str() {
+        let _ = storage.set_item("happy_refresh_token", refresh_token);
+    }
+    if let Some(id) = json["user"]["id"].as_str() {
+        let _ = storage.set_item("happy_user_id", id);
+    }
+    if let Some(email) = json["user"]["email"].as_str() {
+        let _ = storage.set_item("happy_user_email", email);
+    }
+    Ok(())
+}
+
 /// Redirects to login page if not authenticated
 #[function_component(ProtectedRoute)]
 pub fn protected_route(props: &ProtectedRouteProps) -> Html {
-    let authenticated = use_state(|| is_authenticated());
-
-    {
-        let authenticated = authenticated.clone();
-        use_effect_with((), move |_| {
-            authenticated.set(is_authenticated());
-            || ()
-        });
-    }
+    let auth = use_auth();
 
-    if *authenticated {
-        props.children.clone()
-    } else {
-        html! {
+    match auth.token_status {
+        TokenStatus::Expired => html! { <SessionExpiredBanner /> },
+        // Hold the page back until the new token is in place
+        TokenStatus::Refreshing => html! {},
+        TokenStatus::Valid if auth.is_authenticated => props.children.clone(),
+        TokenStatus::Valid => html! {
             <Redirect<Route> to={Route::Login} />
-        }
+        },
     }
 }
 
@@ -68,9 +170,20 @@ pub struct ProtectedRouteProps {
     pub children: Html,
 }
 
+/// Whether the stored access token can still be used
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenStatus {
+    Valid,
+    /// Expired; a refresh is in flight
+    Refreshing,
+    /// Expired and the refresh failed; stored credentials have been cleared
+    Expired,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct AuthState {
     pub is_authenticated: bool,
+    pub token_status: TokenStatus,
     pub token: Option<String>,
     pub user_id: Option<String>,
     pub user_email: Option<String>,
@@ -80,18 +193,14 @@ impl AuthState {
     /// Logout the user by clearing localStorage
     pub fn logout(&self) {
         if let Some(window) = web_sys::window() {
-            if let Ok(Some(storage)) = window.local_storage() {
-                let _ = storage.remove_item("happy_token");
-                let _ = storage.remove_item("happy_user_id");
-                let _ = storage.remove_item("happy_user_email");
-            }
+            clear_auth_storage();
             // Redirect to login
             let _ = window.location().set_href("/login");
         }
     }
 }
 
-/// Hook to get current user info from localStorage
+/// Hook to get current user info from localStorage, refreshing an expired token
 #[hook]
 pub fn use_auth() -> AuthState {
     let auth_state = use_state(get_auth_state);
@@ -99,7 +208,29 @@ pub fn use_auth() -> AuthState {
     {
         let auth_state = auth_state.clone();
         use_effect_with((), move |_| {
-            auth_state.set(get_auth_state());
+            let current = get_auth_state();
+            let refreshing = current.token_status == TokenStatus::Refreshing;
+            auth_state.set(current);
+
+            if refreshing {
+                wasm_bindgen_futures::spawn_local(async move {
+                    match refresh_tokens().await {
+                        // Trust the server over the local clock for the new token
+                        Ok(()) => auth_state.set(AuthState {
+                            token_status: TokenStatus::Valid,
+                            ..get_auth_state()
+                        }),
+                        Err(e) => {
+                            log::warn!("Session expired: {}", e);
+                            clear_auth_storage();
+                            auth_state.set(AuthState {
+                                token_status: TokenStatus::Expired,
+                                ..get_auth_state()
+                            });
+                        }
+                    }
+                });
+            }
             || ()
         });
     }
@@ -0,0 +1,204 @@
+//! Local replay of an imported asciinema recording
+//!
+//! Writes the recording's output into a read-only `XTerm` on the original
+//! timing, scaled by a speed slider. Nothing is sent over the WebSocket.
+
+use std::rc::Rc;
+
+use gloo_timers::callback::Timeout;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::components::XTerm;
+use crate::utils::asciicast::Recording;
+
+const MIN_SPEED: f64 = 0.5;
+const MAX_SPEED: f64 = 4.0;
+
+/// Resets the terminal before replaying from the start
+const TERMINAL_RESET: &[u8] = b"\x1bc";
+
+#[derive(Properties)]
+pub struct RecordingPlayerProps {
+    pub name: String,
+    pub recording: Rc<Recording>,
+    #[prop_or_default]
+    pub on_close: Callback<()>,
+}
+
+impl PartialEq for RecordingPlayerProps {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && Rc::ptr_eq(&self.recording, &other.recording)
+            && self.on_close == other.on_close
+    }
+}
+
+pub enum RecordingPlayerMsg {
+    WriterReady(Callback<Vec<u8>>),
+    /// Write the next event and schedule the one after
+    Tick,
+    TogglePlay,
+    SetSpeed(f64),
+}
+
+pub struct RecordingPlayer {
+    writer: Option<Callback<Vec<u8>>>,
+    /// Index of the next event to write
+    next_event: usize,
+    /// Recording time reached so far, in seconds
+    position: f64,
+    speed: f64,
+    playing: bool,
+    /// Pending `Tick`; dropping it pauses playback
+    timeout: Option<Timeout>,
+}
+
+impl RecordingPlayer {
+    fn schedule_next(&mut self, ctx: &Context<Self>) {
+        let Some(event) = ctx.props().recording.events.get(self.next_event) else {
+            self.playing = false;
+            self.timeout = None;
+            return;
+        };
+        let delay_ms = ((event.time - self.position).max(0.0) / self.speed * 1000.0) as u32;
+        let link = ctx.link().clone();
+        self.timeout = Some(Timeout::new(delay_ms, move || {
+            link.send_message(RecordingPlayerMsg::Tick);
+        }));
+    }
+
+    fn finished(&self, ctx: &Context<Self>) -> bool {
+        self.next_event >= ctx.props().recording.events.len()
+    }
+}
+
+impl Component for RecordingPlayer {
+    type Message = RecordingPlayerMsg;
+    type Properties = RecordingPlayerProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            writer: None,
+            next_event: 0,
+            position: 0.0,
+            speed: 1.0,
+            playing: false,
+            timeout: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            RecordingPlayerMsg::WriterReady(writer) => {
+                // Start as soon as the terminal can take output
+                let first = self.writer.is_none();
+                self.writer = Some(writer);
+                if first {
+                    self.playing = true;
+                    self.schedule_next(ctx);
+                }
+                true
+            }
+            RecordingPlayerMsg::Tick => {
+                let events = &ctx.props().recording.events;
+                if let (Some(event), Some(writer)) = (events.get(self.next_event), &self.writer) {
+                    writer.emit(event.data.as_bytes().to_vec());
+                    self.position = event.time;
+                    self.next_event += 1;
+                }
+                self.schedule_next(ctx);
+                true
+            }
+            RecordingPlayerMsg::TogglePlay => {
+                if self.playing {
+                    self.playing = false;
+                    self.timeout = None;
+                } else {
+                    if self.finished(ctx) {
+                        if let Some(writer) = &self.writer {
+                            writer.emit(TERMINAL_RESET.to_vec());
+                        }
+                        self.next_event = 0;
+                        self.position = 0.0;
+                    }
+                    self.playing = true;
+                    self.schedule_next(ctx);
+                }
+                true
+            }
+            RecordingPlayerMsg::SetSpeed(speed) => {
+                self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+                if self.playing {
+                    self.schedule_next(ctx);
+                }
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        let duration = props.recording.duration();
+        let progress = if duration > 0.0 {
+            (self.position / duration * 100.0).min(100.0)
+        } else if self.finished(ctx) {
+            100.0
+        } else {
+            0.0
+        };
+
+        let link = ctx.link();
+        let on_writer = link.callback(RecordingPlayerMsg::WriterReady);
+        let on_toggle = link.callback(|_| RecordingPlayerMsg::TogglePlay);
+        let on_speed = link.callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            RecordingPlayerMsg::SetSpeed(input.value().parse().unwrap_or(1.0))
+        });
+        let on_close = props.on_close.reform(|_| ());
+
+        html! {
+            <div class="recording-player">
+                <div class="recording-player-header">
+                    <span class="recording-player-title">{ format!("📼 {}", props.name) }</span>
+                    <button class="recording-player-close" title="关闭" onclick={on_close}>{ "✕" }</button>
+                </div>
+                <div class="recording-player-terminal">
+                    <XTerm
+                        id={format!("recording-{}", props.name)}
+                        read_only=true
+                        on_writer={on_writer}
+                    />
+                </div>
+                <div class="recording-player-controls">
+                    <button class="recording-player-toggle" onclick={on_toggle}>
+                        { if self.playing { "⏸" } else { "▶" } }
+                    </button>
+                    <div class="recording-player-progress">
+                        <div class="recording-player-progress-bar" style={format!("width: {:.1}%", progress)}></div>
+                    </div>
+                    <span class="recording-player-time">
+                        { format!("{} / {}", format_time(self.position), format_time(duration)) }
+                    </span>
+                    <label class="recording-player-speed">
+                        <input
+                            type="range"
+                            min={MIN_SPEED.to_string()}
+                            max={MAX_SPEED.to_string()}
+                            step="0.5"
+                            value={self.speed.to_string()}
+                            oninput={on_speed}
+                        />
+                        { format!("{}×", self.speed) }
+                    </label>
+                </div>
+            </div>
+        }
+    }
+}
+
+/// `m:ss` for the time display
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
@@ -0,0 +1,20 @@
+//! Version details from the server's `welcome` message
+//!
+//! Provided at the `App` level so any page can show them; the terminal page fills it in
+//! when its WebSocket receives `welcome`.
+
+use yew::prelude::*;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerInfo {
+    pub server_version: String,
+    pub daemon_version: Option<String>,
+}
+
+/// `None` until a `welcome` message has arrived
+pub type ServerInfoContext = UseStateHandle<Option<ServerInfo>>;
+
+#[hook]
+pub fn use_server_info() -> Option<ServerInfoContext> {
+    use_context::<ServerInfoContext>()
+}
@@ -0,0 +1,38 @@
+//! Notice shown when the login could not be renewed
+//!
+//! Stays up for a few seconds, then sends the user to the login page with
+//! `?reason=expired` so it can explain why they were signed out.
+
+use gloo_timers::callback::Timeout;
+use yew::prelude::*;
+
+/// How long the banner is shown before the redirect
+const REDIRECT_DELAY_MS: u32 = 5_000;
+
+const LOGIN_URL: &str = "/login?reason=expired";
+
+fn go_to_login() {
+    if let Some(window) = web_sys::window() {
+        let _ = window.location().set_href(LOGIN_URL);
+    }
+}
+
+#[function_component(SessionExpiredBanner)]
+pub fn session_expired_banner() -> Html {
+    use_effect_with((), |_| {
+        let redirect = Timeout::new(REDIRECT_DELAY_MS, go_to_login);
+        move || drop(redirect)
+    });
+
+    html! {
+        <div class="session-expired-banner" role="alert">
+            <span>{ format!(
+                "Your session has expired. Redirecting to sign in in {} seconds…",
+                REDIRECT_DELAY_MS / 1_000
+            ) }</span>
+            <button class="btn-link" onclick={Callback::from(|_| go_to_login())}>
+                { "Sign in now" }
+            </button>
+        </div>
+    }
+}
@@ -1,10 +1,14 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use gloo_timers::callback::Timeout;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::{HtmlElement, ResizeObserver};
+use web_sys::{HtmlElement, HtmlInputElement, ResizeObserver};
 use yew::prelude::*;
 
+/// How long the container must keep its size before the terminal is refitted
+const RESIZE_DEBOUNCE_MS: u32 = 50;
+
 /// XTerm terminal component for rendering terminal output
 pub struct XTerm {
     terminal: Option<XTermInstance>,
@@ -17,6 +21,8 @@ pub struct XTerm {
     debug_id: usize,
     /// Current on_input callback - wrapped in Rc to allow updating
     current_on_input: Rc<RefCell<Callback<Vec<u8>>>>,
+    /// Current on_resize callback, swapped in `changed()` like `current_on_input`
+    current_on_resize: Rc<RefCell<Callback<(u32, u32)>>>,
     /// Resize observer to handle layout changes
     resize_observer: Option<ResizeObserver>,
     /// Keep closure alive
@@ -27,6 +33,18 @@ pub struct XTerm {
     _scroll_closure: Option<Closure<dyn FnMut()>>,
     /// Writer callback stored for debugging
     writer: Option<Callback<Vec<u8>>>,
+    /// Whether the search bar is showing
+    search_open: bool,
+    /// `(current, total)` matches for the search bar's query
+    search_matches: (usize, usize),
+    /// Mirrors the `search_enabled` prop for the keydown listener
+    search_enabled: Rc<Cell<bool>>,
+    /// Current on_search_match_count callback, swapped in `changed()` like `current_on_input`
+    current_on_search_match_count: Rc<RefCell<Callback<(usize, usize)>>>,
+    /// Ctrl+F listener on the container (kept alive)
+    _keydown_closure: Option<Closure<dyn FnMut(KeyboardEvent)>>,
+    /// Search results closure (kept alive)
+    _search_results_closure: Option<Closure<dyn FnMut(JsValue)>>,
 }
 
 static XTERM_COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
@@ -56,6 +74,15 @@ pub struct XTermProps {
     /// Callback when user scrolls up (to show scroll-to-bottom button)
     #[prop_or_default]
     pub on_scroll_state_change: Callback<bool>, // true = scrolled up, false = at bottom
+    /// Callback with the terminal's `(cols, rows)` after it is fitted to its container
+    #[prop_or_default]
+    pub on_resize: Callback<(u32, u32)>,
+    /// Whether Ctrl+F opens the search bar
+    #[prop_or(true)]
+    pub search_enabled: bool,
+    /// Callback with the search's `(current, total)` matches, 1-based; `(0, 0)` when it closes
+    #[prop_or_default]
+    pub on_search_match_count: Option<Callback<(usize, usize)>>,
 }
 
 pub enum XTermMsg {
@@ -65,6 +92,11 @@ pub enum XTermMsg {
     Clear,
     ScrollToBottom,
     UserScrolled(bool), // Track if user manually scrolled up
+    OpenSearch,
+    /// Find `query`, forwards or backwards from the current match
+    Search { query: String, forward: bool },
+    SearchResults(usize, usize),
+    CloseSearch,
 }
 
 impl Component for XTerm {
@@ -82,6 +114,9 @@ impl Component for XTerm {
         ctx.link().send_message(XTermMsg::Initialize);
         // Store initial on_input callback in RefCell
         let current_on_input = Rc::new(RefCell::new(ctx.props().on_input.clone()));
+        let current_on_resize = Rc::new(RefCell::new(ctx.props().on_resize.clone()));
+        let current_on_search_match_count =
+            Rc::new(RefCell::new(ctx.props().on_search_match_count.clone().unwrap_or_default()));
         Self {
             terminal: None,
             container_ref: NodeRef::default(),
@@ -89,11 +124,18 @@ impl Component for XTerm {
             pending_content: String::new(),
             debug_id,
             current_on_input,
+            current_on_resize,
             resize_observer: None,
             _resize_closure: None,
             user_scrolled_up: false,
             _scroll_closure: None,
             writer: None,
+            search_open: false,
+            search_matches: (0, 0),
+            search_enabled: Rc::new(Cell::new(ctx.props().search_enabled)),
+            current_on_search_match_count,
+            _keydown_closure: None,
+            _search_results_closure: None,
         }
     }
 
@@ -140,11 +182,26 @@ impl Component for XTerm {
             }
             // Update callback
             *self.current_on_input.borrow_mut() = ctx.props().on_input.clone();
+            *self.current_on_resize.borrow_mut() = ctx.props().on_resize.clone();
+            *self.current_on_search_match_count.borrow_mut() =
+                ctx.props().on_search_match_count.clone().unwrap_or_default();
+            self.search_enabled.set(ctx.props().search_enabled);
+            // Matches in the old session's output are gone
+            if self.search_open {
+                ctx.link().send_message(XTermMsg::CloseSearch);
+            }
             return false;
         }
 
         // ALWAYS update the callback in RefCell to ensure we have the latest closure
         *self.current_on_input.borrow_mut() = ctx.props().on_input.clone();
+        *self.current_on_resize.borrow_mut() = ctx.props().on_resize.clone();
+        *self.current_on_search_match_count.borrow_mut() =
+            ctx.props().on_search_match_count.clone().unwrap_or_default();
+        self.search_enabled.set(ctx.props().search_enabled);
+        if !ctx.props().search_enabled && self.search_open {
+            ctx.link().send_message(XTermMsg::CloseSearch);
+        }
 
         // Check if initial_content changed (e.g., terminal_history arrived after init)
         if ctx.props().initial_content != old_props.initial_content {
@@ -154,8 +211,8 @@ impl Component for XTerm {
                 self.debug_id, old_content.len(), new_content.len());
 
             if let Some(term) = &self.terminal {
-                // If new content is longer, append the difference
-                if new_content.len() > old_content.len() {
+                // If new content extends the old, append the difference
+                if new_content.len() > old_content.len() && new_content.starts_with(old_content.as_str()) {
                     let to_write = if old_content.is_empty() {
                         new_content.as_str()
                     } else {
@@ -170,8 +227,8 @@ impl Component for XTerm {
                     if !self.user_scrolled_up {
                         term.scroll_to_bottom();
                     }
-                } else if new_content.len() < old_content.len() {
-                    // Content was reset (e.g., new session)
+                } else if new_content != old_content {
+                    // Content was reset (e.g., new session) or older history was prepended
                     log::info!("[XTerm#{}] Content shrunk, clearing and rewriting", self.debug_id);
                     term.clear();
                     if !new_content.is_empty() {
@@ -296,7 +353,23 @@ impl Component for XTerm {
                                 }
                                 self._scroll_closure = Some(scroll_cb);
 
+                                if term.has_search() {
+                                    let results_link = ctx.link().clone();
+                                    self._search_results_closure =
+                                        Some(term.on_search_results(move |current, total| {
+                                            results_link
+                                                .send_message(XTermMsg::SearchResults(current, total));
+                                        }));
+                                    self.attach_search_shortcut(ctx);
+                                } else {
+                                    log::warn!(
+                                        "[XTerm#{}] SearchAddon not loaded, search disabled",
+                                        self.debug_id
+                                    );
+                                }
+
                                 self.terminal = Some(term);
+                                self.attach_resize_observer();
                                 return true;
                             }
                             Err(e) => {
@@ -355,88 +428,170 @@ impl Component for XTerm {
                 }
                 false
             }
+            XTermMsg::OpenSearch => {
+                let available = self.terminal.as_ref().is_some_and(|t| t.has_search());
+                if self.search_open || !available || !ctx.props().search_enabled {
+                    return false;
+                }
+                self.search_open = true;
+                true
+            }
+            XTermMsg::Search { query, forward } => {
+                if let Some(term) = &self.terminal {
+                    if query.is_empty() {
+                        term.clear_search();
+                        ctx.link().send_message(XTermMsg::SearchResults(0, 0));
+                    } else {
+                        term.find(&query, forward);
+                    }
+                }
+                false
+            }
+            XTermMsg::SearchResults(current, total) => {
+                if !self.search_open || self.search_matches == (current, total) {
+                    return false;
+                }
+                self.search_matches = (current, total);
+                self.current_on_search_match_count.borrow().emit((current, total));
+                true
+            }
+            XTermMsg::CloseSearch => {
+                if !self.search_open {
+                    return false;
+                }
+                self.search_open = false;
+                self.search_matches = (0, 0);
+                if let Some(term) = &self.terminal {
+                    term.clear_search();
+                    term.focus();
+                }
+                self.current_on_search_match_count.borrow().emit((0, 0));
+                true
+            }
         }
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
         html! {
-            <div
-                ref={self.container_ref.clone()}
-                id={ctx.props().id.clone()}
-                class="xterm-container"
-            />
+            <>
+                <div
+                    ref={self.container_ref.clone()}
+                    id={ctx.props().id.clone()}
+                    class="xterm-container"
+                />
+                if self.search_open {
+                    <SearchBar
+                        matches={self.search_matches}
+                        on_search={link.callback(|(query, forward)| XTermMsg::Search { query, forward })}
+                        on_close={link.callback(|_| XTermMsg::CloseSearch)}
+                    />
+                }
+            </>
         }
     }
+}
 
-    fn rendered(&mut self, _ctx: &Context<Self>, first_render: bool) {
-        if first_render {
-            // Setup ResizeObserver to fit terminal when container size changes
-            if let Some(terminal_instance) = &self.terminal {
-                let terminal_clone = terminal_instance.clone();
-                let on_resize = Closure::wrap(Box::new(
-                    move |_entries: js_sys::Array, _observer: ResizeObserver| {
-                        terminal_clone.fit();
-                        terminal_clone.scroll_to_bottom();
-                    },
-                )
-                    as Box<dyn FnMut(js_sys::Array, ResizeObserver)>);
-
-                if let Ok(observer) = ResizeObserver::new(on_resize.as_ref().unchecked_ref()) {
-                    if let Some(element) = self.container_ref.cast::<HtmlElement>() {
-                        observer.observe(&element);
-                        self.resize_observer = Some(observer);
-                        self._resize_closure = Some(on_resize);
-                        log::info!("[XTerm#{}] ResizeObserver attached", self.debug_id);
-                    }
-                } else {
-                    log::error!("[XTerm#{}] Failed to create ResizeObserver", self.debug_id);
-                }
+impl XTerm {
+    /// Open the search bar on Ctrl+F (Cmd+F on macOS) while the terminal has focus.
+    ///
+    /// Listens in the capture phase so the key never reaches xterm.js, which
+    /// would otherwise send it to the shell as ^F.
+    fn attach_search_shortcut(&mut self, ctx: &Context<Self>) {
+        let Some(element) = self.container_ref.cast::<HtmlElement>() else {
+            return;
+        };
+        let link = ctx.link().clone();
+        let search_enabled = self.search_enabled.clone();
+        let on_keydown = Closure::wrap(Box::new(move |e: KeyboardEvent| {
+            let modifier = e.ctrl_key() || e.meta_key();
+            if search_enabled.get() && modifier && !e.alt_key() && e.key().eq_ignore_ascii_case("f") {
+                e.prevent_default();
+                e.stop_propagation();
+                link.send_message(XTermMsg::OpenSearch);
+            }
+        }) as Box<dyn FnMut(KeyboardEvent)>);
 
-                // Schedule delayed fit() calls to ensure layout has settled
-                // The container may not have its final dimensions immediately
-                let term_50 = terminal_instance.clone();
-                let term_200 = terminal_instance.clone();
-                let term_500 = terminal_instance.clone();
-
-                if let Some(window) = web_sys::window() {
-                    // Fit after a short delay (50ms - layout usually settled)
-                    let cb_50 = Closure::once(Box::new(move || {
-                        term_50.fit();
-                        term_50.scroll_to_bottom();
-                    }) as Box<dyn FnOnce()>);
-                    let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
-                        cb_50.as_ref().unchecked_ref(),
-                        50,
-                    );
-                    cb_50.forget();
-
-                    // Fit after medium delay (200ms - for slow devices)
-                    let cb_200 = Closure::once(Box::new(move || {
-                        term_200.fit();
-                        term_200.scroll_to_bottom();
-                    }) as Box<dyn FnOnce()>);
-                    let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
-                        cb_200.as_ref().unchecked_ref(),
-                        200,
-                    );
-                    cb_200.forget();
-
-                    // Fit after longer delay (500ms - final safety net)
-                    let cb_500 = Closure::once(Box::new(move || {
-                        term_500.fit();
-                        term_500.scroll_to_bottom();
-                    }) as Box<dyn FnOnce()>);
-                    let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
-                        cb_500.as_ref().unchecked_ref(),
-                        500,
-                    );
-                    cb_500.forget();
-                }
+        let options = web_sys::AddEventListenerOptions::new();
+        options.set_capture(true);
+        if element
+            .add_event_listener_with_callback_and_add_event_listener_options(
+                "keydown",
+                on_keydown.as_ref().unchecked_ref(),
+                &options,
+            )
+            .is_ok()
+        {
+            self._keydown_closure = Some(on_keydown);
+        }
+    }
+
+    /// Fit the terminal whenever its container changes size and report the new grid.
+    ///
+    /// Runs once the terminal exists, which is after the first render.
+    fn attach_resize_observer(&mut self) {
+        let Some(terminal_instance) = &self.terminal else {
+            return;
+        };
+        if self.resize_observer.is_some() {
+            return;
+        }
+
+        // Setup ResizeObserver to fit terminal when container size changes; a drag
+        // fires it every frame, so only the last size within the window is reported
+        let terminal_clone = terminal_instance.clone();
+        let on_resize_cb = self.current_on_resize.clone();
+        let pending: Rc<RefCell<Option<Timeout>>> = Rc::new(RefCell::new(None));
+        let on_resize = Closure::wrap(Box::new(
+            move |_entries: js_sys::Array, _observer: ResizeObserver| {
+                let term = terminal_clone.clone();
+                let on_resize_cb = on_resize_cb.clone();
+                // Replacing the timeout drops, and so cancels, the previous one
+                *pending.borrow_mut() = Some(Timeout::new(RESIZE_DEBOUNCE_MS, move || {
+                    fit_and_report(&term, &on_resize_cb);
+                }));
+            },
+        )
+            as Box<dyn FnMut(js_sys::Array, ResizeObserver)>);
+
+        if let Ok(observer) = ResizeObserver::new(on_resize.as_ref().unchecked_ref()) {
+            if let Some(element) = self.container_ref.cast::<HtmlElement>() {
+                observer.observe(&element);
+                self.resize_observer = Some(observer);
+                self._resize_closure = Some(on_resize);
+                log::info!("[XTerm#{}] ResizeObserver attached", self.debug_id);
+            }
+        } else {
+            log::error!("[XTerm#{}] Failed to create ResizeObserver", self.debug_id);
+        }
+
+        // Schedule delayed fit() calls to ensure layout has settled
+        // The container may not have its final dimensions immediately
+        if let Some(window) = web_sys::window() {
+            // 50ms - layout usually settled, 200ms - for slow devices, 500ms - final safety net
+            for delay in [50, 200, 500] {
+                let term = terminal_instance.clone();
+                let on_resize_cb = self.current_on_resize.clone();
+                let cb = Closure::once(Box::new(move || {
+                    fit_and_report(&term, &on_resize_cb);
+                }) as Box<dyn FnOnce()>);
+                let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                    cb.as_ref().unchecked_ref(),
+                    delay,
+                );
+                cb.forget();
             }
         }
     }
 }
 
+/// Fit `term` to its container and emit its grid size to the current `on_resize`
+fn fit_and_report(term: &XTermInstance, on_resize: &Rc<RefCell<Callback<(u32, u32)>>>) {
+    term.fit();
+    term.scroll_to_bottom();
+    on_resize.borrow().emit((term.cols(), term.rows()));
+}
+
 impl Drop for XTerm {
     fn drop(&mut self) {
         log::info!(
@@ -446,11 +601,105 @@ impl Drop for XTerm {
     }
 }
 
+#[derive(Properties, PartialEq)]
+pub struct SearchBarProps {
+    /// `(current, total)` matches, 1-based
+    pub matches: (usize, usize),
+    /// Called with the query and whether to search forwards
+    pub on_search: Callback<(String, bool)>,
+    pub on_close: Callback<()>,
+}
+
+/// Find-in-terminal overlay: Enter for the next match, Shift+Enter for the
+/// previous one, Escape to close
+#[function_component(SearchBar)]
+pub fn search_bar(props: &SearchBarProps) -> Html {
+    let input_ref = use_node_ref();
+    let query = use_state(String::new);
+
+    // Take focus from the terminal as soon as the bar opens
+    {
+        let input_ref = input_ref.clone();
+        use_effect_with((), move |_| {
+            if let Some(input) = input_ref.cast::<HtmlInputElement>() {
+                let _ = input.focus();
+            }
+        });
+    }
+
+    let on_input = {
+        let query = query.clone();
+        let on_search = props.on_search.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let value = input.value();
+            query.set(value.clone());
+            on_search.emit((value, true));
+        })
+    };
+
+    let on_keydown = {
+        let query = query.clone();
+        let on_search = props.on_search.clone();
+        let on_close = props.on_close.clone();
+        Callback::from(move |e: KeyboardEvent| match e.key().as_str() {
+            "Enter" => {
+                e.prevent_default();
+                on_search.emit(((*query).clone(), !e.shift_key()));
+            }
+            "Escape" => {
+                e.prevent_default();
+                on_close.emit(());
+            }
+            _ => {}
+        })
+    };
+
+    let step = |forward: bool| {
+        let query = query.clone();
+        let on_search = props.on_search.clone();
+        Callback::from(move |_: MouseEvent| on_search.emit(((*query).clone(), forward)))
+    };
+    let on_close = {
+        let on_close = props.on_close.clone();
+        Callback::from(move |_: MouseEvent| on_close.emit(()))
+    };
+
+    let (current, total) = props.matches;
+    let count = if query.is_empty() {
+        String::new()
+    } else if total == 0 {
+        "无结果".to_string()
+    } else {
+        format!("{}/{}", current, total)
+    };
+
+    html! {
+        <div class="terminal-search-bar">
+            <input
+                ref={input_ref}
+                type="text"
+                class="terminal-search-input"
+                placeholder="搜索终端"
+                value={(*query).clone()}
+                oninput={on_input}
+                onkeydown={on_keydown}
+            />
+            <span class="terminal-search-count">{ count }</span>
+            <button class="terminal-search-btn" title="上一个 (Shift+Enter)" disabled={total == 0} onclick={step(false)}>{ "↑" }</button>
+            <button class="terminal-search-btn" title="下一个 (Enter)" disabled={total == 0} onclick={step(true)}>{ "↓" }</button>
+            <button class="terminal-search-btn" title="关闭 (Esc)" onclick={on_close}>{ "×" }</button>
+        </div>
+    }
+}
+
 /// Wrapper around xterm.js Terminal instance
 #[derive(Clone)]
 pub struct XTermInstance {
     terminal: JsValue,
     fit_addon: JsValue,
+    /// `None` if addon-search.min.js isn't loaded
+    search_addon: Option<JsValue>,
 }
 
 impl XTermInstance {
@@ -496,6 +745,12 @@ impl XTermInstance {
             &JsValue::from_str("convertEol"),
             &JsValue::from_bool(true), // Convert \n to \r\n
         )?;
+        // SearchAddon's match highlighting uses the decorations API
+        js_sys::Reflect::set(
+            &opts,
+            &JsValue::from_str("allowProposedApi"),
+            &JsValue::from_bool(true),
+        )?;
 
         // Create terminal instance
         let terminal = js_sys::Reflect::construct(
@@ -518,9 +773,12 @@ impl XTermInstance {
             let _ = fit_method.call0(&fit_addon);
         }
 
+        let search_addon = Self::get_search_addon(&terminal).ok();
+
         Ok(Self {
             terminal,
             fit_addon,
+            search_addon,
         })
     }
 
@@ -680,6 +938,115 @@ impl XTermInstance {
         Ok(addon)
     }
 
+    fn get_search_addon(terminal: &JsValue) -> Result<JsValue, JsValue> {
+        let window = web_sys::window().unwrap();
+        let search = js_sys::Reflect::get(&window, &JsValue::from_str("SearchAddon"))?;
+        let search_class = js_sys::Reflect::get(&search, &JsValue::from_str("SearchAddon"))?;
+
+        let addon = js_sys::Reflect::construct(
+            &search_class.dyn_into::<js_sys::Function>()?,
+            &js_sys::Array::new(),
+        )?;
+
+        let load_method = js_sys::Reflect::get(terminal, &JsValue::from_str("loadAddon"))?
+            .dyn_into::<js_sys::Function>()?;
+        load_method.call1(terminal, &addon)?;
+
+        Ok(addon)
+    }
+
+    /// Options for `findNext`/`findPrevious`: every match in yellow, the current one in orange
+    fn search_options() -> JsValue {
+        let decorations = js_sys::Object::new();
+        for (key, color) in [
+            ("matchBackground", "#d29922"),
+            ("matchOverviewRuler", "#d29922"),
+            ("activeMatchBackground", "#f0883e"),
+            ("activeMatchColorOverviewRuler", "#f0883e"),
+        ] {
+            js_sys::Reflect::set(&decorations, &JsValue::from_str(key), &JsValue::from_str(color))
+                .unwrap();
+        }
+
+        let options = js_sys::Object::new();
+        js_sys::Reflect::set(&options, &JsValue::from_str("decorations"), &decorations).unwrap();
+        options.into()
+    }
+
+    /// Whether the search addon is loaded
+    pub fn has_search(&self) -> bool {
+        self.search_addon.is_some()
+    }
+
+    /// Select the next (or previous) match for `query`, highlighting all of them
+    pub fn find(&self, query: &str, forward: bool) {
+        let Some(addon) = &self.search_addon else {
+            return;
+        };
+        let name = if forward { "findNext" } else { "findPrevious" };
+        if let Ok(find_method) = js_sys::Reflect::get(addon, &JsValue::from_str(name))
+            .and_then(|m| m.dyn_into::<js_sys::Function>())
+        {
+            let _ = find_method.call2(addon, &JsValue::from_str(query), &Self::search_options());
+        }
+    }
+
+    /// Remove match highlighting and the selection left by the last search
+    pub fn clear_search(&self) {
+        let Some(addon) = &self.search_addon else {
+            return;
+        };
+        if let Ok(clear_method) = js_sys::Reflect::get(addon, &JsValue::from_str("clearDecorations"))
+            .and_then(|m| m.dyn_into::<js_sys::Function>())
+        {
+            let _ = clear_method.call0(addon);
+        }
+        if let Ok(clear_method) = js_sys::Reflect::get(&self.terminal, &JsValue::from_str("clearSelection"))
+            .and_then(|m| m.dyn_into::<js_sys::Function>())
+        {
+            let _ = clear_method.call0(&self.terminal);
+        }
+    }
+
+    /// Call `callback` with `(current, total)` matches, 1-based, whenever the search results change.
+    ///
+    /// The returned closure must be kept alive for as long as the terminal.
+    pub fn on_search_results<F>(&self, mut callback: F) -> Closure<dyn FnMut(JsValue)>
+    where
+        F: FnMut(usize, usize) + 'static,
+    {
+        let cb = Closure::wrap(Box::new(move |event: JsValue| {
+            let field = |name: &str| {
+                js_sys::Reflect::get(&event, &JsValue::from_str(name))
+                    .ok()
+                    .and_then(|v| v.as_f64())
+                    .unwrap_or(0.0)
+            };
+            // resultIndex is -1 when nothing is selected
+            let total = field("resultCount").max(0.0) as usize;
+            let current = (field("resultIndex") + 1.0).max(0.0) as usize;
+            callback(current.min(total), total);
+        }) as Box<dyn FnMut(JsValue)>);
+
+        if let Some(addon) = &self.search_addon {
+            if let Ok(method) = js_sys::Reflect::get(addon, &JsValue::from_str("onDidChangeResults"))
+                .and_then(|m| m.dyn_into::<js_sys::Function>())
+            {
+                let _ = method.call1(addon, cb.as_ref().unchecked_ref());
+            }
+        }
+        cb
+    }
+
+    /// Give the terminal keyboard focus
+    pub fn focus(&self) {
+        if let Ok(focus_method) = js_sys::Reflect::get(&self.terminal, &JsValue::from_str("focus"))
+            .and_then(|m| m.dyn_into::<js_sys::Function>())
+        {
+            let _ = focus_method.call0(&self.terminal);
+        }
+    }
+
     pub fn write(&self, data: &str) {
         if let Ok(write_method) = js_sys::Reflect::get(&self.terminal, &JsValue::from_str("write"))
             .and_then(|m| m.dyn_into::<js_sys::Function>())
@@ -741,6 +1108,23 @@ impl XTermInstance {
         }
     }
 
+    /// Number of columns in the terminal grid
+    pub fn cols(&self) -> u32 {
+        self.dimension("cols")
+    }
+
+    /// Number of rows in the terminal grid
+    pub fn rows(&self) -> u32 {
+        self.dimension("rows")
+    }
+
+    fn dimension(&self, name: &str) -> u32 {
+        js_sys::Reflect::get(&self.terminal, &JsValue::from_str(name))
+            .ok()
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0) as u32
+    }
+
     pub fn fit(&self) {
         // Use the stored FitAddon instance
         if let Ok(fit_method) = js_sys::Reflect::get(&self.fit_addon, &JsValue::from_str("fit"))
@@ -800,3 +1184,214 @@ impl Drop for XTermInstance {
         }
     }
 }
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use gloo_timers::future::TimeoutFuture;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// Evaluate `source` (a JS expression) in the page
+    fn js(source: &str) -> JsValue {
+        js_sys::Function::new_no_args(&format!("return {}", source))
+            .call0(&JsValue::NULL)
+            .unwrap()
+    }
+
+    fn get(value: &JsValue, path: &str) -> JsValue {
+        path.split('.').fold(value.clone(), |value, key| {
+            js_sys::Reflect::get(&value, &JsValue::from_str(key)).unwrap()
+        })
+    }
+
+    /// A terminal whose search addon, if it has one, records the calls made to it
+    fn fake_instance(with_search: bool) -> XTermInstance {
+        let terminal = js(r#"{
+            cleared: 0,
+            clearSelection() { this.cleared += 1; },
+        }"#);
+        let search_addon = js(r#"{
+            calls: [],
+            findNext(query, options) { this.calls.push(["next", query, options]); },
+            findPrevious(query, options) { this.calls.push(["previous", query, options]); },
+            clearDecorations() { this.calls.push(["clear"]); },
+            onDidChangeResults(handler) { this.handler = handler; },
+        }"#);
+        XTermInstance {
+            terminal,
+            fit_addon: js("{}"),
+            search_addon: with_search.then_some(search_addon),
+        }
+    }
+
+    fn calls(instance: &XTermInstance) -> js_sys::Array {
+        get(instance.search_addon.as_ref().unwrap(), "calls").into()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_find() {
+        let instance = fake_instance(true);
+        assert!(instance.has_search());
+
+        instance.find("error", true);
+        instance.find("error", false);
+        let calls = calls(&instance);
+        assert_eq!(calls.length(), 2);
+        let next = calls.get(0);
+        assert_eq!(get(&next, "0"), "next");
+        assert_eq!(get(&next, "1"), "error");
+        assert_eq!(get(&next, "2.decorations.activeMatchBackground"), "#f0883e");
+        assert_eq!(get(&calls.get(1), "0"), "previous");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_clear_search() {
+        let instance = fake_instance(true);
+        instance.clear_search();
+        assert_eq!(get(&calls(&instance).get(0), "0"), "clear");
+        assert_eq!(get(&instance.terminal, "cleared"), 1);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_on_search_results() {
+        let instance = fake_instance(true);
+        let results = Rc::new(RefCell::new(Vec::new()));
+        let _closure = {
+            let results = results.clone();
+            instance.on_search_results(move |current, total| {
+                results.borrow_mut().push((current, total))
+            })
+        };
+
+        let handler: js_sys::Function =
+            get(instance.search_addon.as_ref().unwrap(), "handler").into();
+        for event in [
+            "{ resultIndex: 2, resultCount: 5 }",
+            // Nothing selected
+            "{ resultIndex: -1, resultCount: 3 }",
+            "{ resultIndex: -1, resultCount: 0 }",
+            "{}",
+        ] {
+            handler.call1(&JsValue::NULL, &js(event)).unwrap();
+        }
+        assert_eq!(*results.borrow(), [(3, 5), (0, 3), (0, 0), (0, 0)]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_without_search_addon() {
+        // addon-search.min.js isn't loaded in the test page
+        assert!(XTermInstance::get_search_addon(&js("{}")).is_err());
+
+        let instance = fake_instance(false);
+        assert!(!instance.has_search());
+        instance.find("error", true);
+        instance.clear_search();
+        assert_eq!(get(&instance.terminal, "cleared"), 0);
+    }
+
+    /// Let Yew render and run effects
+    async fn settle() {
+        TimeoutFuture::new(0).await;
+    }
+
+    fn dispatch(element: &web_sys::Element, event: &str) {
+        let event: web_sys::Event = js(event).unchecked_into();
+        element.dispatch_event(&event).unwrap();
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_search_bar() {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let root = document.create_element("div").unwrap();
+        document.body().unwrap().append_child(&root).unwrap();
+
+        let searches = Rc::new(RefCell::new(Vec::new()));
+        let closed = Rc::new(Cell::new(false));
+        let props = SearchBarProps {
+            matches: (0, 0),
+            on_search: {
+                let searches = searches.clone();
+                Callback::from(move |search| searches.borrow_mut().push(search))
+            },
+            on_close: {
+                let closed = closed.clone();
+                Callback::from(move |_| closed.set(true))
+            },
+        };
+        let app = yew::Renderer::<SearchBar>::with_root_and_props(root.clone(), props).render();
+        settle().await;
+
+        let input: HtmlInputElement = root
+            .query_selector(".terminal-search-input")
+            .unwrap()
+            .unwrap()
+            .unchecked_into();
+        let count = root.query_selector(".terminal-search-count").unwrap().unwrap();
+        // The bar takes focus from the terminal
+        assert_eq!(document.active_element(), Some(input.clone().into()));
+        assert_eq!(count.text_content().unwrap(), "");
+
+        // Typing searches forwards
+        input.set_value("error");
+        dispatch(&input, "new InputEvent('input', { bubbles: true })");
+        settle().await;
+        assert_eq!(*searches.borrow(), [("error".to_string(), true)]);
+        assert_eq!(count.text_content().unwrap(), "无结果");
+
+        // Enter steps forwards, Shift+Enter backwards
+        dispatch(&input, "new KeyboardEvent('keydown', { key: 'Enter', bubbles: true })");
+        dispatch(
+            &input,
+            "new KeyboardEvent('keydown', { key: 'Enter', shiftKey: true, bubbles: true })",
+        );
+        assert_eq!(
+            searches.borrow()[1..],
+            [("error".to_string(), true), ("error".to_string(), false)]
+        );
+
+        assert!(!closed.get());
+        dispatch(&input, "new KeyboardEvent('keydown', { key: 'Escape', bubbles: true })");
+        assert!(closed.get());
+
+        app.destroy();
+        root.remove();
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_search_bar_match_count() {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let root = document.create_element("div").unwrap();
+        document.body().unwrap().append_child(&root).unwrap();
+
+        let props = SearchBarProps {
+            matches: (2, 7),
+            on_search: Callback::noop(),
+            on_close: Callback::noop(),
+        };
+        let app = yew::Renderer::<SearchBar>::with_root_and_props(root.clone(), props).render();
+        settle().await;
+
+        let input: HtmlInputElement = root
+            .query_selector(".terminal-search-input")
+            .unwrap()
+            .unwrap()
+            .unchecked_into();
+        input.set_value("warn");
+        dispatch(&input, "new InputEvent('input', { bubbles: true })");
+        settle().await;
+
+        let count = root.query_selector(".terminal-search-count").unwrap().unwrap();
+        assert_eq!(count.text_content().unwrap(), "2/7");
+        let buttons = root.query_selector_all(".terminal-search-btn").unwrap();
+        assert_eq!(buttons.length(), 3);
+        for i in 0..2 {
+            let button: web_sys::Element = buttons.get(i).unwrap().unchecked_into();
+            assert!(!button.has_attribute("disabled"));
+        }
+
+        app.destroy();
+        root.remove();
+    }
+}
@@ -9,8 +9,8 @@ mod components;
 mod pages;
 mod utils;
 
-use components::ProtectedRoute;
-use pages::{Dashboard, TerminalPage, LoginPage, SettingsPage};
+use components::{ProtectedRoute, ServerInfo, ServerInfoContext};
+use pages::{Dashboard, TerminalPage, LoginPage, ResetPasswordPage, SettingsPage};
 use utils::logger::init_console_capture;
 
 #[derive(Clone, Routable, PartialEq)]
@@ -19,6 +19,8 @@ enum Route {
     Home,
     #[at("/login")]
     Login,
+    #[at("/reset-password")]
+    ResetPassword,
     #[at("/dashboard")]
     Dashboard,
     #[at("/settings")]
@@ -36,6 +38,7 @@ fn switch(routes: Route) -> Html {
             </ProtectedRoute>
         },
         Route::Login => html! { <LoginPage /> },
+        Route::ResetPassword => html! { <ResetPasswordPage /> },
         Route::Dashboard => html! {
             <ProtectedRoute>
                 <Dashboard />
@@ -52,12 +55,14 @@ fn switch(routes: Route) -> Html {
 
 #[function_component(App)]
 fn app() -> Html {
+    let server_info = use_state(|| None::<ServerInfo>);
+
     html! {
-        <>
+        <ContextProvider<ServerInfoContext> context={server_info}>
             <BrowserRouter>
                 <Switch<Route> render={switch} />
             </BrowserRouter>
-        </>
+        </ContextProvider<ServerInfoContext>>
     }
 }
 
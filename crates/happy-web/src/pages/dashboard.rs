@@ -7,9 +7,23 @@ use serde_json::json;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{HtmlSelectElement, WebSocket, Window};
+use web_time::Instant;
 use yew::prelude::*;
 use yew_router::prelude::*;
 
+use crate::components::{ConnectionIndicator, ConnectionState};
+use crate::utils::sound::{self, Chime};
+
+pub mod scoring;
+
+use scoring::{health_class, health_score, LatencyWindow};
+
+/// Delay before reopening a dropped WebSocket
+const RECONNECT_DELAY_MS: u32 = 3_000;
+
+/// Radius of the health ring; its circumference is the dash length
+const HEALTH_RING_RADIUS: f64 = 16.0;
+
 /// Session status for dashboard
 #[derive(Clone, Debug, PartialEq)]
 pub struct SessionCard {
@@ -25,6 +39,12 @@ pub struct SessionCard {
     pub error_count: u64,
     pub bytes_in: u64,
     pub bytes_out: u64,
+    /// When the current output-rate window began, and `bytes_out` at that moment
+    pub output_window: (Instant, u64),
+    /// When the session started waiting for confirmation
+    pub confirmation_since: Option<Instant>,
+    /// Rolling average WebSocket round trip in milliseconds
+    pub latency_ms: Option<f64>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -41,26 +61,26 @@ pub enum SessionState {
 pub struct Dashboard {
     sessions: Vec<SessionCard>,
     ws: Option<WebSocket>,
-    ws_status: ConnectionStatus,
+    ws_status: ConnectionState,
     selected_sessions: Vec<String>,
     show_bulk_actions: bool,
     filter_text: String,
     sort_by: SortBy,
     heartbeat_interval: Option<Interval>,
+    /// Pending reconnect; replacing it cancels the previous one
+    reconnect_timeout: Option<Timeout>,
     auth_token: Option<String>,
     user_email: Option<String>,
-}
-
-#[derive(Clone, Copy, PartialEq)]
-pub enum ConnectionStatus {
-    Connecting,
-    Connected,
-    Disconnected,
-    Error,
+    /// Recent ping/pong round trips
+    latency: LatencyWindow,
+    /// When the outstanding ping went out
+    ping_sent_at: Option<Instant>,
 }
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum SortBy {
+    /// Worst health score first
+    Health,
     LastActivity,
     Status,
     Tag,
@@ -156,7 +176,7 @@ impl Dashboard {
             }
             Err(e) => {
                 log::error!("Failed to create WebSocket: {:?}", e);
-                self.ws_status = ConnectionStatus::Error;
+                self.ws_status = ConnectionState::Error("Connection failed".to_string());
             }
         }
     }
@@ -183,6 +203,16 @@ impl Dashboard {
         self.heartbeat_interval = None;
     }
 
+    fn schedule_reconnect(&mut self, ctx: &Context<Self>) {
+        self.ws_status = ConnectionState::Disconnected {
+            reconnect_at: js_sys::Date::now() + f64::from(RECONNECT_DELAY_MS),
+        };
+        let link = ctx.link().clone();
+        self.reconnect_timeout = Some(Timeout::new(RECONNECT_DELAY_MS, move || {
+            link.send_message(DashboardMsg::WsReconnect);
+        }));
+    }
+
     fn handle_ws_message(&mut self, text: String) {
         // Parse server message
         match serde_json::from_str::<serde_json::Value>(&text) {
@@ -197,9 +227,16 @@ impl Dashboard {
                         "sessions_list" => {
                             // Parse sessions from response
                             if let Some(sessions) = msg.get("sessions").and_then(|v| v.as_array()) {
+                                let now = Instant::now();
                                 let session_cards: Vec<SessionCard> = sessions
                                     .iter()
                                     .filter_map(|s| self.parse_session_card(s))
+                                    .map(|mut card| {
+                                        let previous =
+                                            self.sessions.iter().find(|s| s.id == card.id);
+                                        scoring::observe(previous, &mut card, now);
+                                        card
+                                    })
                                     .collect();
                                 self.sessions = session_cards;
                                 self.sort_sessions();
@@ -207,10 +244,16 @@ impl Dashboard {
                         }
                         "session_update" => {
                             // Update single session
-                            if let Some(session) = self.parse_session_card(&msg) {
-                                if let Some(idx) =
-                                    self.sessions.iter().position(|s| s.id == session.id)
-                                {
+                            if let Some(mut session) = self.parse_session_card(&msg) {
+                                let previous =
+                                    self.sessions.iter().position(|s| s.id == session.id);
+                                scoring::observe(
+                                    previous.map(|idx| &self.sessions[idx]),
+                                    &mut session,
+                                    Instant::now(),
+                                );
+                                if let Some(idx) = previous {
+                                    notify_transition(&self.sessions[idx], &session);
                                     self.sessions[idx] = session;
                                 } else {
                                     self.sessions.push(session);
@@ -220,8 +263,9 @@ impl Dashboard {
                         }
                         "session_created" | "session_started" => {
                             // Add new session
-                            if let Some(session) = self.parse_session_card(&msg) {
+                            if let Some(mut session) = self.parse_session_card(&msg) {
                                 if !self.sessions.iter().any(|s| s.id == session.id) {
+                                    scoring::observe(None, &mut session, Instant::now());
                                     self.sessions.push(session);
                                     self.sort_sessions();
                                 }
@@ -230,11 +274,35 @@ impl Dashboard {
                         "session_terminated" | "session_stopped" => {
                             // Remove terminated session
                             if let Some(id) = msg.get("session_id").and_then(|v| v.as_str()) {
+                                if self
+                                    .sessions
+                                    .iter()
+                                    .any(|s| s.id == id && s.status != SessionState::Exited)
+                                {
+                                    sound::notify(Chime::Terminated);
+                                }
                                 self.sessions.retain(|s| s.id != id);
                             }
                         }
                         "pong" => {
-                            // Heartbeat response received
+                            // Heartbeat response received; its round trip feeds health scores
+                            if let Some(sent_at) = self.ping_sent_at.take() {
+                                self.latency.push(sent_at.elapsed());
+                                let latency_ms = self.latency.average_ms();
+                                for session in &mut self.sessions {
+                                    session.latency_ms = latency_ms;
+                                }
+                            }
+                        }
+                        "account_deleted" => {
+                            log::warn!("Account deleted, logging out");
+                            let window = web_sys::window().unwrap();
+                            let storage = window.local_storage().unwrap().unwrap();
+                            let _ = storage.remove_item("happy_token");
+                            let _ = storage.remove_item("happy_refresh_token");
+                            let _ = storage.remove_item("happy_user_id");
+                            let _ = storage.remove_item("happy_user_email");
+                            let _ = window.location().set_href("/login");
                         }
                         "error" => {
                             if let Some(code) = msg.get("code").and_then(|v| v.as_str()) {
@@ -245,6 +313,7 @@ impl Dashboard {
                                     let window = web_sys::window().unwrap();
                                     let storage = window.local_storage().unwrap().unwrap();
                                     let _ = storage.remove_item("happy_token");
+                                    let _ = storage.remove_item("happy_refresh_token");
                                     let _ = storage.remove_item("happy_user_id");
                                     let _ = storage.remove_item("happy_user_email");
                                     let _ = window.location().set_href("/login");
@@ -303,6 +372,12 @@ impl Dashboard {
                 .unwrap_or(0),
             bytes_in: value.get("bytes_in").and_then(|v| v.as_u64()).unwrap_or(0),
             bytes_out: value.get("bytes_out").and_then(|v| v.as_u64()).unwrap_or(0),
+            output_window: (
+                Instant::now(),
+                value.get("bytes_out").and_then(|v| v.as_u64()).unwrap_or(0),
+            ),
+            confirmation_since: None,
+            latency_ms: self.latency.average_ms(),
         })
     }
 
@@ -333,21 +408,24 @@ impl Component for Dashboard {
         let mut dashboard = Self {
             sessions: Vec::new(),
             ws: None,
-            ws_status: ConnectionStatus::Connecting,
+            ws_status: ConnectionState::Connecting,
             selected_sessions: Vec::new(),
             show_bulk_actions: false,
             filter_text: String::new(),
-            sort_by: SortBy::LastActivity,
+            sort_by: SortBy::Health,
             heartbeat_interval: None,
+            reconnect_timeout: None,
             auth_token: auth_token.clone(),
             user_email,
+            latency: LatencyWindow::default(),
+            ping_sent_at: None,
         };
 
         // Only connect WebSocket if authenticated
         if auth_token.is_some() {
             dashboard.connect_websocket(ctx);
         } else {
-            dashboard.ws_status = ConnectionStatus::Disconnected;
+            dashboard.ws_status = ConnectionState::Error("Not logged in".to_string());
         }
 
         dashboard
@@ -356,7 +434,7 @@ impl Component for Dashboard {
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             DashboardMsg::WsConnected => {
-                self.ws_status = ConnectionStatus::Connected;
+                self.ws_status = ConnectionState::Connected;
                 // Send authentication with actual token
                 if let Some(token) = &self.auth_token {
                     let auth_msg = json!({
@@ -373,31 +451,22 @@ impl Component for Dashboard {
                 true
             }
             DashboardMsg::WsDisconnected => {
-                self.ws_status = ConnectionStatus::Disconnected;
                 self.stop_heartbeat();
-                // Attempt to reconnect after 3 seconds
-                let link = ctx.link().clone();
-                Timeout::new(3_000, move || {
-                    link.send_message(DashboardMsg::WsReconnect);
-                })
-                .forget();
+                self.schedule_reconnect(ctx);
                 true
             }
             DashboardMsg::WsError(err) => {
                 log::error!("WebSocket error: {}", err);
-                self.ws_status = ConnectionStatus::Error;
+                self.ws_status = ConnectionState::Error(err);
                 self.stop_heartbeat();
-                // Attempt reconnect
+                // The close event that follows schedules the reconnect
                 self.ws = None;
-                let link = ctx.link().clone();
-                Timeout::new(3_000, move || {
-                    link.send_message(DashboardMsg::WsReconnect);
-                })
-                .forget();
                 true
             }
             DashboardMsg::WsReconnect => {
                 log::info!("Attempting to reconnect WebSocket...");
+                self.ws_status = ConnectionState::Reconnecting;
+                self.reconnect_timeout = None;
                 self.ws = None;
                 self.connect_websocket(ctx);
                 true
@@ -407,16 +476,28 @@ impl Component for Dashboard {
                 true
             }
             DashboardMsg::SendPing => {
+                self.ping_sent_at = Some(Instant::now());
                 self.send_message(r#"{"type": "ping"}"#);
                 false
             }
-            DashboardMsg::SessionsUpdate(sessions) => {
+            DashboardMsg::SessionsUpdate(mut sessions) => {
+                let now = Instant::now();
+                for session in &mut sessions {
+                    let previous = self.sessions.iter().find(|s| s.id == session.id);
+                    scoring::observe(previous, session, now);
+                }
                 self.sessions = sessions;
                 self.sort_sessions();
                 true
             }
-            DashboardMsg::SessionUpdate(session) => {
-                if let Some(idx) = self.sessions.iter().position(|s| s.id == session.id) {
+            DashboardMsg::SessionUpdate(mut session) => {
+                let previous = self.sessions.iter().position(|s| s.id == session.id);
+                scoring::observe(
+                    previous.map(|idx| &self.sessions[idx]),
+                    &mut session,
+                    Instant::now(),
+                );
+                if let Some(idx) = previous {
                     self.sessions[idx] = session;
                 } else {
                     self.sessions.push(session);
@@ -509,6 +590,7 @@ impl Component for Dashboard {
                                                 let window = web_sys::window().unwrap();
                                                 let storage = window.local_storage().unwrap().unwrap();
                                                 let _ = storage.remove_item("happy_token");
+                                                let _ = storage.remove_item("happy_refresh_token");
                                                 let _ = storage.remove_item("happy_user_id");
                                                 let _ = storage.remove_item("happy_user_email");
                                                 let _ = window.location().set_href("/login");
@@ -533,7 +615,7 @@ impl Component for Dashboard {
                             }
                         }
                         <div class="connection-status">
-                            { self.view_connection_status() }
+                            <ConnectionIndicator state={self.ws_status.clone()} />
                         </div>
                     </div>
                 </header>
@@ -603,9 +685,13 @@ impl Component for Dashboard {
                                 "status" => DashboardMsg::SortChanged(SortBy::Status),
                                 "tag" => DashboardMsg::SortChanged(SortBy::Tag),
                                 "progress" => DashboardMsg::SortChanged(SortBy::Progress),
-                                _ => DashboardMsg::SortChanged(SortBy::LastActivity),
+                                "activity" => DashboardMsg::SortChanged(SortBy::LastActivity),
+                                _ => DashboardMsg::SortChanged(SortBy::Health),
                             }
                         })}>
+                            <option value="health" selected={self.sort_by == SortBy::Health}>
+                                { "Sort: Health" }
+                            </option>
                             <option value="activity" selected={self.sort_by == SortBy::LastActivity}>
                                 { "Sort: Last Activity" }
                             </option>
@@ -687,23 +773,6 @@ impl Component for Dashboard {
 }
 
 impl Dashboard {
-    fn view_connection_status(&self) -> Html {
-        match self.ws_status {
-            ConnectionStatus::Connected => {
-                html! { <span class="status connected">{ "● Connected" }</span> }
-            }
-            ConnectionStatus::Connecting => {
-                html! { <span class="status connecting">{ "○ Connecting..." }</span> }
-            }
-            ConnectionStatus::Disconnected => {
-                html! { <span class="status disconnected">{ "○ Disconnected" }</span> }
-            }
-            ConnectionStatus::Error => {
-                html! { <span class="status error">{ "● Error" }</span> }
-            }
-        }
-    }
-
     fn filtered_sessions(&self) -> Vec<&SessionCard> {
         self.sessions
             .iter()
@@ -726,6 +795,10 @@ impl Dashboard {
 
     fn sort_sessions(&mut self) {
         match self.sort_by {
+            SortBy::Health => {
+                let now = Instant::now();
+                self.sessions.sort_by_key(|s| health_score(s, now));
+            }
             SortBy::LastActivity => {
                 // Already sorted by last activity by default
             }
@@ -785,6 +858,7 @@ impl Dashboard {
                     <span class={classes!("status-badge", status_class)}>
                         { self.format_status(&session.status) }
                     </span>
+                    { render_health_ring(health_score(session, Instant::now())) }
                 </div>
 
                 <div class="card-body">
@@ -855,3 +929,46 @@ impl Dashboard {
         }
     }
 }
+
+/// Circular indicator whose filled arc matches `score`
+fn render_health_ring(score: u8) -> Html {
+    let size = HEALTH_RING_RADIUS * 2.0 + 4.0;
+    let circumference = 2.0 * std::f64::consts::PI * HEALTH_RING_RADIUS;
+    let offset = circumference * (1.0 - f64::from(score) / 100.0);
+    html! {
+        <div
+            class={classes!("health-ring", health_class(score))}
+            title={format!("Health {}/100", score)}
+        >
+            <svg width={size.to_string()} height={size.to_string()}>
+                <circle
+                    class="health-ring-track"
+                    cx={(size / 2.0).to_string()}
+                    cy={(size / 2.0).to_string()}
+                    r={HEALTH_RING_RADIUS.to_string()}
+                />
+                <circle
+                    class="health-ring-value"
+                    cx={(size / 2.0).to_string()}
+                    cy={(size / 2.0).to_string()}
+                    r={HEALTH_RING_RADIUS.to_string()}
+                    stroke-dasharray={format!("{:.2}", circumference)}
+                    stroke-dashoffset={format!("{:.2}", offset)}
+                    transform={format!("rotate(-90 {0} {0})", size / 2.0)}
+                />
+            </svg>
+            <span class="health-ring-label">{ score }</span>
+        </div>
+    }
+}
+
+/// Chime when a session starts waiting on the user or ends
+fn notify_transition(previous: &SessionCard, next: &SessionCard) {
+    let waiting =
+        |card: &SessionCard| card.needs_confirmation || card.status == SessionState::WaitingForConfirm;
+    if waiting(next) && !waiting(previous) {
+        sound::notify(Chime::Attention);
+    } else if next.status == SessionState::Exited && previous.status != SessionState::Exited {
+        sound::notify(Chime::Terminated);
+    }
+}
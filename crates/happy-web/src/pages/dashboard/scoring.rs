@@ -0,0 +1,227 @@
+//! Session health scores for the dashboard
+//!
+//! A score starts at 100 and loses points for errors, a running session that
+//! has stopped producing output, a confirmation left waiting and a slow
+//! WebSocket connection.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use web_time::Instant;
+
+use super::{SessionCard, SessionState};
+
+/// Points lost per reported error
+const ERROR_PENALTY: u32 = 10;
+/// Errors alone never take more than this
+const MAX_ERROR_PENALTY: u32 = 50;
+/// Output measured over less time than this says nothing about a session
+const MIN_RATE_WINDOW: Duration = Duration::from_secs(30);
+/// How long an output window lasts before it starts over
+const RATE_WINDOW: Duration = Duration::from_secs(120);
+/// Running sessions writing less than this (bytes/s) look stuck
+const STUCK_BYTES_PER_SEC: f64 = 1.0;
+const STUCK_PENALTY: u32 = 20;
+/// A confirmation waiting longer than this is probably forgotten
+const CONFIRMATION_GRACE: Duration = Duration::from_secs(30);
+const CONFIRMATION_PENALTY: u32 = 30;
+/// WebSocket round trips this slow (ms) cost `SLOW_PENALTY`, or `VERY_SLOW_PENALTY` past the second
+const SLOW_LATENCY_MS: f64 = 300.0;
+const VERY_SLOW_LATENCY_MS: f64 = 1000.0;
+const SLOW_PENALTY: u32 = 10;
+const VERY_SLOW_PENALTY: u32 = 20;
+/// Round trips kept for the rolling latency average
+const LATENCY_SAMPLES: usize = 10;
+
+/// Health of `card` at `now`, from 0 (broken) to 100 (fine)
+pub fn health_score(card: &SessionCard, now: Instant) -> u8 {
+    let mut penalty = (card.error_count.min(u64::from(u32::MAX)) as u32)
+        .saturating_mul(ERROR_PENALTY)
+        .min(MAX_ERROR_PENALTY);
+
+    let (window_start, bytes_at_start) = card.output_window;
+    let elapsed = now.saturating_duration_since(window_start);
+    if card.status == SessionState::Running && elapsed >= MIN_RATE_WINDOW {
+        let rate = card.bytes_out.saturating_sub(bytes_at_start) as f64 / elapsed.as_secs_f64();
+        if rate < STUCK_BYTES_PER_SEC {
+            penalty += STUCK_PENALTY;
+        }
+    }
+
+    if card
+        .confirmation_since
+        .is_some_and(|since| now.saturating_duration_since(since) > CONFIRMATION_GRACE)
+    {
+        penalty += CONFIRMATION_PENALTY;
+    }
+
+    match card.latency_ms {
+        Some(ms) if ms >= VERY_SLOW_LATENCY_MS => penalty += VERY_SLOW_PENALTY,
+        Some(ms) if ms >= SLOW_LATENCY_MS => penalty += SLOW_PENALTY,
+        _ => {}
+    }
+
+    100u32.saturating_sub(penalty) as u8
+}
+
+/// CSS class for a score: green, yellow, orange or red
+pub fn health_class(score: u8) -> &'static str {
+    match score {
+        80..=100 => "health-good",
+        50..=79 => "health-fair",
+        20..=49 => "health-poor",
+        _ => "health-critical",
+    }
+}
+
+/// Carry the timing state the server doesn't send over from the card `next` replaces
+pub fn observe(previous: Option<&SessionCard>, next: &mut SessionCard, now: Instant) {
+    let waiting = |card: &SessionCard| {
+        card.needs_confirmation || card.status == SessionState::WaitingForConfirm
+    };
+
+    next.confirmation_since = if waiting(next) {
+        previous.and_then(|p| p.confirmation_since).or(Some(now))
+    } else {
+        None
+    };
+
+    next.output_window = match previous {
+        Some(p) if now.saturating_duration_since(p.output_window.0) < RATE_WINDOW => p.output_window,
+        _ => (now, next.bytes_out),
+    };
+}
+
+/// Rolling average of the last few WebSocket round trips
+#[derive(Default)]
+pub struct LatencyWindow {
+    samples: VecDeque<f64>,
+}
+
+impl LatencyWindow {
+    pub fn push(&mut self, rtt: Duration) {
+        if self.samples.len() == LATENCY_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt.as_secs_f64() * 1000.0);
+    }
+
+    /// Average round trip in milliseconds, once there is one
+    pub fn average_ms(&self) -> Option<f64> {
+        (!self.samples.is_empty())
+            .then(|| self.samples.iter().sum::<f64>() / self.samples.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A running session that just started producing output at `start`
+    fn card(start: Instant) -> SessionCard {
+        SessionCard {
+            id: "s1".to_string(),
+            tag: "main".to_string(),
+            status: SessionState::Running,
+            progress: None,
+            operation: None,
+            needs_confirmation: false,
+            confirmation_prompt: None,
+            client_count: 1,
+            last_activity: String::new(),
+            error_count: 0,
+            bytes_in: 0,
+            bytes_out: 0,
+            output_window: (start, 0),
+            confirmation_since: None,
+            latency_ms: None,
+        }
+    }
+
+    #[test]
+    fn test_error_penalty() {
+        let start = Instant::now();
+        let mut card = card(start);
+        assert_eq!(health_score(&card, start), 100);
+
+        card.error_count = 2;
+        assert_eq!(health_score(&card, start), 80);
+        // Capped, however many errors there are
+        card.error_count = 5;
+        assert_eq!(health_score(&card, start), 50);
+        card.error_count = u64::MAX;
+        assert_eq!(health_score(&card, start), 50);
+    }
+
+    #[test]
+    fn test_stuck_penalty() {
+        let start = Instant::now();
+        let mut card = card(start);
+
+        // Too early to tell
+        assert_eq!(health_score(&card, start + Duration::from_secs(29)), 100);
+        // No output for 30s
+        let now = start + MIN_RATE_WINDOW;
+        assert_eq!(health_score(&card, now), 80);
+        // Output since the window began
+        card.bytes_out = 30;
+        assert_eq!(health_score(&card, now), 100);
+        card.bytes_out = 29;
+        assert_eq!(health_score(&card, now), 80);
+
+        // Only running sessions can be stuck
+        card.bytes_out = 0;
+        card.status = SessionState::Idle;
+        assert_eq!(health_score(&card, now), 100);
+    }
+
+    #[test]
+    fn test_confirmation_penalty() {
+        let start = Instant::now();
+        let mut card = card(start);
+        card.status = SessionState::WaitingForConfirm;
+        card.confirmation_since = Some(start);
+
+        assert_eq!(health_score(&card, start + CONFIRMATION_GRACE), 100);
+        assert_eq!(health_score(&card, start + Duration::from_secs(31)), 70);
+    }
+
+    #[test]
+    fn test_latency_penalty() {
+        let start = Instant::now();
+        let mut card = card(start);
+
+        for (ms, score) in [(299.9, 100), (300.0, 90), (999.9, 90), (1000.0, 80)] {
+            card.latency_ms = Some(ms);
+            assert_eq!(health_score(&card, start), score, "{}ms", ms);
+        }
+    }
+
+    #[test]
+    fn test_penalties_add_up() {
+        let start = Instant::now();
+        let mut card = card(start);
+        card.error_count = 5;
+        card.confirmation_since = Some(start);
+        card.latency_ms = Some(1000.0);
+
+        // 50 + 20 + 30 + 20
+        assert_eq!(health_score(&card, start + Duration::from_secs(60)), 0);
+    }
+
+    #[test]
+    fn test_health_class() {
+        for (score, class) in [
+            (100, "health-good"),
+            (80, "health-good"),
+            (79, "health-fair"),
+            (50, "health-fair"),
+            (49, "health-poor"),
+            (20, "health-poor"),
+            (19, "health-critical"),
+            (0, "health-critical"),
+        ] {
+            assert_eq!(health_class(score), class, "{}", score);
+        }
+    }
+}
@@ -13,10 +13,22 @@ pub enum LoginMsg {
     NameChanged(String),
     Submit,
     ToggleMode,
-    LoginSuccess { token: String, user: UserInfo },
+    LoginSuccess {
+        token: String,
+        refresh_token: Option<String>,
+        user: UserInfo,
+    },
+    ProvidersLoaded(Vec<SsoProvider>),
     Error(String),
 }
 
+/// External sign-in provider advertised by the server
+#[derive(Clone)]
+pub struct SsoProvider {
+    pub id: String,
+    pub name: String,
+}
+
 #[derive(Clone)]
 pub struct UserInfo {
     pub id: String,
@@ -32,15 +44,27 @@ pub struct LoginPage {
     loading: bool,
     error: Option<String>,
     redirect_url: Option<String>,
+    /// Why the user landed here, e.g. after their session expired
+    notice: Option<&'static str>,
+    sso_providers: Vec<SsoProvider>,
 }
 
 impl Component for LoginPage {
     type Message = LoginMsg;
     type Properties = ();
 
-    fn create(_ctx: &Context<Self>) -> Self {
+    fn create(ctx: &Context<Self>) -> Self {
+        let search = web_sys::window()
+            .and_then(|w| w.location().search().ok())
+            .unwrap_or_default();
+        let notice = search
+            .trim_start_matches('?')
+            .split('&')
+            .any(|param| param == "reason=expired")
+            .then_some("Your session has expired. Please sign in again.");
+
         // Parse redirect URL from query parameters
-        let redirect_url = web_sys::window()
+        let mut redirect_url = web_sys::window()
             .and_then(|w| w.location().search().ok())
             .and_then(|search| {
                 // Parse ?redirect=xxx
@@ -60,14 +84,50 @@ impl Component for LoginPage {
                     })
             });
 
+        // Returning from an SSO provider: the server passes the result in the fragment
+        let mut error = None;
+        let fragment = parse_fragment();
+        if let Some(message) = fragment.get("error") {
+            error = Some(message.clone());
+        } else if let (Some(token), Some(id), Some(email)) = (
+            fragment.get("access_token"),
+            fragment.get("user_id"),
+            fragment.get("email"),
+        ) {
+            if let Some(redirect) = fragment.get("redirect") {
+                redirect_url = Some(redirect.clone());
+            }
+            ctx.link().send_message(LoginMsg::LoginSuccess {
+                token: token.clone(),
+                refresh_token: fragment.get("refresh_token").cloned(),
+                user: UserInfo {
+                    id: id.clone(),
+                    email: email.clone(),
+                    name: None,
+                },
+            });
+        }
+
+        ctx.link().send_future(async {
+            match fetch_providers().await {
+                Ok(providers) => LoginMsg::ProvidersLoaded(providers),
+                Err(e) => {
+                    log::warn!("Failed to load sign-in providers: {}", e);
+                    LoginMsg::ProvidersLoaded(Vec::new())
+                }
+            }
+        });
+
         Self {
             email: String::new(),
             password: String::new(),
             name: String::new(),
             is_register: false,
             loading: false,
-            error: None,
+            error,
             redirect_url,
+            notice,
+            sso_providers: Vec::new(),
         }
     }
 
@@ -101,20 +161,36 @@ impl Component for LoginPage {
 
                 ctx.link().send_future(async move {
                     match do_auth(&email, &password, &name, is_register).await {
-                        Ok((token, user)) => LoginMsg::LoginSuccess { token, user },
+                        Ok((token, refresh_token, user)) => LoginMsg::LoginSuccess {
+                            token,
+                            refresh_token,
+                            user,
+                        },
                         Err(e) => LoginMsg::Error(e),
                     }
                 });
 
                 true
             }
-            LoginMsg::LoginSuccess { token, user } => {
+            LoginMsg::LoginSuccess {
+                token,
+                refresh_token,
+                user,
+            } => {
                 self.loading = false;
 
                 // Store token in localStorage
                 let window = web_sys::window().unwrap();
                 let storage = window.local_storage().unwrap().unwrap();
                 let _ = storage.set_item("happy_token", &token);
+                match refresh_token {
+                    Some(refresh_token) => {
+                        let _ = storage.set_item("happy_refresh_token", &refresh_token);
+                    }
+                    None => {
+                        let _ = storage.remove_item("happy_refresh_token");
+                    }
+                }
                 let _ = storage.set_item("happy_user_id", &user.id);
                 let _ = storage.set_item("happy_user_email", &user.email);
 
@@ -127,6 +203,10 @@ impl Component for LoginPage {
 
                 true
             }
+            LoginMsg::ProvidersLoaded(providers) => {
+                self.sso_providers = providers;
+                true
+            }
             LoginMsg::Error(e) => {
                 self.loading = false;
                 self.error = Some(e);
@@ -186,6 +266,8 @@ impl Component for LoginPage {
 
                     if let Some(ref error) = self.error {
                         <div class="error-message">{ error }</div>
+                    } else if let Some(notice) = self.notice {
+                        <div class="notice-message">{ notice }</div>
                     }
 
                     <form onsubmit={on_submit}>
@@ -236,10 +318,35 @@ impl Component for LoginPage {
                         </button>
                     </form>
 
+                    if !self.sso_providers.is_empty() {
+                        <div class="sso-divider"><span>{ "or" }</span></div>
+                        <div class="sso-buttons">
+                            { for self.sso_providers.iter().map(|p| {
+                                let mut href = format!("/api/v1/auth/oidc/{}/authorize", p.id);
+                                if let Some(ref redirect) = self.redirect_url {
+                                    href.push_str(&format!(
+                                        "?redirect={}",
+                                        String::from(js_sys::encode_uri_component(redirect))
+                                    ));
+                                }
+                                html! {
+                                    <a class={classes!("btn-sso", format!("btn-sso-{}", p.id))} href={href}>
+                                        { format!("Sign in with {}", p.name) }
+                                    </a>
+                                }
+                            }) }
+                        </div>
+                    }
+
                     <div class="login-footer">
                         <button class="btn-link" onclick={on_toggle}>
                             { toggle_text }
                         </button>
+                        if !self.is_register {
+                            <a class="btn-link" href="/reset-password">
+                                { "Forgot your password?" }
+                            </a>
+                        }
                     </div>
                 </div>
             </div>
@@ -252,7 +359,7 @@ async fn do_auth(
     password: &str,
     name: &str,
     is_register: bool,
-) -> Result<(String, UserInfo), String> {
+) -> Result<(String, Option<String>, UserInfo), String> {
     let window = web_sys::window().unwrap();
     let location = window.location();
     let protocol = location.protocol().unwrap();
@@ -306,6 +413,23 @@ async fn do_auth(
 
     let status = xhr.status().map_err(|e| format!("Status error: {:?}", e))?;
 
+    if status == 423 {
+        let text = xhr.response_text().ok().flatten().unwrap_or_default();
+        let unlock_at = serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|json| json["unlock_at"].as_str().map(|s| s.to_string()))
+            .map(|at| {
+                js_sys::Date::new(&wasm_bindgen::JsValue::from_str(&at))
+                    .to_locale_time_string("default")
+                    .as_string()
+                    .unwrap_or(at)
+            });
+        return Err(match unlock_at {
+            Some(at) => format!("Too many failed attempts. Try again after {}.", at),
+            None => "Too many failed attempts. Try again later.".to_string(),
+        });
+    }
+
     if status != 200 {
         let text = xhr
             .response_text()
@@ -327,6 +451,7 @@ async fn do_auth(
         .as_str()
         .ok_or("No access token in response")?
         .to_string();
+    let refresh_token = json["refresh_token"].as_str().map(|s| s.to_string());
 
     let user = UserInfo {
         id: json["user"]["id"]
@@ -340,5 +465,79 @@ async fn do_auth(
         name: json["user"]["name"].as_str().map(|s| s.to_string()),
     };
 
-    Ok((token, user))
+    Ok((token, refresh_token, user))
+}
+
+/// Parse `key=value` pairs from the location hash
+fn parse_fragment() -> std::collections::HashMap<String, String> {
+    let hash = web_sys::window()
+        .and_then(|w| w.location().hash().ok())
+        .unwrap_or_default();
+
+    hash.trim_start_matches('#')
+        .split('&')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            // Server encodes with form encoding, so spaces arrive as '+'
+            let value = js_sys::decode_uri_component(&value.replace('+', " ")).ok()?;
+            Some((key.to_string(), String::from(value)))
+        })
+        .collect()
+}
+
+/// Fetch the SSO providers enabled on the server
+async fn fetch_providers() -> Result<Vec<SsoProvider>, String> {
+    let request = XmlHttpRequest::new().map_err(|e| format!("XHR error: {:?}", e))?;
+
+    request
+        .open("GET", "/api/v1/auth/providers")
+        .map_err(|e| format!("Open error: {:?}", e))?;
+
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let mut sender = Some(sender);
+
+    let onload = Closure::once_into_js(move |e: ProgressEvent| {
+        let xhr: XmlHttpRequest = e.target().unwrap().dyn_into().unwrap();
+        let sender = sender.take().unwrap();
+        let _ = sender.send(xhr);
+    });
+
+    request.set_onload(Some(onload.as_ref().unchecked_ref()));
+
+    request
+        .send()
+        .map_err(|e| format!("Send error: {:?}", e))?;
+
+    let xhr: XmlHttpRequest = receiver
+        .await
+        .map_err(|e| format!("Response error: {:?}", e))?;
+
+    let status = xhr.status().map_err(|e| format!("Status error: {:?}", e))?;
+    if status != 200 {
+        return Err(format!("Unexpected status {}", status));
+    }
+
+    let response_text = xhr
+        .response_text()
+        .map_err(|e| format!("Text error: {:?}", e))?
+        .unwrap_or_default();
+
+    let json: serde_json::Value =
+        serde_json::from_str(&response_text).map_err(|e| format!("JSON parse error: {}", e))?;
+
+    Ok(json["providers"]
+        .as_array()
+        .map(|providers| {
+            providers
+                .iter()
+                .filter(|p| p["enabled"].as_bool() == Some(true))
+                .filter_map(|p| {
+                    Some(SsoProvider {
+                        id: p["id"].as_str()?.to_string(),
+                        name: p["name"].as_str()?.to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default())
 }
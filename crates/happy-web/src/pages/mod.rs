@@ -2,10 +2,12 @@
 
 pub mod dashboard;
 pub mod login;
+pub mod reset_password;
 pub mod settings;
 pub mod terminal;
 
 pub use dashboard::Dashboard;
 pub use login::LoginPage;
+pub use reset_password::ResetPasswordPage;
 pub use settings::SettingsPage;
 pub use terminal::TerminalPage;
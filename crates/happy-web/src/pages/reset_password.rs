@@ -0,0 +1,274 @@
+//! Password Reset Page
+//!
+//! Without a token, asks for an email and has the server send a reset link.
+//! The link comes back here with `?token=...` to choose a new password.
+
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlInputElement, ProgressEvent, XmlHttpRequest};
+use yew::prelude::*;
+
+/// Keep in sync with the server's `MIN_PASSWORD_LEN`
+const MIN_PASSWORD_LEN: usize = 6;
+
+pub enum ResetPasswordMsg {
+    EmailChanged(String),
+    PasswordChanged(String),
+    ConfirmChanged(String),
+    Submit,
+    Done,
+    Error(String),
+}
+
+pub struct ResetPasswordPage {
+    /// Token from the emailed link; `None` while requesting a link
+    token: Option<String>,
+    email: String,
+    password: String,
+    confirm: String,
+    loading: bool,
+    done: bool,
+    error: Option<String>,
+}
+
+impl Component for ResetPasswordPage {
+    type Message = ResetPasswordMsg;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        let search = web_sys::window()
+            .and_then(|w| w.location().search().ok())
+            .unwrap_or_default();
+        let token = search
+            .trim_start_matches('?')
+            .split('&')
+            .find_map(|param| param.strip_prefix("token="))
+            .filter(|token| !token.is_empty())
+            .and_then(|token| js_sys::decode_uri_component(token).ok())
+            .map(String::from);
+
+        Self {
+            token,
+            email: String::new(),
+            password: String::new(),
+            confirm: String::new(),
+            loading: false,
+            done: false,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            ResetPasswordMsg::EmailChanged(email) => {
+                self.email = email;
+                true
+            }
+            ResetPasswordMsg::PasswordChanged(password) => {
+                self.password = password;
+                true
+            }
+            ResetPasswordMsg::ConfirmChanged(confirm) => {
+                self.confirm = confirm;
+                true
+            }
+            ResetPasswordMsg::Submit => {
+                self.error = None;
+
+                let request = match self.token {
+                    Some(ref token) => {
+                        if self.password.len() < MIN_PASSWORD_LEN {
+                            self.error = Some(format!(
+                                "Password must be at least {} characters.",
+                                MIN_PASSWORD_LEN
+                            ));
+                            return true;
+                        }
+                        if self.password != self.confirm {
+                            self.error = Some("Passwords do not match.".to_string());
+                            return true;
+                        }
+                        let body = serde_json::json!({
+                            "token": token,
+                            "new_password": self.password,
+                        });
+                        ("/api/v1/auth/reset-password", body)
+                    }
+                    None => (
+                        "/api/v1/auth/forgot-password",
+                        serde_json::json!({ "email": self.email }),
+                    ),
+                };
+
+                self.loading = true;
+                let has_token = self.token.is_some();
+                ctx.link().send_future(async move {
+                    let (url, body) = request;
+                    match post_json(url, &body.to_string()).await {
+                        Ok(202 | 204) => ResetPasswordMsg::Done,
+                        Ok(400) if has_token => ResetPasswordMsg::Error(
+                            "This reset link is invalid or has expired. Request a new one."
+                                .to_string(),
+                        ),
+                        Ok(503) => ResetPasswordMsg::Error(
+                            "Password reset by email is not available on this server."
+                                .to_string(),
+                        ),
+                        Ok(status) => ResetPasswordMsg::Error(format!(
+                            "Request failed with status {}",
+                            status
+                        )),
+                        Err(e) => ResetPasswordMsg::Error(e),
+                    }
+                });
+
+                true
+            }
+            ResetPasswordMsg::Done => {
+                self.loading = false;
+                self.done = true;
+                true
+            }
+            ResetPasswordMsg::Error(e) => {
+                self.loading = false;
+                self.error = Some(e);
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let on_submit = ctx.link().callback(|e: SubmitEvent| {
+            e.prevent_default();
+            ResetPasswordMsg::Submit
+        });
+
+        let form = if self.done {
+            let text = if self.token.is_some() {
+                "Your password has been changed. Sign in with your new password."
+            } else {
+                "If an account exists for that email, a reset link is on its way."
+            };
+            html! { <div class="notice-message">{ text }</div> }
+        } else if self.token.is_some() {
+            let on_password_change = ctx.link().callback(|e: Event| {
+                let input: HtmlInputElement = e.target_unchecked_into();
+                ResetPasswordMsg::PasswordChanged(input.value())
+            });
+            let on_confirm_change = ctx.link().callback(|e: Event| {
+                let input: HtmlInputElement = e.target_unchecked_into();
+                ResetPasswordMsg::ConfirmChanged(input.value())
+            });
+
+            html! {
+                <form onsubmit={on_submit}>
+                    <div class="form-group">
+                        <label>{ "New password" }</label>
+                        <input
+                            type="password"
+                            placeholder="Enter password (min 6 chars)"
+                            value={self.password.clone()}
+                            onchange={on_password_change}
+                            disabled={self.loading}
+                            required={true}
+                            minlength={"6"}
+                        />
+                    </div>
+
+                    <div class="form-group">
+                        <label>{ "Confirm password" }</label>
+                        <input
+                            type="password"
+                            placeholder="Repeat the new password"
+                            value={self.confirm.clone()}
+                            onchange={on_confirm_change}
+                            disabled={self.loading}
+                            required={true}
+                        />
+                    </div>
+
+                    <button type="submit" class="btn-primary" disabled={self.loading}>
+                        { if self.loading { "Please wait..." } else { "Set Password" } }
+                    </button>
+                </form>
+            }
+        } else {
+            let on_email_change = ctx.link().callback(|e: Event| {
+                let input: HtmlInputElement = e.target_unchecked_into();
+                ResetPasswordMsg::EmailChanged(input.value())
+            });
+
+            html! {
+                <form onsubmit={on_submit}>
+                    <div class="form-group">
+                        <label>{ "Email" }</label>
+                        <input
+                            type="email"
+                            placeholder="email@example.com"
+                            value={self.email.clone()}
+                            onchange={on_email_change}
+                            disabled={self.loading}
+                            required={true}
+                        />
+                    </div>
+
+                    <button type="submit" class="btn-primary" disabled={self.loading}>
+                        { if self.loading { "Please wait..." } else { "Send Reset Link" } }
+                    </button>
+                </form>
+            }
+        };
+
+        html! {
+            <div class="login-container">
+                <div class="login-box">
+                    <h1>{ "Happy Remote" }</h1>
+                    <h2>{ "Reset Password" }</h2>
+
+                    if let Some(ref error) = self.error {
+                        <div class="error-message">{ error }</div>
+                    }
+
+                    { form }
+
+                    <div class="login-footer">
+                        <a class="btn-link" href="/login">{ "Back to Sign In" }</a>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+}
+
+/// POST a JSON body and return the response status
+async fn post_json(url: &str, body: &str) -> Result<u16, String> {
+    let request = XmlHttpRequest::new().map_err(|e| format!("XHR error: {:?}", e))?;
+
+    request
+        .open("POST", url)
+        .map_err(|e| format!("Open error: {:?}", e))?;
+    request
+        .set_request_header("Content-Type", "application/json")
+        .map_err(|e| format!("Header error: {:?}", e))?;
+
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let mut sender = Some(sender);
+
+    let onload = Closure::once_into_js(move |e: ProgressEvent| {
+        let xhr: XmlHttpRequest = e.target().unwrap().dyn_into().unwrap();
+        let sender = sender.take().unwrap();
+        let _ = sender.send(xhr);
+    });
+
+    request.set_onload(Some(onload.as_ref().unchecked_ref()));
+
+    request
+        .send_with_opt_str(Some(body))
+        .map_err(|e| format!("Send error: {:?}", e))?;
+
+    let xhr: XmlHttpRequest = receiver
+        .await
+        .map_err(|e| format!("Response error: {:?}", e))?;
+
+    xhr.status().map_err(|e| format!("Status error: {:?}", e))
+}
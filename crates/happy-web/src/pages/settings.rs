@@ -1,15 +1,41 @@
 //! Settings page
 
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlInputElement, MessageEvent, ProgressEvent, WebSocket, XmlHttpRequest};
 use yew::prelude::*;
 use yew_router::prelude::*;
 
-use crate::components::use_auth;
+use crate::components::{use_auth, use_server_info};
+use crate::utils::sound;
 use crate::Route;
 
+#[derive(Clone, Copy, PartialEq)]
+enum SettingsTab {
+    General,
+    Daemon,
+}
+
 #[function_component(SettingsPage)]
 pub fn settings_page() -> Html {
     let auth = use_auth();
     let navigator = use_navigator().unwrap();
+    let tab = use_state(|| SettingsTab::General);
+    let notification_sound = use_state(sound::preference);
+
+    let on_sound_toggle = {
+        let notification_sound = notification_sound.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let enabled = input.checked();
+            sound::set_preference(enabled);
+            notification_sound.set(enabled);
+            // The click is the user gesture browsers need before audio may play
+            if enabled {
+                sound::play(sound::Chime::Attention);
+            }
+        })
+    };
 
     let on_logout = {
         let navigator = navigator.clone();
@@ -18,6 +44,7 @@ pub fn settings_page() -> Html {
             if let Some(window) = web_sys::window() {
                 if let Ok(Some(storage)) = window.local_storage() {
                     let _ = storage.remove_item("happy_token");
+                    let _ = storage.remove_item("happy_refresh_token");
                     let _ = storage.remove_item("happy_user_id");
                     let _ = storage.remove_item("happy_user_email");
                 }
@@ -36,75 +63,359 @@ pub fn settings_page() -> Html {
                 </button>
             </header>
 
-            <main class="settings-content">
-                // Account Section
-                <section class="settings-section">
-                    <h2>{ "Account" }</h2>
-                    <div class="account-info">
-                        <div class="info-row">
-                            <label>{ "User ID" }</label>
-                            <span class="info-value">{ auth.user_id.as_deref().unwrap_or("Unknown") }</span>
+            <nav class="settings-tabs">
+                { for [(SettingsTab::General, "General"), (SettingsTab::Daemon, "Daemon")].into_iter().map(|(t, label)| {
+                    let tab = tab.clone();
+                    let class = if *tab == t { "settings-tab active" } else { "settings-tab" };
+                    html! {
+                        <button {class} onclick={Callback::from(move |_| tab.set(t))}>{ label }</button>
+                    }
+                }) }
+            </nav>
+
+            if *tab == SettingsTab::Daemon {
+                <main class="settings-content">
+                    <DaemonPanel />
+                </main>
+            } else {
+                <main class="settings-content">
+                    // Account Section
+                    <section class="settings-section">
+                        <h2>{ "Account" }</h2>
+                        <div class="account-info">
+                            <div class="info-row">
+                                <label>{ "User ID" }</label>
+                                <span class="info-value">{ auth.user_id.as_deref().unwrap_or("Unknown") }</span>
+                            </div>
+                            <div class="info-row">
+                                <label>{ "Email" }</label>
+                                <span class="info-value">{ auth.user_email.as_deref().unwrap_or("Unknown") }</span>
+                            </div>
+                        </div>
+                        <div class="settings-actions">
+                            <button class="btn-danger" onclick={on_logout}>
+                                { "Logout" }
+                            </button>
+                        </div>
+                    </section>
+
+                    // Notifications Section
+                    <section class="settings-section">
+                        <h2>{ "Notifications" }</h2>
+                        <label class="setting-item">
+                            <input type="checkbox" />
+                            <span>{ "Enable push notifications" }</span>
+                        </label>
+                        <label class="setting-item">
+                            <input type="checkbox" checked={true} />
+                            <span>{ "Notify when session needs confirmation" }</span>
+                        </label>
+                        <label class="setting-item">
+                            <input type="checkbox" checked={true} />
+                            <span>{ "Notify on session errors" }</span>
+                        </label>
+                        <label class="setting-item">
+                            <input
+                                type="checkbox"
+                                checked={*notification_sound}
+                                onchange={on_sound_toggle}
+                            />
+                            <span>{ "Play a sound when a session needs attention or ends" }</span>
+                        </label>
+                        if *notification_sound && sound::prefers_reduced_motion() {
+                            <p class="setting-hint">{ "Muted while your system asks for reduced motion" }</p>
+                        }
+                    </section>
+
+                    // Appearance Section
+                    <section class="settings-section">
+                        <h2>{ "Appearance" }</h2>
+                        <div class="setting-item">
+                            <label>{ "Theme:" }</label>
+                            <select class="theme-select">
+                                <option value="dark">{ "Dark" }</option>
+                                <option value="light">{ "Light" }</option>
+                                <option value="system">{ "System" }</option>
+                            </select>
+                        </div>
+                        <div class="setting-item">
+                            <label>{ "Terminal Font Size:" }</label>
+                            <input type="range" min="10" max="24" value="14" class="font-slider" />
                         </div>
-                        <div class="info-row">
-                            <label>{ "Email" }</label>
-                            <span class="info-value">{ auth.user_email.as_deref().unwrap_or("Unknown") }</span>
+                    </section>
+
+                    // About Section
+                    <section class="settings-section">
+                        <h2>{ "About" }</h2>
+                        <div class="about-info">
+                            <p>{ "Happy Remote v0.1.0" }</p>
+                            <p>{ "A Rust-native remote Claude Code control system" }</p>
+                            <p>
+                                <a href="https://github.com/yourusername/happy-remote" target="_blank">
+                                    { "View on GitHub" }
+                                </a>
+                            </p>
                         </div>
-                    </div>
-                    <div class="settings-actions">
-                        <button class="btn-danger" onclick={on_logout}>
-                            { "Logout" }
-                        </button>
-                    </div>
-                </section>
-
-                // Notifications Section
-                <section class="settings-section">
-                    <h2>{ "Notifications" }</h2>
-                    <label class="setting-item">
-                        <input type="checkbox" />
-                        <span>{ "Enable push notifications" }</span>
-                    </label>
-                    <label class="setting-item">
-                        <input type="checkbox" checked={true} />
-                        <span>{ "Notify when session needs confirmation" }</span>
-                    </label>
-                    <label class="setting-item">
-                        <input type="checkbox" checked={true} />
-                        <span>{ "Notify on session errors" }</span>
-                    </label>
-                </section>
-
-                // Appearance Section
-                <section class="settings-section">
-                    <h2>{ "Appearance" }</h2>
-                    <div class="setting-item">
-                        <label>{ "Theme:" }</label>
-                        <select class="theme-select">
-                            <option value="dark">{ "Dark" }</option>
-                            <option value="light">{ "Light" }</option>
-                            <option value="system">{ "System" }</option>
-                        </select>
-                    </div>
-                    <div class="setting-item">
-                        <label>{ "Terminal Font Size:" }</label>
-                        <input type="range" min="10" max="24" value="14" class="font-slider" />
-                    </div>
-                </section>
-
-                // About Section
-                <section class="settings-section">
-                    <h2>{ "About" }</h2>
-                    <div class="about-info">
-                        <p>{ "Happy Remote v0.1.0" }</p>
-                        <p>{ "A Rust-native remote Claude Code control system" }</p>
-                        <p>
-                            <a href="https://github.com/yourusername/happy-remote" target="_blank">
-                                { "View on GitHub" }
-                            </a>
-                        </p>
-                    </div>
-                </section>
-            </main>
+                    </section>
+                </main>
+            }
         </div>
     }
 }
+
+/// Daemon status, connection settings and a round-trip test
+#[function_component(DaemonPanel)]
+fn daemon_panel() -> Html {
+    let server_info = use_server_info();
+    // None while loading; Err when /health could not be reached
+    let daemon_running = use_state(|| None::<Result<bool, String>>);
+    let server_url = use_state(|| {
+        web_sys::window()
+            .and_then(|w| w.location().origin().ok())
+            .unwrap_or_default()
+    });
+    let save_result = use_state(|| None::<Result<String, String>>);
+    let ping_result = use_state(|| None::<Result<f64, String>>);
+    let testing = use_state(|| false);
+
+    let daemon_port = web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item("happy_daemon_port").ok().flatten());
+
+    {
+        let daemon_running = daemon_running.clone();
+        use_effect_with((), move |_| {
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = send_request("GET", "/health", None)
+                    .await
+                    .and_then(|(status, text)| {
+                        if status != 200 {
+                            return Err(format!("Unexpected status {}", status));
+                        }
+                        let json: serde_json::Value = serde_json::from_str(&text)
+                            .map_err(|e| format!("JSON parse error: {}", e))?;
+                        Ok(json["connections"]["cli_bridges"].as_u64().unwrap_or(0) > 0)
+                    });
+                daemon_running.set(Some(result));
+            });
+            || ()
+        });
+    }
+
+    let on_url_input = {
+        let server_url = server_url.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            server_url.set(input.value());
+        })
+    };
+
+    let on_save_url = {
+        let server_url = server_url.clone();
+        let save_result = save_result.clone();
+        Callback::from(move |_| {
+            let url = (*server_url).clone();
+            let save_result = save_result.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let body = serde_json::json!({ "server_url": url }).to_string();
+                let result = send_request("PATCH", "/api/v1/config", Some(&body))
+                    .await
+                    .and_then(|(status, text)| match status {
+                        200 => {
+                            let json: serde_json::Value = serde_json::from_str(&text)
+                                .map_err(|e| format!("JSON parse error: {}", e))?;
+                            Ok(format!(
+                                "Saved; {} daemon(s) updated. Restart them to reconnect.",
+                                json["daemons_updated"].as_u64().unwrap_or(0)
+                            ))
+                        }
+                        400 => Err("URL must start with http:// or https://".to_string()),
+                        401 => Err("Session expired, please log in again".to_string()),
+                        _ => Err(format!("Unexpected status {}", status)),
+                    });
+                save_result.set(Some(result));
+            });
+        })
+    };
+
+    let on_test = {
+        let ping_result = ping_result.clone();
+        let testing = testing.clone();
+        Callback::from(move |_| {
+            testing.set(true);
+            ping_result.set(None);
+            test_connection(ping_result.clone(), testing.clone());
+        })
+    };
+
+    let (status_class, status_text) = match &*daemon_running {
+        None => ("daemon-status", "Checking...".to_string()),
+        Some(Ok(true)) => ("daemon-status running", "Running".to_string()),
+        Some(Ok(false)) => ("daemon-status stopped", "Stopped".to_string()),
+        Some(Err(e)) => ("daemon-status stopped", format!("Unknown ({})", e)),
+    };
+    let info = server_info.as_ref().and_then(|info| (**info).clone());
+
+    html! {
+        <section class="settings-section">
+            <h2>{ "Daemon" }</h2>
+            <div class="account-info">
+                <div class="info-row">
+                    <label>{ "Status" }</label>
+                    <span class={status_class}>{ status_text }</span>
+                </div>
+                <div class="info-row">
+                    <label>{ "Daemon Version" }</label>
+                    <span class="info-value">
+                        { info.as_ref().and_then(|i| i.daemon_version.clone()).unwrap_or_else(|| "Unknown".to_string()) }
+                    </span>
+                </div>
+                <div class="info-row">
+                    <label>{ "Server Version" }</label>
+                    <span class="info-value">
+                        { info.as_ref().map(|i| i.server_version.clone()).unwrap_or_else(|| "Unknown".to_string()) }
+                    </span>
+                </div>
+                <div class="info-row">
+                    <label>{ "WebSocket Port" }</label>
+                    <span class="info-value">{ daemon_port.unwrap_or_else(|| "Not set".to_string()) }</span>
+                </div>
+            </div>
+
+            <div class="setting-item">
+                <label>{ "Server URL:" }</label>
+                <input
+                    type="url"
+                    class="server-url-input"
+                    value={(*server_url).clone()}
+                    oninput={on_url_input}
+                />
+                <button class="btn-primary" onclick={on_save_url}>{ "Save" }</button>
+            </div>
+            {
+                match &*save_result {
+                    Some(Ok(msg)) => html! { <p class="settings-result success">{ msg }</p> },
+                    Some(Err(e)) => html! { <p class="settings-result error">{ e }</p> },
+                    None => html! {},
+                }
+            }
+
+            <div class="settings-actions">
+                <button onclick={on_test} disabled={*testing}>
+                    { if *testing { "Testing..." } else { "Test Connection" } }
+                </button>
+                {
+                    match &*ping_result {
+                        Some(Ok(rtt)) => html! { <span class="settings-result success">{ format!("Round trip: {:.0} ms", rtt) }</span> },
+                        Some(Err(e)) => html! { <span class="settings-result error">{ e }</span> },
+                        None => html! {},
+                    }
+                }
+            </div>
+        </section>
+    }
+}
+
+/// Open a fresh WebSocket, send `ping` and time the `pong`
+fn test_connection(result: UseStateHandle<Option<Result<f64, String>>>, testing: UseStateHandle<bool>) {
+    let window = web_sys::window().unwrap();
+    let location = window.location();
+    let protocol = if location.protocol().unwrap_or_default() == "https:" {
+        "wss"
+    } else {
+        "ws"
+    };
+    let ws_url = format!("{}://{}/ws", protocol, location.host().unwrap_or_default());
+
+    let ws = match WebSocket::new(&ws_url) {
+        Ok(ws) => ws,
+        Err(e) => {
+            result.set(Some(Err(format!("WebSocket error: {:?}", e))));
+            testing.set(false);
+            return;
+        }
+    };
+
+    let started = std::rc::Rc::new(std::cell::Cell::new(0.0));
+
+    let ws_for_open = ws.clone();
+    let started_for_open = started.clone();
+    let onopen = Closure::once_into_js(move || {
+        started_for_open.set(js_sys::Date::now());
+        let _ = ws_for_open.send_with_str(r#"{"type":"ping"}"#);
+    });
+    ws.set_onopen(Some(onopen.unchecked_ref()));
+
+    let ws_for_msg = ws.clone();
+    let result_for_msg = result.clone();
+    let testing_for_msg = testing.clone();
+    let onmessage = Closure::once_into_js(move |e: MessageEvent| {
+        let is_pong = e
+            .data()
+            .as_string()
+            .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+            .is_some_and(|json| json["type"] == "pong");
+        result_for_msg.set(Some(if is_pong {
+            Ok(js_sys::Date::now() - started.get())
+        } else {
+            Err("Unexpected reply from server".to_string())
+        }));
+        testing_for_msg.set(false);
+        let _ = ws_for_msg.close();
+    });
+    ws.set_onmessage(Some(onmessage.unchecked_ref()));
+
+    let onerror = Closure::once_into_js(move || {
+        result.set(Some(Err("Could not connect to server".to_string())));
+        testing.set(false);
+    });
+    ws.set_onerror(Some(onerror.unchecked_ref()));
+}
+
+/// Send an authenticated request to the server, returning the status and body
+async fn send_request(method: &str, url: &str, body: Option<&str>) -> Result<(u16, String), String> {
+    let token = web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item("happy_token").ok().flatten());
+
+    let request = XmlHttpRequest::new().map_err(|e| format!("XHR error: {:?}", e))?;
+
+    request
+        .open(method, url)
+        .map_err(|e| format!("Open error: {:?}", e))?;
+    request
+        .set_request_header("Content-Type", "application/json")
+        .map_err(|e| format!("Header error: {:?}", e))?;
+    if let Some(token) = token {
+        request
+            .set_request_header("Authorization", &format!("Bearer {}", token))
+            .map_err(|e| format!("Header error: {:?}", e))?;
+    }
+
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let mut sender = Some(sender);
+
+    let onload = Closure::once_into_js(move |e: ProgressEvent| {
+        let xhr: XmlHttpRequest = e.target().unwrap().dyn_into().unwrap();
+        let sender = sender.take().unwrap();
+        let _ = sender.send(xhr);
+    });
+
+    request.set_onload(Some(onload.as_ref().unchecked_ref()));
+
+    request
+        .send_with_opt_str(body)
+        .map_err(|e| format!("Send error: {:?}", e))?;
+
+    let xhr: XmlHttpRequest = receiver
+        .await
+        .map_err(|e| format!("Response error: {:?}", e))?;
+
+    let status = xhr.status().map_err(|e| format!("Status error: {:?}", e))?;
+    let text = xhr
+        .response_text()
+        .map_err(|e| format!("Text error: {:?}", e))?
+        .unwrap_or_default();
+
+    Ok((status, text))
+}
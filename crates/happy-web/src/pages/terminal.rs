@@ -7,33 +7,80 @@
 //! Features:
 //! - Sessions grouped by machine, then by folder
 //! - Right-click context menu for delete with confirmation
+//! - Warns before leaving the page while the selected session is running
 //! - "+" button to create new remote session
 
-use gloo_timers::callback::Interval;
+use gloo_timers::callback::{Interval, Timeout};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsCast;
 use web_sys::{
-    Event, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement, InputEvent, MessageEvent, MouseEvent, SubmitEvent,
+    BeforeUnloadEvent, Event, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement, InputEvent, MessageEvent, MouseEvent, SubmitEvent,
     WebSocket,
 };
 use yew::prelude::*;
+use yew_router::prelude::*;
 
-use crate::components::{XTerm, LogViewer};
+use crate::components::{
+    scroll_session_into_view, use_keyboard_shortcuts, use_server_info, LogViewer, RecordingPlayer,
+    ServerInfo, Shortcut, XTerm,
+};
+use crate::utils::asciicast::{self, Recording};
+use crate::utils::clipboard;
+use crate::utils::collapsed_groups;
+use crate::utils::highlight::{highlight_match, matches_query};
+use crate::utils::offline_cache;
+use crate::utils::sound;
+use crate::Route;
+
+const LEAVE_ACTIVE_SESSION_WARNING: &str = "You have an active session. Are you sure?";
+
+/// How much older output each "Load more history" click asks for
+const HISTORY_PAGE_BYTES: u64 = 64 * 1024;
+/// Per-session client buffer cap; sized to hold the server's full 512 KiB history
+const TERMINAL_BUFFER_MAX: usize = 640 * 1024;
+const TERMINAL_BUFFER_KEEP: usize = 576 * 1024;
+
+/// Sessions fetched per `list_sessions` request
+const SESSIONS_PAGE_SIZE: u32 = 50;
+/// Fetch the next page once the session list is scrolled this close to its end
+const LOAD_MORE_THRESHOLD_PX: i32 = 200;
+
+/// `list_sessions` request for the first page, or for the page after `cursor`
+fn list_sessions_msg(cursor: Option<&str>) -> String {
+    json!({ "type": "list_sessions", "limit": SESSIONS_PAGE_SIZE, "cursor": cursor }).to_string()
+}
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct SessionSummary {
     pub id: String,
     pub tag: String,
     pub status: String,
     pub cwd: String,
+    /// Directories visited, oldest first
+    #[serde(default)]
+    pub cwd_history: Vec<String>,
     pub machine_id: String,
     pub machine_name: String,
+    #[serde(skip)]
     pub is_online: bool,
+    pub agent_version: Option<String>,
+    pub home_dir: Option<String>,
 }
 
 impl SessionSummary {
+    /// Machine group this session is listed under in the sidebar
+    pub fn machine_group(&self) -> &str {
+        if self.machine_name.is_empty() {
+            "unknown"
+        } else {
+            &self.machine_name
+        }
+    }
+
     /// Get the last folder name from cwd for display
     pub fn folder_name(&self) -> &str {
         if self.cwd.is_empty() || self.cwd == "/" {
@@ -47,10 +94,131 @@ impl SessionSummary {
     }
 }
 
+/// Entry in the sidebar's archive section
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct ArchivedSession {
+    pub id: String,
+    pub tag: String,
+    #[serde(default)]
+    pub machine_name: String,
+}
+
+/// Chevron that folds a sidebar group away or brings it back
+fn group_toggle(collapsed: bool, on_toggle: Callback<MouseEvent>) -> Html {
+    html! {
+        <button
+            class="group-toggle"
+            title={if collapsed { "展开" } else { "折叠" }}
+            aria-expanded={(!collapsed).to_string()}
+            onclick={on_toggle}
+        >
+            { if collapsed { "▸" } else { "▾" } }
+        </button>
+    }
+}
+
+/// `(label, path)` for each level of `cwd`, starting at `~` when it is under `home`
+fn cwd_breadcrumbs(cwd: &str, home: Option<&str>) -> Vec<(String, String)> {
+    let home = home.map(|h| h.trim_end_matches('/')).filter(|h| !h.is_empty());
+    let (mut crumbs, mut path, rest) = match home {
+        Some(home) if cwd == home || cwd.starts_with(&format!("{}/", home)) => (
+            vec![("~".to_string(), home.to_string())],
+            home.to_string(),
+            &cwd[home.len()..],
+        ),
+        _ => (vec![("/".to_string(), "/".to_string())], String::new(), cwd),
+    };
+    for segment in rest.split('/').filter(|s| !s.is_empty()) {
+        path = format!("{}/{}", path, segment);
+        crumbs.push((segment.to_string(), path.clone()));
+    }
+    crumbs
+}
+
+/// Directories listed in the breadcrumb's history menu
+const RECENT_DIRS_SHOWN: usize = 5;
+
+/// The most recently visited directories other than `cwd`, newest first and without repeats
+fn recent_dirs(history: &[String], cwd: &str) -> Vec<String> {
+    let mut seen = HashSet::from([cwd]);
+    history
+        .iter()
+        .rev()
+        .filter(|dir| seen.insert(dir.as_str()))
+        .take(RECENT_DIRS_SHOWN)
+        .cloned()
+        .collect()
+}
+
+/// `cd` into `path`, single-quoted so spaces and shell characters survive
+fn cd_command(path: &str) -> Vec<u8> {
+    format!("cd '{}'\r", path.replace('\'', "'\\''")).into_bytes()
+}
+
+/// Badge text for cached data last refreshed at `updated_at` (`Date.now()` ms)
+fn offline_label(updated_at: f64) -> String {
+    let minutes = ((js_sys::Date::now() - updated_at) / 60_000.0).max(0.0) as u64;
+    if minutes == 0 {
+        "离线 · 刚刚更新".to_string()
+    } else {
+        format!("离线 · {} 分钟前更新", minutes)
+    }
+}
+
+/// Replace the list with a first page, or append a later page without duplicates
+fn merge_session_page(sessions: &mut Vec<SessionSummary>, page: Vec<SessionSummary>, append: bool) {
+    if append {
+        let known: HashSet<String> = sessions.iter().map(|s| s.id.clone()).collect();
+        sessions.extend(page.into_iter().filter(|s| !known.contains(&s.id)));
+    } else {
+        *sessions = page;
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub struct MachineInfo {
     pub id: String,
     pub name: String,
+    pub platform: Option<String>,
+    pub arch: Option<String>,
+    pub daemon_version: Option<String>,
+    /// Capability names as reported by the daemon, e.g. `"terminal"`
+    pub capabilities: Vec<String>,
+}
+
+impl MachineInfo {
+    /// Icons for the built-in capabilities; custom ones only show in `capability_names`
+    pub fn capability_icons(&self) -> String {
+        self.capabilities
+            .iter()
+            .filter_map(|c| match c.as_str() {
+                "terminal" => Some("🖥️"),
+                "file_system" => Some("📁"),
+                "notifications" => Some("🔔"),
+                "voice" => Some("🎙️"),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn capability_names(&self) -> String {
+        self.capabilities.join(", ")
+    }
+
+    /// Hover text for the machine group header, e.g. "linux.x86_64 · daemon v0.1.0"
+    pub fn tooltip(&self) -> String {
+        let mut parts = Vec::new();
+        match (&self.platform, &self.arch) {
+            (Some(platform), Some(arch)) => parts.push(format!("{}.{}", platform, arch)),
+            (Some(platform), None) => parts.push(platform.clone()),
+            (None, Some(arch)) => parts.push(arch.clone()),
+            (None, None) => {}
+        }
+        if let Some(version) = &self.daemon_version {
+            parts.push(format!("daemon v{}", version));
+        }
+        parts.join(" · ")
+    }
 }
 
 /// Mobile view state for responsive UI
@@ -93,27 +261,66 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         }
     });
 
-    // Delete confirmation state
+    // Permanent delete confirmation state, for archived sessions
     let delete_confirm = use_state(|| None::<(String, String)>); // (session_id, tag)
 
+    // Archived sessions, listed folded at the bottom of the sidebar
+    let archived_sessions = use_state(Vec::<ArchivedSession>::new);
+    let show_archived = use_state(|| false);
+
+    // Route the user tried to open while a session was running
+    let pending_route = use_state(|| None::<Route>);
+    let navigator = use_navigator().unwrap();
+    let server_info = use_server_info();
+
     // Create session modal state
     let show_create_modal = use_state(|| false);
     let create_cwd = use_state(|| String::new());
     let create_machine = use_state(|| String::new());
     let create_args = use_state(|| String::new());
 
+    // Transfer modal state: the session being moved, and the machine picked for it
+    let transfer_session = use_state(|| None::<String>);
+    let transfer_machine = use_state(String::new);
+
+    // Sidebar filter; sessions whose tag or folder doesn't contain it are hidden
+    let filter_text = use_state(String::new);
+
+    // Sidebar groups folded away; changes re-render through `sessions_version`
+    let collapsed = use_mut_ref(collapsed_groups::load);
+
     // Context menu state
     let context_menu = use_state(|| None::<(i32, i32, String, String)>); // (x, y, session_id, tag)
 
     // Use Rc<RefCell<>> for terminal buffers
     let terminal_buffers = use_mut_ref(|| HashMap::<String, String>::new());
     let buffer_version = use_state(|| 0u32);
+    // Oldest loaded stream offset, for sessions with older history still on the server
+    let history_offsets = use_mut_ref(HashMap::<String, u64>::new);
 
     // Track loading state - true until we receive first sessions_list
     let sessions_loaded = use_state(|| false);
+    // When the server last sent the session list, and when the data on screen was
+    // current while it is unreachable
+    let last_synced = use_mut_ref(|| None::<f64>);
+    let stale_since = use_state(|| None::<f64>);
+    {
+        // Tick once a minute so the offline badge's age stays current
+        let force_update = use_force_update();
+        use_effect_with(stale_since.is_some(), move |stale| {
+            let ticker = stale.then(|| Interval::new(60_000, move || force_update.force_update()));
+            move || drop(ticker)
+        });
+    }
+    // `next_cursor` of the last page, and whether a later page is in flight
+    let sessions_cursor = use_mut_ref(|| None::<String>);
+    let loading_more_sessions = use_mut_ref(|| false);
 
     // Virtual keyboard key sender callback
     let key_sender = use_state(|| None::<Callback<String>>);
+    // Confirmation shown after the Paste button sends clipboard text
+    let paste_toast = use_state(|| None::<String>);
+    let paste_toast_timeout = use_mut_ref(|| None::<Timeout>);
 
     // Terminal direct writer callback - for incremental updates without re-rendering
     // Use use_mut_ref to allow updates from WebSocket callbacks
@@ -122,6 +329,8 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
     // Terminal scroll-to-bottom callback
     let scroll_to_bottom = use_state(|| None::<Callback<()>>);
     let show_scroll_to_bottom = use_state(|| false);
+    // `(current, total)` from the terminal's search bar
+    let search_matches = use_state(|| (0usize, 0usize));
 
     // Git status state
     let git_status = use_state(|| None::<GitStatusInfo>);
@@ -132,10 +341,21 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
     let commit_message = use_state(|| String::new());
     let show_commit_modal = use_state(|| false);
     let is_amend = use_state(|| false);
+    // Outcome of the last push or pull: (succeeded, text to show)
+    let git_sync_result = use_state(|| None::<(bool, String)>);
 
     // Log viewer state
     let log_viewer_open = use_state(|| false);
 
+    // Sessions being recorded on their machine, with the cast file being written
+    let recording_paths = use_mut_ref(HashMap::<String, String>::new);
+    // Outcome of the last recording start or stop: (session_id, succeeded, text)
+    let recording_notice = use_state(|| None::<(String, bool, String)>);
+
+    // Imported recording being replayed locally, with its file name
+    let recording = use_state(|| None::<(String, Rc<Recording>)>);
+    let recording_input_ref = use_node_ref();
+
     // Git status info structure
     #[derive(Clone, PartialEq)]
     struct GitStatusInfo {
@@ -152,14 +372,31 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
     struct ModifiedFile {
         path: String,
         change_type: String,
+        /// Original path of a renamed or copied file
+        rename_from: Option<String>,
         additions: u32,
         deletions: u32,
     }
 
+    // File name in the git panel, as "old → new" for renames
+    fn render_file_name(file: &ModifiedFile) -> Html {
+        match &file.rename_from {
+            Some(from) => html! {
+                <span class="file-name" title={format!("{} → {}", from, file.path)}>
+                    { from }
+                    <span class="rename-arrow">{ " → " }</span>
+                    { &file.path }
+                </span>
+            },
+            None => html! { <span class="file-name">{ &file.path }</span> },
+        }
+    }
+
     // Diff line types for syntax highlighting
     #[derive(Clone, PartialEq)]
     enum DiffLine {
         Header(String),       // diff --git, index, ---, +++ lines
+        RenameHeader(String), // similarity index, rename/copy from/to
         ChunkHeader(String),  // @@ -x,x +x,x @@
         Context(String),      // Context lines (space prefix)
         Addition(String),     // Added lines (+ prefix)
@@ -175,6 +412,11 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                 DiffLine::Header(line.to_string())
             } else if line.starts_with("index ") || line.starts_with("--- ") || line.starts_with("+++ ") {
                 DiffLine::Header(line.to_string())
+            } else if ["similarity index ", "rename from ", "rename to ", "copy from ", "copy to "]
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+            {
+                DiffLine::RenameHeader(line.to_string())
             } else if line.starts_with("@@") && line.contains("@@") {
                 DiffLine::ChunkHeader(line.to_string())
             } else if line.starts_with('+') {
@@ -204,6 +446,9 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                         DiffLine::Header(text) => html! {
                             <div class="diff-line header">{ text }</div>
                         },
+                        DiffLine::RenameHeader(text) => html! {
+                            <div class="diff-line rename-header">{ text }</div>
+                        },
                         DiffLine::ChunkHeader(text) => html! {
                             <div class="diff-line chunk-header">{ text }</div>
                         },
@@ -292,14 +537,66 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
     // Clone sessions_version for use outside the effect closure
     let sessions_version_clone = sessions_version.clone();
 
+    // Whether leaving now would walk away from a running session; read by the unload listener
+    let leave_guard = use_mut_ref(|| false);
+    *leave_guard.borrow_mut() = (*selected_session_id)
+        .as_ref()
+        .map(|id| {
+            sessions
+                .borrow()
+                .iter()
+                .any(|s| &s.id == id && s.status == "running")
+        })
+        .unwrap_or(false);
+
+    {
+        let leave_guard = leave_guard.clone();
+        use_effect_with((), move |_| {
+            let window = web_sys::window().unwrap();
+            let on_before_unload = Closure::wrap(Box::new(move |e: BeforeUnloadEvent| {
+                if *leave_guard.borrow() {
+                    e.prevent_default();
+                    e.set_return_value(LEAVE_ACTIVE_SESSION_WARNING);
+                }
+            }) as Box<dyn FnMut(BeforeUnloadEvent)>);
+            let _ = window.add_event_listener_with_callback(
+                "beforeunload",
+                on_before_unload.as_ref().unchecked_ref(),
+            );
+
+            move || {
+                let _ = window.remove_event_listener_with_callback(
+                    "beforeunload",
+                    on_before_unload.as_ref().unchecked_ref(),
+                );
+            }
+        });
+    }
+
+    // In-app navigation asks first when a session is running
+    let on_navigate = {
+        let leave_guard = leave_guard.clone();
+        let pending_route = pending_route.clone();
+        let navigator = navigator.clone();
+        Callback::from(move |route: Route| {
+            if *leave_guard.borrow() {
+                pending_route.set(Some(route));
+            } else {
+                navigator.push(&route);
+            }
+        })
+    };
+
     // WebSocket setup
     {
         let ws_status = ws_status.clone();
         let sessions = sessions.clone();
         let machines = machines.clone();
+        let collapsed = collapsed.clone();
         let selected_session_id = selected_session_id.clone();
         // selected_session_id_ref is unused in the effect, so we don't clone it
         let terminal_buffers = terminal_buffers.clone();
+        let history_offsets = history_offsets.clone();
         let buffer_version = buffer_version.clone();
         let ws_ref = ws_ref.clone();
         let joined_tags_ref = joined_tags_ref.clone();
@@ -311,7 +608,16 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         let file_diff_for_effect = file_diff.clone();
         let show_commit_modal_for_effect = show_commit_modal.clone();
         let commit_message_for_effect = commit_message.clone();
+        let git_sync_result_for_effect = git_sync_result.clone();
+        let recording_paths_for_effect = recording_paths.clone();
+        let recording_notice_for_effect = recording_notice.clone();
         let ws_ref_for_effect = ws_ref.clone();
+        let server_info_for_effect = server_info.clone();
+        let sessions_cursor_for_effect = sessions_cursor.clone();
+        let loading_more_for_effect = loading_more_sessions.clone();
+        let archived_sessions_for_effect = archived_sessions.clone();
+        let last_synced = last_synced.clone();
+        let stale_since = stale_since.clone();
 
         use_effect_with((), move |_| {
             let window = web_sys::window().unwrap();
@@ -330,6 +636,45 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
             }
             let auth_token = auth_token.unwrap_or_default();
 
+            // Show the last-known state until the server answers
+            {
+                let sessions = sessions.clone();
+                let terminal_buffers = terminal_buffers.clone();
+                let buffer_version = buffer_version.clone();
+                let sessions_version = sessions_version_for_effect.clone();
+                let sessions_loaded = sessions_loaded_for_effect.clone();
+                let selected_session_id = selected_session_id.clone();
+                let target_tag = target_tag.clone();
+                let last_synced = last_synced.clone();
+                let stale_since = stale_since.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let snapshot =
+                        offline_cache::load(|s: &SessionSummary| s.id.clone()).await;
+                    let Some(snapshot) = snapshot else {
+                        return;
+                    };
+                    if last_synced.borrow().is_some() || snapshot.sessions.is_empty() {
+                        return;
+                    }
+                    log::info!("Showing {} cached sessions", snapshot.sessions.len());
+
+                    let selected = target_tag
+                        .as_ref()
+                        .and_then(|tag| snapshot.sessions.iter().find(|s| &s.tag == tag))
+                        .or(snapshot.sessions.first())
+                        .map(|s| s.id.clone());
+                    if (*selected_session_id).is_none() {
+                        selected_session_id.set(selected);
+                    }
+                    *sessions.borrow_mut() = snapshot.sessions;
+                    terminal_buffers.borrow_mut().extend(snapshot.buffers);
+                    sessions_version.set(*sessions_version + 1);
+                    buffer_version.set(*buffer_version + 1);
+                    sessions_loaded.set(true);
+                    stale_since.set(Some(snapshot.updated_at));
+                });
+            }
+
             let location = window.location();
             let protocol = if location.protocol().unwrap() == "https:" {
                 "wss"
@@ -347,10 +692,16 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
 
             let onopen = Closure::wrap(Box::new(move || {
                 ws_status_clone.set("Authenticating...".to_string());
-                let auth_msg = json!({ "type": "authenticate", "token": auth_token }).to_string();
+                let auth_msg = json!({
+                    "type": "authenticate",
+                    "token": auth_token,
+                    "binary_frames": true,
+                })
+                .to_string();
                 let _ = ws_clone.send_with_str(&auth_msg);
-                let list_msg = json!({ "type": "list_sessions" }).to_string();
-                let _ = ws_clone.send_with_str(&list_msg);
+                let _ = ws_clone.send_with_str(&list_sessions_msg(None));
+                let archived_msg = json!({ "type": "list_archived_sessions" }).to_string();
+                let _ = ws_clone.send_with_str(&archived_msg);
                 // Also request machine list
                 let machines_msg = json!({ "type": "list_machines" }).to_string();
                 let _ = ws_clone.send_with_str(&machines_msg);
@@ -359,19 +710,26 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
             onopen.forget();
 
             let ws_status_for_close = ws_status.clone();
+            let last_synced_for_close = last_synced.clone();
+            let stale_since_for_close = stale_since.clone();
             let onclose = Closure::wrap(Box::new(move || {
                 ws_status_for_close.set("Disconnected".to_string());
+                if let Some(synced) = *last_synced_for_close.borrow() {
+                    stale_since_for_close.set(Some(synced));
+                }
             }) as Box<dyn FnMut()>);
             ws.set_onclose(Some(onclose.as_ref().unchecked_ref()));
             onclose.forget();
 
             let ws_status_for_msg = ws_status.clone();
             let sessions_for_msg = sessions.clone();
+            let collapsed_for_msg = collapsed.clone();
             let sessions_loaded_for_msg = sessions_loaded_for_effect.clone();
             let machines_for_msg = machines.clone();
             let selected_session_id_for_msg = selected_session_id.clone();
             let terminal_buffers_for_msg = terminal_buffers.clone();
             let buffer_version_for_msg = buffer_version.clone();
+            let history_offsets_for_msg = history_offsets.clone();
             let ws_for_msg = ws.clone();
             let joined_tags_ref_for_msg = joined_tags_ref.clone();
             let target_tag_for_msg = target_tag.clone();
@@ -380,12 +738,126 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
             let file_diff_for_msg = file_diff_for_effect.clone();
             let show_commit_modal_for_msg = show_commit_modal_for_effect.clone();
             let commit_message_for_msg = commit_message_for_effect.clone();
+            let git_sync_result_for_msg = git_sync_result_for_effect.clone();
+            let recording_paths_for_msg = recording_paths_for_effect.clone();
+            let recording_notice_for_msg = recording_notice_for_effect.clone();
             let ws_ref_for_msg = ws_ref_for_effect.clone();
             let terminal_writer_for_msg = terminal_writer_for_effect.clone();
+            let server_info_for_msg = server_info_for_effect.clone();
+            let sessions_cursor_for_msg = sessions_cursor_for_effect.clone();
+            let loading_more_for_msg = loading_more_for_effect.clone();
+            let archived_sessions_for_msg = archived_sessions_for_effect.clone();
+            let last_synced_for_msg = last_synced.clone();
+            let stale_since_for_msg = stale_since.clone();
 
             let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
-                if let Ok(text) = e.data().dyn_into::<js_sys::JsString>() {
-                    let text = String::from(text);
+                // Replayed output: the whole buffer is replaced
+                let terminal_history = |session_id: &str, bytes: Vec<u8>| {
+                    let text = String::from_utf8_lossy(&bytes).to_string();
+                    log::info!("terminal_history received: session={}, bytes={}, text_len={}, is_current={}",
+                        session_id, bytes.len(), text.len(),
+                        selected_session_id_for_msg.as_ref().map(|s| s == session_id).unwrap_or(false));
+
+                    // Replayed history restarts the server's stream offsets
+                    history_offsets_for_msg.borrow_mut().remove(session_id);
+
+                    match terminal_buffers_for_msg.try_borrow_mut() {
+                        Ok(mut buffers) => {
+                            buffers.insert(session_id.to_string(), text);
+                            buffer_version_for_msg.set(*buffer_version_for_msg + 1);
+                            log::info!("terminal_history stored in buffer for session {}", session_id);
+                            offline_cache::save_buffer_later(
+                                terminal_buffers_for_msg.clone(),
+                                session_id,
+                            );
+                        }
+                        Err(_) => {
+                            log::warn!("terminal_buffers contention detected for history, deferring...");
+                            let buffers_clone = terminal_buffers_for_msg.clone();
+                            let session_id = session_id.to_string();
+                            let version_clone = buffer_version_for_msg.clone();
+                            wasm_bindgen_futures::spawn_local(async move {
+                                if let Ok(mut buffers) = buffers_clone.try_borrow_mut() {
+                                    buffers.insert(session_id, text);
+                                    version_clone.set(*version_clone + 1);
+                                }
+                            });
+                        }
+                    }
+                };
+                // Live output: written straight to the terminal when it is showing
+                let terminal_output = |session_id: &str, bytes: Vec<u8>| {
+                    // ALWAYS store to buffer first
+                    let text = String::from_utf8_lossy(&bytes).to_string();
+
+                    // Check if this is the currently selected session
+                    let is_current_session = selected_session_id_for_msg.as_ref().map(|s| s == session_id).unwrap_or(false);
+
+                    log::info!("terminal_output: session={}, bytes={}, is_current={}", session_id, bytes.len(), is_current_session);
+
+                    // For current session: also write directly to terminal for real-time display
+                    if is_current_session {
+                        let writer_opt = terminal_writer_for_msg.borrow().clone();
+                        if let Some(ref writer) = writer_opt {
+                            log::info!("terminal_output: emitting {} bytes via writer", bytes.len());
+                            writer.emit(bytes.clone());
+                        } else {
+                            log::warn!("terminal_output: no writer available, content will show on next render");
+                        }
+                    }
+
+                    // ALWAYS update buffer for persistence and display
+                    match terminal_buffers_for_msg.try_borrow_mut() {
+                        Ok(mut buffers) => {
+                            let buffer = buffers.entry(session_id.to_string()).or_default();
+                            buffer.push_str(&text);
+                            // Limit buffer size
+                            if buffer.len() > TERMINAL_BUFFER_MAX {
+                                *buffer = buffer[buffer.len() - TERMINAL_BUFFER_KEEP..].to_string();
+                            }
+                            // Trigger re-render to show content
+                            buffer_version_for_msg.set(*buffer_version_for_msg + 1);
+                            log::info!("terminal_output: buffer updated for session {}, total len={}", session_id, buffer.len());
+                            offline_cache::save_buffer_later(
+                                terminal_buffers_for_msg.clone(),
+                                session_id,
+                            );
+                        }
+                        Err(_) => {
+                            let buffers_clone = terminal_buffers_for_msg.clone();
+                            let session_id = session_id.to_string();
+                            let version_clone = buffer_version_for_msg.clone();
+                            wasm_bindgen_futures::spawn_local(async move {
+                                if let Ok(mut buffers) = buffers_clone.try_borrow_mut() {
+                                    let buffer = buffers.entry(session_id).or_default();
+                                    buffer.push_str(&text);
+                                    if buffer.len() > TERMINAL_BUFFER_MAX {
+                                        *buffer = buffer[buffer.len() - TERMINAL_BUFFER_KEEP..].to_string();
+                                    }
+                                    version_clone.set(*version_clone + 1);
+                                }
+                            });
+                        }
+                    }
+                };
+
+                let data = e.data();
+                // Binary frames only carry terminal output, so skip the JSON round trip
+                if let Some(buffer) = data.dyn_ref::<js_sys::ArrayBuffer>() {
+                    let bytes = js_sys::Uint8Array::new(buffer).to_vec();
+                    match happy_types::wire::decode_binary(&bytes) {
+                        Ok(happy_types::ServerMessage::TerminalOutput { session_id, data }) => {
+                            terminal_output(&session_id, data);
+                        }
+                        Ok(happy_types::ServerMessage::TerminalHistory { session_id, data }) => {
+                            terminal_history(&session_id, data);
+                        }
+                        Ok(other) => log::warn!("Unexpected binary frame: {:?}", other),
+                        Err(e) => log::warn!("Malformed binary frame: {}", e),
+                    }
+                    return;
+                }
+                if let Some(text) = data.as_string() {
                     if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
                         let msg_type = json.get("type").and_then(|t| t.as_str()).unwrap_or("");
                         // Log all message types except frequent ones
@@ -407,9 +879,31 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                             "authenticated" => {
                                 ws_status_for_msg.set("Connected".to_string());
                             }
+                            "welcome" => {
+                                if let Some(server_info) = &server_info_for_msg {
+                                    server_info.set(Some(ServerInfo {
+                                        server_version: json
+                                            .get("server_version")
+                                            .and_then(|v| v.as_str())
+                                            .unwrap_or_default()
+                                            .to_string(),
+                                        daemon_version: json
+                                            .get("daemon_version")
+                                            .and_then(|v| v.as_str())
+                                            .map(|s| s.to_string()),
+                                    }));
+                                }
+                            }
                             "sessions_list" => {
                                 log::info!("Received sessions_list message: {}", text);
                                 let mut next_sessions = Vec::new();
+                                // Responses arrive in request order, so this one answers the load-more
+                                let appending =
+                                    std::mem::take(&mut *loading_more_for_msg.borrow_mut());
+                                *sessions_cursor_for_msg.borrow_mut() = json
+                                    .get("next_cursor")
+                                    .and_then(|c| c.as_str())
+                                    .map(|c| c.to_string());
 
                                 if let Some(list) = json.get("sessions").and_then(|s| s.as_array())
                                 {
@@ -448,15 +942,33 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                 .and_then(|c| c.as_str())
                                                 .unwrap_or("/")
                                                 .to_string();
+                                            let agent_version = session
+                                                .get("metadata")
+                                                .and_then(|m| m.get("agent_version"))
+                                                .and_then(|v| v.as_str())
+                                                .map(|v| v.to_string());
+                                            let home_dir = session
+                                                .get("metadata")
+                                                .and_then(|m| m.get("home_dir"))
+                                                .and_then(|v| v.as_str())
+                                                .map(|v| v.to_string());
+                                            let cwd_history = session
+                                                .get("metadata")
+                                                .and_then(|m| m.get("cwd_history"))
+                                                .and_then(|h| serde_json::from_value(h.clone()).ok())
+                                                .unwrap_or_default();
 
                                             next_sessions.push(SessionSummary {
                                                 id: id.to_string(),
                                                 tag: tag.to_string(),
                                                 status: status.to_string(),
                                                 cwd,
+                                                cwd_history,
                                                 machine_id: machine_id.clone(),
                                                 machine_name,
                                                 is_online: false, // Will be updated when machines list arrives
+                                                agent_version,
+                                                home_dir,
                                             });
 
                                             // Auto-join sessions
@@ -541,7 +1053,12 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                 {
                                     match sessions_for_msg.try_borrow_mut() {
                                         Ok(mut sessions_ref) => {
-                                            *sessions_ref = next_sessions;
+                                            merge_session_page(
+                                                &mut sessions_ref,
+                                                next_sessions,
+                                                appending,
+                                            );
+                                            offline_cache::save_sessions(&sessions_ref);
                                             sessions_version_for_msg
                                                 .set(*sessions_version_for_msg + 1);
                                         }
@@ -555,7 +1072,12 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                 if let Ok(mut sessions_ref) =
                                                     sessions_clone.try_borrow_mut()
                                                 {
-                                                    *sessions_ref = next_sessions;
+                                                    merge_session_page(
+                                                        &mut sessions_ref,
+                                                        next_sessions,
+                                                        appending,
+                                                    );
+                                                    offline_cache::save_sessions(&sessions_ref);
                                                     version_clone.set(*version_clone + 1);
                                                 }
                                             });
@@ -564,6 +1086,15 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                 }
                                 // Mark sessions as loaded after first response
                                 sessions_loaded_for_msg.set(true);
+                                *last_synced_for_msg.borrow_mut() = Some(js_sys::Date::now());
+                                stale_since_for_msg.set(None);
+                            }
+                            "archived_sessions_list" => {
+                                let archived: Vec<ArchivedSession> = json
+                                    .get("sessions")
+                                    .and_then(|s| serde_json::from_value(s.clone()).ok())
+                                    .unwrap_or_default();
+                                archived_sessions_for_msg.set(archived);
                             }
                             "session_updated" | "session_started" => {
                                 // Only process if we already have sessions loaded
@@ -581,6 +1112,21 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                 .and_then(|c| c.as_str())
                                                 .unwrap_or("/")
                                                 .to_string();
+                                            let agent_version = session
+                                                .get("metadata")
+                                                .and_then(|m| m.get("agent_version"))
+                                                .and_then(|v| v.as_str())
+                                                .map(|v| v.to_string());
+                                            let home_dir = session
+                                                .get("metadata")
+                                                .and_then(|m| m.get("home_dir"))
+                                                .and_then(|v| v.as_str())
+                                                .map(|v| v.to_string());
+                                            let cwd_history = session
+                                                .get("metadata")
+                                                .and_then(|m| m.get("cwd_history"))
+                                                .and_then(|h| serde_json::from_value(h.clone()).ok())
+                                                .unwrap_or_default();
                                             let machine_id = session
                                                 .get("machine_id")
                                                 .and_then(|v| v.as_str())
@@ -607,20 +1153,42 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                             if let Some(existing) =
                                                 next_sessions.iter_mut().find(|s| s.id == id)
                                             {
+                                                if let Some(chime) =
+                                                    sound::chime_for_transition(&existing.status, status)
+                                                {
+                                                    sound::notify(chime);
+                                                }
                                                 existing.tag = tag.to_string();
                                                 existing.status = status.to_string();
                                                 existing.cwd = cwd;
+                                                existing.cwd_history = cwd_history;
                                                 existing.machine_name = machine_name;
+                                                existing.agent_version = agent_version;
+                                                existing.home_dir = home_dir;
                                             } else {
-                                                next_sessions.push(SessionSummary {
+                                                let session = SessionSummary {
                                                     id: id.to_string(),
                                                     tag: tag.to_string(),
                                                     status: status.to_string(),
                                                     cwd,
+                                                    cwd_history,
                                                     machine_id: machine_id.clone(),
                                                     machine_name,
                                                     is_online: false,
-                                                });
+                                                    agent_version,
+                                                    home_dir,
+                                                };
+                                                // A new session shouldn't land out of sight
+                                                if let Ok(mut collapsed) =
+                                                    collapsed_for_msg.try_borrow_mut()
+                                                {
+                                                    collapsed_groups::expand(
+                                                        &mut collapsed,
+                                                        session.machine_group(),
+                                                        session.folder_name(),
+                                                    );
+                                                }
+                                                next_sessions.push(session);
                                             }
                                             match sessions_for_msg.try_borrow_mut() {
                                                 Ok(mut sessions_ref) => {
@@ -653,6 +1221,13 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                     json.get("session_id").and_then(|v| v.as_str())
                                 {
                                     log::info!("session_stopped received for: {}", session_id);
+                                    let was_live = sessions_for_msg
+                                        .borrow()
+                                        .iter()
+                                        .any(|s| s.id == session_id && s.status != "terminated");
+                                    if was_live {
+                                        sound::notify(sound::Chime::Terminated);
+                                    }
                                     let next_sessions: Vec<_> = sessions_for_msg
                                         .borrow()
                                         .iter()
@@ -734,99 +1309,81 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                         .iter()
                                         .filter_map(|v| v.as_u64().map(|n| n as u8))
                                         .collect();
-                                    let text = String::from_utf8_lossy(&bytes).to_string();
-                                    log::info!("terminal_history received: session={}, bytes={}, text_len={}, is_current={}",
-                                        session_id, bytes.len(), text.len(),
-                                        selected_session_id_for_msg.as_ref().map(|s| s == session_id).unwrap_or(false));
-
-                                    match terminal_buffers_for_msg.try_borrow_mut() {
-                                        Ok(mut buffers) => {
-                                            buffers.insert(session_id.to_string(), text);
-                                            buffer_version_for_msg.set(*buffer_version_for_msg + 1);
-                                            log::info!("terminal_history stored in buffer for session {}", session_id);
-                                        }
-                                        Err(_) => {
-                                            log::warn!("terminal_buffers contention detected for history, deferring...");
-                                            let buffers_clone = terminal_buffers_for_msg.clone();
-                                            let session_id = session_id.to_string();
-                                            let version_clone = buffer_version_for_msg.clone();
-                                            wasm_bindgen_futures::spawn_local(async move {
-                                                if let Ok(mut buffers) =
-                                                    buffers_clone.try_borrow_mut()
-                                                {
-                                                    buffers.insert(session_id, text);
-                                                    version_clone.set(*version_clone + 1);
-                                                }
-                                            });
-                                        }
-                                    }
+                                    terminal_history(session_id, bytes);
                                 } else {
                                     log::warn!("terminal_history message missing session_id or data: {:?}", json);
                                 }
                             }
-                            "terminal_output" => {
-                                if let (Some(session_id), Some(data)) = (
+                            "history_truncated" => {
+                                if let (Some(session_id), Some(offset)) = (
+                                    json.get("session_id").and_then(|v| v.as_str()),
+                                    json.get("offset").and_then(|v| v.as_u64()),
+                                ) {
+                                    history_offsets_for_msg
+                                        .borrow_mut()
+                                        .insert(session_id.to_string(), offset);
+                                    buffer_version_for_msg.set(*buffer_version_for_msg + 1);
+                                }
+                            }
+                            "history_range" => {
+                                if let (Some(session_id), Some(offset), Some(data)) = (
                                     json.get("session_id").and_then(|v| v.as_str()),
+                                    json.get("offset").and_then(|v| v.as_u64()),
                                     json.get("data").and_then(|d| d.as_array()),
                                 ) {
                                     let bytes: Vec<u8> = data
                                         .iter()
                                         .filter_map(|v| v.as_u64().map(|n| n as u8))
                                         .collect();
+                                    let has_more = json
+                                        .get("has_more")
+                                        .and_then(|v| v.as_bool())
+                                        .unwrap_or(false);
+                                    log::info!(
+                                        "history_range: session={}, offset={}, bytes={}, has_more={}",
+                                        session_id,
+                                        offset,
+                                        bytes.len(),
+                                        has_more
+                                    );
 
-                                    // Check if this is the currently selected session
-                                    let is_current_session = selected_session_id_for_msg.as_ref().map(|s| s == session_id).unwrap_or(false);
-
-                                    // ALWAYS store to buffer first
+                                    // Older output goes in front; the terminal redraws from the buffer
                                     let text = String::from_utf8_lossy(&bytes).to_string();
-
-                                    // Check if this is the currently selected session
-                                    let is_current_session = selected_session_id_for_msg.as_ref().map(|s| s == session_id).unwrap_or(false);
-
-                                    log::info!("terminal_output: session={}, bytes={}, is_current={}", session_id, bytes.len(), is_current_session);
-
-                                    // For current session: also write directly to terminal for real-time display
-                                    if is_current_session {
-                                        let writer_opt = terminal_writer_for_msg.borrow().clone();
-                                        if let Some(ref writer) = writer_opt {
-                                            log::info!("terminal_output: emitting {} bytes via writer", bytes.len());
-                                            writer.emit(bytes.clone());
-                                        } else {
-                                            log::warn!("terminal_output: no writer available, content will show on next render");
-                                        }
+                                    if let Ok(mut buffers) = terminal_buffers_for_msg.try_borrow_mut() {
+                                        let buffer = buffers.entry(session_id.to_string()).or_default();
+                                        buffer.insert_str(0, &text);
                                     }
-
-                                    // ALWAYS update buffer for persistence and display
-                                    match terminal_buffers_for_msg.try_borrow_mut() {
-                                        Ok(mut buffers) => {
-                                            let buffer = buffers.entry(session_id.to_string()).or_default();
-                                            buffer.push_str(&text);
-                                            // Limit buffer size
-                                            if buffer.len() > 100000 {
-                                                *buffer = buffer[buffer.len() - 80000..].to_string();
-                                            }
-                                            // Trigger re-render to show content
-                                            buffer_version_for_msg.set(*buffer_version_for_msg + 1);
-                                            log::info!("terminal_output: buffer updated for session {}, total len={}", session_id, buffer.len());
-                                        }
-                                        Err(_) => {
-                                            let buffers_clone = terminal_buffers_for_msg.clone();
-                                            let session_id = session_id.to_string();
-                                            let version_clone = buffer_version_for_msg.clone();
-                                            wasm_bindgen_futures::spawn_local(async move {
-                                                if let Ok(mut buffers) = buffers_clone.try_borrow_mut() {
-                                                    let buffer = buffers.entry(session_id).or_default();
-                                                    buffer.push_str(&text);
-                                                    if buffer.len() > 100000 {
-                                                        *buffer = buffer[buffer.len() - 80000..].to_string();
-                                                    }
-                                                    version_clone.set(*version_clone + 1);
-                                                }
-                                            });
-                                        }
+                                    let mut offsets = history_offsets_for_msg.borrow_mut();
+                                    if has_more {
+                                        offsets.insert(session_id.to_string(), offset);
+                                    } else {
+                                        offsets.remove(session_id);
                                     }
+                                    buffer_version_for_msg.set(*buffer_version_for_msg + 1);
                                 }
                             }
+                            "terminal_output" => {
+                                if let (Some(session_id), Some(data)) = (
+                                    json.get("session_id").and_then(|v| v.as_str()),
+                                    json.get("data").and_then(|d| d.as_array()),
+                                ) {
+                                    let bytes: Vec<u8> = data
+                                        .iter()
+                                        .filter_map(|v| v.as_u64().map(|n| n as u8))
+                                        .collect();
+                                    terminal_output(session_id, bytes);
+                                }
+                            }
+                            "account_deleted" => {
+                                log::warn!("Account deleted, logging out");
+                                let window = web_sys::window().unwrap();
+                                let storage = window.local_storage().unwrap().unwrap();
+                                let _ = storage.remove_item("happy_token");
+                                let _ = storage.remove_item("happy_refresh_token");
+                                let _ = storage.remove_item("happy_user_id");
+                                let _ = storage.remove_item("happy_user_email");
+                                let _ = window.location().set_href("/login");
+                            }
                             "error" => {
                                 let code = json.get("code").and_then(|c| c.as_str()).unwrap_or("");
                                 let message =
@@ -836,6 +1393,7 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                     let window = web_sys::window().unwrap();
                                     let storage = window.local_storage().unwrap().unwrap();
                                     let _ = storage.remove_item("happy_token");
+                                    let _ = storage.remove_item("happy_refresh_token");
                                     let _ = window.location().set_href("/login");
                                 }
                             }
@@ -849,9 +1407,20 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
 
                                     let parse_files = |arr: Option<&Vec<serde_json::Value>>| -> Vec<ModifiedFile> {
                                         arr.unwrap_or(&Vec::new()).iter().filter_map(|f| {
+                                            // Unit variants arrive as "modified", renames as {"renamed": {"from": ...}}
+                                            let (change_type, rename_from) = match f.get("change_type")? {
+                                                serde_json::Value::String(kind) => (kind.clone(), None),
+                                                serde_json::Value::Object(map) => {
+                                                    let (kind, fields) = map.iter().next()?;
+                                                    let from = fields.get("from").and_then(|v| v.as_str()).map(|s| s.to_string());
+                                                    (kind.clone(), from)
+                                                }
+                                                _ => return None,
+                                            };
                                             Some(ModifiedFile {
                                                 path: f.get("path")?.as_str()?.to_string(),
-                                                change_type: f.get("change_type")?.as_str()?.to_string(),
+                                                change_type,
+                                                rename_from,
                                                 additions: f.get("additions")?.as_u64()? as u32,
                                                 deletions: f.get("deletions")?.as_u64()? as u32,
                                             })
@@ -913,6 +1482,63 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                     log::info!("Commit result: {} - {}", success, msg_text);
                                 }
                             }
+                            "git_stage_all_result" | "git_unstage_all_result" => {
+                                if let Some(session_id) = json.get("session_id").and_then(|v| v.as_str()) {
+                                    let success = json.get("success").and_then(|v| v.as_bool()).unwrap_or(false);
+                                    let msg_text = json.get("message").and_then(|v| v.as_str()).unwrap_or("");
+                                    log::info!("{}: {} - {}", msg_type, success, msg_text);
+                                    // Refresh either way; a partial failure may still have changed the index
+                                    if let Some(ws) = ws_ref_for_msg.borrow().as_ref() {
+                                        let msg = json!({
+                                            "type": "get_git_status",
+                                            "session_id": session_id
+                                        });
+                                        let _ = ws.send_with_str(&msg.to_string());
+                                    }
+                                }
+                            }
+                            "git_push_result" | "git_pull_result" => {
+                                if let Some(session_id) = json.get("session_id").and_then(|v| v.as_str()) {
+                                    let success = json.get("success").and_then(|v| v.as_bool()).unwrap_or(false);
+                                    let mut text = json.get("message").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                                    let conflicts: Vec<&str> = json
+                                        .get("conflicts")
+                                        .and_then(|v| v.as_array())
+                                        .map(|files| files.iter().filter_map(|f| f.as_str()).collect())
+                                        .unwrap_or_default();
+                                    if !conflicts.is_empty() {
+                                        text = format!("{}\n冲突文件: {}", text, conflicts.join(", "));
+                                    }
+                                    log::info!("{}: {} - {}", msg_type, success, text);
+                                    git_sync_result_for_msg.set(Some((success, text)));
+                                    // Ahead/behind counts and files change either way
+                                    if let Some(ws) = ws_ref_for_msg.borrow().as_ref() {
+                                        let msg = json!({
+                                            "type": "get_git_status",
+                                            "session_id": session_id
+                                        });
+                                        let _ = ws.send_with_str(&msg.to_string());
+                                    }
+                                }
+                            }
+                            "recording_result" => {
+                                if let Some(session_id) = json.get("session_id").and_then(|v| v.as_str()) {
+                                    let recording = json.get("recording").and_then(|v| v.as_bool()).unwrap_or(false);
+                                    let path = json.get("path").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                                    let notice = match json.get("error").and_then(|v| v.as_str()) {
+                                        Some(error) => (false, format!("录制失败: {}", error)),
+                                        None if recording => (true, format!("正在录制到 {}", path)),
+                                        None => (true, format!("录制已保存到 {}", path)),
+                                    };
+                                    if recording {
+                                        recording_paths_for_msg.borrow_mut().insert(session_id.to_string(), path);
+                                    } else {
+                                        recording_paths_for_msg.borrow_mut().remove(session_id);
+                                    }
+                                    recording_notice_for_msg.set(Some((session_id.to_string(), notice.0, notice.1)));
+                                    sessions_version_for_msg.set(*sessions_version_for_msg + 1);
+                                }
+                            }
                             "remote_session_response" => {
                                 let success = json
                                     .get("success")
@@ -927,9 +1553,8 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                             log::info!("Remote session created: {} ({})", tag, id);
                                             // Auto-select the new session
                                             selected_session_id_for_msg.set(Some(id.to_string()));
-                                            let list_msg =
-                                                json!({ "type": "list_sessions" }).to_string();
-                                            let _ = ws_for_msg.send_with_str(&list_msg);
+                                            let _ = ws_for_msg
+                                                .send_with_str(&list_sessions_msg(None));
                                         }
                                     }
                                 } else {
@@ -940,6 +1565,28 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                     log::error!("Failed to create remote session: {}", error);
                                 }
                             }
+                            "session_transferred" => {
+                                let from_id = json.get("from_session_id").and_then(|v| v.as_str());
+                                let session = json.get("session");
+                                let id = session.and_then(|s| s.get("id")).and_then(|v| v.as_str());
+                                let tag = session.and_then(|s| s.get("tag")).and_then(|v| v.as_str());
+                                if let (Some(from_id), Some(id), Some(tag)) = (from_id, id, tag) {
+                                    log::info!("Session {} moved to {} ({})", from_id, tag, id);
+                                    // Output already follows the move; joining lets input through too
+                                    if let Ok(mut joined) = joined_tags_ref_for_msg.try_borrow_mut() {
+                                        joined.insert(tag.to_string());
+                                    }
+                                    let join_msg = json!({ "type": "join_session", "tag": tag });
+                                    let _ = ws_for_msg.send_with_str(&join_msg.to_string());
+                                    if selected_session_id_for_msg.as_deref() == Some(from_id) {
+                                        selected_session_id_for_msg.set(Some(id.to_string()));
+                                        if let Some(window) = web_sys::window() {
+                                            let _ = window.location().set_hash(tag);
+                                        }
+                                    }
+                                    let _ = ws_for_msg.send_with_str(&list_sessions_msg(None));
+                                }
+                            }
                             "machine_list" => {
                                 if let Some(machine_list) =
                                     json.get("machines").and_then(|m| m.as_array())
@@ -952,9 +1599,27 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                             m.get("name").and_then(|v| v.as_str()),
                                             m.get("is_online").and_then(|v| v.as_bool()),
                                         ) {
+                                            let text_field = |key: &str| {
+                                                m.get(key)
+                                                    .and_then(|v| v.as_str())
+                                                    .map(|s| s.to_string())
+                                            };
                                             next_machines.push(MachineInfo {
                                                 id: id.to_string(),
                                                 name: name.to_string(),
+                                                platform: text_field("platform"),
+                                                arch: text_field("arch"),
+                                                daemon_version: text_field("daemon_version"),
+                                                capabilities: m
+                                                    .get("capabilities")
+                                                    .and_then(|v| v.as_array())
+                                                    .map(|caps| {
+                                                        caps.iter()
+                                                            .filter_map(|c| c.as_str())
+                                                            .map(|c| c.to_string())
+                                                            .collect()
+                                                    })
+                                                    .unwrap_or_default(),
                                             });
                                             if online {
                                                 online_machine_ids.insert(id.to_string());
@@ -1070,6 +1735,28 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         );
     }
 
+    // Incremental loading: ask for the next page near the bottom of the list
+    let on_session_list_scroll = {
+        let ws_ref = ws_ref.clone();
+        let sessions_cursor = sessions_cursor.clone();
+        let loading_more_sessions = loading_more_sessions.clone();
+        Callback::from(move |e: Event| {
+            let list: web_sys::Element = e.target_unchecked_into();
+            let remaining = list.scroll_height() - list.scroll_top() - list.client_height();
+            if remaining > LOAD_MORE_THRESHOLD_PX || *loading_more_sessions.borrow() {
+                return;
+            }
+            let Some(cursor) = sessions_cursor.borrow().clone() else {
+                return;
+            };
+            if let Some(ws) = ws_ref.borrow().as_ref() {
+                if ws.send_with_str(&list_sessions_msg(Some(&cursor))).is_ok() {
+                    *loading_more_sessions.borrow_mut() = true;
+                }
+            }
+        })
+    };
+
     let on_input = {
         let input_value = input_value.clone();
         Callback::from(move |e: InputEvent| {
@@ -1106,6 +1793,40 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         })
     };
 
+    // Last size sent per session, so refits that don't change the grid stay quiet
+    let last_terminal_size = use_mut_ref(|| None::<(String, u32, u32)>);
+
+    let on_terminal_resize = {
+        let selected_session_id = selected_session_id.clone();
+        let ws_ref = ws_ref.clone();
+        let last_terminal_size = last_terminal_size.clone();
+        Callback::from(move |(cols, rows): (u32, u32)| {
+            let Some(session_id) = (*selected_session_id).clone() else {
+                return;
+            };
+            // xterm.js reports 0x0 while the container is hidden
+            if cols == 0 || rows == 0 {
+                return;
+            }
+            let size = (session_id.clone(), cols, rows);
+            if last_terminal_size.borrow().as_ref() == Some(&size) {
+                return;
+            }
+            let msg = json!({
+                "type": "terminal_resize",
+                "session_id": session_id,
+                "cols": cols.min(u16::MAX as u32),
+                "rows": rows.min(u16::MAX as u32)
+            });
+            if let Some(ws) = ws_ref.borrow().as_ref() {
+                if ws.send_with_str(&msg.to_string()).is_ok() {
+                    log::info!("Sent terminal_resize {}x{} for session {}", cols, rows, session_id);
+                    *last_terminal_size.borrow_mut() = Some(size);
+                }
+            }
+        })
+    };
+
     // Key sequences for virtual keyboard
     // Arrow keys use ANSI escape sequences
     // Enter uses \r (carriage return)
@@ -1154,6 +1875,31 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         })
     };
 
+    // Reads the clipboard from the button's own click, since iOS never
+    // delivers a paste event to xterm.js
+    let on_key_paste = {
+        let on_terminal_input = on_terminal_input.clone();
+        let paste_toast = paste_toast.clone();
+        let paste_toast_timeout = paste_toast_timeout.clone();
+        Callback::from(move |_| {
+            let on_terminal_input = on_terminal_input.clone();
+            let paste_toast = paste_toast.clone();
+            let paste_toast_timeout = paste_toast_timeout.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let Some(text) = clipboard::read_text().await.filter(|t| !t.is_empty()) else {
+                    return;
+                };
+                let chars = text.chars().count();
+                on_terminal_input.emit(text.into_bytes());
+
+                paste_toast.set(Some(format!("Pasted {} chars", chars)));
+                let hide = paste_toast.clone();
+                *paste_toast_timeout.borrow_mut() =
+                    Some(Timeout::new(2_000, move || hide.set(None)));
+            });
+        })
+    };
+
     let on_send = {
         let input_value = input_value.clone();
         let selected_session_id = selected_session_id.clone();
@@ -1257,6 +2003,29 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         })
     };
 
+    // Start or stop recording the selected session on its machine
+    let on_toggle_recording = {
+        let selected_session_id = selected_session_id.clone();
+        let recording_paths = recording_paths.clone();
+        let ws_ref = ws_ref.clone();
+        Callback::from(move |_| {
+            if let Some(ref session_id) = *selected_session_id {
+                let msg_type = if recording_paths.borrow().contains_key(session_id) {
+                    "stop_recording"
+                } else {
+                    "start_recording"
+                };
+                if let Some(ws) = ws_ref.borrow().as_ref() {
+                    let msg = json!({
+                        "type": msg_type,
+                        "session_id": session_id
+                    });
+                    let _ = ws.send_with_str(&msg.to_string());
+                }
+            }
+        })
+    };
+
     // Log viewer toggle
     let on_toggle_log_viewer = {
         let log_viewer_open = log_viewer_open.clone();
@@ -1266,6 +2035,70 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         })
     };
 
+    let on_import_recording_click = {
+        let recording_input_ref = recording_input_ref.clone();
+        Callback::from(move |_| {
+            if let Some(input) = recording_input_ref.cast::<HtmlInputElement>() {
+                input.click();
+            }
+        })
+    };
+
+    let on_recording_file = {
+        let recording = recording.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            // Let the same file be picked again after closing the player
+            input.set_value("");
+            let recording = recording.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let name = file.name();
+                let text = match wasm_bindgen_futures::JsFuture::from(file.text()).await {
+                    Ok(text) => text.as_string().unwrap_or_default(),
+                    Err(e) => {
+                        log::error!("Failed to read recording {}: {:?}", name, e);
+                        return;
+                    }
+                };
+                match asciicast::parse(&text) {
+                    Ok(parsed) => recording.set(Some((name, Rc::new(parsed)))),
+                    Err(e) => log::error!("Failed to parse recording {}: {}", name, e),
+                }
+            });
+        })
+    };
+
+    let on_recording_close = {
+        let recording = recording.clone();
+        Callback::from(move |_| recording.set(None))
+    };
+
+    let on_load_more_history = {
+        let selected_session_id = selected_session_id.clone();
+        let history_offsets = history_offsets.clone();
+        let ws_ref = ws_ref.clone();
+        Callback::from(move |_| {
+            if let Some(ref session_id) = *selected_session_id {
+                let Some(oldest) = history_offsets.borrow().get(session_id).copied() else {
+                    return;
+                };
+                let offset = oldest.saturating_sub(HISTORY_PAGE_BYTES);
+                if let Some(ws) = ws_ref.borrow().as_ref() {
+                    let msg = json!({
+                        "type": "fetch_history_range",
+                        "session_id": session_id,
+                        "offset": offset,
+                        "length": oldest - offset
+                    });
+                    let _ = ws.send_with_str(&msg.to_string());
+                }
+            }
+        })
+    };
+
     let on_log_viewer_close = {
         let log_viewer_open = log_viewer_open.clone();
         Callback::from(move |_| log_viewer_open.set(false))
@@ -1304,6 +2137,45 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         })
     };
 
+    // Stage or unstage everything; takes the message type to send
+    let on_stage_all = {
+        let selected_session_id = selected_session_id.clone();
+        let ws_ref = ws_ref.clone();
+        Callback::from(move |msg_type: &'static str| {
+            if let Some(ref session_id) = *selected_session_id {
+                if let Some(ws) = ws_ref.borrow().as_ref() {
+                    let msg = json!({
+                        "type": msg_type,
+                        "session_id": session_id
+                    });
+                    let _ = ws.send_with_str(&msg.to_string());
+                }
+            }
+        })
+    };
+
+    // Push or pull with the branch's upstream; takes the message type to send
+    let on_git_sync = {
+        let selected_session_id = selected_session_id.clone();
+        let ws_ref = ws_ref.clone();
+        let git_sync_result = git_sync_result.clone();
+        Callback::from(move |msg_type: &'static str| {
+            if let Some(ref session_id) = *selected_session_id {
+                if let Some(ws) = ws_ref.borrow().as_ref() {
+                    let msg = json!({
+                        "type": msg_type,
+                        "session_id": session_id,
+                        "remote": null,
+                        "branch": null
+                    });
+                    let _ = ws.send_with_str(&msg.to_string());
+                    let pending = if msg_type == "git_push" { "推送中..." } else { "拉取中..." };
+                    git_sync_result.set(Some((true, pending.to_string())));
+                }
+            }
+        })
+    };
+
     // Submit commit
     let on_submit_commit = {
         let selected_session_id = selected_session_id.clone();
@@ -1325,7 +2197,39 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         })
     };
 
-    // Delete session callback
+    // Archive session callback; the server moves it out of the list
+    let on_archive_session = {
+        let ws_ref = ws_ref.clone();
+        let selected_session_id = selected_session_id.clone();
+        Callback::from(move |session_id: String| {
+            let msg = json!({
+                "type": "archive_session",
+                "session_id": session_id
+            });
+            if let Some(ws) = ws_ref.borrow().as_ref() {
+                let _ = ws.send_with_str(&msg.to_string());
+            }
+            if *selected_session_id == Some(session_id.clone()) {
+                selected_session_id.set(None);
+            }
+        })
+    };
+
+    // Restore an archived session into the list
+    let on_restore_session = {
+        let ws_ref = ws_ref.clone();
+        Callback::from(move |session_id: String| {
+            let msg = json!({
+                "type": "restore_session",
+                "session_id": session_id
+            });
+            if let Some(ws) = ws_ref.borrow().as_ref() {
+                let _ = ws.send_with_str(&msg.to_string());
+            }
+        })
+    };
+
+    // Delete session callback; only offered for archived sessions, which it purges
     let on_delete_session = {
         let ws_ref = ws_ref.clone();
         let selected_session_id = selected_session_id.clone();
@@ -1363,12 +2267,10 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         let mut machine_groups: HashMap<String, HashMap<String, Vec<SessionSummary>>> =
             HashMap::new();
 
-        for session in sessions_ref.iter() {
-            let machine_key = if session.machine_name.is_empty() {
-                "unknown".to_string()
-            } else {
-                session.machine_name.clone()
-            };
+        for session in sessions_ref.iter().filter(|s| {
+            matches_query(&s.tag, &filter_text) || matches_query(s.folder_name(), &filter_text)
+        }) {
+            let machine_key = session.machine_group().to_string();
             let folder = session.folder_name().to_string();
 
             machine_groups
@@ -1406,6 +2308,120 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
     let mut sorted_machines: Vec<_> = grouped_sessions.keys().cloned().collect();
     sorted_machines.sort();
 
+    // Visible sessions in sidebar order: machine, then folder, then the order within the folder
+    let ordered_sessions: Vec<SessionSummary> = {
+        let collapsed = collapsed.borrow();
+        sorted_machines
+            .iter()
+            .filter(|machine| !collapsed.contains(&collapsed_groups::machine_key(machine)))
+            .flat_map(|machine_name| {
+                let folders = &grouped_sessions[machine_name];
+                let mut sorted_folders: Vec<_> = folders
+                    .keys()
+                    .filter(|folder| {
+                        !collapsed.contains(&collapsed_groups::folder_key(machine_name, folder))
+                    })
+                    .collect();
+                sorted_folders.sort();
+                sorted_folders
+                    .into_iter()
+                    .flat_map(|folder| folders[folder].iter().cloned())
+            })
+            .collect()
+    };
+
+    // Fold or unfold the sidebar group with the given key
+    let on_toggle_group = {
+        let collapsed = collapsed.clone();
+        let sessions_version = sessions_version.clone();
+        Callback::from(move |key: String| {
+            collapsed_groups::toggle(&mut collapsed.borrow_mut(), key);
+            sessions_version.set(*sessions_version + 1);
+        })
+    };
+
+    // Show a session, joining it on first selection
+    let select_session = {
+        let selected_session_id = selected_session_id.clone();
+        let mobile_view = mobile_view.clone();
+        let ws_ref = ws_ref.clone();
+        let joined_tags_ref = joined_tags_ref.clone();
+        Callback::from(move |session: SessionSummary| {
+            let SessionSummary {
+                id: session_id,
+                tag: session_tag,
+                status: session_status,
+                ..
+            } = session;
+            log::info!("Session selected: tag='{}' id='{}'", session_tag, session_id);
+            selected_session_id.set(Some(session_id.clone()));
+            mobile_view.set(MobileView::Terminal);
+
+            // Update URL hash without reloading page
+            if let Some(window) = web_sys::window() {
+                let location = window.location();
+                let _ = location.set_hash(&session_tag);
+            }
+
+            // Join the session if not already joined
+            {
+                let mut joined = joined_tags_ref.borrow_mut();
+                log::info!("Checking if need to join: tag='{}', already_joined={}, status='{}'", session_tag, joined.contains(&session_tag), session_status);
+                if !joined.contains(&session_tag) {
+                    // Only join if session is not terminated
+                    if session_status != "terminated" {
+                        joined.insert(session_tag.clone());
+                        let join_msg = json!({
+                            "type": "join_session",
+                            "tag": &session_tag
+                        }).to_string();
+                        log::info!("Sending join_session message: {}", join_msg);
+                        if let Some(ws) = ws_ref.borrow().as_ref() {
+                            let result = ws.send_with_str(&join_msg);
+                            if let Err(e) = result {
+                                log::error!("Failed to send join_session: {:?}", e);
+                            } else {
+                                log::info!("join_session message sent successfully");
+                            }
+                        } else {
+                            log::warn!("WebSocket not available, cannot send join_session");
+                        }
+                    } else {
+                        log::info!("Session is terminated, not sending join_session");
+                    }
+                } else {
+                    log::info!("Already joined session '{}', skipping join", session_tag);
+                }
+            }
+        })
+    };
+
+    // Alt+1..9 and Alt+←/→ move through the sidebar
+    {
+        let select_session = select_session.clone();
+        let current = (*selected_session_id)
+            .as_ref()
+            .and_then(|id| ordered_sessions.iter().position(|s| &s.id == id));
+        let ordered_sessions = ordered_sessions.clone();
+        use_keyboard_shortcuts(Callback::from(move |shortcut: Shortcut| {
+            let count = ordered_sessions.len();
+            if count == 0 {
+                return;
+            }
+            let index = match (shortcut, current) {
+                (Shortcut::Select(index), _) if index < count => index,
+                (Shortcut::Select(_), _) => return,
+                (Shortcut::Previous, Some(i)) => (i + count - 1) % count,
+                (Shortcut::Previous, None) => count - 1,
+                (Shortcut::Next, Some(i)) => (i + 1) % count,
+                (Shortcut::Next, None) => 0,
+            };
+            let session = ordered_sessions[index].clone();
+            scroll_session_into_view(&session.id);
+            select_session.emit(session);
+        }));
+    }
+
     // Create session handler
     let on_create_session = {
         let ws_ref = ws_ref.clone();
@@ -1447,6 +2463,32 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
         })
     };
 
+    // Transfer session handler
+    let on_transfer_session = {
+        let ws_ref = ws_ref.clone();
+        let transfer_session = transfer_session.clone();
+        let transfer_machine = transfer_machine.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let Some(session_id) = (*transfer_session).clone() else {
+                return;
+            };
+            if transfer_machine.is_empty() {
+                return;
+            }
+            let msg = json!({
+                "type": "transfer_session",
+                "session_id": session_id,
+                "target_machine_id": *transfer_machine,
+            });
+            if let Some(ws) = ws_ref.borrow().as_ref() {
+                let _ = ws.send_with_str(&msg.to_string());
+            }
+            transfer_session.set(None);
+            transfer_machine.set(String::new());
+        })
+    };
+
     // Clone state for use in html! closures
     let show_create_modal_clone = show_create_modal.clone();
     let delete_confirm_clone = delete_confirm.clone();
@@ -1478,13 +2520,52 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                     <button class="btn-create-session" onclick={Callback::from(move |_| show_create_modal_clone.set(true))}>
                         { "+" }
                     </button>
+                    <button class="btn-import-recording" title="导入录像" onclick={on_import_recording_click}>
+                        { "📼" }
+                    </button>
+                    <input
+                        ref={recording_input_ref}
+                        type="file"
+                        accept=".cast,.json"
+                        style="display: none"
+                        onchange={on_recording_file}
+                    />
+                    <button
+                        class="btn-settings"
+                        title="设置"
+                        onclick={{
+                            let on_navigate = on_navigate.clone();
+                            Callback::from(move |_| on_navigate.emit(Route::Settings))
+                        }}
+                    >
+                        { "⚙" }
+                    </button>
                 </div>
             </header>
 
             <main class="chat-main">
                 <aside class={sidebar_class}>
                     <div class="chat-sidebar-header">{ "会话列表" }</div>
-                    <div class="chat-session-list">
+                    if let Some(updated_at) = *stale_since {
+                        <div class="offline-badge">{ offline_label(updated_at) }</div>
+                    }
+                    if !sessions.borrow().is_empty() {
+                        <div class="session-filter">
+                            <input
+                                type="search"
+                                placeholder="筛选会话..."
+                                value={(*filter_text).clone()}
+                                oninput={{
+                                    let filter_text = filter_text.clone();
+                                    Callback::from(move |e: InputEvent| {
+                                        let input: HtmlInputElement = e.target_unchecked_into();
+                                        filter_text.set(input.value());
+                                    })
+                                }}
+                            />
+                        </div>
+                    }
+                    <div class="chat-session-list" onscroll={on_session_list_scroll}>
                         {
                             if !*sessions_loaded {
                                 // Loading state
@@ -1507,6 +2588,12 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                         <small>{ "运行后即可在此处查看和管理会话" }</small>
                                     </div>
                                 }
+                            } else if sorted_machines.is_empty() {
+                                html! {
+                                    <div class="session-list-empty">
+                                        <p>{ "没有匹配的会话" }</p>
+                                    </div>
+                                }
                             } else {
                                 // Normal session list
                                 html! {
@@ -1514,80 +2601,53 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                             let folders = grouped_sessions.get(machine_name).unwrap();
                             let mut sorted_folders: Vec<_> = folders.keys().cloned().collect();
                             sorted_folders.sort();
+                            let machine_tooltip = machines
+                                .borrow()
+                                .iter()
+                                .find(|m| &m.name == machine_name)
+                                .map(|m| m.tooltip())
+                                .unwrap_or_default();
+                            let machine_key = collapsed_groups::machine_key(machine_name);
+                            let machine_collapsed = collapsed.borrow().contains(&machine_key);
+                            let machine_count: usize = folders.values().map(Vec::len).sum();
 
                             html! {
                                 <div class="machine-group">
-                                    <div class="machine-group-header">
+                                    <div class="machine-group-header" title={machine_tooltip}>
+                                        { group_toggle(machine_collapsed, on_toggle_group.reform(move |_| machine_key.clone())) }
                                         <span class="machine-icon">{ "💻" }</span>
                                         { machine_name }
+                                        if machine_collapsed {
+                                            <span class="group-count">{ machine_count }</span>
+                                        }
                                     </div>
+                                    if !machine_collapsed {
                                     { for sorted_folders.iter().map(|folder| {
                                         let sessions_in_folder = folders.get(folder).unwrap();
+                                        let folder_key = collapsed_groups::folder_key(machine_name, folder);
+                                        let folder_collapsed = collapsed.borrow().contains(&folder_key);
                                         html! {
                                             <div class="session-folder-group">
-                                                <div class="session-folder-header">{ folder }</div>
+                                                <div class="session-folder-header">
+                                                    { group_toggle(folder_collapsed, on_toggle_group.reform(move |_| folder_key.clone())) }
+                                                    { highlight_match(folder, &filter_text) }
+                                                    if folder_collapsed {
+                                                        <span class="group-count">{ sessions_in_folder.len() }</span>
+                                                    }
+                                                </div>
+                                                if !folder_collapsed {
                                                 { for sessions_in_folder.iter().map(|session| {
                                                     let is_selected = (*selected_session_id)
                                                         .as_ref()
                                                         .map(|id| id == &session.id)
                                                         .unwrap_or(false);
 
-                                                    let session_id = session.id.clone();
-                                                    let session_tag = session.tag.clone();
-                                                    let session_status = session.status.clone();
                                                     let session_id_for_delete = session.id.clone();
                                                     let session_tag_for_delete = session.tag.clone();
-                                                    let mobile_view_clone = mobile_view.clone();
 
                                                     let on_select = {
-                                                        let selected_session_id = selected_session_id.clone();
-                                                        // selected_session_id_ref removed
-                                                        let mobile_view = mobile_view_clone.clone();
-                                                        let ws_ref = ws_ref.clone();
-                                                        let joined_tags_ref = joined_tags_ref.clone();
-                                                        let session_tag = session_tag.clone();
-                                                        Callback::from(move |_| {
-                                                            log::info!("Session selected: tag='{}' id='{}'", session_tag, session_id);
-                                                            selected_session_id.set(Some(session_id.clone()));
-                                                            mobile_view.set(MobileView::Terminal);
-
-                                                            // Update URL hash without reloading page
-                                                            if let Some(window) = web_sys::window() {
-                                                                let location = window.location();
-                                                                let _ = location.set_hash(&session_tag);
-                                                            }
-
-                                                            // Join the session if not already joined
-                                                            {
-                                                                let mut joined = joined_tags_ref.borrow_mut();
-                                                                log::info!("Checking if need to join: tag='{}', already_joined={}, status='{}'", session_tag, joined.contains(&session_tag), session_status);
-                                                                if !joined.contains(&session_tag) {
-                                                                    // Only join if session is not terminated
-                                                                    if session_status != "terminated" {
-                                                                        joined.insert(session_tag.clone());
-                                                                        let join_msg = json!({
-                                                                            "type": "join_session",
-                                                                            "tag": &session_tag
-                                                                        }).to_string();
-                                                                        log::info!("Sending join_session message: {}", join_msg);
-                                                                        if let Some(ws) = ws_ref.borrow().as_ref() {
-                                                                            let result = ws.send_with_str(&join_msg);
-                                                                            if let Err(e) = result {
-                                                                                log::error!("Failed to send join_session: {:?}", e);
-                                                                            } else {
-                                                                                log::info!("join_session message sent successfully");
-                                                                            }
-                                                                        } else {
-                                                                            log::warn!("WebSocket not available, cannot send join_session");
-                                                                        }
-                                                                    } else {
-                                                                        log::info!("Session is terminated, not sending join_session");
-                                                                    }
-                                                                } else {
-                                                                    log::info!("Already joined session '{}', skipping join", session_tag);
-                                                                }
-                                                            }
-                                                        })
+                                                        let session = session.clone();
+                                                        select_session.reform(move |_| session.clone())
                                                     };
 
                                                     let on_right_click = {
@@ -1612,11 +2672,12 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                     html! {
                                                         <div
                                                             class={classes!("chat-session-item", if is_selected { "selected" } else { "" })}
+                                                            data-session-id={session.id.clone()}
                                                             oncontextmenu={on_right_click}
                                                         >
                                                             <button class="session-select-btn" onclick={on_select}>
                                                                 <div class="session-title-row">
-                                                                    <span class="chat-session-tag">{ session.tag.clone() }</span>
+                                                                    <span class="chat-session-tag">{ highlight_match(&session.tag, &filter_text) }</span>
                                                                     <span class={classes!("conn-dot", conn_cls)} title={if session.is_online { "在线" } else { "离线" }}/>
                                                                 </div>
                                                                 <div class="session-status-row">
@@ -1626,9 +2687,11 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                         </div>
                                                     }
                                                 }) }
+                                                }
                                             </div>
                                         }
                                     }) }
+                                    }
                                 </div>
                             }
                         }) }
@@ -1636,6 +2699,44 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                             }
                         }
                     </div>
+                    if !archived_sessions.is_empty() {
+                        <div class="archived-sessions">
+                            <div class="machine-group-header">
+                                { group_toggle(!*show_archived, {
+                                    let show_archived = show_archived.clone();
+                                    Callback::from(move |_| show_archived.set(!*show_archived))
+                                }) }
+                                <span class="machine-icon">{ "🗃" }</span>
+                                { "已归档" }
+                                <span class="group-count">{ archived_sessions.len() }</span>
+                            </div>
+                            if *show_archived {
+                                <div class="archived-session-list">
+                                    { for archived_sessions.iter().map(|session| {
+                                        let on_restore = on_restore_session.reform({
+                                            let id = session.id.clone();
+                                            move |_: MouseEvent| id.clone()
+                                        });
+                                        let on_purge = {
+                                            let delete_confirm = delete_confirm_clone.clone();
+                                            let target = (session.id.clone(), session.tag.clone());
+                                            Callback::from(move |_| delete_confirm.set(Some(target.clone())))
+                                        };
+                                        html! {
+                                            <div class="archived-session-item">
+                                                <div class="archived-session-info">
+                                                    <span class="chat-session-tag">{ session.tag.clone() }</span>
+                                                    <span class="archived-session-machine">{ session.machine_name.clone() }</span>
+                                                </div>
+                                                <button class="btn-restore" onclick={on_restore}>{ "恢复" }</button>
+                                                <button class="btn-purge" title="永久删除" onclick={on_purge}>{ "🗑" }</button>
+                                            </div>
+                                        }
+                                    }) }
+                                </div>
+                            }
+                        </div>
+                    }
                 </aside>
 
                 <section class={panel_class}>
@@ -1688,19 +2789,106 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                 let terminal_writer_clone = terminal_writer_for_xterm.clone();
                                 let scroll_to_bottom_clone = scroll_to_bottom.clone();
                                 let show_scroll_to_bottom_clone = show_scroll_to_bottom.clone();
+                                let search_matches_clone = search_matches.clone();
                                 let session_id_for_header = (*selected_session_id).as_ref().unwrap_or(&String::new()).clone();
                                 let session_tag_for_header = sessions.borrow().iter()
                                     .find(|s| s.id == session_id_for_header)
                                     .map(|s| s.tag.clone())
                                     .unwrap_or_else(|| "Unknown".to_string());
+                                let agent_version_for_header = sessions.borrow().iter()
+                                    .find(|s| s.id == session_id_for_header)
+                                    .and_then(|s| s.agent_version.clone());
+                                let breadcrumbs_for_header = sessions.borrow().iter()
+                                    .find(|s| s.id == session_id_for_header)
+                                    .map(|s| cwd_breadcrumbs(&s.cwd, s.home_dir.as_deref()))
+                                    .unwrap_or_default();
+                                let recent_dirs_for_header = sessions.borrow().iter()
+                                    .find(|s| s.id == session_id_for_header)
+                                    .map(|s| recent_dirs(&s.cwd_history, &s.cwd))
+                                    .unwrap_or_default();
+                                let can_transfer_for_header = sessions.borrow().iter()
+                                    .any(|s| s.id == session_id_for_header && s.status != "terminated");
+                                let on_open_transfer_for_header = {
+                                    let transfer_session = transfer_session.clone();
+                                    let session_id = session_id_for_header.clone();
+                                    Callback::from(move |_| transfer_session.set(Some(session_id.clone())))
+                                };
+                                let recording_path_for_header = recording_paths.borrow().get(&session_id_for_header).cloned();
+                                let recording_notice_for_header = (*recording_notice).clone()
+                                    .filter(|(id, _, _)| *id == session_id_for_header);
+                                let on_dismiss_recording_notice = {
+                                    let recording_notice = recording_notice.clone();
+                                    Callback::from(move |_| recording_notice.set(None))
+                                };
+                                let on_terminal_input_for_header = on_terminal_input.clone();
                                 let on_toggle_log_viewer_for_header = on_toggle_log_viewer.clone();
+                                let has_more_history = history_offsets.borrow().contains_key(&session_id_for_header);
                                 let on_log_viewer_close_for_header = on_log_viewer_close.clone();
                                 html! {
                                     <>
                                         <div class="terminal-header">
-                                            <div class="terminal-header-info">
-                                                <span class="terminal-session-tag">{ session_tag_for_header }</span>
-                                                <span class="terminal-session-id">{ format!("({})", &session_id_for_header[..8.min(session_id_for_header.len())]) }</span>
+                                            <div class="terminal-header-main">
+                                                <div class="terminal-header-info">
+                                                    <span class="terminal-session-tag">{ session_tag_for_header }</span>
+                                                    if let Some(version) = agent_version_for_header {
+                                                        <span class="terminal-agent-version">{ format!("v{}", version) }</span>
+                                                    }
+                                                    <span class="terminal-session-id">{ format!("({})", &session_id_for_header[..8.min(session_id_for_header.len())]) }</span>
+                                                    if search_matches.1 > 0 {
+                                                        <span class="terminal-search-matches">{ format!("{} of {} matches", search_matches.0, search_matches.1) }</span>
+                                                    }
+                                                </div>
+                                                <nav class="cwd-breadcrumb">
+                                                    { for breadcrumbs_for_header.iter().enumerate().map(|(i, (label, path))| {
+                                                        let is_current = i + 1 == breadcrumbs_for_header.len();
+                                                        let on_click = {
+                                                            let on_input = on_terminal_input_for_header.clone();
+                                                            let command = cd_command(path);
+                                                            Callback::from(move |_| on_input.emit(command.clone()))
+                                                        };
+                                                        html! {
+                                                            <>
+                                                                if i > 0 && breadcrumbs_for_header[i - 1].0 != "/" {
+                                                                    <span class="cwd-breadcrumb-sep">{ "/" }</span>
+                                                                }
+                                                                <button
+                                                                    class={classes!("cwd-breadcrumb-segment", is_current.then_some("current"))}
+                                                                    title={format!("cd {}", path)}
+                                                                    disabled={is_current}
+                                                                    onclick={on_click}
+                                                                >
+                                                                    { label }
+                                                                </button>
+                                                            </>
+                                                        }
+                                                    }) }
+                                                    if !recent_dirs_for_header.is_empty() {
+                                                        <details class="cwd-history">
+                                                            <summary title="最近访问的目录">{ "▾" }</summary>
+                                                            <div class="cwd-history-menu">
+                                                                { for recent_dirs_for_header.iter().map(|dir| {
+                                                                    let on_click = {
+                                                                        let on_input = on_terminal_input_for_header.clone();
+                                                                        let command = cd_command(dir);
+                                                                        Callback::from(move |e: MouseEvent| {
+                                                                            // Picking an entry closes the menu
+                                                                            let item: web_sys::Element = e.target_unchecked_into();
+                                                                            if let Ok(Some(menu)) = item.closest("details") {
+                                                                                let _ = menu.remove_attribute("open");
+                                                                            }
+                                                                            on_input.emit(command.clone());
+                                                                        })
+                                                                    };
+                                                                    html! {
+                                                                        <button class="cwd-history-item" title={format!("cd {}", dir)} onclick={on_click}>
+                                                                            { dir }
+                                                                        </button>
+                                                                    }
+                                                                }) }
+                                                            </div>
+                                                        </details>
+                                                    }
+                                                </nav>
                                             </div>
                                             <div class="terminal-header-actions">
                                                 <button
@@ -1714,6 +2902,22 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                         }
                                                     }
                                                 </button>
+                                                <button
+                                                    class="btn-terminal-transfer"
+                                                    title="迁移到其他机器，保留终端历史"
+                                                    disabled={!can_transfer_for_header}
+                                                    onclick={on_open_transfer_for_header}
+                                                >
+                                                    { "🚚 Transfer" }
+                                                </button>
+                                                <button
+                                                    class={classes!("btn-terminal-record", recording_path_for_header.is_some().then_some("active"))}
+                                                    title={recording_path_for_header.clone().unwrap_or_else(|| "录制终端输出到 ~/.happy/recordings/".to_string())}
+                                                    disabled={!can_transfer_for_header && recording_path_for_header.is_none()}
+                                                    onclick={on_toggle_recording.clone()}
+                                                >
+                                                    { if recording_path_for_header.is_some() { "⏹ 停止录制" } else { "⏺ 录制" } }
+                                                </button>
                                                 <button
                                                     class={classes!("btn-terminal-logs", if *log_viewer_open { "active" } else { "" })}
                                                     onclick={on_toggle_log_viewer_for_header.clone()}
@@ -1722,11 +2926,29 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                 </button>
                                             </div>
                                         </div>
+                                        if let Some((_, success, text)) = recording_notice_for_header {
+                                            <div
+                                                class={classes!("recording-notice", (!success).then_some("error"))}
+                                                title="点击关闭"
+                                                onclick={on_dismiss_recording_notice}
+                                            >
+                                                { text }
+                                            </div>
+                                        }
                                         <div class="terminal-content">
+                                            if has_more_history {
+                                                <button
+                                                    class="btn-load-more-history"
+                                                    onclick={on_load_more_history.clone()}
+                                                >
+                                                    { "⬆ Load more history" }
+                                                </button>
+                                            }
                                             <XTerm
                                                 id={format!("terminal-{}", session_id_for_header)}
                                                 initial_content={terminal_content}
                                                 on_input={on_terminal_input.clone()}
+                                                on_resize={on_terminal_resize.clone()}
                                                 on_key_sender={Callback::from(move |sender| {
                                                     key_sender.set(Some(sender));
                                                 })}
@@ -1739,6 +2961,9 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                 on_scroll_state_change={Callback::from(move |scrolled_up| {
                                                     show_scroll_to_bottom_clone.set(scrolled_up);
                                                 })}
+                                                on_search_match_count={Some(Callback::from(move |matches| {
+                                                    search_matches_clone.set(matches);
+                                                }))}
                                                 read_only=false
                                             />
                                             {if *show_scroll_to_bottom {
@@ -1790,17 +3015,34 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                     }
                                 </div>
                                 <div class="git-actions">
+                                    <button class="btn-git-action" title="Stage All" onclick={on_stage_all.reform(|_| "git_stage_all")}>
+                                        { "全部暂存" }
+                                    </button>
+                                    <button class="btn-git-action" title="Unstage All" onclick={on_stage_all.reform(|_| "git_unstage_all")}>
+                                        { "全部取消暂存" }
+                                    </button>
                                     <button class="btn-git-action" onclick={on_commit.reform(|_| false)}>
                                         { "提交" }
                                     </button>
                                     <button class="btn-git-action amend" onclick={on_commit.reform(|_| true)}>
                                         { "修订" }
                                     </button>
+                                    <button class="btn-git-action sync" title="Push" onclick={on_git_sync.reform(|_| "git_push")}>
+                                        { "推送" }
+                                    </button>
+                                    <button class="btn-git-action sync" title="Pull" onclick={on_git_sync.reform(|_| "git_pull")}>
+                                        { "拉取" }
+                                    </button>
                                     <button class="btn-git-close" onclick={Callback::from(move |_| show_git_panel.set(false))}>
                                         { "✕" }
                                     </button>
                                 </div>
                             </div>
+                            if let Some((success, ref text)) = *git_sync_result {
+                                <div class={classes!("git-sync-result", (!success).then_some("error"))}>
+                                    { text.clone() }
+                                </div>
+                            }
                             <div class="git-content">
                                 <div class="git-file-list">
                                     if let Some(ref status) = *git_status {
@@ -1816,7 +3058,7 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                             onclick={on_select_file.reform(move |_| path.clone())}
                                                         >
                                                             <span class="file-status staged">{ "●" }</span>
-                                                            <span class="file-name">{ &f.path }</span>
+                                                            { render_file_name(f) }
                                                             <span class="file-stats">
                                                                 if f.additions > 0 {
                                                                     <span class="additions">{ format!("+{}", f.additions) }</span>
@@ -1842,7 +3084,7 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                                             onclick={on_select_file.reform(move |_| path.clone())}
                                                         >
                                                             <span class="file-status modified">{ "M" }</span>
-                                                            <span class="file-name">{ &f.path }</span>
+                                                            { render_file_name(f) }
                                                             <span class="file-stats">
                                                                 if f.additions > 0 {
                                                                     <span class="additions">{ format!("+{}", f.additions) }</span>
@@ -1909,26 +3151,33 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                             <button class="vk-btn" onclick={on_key_down}>{ "↓" }</button>
                             <button class="vk-btn" onclick={on_key_left}>{ "←" }</button>
                             <button class="vk-btn" onclick={on_key_right}>{ "→" }</button>
+                            <button class="vk-btn vk-paste" onclick={on_key_paste}>{ "Paste" }</button>
                             <button class="vk-btn vk-enter" onclick={on_key_enter}>{ "Enter" }</button>
                         </div>
                     }
+                    if let Some(ref toast) = *paste_toast {
+                        <div class="paste-toast">{ toast.clone() }</div>
+                    }
                 </section>
             </main>
 
             // Context Menu
-            if let Some((x, y, session_id, tag)) = (*context_menu).clone() {
+            if let Some((x, y, session_id, _tag)) = (*context_menu).clone() {
                 <div
                     class="context-menu"
                     style={format!("left: {}px; top: {}px;", x, y)}
                     onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
                 >
-                    <div class="context-menu-item context-menu-delete"
-                        onclick={Callback::from(move |_| {
-                            delete_confirm_clone.set(Some((session_id.clone(), tag.clone())));
-                            context_menu.set(None);
-                        })}
+                    <div class="context-menu-item"
+                        onclick={{
+                            let on_archive = on_archive_session.clone();
+                            Callback::from(move |_| {
+                                on_archive.emit(session_id.clone());
+                                context_menu.set(None);
+                            })
+                        }}
                     >
-                        { "🗑 删除会话" }
+                        { "🗃 归档会话" }
                     </div>
                 </div>
             }
@@ -1937,8 +3186,8 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
             if let Some((ref session_id, ref tag)) = *delete_confirm {
                 <div class="modal-overlay" onclick={Callback::from(move |_| delete_confirm_for_overlay.set(None))}>
                     <div class="modal" onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}>
-                        <h3>{ "确认删除" }</h3>
-                        <p>{ format!("确定要删除会话 '{}' 吗？", tag) }</p>
+                        <h3>{ "确认永久删除" }</h3>
+                        <p>{ format!("确定要永久删除会话 '{}' 吗？此操作无法撤销。", tag) }</p>
                         <div class="modal-actions">
                             <button class="btn-cancel" onclick={Callback::from(move |_| delete_confirm_for_cancel.set(None))}>
                                 { "取消" }
@@ -1950,7 +3199,41 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                     Callback::from(move |_| on_delete.emit(sid.clone()))
                                 }}
                             >
-                                { "删除" }
+                                { "永久删除" }
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            }
+
+            // Leave Session Confirmation Modal
+            if let Some(ref route) = *pending_route {
+                <div class="modal-overlay" onclick={{
+                    let pending_route = pending_route.clone();
+                    Callback::from(move |_| pending_route.set(None))
+                }}>
+                    <div class="modal" onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}>
+                        <h3>{ "离开会话" }</h3>
+                        <p>{ LEAVE_ACTIVE_SESSION_WARNING }</p>
+                        <div class="modal-actions">
+                            <button class="btn-cancel" onclick={{
+                                let pending_route = pending_route.clone();
+                                Callback::from(move |_| pending_route.set(None))
+                            }}>
+                                { "取消" }
+                            </button>
+                            <button class="btn-danger"
+                                onclick={{
+                                    let pending_route = pending_route.clone();
+                                    let navigator = navigator.clone();
+                                    let route = route.clone();
+                                    Callback::from(move |_| {
+                                        pending_route.set(None);
+                                        navigator.push(&route);
+                                    })
+                                }}
+                            >
+                                { "离开" }
                             </button>
                         </div>
                     </div>
@@ -1976,7 +3259,9 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                                     <option value="">{ "-- 选择机器 --" }</option>
                                     { for machines.borrow().iter().map(|m| {
                                         html! {
-                                            <option value={m.id.clone()}>{ &m.name }</option>
+                                            <option value={m.id.clone()} title={m.capability_names()}>
+                                                { format!("{} {}", m.name, m.capability_icons()) }
+                                            </option>
                                         }
                                     })}
                                 </select>
@@ -2026,6 +3311,69 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                 </div>
             }
 
+            // Transfer Session Modal
+            if let Some(ref session_id) = *transfer_session {
+                {{
+                    let source_machine = sessions.borrow().iter()
+                        .find(|s| &s.id == session_id)
+                        .map(|s| s.machine_id.clone())
+                        .unwrap_or_default();
+                    let targets: Vec<MachineInfo> = machines.borrow().iter()
+                        .filter(|m| m.id != source_machine)
+                        .cloned()
+                        .collect();
+                    let close = {
+                        let transfer_session = transfer_session.clone();
+                        Callback::from(move |_: MouseEvent| transfer_session.set(None))
+                    };
+                    let on_machine_change = {
+                        let transfer_machine = transfer_machine.clone();
+                        Callback::from(move |e: Event| {
+                            let input: web_sys::HtmlSelectElement = e.target_unchecked_into();
+                            transfer_machine.set(input.value());
+                        })
+                    };
+                    html! {
+                        <div class="modal-overlay" onclick={close.clone()}>
+                            <div class="modal transfer-session-modal" onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}>
+                                <h3>{ "迁移会话" }</h3>
+                                <form onsubmit={on_transfer_session}>
+                                    <div class="form-group">
+                                        <label>{ "目标机器" }</label>
+                                        <select
+                                            class="machine-select"
+                                            value={(*transfer_machine).clone()}
+                                            onchange={on_machine_change}
+                                        >
+                                            <option value="">{ "-- 选择机器 --" }</option>
+                                            { for targets.iter().map(|m| html! {
+                                                <option value={m.id.clone()} title={m.capability_names()}>
+                                                    { format!("{} {}", m.name, m.capability_icons()) }
+                                                </option>
+                                            })}
+                                        </select>
+                                        if targets.is_empty() {
+                                            <small class="form-hint-inline">{ "没有其他在线机器。请确保在目标机器上运行了 happy daemon。" }</small>
+                                        }
+                                    </div>
+                                    <p class="form-hint">
+                                        { "将在目标机器的相同目录下启动新会话，并带上当前的终端历史。原会话会继续运行，可稍后手动结束。" }
+                                    </p>
+                                    <div class="modal-actions">
+                                        <button type="button" class="btn-cancel" onclick={close}>
+                                            { "取消" }
+                                        </button>
+                                        <button type="submit" class="btn-primary" disabled={transfer_machine.is_empty()}>
+                                            { "迁移" }
+                                        </button>
+                                    </div>
+                                </form>
+                            </div>
+                        </div>
+                    }
+                }}
+            }
+
             // Commit Modal
             if *show_commit_modal {
                 <div class="modal-overlay" onclick={Callback::from(move |_| show_commit_modal_for_modal.set(false))}>
@@ -2065,6 +3413,15 @@ pub fn terminal_page(_props: &TerminalPageProps) -> Html {
                     </div>
                 </div>
             }
+            if let Some((ref name, ref parsed)) = *recording {
+                <div class="modal-overlay">
+                    <RecordingPlayer
+                        name={name.clone()}
+                        recording={parsed.clone()}
+                        on_close={on_recording_close}
+                    />
+                </div>
+            }
             <LogViewer
                 visible={*log_viewer_open}
                 on_close={on_log_viewer_close.clone()}
@@ -0,0 +1,72 @@
+//! asciinema v2 (`.cast`) recordings
+//!
+//! A header object on the first line, then one `[time, type, data]` array per
+//! line. Only output (`"o"`) events are kept for replay.
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+    /// Longest pause kept on replay, in seconds
+    #[serde(default)]
+    idle_time_limit: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputEvent {
+    /// Seconds since the recording started
+    pub time: f64,
+    pub data: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub events: Vec<OutputEvent>,
+}
+
+impl Recording {
+    /// Length in seconds, up to the last output event
+    pub fn duration(&self) -> f64 {
+        self.events.last().map(|e| e.time).unwrap_or(0.0)
+    }
+}
+
+/// Parse an asciicast v2 file, compressing pauses longer than its `idle_time_limit`
+pub fn parse(text: &str) -> Result<Recording, String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+
+    let header: Header = lines
+        .next()
+        .ok_or("Recording is empty")
+        .and_then(|l| serde_json::from_str(l).map_err(|_| "Missing asciicast header"))?;
+    if header.version != 2 {
+        return Err(format!(
+            "Unsupported asciicast version {}; only v2 can be replayed",
+            header.version
+        ));
+    }
+
+    let mut events = Vec::new();
+    // Recorded time minus the pauses cut by `idle_time_limit`
+    let mut skipped = 0.0;
+    let mut last_time = 0.0;
+    for (index, line) in lines.enumerate() {
+        let (time, kind, data): (f64, String, String) = serde_json::from_str(line)
+            .map_err(|e| format!("Invalid event on line {}: {}", index + 2, e))?;
+
+        if let Some(limit) = header.idle_time_limit {
+            skipped += (time - last_time - limit).max(0.0);
+        }
+        last_time = time;
+
+        if kind == "o" {
+            events.push(OutputEvent {
+                time: time - skipped,
+                data,
+            });
+        }
+    }
+
+    Ok(Recording { events })
+}
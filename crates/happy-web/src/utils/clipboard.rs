@@ -0,0 +1,56 @@
+//! Clipboard access for pasting into the terminal
+//!
+//! xterm.js never sees a `paste` event inside iOS WKWebViews, so the virtual
+//! keyboard's Paste button reads the clipboard itself from the button's click.
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{HtmlDocument, HtmlTextAreaElement};
+
+/// Read the clipboard as text, or `None` when the browser refuses.
+///
+/// Must be called from a user gesture. Uses `navigator.clipboard.readText()`
+/// and falls back to `document.execCommand("paste")` into a hidden textarea
+/// where the Clipboard API is missing (plain HTTP, older WebKit).
+pub async fn read_text() -> Option<String> {
+    let window = web_sys::window()?;
+    let clipboard = window.navigator().clipboard();
+    if clipboard.is_undefined() {
+        return read_text_via_exec_command();
+    }
+
+    match JsFuture::from(clipboard.read_text()).await {
+        Ok(text) => text.as_string(),
+        Err(e) => {
+            log::warn!("Clipboard readText failed: {:?}", e);
+            None
+        }
+    }
+}
+
+fn read_text_via_exec_command() -> Option<String> {
+    let document = web_sys::window()?.document()?;
+    let textarea: HtmlTextAreaElement =
+        document.create_element("textarea").ok()?.dyn_into().ok()?;
+    let _ = textarea.set_attribute(
+        "style",
+        "position: fixed; top: 0; left: 0; width: 1px; height: 1px; opacity: 0;",
+    );
+    let body = document.body()?;
+    let _ = body.append_child(&textarea);
+    let _ = textarea.focus();
+
+    let pasted = document
+        .dyn_ref::<HtmlDocument>()
+        .and_then(|doc| doc.exec_command("paste").ok())
+        .unwrap_or(false);
+    let text = textarea.value();
+    textarea.remove();
+
+    if pasted && !text.is_empty() {
+        Some(text)
+    } else {
+        log::warn!("execCommand(\"paste\") is unavailable");
+        None
+    }
+}
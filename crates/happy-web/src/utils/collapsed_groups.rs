@@ -0,0 +1,55 @@
+//! Sidebar groups the user has collapsed
+//!
+//! Group keys are kept in `localStorage["happy_collapsed_groups"]` as a JSON
+//! array, so the sidebar comes back the way it was left after a reload.
+
+use std::collections::HashSet;
+
+const STORAGE_KEY: &str = "happy_collapsed_groups";
+
+/// Key of a machine group
+pub fn machine_key(machine: &str) -> String {
+    format!("machine:{}", machine)
+}
+
+/// Key of a folder group; folder names repeat across machines
+pub fn folder_key(machine: &str, folder: &str) -> String {
+    format!("folder:{}/{}", machine, folder)
+}
+
+/// The stored set; empty when missing or unreadable
+pub fn load() -> HashSet<String> {
+    web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(groups: &HashSet<String>) {
+    let mut keys: Vec<&String> = groups.iter().collect();
+    keys.sort();
+    let Ok(json) = serde_json::to_string(&keys) else {
+        return;
+    };
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        let _ = storage.set_item(STORAGE_KEY, &json);
+    }
+}
+
+/// Collapse `key` if expanded, expand it otherwise
+pub fn toggle(groups: &mut HashSet<String>, key: String) {
+    if !groups.remove(&key) {
+        groups.insert(key);
+    }
+    save(groups);
+}
+
+/// Expand the groups holding a session in `folder` on `machine`, if collapsed
+pub fn expand(groups: &mut HashSet<String>, machine: &str, folder: &str) {
+    let machine_expanded = groups.remove(&machine_key(machine));
+    let folder_expanded = groups.remove(&folder_key(machine, folder));
+    if machine_expanded || folder_expanded {
+        save(groups);
+    }
+}
@@ -0,0 +1,107 @@
+//! Search-term highlighting for filtered lists
+
+use yew::prelude::*;
+
+/// Render `text` with every case-insensitive occurrence of `query` wrapped in
+/// `<mark class="search-highlight">`.
+///
+/// Both halves are emitted as text nodes, so neither the text nor the query
+/// can inject markup.
+pub fn highlight_match(text: &str, query: &str) -> Html {
+    let query = query.trim();
+    if query.is_empty() {
+        return html! { { text.to_string() } };
+    }
+
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some((start, end)) = find_ignore_case(rest, query) {
+        if start > 0 {
+            parts.push(html! { { rest[..start].to_string() } });
+        }
+        parts.push(html! {
+            <mark class="search-highlight">{ rest[start..end].to_string() }</mark>
+        });
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        parts.push(html! { { rest.to_string() } });
+    }
+
+    html! { <>{ for parts }</> }
+}
+
+/// Whether `text` contains `query`, ignoring case
+pub fn matches_query(text: &str, query: &str) -> bool {
+    let query = query.trim();
+    query.is_empty() || find_ignore_case(text, query).is_some()
+}
+
+/// Byte range of the first case-insensitive occurrence of `needle`.
+///
+/// Compares char by char rather than lowercasing the whole string, because
+/// lowercasing can change byte lengths and the range must index `haystack`.
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<(usize, usize)> {
+    let needle: Vec<char> = needle.chars().flat_map(char::to_lowercase).collect();
+    if needle.is_empty() {
+        return None;
+    }
+
+    for (start, _) in haystack.char_indices() {
+        let mut remaining = needle.iter();
+        let mut end = start;
+        for (offset, c) in haystack[start..].char_indices() {
+            if remaining.as_slice().is_empty() {
+                break;
+            }
+            let lower: Vec<char> = c.to_lowercase().collect();
+            if !remaining.as_slice().starts_with(&lower) {
+                break;
+            }
+            remaining.nth(lower.len() - 1);
+            end = start + offset + c.len_utf8();
+        }
+        if remaining.as_slice().is_empty() {
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(text: &str) -> Html {
+        html! { <mark class="search-highlight">{ text.to_string() }</mark> }
+    }
+
+    fn plain(text: &str) -> Html {
+        html! { { text.to_string() } }
+    }
+
+    #[test]
+    fn test_empty_query_renders_plain_text() {
+        assert_eq!(highlight_match("main", ""), plain("main"));
+        assert_eq!(highlight_match("main", "   "), plain("main"));
+    }
+
+    #[test]
+    fn test_highlights_every_match() {
+        let expected = html! {
+            <>{ for [mark("ab"), plain(" x "), mark("ab"), mark("ab")] }</>
+        };
+        assert_eq!(highlight_match("ab x abab", "ab"), expected);
+    }
+
+    #[test]
+    fn test_match_ignores_case_but_keeps_original_text() {
+        let expected = html! {
+            <>{ for [plain("feature/"), mark("Login"), plain("-"), mark("LOGIN")] }</>
+        };
+        assert_eq!(highlight_match("feature/Login-LOGIN", "login"), expected);
+        assert_ne!(highlight_match("Login", "login"), plain("Login"));
+        // Lowercasing İ yields two chars, so the range must still index the original
+        assert_eq!(find_ignore_case("İx", "X"), Some((2, 3)));
+    }
+}
@@ -2,7 +2,8 @@
 //!
 //! Captures all console logs and stores them for in-app viewing.
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
@@ -102,9 +103,43 @@ impl LogStore {
     }
 }
 
+type LogListener = Rc<dyn Fn()>;
+
 thread_local! {
     static LOG_STORE: RefCell<LogStore> = RefCell::new(LogStore::new(1000));
     static ORIGINAL_CONSOLE: RefCell<Option<js_sys::Object>> = RefCell::new(None);
+    static LOG_LISTENERS: RefCell<Vec<(u32, LogListener)>> = RefCell::new(Vec::new());
+    static NEXT_LISTENER_ID: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Keeps a log listener registered until dropped
+pub struct LogSubscription(u32);
+
+impl Drop for LogSubscription {
+    fn drop(&mut self) {
+        let id = self.0;
+        LOG_LISTENERS.with(|listeners| listeners.borrow_mut().retain(|(i, _)| *i != id));
+    }
+}
+
+/// Call `listener` whenever an entry is added or the logs are cleared
+pub fn subscribe(listener: impl Fn() + 'static) -> LogSubscription {
+    let id = NEXT_LISTENER_ID.with(|next| {
+        let id = next.get();
+        next.set(id.wrapping_add(1));
+        id
+    });
+    LOG_LISTENERS.with(|listeners| listeners.borrow_mut().push((id, Rc::new(listener))));
+    LogSubscription(id)
+}
+
+fn notify_listeners() {
+    // Clone out first so a listener may log or unsubscribe without a double borrow
+    let listeners: Vec<_> =
+        LOG_LISTENERS.with(|listeners| listeners.borrow().iter().map(|(_, l)| l.clone()).collect());
+    for listener in listeners {
+        listener();
+    }
 }
 
 /// Add a log entry
@@ -118,6 +153,7 @@ pub fn add_log(level: LogLevel, message: impl Into<String>) {
     LOG_STORE.with(|store| {
         store.borrow_mut().push(entry);
     });
+    notify_listeners();
 }
 
 /// Get all log entries
@@ -140,6 +176,7 @@ pub fn get_logs_filtered(min_level: LogLevel) -> Vec<LogEntry> {
 /// Clear all logs
 pub fn clear_logs() {
     LOG_STORE.with(|store| store.borrow_mut().clear());
+    notify_listeners();
 }
 
 /// Copy logs to clipboard using JavaScript interop
@@ -1 +1,7 @@
+pub mod asciicast;
+pub mod clipboard;
+pub mod collapsed_groups;
+pub mod highlight;
 pub mod logger;
+pub mod offline_cache;
+pub mod sound;
@@ -0,0 +1,155 @@
+//! Last-known sessions and terminal output kept in IndexedDB
+//!
+//! The session list is stored as JSON under `"sessions"` and each terminal
+//! buffer as plain text under `"buffer:<session_id>"`. The terminal page shows this
+//! snapshot until the server answers, so sessions stay readable across reloads
+//! and brief network drops.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use gloo_timers::callback::Timeout;
+use indexed_db_futures::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+
+const DB_NAME: &str = "happy_offline";
+const STORE: &str = "cache";
+const SESSIONS_KEY: &str = "sessions";
+
+/// Terminal output arrives in bursts, so buffer writes are batched
+const BUFFER_FLUSH_MS: u32 = 1_000;
+
+thread_local! {
+    /// Sessions whose buffer changed since the last flush
+    static PENDING_BUFFERS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+    static FLUSH_TIMER: RefCell<Option<Timeout>> = const { RefCell::new(None) };
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredSessions<S> {
+    /// `Date.now()` when the server last sent the list
+    updated_at: f64,
+    sessions: S,
+}
+
+/// What the page showed when it was last connected
+pub struct Snapshot<T> {
+    pub updated_at: f64,
+    pub sessions: Vec<T>,
+    pub buffers: HashMap<String, String>,
+}
+
+/// The cached snapshot; `buffer_key` names the session each buffer belongs to
+pub async fn load<T: DeserializeOwned>(
+    buffer_key: impl Fn(&T) -> String,
+) -> Option<Snapshot<T>> {
+    let result = async {
+        let db = open().await?;
+        let stored: StoredSessions<Vec<T>> = match read(&db, SESSIONS_KEY).await? {
+            Some(json) => serde_json::from_str(&json).map_err(|e| e.to_string())?,
+            None => return Ok(None),
+        };
+        let mut buffers = HashMap::new();
+        for session in &stored.sessions {
+            let id = buffer_key(session);
+            if let Some(buffer) = read(&db, &format!("buffer:{}", id)).await? {
+                buffers.insert(id, buffer);
+            }
+        }
+        Ok::<_, JsValue>(Some(Snapshot {
+            updated_at: stored.updated_at,
+            sessions: stored.sessions,
+            buffers,
+        }))
+    }
+    .await;
+
+    result.unwrap_or_else(|e| {
+        log::warn!("Failed to read offline cache: {:?}", e);
+        None
+    })
+}
+
+/// Replace the cached session list
+pub fn save_sessions<T: Serialize>(sessions: &[T]) {
+    let stored = StoredSessions {
+        updated_at: js_sys::Date::now(),
+        sessions,
+    };
+    match serde_json::to_string(&stored) {
+        Ok(json) => write(vec![(SESSIONS_KEY.to_string(), json)]),
+        Err(e) => log::warn!("Failed to serialize sessions for offline cache: {}", e),
+    }
+}
+
+/// Store `session_id`'s buffer with the next batch of writes
+pub fn save_buffer_later(buffers: Rc<RefCell<HashMap<String, String>>>, session_id: &str) {
+    PENDING_BUFFERS.with(|pending| pending.borrow_mut().insert(session_id.to_string()));
+    FLUSH_TIMER.with(|timer| {
+        let mut timer = timer.borrow_mut();
+        if timer.is_none() {
+            *timer = Some(Timeout::new(BUFFER_FLUSH_MS, move || {
+                FLUSH_TIMER.with(|timer| timer.borrow_mut().take());
+                flush_buffers(&buffers);
+            }));
+        }
+    });
+}
+
+fn flush_buffers(buffers: &RefCell<HashMap<String, String>>) {
+    let ids = PENDING_BUFFERS.with(|pending| std::mem::take(&mut *pending.borrow_mut()));
+    let Ok(buffers) = buffers.try_borrow() else {
+        // Busy; keep the sessions for the next flush
+        PENDING_BUFFERS.with(|pending| pending.borrow_mut().extend(ids));
+        return;
+    };
+    let entries = ids
+        .into_iter()
+        .filter_map(|id| Some((format!("buffer:{}", id), buffers.get(&id)?.clone())))
+        .collect();
+    write(entries);
+}
+
+async fn open() -> Result<IdbDatabase, JsValue> {
+    let mut request = IdbDatabase::open_u32(DB_NAME, 1)?;
+    request.set_on_upgrade_needed(Some(|event: &IdbVersionChangeEvent| -> Result<(), JsValue> {
+        if !event.db().object_store_names().any(|name| name == STORE) {
+            event.db().create_object_store(STORE)?;
+        }
+        Ok(())
+    }));
+    Ok(request.await?)
+}
+
+/// A transaction of its own, since transactions commit as soon as they go idle
+async fn read(db: &IdbDatabase, key: &str) -> Result<Option<String>, JsValue> {
+    let tx = db.transaction_on_one(STORE)?;
+    let store = tx.object_store(STORE)?;
+    let value = store.get_owned(key)?.await?;
+    Ok(value.and_then(|value| value.as_string()))
+}
+
+fn write(entries: Vec<(String, String)>) {
+    if entries.is_empty() {
+        return;
+    }
+    wasm_bindgen_futures::spawn_local(async move {
+        let result = async {
+            let db = open().await?;
+            let tx = db.transaction_on_one_with_mode(STORE, IdbTransactionMode::Readwrite)?;
+            let store = tx.object_store(STORE)?;
+            for (key, text) in &entries {
+                store.put_key_val_owned(key.as_str(), &JsValue::from_str(text))?;
+            }
+            tx.await.into_result()?;
+            Ok::<_, JsValue>(())
+        }
+        .await;
+        if let Err(e) = result {
+            log::warn!("Failed to write offline cache: {:?}", e);
+        }
+    });
+}
@@ -0,0 +1,125 @@
+//! Opt-in chimes for session status changes
+//!
+//! Tones are synthesized with the Web Audio API so no audio assets are served.
+//! Sounds stay off unless `localStorage["happy_sound"]` is `"true"`, and the
+//! `prefers-reduced-motion` media query silences them regardless.
+
+use std::cell::RefCell;
+
+use web_sys::{AudioContext, AudioContextState, OscillatorType};
+
+const STORAGE_KEY: &str = "happy_sound";
+
+thread_local! {
+    /// Browsers cap the number of live contexts, so one is shared
+    static CONTEXT: RefCell<Option<AudioContext>> = const { RefCell::new(None) };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Chime {
+    /// Two rising notes when a session waits for confirmation
+    Attention,
+    /// One soft, short note when a session ends
+    Terminated,
+}
+
+/// The stored `notification_sound` preference
+pub fn preference() -> bool {
+    web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten())
+        .is_some_and(|value| value == "true")
+}
+
+pub fn set_preference(enabled: bool) {
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        let _ = storage.set_item(STORAGE_KEY, if enabled { "true" } else { "false" });
+    }
+}
+
+/// Whether the user asked the system to cut down on motion and effects
+pub fn prefers_reduced_motion() -> bool {
+    web_sys::window()
+        .and_then(|w| w.match_media("(prefers-reduced-motion: reduce)").ok().flatten())
+        .is_some_and(|query| query.matches())
+}
+
+/// The chime for a status change, if it deserves one
+pub fn chime_for_transition(previous: &str, next: &str) -> Option<Chime> {
+    let needs_attention = |status: &str| matches!(status, "needs_attention" | "waiting_for_confirm");
+    if previous == next {
+        None
+    } else if needs_attention(next) {
+        Some(Chime::Attention)
+    } else if matches!(next, "terminated" | "exited") {
+        Some(Chime::Terminated)
+    } else {
+        None
+    }
+}
+
+/// Play `chime` if sounds are enabled
+pub fn notify(chime: Chime) {
+    if preference() && !prefers_reduced_motion() {
+        play(chime);
+    }
+}
+
+/// Play `chime` unconditionally, e.g. as a preview from the settings toggle
+pub fn play(chime: Chime) {
+    CONTEXT.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        if cell.is_none() {
+            *cell = AudioContext::new().ok();
+        }
+        let Some(ctx) = cell.as_ref() else {
+            log::warn!("Web Audio is unavailable");
+            return;
+        };
+        // Contexts start suspended until the page has seen a user gesture
+        if ctx.state() == AudioContextState::Suspended {
+            let _ = ctx.resume();
+        }
+
+        let notes: &[(f32, f64, f64, f32)] = match chime {
+            // (frequency Hz, offset s, duration s, peak gain)
+            Chime::Attention => &[(880.0, 0.0, 0.18, 0.2), (1318.5, 0.15, 0.3, 0.2)],
+            Chime::Terminated => &[(523.25, 0.0, 0.45, 0.08)],
+        };
+        for &(frequency, offset, duration, peak) in notes {
+            if let Err(e) = play_note(ctx, frequency, offset, duration, peak) {
+                log::warn!("Failed to play notification sound: {:?}", e);
+                return;
+            }
+        }
+    });
+}
+
+/// A sine note with a quick attack and exponential decay
+fn play_note(
+    ctx: &AudioContext,
+    frequency: f32,
+    offset: f64,
+    duration: f64,
+    peak: f32,
+) -> Result<(), wasm_bindgen::JsValue> {
+    let start = ctx.current_time() + offset;
+    let end = start + duration;
+
+    let oscillator = ctx.create_oscillator()?;
+    oscillator.set_type(OscillatorType::Sine);
+    oscillator.frequency().set_value(frequency);
+
+    let gain = ctx.create_gain()?;
+    let envelope = gain.gain();
+    envelope.set_value_at_time(0.0, start)?;
+    envelope.linear_ramp_to_value_at_time(peak, start + 0.01)?;
+    // Exponential ramps cannot reach zero
+    envelope.exponential_ramp_to_value_at_time(0.0001, end)?;
+
+    oscillator.connect_with_audio_node(&gain)?;
+    gain.connect_with_audio_node(&ctx.destination())?;
+    oscillator.start_with_when(start)?;
+    oscillator.stop_with_when(end)?;
+    Ok(())
+}
@@ -122,13 +122,23 @@ if [ "$DEPLOY_FRONTEND" = true ]; then
     wasm-bindgen --out-dir pkg --target web --no-typescript \
         ../../target/wasm32-unknown-unknown/release/happy-web.wasm
 
+    # xterm.js and its addons are vendored from npm, pinned to the releases
+    # that go with @xterm/xterm 5.5.0; fetch the search addon if it is missing
+    XTERM_SEARCH_ADDON=assets/xterm/addon-search.min.js
+    if [ ! -f "$XTERM_SEARCH_ADDON" ]; then
+        echo ">>> Fetching @xterm/addon-search@0.15.0..."
+        curl -fsSL -o "$XTERM_SEARCH_ADDON" \
+            https://cdn.jsdelivr.net/npm/@xterm/addon-search@0.15.0/lib/addon-search.min.js
+    fi
+
     # Copy static assets
     mkdir -p dist/pkg
     cp pkg/* dist/pkg/
     cp index.html dist/
     cp style.css dist/pkg/ 2>/dev/null || true
     mkdir -p dist/assets
-    cp -r assets/* dist/assets/ 2>/dev/null || true
+    # index.html loads every file under assets/xterm, so a failed copy fails the build
+    cp -r assets/* dist/assets/
 
     cd ../..
     echo -e "${GREEN}>>> Frontend build complete${NC}"
@@ -0,0 +1,12 @@
+#!/bin/sh
+# Start Postgres through the image's own entrypoint, which initializes the
+# data directory on first run, then run happy-server once it accepts TCP.
+set -e
+
+docker-entrypoint.sh postgres &
+
+until pg_isready -q -h 127.0.0.1 -U "$POSTGRES_USER" -d "$POSTGRES_DB"; do
+    sleep 1
+done
+
+exec happy-server